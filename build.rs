@@ -0,0 +1,23 @@
+fn main() {
+    #[cfg(feature = "ffi")]
+    generate_header();
+}
+
+/// Writes C header of functions of `ffi` module into build output folder.
+///
+/// Committed `include/anvil_region.h` is left untouched, build never writes into source tree.
+#[cfg(feature = "ffi")]
+fn generate_header() {
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR is not set");
+    let config = cbindgen::Config::from_file("cbindgen.toml").expect("Invalid cbindgen.toml");
+
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src("src/ffi.rs")
+        .generate()
+        .expect("Unable to generate C header")
+        .write_to_file(std::path::Path::new(&out_dir).join("anvil_region.h"));
+}
@@ -0,0 +1,121 @@
+#ifndef ANVIL_REGION_H
+#define ANVIL_REGION_H
+
+/* Generated by cbindgen from src/ffi.rs, don't edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Result of C interface function.
+ */
+typedef enum AnvilStatus {
+  ANVIL_STATUS_OK = 0,
+  /**
+   * Pointer argument is null or string is not valid UTF-8.
+   */
+  ANVIL_STATUS_INVALID_ARGUMENT = 1,
+  /**
+   * Region file of chunk doesn't exist.
+   */
+  ANVIL_STATUS_REGION_NOT_FOUND = 2,
+  /**
+   * Chunk is not present in region file.
+   */
+  ANVIL_STATUS_CHUNK_NOT_FOUND = 3,
+  /**
+   * Chunk can't be read or decoded.
+   */
+  ANVIL_STATUS_LOAD_ERROR = 4,
+  /**
+   * Bytes passed to save are not NBT compound.
+   */
+  ANVIL_STATUS_INVALID_NBT = 5,
+  /**
+   * Chunk can't be written.
+   */
+  ANVIL_STATUS_SAVE_ERROR = 6,
+} AnvilStatus;
+
+/**
+ * Opaque provider of chunks of region folder.
+ */
+typedef struct AnvilProvider AnvilProvider;
+
+/**
+ * Creates provider of region folder, returns null if path is null or not UTF-8.
+ *
+ * # Safety
+ *
+ * `folder_path` must be null or point to null terminated string.
+ */
+struct AnvilProvider *anvil_provider_open(const char *folder_path);
+
+/**
+ * Releases provider, null is ignored.
+ *
+ * # Safety
+ *
+ * `provider` must be null or returned by `anvil_provider_open` and not yet released.
+ */
+void anvil_provider_free(struct AnvilProvider *provider);
+
+/**
+ * Loads chunk as uncompressed NBT bytes, which are released by `anvil_bytes_free`.
+ *
+ * # Safety
+ *
+ * `provider` must be returned by `anvil_provider_open`, `data` and `length` must
+ * point to writable memory.
+ */
+enum AnvilStatus anvil_load_chunk(const struct AnvilProvider *provider,
+                                  int32_t chunk_x,
+                                  int32_t chunk_z,
+                                  uint8_t **data,
+                                  size_t *length);
+
+/**
+ * Saves chunk from uncompressed NBT bytes of root compound.
+ *
+ * # Safety
+ *
+ * `provider` must be returned by `anvil_provider_open`, `data` must point to
+ * `length` readable bytes.
+ */
+enum AnvilStatus anvil_save_chunk(const struct AnvilProvider *provider,
+                                  int32_t chunk_x,
+                                  int32_t chunk_z,
+                                  const uint8_t *data,
+                                  size_t length);
+
+/**
+ * Deletes chunk, chunk which is not present is reported as not found.
+ *
+ * # Safety
+ *
+ * `provider` must be returned by `anvil_provider_open`.
+ */
+enum AnvilStatus anvil_delete_chunk(const struct AnvilProvider *provider,
+                                    int32_t chunk_x,
+                                    int32_t chunk_z);
+
+/**
+ * Releases bytes returned by `anvil_load_chunk`, null is ignored.
+ *
+ * # Safety
+ *
+ * `data` and `length` must be returned by `anvil_load_chunk` and not yet released.
+ */
+void anvil_bytes_free(uint8_t *data, size_t length);
+
+/**
+ * Returns message of the last error of calling thread, null if there was none.
+ *
+ * Message is valid until the next error on the same thread.
+ */
+const char *anvil_last_error_message(void);
+
+#endif  /* ANVIL_REGION_H */
@@ -0,0 +1,616 @@
+//! Reader of Alpha and Infdev worlds which store every chunk in its own file.
+//!
+//! Chunks are gzip compressed NBT at `<x>/<z>/c.<chunk x>.<chunk z>.dat`, where folder
+//! names are chunk coordinates modulo 64 and every number is written in base 36
+//! like Java `Integer.toString(value, 36)` does. Blocks of the whole 128 blocks high
+//! column are stored in single array in XZY order.
+//!
+//! [`convert_chunk`] restructures chunk into sections of the first Anvil format, so
+//! converted chunks are loaded by 1.2 and can be brought further by [`upgrade`].
+//!
+//! [`upgrade`]: crate::upgrade
+//!
+//! # Example
+//!
+//! ```no_run
+//! use anvil_region::alpha::convert_world;
+//! use std::path::Path;
+//!
+//! let converted_chunks = convert_world(Path::new("saves/World1"), Path::new("saves/World1_anvil")).unwrap();
+//! println!("Converted {} chunks", converted_chunks);
+//! ```
+use crate::data::DataFileError;
+use crate::level::{LevelData, LEVEL_DAT_FILE};
+use crate::tag::get_tag;
+use crate::{AnvilChunkProvider, ChunkSaveError};
+use nbt::decode::{read_gzip_compound_tag, TagDecodeError};
+use nbt::{CompoundTag, CompoundTagError, Tag};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Height of Alpha world in blocks.
+pub const ALPHA_WORLD_HEIGHT: usize = 128;
+/// Value of `version` tag of `level.dat` which marks world as Anvil.
+pub const ANVIL_LEVEL_VERSION: i32 = 19133;
+
+/// Amount of blocks in chunk column.
+const COLUMN_BLOCKS: usize = 16 * 16 * ALPHA_WORLD_HEIGHT;
+/// Amount of blocks in section of converted chunk.
+const SECTION_BLOCKS: usize = 16 * 16 * 16;
+/// Folders of chunks are named by coordinates modulo this value.
+const FOLDER_COUNT: i32 = 64;
+
+/// Possible errors while reading or converting Alpha chunks.
+#[derive(Debug)]
+pub enum AlphaChunkError {
+    /// Chunk file at specified coordinates not found.
+    ChunkNotFound { chunk_x: i32, chunk_z: i32 },
+    /// I/O Error which happened while were reading chunk file.
+    ReadError { io_error: io::Error },
+    /// Error while decoding binary data to NBT tag.
+    TagDecodeError { tag_decode_error: TagDecodeError },
+    /// Chunk tag is missing or has unexpected type or length.
+    InvalidTag {
+        /// Tag name.
+        name: String,
+    },
+    /// Converted chunk can't be saved.
+    ChunkSaveError { chunk_save_error: ChunkSaveError },
+    /// Level file can't be read or written.
+    DataFileError { data_file_error: DataFileError },
+}
+
+impl From<io::Error> for AlphaChunkError {
+    fn from(io_error: io::Error) -> Self {
+        AlphaChunkError::ReadError { io_error }
+    }
+}
+
+impl From<TagDecodeError> for AlphaChunkError {
+    fn from(tag_decode_error: TagDecodeError) -> Self {
+        AlphaChunkError::TagDecodeError { tag_decode_error }
+    }
+}
+
+impl From<CompoundTagError<'_>> for AlphaChunkError {
+    fn from(compound_tag_error: CompoundTagError) -> Self {
+        let name = match compound_tag_error {
+            CompoundTagError::TagNotFound { name } => name,
+            CompoundTagError::TagWrongType { name, .. } => name,
+        };
+
+        AlphaChunkError::InvalidTag {
+            name: name.to_owned(),
+        }
+    }
+}
+
+impl From<ChunkSaveError> for AlphaChunkError {
+    fn from(chunk_save_error: ChunkSaveError) -> Self {
+        AlphaChunkError::ChunkSaveError { chunk_save_error }
+    }
+}
+
+impl From<DataFileError> for AlphaChunkError {
+    fn from(data_file_error: DataFileError) -> Self {
+        AlphaChunkError::DataFileError { data_file_error }
+    }
+}
+
+/// Provider of chunks of Alpha world folder.
+pub struct AlphaChunkProvider<'a> {
+    /// World folder which contains chunk folders.
+    folder_path: &'a Path,
+}
+
+impl<'a> AlphaChunkProvider<'a> {
+    pub fn new(folder: &'a str) -> Self {
+        AlphaChunkProvider::from_path(Path::new(folder))
+    }
+
+    fn from_path(folder_path: &'a Path) -> Self {
+        AlphaChunkProvider { folder_path }
+    }
+
+    /// Returns path of chunk file at the specified coordinates.
+    pub fn chunk_path(&self, chunk_x: i32, chunk_z: i32) -> PathBuf {
+        let file_name = format!("c.{}.{}.dat", to_base36(chunk_x), to_base36(chunk_z));
+
+        self.folder_path
+            .join(to_base36(chunk_x.rem_euclid(FOLDER_COUNT)))
+            .join(to_base36(chunk_z.rem_euclid(FOLDER_COUNT)))
+            .join(file_name)
+    }
+
+    /// Loads chunk in Alpha format from the specified coordinates.
+    pub fn load_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<CompoundTag, AlphaChunkError> {
+        let mut file = match File::open(self.chunk_path(chunk_x, chunk_z)) {
+            Ok(file) => file,
+            Err(io_error) if io_error.kind() == io::ErrorKind::NotFound => {
+                return Err(AlphaChunkError::ChunkNotFound { chunk_x, chunk_z })
+            }
+            Err(io_error) => return Err(io_error.into()),
+        };
+
+        Ok(read_gzip_compound_tag(&mut file)?)
+    }
+
+    /// Returns coordinates of chunk files in ascending order.
+    ///
+    /// Files which are not in folder of their coordinates are skipped, game never reads them.
+    pub fn chunk_positions(&self) -> Result<Vec<(i32, i32)>, io::Error> {
+        let mut chunk_positions = Vec::new();
+
+        if !self.folder_path.exists() {
+            return Ok(chunk_positions);
+        }
+
+        for x_entry in fs::read_dir(self.folder_path)? {
+            let x_path = x_entry?.path();
+
+            let folder_x = match folder_position(&x_path) {
+                Some(folder_x) => folder_x,
+                None => continue,
+            };
+
+            for z_entry in fs::read_dir(&x_path)? {
+                let z_path = z_entry?.path();
+
+                let folder_z = match folder_position(&z_path) {
+                    Some(folder_z) => folder_z,
+                    None => continue,
+                };
+
+                for entry in fs::read_dir(&z_path)? {
+                    let path = entry?.path();
+
+                    let (chunk_x, chunk_z) = match path
+                        .file_name()
+                        .and_then(|file_name| file_name.to_str())
+                        .and_then(chunk_position)
+                    {
+                        Some(chunk_position) => chunk_position,
+                        None => continue,
+                    };
+
+                    if chunk_x.rem_euclid(FOLDER_COUNT) == folder_x
+                        && chunk_z.rem_euclid(FOLDER_COUNT) == folder_z
+                    {
+                        chunk_positions.push((chunk_x, chunk_z));
+                    }
+                }
+            }
+        }
+
+        chunk_positions.sort_unstable();
+
+        Ok(chunk_positions)
+    }
+}
+
+/// Formats number in base 36 like Java `Integer.toString(value, 36)`.
+pub fn to_base36(value: i32) -> String {
+    const DIGITS: &[u8; 36] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+    let mut remaining = (value as i64).abs();
+    let mut digits = Vec::new();
+
+    loop {
+        digits.push(DIGITS[(remaining % 36) as usize]);
+        remaining /= 36;
+
+        if remaining == 0 {
+            break;
+        }
+    }
+
+    if value < 0 {
+        digits.push(b'-');
+    }
+
+    digits.iter().rev().map(|digit| *digit as char).collect()
+}
+
+/// Parses number written in base 36, `None` if it's not valid or doesn't fit into i32.
+pub fn from_base36(value: &str) -> Option<i32> {
+    // Plus sign is accepted by parser, but never written by game.
+    if value.starts_with('+') {
+        return None;
+    }
+
+    i32::from_str_radix(value, 36).ok()
+}
+
+fn folder_position(path: &Path) -> Option<i32> {
+    if !path.is_dir() {
+        return None;
+    }
+
+    let position = from_base36(path.file_name()?.to_str()?)?;
+
+    if (0..FOLDER_COUNT).contains(&position) {
+        Some(position)
+    } else {
+        None
+    }
+}
+
+fn chunk_position(file_name: &str) -> Option<(i32, i32)> {
+    let mut parts = file_name.split('.');
+
+    if parts.next() != Some("c") {
+        return None;
+    }
+
+    let chunk_x = from_base36(parts.next()?)?;
+    let chunk_z = from_base36(parts.next()?)?;
+
+    if parts.next() != Some("dat") || parts.next().is_some() {
+        return None;
+    }
+
+    Some((chunk_x, chunk_z))
+}
+
+/// Converts chunk from Alpha format into sections of the first Anvil format.
+///
+/// Sections which contain only air are omitted. Light is kept, chunk is marked as
+/// not lit when Alpha chunk has no light arrays.
+pub fn convert_chunk(alpha_chunk: &CompoundTag) -> Result<CompoundTag, AlphaChunkError> {
+    let alpha_level = alpha_chunk.get_compound_tag("Level")?;
+
+    let blocks = column_array(alpha_level, "Blocks", COLUMN_BLOCKS)?;
+    let data = column_array(alpha_level, "Data", COLUMN_BLOCKS / 2)?;
+    let block_light = optional_column_array(alpha_level, "BlockLight")?;
+    let sky_light = optional_column_array(alpha_level, "SkyLight")?;
+
+    let mut level = CompoundTag::new();
+    level.insert_i32("xPos", alpha_level.get_i32("xPos")?);
+    level.insert_i32("zPos", alpha_level.get_i32("zPos")?);
+    level.insert_i64("LastUpdate", alpha_level.get_i64("LastUpdate").unwrap_or(0));
+    level.insert_bool(
+        "TerrainPopulated",
+        alpha_level.get_bool("TerrainPopulated").unwrap_or(false),
+    );
+    level.insert_bool(
+        "LightPopulated",
+        block_light.is_some() && sky_light.is_some(),
+    );
+    level.insert_i32_vec("HeightMap", height_map(blocks));
+
+    let mut sections = Vec::new();
+
+    for section_y in 0..ALPHA_WORLD_HEIGHT / 16 {
+        let mut section_blocks = vec![0; SECTION_BLOCKS];
+        let mut section_data = vec![0; SECTION_BLOCKS / 2];
+        let mut section_block_light = vec![0; SECTION_BLOCKS / 2];
+        // Sky is fully lit when light wasn't stored.
+        let sky_light_fill = if sky_light.is_some() { 0 } else { -1 };
+        let mut section_sky_light = vec![sky_light_fill; SECTION_BLOCKS / 2];
+
+        for y in 0..16 {
+            for z in 0..16 {
+                for x in 0..16 {
+                    let column_index = alpha_index(x, section_y * 16 + y, z);
+                    let section_index = y * 256 + z * 16 + x;
+
+                    section_blocks[section_index] = blocks[column_index];
+                    set_nibble(&mut section_data, section_index, nibble(data, column_index));
+
+                    if let Some(block_light) = block_light {
+                        let value = nibble(block_light, column_index);
+                        set_nibble(&mut section_block_light, section_index, value);
+                    }
+
+                    if let Some(sky_light) = sky_light {
+                        let value = nibble(sky_light, column_index);
+                        set_nibble(&mut section_sky_light, section_index, value);
+                    }
+                }
+            }
+        }
+
+        if section_blocks.iter().all(|block| *block == 0) {
+            continue;
+        }
+
+        let mut section = CompoundTag::new();
+        section.insert_i8("Y", section_y as i8);
+        section.insert_i8_vec("Blocks", section_blocks);
+        section.insert_i8_vec("Data", section_data);
+        section.insert_i8_vec("BlockLight", section_block_light);
+        section.insert_i8_vec("SkyLight", section_sky_light);
+
+        sections.push(section);
+    }
+
+    level.insert_compound_tag_vec("Sections", sections);
+
+    for name in &["Entities", "TileEntities"] {
+        let tag = match get_tag(alpha_level, name) {
+            Some(Tag::List(tags)) => Tag::List(tags.clone()),
+            _ => Tag::List(Vec::new()),
+        };
+
+        level.insert(*name, tag);
+    }
+
+    if let Some(Tag::List(tile_ticks)) = get_tag(alpha_level, "TileTicks") {
+        level.insert("TileTicks", Tag::List(tile_ticks.clone()));
+    }
+
+    let mut chunk_compound_tag = CompoundTag::new();
+    chunk_compound_tag.insert_compound_tag("Level", level);
+
+    Ok(chunk_compound_tag)
+}
+
+/// Converts every chunk of Alpha provider and saves it into Anvil provider.
+///
+/// Returns amount of converted chunks.
+pub fn convert_provider(
+    alpha_chunk_provider: &AlphaChunkProvider,
+    anvil_chunk_provider: &AnvilChunkProvider,
+) -> Result<usize, AlphaChunkError> {
+    let mut converted_chunks = 0;
+
+    for (chunk_x, chunk_z) in alpha_chunk_provider.chunk_positions()? {
+        let alpha_chunk = alpha_chunk_provider.load_chunk(chunk_x, chunk_z)?;
+        let chunk_compound_tag = convert_chunk(&alpha_chunk)?;
+
+        anvil_chunk_provider.save_chunk(chunk_x, chunk_z, chunk_compound_tag)?;
+        converted_chunks += 1;
+    }
+
+    Ok(converted_chunks)
+}
+
+/// Converts Alpha world into Anvil world in another folder.
+///
+/// Chunks are saved into `region` folder, `level.dat` is copied with version of
+/// Anvil worlds. Alpha worlds have no other dimensions. Returns amount of converted chunks.
+pub fn convert_world(
+    alpha_world_folder_path: &Path,
+    anvil_world_folder_path: &Path,
+) -> Result<usize, AlphaChunkError> {
+    let alpha_chunk_provider = AlphaChunkProvider::from_path(alpha_world_folder_path);
+    let region_folder_path = anvil_world_folder_path.join("region");
+    let anvil_chunk_provider = AnvilChunkProvider::from_path(&region_folder_path);
+
+    let converted_chunks = convert_provider(&alpha_chunk_provider, &anvil_chunk_provider)?;
+
+    if alpha_world_folder_path.join(LEVEL_DAT_FILE).exists() {
+        let mut level_data = LevelData::load(alpha_world_folder_path)?;
+        level_data
+            .data_mut()
+            .insert_i32("version", ANVIL_LEVEL_VERSION);
+        level_data.save(anvil_world_folder_path)?;
+    }
+
+    Ok(converted_chunks)
+}
+
+fn column_array<'a>(
+    level: &'a CompoundTag,
+    name: &'a str,
+    length: usize,
+) -> Result<&'a [i8], AlphaChunkError> {
+    let array = level.get_i8_vec(name)?;
+
+    if array.len() != length {
+        return Err(AlphaChunkError::InvalidTag {
+            name: name.to_owned(),
+        });
+    }
+
+    Ok(array)
+}
+
+fn optional_column_array<'a>(
+    level: &'a CompoundTag,
+    name: &'a str,
+) -> Result<Option<&'a [i8]>, AlphaChunkError> {
+    if !level.contains_key(name) {
+        return Ok(None);
+    }
+
+    column_array(level, name, COLUMN_BLOCKS / 2).map(Some)
+}
+
+fn alpha_index(x: usize, y: usize, z: usize) -> usize {
+    y + z * ALPHA_WORLD_HEIGHT + x * ALPHA_WORLD_HEIGHT * 16
+}
+
+/// Returns height above the highest non-air block of every column.
+fn height_map(blocks: &[i8]) -> Vec<i32> {
+    let mut height_map = Vec::with_capacity(256);
+
+    for z in 0..16 {
+        for x in 0..16 {
+            let height = (0..ALPHA_WORLD_HEIGHT)
+                .rev()
+                .find(|y| blocks[alpha_index(x, *y, z)] != 0)
+                .map_or(0, |y| y as i32 + 1);
+
+            height_map.push(height);
+        }
+    }
+
+    height_map
+}
+
+fn nibble(array: &[i8], index: usize) -> u8 {
+    (array[index / 2] as u8 >> ((index % 2) * 4)) & 0xF
+}
+
+fn set_nibble(array: &mut [i8], index: usize, value: u8) {
+    let shift = (index % 2) * 4;
+    let byte = array[index / 2] as u8 & !(0xF << shift) | (value & 0xF) << shift;
+
+    array[index / 2] = byte as i8;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::alpha::{
+        alpha_index, convert_chunk, convert_world, from_base36, to_base36, AlphaChunkError,
+        AlphaChunkProvider, ALPHA_WORLD_HEIGHT, ANVIL_LEVEL_VERSION,
+    };
+    use crate::data::{read_data_file, write_data_file};
+    use crate::upgrade::upgrade_chunk;
+    use crate::version::FLATTENING_DATA_VERSION;
+    use crate::AnvilChunkProvider;
+    use nbt::CompoundTag;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    fn alpha_chunk(chunk_x: i32, chunk_z: i32) -> CompoundTag {
+        let mut blocks = vec![0; 16 * 16 * ALPHA_WORLD_HEIGHT];
+        let mut data = vec![0; 16 * 16 * ALPHA_WORLD_HEIGHT / 2];
+
+        for z in 0..16 {
+            for x in 0..16 {
+                // Bedrock floor and stone up to 63.
+                blocks[alpha_index(x, 0, z)] = 7;
+
+                for y in 1..64 {
+                    blocks[alpha_index(x, y, z)] = 1;
+                }
+            }
+        }
+
+        // Red wool with data value in high nibble of odd index.
+        let index = alpha_index(3, 65, 4);
+        blocks[index] = 35;
+        data[index / 2] = (14 << 4) as i8;
+
+        let mut level = CompoundTag::new();
+        level.insert_i32("xPos", chunk_x);
+        level.insert_i32("zPos", chunk_z);
+        level.insert_i64("LastUpdate", 1200);
+        level.insert_bool("TerrainPopulated", true);
+        level.insert_i8_vec("Blocks", blocks);
+        level.insert_i8_vec("Data", data);
+        level.insert_compound_tag_vec("Entities", Vec::new());
+
+        let mut chunk_compound_tag = CompoundTag::new();
+        chunk_compound_tag.insert_compound_tag("Level", level);
+
+        chunk_compound_tag
+    }
+
+    #[test]
+    fn test_base36() {
+        assert_eq!(to_base36(0), "0");
+        assert_eq!(to_base36(35), "z");
+        assert_eq!(to_base36(-13), "-d");
+        assert_eq!(to_base36(1295), "zz");
+        assert_eq!(to_base36(i32::MIN), "-zik0zk");
+
+        assert_eq!(from_base36("-zik0zk"), Some(i32::MIN));
+        assert_eq!(from_base36("zz"), Some(1295));
+        assert_eq!(from_base36("+1"), None);
+        assert_eq!(from_base36("c"), Some(12));
+        assert_eq!(from_base36("players"), None);
+    }
+
+    #[test]
+    fn test_chunk_path_and_positions() {
+        let world_dir = TempDir::new().unwrap();
+        let folder = world_dir.path().to_str().unwrap();
+        let chunk_provider = AlphaChunkProvider::new(folder);
+
+        assert_eq!(
+            chunk_provider.chunk_path(-13, 70),
+            Path::new(folder).join("1f").join("6").join("c.-d.1y.dat")
+        );
+
+        for (chunk_x, chunk_z) in [(-13, 70), (0, 0)] {
+            write_data_file(
+                &chunk_provider.chunk_path(chunk_x, chunk_z),
+                &alpha_chunk(chunk_x, chunk_z),
+            )
+            .unwrap();
+        }
+
+        // Chunk in folder of another position is never read by game.
+        std::fs::write(Path::new(folder).join("0").join("0").join("c.1.0.dat"), []).unwrap();
+
+        assert_eq!(
+            chunk_provider.chunk_positions().unwrap(),
+            vec![(-13, 70), (0, 0)]
+        );
+
+        let chunk_compound_tag = chunk_provider.load_chunk(-13, 70).unwrap();
+        let level = chunk_compound_tag.get_compound_tag("Level").unwrap();
+        assert_eq!(level.get_i32("xPos").unwrap(), -13);
+
+        assert!(matches!(
+            chunk_provider.load_chunk(5, 5),
+            Err(AlphaChunkError::ChunkNotFound {
+                chunk_x: 5,
+                chunk_z: 5
+            })
+        ));
+    }
+
+    #[test]
+    fn test_convert_chunk() {
+        let mut chunk_compound_tag = convert_chunk(&alpha_chunk(2, 3)).unwrap();
+        let level = chunk_compound_tag.get_compound_tag("Level").unwrap();
+
+        assert_eq!(level.get_i32("xPos").unwrap(), 2);
+        assert!(!level.get_bool("LightPopulated").unwrap());
+        assert_eq!(level.get_i32_vec("HeightMap").unwrap()[4 * 16 + 3], 66);
+        assert_eq!(level.get_i32_vec("HeightMap").unwrap()[0], 64);
+
+        let sections = level.get_compound_tag_vec("Sections").unwrap();
+        // Sections above wool contain only air.
+        assert_eq!(sections.len(), 5);
+
+        let section = sections[4];
+        assert_eq!(section.get_i8("Y").unwrap(), 4);
+
+        let index = 256 + 4 * 16 + 3;
+        assert_eq!(section.get_i8_vec("Blocks").unwrap()[index], 35);
+        assert_eq!(
+            section.get_i8_vec("Data").unwrap()[index / 2] as u8,
+            14 << 4
+        );
+
+        upgrade_chunk(&mut chunk_compound_tag, FLATTENING_DATA_VERSION).unwrap();
+    }
+
+    #[test]
+    fn test_convert_world() {
+        let alpha_dir = TempDir::new().unwrap();
+        let anvil_dir = TempDir::new().unwrap();
+        let chunk_provider = AlphaChunkProvider::new(alpha_dir.path().to_str().unwrap());
+
+        write_data_file(&chunk_provider.chunk_path(40, -1), &alpha_chunk(40, -1)).unwrap();
+
+        let mut data = CompoundTag::new();
+        data.insert_str("LevelName", "Alpha");
+        let mut level_compound_tag = CompoundTag::new();
+        level_compound_tag.insert_compound_tag("Data", data);
+        write_data_file(&alpha_dir.path().join("level.dat"), &level_compound_tag).unwrap();
+
+        assert_eq!(
+            convert_world(alpha_dir.path(), anvil_dir.path()).unwrap(),
+            1
+        );
+
+        let region_path = anvil_dir.path().join("region");
+        let anvil_chunk_provider = AnvilChunkProvider::new(region_path.to_str().unwrap());
+        assert_eq!(
+            anvil_chunk_provider.chunk_positions().unwrap(),
+            vec![(40, -1)]
+        );
+
+        let level_compound_tag = read_data_file(&anvil_dir.path().join("level.dat")).unwrap();
+        let data = level_compound_tag.get_compound_tag("Data").unwrap();
+        assert_eq!(data.get_i32("version").unwrap(), ANVIL_LEVEL_VERSION);
+        assert_eq!(data.get_str("LevelName").unwrap(), "Alpha");
+    }
+}
@@ -0,0 +1,1471 @@
+//! Chunk providers for async code.
+//!
+//! [`AsyncAnvilChunkProvider`] reads and writes region folder, [`AsyncZipChunkProvider`]
+//! reads region folder inside of zip archive without extracting it. Every operation runs
+//! on a background thread and returns future which completes when operation is done,
+//! so it doesn't block executor thread. Futures don't depend on any runtime and can be
+//! awaited on any executor, background threads are provided by [`BlockingSpawner`]
+//! which may use thread pool of runtime. Operations of one provider are executed one at
+//! a time, so concurrent saves to the same region file can't corrupt it, but operations
+//! which weren't awaited may run in any order.
+//!
+//! [`AsyncChunkProvider`] trait is implemented by both providers, so async code can be
+//! generic over storage like synchronous code with [`ChunkProvider`]. With `tokio`
+//! feature `tokio_fs::TokioAnvilChunkProvider` implements it too, reading and writing
+//! region files with `tokio::fs` instead of running operations on background threads.
+//!
+//! Region files are kept open between operations, up to [`REGION_CACHE_CAPACITY`] least
+//! recently used ones, so provider must be the only writer of its folder while in use.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::async_provider::AsyncAnvilChunkProvider;
+//!
+//! async fn chunk_x_position(chunk_provider: &AsyncAnvilChunkProvider) -> i32 {
+//!     let chunk_compound_tag = chunk_provider.load_chunk(4, 2).await.unwrap();
+//!     let level_compound_tag = chunk_compound_tag.get_compound_tag("Level").unwrap();
+//!
+//!     level_compound_tag.get_i32("xPos").unwrap()
+//! }
+//! ```
+//!
+//! [`ChunkProvider`]: crate::provider::ChunkProvider
+use crate::async_provider::runtime::{BlockingSpawner, PoolSpawner};
+use crate::memory::{MemoryBudget, MemoryConsumer, MemoryRegistration};
+use crate::region_slice::{read_sectors_chunk_data, RegionSlice};
+use crate::snapshot::break_hard_link;
+use crate::zip::{self, ZipEntry};
+use crate::{
+    decode_chunk, region_position, AnvilChunkMetadata, AnvilChunkProvider, AnvilRegion,
+    ChunkLoadError, ChunkSaveError, REGION_CHUNKS, REGION_HEADER_BYTES_LENGTH,
+    REGION_SECTOR_BYTES_LENGTH,
+};
+use nbt::CompoundTag;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::fs;
+use std::fs::File;
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll, Waker};
+
+pub mod runtime;
+pub mod stream;
+#[cfg(feature = "tokio")]
+pub mod tokio_fs;
+
+/// Maximum amount of region files kept open by provider.
+pub const REGION_CACHE_CAPACITY: usize = 16;
+
+/// Result of loading one chunk of bulk load.
+pub type ChunkLoadResult = Result<CompoundTag, ChunkLoadError>;
+
+/// Index of request with region coordinates of chunk.
+type RequestedChunk = (usize, (u8, u8));
+
+/// Cached region file if archive contains it with results of its chunks and their indices.
+type LoadedRegion = (Option<Arc<ZipRegion>>, Vec<(usize, ChunkLoadResult)>);
+
+/// Async counterpart of [`ChunkProvider`] for storages of chunk columns.
+///
+/// Futures are `Send`, so operations can be awaited inside of tasks spawned on multi
+/// threaded runtimes.
+///
+/// [`ChunkProvider`]: crate::provider::ChunkProvider
+pub trait AsyncChunkProvider {
+    /// Error while loading chunk.
+    type LoadError;
+    /// Error while saving or deleting chunk.
+    type SaveError;
+
+    /// Loads chunk from the specified coordinates.
+    fn load_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> impl Future<Output = Result<CompoundTag, Self::LoadError>> + Send;
+
+    /// Saves chunk to the specified coordinates.
+    fn save_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        chunk_compound_tag: CompoundTag,
+    ) -> impl Future<Output = Result<(), Self::SaveError>> + Send;
+
+    /// Deletes chunk, completes with false if chunk is not present.
+    fn delete_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> impl Future<Output = Result<bool, Self::SaveError>> + Send;
+
+    /// Returns coordinates of chunks which are present.
+    fn chunk_positions(&self) -> impl Future<Output = Result<Vec<(i32, i32)>, io::Error>> + Send;
+}
+
+/// Provider of chunks of region folder with async operations.
+#[derive(Clone)]
+pub struct AsyncAnvilChunkProvider {
+    /// Folder where region files located.
+    folder_path: Arc<PathBuf>,
+    /// Held while operation is running.
+    region_cache: Arc<Mutex<RegionCache>>,
+    spawner: Arc<dyn BlockingSpawner>,
+}
+
+impl AsyncAnvilChunkProvider {
+    /// Creates provider which runs operations on [`PoolSpawner::shared`] pool.
+    pub fn new(folder_path: impl AsRef<Path>) -> Self {
+        AsyncAnvilChunkProvider::with_spawner(folder_path, PoolSpawner::shared())
+    }
+
+    /// Creates provider which runs operations with specified spawner.
+    pub fn with_spawner(
+        folder_path: impl AsRef<Path>,
+        spawner: impl BlockingSpawner + 'static,
+    ) -> Self {
+        let folder_path = folder_path.as_ref().to_path_buf();
+
+        let region_cache = RegionCache {
+            folder_path: folder_path.clone(),
+            regions: Vec::new(),
+        };
+
+        AsyncAnvilChunkProvider {
+            folder_path: Arc::new(folder_path),
+            region_cache: Arc::new(Mutex::new(region_cache)),
+            spawner: Arc::new(spawner),
+        }
+    }
+
+    pub fn folder_path(&self) -> &Path {
+        &self.folder_path
+    }
+
+    /// Loads chunk from the specified coordinates.
+    pub fn load_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> BlockingFuture<Result<CompoundTag, ChunkLoadError>> {
+        self.spawn(move |region_cache| {
+            let region_position = (chunk_x >> 5, chunk_z >> 5);
+            let region_path = region_cache.region_path(region_position);
+
+            let result = match region_cache.region(region_position, false) {
+                Ok(Some(region)) => region.read_chunk((chunk_x & 31) as u8, (chunk_z & 31) as u8),
+                Ok(None) => {
+                    return Err(ChunkLoadError::RegionNotFound {
+                        region_x: region_position.0,
+                        region_z: region_position.1,
+                    })
+                }
+                Err(io_error) => Err(io_error.into()),
+            };
+
+            result.map_err(|chunk_load_error| {
+                chunk_load_error.with_context(&region_path, chunk_x, chunk_z)
+            })
+        })
+    }
+
+    /// Saves chunk data to the specified coordinates.
+    pub fn save_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        chunk_compound_tag: CompoundTag,
+    ) -> BlockingFuture<Result<(), ChunkSaveError>> {
+        self.spawn(move |region_cache| {
+            let region_position = (chunk_x >> 5, chunk_z >> 5);
+            let region_path = region_cache.region_path(region_position);
+
+            region_cache
+                .region(region_position, true)
+                .map_err(ChunkSaveError::from)
+                .and_then(|region| {
+                    // Region is always opened when it may be created.
+                    region.unwrap().write_locked(|region| {
+                        region.write_chunk(
+                            (chunk_x & 31) as u8,
+                            (chunk_z & 31) as u8,
+                            chunk_compound_tag,
+                        )
+                    })
+                })
+                .map_err(|chunk_save_error| {
+                    chunk_save_error.with_context(&region_path, chunk_x, chunk_z)
+                })
+        })
+    }
+
+    /// Deletes chunk at the specified coordinates, completes with false if chunk is
+    /// not present.
+    pub fn delete_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> BlockingFuture<Result<bool, ChunkSaveError>> {
+        self.spawn(move |region_cache| {
+            let region_position = (chunk_x >> 5, chunk_z >> 5);
+            let region_path = region_cache.region_path(region_position);
+
+            let result = match region_cache.region(region_position, false) {
+                Ok(Some(region)) => region.write_locked(|region| {
+                    region.delete_chunk((chunk_x & 31) as u8, (chunk_z & 31) as u8)
+                }),
+                Ok(None) => Ok(false),
+                Err(io_error) => Err(io_error),
+            };
+
+            result.map_err(|io_error| {
+                ChunkSaveError::from(io_error).with_context(&region_path, chunk_x, chunk_z)
+            })
+        })
+    }
+
+    /// Returns coordinates of all chunks stored in the folder region files.
+    pub fn chunk_positions(&self) -> BlockingFuture<Result<Vec<(i32, i32)>, io::Error>> {
+        self.spawn(|region_cache| {
+            let chunk_provider = AnvilChunkProvider::from_path(&region_cache.folder_path);
+
+            chunk_provider.chunk_positions()
+        })
+    }
+
+    /// Closes all cached region files.
+    pub fn close_regions(&self) -> BlockingFuture<()> {
+        self.spawn(|region_cache| region_cache.regions.clear())
+    }
+
+    /// Closes cached region file, so the next operation reads its header again.
+    ///
+    /// Header of cached region file is read again only before writes, reads after
+    /// other process modified region file need invalidation.
+    pub fn invalidate(&self, region_x: i32, region_z: i32) -> BlockingFuture<()> {
+        self.spawn(move |region_cache| {
+            region_cache
+                .regions
+                .retain(|(region_position, _)| *region_position != (region_x, region_z))
+        })
+    }
+
+    /// Closes all cached region files like [`close_regions`].
+    ///
+    /// [`close_regions`]: AsyncAnvilChunkProvider::close_regions
+    pub fn invalidate_all(&self) -> BlockingFuture<()> {
+        self.close_regions()
+    }
+
+    /// Syncs cached region files to disk and closes them, completes with the first
+    /// error of syncing.
+    ///
+    /// Writes are done by the time their futures complete, so dropping provider loses
+    /// no data, but only syncing reports errors of writing it out to disk.
+    pub fn close(self) -> BlockingFuture<Result<(), io::Error>> {
+        self.spawn(|region_cache| {
+            let mut result = Ok(());
+
+            for (_, region) in region_cache.regions.drain(..) {
+                let sync_result = region.file.sync_all();
+                result = result.and(sync_result);
+            }
+
+            result
+        })
+    }
+
+    fn spawn<T: Send + 'static>(
+        &self,
+        operation: impl FnOnce(&mut RegionCache) -> T + Send + 'static,
+    ) -> BlockingFuture<T> {
+        let region_cache = self.region_cache.clone();
+
+        spawn_blocking(&*self.spawner, move || {
+            // Lock is only poisoned if operation panicked, cache is dropped in that case
+            // because region handles may be left in unknown state.
+            let mut region_cache = region_cache.lock().unwrap_or_else(|error| {
+                let mut region_cache = error.into_inner();
+                region_cache.regions.clear();
+
+                region_cache
+            });
+
+            operation(&mut region_cache)
+        })
+    }
+}
+
+/// Provider of chunks of region folder inside of zip archive with async operations.
+///
+/// Archive is read only, region files are cached compressed as they are stored in
+/// archive, up to [`REGION_CACHE_CAPACITY`] least recently used ones. Only header and
+/// sectors of requested chunk are decompressed when chunk is loaded, so sparse reads
+/// don't keep whole decompressed region files in memory.
+///
+/// # Example
+///
+/// ```
+/// use anvil_region::async_provider::AsyncZipChunkProvider;
+///
+/// async fn chunk_count(zip_path: &str) -> usize {
+///     let chunk_provider = AsyncZipChunkProvider::open(zip_path, "world/region")
+///         .await
+///         .unwrap();
+///
+///     chunk_provider.chunk_positions().await.unwrap().len()
+/// }
+/// ```
+#[derive(Clone)]
+pub struct AsyncZipChunkProvider {
+    /// Held while operation is running.
+    zip_region_cache: Arc<Mutex<ZipRegionCache>>,
+    spawner: Arc<dyn BlockingSpawner>,
+}
+
+impl AsyncZipChunkProvider {
+    /// Opens archive reading its central directory.
+    ///
+    /// Region folder is path inside of archive like `world/region` or empty string
+    /// if region files are at archive root.
+    pub fn open(
+        zip_path: impl AsRef<Path>,
+        region_folder: &str,
+    ) -> BlockingFuture<Result<Self, io::Error>> {
+        AsyncZipChunkProvider::open_with_spawner(zip_path, region_folder, PoolSpawner::shared())
+    }
+
+    /// Opens archive like [`open`] running operations with specified spawner.
+    ///
+    /// [`open`]: AsyncZipChunkProvider::open
+    pub fn open_with_spawner(
+        zip_path: impl AsRef<Path>,
+        region_folder: &str,
+        spawner: impl BlockingSpawner + 'static,
+    ) -> BlockingFuture<Result<Self, io::Error>> {
+        let zip_path = zip_path.as_ref().to_path_buf();
+        let region_folder = region_folder.trim_matches('/').to_owned();
+        let spawner: Arc<dyn BlockingSpawner> = Arc::new(spawner);
+        let provider_spawner = spawner.clone();
+
+        spawn_blocking(&*spawner, move || {
+            let mut file = File::open(&zip_path)?;
+            let mut region_entries = Vec::new();
+
+            for entry in zip::entries(&mut file)? {
+                let file_name = match region_folder.as_str() {
+                    "" => Some(entry.name.as_str()),
+                    region_folder => entry
+                        .name
+                        .strip_prefix(region_folder)
+                        .and_then(|name| name.strip_prefix('/')),
+                };
+
+                if let Some(region_position) = file_name.and_then(region_position) {
+                    region_entries.push((region_position, entry));
+                }
+            }
+
+            region_entries.sort_unstable_by_key(|(region_position, _)| *region_position);
+
+            let zip_region_cache = ZipRegionCache {
+                file,
+                region_folder_path: zip_path.join(&region_folder),
+                region_entries,
+                regions: Vec::new(),
+                memory_registration: None,
+            };
+
+            Ok(AsyncZipChunkProvider {
+                zip_region_cache: Arc::new(Mutex::new(zip_region_cache)),
+                spawner: provider_spawner,
+            })
+        })
+    }
+
+    /// Accounts cached compressed region files in memory budget, least recently used
+    /// of them are evicted when it's exceeded.
+    ///
+    /// Clones of provider share cache, so budget applies to all of them.
+    pub fn memory_budget(self, memory_budget: MemoryBudget) -> Self {
+        let weak_zip_region_cache: Weak<Mutex<ZipRegionCache>> =
+            Arc::downgrade(&self.zip_region_cache);
+        let memory_registration = memory_budget.register(weak_zip_region_cache);
+        let mut zip_region_cache = self
+            .zip_region_cache
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+
+        memory_registration.set_usage(zip_region_cache.memory_usage());
+        zip_region_cache.memory_registration = Some(memory_registration);
+        drop(zip_region_cache);
+
+        self
+    }
+
+    /// Loads chunk from the specified coordinates.
+    pub fn load_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> BlockingFuture<Result<CompoundTag, ChunkLoadError>> {
+        self.spawn(move |zip_region_cache| {
+            let region_position = (chunk_x >> 5, chunk_z >> 5);
+
+            let result = match zip_region_cache.region(region_position) {
+                Ok(Some(region)) => region.read_chunk((chunk_x & 31) as u8, (chunk_z & 31) as u8),
+                Ok(None) => {
+                    return Err(ChunkLoadError::RegionNotFound {
+                        region_x: region_position.0,
+                        region_z: region_position.1,
+                    })
+                }
+                Err(io_error) => Err(io_error.into()),
+            };
+
+            result.map_err(|chunk_load_error| {
+                let region_path = zip_region_cache.region_path(region_position);
+
+                chunk_load_error.with_context(&region_path, chunk_x, chunk_z)
+            })
+        })
+    }
+
+    /// Completes with `UnsupportedOperation` error, archive is read only.
+    ///
+    /// Exists so code which works with both providers can handle read only archive
+    /// like failed save.
+    pub fn save_chunk(
+        &self,
+        _chunk_x: i32,
+        _chunk_z: i32,
+        _chunk_compound_tag: CompoundTag,
+    ) -> BlockingFuture<Result<(), ChunkSaveError>> {
+        BlockingFuture::ready(Err(ChunkSaveError::UnsupportedOperation {
+            operation: "save_chunk",
+        }))
+    }
+
+    /// Completes with `UnsupportedOperation` error, archive is read only.
+    pub fn delete_chunk(
+        &self,
+        _chunk_x: i32,
+        _chunk_z: i32,
+    ) -> BlockingFuture<Result<bool, ChunkSaveError>> {
+        BlockingFuture::ready(Err(ChunkSaveError::UnsupportedOperation {
+            operation: "delete_chunk",
+        }))
+    }
+
+    /// Loads chunks from the specified coordinates, results are in order of coordinates.
+    ///
+    /// Region file which has several of requested chunks is decompressed once for all of
+    /// them instead of once per chunk. With `rayon` feature region files are decompressed
+    /// and chunks decoded on rayon worker threads in parallel. Returns error if archive
+    /// can't be read.
+    pub fn load_chunks(
+        &self,
+        chunk_positions: Vec<(i32, i32)>,
+    ) -> BlockingFuture<Result<Vec<ChunkLoadResult>, io::Error>> {
+        self.spawn(move |zip_region_cache| zip_region_cache.load_chunks(&chunk_positions))
+    }
+
+    /// Returns coordinates of all chunks stored in the archive region files.
+    pub fn chunk_positions(&self) -> BlockingFuture<Result<Vec<(i32, i32)>, io::Error>> {
+        self.spawn(|zip_region_cache| {
+            let mut chunk_positions = Vec::new();
+            let region_positions: Vec<_> = zip_region_cache
+                .region_entries
+                .iter()
+                .map(|(region_position, _)| *region_position)
+                .collect();
+
+            for (region_x, region_z) in region_positions {
+                // Region is listed in central directory.
+                let region = zip_region_cache.region((region_x, region_z))?.unwrap();
+
+                for (region_chunk_x, region_chunk_z) in region.chunk_positions() {
+                    let chunk_x = (region_x << 5) + region_chunk_x as i32;
+                    let chunk_z = (region_z << 5) + region_chunk_z as i32;
+
+                    chunk_positions.push((chunk_x, chunk_z));
+                }
+            }
+
+            chunk_positions.sort_unstable();
+
+            Ok(chunk_positions)
+        })
+    }
+
+    /// Drops cached region file, so the next operation reads it from archive again.
+    ///
+    /// Entries of archive are read once when it is opened, archive replaced by other
+    /// process must be opened again.
+    pub fn invalidate(&self, region_x: i32, region_z: i32) -> BlockingFuture<()> {
+        self.spawn(move |zip_region_cache| {
+            zip_region_cache.remove_cached((region_x, region_z));
+        })
+    }
+
+    /// Drops all cached region files.
+    pub fn invalidate_all(&self) -> BlockingFuture<()> {
+        self.spawn(|zip_region_cache| {
+            zip_region_cache.regions.clear();
+            zip_region_cache.update_memory_usage();
+        })
+    }
+
+    fn spawn<T: Send + 'static>(
+        &self,
+        operation: impl FnOnce(&mut ZipRegionCache) -> T + Send + 'static,
+    ) -> BlockingFuture<T> {
+        let zip_region_cache = self.zip_region_cache.clone();
+
+        spawn_blocking(&*self.spawner, move || {
+            // Cached regions are immutable, so poisoned lock is safe to reuse.
+            let mut zip_region_cache = zip_region_cache
+                .lock()
+                .unwrap_or_else(|error| error.into_inner());
+
+            let output = operation(&mut zip_region_cache);
+            let memory_budget = zip_region_cache
+                .memory_registration
+                .as_ref()
+                .map(|memory_registration| memory_registration.memory_budget().clone());
+
+            drop(zip_region_cache);
+
+            if let Some(memory_budget) = memory_budget {
+                memory_budget.enforce();
+            }
+
+            output
+        })
+    }
+}
+
+/// Runs operation on background thread of spawner.
+fn spawn_blocking<T: Send + 'static>(
+    spawner: &dyn BlockingSpawner,
+    operation: impl FnOnce() -> T + Send + 'static,
+) -> BlockingFuture<T> {
+    let state = Arc::new(Mutex::new(BlockingState {
+        output: None,
+        waker: None,
+    }));
+
+    let thread_state = state.clone();
+
+    spawner.spawn_blocking(Box::new(move || {
+        let output = operation();
+
+        let mut state = thread_state.lock().unwrap();
+        state.output = Some(output);
+
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }));
+
+    BlockingFuture { state }
+}
+
+/// Open region files of folder, most recently used last.
+struct RegionCache {
+    /// Folder where region files located.
+    folder_path: PathBuf,
+    regions: Vec<((i32, i32), AnvilRegion)>,
+}
+
+impl RegionCache {
+    fn region_path(&self, region_position: (i32, i32)) -> PathBuf {
+        let (region_x, region_z) = region_position;
+
+        self.folder_path
+            .join(format!("r.{}.{}.mca", region_x, region_z))
+    }
+
+    /// Returns open region file, none if it doesn't exist and must not be created.
+    ///
+    /// Region file opened for writing is separated from snapshot links first.
+    fn region(
+        &mut self,
+        region_position: (i32, i32),
+        write: bool,
+    ) -> Result<Option<&mut AnvilRegion>, io::Error> {
+        let region_path = self.region_path(region_position);
+
+        let index = self
+            .regions
+            .iter()
+            .position(|(cached_position, _)| *cached_position == region_position);
+
+        let region = match index {
+            Some(index) => Some(self.regions.remove(index).1),
+            None => None,
+        };
+
+        let region = match region {
+            // Cached handle refers to file which was replaced by private copy.
+            Some(_) if write && break_hard_link(&region_path)? => AnvilRegion::new(&region_path)?,
+            Some(region) => region,
+            None if !region_path.exists() && !write => return Ok(None),
+            None => {
+                if write {
+                    fs::create_dir_all(&self.folder_path)?;
+                    break_hard_link(&region_path)?;
+                }
+
+                AnvilRegion::new(&region_path)?
+            }
+        };
+
+        if self.regions.len() == REGION_CACHE_CAPACITY {
+            self.regions.remove(0);
+        }
+
+        self.regions.push((region_position, region));
+
+        Ok(self.regions.last_mut().map(|(_, region)| region))
+    }
+}
+
+/// Archive with compressed region files, most recently used last.
+struct ZipRegionCache {
+    file: File,
+    /// Path of archive joined with region folder inside of it, used in errors.
+    region_folder_path: PathBuf,
+    /// Region file entries of region folder in ascending order of position.
+    region_entries: Vec<((i32, i32), ZipEntry)>,
+    regions: Vec<Arc<ZipRegion>>,
+    memory_registration: Option<MemoryRegistration>,
+}
+
+/// Region file of archive with data as it is stored in archive.
+struct ZipRegion {
+    region_position: (i32, i32),
+    entry: ZipEntry,
+    compressed: Vec<u8>,
+    /// Array of chunks metadata, none if region file is shorter than header.
+    chunks_metadata: Option<[AnvilChunkMetadata; REGION_CHUNKS]>,
+}
+
+/// Region file of bulk load with requested chunks.
+struct RegionLoad {
+    region_position: (i32, i32),
+    source: RegionSource,
+    chunks: Vec<RequestedChunk>,
+}
+
+enum RegionSource {
+    Cached(Arc<ZipRegion>),
+    /// Entry with data read from archive, header isn't decompressed yet.
+    Compressed(ZipEntry, Vec<u8>),
+    Missing,
+}
+
+impl ZipRegionCache {
+    /// Returns compressed region file, none if archive doesn't contain it.
+    fn region(&mut self, region_position: (i32, i32)) -> Result<Option<Arc<ZipRegion>>, io::Error> {
+        let region = match self.remove_cached(region_position) {
+            Some(region) => region,
+            None => match self.entry(region_position) {
+                Some(entry) => {
+                    let compressed = zip::read_compressed_entry(&mut self.file, &entry)?;
+
+                    Arc::new(ZipRegion::new(region_position, entry, compressed)?)
+                }
+                None => return Ok(None),
+            },
+        };
+
+        self.insert(region.clone());
+
+        Ok(Some(region))
+    }
+
+    /// Loads chunks grouped by region file, region files are read one by one and
+    /// decompressed in parallel.
+    fn load_chunks(
+        &mut self,
+        chunk_positions: &[(i32, i32)],
+    ) -> Result<Vec<ChunkLoadResult>, io::Error> {
+        let mut region_chunks: BTreeMap<(i32, i32), Vec<RequestedChunk>> = BTreeMap::new();
+
+        for (index, (chunk_x, chunk_z)) in chunk_positions.iter().enumerate() {
+            region_chunks
+                .entry((chunk_x >> 5, chunk_z >> 5))
+                .or_default()
+                .push((index, ((chunk_x & 31) as u8, (chunk_z & 31) as u8)));
+        }
+
+        let mut region_loads = Vec::with_capacity(region_chunks.len());
+
+        for (region_position, chunks) in region_chunks {
+            let source = match self.remove_cached(region_position) {
+                Some(region) => RegionSource::Cached(region),
+                None => match self.entry(region_position) {
+                    Some(entry) => {
+                        let compressed = zip::read_compressed_entry(&mut self.file, &entry)?;
+
+                        RegionSource::Compressed(entry, compressed)
+                    }
+                    None => RegionSource::Missing,
+                },
+            };
+
+            region_loads.push(RegionLoad {
+                region_position,
+                source,
+                chunks,
+            });
+        }
+
+        #[cfg(feature = "rayon")]
+        let loaded_regions = region_loads.into_par_iter().map(RegionLoad::load);
+        #[cfg(not(feature = "rayon"))]
+        let loaded_regions = region_loads.into_iter().map(RegionLoad::load);
+
+        let loaded_regions: Vec<LoadedRegion> = loaded_regions.collect::<Result<_, _>>()?;
+        let mut results: Vec<Option<ChunkLoadResult>> =
+            chunk_positions.iter().map(|_| None).collect();
+
+        for (region, chunk_results) in loaded_regions {
+            if let Some(region) = region {
+                self.insert(region);
+            }
+
+            for (index, result) in chunk_results {
+                results[index] = Some(result);
+            }
+        }
+
+        // Every request belongs to one of regions.
+        let results = results
+            .into_iter()
+            .zip(chunk_positions)
+            .map(|(result, &(chunk_x, chunk_z))| {
+                result.unwrap().map_err(|chunk_load_error| {
+                    let region_path = self.region_path((chunk_x >> 5, chunk_z >> 5));
+
+                    chunk_load_error.with_context(&region_path, chunk_x, chunk_z)
+                })
+            })
+            .collect();
+
+        Ok(results)
+    }
+
+    fn region_path(&self, region_position: (i32, i32)) -> PathBuf {
+        let (region_x, region_z) = region_position;
+
+        self.region_folder_path
+            .join(format!("r.{}.{}.mca", region_x, region_z))
+    }
+
+    fn remove_cached(&mut self, region_position: (i32, i32)) -> Option<Arc<ZipRegion>> {
+        let index = self
+            .regions
+            .iter()
+            .position(|zip_region| zip_region.region_position == region_position)?;
+
+        let region = self.regions.remove(index);
+        self.update_memory_usage();
+
+        Some(region)
+    }
+
+    fn entry(&self, region_position: (i32, i32)) -> Option<ZipEntry> {
+        let index = self
+            .region_entries
+            .binary_search_by_key(&region_position, |(position, _)| *position)
+            .ok()?;
+
+        Some(self.region_entries[index].1.clone())
+    }
+
+    /// Caches region as most recently used, evicting least recently used one.
+    fn insert(&mut self, region: Arc<ZipRegion>) {
+        if self.regions.len() == REGION_CACHE_CAPACITY {
+            self.regions.remove(0);
+        }
+
+        self.regions.push(region);
+        self.update_memory_usage();
+    }
+
+    /// Returns amount of bytes of cached compressed region files.
+    fn memory_usage(&self) -> usize {
+        self.regions
+            .iter()
+            .map(|zip_region| zip_region.compressed.len())
+            .sum()
+    }
+
+    fn update_memory_usage(&self) {
+        if let Some(memory_registration) = &self.memory_registration {
+            memory_registration.set_usage(self.memory_usage());
+        }
+    }
+}
+
+impl MemoryConsumer for Mutex<ZipRegionCache> {
+    /// Evicts least recently used regions.
+    fn release_memory(&self, bytes: usize) {
+        let mut zip_region_cache = self.lock().unwrap_or_else(|error| error.into_inner());
+        let target_usage = zip_region_cache.memory_usage().saturating_sub(bytes);
+
+        while !zip_region_cache.regions.is_empty() && zip_region_cache.memory_usage() > target_usage
+        {
+            zip_region_cache.regions.remove(0);
+        }
+
+        zip_region_cache.update_memory_usage();
+    }
+}
+
+impl RegionLoad {
+    /// Decompresses region file and reads its chunks.
+    fn load(self) -> Result<LoadedRegion, io::Error> {
+        let region = match self.source {
+            RegionSource::Cached(region) => region,
+            RegionSource::Compressed(entry, compressed) => {
+                Arc::new(ZipRegion::new(self.region_position, entry, compressed)?)
+            }
+            RegionSource::Missing => {
+                let (region_x, region_z) = self.region_position;
+                let chunk_results = self
+                    .chunks
+                    .into_iter()
+                    .map(|(index, _)| {
+                        (
+                            index,
+                            Err(ChunkLoadError::RegionNotFound { region_x, region_z }),
+                        )
+                    })
+                    .collect();
+
+                return Ok((None, chunk_results));
+            }
+        };
+
+        let chunk_results = region.read_chunks(&self.chunks)?;
+
+        Ok((Some(region), chunk_results))
+    }
+}
+
+impl ZipRegion {
+    /// Decompresses header of compressed region file.
+    fn new(
+        region_position: (i32, i32),
+        entry: ZipEntry,
+        compressed: Vec<u8>,
+    ) -> Result<Self, io::Error> {
+        let header =
+            zip::read_entry_range(&entry, &compressed, 0, REGION_HEADER_BYTES_LENGTH as usize)?;
+
+        Ok(ZipRegion {
+            region_position,
+            entry,
+            compressed,
+            chunks_metadata: AnvilRegion::read_header(&mut header.as_slice()).ok(),
+        })
+    }
+
+    /// Reads chunk decompressing only sectors of chunk.
+    fn read_chunk(&self, chunk_x: u8, chunk_z: u8) -> Result<CompoundTag, ChunkLoadError> {
+        let metadata = match &self.chunks_metadata {
+            Some(chunks_metadata) => chunks_metadata[AnvilRegion::metadata_index(chunk_x, chunk_z)],
+            None => {
+                return Err(ChunkLoadError::ChunkNotFound {
+                    chunk_x: chunk_x as i32,
+                    chunk_z: chunk_z as i32,
+                })
+            }
+        };
+
+        if metadata.is_empty() {
+            return Err(ChunkLoadError::ChunkNotFound {
+                chunk_x: chunk_x as i32,
+                chunk_z: chunk_z as i32,
+            });
+        }
+
+        let sector_bytes_length = REGION_SECTOR_BYTES_LENGTH as usize;
+        let sectors = zip::read_entry_range(
+            &self.entry,
+            &self.compressed,
+            metadata.sector_index as usize * sector_bytes_length,
+            metadata.sectors as usize * sector_bytes_length,
+        )?;
+
+        let (compression_scheme, compressed_buffer) = read_sectors_chunk_data(&sectors, metadata)?;
+
+        decode_chunk(compression_scheme, compressed_buffer)
+    }
+
+    /// Reads chunks decompressing whole region file once if there are several of them.
+    fn read_chunks(
+        &self,
+        chunks: &[RequestedChunk],
+    ) -> Result<Vec<(usize, ChunkLoadResult)>, io::Error> {
+        if let [(index, (chunk_x, chunk_z))] = chunks {
+            return Ok(vec![(*index, self.read_chunk(*chunk_x, *chunk_z))]);
+        }
+
+        let data = zip::inflate_entry(&self.entry, &self.compressed)?;
+        let region_slice = RegionSlice::new(&data).ok();
+
+        let chunk_results = chunks
+            .iter()
+            .map(|&(index, (chunk_x, chunk_z))| {
+                let result = match &region_slice {
+                    Some(region_slice) => region_slice.read_chunk(chunk_x, chunk_z),
+                    None => Err(ChunkLoadError::ChunkNotFound {
+                        chunk_x: chunk_x as i32,
+                        chunk_z: chunk_z as i32,
+                    }),
+                };
+
+                (index, result)
+            })
+            .collect();
+
+        Ok(chunk_results)
+    }
+
+    /// Returns region coordinates of chunks which are present in region.
+    fn chunk_positions(&self) -> Vec<(u8, u8)> {
+        let chunks_metadata = match &self.chunks_metadata {
+            Some(chunks_metadata) => chunks_metadata,
+            None => return Vec::new(),
+        };
+
+        let mut chunk_positions = Vec::new();
+
+        for (index, metadata) in chunks_metadata.iter().enumerate() {
+            if !metadata.is_empty() {
+                chunk_positions.push(((index % 32) as u8, (index / 32) as u8));
+            }
+        }
+
+        chunk_positions
+    }
+}
+
+impl AsyncChunkProvider for AsyncAnvilChunkProvider {
+    type LoadError = ChunkLoadError;
+    type SaveError = ChunkSaveError;
+
+    fn load_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> impl Future<Output = Result<CompoundTag, ChunkLoadError>> + Send {
+        AsyncAnvilChunkProvider::load_chunk(self, chunk_x, chunk_z)
+    }
+
+    fn save_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        chunk_compound_tag: CompoundTag,
+    ) -> impl Future<Output = Result<(), ChunkSaveError>> + Send {
+        AsyncAnvilChunkProvider::save_chunk(self, chunk_x, chunk_z, chunk_compound_tag)
+    }
+
+    fn delete_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> impl Future<Output = Result<bool, ChunkSaveError>> + Send {
+        AsyncAnvilChunkProvider::delete_chunk(self, chunk_x, chunk_z)
+    }
+
+    fn chunk_positions(&self) -> impl Future<Output = Result<Vec<(i32, i32)>, io::Error>> + Send {
+        AsyncAnvilChunkProvider::chunk_positions(self)
+    }
+}
+
+impl AsyncChunkProvider for AsyncZipChunkProvider {
+    type LoadError = ChunkLoadError;
+    type SaveError = ChunkSaveError;
+
+    fn load_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> impl Future<Output = Result<CompoundTag, ChunkLoadError>> + Send {
+        AsyncZipChunkProvider::load_chunk(self, chunk_x, chunk_z)
+    }
+
+    fn save_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        chunk_compound_tag: CompoundTag,
+    ) -> impl Future<Output = Result<(), ChunkSaveError>> + Send {
+        AsyncZipChunkProvider::save_chunk(self, chunk_x, chunk_z, chunk_compound_tag)
+    }
+
+    fn delete_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> impl Future<Output = Result<bool, ChunkSaveError>> + Send {
+        AsyncZipChunkProvider::delete_chunk(self, chunk_x, chunk_z)
+    }
+
+    fn chunk_positions(&self) -> impl Future<Output = Result<Vec<(i32, i32)>, io::Error>> + Send {
+        AsyncZipChunkProvider::chunk_positions(self)
+    }
+}
+
+/// Future of operation running on background thread.
+#[derive(Debug)]
+pub struct BlockingFuture<T> {
+    state: Arc<Mutex<BlockingState<T>>>,
+}
+
+#[derive(Debug)]
+struct BlockingState<T> {
+    /// Result of operation, none while it is running.
+    output: Option<T>,
+    /// Waker of last poll which must be woken when operation is done.
+    waker: Option<Waker>,
+}
+
+impl<T> BlockingFuture<T> {
+    /// Returns future which is already completed.
+    fn ready(output: T) -> Self {
+        let state = BlockingState {
+            output: Some(output),
+            waker: None,
+        };
+
+        BlockingFuture {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+}
+
+impl<T> Future for BlockingFuture<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<T> {
+        let mut state = self.state.lock().unwrap();
+
+        match state.output.take() {
+            Some(output) => Poll::Ready(output),
+            None => {
+                state.waker = Some(context.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::async_provider::{
+        AsyncAnvilChunkProvider, AsyncChunkProvider, AsyncZipChunkProvider, REGION_CACHE_CAPACITY,
+    };
+    use crate::memory::MemoryBudget;
+    use crate::relocate::copy_chunk;
+    use crate::zip::write_zip;
+    use crate::{AnvilChunkProvider, ChunkLoadError, ChunkSaveError};
+    use nbt::CompoundTag;
+    use std::fs;
+    use std::future::Future;
+    use std::pin::pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake};
+    use std::thread::{self, Thread};
+    use tempfile::TempDir;
+
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+        let waker = Arc::new(ThreadWaker(thread::current())).into();
+        let mut context = Context::from_waker(&waker);
+        let mut future = pin!(future);
+
+        loop {
+            match future.as_mut().poll(&mut context) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_async_provider() {
+        let temp_dir = TempDir::new().unwrap();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+        copy_chunk(&fixture_chunk_provider, (4, 2), &chunk_provider, (4, 2)).unwrap();
+
+        let async_chunk_provider = AsyncAnvilChunkProvider::new(temp_dir.path());
+
+        block_on(async {
+            let chunk_compound_tag = async_chunk_provider.load_chunk(4, 2).await.unwrap();
+
+            let saves: Vec<_> = (0..8)
+                .map(|chunk_x| {
+                    async_chunk_provider.save_chunk(chunk_x, 0, chunk_compound_tag.clone())
+                })
+                .collect();
+
+            for save in saves {
+                save.await.unwrap();
+            }
+
+            assert!(async_chunk_provider.delete_chunk(4, 2).await.unwrap());
+            assert!(!async_chunk_provider.delete_chunk(-1, -1).await.unwrap());
+            assert_eq!(
+                async_chunk_provider.chunk_positions().await.unwrap(),
+                (0..8).map(|chunk_x| (chunk_x, 0)).collect::<Vec<_>>()
+            );
+
+            match async_chunk_provider.load_chunk(-1, -1).await {
+                Err(ChunkLoadError::RegionNotFound { region_x, region_z }) => {
+                    assert_eq!((region_x, region_z), (-1, -1))
+                }
+                result => panic!("Expected `RegionNotFound` but got `{:?}`", result),
+            }
+        });
+    }
+
+    #[test]
+    fn test_close() {
+        let temp_dir = TempDir::new().unwrap();
+        let async_chunk_provider = AsyncAnvilChunkProvider::new(temp_dir.path());
+
+        block_on(async {
+            async_chunk_provider
+                .save_chunk(4, 2, CompoundTag::new())
+                .await
+                .unwrap();
+
+            async_chunk_provider.close().await.unwrap();
+        });
+
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+        assert!(chunk_provider.load_chunk(4, 2).is_ok());
+    }
+
+    #[test]
+    fn test_region_cache_capacity() {
+        let temp_dir = TempDir::new().unwrap();
+        let async_chunk_provider = AsyncAnvilChunkProvider::new(temp_dir.path());
+        let region_count = REGION_CACHE_CAPACITY as i32 + 2;
+
+        block_on(async {
+            for region_x in 0..region_count {
+                let mut chunk_compound_tag = CompoundTag::new();
+                chunk_compound_tag.insert_i32("xPos", region_x << 5);
+
+                async_chunk_provider
+                    .save_chunk(region_x << 5, 0, chunk_compound_tag)
+                    .await
+                    .unwrap();
+            }
+
+            for region_x in 0..region_count {
+                let chunk_compound_tag = async_chunk_provider
+                    .load_chunk(region_x << 5, 0)
+                    .await
+                    .unwrap();
+
+                assert_eq!(chunk_compound_tag.get_i32("xPos").unwrap(), region_x << 5);
+            }
+
+            async_chunk_provider.close_regions().await;
+        });
+
+        assert_eq!(
+            async_chunk_provider
+                .region_cache
+                .lock()
+                .unwrap()
+                .regions
+                .len(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_invalidate() {
+        let temp_dir = TempDir::new().unwrap();
+        let async_chunk_provider = AsyncAnvilChunkProvider::new(temp_dir.path());
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+
+        block_on(async {
+            async_chunk_provider
+                .save_chunk(4, 2, CompoundTag::new())
+                .await
+                .unwrap();
+
+            // Header of cached region doesn't know about delete by other provider.
+            assert!(chunk_provider.delete_chunk(4, 2).unwrap());
+            assert!(async_chunk_provider.load_chunk(4, 2).await.is_ok());
+
+            async_chunk_provider.invalidate(0, 0).await;
+
+            match async_chunk_provider.load_chunk(4, 2).await {
+                Err(ChunkLoadError::ChunkNotFound { chunk_x, chunk_z }) => {
+                    assert_eq!(chunk_x, 4);
+                    assert_eq!(chunk_z, 2);
+                }
+                result => panic!("Expected `ChunkNotFound` but got `{:?}`", result),
+            }
+
+            async_chunk_provider.invalidate_all().await;
+        });
+
+        assert!(async_chunk_provider
+            .region_cache
+            .lock()
+            .unwrap()
+            .regions
+            .is_empty());
+    }
+
+    /// Copies chunk between providers which are known only by trait.
+    pub(crate) async fn copy_chunk_async<S, D>(
+        source: &S,
+        destination: &D,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> Result<(), D::SaveError>
+    where
+        S: AsyncChunkProvider,
+        D: AsyncChunkProvider,
+    {
+        let chunk_compound_tag = source.load_chunk(chunk_x, chunk_z).await.ok().unwrap();
+
+        destination
+            .save_chunk(chunk_x, chunk_z, chunk_compound_tag)
+            .await
+    }
+
+    #[test]
+    fn test_async_chunk_provider_trait() {
+        let temp_dir = TempDir::new().unwrap();
+        let zip_path = temp_dir.path().join("world.zip");
+        let region = fs::read("test/region/r.0.0.mca").unwrap();
+        write_zip(&zip_path, &[("r.0.0.mca", &region)], 8);
+
+        let folder_chunk_provider = AsyncAnvilChunkProvider::new(temp_dir.path().join("region"));
+
+        block_on(async {
+            let zip_chunk_provider = AsyncZipChunkProvider::open(&zip_path, "").await.unwrap();
+
+            copy_chunk_async(&zip_chunk_provider, &folder_chunk_provider, 4, 2)
+                .await
+                .unwrap();
+
+            assert_eq!(
+                AsyncChunkProvider::chunk_positions(&folder_chunk_provider)
+                    .await
+                    .unwrap(),
+                vec![(4, 2)]
+            );
+
+            match copy_chunk_async(&folder_chunk_provider, &zip_chunk_provider, 4, 2).await {
+                Err(ChunkSaveError::UnsupportedOperation { operation }) => {
+                    assert_eq!(operation, "save_chunk")
+                }
+                result => panic!("Expected `UnsupportedOperation` but got `{:?}`", result),
+            }
+        });
+    }
+
+    #[test]
+    fn test_async_zip_provider() {
+        let temp_dir = TempDir::new().unwrap();
+        let zip_path = temp_dir.path().join("world.zip");
+        let region = fs::read("test/region/r.0.0.mca").unwrap();
+
+        write_zip(
+            &zip_path,
+            &[
+                ("world/region/r.0.0.mca", &region),
+                ("world/region/r.1.0.mca", &[]),
+                ("world/DIM-1/region/r.0.0.mca", &region),
+            ],
+            8,
+        );
+
+        block_on(async {
+            let zip_chunk_provider = AsyncZipChunkProvider::open(&zip_path, "world/region/")
+                .await
+                .unwrap();
+
+            let chunk_compound_tag = zip_chunk_provider.load_chunk(4, 2).await.unwrap();
+            let level_compound_tag = chunk_compound_tag.get_compound_tag("Level").unwrap();
+
+            assert_eq!(level_compound_tag.get_i32("xPos").unwrap(), 4);
+            assert_eq!(
+                zip_chunk_provider.chunk_positions().await.unwrap(),
+                AnvilChunkProvider::new("test/region")
+                    .chunk_positions()
+                    .unwrap()
+            );
+
+            match zip_chunk_provider.load_chunk(31, 31).await {
+                Err(ChunkLoadError::ChunkNotFound { chunk_x, chunk_z }) => {
+                    assert_eq!((chunk_x, chunk_z), (31, 31))
+                }
+                result => panic!("Expected `ChunkNotFound` but got `{:?}`", result),
+            }
+
+            match zip_chunk_provider.load_chunk(32, 0).await {
+                Err(ChunkLoadError::ChunkNotFound { .. }) => {}
+                result => panic!("Expected `ChunkNotFound` but got `{:?}`", result),
+            }
+
+            match zip_chunk_provider.load_chunk(-1, 0).await {
+                Err(ChunkLoadError::RegionNotFound { region_x, region_z }) => {
+                    assert_eq!((region_x, region_z), (-1, 0))
+                }
+                result => panic!("Expected `RegionNotFound` but got `{:?}`", result),
+            }
+        });
+    }
+
+    #[test]
+    fn test_async_zip_provider_load_chunks() {
+        let temp_dir = TempDir::new().unwrap();
+        let zip_path = temp_dir.path().join("world.zip");
+        let region = fs::read("test/region/r.0.0.mca").unwrap();
+
+        write_zip(
+            &zip_path,
+            &[("r.0.0.mca", &region), ("r.1.0.mca", &region)],
+            8,
+        );
+
+        block_on(async {
+            let zip_chunk_provider = AsyncZipChunkProvider::open(&zip_path, "").await.unwrap();
+
+            // Cached region is reused by bulk load.
+            zip_chunk_provider.load_chunk(36, 2).await.unwrap();
+
+            let results = zip_chunk_provider
+                .load_chunks(vec![(15, 3), (-1, 0), (4, 2), (36, 2), (15, 14)])
+                .await
+                .unwrap();
+
+            let x_positions: Vec<_> = results
+                .iter()
+                .map(|result| match result {
+                    Ok(chunk_compound_tag) => {
+                        let level_compound_tag =
+                            chunk_compound_tag.get_compound_tag("Level").unwrap();
+
+                        Some(level_compound_tag.get_i32("xPos").unwrap())
+                    }
+                    Err(_) => None,
+                })
+                .collect();
+
+            // Second region is a copy of the first one.
+            assert_eq!(x_positions, vec![Some(15), None, Some(4), Some(4), None]);
+
+            match &results[1] {
+                Err(ChunkLoadError::RegionNotFound { region_x, region_z }) => {
+                    assert_eq!((*region_x, *region_z), (-1, 0))
+                }
+                result => panic!("Expected `RegionNotFound` but got `{:?}`", result),
+            }
+
+            match &results[4] {
+                Err(ChunkLoadError::ChunkNotFound { chunk_x, chunk_z }) => {
+                    assert_eq!((*chunk_x, *chunk_z), (15, 14))
+                }
+                result => panic!("Expected `ChunkNotFound` but got `{:?}`", result),
+            }
+        });
+    }
+
+    #[test]
+    fn test_async_zip_provider_read_only() {
+        let temp_dir = TempDir::new().unwrap();
+        let zip_path = temp_dir.path().join("world.zip");
+        let region = fs::read("test/region/r.0.0.mca").unwrap();
+
+        write_zip(&zip_path, &[("r.0.0.mca", &region)], 8);
+
+        block_on(async {
+            let zip_chunk_provider = AsyncZipChunkProvider::open(&zip_path, "").await.unwrap();
+
+            match zip_chunk_provider
+                .save_chunk(4, 2, CompoundTag::new())
+                .await
+            {
+                Err(ChunkSaveError::UnsupportedOperation { operation }) => {
+                    assert_eq!(operation, "save_chunk")
+                }
+                result => panic!("Expected `UnsupportedOperation` but got `{:?}`", result),
+            }
+
+            match zip_chunk_provider.delete_chunk(4, 2).await {
+                Err(ChunkSaveError::UnsupportedOperation { operation }) => {
+                    assert_eq!(operation, "delete_chunk")
+                }
+                result => panic!("Expected `UnsupportedOperation` but got `{:?}`", result),
+            }
+
+            assert!(zip_chunk_provider.load_chunk(4, 2).await.is_ok());
+        });
+    }
+
+    #[test]
+    fn test_async_zip_provider_memory_budget() {
+        let temp_dir = TempDir::new().unwrap();
+        let zip_path = temp_dir.path().join("world.zip");
+        let region = fs::read("test/region/r.0.0.mca").unwrap();
+
+        write_zip(&zip_path, &[("r.0.0.mca", &region)], 8);
+
+        block_on(async {
+            let memory_budget = MemoryBudget::new(usize::MAX);
+            let zip_chunk_provider = AsyncZipChunkProvider::open(&zip_path, "")
+                .await
+                .unwrap()
+                .memory_budget(memory_budget.clone());
+
+            zip_chunk_provider.load_chunk(4, 2).await.unwrap();
+            assert!(memory_budget.used() > 0);
+
+            drop(zip_chunk_provider);
+            assert_eq!(memory_budget.used(), 0);
+            assert_eq!(memory_budget.consumer_count(), 0);
+
+            // Region is evicted right after it's read.
+            let memory_budget = MemoryBudget::new(1);
+            let zip_chunk_provider = AsyncZipChunkProvider::open(&zip_path, "")
+                .await
+                .unwrap()
+                .memory_budget(memory_budget.clone());
+
+            zip_chunk_provider.load_chunk(4, 2).await.unwrap();
+            assert_eq!(memory_budget.used(), 0);
+        });
+    }
+}
@@ -0,0 +1,206 @@
+//! Spawning blocking operations of async providers on threads of async runtime.
+//!
+//! Region files are read and written with blocking file I/O inside of operations, so
+//! runtime only has to provide threads on which blocking code may run. By default
+//! operations run on [`PoolSpawner::shared`] pool with fixed amount of threads,
+//! `TokioSpawner` and `SmolSpawner` run operations on blocking thread pools of tokio
+//! and smol and are enabled by `tokio` and `smol` features.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::async_provider::runtime::PoolSpawner;
+//! use anvil_region::async_provider::AsyncAnvilChunkProvider;
+//!
+//! let chunk_provider = AsyncAnvilChunkProvider::with_spawner("test/region", PoolSpawner::new(2));
+//! ```
+use std::num::NonZeroUsize;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+
+/// Operation which must be run on thread where blocking is allowed.
+pub type BlockingOperation = Box<dyn FnOnce() + Send>;
+
+/// Runs blocking operations of async providers.
+///
+/// Future of operation completes when operation is run, so it never completes if
+/// spawner drops operation, for example because runtime shut down.
+pub trait BlockingSpawner: Send + Sync {
+    fn spawn_blocking(&self, operation: BlockingOperation);
+}
+
+/// Spawner which runs every operation on new thread, doesn't depend on any runtime.
+///
+/// Amount of threads isn't bounded, many concurrent operations start as many threads.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThreadSpawner;
+
+impl BlockingSpawner for ThreadSpawner {
+    fn spawn_blocking(&self, operation: BlockingOperation) {
+        thread::spawn(operation);
+    }
+}
+
+/// Spawner which runs operations on fixed amount of threads, doesn't depend on any
+/// runtime.
+///
+/// Operations wait in queue while every thread is busy. Threads exit when every clone
+/// of spawner is dropped and queue is empty.
+#[derive(Debug, Clone)]
+pub struct PoolSpawner {
+    sender: Sender<BlockingOperation>,
+}
+
+impl PoolSpawner {
+    /// Starts pool with specified amount of threads, at least one.
+    pub fn new(threads: usize) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        for _ in 0..threads.max(1) {
+            let receiver = receiver.clone();
+            thread::spawn(move || run_operations(&receiver));
+        }
+
+        PoolSpawner { sender }
+    }
+
+    /// Returns pool shared by providers created without spawner, which has thread
+    /// for every available CPU.
+    pub fn shared() -> Self {
+        static SHARED_POOL: OnceLock<PoolSpawner> = OnceLock::new();
+
+        SHARED_POOL
+            .get_or_init(|| {
+                let threads = thread::available_parallelism().map_or(4, NonZeroUsize::get);
+
+                PoolSpawner::new(threads)
+            })
+            .clone()
+    }
+}
+
+impl BlockingSpawner for PoolSpawner {
+    fn spawn_blocking(&self, operation: BlockingOperation) {
+        // Threads only exit after every sender is dropped, so operation is never lost.
+        let _ = self.sender.send(operation);
+    }
+}
+
+/// Runs operations of pool until every sender is dropped.
+fn run_operations(receiver: &Mutex<Receiver<BlockingOperation>>) {
+    loop {
+        // Lock is only held while waiting for operation, not while running it.
+        let operation = match receiver.lock() {
+            Ok(receiver) => receiver.recv(),
+            Err(_) => return,
+        };
+
+        match operation {
+            Ok(operation) => operation(),
+            Err(_) => return,
+        }
+    }
+}
+
+/// Spawner which runs operations on blocking thread pool of tokio runtime.
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone)]
+pub struct TokioSpawner {
+    handle: tokio::runtime::Handle,
+}
+
+#[cfg(feature = "tokio")]
+impl TokioSpawner {
+    pub fn new(handle: tokio::runtime::Handle) -> Self {
+        TokioSpawner { handle }
+    }
+
+    /// Returns spawner of runtime in which context it is called.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of tokio runtime.
+    pub fn current() -> Self {
+        TokioSpawner::new(tokio::runtime::Handle::current())
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl BlockingSpawner for TokioSpawner {
+    fn spawn_blocking(&self, operation: BlockingOperation) {
+        // Operation reports its output through future, so join handle isn't needed.
+        drop(self.handle.spawn_blocking(operation));
+    }
+}
+
+/// Spawner which runs operations on blocking thread pool shared by smol and
+/// async-std.
+#[cfg(feature = "smol")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SmolSpawner;
+
+#[cfg(feature = "smol")]
+impl BlockingSpawner for SmolSpawner {
+    fn spawn_blocking(&self, operation: BlockingOperation) {
+        blocking::unblock(operation).detach();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::async_provider::runtime::PoolSpawner;
+    use crate::async_provider::tests::block_on;
+    use crate::async_provider::AsyncAnvilChunkProvider;
+
+    #[test]
+    fn test_pool_spawner() {
+        let chunk_provider =
+            AsyncAnvilChunkProvider::with_spawner("test/region", PoolSpawner::new(2));
+
+        // More operations than threads wait in queue.
+        let futures: Vec<_> = (0..8).map(|_| chunk_provider.load_chunk(4, 2)).collect();
+
+        for future in futures {
+            let chunk_compound_tag = block_on(future).unwrap();
+            let level_compound_tag = chunk_compound_tag.get_compound_tag("Level").unwrap();
+
+            assert_eq!(level_compound_tag.get_i32("xPos").unwrap(), 4);
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn test_tokio_spawner() {
+        use crate::async_provider::runtime::TokioSpawner;
+        use crate::async_provider::AsyncAnvilChunkProvider;
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        let chunk_compound_tag = runtime.block_on(async {
+            let chunk_provider =
+                AsyncAnvilChunkProvider::with_spawner("test/region", TokioSpawner::current());
+
+            chunk_provider.load_chunk(4, 2).await.unwrap()
+        });
+
+        let level_compound_tag = chunk_compound_tag.get_compound_tag("Level").unwrap();
+        assert_eq!(level_compound_tag.get_i32("xPos").unwrap(), 4);
+    }
+
+    #[cfg(feature = "smol")]
+    #[test]
+    fn test_smol_spawner() {
+        use crate::async_provider::runtime::SmolSpawner;
+        use crate::async_provider::tests::block_on;
+        use crate::async_provider::AsyncAnvilChunkProvider;
+
+        let chunk_provider = AsyncAnvilChunkProvider::with_spawner("test/region", SmolSpawner);
+        let chunk_positions = block_on(chunk_provider.chunk_positions()).unwrap();
+
+        assert!(chunk_positions.contains(&(4, 2)));
+    }
+}
@@ -0,0 +1,241 @@
+//! Streams of all chunks of async providers.
+//!
+//! Stream loads a configurable amount of chunks ahead of consumer and no more, so slow
+//! consumer holds back loading. With `futures` feature [`ChunkStream`] implements
+//! `Stream` trait of `futures` crate, so stream combinators can be used with it.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::async_provider::AsyncAnvilChunkProvider;
+//!
+//! async fn count_chunks(chunk_provider: &AsyncAnvilChunkProvider) -> usize {
+//!     let mut chunk_stream = chunk_provider.chunk_stream(4);
+//!     let mut chunk_count = 0;
+//!
+//!     while let Some(result) = chunk_stream.next_chunk().await {
+//!         let (chunk_x, chunk_z, _chunk_compound_tag) = result.unwrap();
+//!         println!("Loaded chunk {} {}", chunk_x, chunk_z);
+//!         chunk_count += 1;
+//!     }
+//!
+//!     chunk_count
+//! }
+//! ```
+use crate::async_provider::{AsyncAnvilChunkProvider, AsyncZipChunkProvider, BlockingFuture};
+use crate::ChunkLoadError;
+use nbt::CompoundTag;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::vec;
+
+/// Item of chunk stream, chunk position with compound tag.
+pub type ChunkStreamItem = Result<(i32, i32, CompoundTag), ChunkLoadError>;
+
+type LoadChunk =
+    Box<dyn Fn(i32, i32) -> BlockingFuture<Result<CompoundTag, ChunkLoadError>> + Send>;
+
+/// Stream of chunks in ascending order of position.
+pub struct ChunkStream {
+    chunk_positions: ChunkPositions,
+    /// Chunks which are being loaded in order they will be yielded.
+    pending: VecDeque<PendingChunk>,
+    load_chunk: LoadChunk,
+    /// Maximum amount of pending chunks.
+    concurrency: usize,
+}
+
+enum ChunkPositions {
+    Listing(BlockingFuture<Result<Vec<(i32, i32)>, io::Error>>),
+    Listed(vec::IntoIter<(i32, i32)>),
+}
+
+struct PendingChunk {
+    chunk_x: i32,
+    chunk_z: i32,
+    future: BlockingFuture<Result<CompoundTag, ChunkLoadError>>,
+}
+
+impl AsyncAnvilChunkProvider {
+    /// Returns stream of all chunks of folder loading up to `concurrency` chunks
+    /// ahead of consumer.
+    pub fn chunk_stream(&self, concurrency: usize) -> ChunkStream {
+        let chunk_provider = self.clone();
+
+        ChunkStream::new(
+            self.chunk_positions(),
+            Box::new(move |chunk_x, chunk_z| chunk_provider.load_chunk(chunk_x, chunk_z)),
+            concurrency,
+        )
+    }
+}
+
+impl AsyncZipChunkProvider {
+    /// Returns stream of all chunks of archive region folder loading up to
+    /// `concurrency` chunks ahead of consumer.
+    pub fn chunk_stream(&self, concurrency: usize) -> ChunkStream {
+        let chunk_provider = self.clone();
+
+        ChunkStream::new(
+            self.chunk_positions(),
+            Box::new(move |chunk_x, chunk_z| chunk_provider.load_chunk(chunk_x, chunk_z)),
+            concurrency,
+        )
+    }
+}
+
+impl ChunkStream {
+    fn new(
+        chunk_positions: BlockingFuture<Result<Vec<(i32, i32)>, io::Error>>,
+        load_chunk: LoadChunk,
+        concurrency: usize,
+    ) -> Self {
+        ChunkStream {
+            chunk_positions: ChunkPositions::Listing(chunk_positions),
+            pending: VecDeque::new(),
+            load_chunk,
+            concurrency: concurrency.max(1),
+        }
+    }
+
+    /// Polls next chunk, none when all chunks were yielded.
+    ///
+    /// Error of listing chunks is yielded once and ends stream.
+    pub fn poll_next(&mut self, context: &mut Context<'_>) -> Poll<Option<ChunkStreamItem>> {
+        if let ChunkPositions::Listing(future) = &mut self.chunk_positions {
+            let chunk_positions = match Pin::new(future).poll(context) {
+                Poll::Ready(Ok(chunk_positions)) => chunk_positions,
+                Poll::Ready(Err(io_error)) => {
+                    self.chunk_positions = ChunkPositions::Listed(Vec::new().into_iter());
+                    return Poll::Ready(Some(Err(ChunkLoadError::from(io_error))));
+                }
+                Poll::Pending => return Poll::Pending,
+            };
+
+            self.chunk_positions = ChunkPositions::Listed(chunk_positions.into_iter());
+        }
+
+        if let ChunkPositions::Listed(chunk_positions) = &mut self.chunk_positions {
+            while self.pending.len() < self.concurrency {
+                let (chunk_x, chunk_z) = match chunk_positions.next() {
+                    Some(chunk_position) => chunk_position,
+                    None => break,
+                };
+
+                self.pending.push_back(PendingChunk {
+                    chunk_x,
+                    chunk_z,
+                    future: (self.load_chunk)(chunk_x, chunk_z),
+                });
+            }
+        }
+
+        let pending_chunk = match self.pending.front_mut() {
+            Some(pending_chunk) => pending_chunk,
+            None => return Poll::Ready(None),
+        };
+
+        let result = match Pin::new(&mut pending_chunk.future).poll(context) {
+            Poll::Ready(result) => result,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        let (chunk_x, chunk_z) = (pending_chunk.chunk_x, pending_chunk.chunk_z);
+        self.pending.pop_front();
+
+        Poll::Ready(Some(
+            result.map(|chunk_compound_tag| (chunk_x, chunk_z, chunk_compound_tag)),
+        ))
+    }
+
+    /// Returns future of next chunk, none when all chunks were yielded.
+    pub fn next_chunk(&mut self) -> ChunkStreamNext<'_> {
+        ChunkStreamNext { chunk_stream: self }
+    }
+}
+
+/// Future returned by [`ChunkStream::next_chunk`].
+pub struct ChunkStreamNext<'a> {
+    chunk_stream: &'a mut ChunkStream,
+}
+
+impl Future for ChunkStreamNext<'_> {
+    type Output = Option<ChunkStreamItem>;
+
+    fn poll(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Self::Output> {
+        self.chunk_stream.poll_next(context)
+    }
+}
+
+#[cfg(feature = "futures")]
+impl futures_core::Stream for ChunkStream {
+    type Item = ChunkStreamItem;
+
+    fn poll_next(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        ChunkStream::poll_next(self.get_mut(), context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::async_provider::tests::block_on;
+    use crate::async_provider::AsyncAnvilChunkProvider;
+    use crate::AnvilChunkProvider;
+
+    #[test]
+    fn test_chunk_stream() {
+        let async_chunk_provider = AsyncAnvilChunkProvider::new("test/region");
+        let mut chunk_stream = async_chunk_provider.chunk_stream(3);
+
+        let chunk_positions = block_on(async {
+            let mut chunk_positions = Vec::new();
+
+            while let Some(result) = chunk_stream.next_chunk().await {
+                let (chunk_x, chunk_z, chunk_compound_tag) = result.unwrap();
+                let level_compound_tag = chunk_compound_tag.get_compound_tag("Level").unwrap();
+
+                assert_eq!(level_compound_tag.get_i32("xPos").unwrap(), chunk_x);
+                chunk_positions.push((chunk_x, chunk_z));
+            }
+
+            chunk_positions
+        });
+
+        assert_eq!(
+            chunk_positions,
+            AnvilChunkProvider::new("test/region")
+                .chunk_positions()
+                .unwrap()
+        );
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn test_futures_stream() {
+        use futures_core::Stream;
+        use std::future;
+        use std::pin::Pin;
+
+        let async_chunk_provider = AsyncAnvilChunkProvider::new("test/region");
+        let mut chunk_stream = async_chunk_provider.chunk_stream(2);
+        let mut chunk_count = 0;
+
+        while let Some(result) = block_on(future::poll_fn(|context| {
+            Stream::poll_next(Pin::new(&mut chunk_stream), context)
+        })) {
+            assert!(result.is_ok());
+            chunk_count += 1;
+        }
+
+        assert_eq!(
+            chunk_count,
+            AnvilChunkProvider::new("test/region")
+                .chunk_positions()
+                .unwrap()
+                .len()
+        );
+    }
+}
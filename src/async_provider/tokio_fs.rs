@@ -0,0 +1,639 @@
+//! Chunk provider of region folder reading files with `tokio::fs`.
+//!
+//! [`TokioAnvilChunkProvider`] doesn't run operations on spawner, its futures read
+//! region files through tokio file handles and must be awaited inside of tokio runtime.
+//! Writes run on blocking threads of tokio with the same region code as blocking
+//! providers, holding advisory lock of region file, so they don't interleave with
+//! writes of other processes using this crate. Open region files are cached like by
+//! [`AsyncAnvilChunkProvider`], up to [`REGION_CACHE_CAPACITY`] least recently used
+//! ones, and every one of them is locked separately, so operations on different region
+//! files run concurrently while operations on the same region file run one at a time.
+//!
+//! [`AsyncAnvilChunkProvider`]: crate::async_provider::AsyncAnvilChunkProvider
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::async_provider::tokio_fs::TokioAnvilChunkProvider;
+//!
+//! let runtime = tokio::runtime::Builder::new_current_thread()
+//!     .build()
+//!     .unwrap();
+//!
+//! let chunk_compound_tag = runtime.block_on(async {
+//!     let chunk_provider = TokioAnvilChunkProvider::new("test/region");
+//!
+//!     chunk_provider.load_chunk(4, 2).await.unwrap()
+//! });
+//!
+//! let level_compound_tag = chunk_compound_tag.get_compound_tag("Level").unwrap();
+//! assert_eq!(level_compound_tag.get_i32("xPos").unwrap(), 4);
+//! ```
+use crate::async_provider::{AsyncChunkProvider, REGION_CACHE_CAPACITY};
+use crate::buffer::decode_chunk;
+use crate::region_slice::read_sectors_chunk_data;
+use crate::snapshot::break_hard_link;
+use crate::{
+    encode_chunk, is_same_metadata, region_position, AnvilChunkMetadata, AnvilRegion,
+    ChunkLoadError, ChunkSaveError, REGION_CHUNKS, REGION_HEADER_BYTES_LENGTH,
+    REGION_SECTOR_BYTES_LENGTH,
+};
+use nbt::CompoundTag;
+use std::future::Future;
+use std::io;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::sync::Mutex;
+
+/// Open region files of folder, most recently used last.
+type TokioRegionCache = Vec<((i32, i32), Arc<Mutex<TokioRegion>>)>;
+
+/// Provider of chunks of region folder with file I/O of tokio.
+#[derive(Clone)]
+pub struct TokioAnvilChunkProvider {
+    /// Folder where region files located.
+    folder_path: Arc<PathBuf>,
+    /// Held while region file is looked up or opened.
+    region_cache: Arc<Mutex<TokioRegionCache>>,
+}
+
+impl TokioAnvilChunkProvider {
+    pub fn new(folder_path: impl AsRef<Path>) -> Self {
+        TokioAnvilChunkProvider {
+            folder_path: Arc::new(folder_path.as_ref().to_path_buf()),
+            region_cache: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn folder_path(&self) -> &Path {
+        &self.folder_path
+    }
+
+    /// Loads chunk from the specified coordinates.
+    pub async fn load_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> Result<CompoundTag, ChunkLoadError> {
+        let region_position = (chunk_x >> 5, chunk_z >> 5);
+
+        let result = match self.region(region_position, false).await {
+            Ok(Some(region)) => {
+                let mut region = region.lock().await;
+
+                region
+                    .read_chunk((chunk_x & 31) as u8, (chunk_z & 31) as u8)
+                    .await
+            }
+            Ok(None) => {
+                return Err(ChunkLoadError::RegionNotFound {
+                    region_x: region_position.0,
+                    region_z: region_position.1,
+                })
+            }
+            Err(io_error) => Err(io_error.into()),
+        };
+
+        result.map_err(|chunk_load_error| {
+            chunk_load_error.with_context(&self.region_path(region_position), chunk_x, chunk_z)
+        })
+    }
+
+    /// Saves chunk data to the specified coordinates.
+    pub async fn save_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        chunk_compound_tag: CompoundTag,
+    ) -> Result<(), ChunkSaveError> {
+        let buffer = encode_chunk(&chunk_compound_tag)?;
+        let region_position = (chunk_x >> 5, chunk_z >> 5);
+
+        let result = match self.region(region_position, true).await {
+            // Region is always opened when it may be created.
+            Ok(region) => {
+                let region = region.unwrap();
+                let mut region = region.lock().await;
+
+                let (region_chunk_x, region_chunk_z) = ((chunk_x & 31) as u8, (chunk_z & 31) as u8);
+
+                region
+                    .write(move |region| {
+                        region.write_chunk_buffer(region_chunk_x, region_chunk_z, &buffer)
+                    })
+                    .await
+            }
+            Err(io_error) => Err(io_error.into()),
+        };
+
+        result.map_err(|chunk_save_error| {
+            chunk_save_error.with_context(&self.region_path(region_position), chunk_x, chunk_z)
+        })
+    }
+
+    /// Deletes chunk at the specified coordinates, returns false if chunk is not
+    /// present.
+    pub async fn delete_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<bool, ChunkSaveError> {
+        let region_position = (chunk_x >> 5, chunk_z >> 5);
+
+        let result = match self.region(region_position, false).await {
+            Ok(Some(region)) => {
+                let mut region = region.lock().await;
+
+                let (region_chunk_x, region_chunk_z) = ((chunk_x & 31) as u8, (chunk_z & 31) as u8);
+
+                region
+                    .write(move |region| region.delete_chunk(region_chunk_x, region_chunk_z))
+                    .await
+            }
+            Ok(None) => Ok(false),
+            Err(io_error) => Err(io_error),
+        };
+
+        result.map_err(|io_error| {
+            let region_path = self.region_path(region_position);
+
+            ChunkSaveError::from(io_error).with_context(&region_path, chunk_x, chunk_z)
+        })
+    }
+
+    /// Returns coordinates of all chunks stored in the folder region files.
+    pub async fn chunk_positions(&self) -> Result<Vec<(i32, i32)>, io::Error> {
+        let mut chunk_positions = Vec::new();
+
+        let mut entries = match fs::read_dir(&*self.folder_path).await {
+            Ok(entries) => entries,
+            Err(io_error) if io_error.kind() == io::ErrorKind::NotFound => {
+                return Ok(chunk_positions)
+            }
+            Err(io_error) => return Err(io_error),
+        };
+
+        while let Some(entry) = entries.next_entry().await? {
+            let (region_x, region_z) = match entry.file_name().to_str().and_then(region_position) {
+                Some(region_position) => region_position,
+                None => continue,
+            };
+
+            let mut header = Vec::with_capacity(REGION_HEADER_BYTES_LENGTH as usize);
+            File::open(entry.path())
+                .await?
+                .take(REGION_HEADER_BYTES_LENGTH)
+                .read_to_end(&mut header)
+                .await?;
+
+            let region_headers = AnvilRegion::open_headers_only(&mut header.as_slice())?;
+
+            for (region_chunk_x, region_chunk_z) in region_headers.chunk_positions() {
+                let chunk_x = (region_x << 5) + region_chunk_x as i32;
+                let chunk_z = (region_z << 5) + region_chunk_z as i32;
+
+                chunk_positions.push((chunk_x, chunk_z));
+            }
+        }
+
+        chunk_positions.sort_unstable();
+
+        Ok(chunk_positions)
+    }
+
+    /// Closes all cached region files.
+    pub async fn close_regions(&self) {
+        self.region_cache.lock().await.clear();
+    }
+
+    /// Closes cached region file, so the next operation reads its header again.
+    ///
+    /// Header of cached region file is read again only before writes, reads after
+    /// other process modified region file need invalidation.
+    pub async fn invalidate(&self, region_x: i32, region_z: i32) {
+        self.region_cache
+            .lock()
+            .await
+            .retain(|(region_position, _)| *region_position != (region_x, region_z));
+    }
+
+    /// Syncs cached region files to disk and closes them, returns the first error of
+    /// syncing.
+    pub async fn close(self) -> Result<(), io::Error> {
+        let regions = std::mem::take(&mut *self.region_cache.lock().await);
+        let mut result = Ok(());
+
+        for (_, region) in regions {
+            let sync_result = region.lock().await.sync().await;
+            result = result.and(sync_result);
+        }
+
+        result
+    }
+
+    fn region_path(&self, region_position: (i32, i32)) -> PathBuf {
+        let (region_x, region_z) = region_position;
+
+        self.folder_path
+            .join(format!("r.{}.{}.mca", region_x, region_z))
+    }
+
+    /// Returns open region file, none if it doesn't exist and must not be created.
+    async fn region(
+        &self,
+        region_position: (i32, i32),
+        write: bool,
+    ) -> Result<Option<Arc<Mutex<TokioRegion>>>, io::Error> {
+        let mut regions = self.region_cache.lock().await;
+
+        let index = regions
+            .iter()
+            .position(|(cached_position, _)| *cached_position == region_position);
+
+        let region = match index {
+            Some(index) => regions.remove(index).1,
+            None => {
+                let region_path = self.region_path(region_position);
+
+                if write {
+                    fs::create_dir_all(&*self.folder_path).await?;
+                } else if !fs::try_exists(&region_path).await? {
+                    return Ok(None);
+                }
+
+                Arc::new(Mutex::new(TokioRegion::open(region_path).await?))
+            }
+        };
+
+        if regions.len() == REGION_CACHE_CAPACITY {
+            regions.remove(0);
+        }
+
+        regions.push((region_position, region.clone()));
+
+        Ok(Some(region))
+    }
+}
+
+impl AsyncChunkProvider for TokioAnvilChunkProvider {
+    type LoadError = ChunkLoadError;
+    type SaveError = ChunkSaveError;
+
+    fn load_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> impl Future<Output = Result<CompoundTag, ChunkLoadError>> + Send {
+        TokioAnvilChunkProvider::load_chunk(self, chunk_x, chunk_z)
+    }
+
+    fn save_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        chunk_compound_tag: CompoundTag,
+    ) -> impl Future<Output = Result<(), ChunkSaveError>> + Send {
+        TokioAnvilChunkProvider::save_chunk(self, chunk_x, chunk_z, chunk_compound_tag)
+    }
+
+    fn delete_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> impl Future<Output = Result<bool, ChunkSaveError>> + Send {
+        TokioAnvilChunkProvider::delete_chunk(self, chunk_x, chunk_z)
+    }
+
+    fn chunk_positions(&self) -> impl Future<Output = Result<Vec<(i32, i32)>, io::Error>> + Send {
+        TokioAnvilChunkProvider::chunk_positions(self)
+    }
+}
+
+/// Region file opened with tokio.
+struct TokioRegion {
+    path: PathBuf,
+    /// File from which chunks are read.
+    file: File,
+    /// Array of chunks metadata.
+    chunks_metadata: [AnvilChunkMetadata; REGION_CHUNKS],
+    /// Region file written on blocking threads, none until the first write.
+    region: Option<AnvilRegion>,
+}
+
+impl TokioRegion {
+    async fn open(path: PathBuf) -> Result<Self, io::Error> {
+        let file = Self::open_file(&path).await?;
+
+        let mut region = TokioRegion {
+            path,
+            file,
+            chunks_metadata: [Default::default(); REGION_CHUNKS],
+            region: None,
+        };
+
+        region.read_header().await?;
+
+        Ok(region)
+    }
+
+    async fn open_file(path: &Path) -> Result<File, io::Error> {
+        OpenOptions::new()
+            .write(true)
+            .read(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .await
+    }
+
+    async fn read_header(&mut self) -> Result<(), io::Error> {
+        // If necessary, extend the file length to the length of the header.
+        if REGION_HEADER_BYTES_LENGTH > self.file.metadata().await?.len() {
+            self.file.set_len(REGION_HEADER_BYTES_LENGTH).await?;
+        }
+
+        let mut header = vec![0; REGION_HEADER_BYTES_LENGTH as usize];
+        self.file.seek(SeekFrom::Start(0)).await?;
+        self.file.read_exact(&mut header).await?;
+
+        self.chunks_metadata = AnvilRegion::read_header(&mut header.as_slice())?;
+
+        Ok(())
+    }
+
+    /// Runs write operation on blocking thread holding advisory lock of region file,
+    /// see [`AnvilRegion::write_locked`].
+    ///
+    /// Region file is separated from snapshot links first. Header written by operation
+    /// is used by following reads, which go to new file if region file was replaced.
+    async fn write<T, E>(
+        &mut self,
+        operation: impl FnOnce(&mut AnvilRegion) -> Result<T, E> + Send + 'static,
+    ) -> Result<T, E>
+    where
+        T: Send + 'static,
+        E: From<io::Error> + Send + 'static,
+    {
+        let path = self.path.clone();
+        let region = self.region.take();
+
+        let (region, result) = tokio::task::spawn_blocking(move || {
+            let mut region = match region {
+                Some(region) => region,
+                None => AnvilRegion::new(&path)?,
+            };
+
+            break_hard_link(&path)?;
+            let result = region.write_locked(operation);
+
+            Ok::<_, io::Error>((region, result))
+        })
+        .await
+        .map_err(io::Error::other)??;
+
+        self.chunks_metadata = region.chunks_metadata;
+        self.region = Some(region);
+
+        // Handle refers to file which was replaced by private copy or other process.
+        let file_metadata = self.file.metadata().await?;
+
+        if !is_same_metadata(&file_metadata, &fs::metadata(&self.path).await?) {
+            self.file = Self::open_file(&self.path).await?;
+        }
+
+        result
+    }
+
+    /// Syncs written data of region file to disk.
+    async fn sync(&mut self) -> Result<(), io::Error> {
+        match self.region.take() {
+            Some(region) => tokio::task::spawn_blocking(move || region.file.sync_all())
+                .await
+                .map_err(io::Error::other)?,
+            None => Ok(()),
+        }
+    }
+
+    async fn read_chunk(
+        &mut self,
+        chunk_x: u8,
+        chunk_z: u8,
+    ) -> Result<CompoundTag, ChunkLoadError> {
+        let metadata = self.chunks_metadata[AnvilRegion::metadata_index(chunk_x, chunk_z)];
+
+        if metadata.is_empty() {
+            return Err(ChunkLoadError::ChunkNotFound {
+                chunk_x: chunk_x as i32,
+                chunk_z: chunk_z as i32,
+            });
+        }
+
+        let seek_offset = metadata.sector_index as u64 * REGION_SECTOR_BYTES_LENGTH as u64;
+        let sectors_length = metadata.sectors as u64 * REGION_SECTOR_BYTES_LENGTH as u64;
+        let mut sectors = Vec::with_capacity(sectors_length as usize);
+
+        self.file.seek(SeekFrom::Start(seek_offset)).await?;
+        (&mut self.file)
+            .take(sectors_length)
+            .read_to_end(&mut sectors)
+            .await?;
+
+        let (compression_scheme, compressed_buffer) = read_sectors_chunk_data(&sectors, metadata)?;
+
+        decode_chunk(compression_scheme, compressed_buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::async_provider::tests::copy_chunk_async;
+    use crate::async_provider::tokio_fs::TokioAnvilChunkProvider;
+    use crate::async_provider::REGION_CACHE_CAPACITY;
+    use crate::snapshot::snapshot;
+    use crate::{AnvilChunkProvider, ChunkLoadError};
+    use nbt::CompoundTag;
+    use std::fs;
+    use std::future::Future;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn test_load_chunk() {
+        let chunk_provider = TokioAnvilChunkProvider::new("test/region");
+
+        block_on(async {
+            let chunk_compound_tag = chunk_provider.load_chunk(4, 2).await.unwrap();
+            let level_compound_tag = chunk_compound_tag.get_compound_tag("Level").unwrap();
+
+            assert_eq!(level_compound_tag.get_i32("xPos").unwrap(), 4);
+            assert_eq!(level_compound_tag.get_i32("zPos").unwrap(), 2);
+
+            match chunk_provider.load_chunk(15, 14).await {
+                Err(ChunkLoadError::ChunkNotFound { chunk_x, chunk_z }) => {
+                    assert_eq!(chunk_x, 15);
+                    assert_eq!(chunk_z, 14);
+                }
+                result => panic!("Expected `ChunkNotFound` but got `{:?}`", result),
+            }
+
+            match chunk_provider.load_chunk(100, 100).await {
+                Err(ChunkLoadError::RegionNotFound { region_x, region_z }) => {
+                    assert_eq!(region_x, 3);
+                    assert_eq!(region_z, 3);
+                }
+                result => panic!("Expected `RegionNotFound` but got `{:?}`", result),
+            }
+
+            assert_eq!(
+                chunk_provider.chunk_positions().await.unwrap(),
+                AnvilChunkProvider::new("test/region")
+                    .chunk_positions()
+                    .unwrap()
+            );
+        });
+    }
+
+    #[test]
+    fn test_save_and_delete_chunk() {
+        let temp_dir = TempDir::new().unwrap();
+        let folder_path = temp_dir.path().join("region");
+        let fixture_chunk_provider = TokioAnvilChunkProvider::new("test/region");
+        let chunk_provider = TokioAnvilChunkProvider::new(&folder_path);
+
+        block_on(async {
+            for &(chunk_x, chunk_z) in &[(4, 2), (5, 2), (4, 40)] {
+                let chunk_compound_tag = fixture_chunk_provider.load_chunk(4, 2).await.unwrap();
+
+                chunk_provider
+                    .save_chunk(chunk_x, chunk_z, chunk_compound_tag)
+                    .await
+                    .unwrap();
+            }
+
+            assert_eq!(
+                chunk_provider.chunk_positions().await.unwrap(),
+                vec![(4, 2), (4, 40), (5, 2)]
+            );
+            assert!(chunk_provider.delete_chunk(5, 2).await.unwrap());
+            assert!(!chunk_provider.delete_chunk(5, 2).await.unwrap());
+            assert!(!chunk_provider.delete_chunk(100, 100).await.unwrap());
+        });
+
+        // Blocking provider reads what was written.
+        let blocking_chunk_provider = AnvilChunkProvider::new(folder_path.to_str().unwrap());
+        let chunk_compound_tag = blocking_chunk_provider.load_chunk(4, 40).unwrap();
+        let level_compound_tag = chunk_compound_tag.get_compound_tag("Level").unwrap();
+
+        assert_eq!(level_compound_tag.get_i32("xPos").unwrap(), 4);
+        assert_eq!(
+            blocking_chunk_provider.chunk_positions().unwrap(),
+            vec![(4, 2), (4, 40)]
+        );
+
+        block_on(chunk_provider.close()).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_saves() {
+        let temp_dir = TempDir::new().unwrap();
+        let fixture_chunk_provider = TokioAnvilChunkProvider::new("test/region");
+        let chunk_provider = TokioAnvilChunkProvider::new(temp_dir.path());
+
+        // File operations of tasks run on blocking threads, so they interleave.
+        block_on(async {
+            let chunk_compound_tag = fixture_chunk_provider.load_chunk(4, 2).await.unwrap();
+            let mut tasks = Vec::new();
+
+            // More regions than cache keeps open and several chunks of every region.
+            for region_x in 0..REGION_CACHE_CAPACITY as i32 + 2 {
+                for chunk_z in 0..4 {
+                    let chunk_provider = chunk_provider.clone();
+                    let chunk_compound_tag = chunk_compound_tag.clone();
+
+                    tasks.push(tokio::spawn(async move {
+                        chunk_provider
+                            .save_chunk(region_x << 5, chunk_z, chunk_compound_tag)
+                            .await
+                    }));
+                }
+            }
+
+            for task in tasks {
+                task.await.unwrap().unwrap();
+            }
+        });
+
+        let blocking_chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+        assert_eq!(
+            blocking_chunk_provider.chunk_positions().unwrap().len(),
+            (REGION_CACHE_CAPACITY + 2) * 4
+        );
+
+        for (chunk_x, chunk_z) in blocking_chunk_provider.chunk_positions().unwrap() {
+            assert!(blocking_chunk_provider.load_chunk(chunk_x, chunk_z).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_write_waits_for_region_lock() {
+        let temp_dir = TempDir::new().unwrap();
+        let chunk_provider = TokioAnvilChunkProvider::new(temp_dir.path());
+        block_on(chunk_provider.save_chunk(4, 2, CompoundTag::new())).unwrap();
+
+        // Lock of other process.
+        let region_file = fs::File::open(temp_dir.path().join("r.0.0.mca")).unwrap();
+        region_file.lock().unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                block_on(chunk_provider.save_chunk(5, 2, CompoundTag::new())).unwrap();
+                sender.send(()).unwrap();
+            });
+
+            assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+            region_file.unlock().unwrap();
+            receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+        });
+
+        assert!(block_on(chunk_provider.load_chunk(5, 2)).is_ok());
+    }
+
+    #[test]
+    fn test_save_doesnt_change_snapshot() {
+        let temp_dir = TempDir::new().unwrap();
+        let folder_path = temp_dir.path().join("region");
+        let snapshot_path = temp_dir.path().join("snapshot");
+        let fixture_chunk_provider = TokioAnvilChunkProvider::new("test/region");
+        let chunk_provider = TokioAnvilChunkProvider::new(&folder_path);
+
+        block_on(async {
+            copy_chunk_async(&fixture_chunk_provider, &chunk_provider, 4, 2)
+                .await
+                .unwrap();
+
+            // Cached region file becomes shared with snapshot.
+            snapshot(&folder_path, &snapshot_path).unwrap();
+            let snapshot_buffer = fs::read(snapshot_path.join("r.0.0.mca")).unwrap();
+
+            copy_chunk_async(&fixture_chunk_provider, &chunk_provider, 5, 2)
+                .await
+                .unwrap();
+
+            assert_eq!(
+                fs::read(snapshot_path.join("r.0.0.mca")).unwrap(),
+                snapshot_buffer
+            );
+            assert!(chunk_provider.load_chunk(5, 2).await.is_ok());
+        });
+    }
+}
@@ -0,0 +1,579 @@
+//! Incremental backups of world folders stored as generations.
+//!
+//! Every backup creates new generation folder with manifest listing all files of world
+//! with their content hashes. Only files whose hash differs from the previous generation
+//! are stored, unchanged files point to generation which stored them. Region files of
+//! areas nobody visited since the last backup aren't copied again, so frequent backups
+//! of large worlds stay small.
+//!
+//! ```text
+//! backups/
+//!     000001/
+//!         manifest.txt
+//!         files/level.dat
+//!         files/region/r.0.0.mca
+//!     000002/
+//!         manifest.txt
+//!         files/region/r.0.0.mca
+//! ```
+//!
+//! Generations depend on files of older ones, so only the whole folder of backups can
+//! be removed.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::backup::IncrementalBackup;
+//! use anvil_region::relocate::copy_chunk;
+//! use anvil_region::AnvilChunkProvider;
+//! use tempfile::TempDir;
+//!
+//! let world_dir = TempDir::new().unwrap();
+//! let backup_dir = TempDir::new().unwrap();
+//! let restore_dir = TempDir::new().unwrap();
+//! let region_folder = world_dir.path().join("region");
+//!
+//! let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+//! let chunk_provider = AnvilChunkProvider::new(region_folder.to_str().unwrap());
+//! copy_chunk(&fixture_chunk_provider, (4, 2), &chunk_provider, (4, 2)).unwrap();
+//!
+//! let backup = IncrementalBackup::new(backup_dir.path());
+//! let backup_report = backup.backup(world_dir.path()).unwrap();
+//!
+//! chunk_provider.delete_chunk(4, 2).unwrap();
+//! backup.restore(backup_report.generation, restore_dir.path()).unwrap();
+//!
+//! let restored_region_folder = restore_dir.path().join("region");
+//! let restored_chunk_provider = AnvilChunkProvider::new(restored_region_folder.to_str().unwrap());
+//!
+//! assert!(restored_chunk_provider.load_chunk(4, 2).is_ok());
+//! ```
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{fs, io};
+
+/// Manifest file of generation folder.
+pub const MANIFEST_FILE: &str = "manifest.txt";
+
+/// Folder of generation with files stored by it.
+const FILES_FOLDER: &str = "files";
+/// Lock file of running game which must not be backed up.
+const SESSION_LOCK_FILE: &str = "session.lock";
+
+/// Offset basis and prime of 64-bit FNV-1a.
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Possible errors while backing up or restoring world.
+#[derive(Debug)]
+pub enum BackupError {
+    /// Backup folder is world folder or is inside of it.
+    TargetIsSource,
+    /// Generation has no complete manifest.
+    GenerationNotFound { generation: u32 },
+    /// Line of manifest can't be parsed.
+    InvalidManifest { generation: u32, line: usize },
+    /// Stored file doesn't match hash of manifest.
+    HashMismatch { generation: u32, path: PathBuf },
+    /// File can't be read or written.
+    IoError { io_error: io::Error },
+}
+
+impl From<io::Error> for BackupError {
+    fn from(io_error: io::Error) -> Self {
+        BackupError::IoError { io_error }
+    }
+}
+
+/// File of world listed in manifest.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BackupEntry {
+    /// Path relative to world folder.
+    pub path: PathBuf,
+    /// FNV-1a hash of file contents.
+    pub hash: u64,
+    /// Length of file.
+    pub length: u64,
+    /// Generation which stored file.
+    pub stored_generation: u32,
+}
+
+/// Files of world at the time of backup.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BackupManifest {
+    pub generation: u32,
+    /// Seconds since Unix epoch when backup was made.
+    pub created_timestamp: u64,
+    /// Files sorted by path.
+    pub entries: Vec<BackupEntry>,
+}
+
+/// Result of backup.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BackupReport {
+    /// Generation which was created.
+    pub generation: u32,
+    /// Files which changed and were stored.
+    pub stored_files: usize,
+    /// Files equal to the previous generation.
+    pub unchanged_files: usize,
+    /// Length of stored files.
+    pub stored_bytes: u64,
+}
+
+/// Folder of backup generations of single world.
+#[derive(Debug, Clone)]
+pub struct IncrementalBackup {
+    folder_path: PathBuf,
+}
+
+impl IncrementalBackup {
+    pub fn new(folder_path: impl AsRef<Path>) -> Self {
+        IncrementalBackup {
+            folder_path: folder_path.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn folder_path(&self) -> &Path {
+        &self.folder_path
+    }
+
+    /// Returns sorted generations which have manifest.
+    ///
+    /// Generation left without manifest by interrupted backup is skipped.
+    pub fn generations(&self) -> Result<Vec<u32>, BackupError> {
+        let mut generations = Vec::new();
+
+        if !self.folder_path.exists() {
+            return Ok(generations);
+        }
+
+        for entry in fs::read_dir(&self.folder_path)? {
+            let path = entry?.path();
+
+            let generation = match path
+                .file_name()
+                .and_then(|file_name| file_name.to_str())
+                .and_then(|file_name| file_name.parse().ok())
+            {
+                Some(generation) => generation,
+                None => continue,
+            };
+
+            if path.join(MANIFEST_FILE).is_file() {
+                generations.push(generation);
+            }
+        }
+
+        generations.sort_unstable();
+
+        Ok(generations)
+    }
+
+    /// Reads manifest of generation.
+    pub fn manifest(&self, generation: u32) -> Result<BackupManifest, BackupError> {
+        let manifest_path = self.generation_path(generation).join(MANIFEST_FILE);
+
+        let manifest = match fs::read_to_string(manifest_path) {
+            Ok(manifest) => manifest,
+            Err(io_error) if io_error.kind() == io::ErrorKind::NotFound => {
+                return Err(BackupError::GenerationNotFound { generation });
+            }
+            Err(io_error) => return Err(io_error.into()),
+        };
+
+        parse_manifest(generation, &manifest)
+    }
+
+    /// Backs up world folder as new generation.
+    ///
+    /// Every file except `session.lock` is hashed and stored only if it's missing in
+    /// the latest generation or its hash or length differs. Manifest is written last,
+    /// so interrupted backup leaves no generation behind.
+    pub fn backup(&self, world_folder_path: &Path) -> Result<BackupReport, BackupError> {
+        if self.folder_path.starts_with(world_folder_path) {
+            return Err(BackupError::TargetIsSource);
+        }
+
+        let previous_manifest = match self.generations()?.last() {
+            Some(&generation) => Some(self.manifest(generation)?),
+            None => None,
+        };
+
+        let generation = previous_manifest
+            .as_ref()
+            .map_or(1, |manifest| manifest.generation + 1);
+
+        let generation_path = self.generation_path(generation);
+
+        // Files of interrupted backup with the same generation are replaced.
+        if generation_path.exists() {
+            fs::remove_dir_all(&generation_path)?;
+        }
+
+        let files_path = generation_path.join(FILES_FOLDER);
+        fs::create_dir_all(&files_path)?;
+
+        let mut paths = Vec::new();
+        world_files(world_folder_path, Path::new(""), &mut paths)?;
+        paths.sort_unstable();
+
+        let mut backup_report = BackupReport {
+            generation,
+            ..BackupReport::default()
+        };
+        let mut entries = Vec::with_capacity(paths.len());
+
+        let previous_entries: HashMap<_, _> = previous_manifest
+            .iter()
+            .flat_map(|manifest| &manifest.entries)
+            .map(|entry| (entry.path.as_path(), entry))
+            .collect();
+
+        for path in paths {
+            let data = fs::read(world_folder_path.join(&path))?;
+            let hash = fnv_hash(&data);
+            let length = data.len() as u64;
+
+            let previous_entry = previous_entries
+                .get(path.as_path())
+                .filter(|entry| entry.hash == hash && entry.length == length);
+
+            let stored_generation = match previous_entry {
+                Some(previous_entry) => {
+                    backup_report.unchanged_files += 1;
+                    previous_entry.stored_generation
+                }
+                None => {
+                    let stored_path = files_path.join(&path);
+
+                    if let Some(parent_path) = stored_path.parent() {
+                        fs::create_dir_all(parent_path)?;
+                    }
+
+                    // Stored data is the hashed data, even if file changes meanwhile.
+                    fs::write(stored_path, &data)?;
+
+                    backup_report.stored_files += 1;
+                    backup_report.stored_bytes += length;
+                    generation
+                }
+            };
+
+            entries.push(BackupEntry {
+                path,
+                hash,
+                length,
+                stored_generation,
+            });
+        }
+
+        let manifest = BackupManifest {
+            generation,
+            created_timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_secs()),
+            entries,
+        };
+
+        let temporary_manifest_path = generation_path.join("manifest.tmp");
+        fs::write(&temporary_manifest_path, format_manifest(&manifest))?;
+        fs::rename(temporary_manifest_path, generation_path.join(MANIFEST_FILE))?;
+
+        Ok(backup_report)
+    }
+
+    /// Restores files of generation into target folder and returns amount of files.
+    ///
+    /// Files of target which aren't in manifest are kept, so world should be restored
+    /// into empty folder. Every file is checked against its hash before it's written.
+    pub fn restore(
+        &self,
+        generation: u32,
+        target_folder_path: &Path,
+    ) -> Result<usize, BackupError> {
+        let manifest = self.manifest(generation)?;
+
+        for entry in &manifest.entries {
+            let data = fs::read(self.stored_path(entry))?;
+
+            if data.len() as u64 != entry.length || fnv_hash(&data) != entry.hash {
+                return Err(BackupError::HashMismatch {
+                    generation: entry.stored_generation,
+                    path: entry.path.clone(),
+                });
+            }
+
+            let target_path = target_folder_path.join(&entry.path);
+
+            if let Some(parent_path) = target_path.parent() {
+                fs::create_dir_all(parent_path)?;
+            }
+
+            fs::write(target_path, data)?;
+        }
+
+        Ok(manifest.entries.len())
+    }
+
+    /// Returns path of file of entry in generation which stored it.
+    pub(crate) fn stored_path(&self, entry: &BackupEntry) -> PathBuf {
+        self.generation_path(entry.stored_generation)
+            .join(FILES_FOLDER)
+            .join(&entry.path)
+    }
+
+    fn generation_path(&self, generation: u32) -> PathBuf {
+        self.folder_path.join(format!("{:06}", generation))
+    }
+}
+
+/// Collects paths of files of folder relative to world folder.
+fn world_files(
+    folder_path: &Path,
+    relative_path: &Path,
+    paths: &mut Vec<PathBuf>,
+) -> Result<(), io::Error> {
+    for entry in fs::read_dir(folder_path)? {
+        let path = entry?.path();
+
+        let file_name = match path.file_name() {
+            Some(file_name) => file_name,
+            None => continue,
+        };
+
+        if path.is_dir() {
+            world_files(&path, &relative_path.join(file_name), paths)?;
+        } else if file_name != SESSION_LOCK_FILE {
+            paths.push(relative_path.join(file_name));
+        }
+    }
+
+    Ok(())
+}
+
+/// Formats manifest as header line followed by line of hash, length, stored
+/// generation and path for every file. Paths use forward slashes on every platform.
+fn format_manifest(manifest: &BackupManifest) -> String {
+    let mut text = format!(
+        "generation {} created {}\n",
+        manifest.generation, manifest.created_timestamp
+    );
+
+    for entry in &manifest.entries {
+        let path: Vec<_> = entry
+            .path
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect();
+
+        let _ = writeln!(
+            text,
+            "{:016x} {} {} {}",
+            entry.hash,
+            entry.length,
+            entry.stored_generation,
+            path.join("/")
+        );
+    }
+
+    text
+}
+
+fn parse_manifest(generation: u32, text: &str) -> Result<BackupManifest, BackupError> {
+    let mut lines = text.lines();
+    let invalid_manifest = |line| BackupError::InvalidManifest { generation, line };
+
+    let header: Vec<_> = lines
+        .next()
+        .ok_or_else(|| invalid_manifest(1))?
+        .split(' ')
+        .collect();
+
+    let created_timestamp = match header.as_slice() {
+        ["generation", header_generation, "created", created_timestamp]
+            if header_generation.parse() == Ok(generation) =>
+        {
+            created_timestamp.parse().map_err(|_| invalid_manifest(1))?
+        }
+        _ => return Err(invalid_manifest(1)),
+    };
+
+    let mut entries = Vec::new();
+
+    for (index, line) in lines.enumerate() {
+        let mut fields = line.splitn(4, ' ');
+        let entry = (|| {
+            let hash = u64::from_str_radix(fields.next()?, 16).ok()?;
+            let length = fields.next()?.parse().ok()?;
+            let stored_generation = fields.next()?.parse().ok()?;
+            let path = PathBuf::from(fields.next()?);
+
+            // Entries never point outside of world folder or to newer generations.
+            let is_relative = path
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)));
+
+            if !is_relative || stored_generation > generation {
+                return None;
+            }
+
+            Some(BackupEntry {
+                path,
+                hash,
+                length,
+                stored_generation,
+            })
+        })();
+
+        entries.push(entry.ok_or_else(|| invalid_manifest(index + 2))?);
+    }
+
+    Ok(BackupManifest {
+        generation,
+        created_timestamp,
+        entries,
+    })
+}
+
+/// Returns 64-bit FNV-1a hash of data, which is stable across platforms and releases.
+pub(crate) fn fnv_hash(data: &[u8]) -> u64 {
+    data.iter().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backup::{BackupError, IncrementalBackup};
+    use crate::relocate::copy_chunk;
+    use crate::AnvilChunkProvider;
+    use std::fs;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    fn create_world(world_folder_path: &Path) {
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let region_folder = world_folder_path.join("region");
+        let chunk_provider = AnvilChunkProvider::new(region_folder.to_str().unwrap());
+
+        copy_chunk(&fixture_chunk_provider, (4, 2), &chunk_provider, (4, 2)).unwrap();
+        copy_chunk(&fixture_chunk_provider, (4, 2), &chunk_provider, (40, 2)).unwrap();
+        fs::write(world_folder_path.join("level.dat"), b"level").unwrap();
+        fs::write(world_folder_path.join("session.lock"), b"lock").unwrap();
+    }
+
+    #[test]
+    fn test_backup_stores_changed_files() {
+        let world_dir = TempDir::new().unwrap();
+        let backup_dir = TempDir::new().unwrap();
+        let region_folder = world_dir.path().join("region");
+        let chunk_provider = AnvilChunkProvider::new(region_folder.to_str().unwrap());
+        let backup = IncrementalBackup::new(backup_dir.path());
+        create_world(world_dir.path());
+
+        let backup_report = backup.backup(world_dir.path()).unwrap();
+        assert_eq!(backup_report.generation, 1);
+        assert_eq!(backup_report.stored_files, 3);
+        assert_eq!(backup_report.unchanged_files, 0);
+
+        chunk_provider.delete_chunk(40, 2).unwrap();
+
+        let backup_report = backup.backup(world_dir.path()).unwrap();
+        assert_eq!(backup_report.generation, 2);
+        assert_eq!(backup_report.stored_files, 1);
+        assert_eq!(backup_report.unchanged_files, 2);
+        assert_eq!(backup.generations().unwrap(), vec![1, 2]);
+
+        let manifest = backup.manifest(2).unwrap();
+        let stored_generations: Vec<_> = manifest
+            .entries
+            .iter()
+            .map(|entry| (entry.path.as_path(), entry.stored_generation))
+            .collect();
+
+        assert_eq!(
+            stored_generations,
+            vec![
+                (Path::new("level.dat"), 1),
+                (Path::new("region/r.0.0.mca"), 1),
+                (Path::new("region/r.1.0.mca"), 2),
+            ]
+        );
+        assert!(!backup_dir
+            .path()
+            .join("000002/files/region/r.0.0.mca")
+            .exists());
+    }
+
+    #[test]
+    fn test_restore() {
+        let world_dir = TempDir::new().unwrap();
+        let backup_dir = TempDir::new().unwrap();
+        let region_folder = world_dir.path().join("region");
+        let chunk_provider = AnvilChunkProvider::new(region_folder.to_str().unwrap());
+        let backup = IncrementalBackup::new(backup_dir.path());
+        create_world(world_dir.path());
+
+        backup.backup(world_dir.path()).unwrap();
+        chunk_provider.delete_chunk(4, 2).unwrap();
+        backup.backup(world_dir.path()).unwrap();
+
+        for (generation, chunk_positions) in &[(1, vec![(4, 2), (40, 2)]), (2, vec![(40, 2)])] {
+            let restore_dir = TempDir::new().unwrap();
+            assert_eq!(backup.restore(*generation, restore_dir.path()).unwrap(), 3);
+
+            let region_folder = restore_dir.path().join("region");
+            let restored_chunk_provider = AnvilChunkProvider::new(region_folder.to_str().unwrap());
+
+            assert_eq!(
+                &restored_chunk_provider.chunk_positions().unwrap(),
+                chunk_positions
+            );
+            assert_eq!(
+                fs::read(restore_dir.path().join("level.dat")).unwrap(),
+                b"level"
+            );
+            assert!(!restore_dir.path().join("session.lock").exists());
+        }
+
+        match backup.restore(3, world_dir.path()) {
+            Err(BackupError::GenerationNotFound { generation: 3 }) => {}
+            result => panic!("Expected `GenerationNotFound` but got `{:?}`", result),
+        }
+    }
+
+    #[test]
+    fn test_restore_detects_changed_files() {
+        let world_dir = TempDir::new().unwrap();
+        let backup_dir = TempDir::new().unwrap();
+        let backup = IncrementalBackup::new(backup_dir.path());
+        create_world(world_dir.path());
+
+        backup.backup(world_dir.path()).unwrap();
+        fs::write(backup_dir.path().join("000001/files/level.dat"), b"lever").unwrap();
+
+        let restore_dir = TempDir::new().unwrap();
+
+        match backup.restore(1, restore_dir.path()) {
+            Err(BackupError::HashMismatch { generation: 1, .. }) => {}
+            result => panic!("Expected `HashMismatch` but got `{:?}`", result),
+        }
+    }
+
+    #[test]
+    fn test_backup_into_world() {
+        let world_dir = TempDir::new().unwrap();
+        let backup = IncrementalBackup::new(world_dir.path().join("backups"));
+
+        match backup.backup(world_dir.path()) {
+            Err(BackupError::TargetIsSource) => {}
+            result => panic!("Expected `TargetIsSource` but got `{:?}`", result),
+        }
+    }
+}
@@ -0,0 +1,563 @@
+//! Export of Java Edition chunks into Bedrock Edition worlds.
+//!
+//! Bedrock world is a folder with `level.dat` and LevelDB database in `db` folder,
+//! where every chunk is stored as several keys: version, finalized state, biomes with
+//! heightmap and a key for every non-empty subchunk of 16x16x16 blocks. Blocks are
+//! written as palettes of Bedrock block states, mapped from Java ones with tables
+//! in this module, so blocks without mapping of their properties end up in default
+//! state. Biomes without Bedrock counterpart become plains.
+//!
+//! Export is one-way and covers terrain of overworld: block entities, entities and
+//! scheduled ticks are not converted, the game recreates block entities of containers
+//! empty. Chunks must be at least 1.13, older worlds are upgraded first with
+//! [`crate::upgrade`]. More information https://minecraft.wiki/w/Bedrock_Edition_level_format.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::bedrock::{export_bedrock_world, BedrockExportOptions};
+//! use anvil_region::AnvilChunkProvider;
+//! use tempfile::TempDir;
+//!
+//! let world_dir = TempDir::new().unwrap();
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//! let options = BedrockExportOptions::new("Exported");
+//!
+//! let report = export_bedrock_world(&chunk_provider, world_dir.path(), &options).unwrap();
+//!
+//! assert!(report.exported_chunks > 0);
+//! assert!(world_dir.path().join("db/CURRENT").exists());
+//! ```
+use crate::bedrock::blocks::{
+    bedrock_biome_id, bedrock_block_state, is_waterlogged, BLOCK_STATE_VERSION, PLAINS_BIOME_ID,
+};
+use crate::bedrock::leveldb::{Entry, LevelDbWriter};
+use crate::bedrock::little_endian::write_compound_tag;
+use crate::chunk::Chunk;
+use crate::height::HeightRange;
+use crate::section::{block_index, BlockState, Section, SectionError};
+use crate::{AnvilChunkProvider, ChunkLoadError};
+use nbt::{CompoundTag, Tag};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+mod blocks;
+mod leveldb;
+mod little_endian;
+
+/// Chunk format version of Bedrock 1.18.30 and later.
+const CHUNK_VERSION: u8 = 40;
+/// Version of `level.dat` format.
+const STORAGE_VERSION: i32 = 10;
+/// Version of game which is written as last to open world.
+const GAME_VERSION: [i32; 5] = [1, 21, 0, 0, 0];
+
+const DATA_3D_KEY: u8 = 43;
+const VERSION_KEY: u8 = 44;
+const SUB_CHUNK_KEY: u8 = 47;
+const FINALIZED_STATE_KEY: u8 = 54;
+
+/// Subchunk format with Y index, storing block palettes as NBT.
+const SUB_CHUNK_VERSION: u8 = 9;
+/// Chunk is generated and populated.
+const FINALIZED_STATE_DONE: i32 = 2;
+
+/// Blocks in subchunk.
+const SUB_CHUNK_BLOCKS: usize = 4096;
+/// Bits of palette indices which Bedrock supports, entries don't span words.
+const PALETTE_BITS: [u32; 8] = [1, 2, 3, 4, 5, 6, 8, 16];
+
+/// Possible errors while exporting Bedrock world.
+#[derive(Debug)]
+pub enum BedrockExportError {
+    /// Chunk can't be loaded.
+    ChunkLoadError { chunk_load_error: ChunkLoadError },
+    /// Chunk sections can't be read.
+    SectionError { section_error: SectionError },
+    /// World files can't be written.
+    WriteError { io_error: io::Error },
+}
+
+impl From<ChunkLoadError> for BedrockExportError {
+    fn from(chunk_load_error: ChunkLoadError) -> Self {
+        BedrockExportError::ChunkLoadError { chunk_load_error }
+    }
+}
+
+impl From<SectionError> for BedrockExportError {
+    fn from(section_error: SectionError) -> Self {
+        BedrockExportError::SectionError { section_error }
+    }
+}
+
+impl From<io::Error> for BedrockExportError {
+    fn from(io_error: io::Error) -> Self {
+        BedrockExportError::WriteError { io_error }
+    }
+}
+
+/// How Bedrock world is written.
+#[derive(Debug, Clone)]
+pub struct BedrockExportOptions {
+    /// Name which world list shows.
+    pub level_name: String,
+    /// Block position players spawn at.
+    pub spawn: (i32, i32, i32),
+}
+
+impl BedrockExportOptions {
+    pub fn new(level_name: &str) -> Self {
+        BedrockExportOptions {
+            level_name: level_name.to_owned(),
+            spawn: (0, 64, 0),
+        }
+    }
+
+    pub fn spawn(mut self, spawn: (i32, i32, i32)) -> Self {
+        self.spawn = spawn;
+        self
+    }
+}
+
+/// Result of Bedrock world export.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BedrockExportReport {
+    pub exported_chunks: usize,
+    /// Chunks which are not fully generated, the game generates them anew.
+    pub skipped_chunks: usize,
+    /// Java block names which were written without some of their properties.
+    pub approximated_blocks: BTreeSet<String>,
+}
+
+/// Writes fully generated chunks of provider into new Bedrock world in folder.
+///
+/// Existing database of world in folder is replaced.
+pub fn export_bedrock_world(
+    chunk_provider: &AnvilChunkProvider,
+    world_folder_path: &Path,
+    options: &BedrockExportOptions,
+) -> Result<BedrockExportReport, BedrockExportError> {
+    fs::create_dir_all(world_folder_path)?;
+
+    let mut db_writer = LevelDbWriter::create(&world_folder_path.join("db"))?;
+    let mut palette_cache = PaletteCache::default();
+    let mut report = BedrockExportReport::default();
+
+    for (chunk_x, chunk_z) in chunk_provider.chunk_positions()? {
+        let chunk = Chunk::new(chunk_provider.load_chunk(chunk_x, chunk_z)?);
+
+        if !chunk.is_fully_generated() {
+            report.skipped_chunks += 1;
+            continue;
+        }
+
+        let entries = chunk_entries(&chunk, (chunk_x, chunk_z), &mut palette_cache)?;
+        db_writer.write_batch(&entries)?;
+
+        report.exported_chunks += 1;
+    }
+
+    db_writer.finish()?;
+    write_level_data(world_folder_path, options)?;
+
+    report.approximated_blocks = palette_cache.approximated_blocks;
+
+    Ok(report)
+}
+
+/// Bedrock palette entries of Java block states which were already converted.
+#[derive(Default)]
+struct PaletteCache {
+    entries: HashMap<BlockState, Vec<u8>>,
+    approximated_blocks: BTreeSet<String>,
+}
+
+impl PaletteCache {
+    /// Returns little endian NBT of Bedrock block state.
+    fn entry(&mut self, block_state: &BlockState) -> &[u8] {
+        let approximated_blocks = &mut self.approximated_blocks;
+
+        self.entries.entry(block_state.clone()).or_insert_with(|| {
+            let bedrock_block_state = bedrock_block_state(block_state);
+
+            if bedrock_block_state.approximated {
+                approximated_blocks.insert(block_state.name.clone());
+            }
+
+            let mut entry = Vec::new();
+            write_compound_tag(&mut entry, &bedrock_block_state.compound_tag);
+
+            entry
+        })
+    }
+}
+
+/// Returns database entries of chunk.
+fn chunk_entries(
+    chunk: &Chunk,
+    (chunk_x, chunk_z): (i32, i32),
+    palette_cache: &mut PaletteCache,
+) -> Result<Vec<Entry>, BedrockExportError> {
+    let mut key_prefix = Vec::with_capacity(10);
+    key_prefix.extend_from_slice(&chunk_x.to_le_bytes());
+    key_prefix.extend_from_slice(&chunk_z.to_le_bytes());
+
+    let key = |key_type: u8| {
+        let mut key = key_prefix.clone();
+        key.push(key_type);
+        key
+    };
+
+    let mut sections = chunk.read_sections()?;
+    sections.sort_by_key(|section| section.y);
+
+    let mut entries = vec![
+        (key(VERSION_KEY), vec![CHUNK_VERSION]),
+        (
+            key(FINALIZED_STATE_KEY),
+            FINALIZED_STATE_DONE.to_le_bytes().to_vec(),
+        ),
+        (key(DATA_3D_KEY), data_3d(&sections)),
+    ];
+
+    for section in &sections {
+        if let Some(sub_chunk) = sub_chunk(section, palette_cache) {
+            let mut sub_chunk_key = key(SUB_CHUNK_KEY);
+            sub_chunk_key.push(section.y as u8);
+
+            entries.push((sub_chunk_key, sub_chunk));
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Returns subchunk with block layer and water layer of waterlogged blocks, none if
+/// section has only air.
+fn sub_chunk(section: &Section, palette_cache: &mut PaletteCache) -> Option<Vec<u8>> {
+    let block_states = section.block_states.as_ref()?;
+
+    if block_states.palette().iter().all(is_air) {
+        return None;
+    }
+
+    let mut block_indices = vec![0; SUB_CHUNK_BLOCKS];
+    let mut water_indices = vec![0; SUB_CHUNK_BLOCKS];
+    let waterlogged: Vec<_> = block_states.palette().iter().map(is_waterlogged).collect();
+
+    for x in 0..16 {
+        for z in 0..16 {
+            for y in 0..16 {
+                let palette_index = block_states.indices()[block_index(x, y, z)];
+                let bedrock_index = bedrock_block_index(x, y, z);
+
+                block_indices[bedrock_index] = palette_index;
+                water_indices[bedrock_index] = waterlogged[palette_index as usize] as u16;
+            }
+        }
+    }
+
+    let has_water = waterlogged.contains(&true);
+
+    let mut sub_chunk = vec![SUB_CHUNK_VERSION, 1 + has_water as u8, section.y as u8];
+
+    let palette: Vec<_> = block_states
+        .palette()
+        .iter()
+        .map(|block_state| palette_cache.entry(block_state).to_vec())
+        .collect();
+    write_block_storage(&mut sub_chunk, &block_indices, &palette);
+
+    if has_water {
+        let water_palette = [
+            palette_cache
+                .entry(&BlockState::new("minecraft:air"))
+                .to_vec(),
+            palette_cache
+                .entry(&BlockState::new("minecraft:water").with_property("level", "0"))
+                .to_vec(),
+        ];
+        write_block_storage(&mut sub_chunk, &water_indices, &water_palette);
+    }
+
+    Some(sub_chunk)
+}
+
+/// Returns heightmap and biomes of every subchunk from the bottom of the world.
+fn data_3d(sections: &[Section]) -> Vec<u8> {
+    let height_range = HeightRange::OVERWORLD;
+    let mut heights = [0i16; 256];
+    let mut data = Vec::new();
+
+    for section in sections {
+        let block_states = match &section.block_states {
+            Some(block_states) => block_states,
+            None => continue,
+        };
+
+        for z in 0..16 {
+            for x in 0..16 {
+                for y in (0..16).rev() {
+                    let block_state = block_states
+                        .get(block_index(x, y, z))
+                        .expect("Index is inside of section");
+
+                    if !is_air(block_state) {
+                        let height = section.y as i32 * 16 + y as i32 + 1 - height_range.min_y;
+                        heights[z * 16 + x] = heights[z * 16 + x].max(height as i16);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    for height in &heights {
+        data.extend_from_slice(&height.to_le_bytes());
+    }
+
+    for section_y in height_range.min_section_y()..height_range.max_section_y() {
+        let biomes = sections
+            .iter()
+            .find(|section| section.y as i32 == section_y)
+            .and_then(|section| section.biomes.as_ref());
+
+        let biomes = match biomes {
+            Some(biomes) => biomes,
+            None => {
+                write_biome_storage(&mut data, &[0; SUB_CHUNK_BLOCKS], &[PLAINS_BIOME_ID]);
+                continue;
+            }
+        };
+
+        let palette: Vec<_> = biomes
+            .palette()
+            .iter()
+            .map(|biome| bedrock_biome_id(biome))
+            .collect();
+
+        // Java stores biome for every 4x4x4 cell, Bedrock for every block.
+        let mut indices = vec![0; SUB_CHUNK_BLOCKS];
+
+        for x in 0..16 {
+            for z in 0..16 {
+                for y in 0..16 {
+                    let biome_index = (y / 4) * 16 + (z / 4) * 4 + x / 4;
+                    indices[bedrock_block_index(x, y, z)] = biomes.indices()[biome_index];
+                }
+            }
+        }
+
+        write_biome_storage(&mut data, &indices, &palette);
+    }
+
+    data
+}
+
+/// Writes block storage with palette of little endian NBT entries.
+fn write_block_storage(buffer: &mut Vec<u8>, indices: &[u16], palette: &[Vec<u8>]) {
+    let bits = palette_bits(palette.len());
+
+    buffer.push((bits as u8) << 1);
+    write_packed_indices(buffer, indices, bits);
+    buffer.extend_from_slice(&(palette.len() as i32).to_le_bytes());
+
+    for entry in palette {
+        buffer.extend_from_slice(entry);
+    }
+}
+
+/// Writes biome storage with palette of numeric biome ids.
+///
+/// Storage with single biome has no indices and palette length.
+fn write_biome_storage(buffer: &mut Vec<u8>, indices: &[u16], palette: &[u32]) {
+    if palette.len() == 1 {
+        buffer.push(1);
+        buffer.extend_from_slice(&palette[0].to_le_bytes());
+        return;
+    }
+
+    let bits = palette_bits(palette.len());
+
+    buffer.push((bits as u8) << 1 | 1);
+    write_packed_indices(buffer, indices, bits);
+    buffer.extend_from_slice(&(palette.len() as i32).to_le_bytes());
+
+    for biome_id in palette {
+        buffer.extend_from_slice(&biome_id.to_le_bytes());
+    }
+}
+
+/// Writes indices packed into little endian words, leftover bits of words are unused.
+fn write_packed_indices(buffer: &mut Vec<u8>, indices: &[u16], bits: u32) {
+    let indices_per_word = (32 / bits) as usize;
+
+    for word_indices in indices.chunks(indices_per_word) {
+        let word = word_indices
+            .iter()
+            .enumerate()
+            .fold(0u32, |word, (index, value)| {
+                word | (*value as u32) << (index as u32 * bits)
+            });
+
+        buffer.extend_from_slice(&word.to_le_bytes());
+    }
+}
+
+fn palette_bits(palette_length: usize) -> u32 {
+    let required_bits = usize::BITS - (palette_length.max(2) - 1).leading_zeros();
+
+    PALETTE_BITS
+        .iter()
+        .copied()
+        .find(|bits| *bits >= required_bits)
+        .unwrap_or(16)
+}
+
+/// Returns index of block in Bedrock subchunk, which is ordered by X, Z and Y.
+fn bedrock_block_index(x: usize, y: usize, z: usize) -> usize {
+    x * 256 + z * 16 + y
+}
+
+fn is_air(block_state: &BlockState) -> bool {
+    matches!(
+        block_state.name.as_str(),
+        "minecraft:air" | "minecraft:cave_air" | "minecraft:void_air"
+    )
+}
+
+/// Writes `level.dat` with 8 byte header and `levelname.txt`.
+fn write_level_data(
+    world_folder_path: &Path,
+    options: &BedrockExportOptions,
+) -> Result<(), io::Error> {
+    let (spawn_x, spawn_y, spawn_z) = options.spawn;
+    let last_played = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as i64);
+    let game_version = || Tag::List(GAME_VERSION.iter().map(|part| Tag::Int(*part)).collect());
+
+    let mut level_data = CompoundTag::new();
+    level_data.insert_str("LevelName", &options.level_name);
+    level_data.insert_i32("StorageVersion", STORAGE_VERSION);
+    level_data.insert_i32("Generator", 1);
+    level_data.insert_i32("GameType", 0);
+    level_data.insert_i64("LastPlayed", last_played);
+    level_data.insert_i32("SpawnX", spawn_x);
+    level_data.insert_i32("SpawnY", spawn_y);
+    level_data.insert_i32("SpawnZ", spawn_z);
+    level_data.insert("lastOpenedWithVersion", game_version());
+    level_data.insert("MinimumCompatibleClientVersion", game_version());
+    level_data.insert_i32("WorldVersion", 1);
+    level_data.insert_i32("BlockStateVersion", BLOCK_STATE_VERSION);
+
+    let mut level_data_nbt = Vec::new();
+    write_compound_tag(&mut level_data_nbt, &level_data);
+
+    let mut level_dat = Vec::with_capacity(8 + level_data_nbt.len());
+    level_dat.extend_from_slice(&STORAGE_VERSION.to_le_bytes());
+    level_dat.extend_from_slice(&(level_data_nbt.len() as i32).to_le_bytes());
+    level_dat.extend_from_slice(&level_data_nbt);
+
+    fs::write(world_folder_path.join("level.dat"), level_dat)?;
+    fs::write(world_folder_path.join("levelname.txt"), &options.level_name)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bedrock::{
+        export_bedrock_world, palette_bits, sub_chunk, write_packed_indices, BedrockExportOptions,
+        PaletteCache, SUB_CHUNK_VERSION,
+    };
+    use crate::chunk::Chunk;
+    use crate::relocate::copy_chunk;
+    use crate::section::{BlockState, Section};
+    use crate::AnvilChunkProvider;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_palette_bits() {
+        assert_eq!(palette_bits(1), 1);
+        assert_eq!(palette_bits(2), 1);
+        assert_eq!(palette_bits(3), 2);
+        assert_eq!(palette_bits(33), 6);
+        assert_eq!(palette_bits(65), 8);
+        assert_eq!(palette_bits(300), 16);
+    }
+
+    #[test]
+    fn test_write_packed_indices() {
+        // Three bits leave 2 unused bits of every word.
+        let mut buffer = Vec::new();
+        write_packed_indices(&mut buffer, &[1; 11], 3);
+
+        assert_eq!(buffer.len(), 8);
+        assert_eq!(
+            u32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]),
+            0o1111111111
+        );
+        assert_eq!(buffer[4], 1);
+    }
+
+    #[test]
+    fn test_sub_chunk() {
+        let mut palette_cache = PaletteCache::default();
+        let mut section = Section::new(-2);
+        assert!(sub_chunk(&section, &mut palette_cache).is_none());
+
+        let stairs = BlockState::new("minecraft:stone_stairs").with_property("waterlogged", "true");
+        section.set_block_state(0, 1, 0, stairs);
+
+        let sub_chunk = sub_chunk(&section, &mut palette_cache).unwrap();
+        // Version, block and water layer, Y index of -2 and header of 1 bit layer.
+        assert_eq!(sub_chunk[..4], [SUB_CHUNK_VERSION, 2, 254, 2]);
+        // Block at Y 1 is the second one of the first word.
+        assert_eq!(sub_chunk[4], 0b10);
+    }
+
+    #[test]
+    fn test_export_bedrock_world() {
+        let temp_dir = TempDir::new().unwrap();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+        copy_chunk(&fixture_chunk_provider, (4, 2), &chunk_provider, (4, 2)).unwrap();
+
+        let chunk = Chunk::new(chunk_provider.load_chunk(4, 2).unwrap());
+        let sub_chunks = chunk
+            .read_sections()
+            .unwrap()
+            .iter()
+            .filter(|section| section.block_states.is_some())
+            .count();
+
+        let world_dir = TempDir::new().unwrap();
+        let options = BedrockExportOptions::new("Exported").spawn((64, 80, 32));
+        let report = export_bedrock_world(&chunk_provider, world_dir.path(), &options).unwrap();
+
+        assert_eq!(report.exported_chunks, 1);
+        assert_eq!(report.skipped_chunks, 0);
+
+        let level_dat = fs::read(world_dir.path().join("level.dat")).unwrap();
+        assert_eq!(level_dat[..4], 10i32.to_le_bytes());
+        assert_eq!(
+            i32::from_le_bytes([level_dat[4], level_dat[5], level_dat[6], level_dat[7]]) as usize,
+            level_dat.len() - 8
+        );
+        assert_eq!(
+            fs::read_to_string(world_dir.path().join("levelname.txt")).unwrap(),
+            "Exported"
+        );
+
+        // Batch of chunk has version, finalized state, biomes and subchunks.
+        let log = fs::read(world_dir.path().join("db/000003.log")).unwrap();
+        let entries = u32::from_le_bytes([log[15], log[16], log[17], log[18]]) as usize;
+        assert!(entries > 3 && entries <= 3 + sub_chunks);
+    }
+}
@@ -0,0 +1,329 @@
+//! Tables which map Java Edition block states and biomes to Bedrock Edition ones.
+//!
+//! Most blocks have the same name in both editions since Bedrock 1.21 split stone,
+//! wool, log and similar variants into separate blocks. Properties differ more, so
+//! properties of common block families are converted and the rest are dropped, which
+//! leaves such blocks in their default state.
+use crate::section::BlockState;
+use nbt::CompoundTag;
+
+/// Version of block states which are written, Bedrock 1.21.0.
+pub(crate) const BLOCK_STATE_VERSION: i32 = 1 << 24 | 21 << 16;
+
+/// Java block names with different Bedrock name, without namespace.
+const RENAMED_BLOCKS: &[(&str, &str)] = &[
+    ("bricks", "brick_block"),
+    ("cave_air", "air"),
+    ("cobweb", "web"),
+    ("comparator", "unpowered_comparator"),
+    ("dead_bush", "deadbush"),
+    ("dirt_path", "grass_path"),
+    ("end_stone_bricks", "end_bricks"),
+    ("grass", "short_grass"),
+    ("jack_o_lantern", "lit_pumpkin"),
+    ("lava_cauldron", "cauldron"),
+    ("light_gray_glazed_terracotta", "silver_glazed_terracotta"),
+    ("lily_pad", "waterlily"),
+    ("magma_block", "magma"),
+    ("melon", "melon_block"),
+    ("moving_piston", "moving_block"),
+    ("nether_bricks", "nether_brick"),
+    ("nether_portal", "portal"),
+    ("nether_quartz_ore", "quartz_ore"),
+    ("note_block", "noteblock"),
+    ("piston_head", "piston_arm_collision"),
+    ("powered_rail", "golden_rail"),
+    ("red_nether_bricks", "red_nether_brick"),
+    ("redstone_wall_torch", "redstone_torch"),
+    ("repeater", "unpowered_repeater"),
+    ("rooted_dirt", "dirt_with_roots"),
+    ("shulker_box", "undyed_shulker_box"),
+    ("slime_block", "slime"),
+    ("snow", "snow_layer"),
+    ("snow_block", "snow"),
+    ("soul_wall_torch", "soul_torch"),
+    ("spawner", "mob_spawner"),
+    ("sugar_cane", "reeds"),
+    ("terracotta", "hardened_clay"),
+    ("tripwire", "trip_wire"),
+    ("void_air", "air"),
+    ("wall_torch", "torch"),
+    ("water_cauldron", "cauldron"),
+];
+
+/// Blocks which become separate lit blocks when `lit` is true.
+const LIT_BLOCKS: &[&str] = &[
+    "blast_furnace",
+    "deepslate_redstone_ore",
+    "furnace",
+    "redstone_lamp",
+    "redstone_ore",
+    "smoker",
+];
+
+/// Blocks with horizontal `facing` stored as `minecraft:cardinal_direction`.
+const CARDINAL_BLOCKS: &[&str] = &[
+    "blast_furnace",
+    "carved_pumpkin",
+    "chest",
+    "ender_chest",
+    "furnace",
+    "jack_o_lantern",
+    "smoker",
+    "stonecutter",
+    "trapped_chest",
+];
+
+/// Crops with `age` stored as `growth`.
+const CROPS: &[&str] = &["carrots", "potatoes", "wheat"];
+
+/// Numeric Bedrock ids of Java biomes, without namespace.
+const BIOMES: &[(&str, u32)] = &[
+    ("ocean", 0),
+    ("plains", 1),
+    ("desert", 2),
+    ("windswept_hills", 3),
+    ("forest", 4),
+    ("taiga", 5),
+    ("swamp", 6),
+    ("river", 7),
+    ("nether_wastes", 8),
+    ("the_end", 9),
+    ("frozen_river", 11),
+    ("snowy_plains", 12),
+    ("mushroom_fields", 14),
+    ("beach", 16),
+    ("jungle", 21),
+    ("sparse_jungle", 23),
+    ("deep_ocean", 24),
+    ("stony_shore", 25),
+    ("snowy_beach", 26),
+    ("birch_forest", 27),
+    ("dark_forest", 29),
+    ("snowy_taiga", 30),
+    ("old_growth_pine_taiga", 32),
+    ("windswept_forest", 34),
+    ("savanna", 35),
+    ("savanna_plateau", 36),
+    ("badlands", 37),
+    ("wooded_badlands", 38),
+    ("warm_ocean", 40),
+    ("lukewarm_ocean", 42),
+    ("deep_lukewarm_ocean", 43),
+    ("cold_ocean", 44),
+    ("deep_cold_ocean", 45),
+    ("bamboo_jungle", 48),
+    ("sunflower_plains", 129),
+    ("windswept_gravelly_hills", 131),
+    ("flower_forest", 132),
+    ("ice_spikes", 140),
+    ("old_growth_birch_forest", 155),
+    ("old_growth_spruce_taiga", 160),
+    ("windswept_savanna", 163),
+    ("eroded_badlands", 165),
+    ("soul_sand_valley", 178),
+    ("crimson_forest", 179),
+    ("warped_forest", 180),
+    ("basalt_deltas", 181),
+    ("jagged_peaks", 182),
+    ("frozen_peaks", 183),
+    ("snowy_slopes", 184),
+    ("grove", 185),
+    ("meadow", 186),
+    ("lush_caves", 187),
+    ("dripstone_caves", 188),
+    ("stony_peaks", 189),
+    ("deep_dark", 190),
+    ("mangrove_swamp", 191),
+    ("cherry_grove", 192),
+];
+
+/// Id of plains, which biomes without Bedrock counterpart become.
+pub(crate) const PLAINS_BIOME_ID: u32 = 1;
+
+/// Bedrock block state of Java block state.
+#[derive(Debug, Clone)]
+pub(crate) struct BedrockBlockState {
+    pub(crate) compound_tag: CompoundTag,
+    /// Java properties were dropped, so block is left in default state.
+    pub(crate) approximated: bool,
+}
+
+/// Returns Bedrock block state palette entry with `name`, `states` and `version`.
+pub(crate) fn bedrock_block_state(block_state: &BlockState) -> BedrockBlockState {
+    let name = block_state
+        .name
+        .strip_prefix("minecraft:")
+        .unwrap_or(&block_state.name);
+    let property = |property_name: &str| {
+        block_state
+            .properties
+            .iter()
+            .find(|(name, _)| name == property_name)
+            .map(|(_, value)| value.as_str())
+    };
+
+    let mut bedrock_name = RENAMED_BLOCKS
+        .iter()
+        .find(|(java_name, _)| *java_name == name)
+        .map_or(name, |(_, bedrock_name)| bedrock_name)
+        .to_owned();
+
+    if LIT_BLOCKS.contains(&name) && property("lit") == Some("true") {
+        bedrock_name = format!("lit_{}", bedrock_name);
+    }
+
+    match (name, property("lit"), property("powered")) {
+        ("redstone_torch" | "redstone_wall_torch", Some("false"), _) => {
+            bedrock_name = "unlit_redstone_torch".to_owned()
+        }
+        ("repeater", _, Some("true")) => bedrock_name = "powered_repeater".to_owned(),
+        ("comparator", _, Some("true")) => bedrock_name = "powered_comparator".to_owned(),
+        _ => {}
+    }
+
+    let mut states = CompoundTag::new();
+    let mut approximated = false;
+
+    for (property_name, value) in &block_state.properties {
+        match property_name.as_str() {
+            // Stored as separate block name, layer or not at all.
+            "lit" | "powered" | "waterlogged" | "snowy" | "distance" => {}
+            "axis" => states.insert_str("pillar_axis", value),
+            "facing" if name.ends_with("_stairs") => {
+                let direction = match value.as_str() {
+                    "east" => 0,
+                    "west" => 1,
+                    "south" => 2,
+                    _ => 3,
+                };
+                states.insert_i32("weirdo_direction", direction);
+            }
+            "half" if name.ends_with("_stairs") => {
+                states.insert_i8("upside_down_bit", (value == "top") as i8)
+            }
+            "shape" if name.ends_with("_stairs") => {}
+            "type" if name.ends_with("_slab") => {
+                if value == "double" {
+                    bedrock_name = bedrock_name.replace("_slab", "_double_slab");
+                }
+
+                let half = if value == "top" { "top" } else { "bottom" };
+                states.insert_str("minecraft:vertical_half", half);
+            }
+            "facing" if CARDINAL_BLOCKS.contains(&name) => {
+                states.insert_str("minecraft:cardinal_direction", value)
+            }
+            "facing" if name.ends_with("wall_torch") => {
+                states.insert_str("torch_facing_direction", value)
+            }
+            "level" if name == "water" || name == "lava" => {
+                states.insert_i32("liquid_depth", value.parse().unwrap_or_default())
+            }
+            "layers" if name == "snow" => {
+                let layers: i32 = value.parse().unwrap_or(1);
+                states.insert_i32("height", layers - 1);
+            }
+            "persistent" if name.ends_with("_leaves") => {
+                states.insert_i8("persistent_bit", (value == "true") as i8);
+                states.insert_i8("update_bit", 0);
+            }
+            "age" if CROPS.contains(&name) => {
+                states.insert_i32("growth", value.parse().unwrap_or_default())
+            }
+            _ => approximated = true,
+        }
+    }
+
+    if name == "torch" || name == "redstone_torch" || name == "soul_torch" {
+        states.insert_str("torch_facing_direction", "top");
+    }
+
+    let mut compound_tag = CompoundTag::new();
+    compound_tag.insert_str("name", format!("minecraft:{}", bedrock_name));
+    compound_tag.insert_compound_tag("states", states);
+    compound_tag.insert_i32("version", BLOCK_STATE_VERSION);
+
+    BedrockBlockState {
+        compound_tag,
+        approximated,
+    }
+}
+
+/// Returns true if Java block state contains water, which Bedrock stores in second layer.
+pub(crate) fn is_waterlogged(block_state: &BlockState) -> bool {
+    block_state
+        .properties
+        .iter()
+        .any(|(name, value)| name == "waterlogged" && value == "true")
+}
+
+/// Returns numeric Bedrock id of Java biome.
+pub(crate) fn bedrock_biome_id(biome: &str) -> u32 {
+    let biome = biome.strip_prefix("minecraft:").unwrap_or(biome);
+
+    BIOMES
+        .iter()
+        .find(|(java_biome, _)| *java_biome == biome)
+        .map_or(PLAINS_BIOME_ID, |(_, biome_id)| *biome_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bedrock::blocks::{bedrock_biome_id, bedrock_block_state, BLOCK_STATE_VERSION};
+    use crate::section::BlockState;
+
+    #[test]
+    fn test_bedrock_block_state() {
+        let stairs = BlockState::new("minecraft:oak_stairs")
+            .with_property("facing", "south")
+            .with_property("half", "top")
+            .with_property("shape", "straight")
+            .with_property("waterlogged", "false");
+        let bedrock_stairs = bedrock_block_state(&stairs);
+        let states = bedrock_stairs
+            .compound_tag
+            .get_compound_tag("states")
+            .unwrap();
+
+        assert!(!bedrock_stairs.approximated);
+        assert_eq!(
+            bedrock_stairs.compound_tag.get_str("name").unwrap(),
+            "minecraft:oak_stairs"
+        );
+        assert_eq!(states.get_i32("weirdo_direction").unwrap(), 2);
+        assert_eq!(states.get_i8("upside_down_bit").unwrap(), 1);
+        assert_eq!(
+            bedrock_stairs.compound_tag.get_i32("version").unwrap(),
+            BLOCK_STATE_VERSION
+        );
+
+        let furnace = BlockState::new("minecraft:furnace")
+            .with_property("facing", "west")
+            .with_property("lit", "true");
+        let bedrock_furnace = bedrock_block_state(&furnace).compound_tag;
+        assert_eq!(
+            bedrock_furnace.get_str("name").unwrap(),
+            "minecraft:lit_furnace"
+        );
+
+        let slab = BlockState::new("minecraft:stone_slab").with_property("type", "double");
+        assert_eq!(
+            bedrock_block_state(&slab)
+                .compound_tag
+                .get_str("name")
+                .unwrap(),
+            "minecraft:stone_double_slab"
+        );
+
+        let door = BlockState::new("minecraft:oak_door").with_property("hinge", "left");
+        assert!(bedrock_block_state(&door).approximated);
+    }
+
+    #[test]
+    fn test_bedrock_biome_id() {
+        assert_eq!(bedrock_biome_id("minecraft:desert"), 2);
+        assert_eq!(bedrock_biome_id("minecraft:cherry_grove"), 192);
+        assert_eq!(bedrock_biome_id("mod:unknown"), 1);
+    }
+}
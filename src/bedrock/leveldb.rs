@@ -0,0 +1,234 @@
+//! Minimal writer of LevelDB databases.
+//!
+//! Key value pairs are written as write batches into log file, along with manifest
+//! which points to it. LevelDB replays log into sorted tables when database is opened
+//! first time, so writer needs neither tables nor compression. More information
+//! https://github.com/google/leveldb/blob/main/doc/log_format.md.
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Size of log block, records never cross block boundary.
+const BLOCK_SIZE: usize = 32 * 1024;
+/// Checksum, length and type of record.
+const RECORD_HEADER_SIZE: usize = 7;
+
+const FULL_RECORD: u8 = 1;
+const FIRST_RECORD: u8 = 2;
+const MIDDLE_RECORD: u8 = 3;
+const LAST_RECORD: u8 = 4;
+
+/// Type of write batch entry which sets value.
+const VALUE_ENTRY: u8 = 1;
+
+const COMPARATOR_TAG: u32 = 1;
+const LOG_NUMBER_TAG: u32 = 2;
+const NEXT_FILE_NUMBER_TAG: u32 = 3;
+const LAST_SEQUENCE_TAG: u32 = 4;
+
+const COMPARATOR: &str = "leveldb.BytewiseComparator";
+const MANIFEST_NUMBER: u64 = 2;
+const LOG_NUMBER: u64 = 3;
+
+/// Key and value of database entry.
+pub(crate) type Entry = (Vec<u8>, Vec<u8>);
+
+/// Writer of new database, existing database in folder is replaced.
+pub(crate) struct LevelDbWriter {
+    log: LogWriter<BufWriter<File>>,
+    sequence: u64,
+}
+
+impl LevelDbWriter {
+    pub(crate) fn create(folder_path: &Path) -> Result<Self, io::Error> {
+        if folder_path.exists() {
+            fs::remove_dir_all(folder_path)?;
+        }
+
+        fs::create_dir_all(folder_path)?;
+
+        // Manifest doesn't know last sequence, which is recovered from log.
+        let mut version_edit = Vec::new();
+        write_varint(&mut version_edit, COMPARATOR_TAG as u64);
+        write_varint(&mut version_edit, COMPARATOR.len() as u64);
+        version_edit.extend_from_slice(COMPARATOR.as_bytes());
+        write_varint(&mut version_edit, LOG_NUMBER_TAG as u64);
+        write_varint(&mut version_edit, LOG_NUMBER);
+        write_varint(&mut version_edit, NEXT_FILE_NUMBER_TAG as u64);
+        write_varint(&mut version_edit, LOG_NUMBER + 1);
+        write_varint(&mut version_edit, LAST_SEQUENCE_TAG as u64);
+        write_varint(&mut version_edit, 0);
+
+        let manifest_name = format!("MANIFEST-{:06}", MANIFEST_NUMBER);
+        let manifest_file = File::create(folder_path.join(&manifest_name))?;
+        let mut manifest = LogWriter::new(manifest_file);
+        manifest.add_record(&version_edit)?;
+        manifest.writer.sync_all()?;
+
+        fs::write(folder_path.join("CURRENT"), format!("{}\n", manifest_name))?;
+
+        let log_file = File::create(folder_path.join(format!("{:06}.log", LOG_NUMBER)))?;
+
+        Ok(LevelDbWriter {
+            log: LogWriter::new(BufWriter::new(log_file)),
+            sequence: 1,
+        })
+    }
+
+    /// Writes key value pairs as single batch.
+    pub(crate) fn write_batch(&mut self, entries: &[Entry]) -> Result<(), io::Error> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let mut batch = Vec::new();
+        batch.extend_from_slice(&self.sequence.to_le_bytes());
+        batch.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+
+        for (key, value) in entries {
+            batch.push(VALUE_ENTRY);
+            write_varint(&mut batch, key.len() as u64);
+            batch.extend_from_slice(key);
+            write_varint(&mut batch, value.len() as u64);
+            batch.extend_from_slice(value);
+        }
+
+        self.log.add_record(&batch)?;
+        self.sequence += entries.len() as u64;
+
+        Ok(())
+    }
+
+    /// Flushes and syncs log.
+    pub(crate) fn finish(self) -> Result<(), io::Error> {
+        let file = self.log.writer.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()
+    }
+}
+
+/// Writer of records split into blocks.
+struct LogWriter<W: Write> {
+    writer: W,
+    block_offset: usize,
+}
+
+impl<W: Write> LogWriter<W> {
+    fn new(writer: W) -> Self {
+        LogWriter {
+            writer,
+            block_offset: 0,
+        }
+    }
+
+    fn add_record(&mut self, mut data: &[u8]) -> Result<(), io::Error> {
+        let mut first = true;
+
+        loop {
+            let remaining = BLOCK_SIZE - self.block_offset;
+
+            // Header doesn't fit into block, rest of block is padded.
+            if remaining < RECORD_HEADER_SIZE {
+                self.writer
+                    .write_all(&[0; RECORD_HEADER_SIZE][..remaining])?;
+                self.block_offset = 0;
+                continue;
+            }
+
+            let fragment_length = data.len().min(remaining - RECORD_HEADER_SIZE);
+            let last = fragment_length == data.len();
+
+            let record_type = match (first, last) {
+                (true, true) => FULL_RECORD,
+                (true, false) => FIRST_RECORD,
+                (false, false) => MIDDLE_RECORD,
+                (false, true) => LAST_RECORD,
+            };
+
+            let fragment = &data[..fragment_length];
+            let checksum = mask_checksum(crc32c(crc32c_byte(!0, record_type), fragment) ^ !0);
+
+            self.writer.write_all(&checksum.to_le_bytes())?;
+            self.writer
+                .write_all(&(fragment_length as u16).to_le_bytes())?;
+            self.writer.write_all(&[record_type])?;
+            self.writer.write_all(fragment)?;
+
+            self.block_offset += RECORD_HEADER_SIZE + fragment_length;
+            data = &data[fragment_length..];
+            first = false;
+
+            if last {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn write_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+
+    buffer.push(value as u8);
+}
+
+/// Rotates checksum, so checksums of data containing checksums are not trivial.
+fn mask_checksum(checksum: u32) -> u32 {
+    checksum.rotate_right(15).wrapping_add(0xa282_ead8)
+}
+
+/// Updates CRC-32C of Castagnoli, which LevelDB uses instead of CRC-32 of zlib.
+fn crc32c(crc: u32, data: &[u8]) -> u32 {
+    data.iter().fold(crc, |crc, byte| crc32c_byte(crc, *byte))
+}
+
+fn crc32c_byte(mut crc: u32, byte: u8) -> u32 {
+    crc ^= byte as u32;
+
+    for _ in 0..8 {
+        crc = (crc >> 1) ^ (0x82f6_3b78 & (!(crc & 1)).wrapping_add(1));
+    }
+
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bedrock::leveldb::{crc32c, mask_checksum, LevelDbWriter, BLOCK_SIZE};
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_crc32c() {
+        assert_eq!(crc32c(!0, b"123456789") ^ !0, 0xe306_9283);
+        assert_eq!(mask_checksum(0), 0xa282_ead8);
+    }
+
+    #[test]
+    fn test_write_batches() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("db");
+
+        let mut writer = LevelDbWriter::create(&db_path).unwrap();
+        writer
+            .write_batch(&[(b"key".to_vec(), vec![7; BLOCK_SIZE * 2])])
+            .unwrap();
+        writer
+            .write_batch(&[(b"other".to_vec(), b"value".to_vec())])
+            .unwrap();
+        writer.finish().unwrap();
+
+        assert_eq!(
+            fs::read_to_string(db_path.join("CURRENT")).unwrap(),
+            "MANIFEST-000002\n"
+        );
+
+        // Large record is split into fragments which fill blocks up to the end.
+        let log = fs::read(db_path.join("000003.log")).unwrap();
+        assert_eq!(log[6], 2);
+        assert_eq!(log[BLOCK_SIZE + 6], 3);
+        assert_eq!(log[BLOCK_SIZE * 2 + 6], 4);
+        assert!(log.ends_with(b"value"));
+    }
+}
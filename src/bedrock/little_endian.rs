@@ -0,0 +1,108 @@
+//! Encoding of NBT with little endian numbers, which Bedrock Edition uses on disk.
+use nbt::{CompoundTag, Tag};
+
+const END_TAG_TYPE: u8 = 0;
+
+/// Appends compound tag with its name, empty if it's unnamed.
+pub(crate) fn write_compound_tag(buffer: &mut Vec<u8>, compound_tag: &CompoundTag) {
+    buffer.push(tag_type(&Tag::Compound(CompoundTag::new())));
+    write_string(buffer, compound_tag.name.as_deref().unwrap_or_default());
+    write_compound_payload(buffer, compound_tag);
+}
+
+fn write_compound_payload(buffer: &mut Vec<u8>, compound_tag: &CompoundTag) {
+    for (name, tag) in compound_tag.iter() {
+        buffer.push(tag_type(tag));
+        write_string(buffer, name);
+        write_payload(buffer, tag);
+    }
+
+    buffer.push(END_TAG_TYPE);
+}
+
+fn write_payload(buffer: &mut Vec<u8>, tag: &Tag) {
+    match tag {
+        Tag::Byte(value) => buffer.push(*value as u8),
+        Tag::Short(value) => buffer.extend_from_slice(&value.to_le_bytes()),
+        Tag::Int(value) => buffer.extend_from_slice(&value.to_le_bytes()),
+        Tag::Long(value) => buffer.extend_from_slice(&value.to_le_bytes()),
+        Tag::Float(value) => buffer.extend_from_slice(&value.to_le_bytes()),
+        Tag::Double(value) => buffer.extend_from_slice(&value.to_le_bytes()),
+        Tag::ByteArray(values) => {
+            buffer.extend_from_slice(&(values.len() as i32).to_le_bytes());
+            buffer.extend(values.iter().map(|value| *value as u8));
+        }
+        Tag::String(value) => write_string(buffer, value),
+        Tag::List(tags) => {
+            buffer.push(tags.first().map_or(END_TAG_TYPE, tag_type));
+            buffer.extend_from_slice(&(tags.len() as i32).to_le_bytes());
+
+            for tag in tags {
+                write_payload(buffer, tag);
+            }
+        }
+        Tag::Compound(compound_tag) => write_compound_payload(buffer, compound_tag),
+        Tag::IntArray(values) => {
+            buffer.extend_from_slice(&(values.len() as i32).to_le_bytes());
+
+            for value in values {
+                buffer.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        Tag::LongArray(values) => {
+            buffer.extend_from_slice(&(values.len() as i32).to_le_bytes());
+
+            for value in values {
+                buffer.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+    }
+}
+
+fn write_string(buffer: &mut Vec<u8>, value: &str) {
+    buffer.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    buffer.extend_from_slice(value.as_bytes());
+}
+
+fn tag_type(tag: &Tag) -> u8 {
+    match tag {
+        Tag::Byte(_) => 1,
+        Tag::Short(_) => 2,
+        Tag::Int(_) => 3,
+        Tag::Long(_) => 4,
+        Tag::Float(_) => 5,
+        Tag::Double(_) => 6,
+        Tag::ByteArray(_) => 7,
+        Tag::String(_) => 8,
+        Tag::List(_) => 9,
+        Tag::Compound(_) => 10,
+        Tag::IntArray(_) => 11,
+        Tag::LongArray(_) => 12,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bedrock::little_endian::write_compound_tag;
+    use nbt::{CompoundTag, Tag};
+
+    #[test]
+    fn test_write_compound_tag() {
+        let mut compound_tag = CompoundTag::new();
+        compound_tag.insert_i32("a", 1);
+        compound_tag.insert("b", Tag::List(Vec::new()));
+
+        let mut buffer = Vec::new();
+        write_compound_tag(&mut buffer, &compound_tag);
+
+        assert_eq!(
+            buffer,
+            vec![
+                10, 0, 0, // Unnamed root.
+                3, 1, 0, b'a', 1, 0, 0, 0, // Int.
+                9, 1, 0, b'b', 0, 0, 0, 0, 0, // Empty list.
+                0,
+            ]
+        );
+    }
+}
@@ -0,0 +1,29 @@
+//! Command line tool for inspecting and repairing region files, see `anvil_region::cli`.
+use anvil_region::cli::{run, CliError, Command, USAGE};
+use std::io::{self, Write};
+use std::{env, process};
+
+fn main() {
+    let result = Command::parse(env::args().skip(1)).and_then(|command| {
+        let stdout = io::stdout();
+        let mut output = stdout.lock();
+
+        run(&command, &mut output)?;
+        output.flush()?;
+
+        Ok(())
+    });
+
+    match result {
+        Ok(()) => {}
+        Err(CliError::Usage { message }) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+        Err(CliError::ProblemsFound { .. }) => process::exit(1),
+        Err(cli_error) => {
+            eprintln!("error: {}", cli_error);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,454 @@
+//! Biome maps of world areas.
+//!
+//! [`export_biome_map`] samples biome of every block column of rectangle at chosen
+//! height. Biomes are read from sections since 1.18, from 3D `Biomes` array of 4×4×4
+//! cells since 1.15 and from per column `Biomes` array before that, numeric ids are
+//! mapped to names of 1.18. Map is a raw grid of biome names or legacy numeric ids,
+//! or an image colored like biome maps of seed viewers.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::biome_map::export_biome_map;
+//! use anvil_region::AnvilChunkProvider;
+//!
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//! let biome_map = export_biome_map(&chunk_provider, (64, 32), (79, 47), 64).unwrap();
+//!
+//! assert_eq!((biome_map.width, biome_map.length), (16, 16));
+//! assert!(biome_map.biome(64, 32).is_some());
+//! assert_eq!(biome_map.biome(0, 0), None);
+//! ```
+use crate::chunk::Chunk;
+use crate::section::{Section, SectionError, SectionFormat};
+use crate::tag::get_tag;
+use crate::upgrade::legacy::{biome_id, biome_name};
+use crate::{AnvilChunkProvider, ChunkLoadError};
+use nbt::Tag;
+use std::collections::HashMap;
+
+/// Colors of biomes without namespace, sorted by name.
+const BIOME_COLORS: &[(&str, [u8; 3])] = &[
+    ("badlands", [217, 69, 21]),
+    ("bamboo_jungle", [118, 142, 20]),
+    ("basalt_deltas", [64, 54, 54]),
+    ("beach", [250, 222, 85]),
+    ("birch_forest", [48, 116, 68]),
+    ("cherry_grove", [237, 178, 205]),
+    ("cold_ocean", [32, 32, 112]),
+    ("crimson_forest", [221, 8, 8]),
+    ("dark_forest", [64, 81, 26]),
+    ("deep_cold_ocean", [32, 32, 56]),
+    ("deep_dark", [3, 31, 41]),
+    ("deep_frozen_ocean", [64, 64, 144]),
+    ("deep_lukewarm_ocean", [0, 0, 64]),
+    ("deep_ocean", [0, 0, 48]),
+    ("desert", [250, 148, 24]),
+    ("dripstone_caves", [134, 96, 67]),
+    ("end_barrens", [128, 128, 255]),
+    ("end_highlands", [128, 128, 255]),
+    ("end_midlands", [128, 128, 255]),
+    ("eroded_badlands", [255, 109, 61]),
+    ("flower_forest", [45, 142, 73]),
+    ("forest", [5, 102, 33]),
+    ("frozen_ocean", [112, 112, 214]),
+    ("frozen_peaks", [160, 160, 223]),
+    ("frozen_river", [160, 160, 255]),
+    ("grove", [71, 114, 108]),
+    ("ice_spikes", [180, 220, 220]),
+    ("jagged_peaks", [220, 220, 200]),
+    ("jungle", [83, 123, 9]),
+    ("lukewarm_ocean", [0, 0, 144]),
+    ("lush_caves", [40, 60, 0]),
+    ("mangrove_swamp", [44, 204, 142]),
+    ("meadow", [96, 164, 69]),
+    ("mushroom_fields", [255, 0, 255]),
+    ("nether_wastes", [191, 59, 59]),
+    ("ocean", [0, 0, 112]),
+    ("old_growth_birch_forest", [88, 156, 108]),
+    ("old_growth_pine_taiga", [89, 102, 81]),
+    ("old_growth_spruce_taiga", [129, 142, 121]),
+    ("plains", [141, 179, 96]),
+    ("river", [0, 0, 255]),
+    ("savanna", [189, 178, 95]),
+    ("savanna_plateau", [167, 157, 100]),
+    ("small_end_islands", [128, 128, 255]),
+    ("snowy_beach", [250, 240, 192]),
+    ("snowy_plains", [255, 255, 255]),
+    ("snowy_slopes", [196, 196, 196]),
+    ("snowy_taiga", [49, 85, 74]),
+    ("soul_sand_valley", [82, 41, 33]),
+    ("sparse_jungle", [98, 139, 23]),
+    ("stony_peaks", [123, 143, 116]),
+    ("stony_shore", [162, 162, 132]),
+    ("sunflower_plains", [181, 219, 136]),
+    ("swamp", [7, 249, 178]),
+    ("taiga", [11, 102, 89]),
+    ("the_end", [128, 128, 255]),
+    ("the_void", [0, 0, 0]),
+    ("warm_ocean", [0, 0, 172]),
+    ("warped_forest", [73, 144, 123]),
+    ("windswept_forest", [34, 85, 28]),
+    ("windswept_gravelly_hills", [136, 136, 136]),
+    ("windswept_hills", [96, 96, 96]),
+    ("windswept_savanna", [229, 218, 135]),
+    ("wooded_badlands", [176, 151, 101]),
+];
+
+/// Possible errors while exporting biome map.
+#[derive(Debug)]
+pub enum BiomeMapError {
+    /// Chunk can't be loaded.
+    ChunkLoadError { chunk_load_error: ChunkLoadError },
+    /// Chunk section can't be read.
+    SectionError { section_error: SectionError },
+}
+
+impl From<ChunkLoadError> for BiomeMapError {
+    fn from(chunk_load_error: ChunkLoadError) -> Self {
+        BiomeMapError::ChunkLoadError { chunk_load_error }
+    }
+}
+
+impl From<SectionError> for BiomeMapError {
+    fn from(section_error: SectionError) -> Self {
+        BiomeMapError::SectionError { section_error }
+    }
+}
+
+/// Biomes of block columns of rectangle, columns of missing chunks have no biome.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BiomeMap {
+    /// Block X of the west edge.
+    pub min_x: i32,
+    /// Block Z of the north edge.
+    pub min_z: i32,
+    /// Amount of columns along X.
+    pub width: usize,
+    /// Amount of columns along Z.
+    pub length: usize,
+    palette: Vec<String>,
+    /// Palette indices row by row from north-west corner.
+    indices: Vec<Option<u16>>,
+}
+
+impl BiomeMap {
+    /// Returns names of biomes which are present on map.
+    pub fn palette(&self) -> &[String] {
+        &self.palette
+    }
+
+    /// Returns palette index of every column row by row from north-west corner.
+    pub fn indices(&self) -> &[Option<u16>] {
+        &self.indices
+    }
+
+    /// Returns biome of column at block X and Z, none if it's outside of map or
+    /// its chunk is missing.
+    pub fn biome(&self, x: i32, z: i32) -> Option<&str> {
+        if x < self.min_x || z < self.min_z {
+            return None;
+        }
+
+        let column_x = (x as i64 - self.min_x as i64) as usize;
+        let column_z = (z as i64 - self.min_z as i64) as usize;
+
+        if column_x >= self.width || column_z >= self.length {
+            return None;
+        }
+
+        let index = self.indices[column_z * self.width + column_x]?;
+
+        Some(self.palette[index as usize].as_str())
+    }
+
+    /// Returns numeric biome id used before 1.18 of every column.
+    ///
+    /// Biomes which were added in 1.18 and later have no id.
+    pub fn legacy_ids(&self) -> Vec<Option<i32>> {
+        let palette_ids: Vec<_> = self.palette.iter().map(|name| biome_id(name)).collect();
+
+        self.indices
+            .iter()
+            .map(|index| index.and_then(|index| palette_ids[index as usize]))
+            .collect()
+    }
+
+    /// Returns amount of columns of every biome in descending order.
+    pub fn counts(&self) -> Vec<(&str, usize)> {
+        let mut counts = vec![0; self.palette.len()];
+
+        for index in self.indices.iter().flatten() {
+            counts[*index as usize] += 1;
+        }
+
+        let mut counts: Vec<_> = self
+            .palette
+            .iter()
+            .map(|name| name.as_str())
+            .zip(counts)
+            .collect();
+
+        counts.sort_by(|(name, count), (other_name, other_count)| {
+            other_count.cmp(count).then(name.cmp(other_name))
+        });
+
+        counts
+    }
+
+    /// Returns RGBA pixels row by row, columns of missing chunks are transparent.
+    pub fn to_rgba(&self) -> Vec<u8> {
+        let palette_colors: Vec<_> = self.palette.iter().map(|name| biome_color(name)).collect();
+        let mut pixels = Vec::with_capacity(self.indices.len() * 4);
+
+        for index in &self.indices {
+            match index {
+                Some(index) => {
+                    pixels.extend_from_slice(&palette_colors[*index as usize]);
+                    pixels.push(255);
+                }
+                None => pixels.extend_from_slice(&[0; 4]),
+            }
+        }
+
+        pixels
+    }
+
+    /// Writes map as PNG image with one pixel per column.
+    #[cfg(feature = "render")]
+    pub fn write_png<W: std::io::Write>(&self, writer: W) -> Result<(), png::EncodingError> {
+        let mut encoder = png::Encoder::new(writer, self.width as u32, self.length as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.to_rgba())?;
+
+        writer.finish()
+    }
+}
+
+/// Returns map color of biome, biomes which aren't in table get color derived from name.
+pub fn biome_color(name: &str) -> [u8; 3] {
+    let short_name = name.strip_prefix("minecraft:").unwrap_or(name);
+
+    if let Ok(index) = BIOME_COLORS.binary_search_by(|(biome, _)| (*biome).cmp(short_name)) {
+        return BIOME_COLORS[index].1;
+    }
+
+    // FNV-1a keeps colors of modded biomes stable between exports.
+    let hash = name.bytes().fold(0x811c_9dc5_u32, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    });
+
+    [(hash >> 16) as u8, (hash >> 8) as u8, hash as u8]
+}
+
+/// Exports biomes of block columns between lowest and highest block X and Z inclusive
+/// at block Y.
+pub fn export_biome_map(
+    chunk_provider: &AnvilChunkProvider,
+    min: (i32, i32),
+    max: (i32, i32),
+    y: i32,
+) -> Result<BiomeMap, BiomeMapError> {
+    let (min_x, max_x) = (min.0.min(max.0), min.0.max(max.0));
+    let (min_z, max_z) = (min.1.min(max.1), min.1.max(max.1));
+    let width = (max_x as i64 - min_x as i64 + 1) as usize;
+    let length = (max_z as i64 - min_z as i64 + 1) as usize;
+
+    let mut palette = Vec::new();
+    let mut palette_indices: HashMap<String, u16> = HashMap::new();
+    let mut indices = vec![None; width * length];
+
+    for chunk_z in min_z >> 4..=max_z >> 4 {
+        for chunk_x in min_x >> 4..=max_x >> 4 {
+            let chunk = match chunk_provider.load_chunk(chunk_x, chunk_z) {
+                Ok(chunk_compound_tag) => Chunk::new(chunk_compound_tag),
+                Err(ChunkLoadError::RegionNotFound { .. })
+                | Err(ChunkLoadError::ChunkNotFound { .. }) => continue,
+                Err(chunk_load_error) => return Err(chunk_load_error.into()),
+            };
+
+            let column_biomes = column_biomes(&chunk, y)?;
+
+            for (column, biome) in column_biomes.into_iter().enumerate() {
+                let x = chunk_x * 16 + (column % 16) as i32;
+                let z = chunk_z * 16 + (column / 16) as i32;
+
+                let biome = match biome {
+                    Some(biome) if x >= min_x && x <= max_x && z >= min_z && z <= max_z => biome,
+                    _ => continue,
+                };
+
+                let palette_index = *palette_indices.entry(biome.clone()).or_insert_with(|| {
+                    palette.push(biome);
+                    (palette.len() - 1) as u16
+                });
+
+                let index = (z - min_z) as usize * width + (x - min_x) as usize;
+                indices[index] = Some(palette_index);
+            }
+        }
+    }
+
+    Ok(BiomeMap {
+        min_x,
+        min_z,
+        width,
+        length,
+        palette,
+        indices,
+    })
+}
+
+/// Returns biome of every column at block Y indexed by `z * 16 + x`.
+fn column_biomes(chunk: &Chunk, y: i32) -> Result<Vec<Option<String>>, SectionError> {
+    let legacy_name = |id: i32| match id {
+        // Columns which weren't generated yet.
+        -1 | 255 => None,
+        id => Some(biome_name(id).to_owned()),
+    };
+
+    match get_tag(chunk.data(), "Biomes") {
+        Some(Tag::ByteArray(biomes)) if biomes.len() == 256 => {
+            return Ok(biomes
+                .iter()
+                .map(|biome| legacy_name(*biome as u8 as i32))
+                .collect());
+        }
+        Some(Tag::IntArray(biomes)) if biomes.len() == 256 => {
+            return Ok(biomes.iter().map(|biome| legacy_name(*biome)).collect());
+        }
+        Some(Tag::IntArray(biomes)) if biomes.len() >= 1024 => {
+            let cell_y = ((y - chunk.height_range().min_y) >> 2).max(0) as usize;
+            let cell_y = cell_y.min(biomes.len() / 16 - 1);
+
+            return Ok((0..256)
+                .map(|column| {
+                    let (x, z) = (column % 16, column / 16);
+                    legacy_name(biomes[cell_y * 16 + (z >> 2) * 4 + (x >> 2)])
+                })
+                .collect());
+        }
+        _ => {}
+    }
+
+    if chunk.section_format().ok() != Some(SectionFormat::Modern) {
+        return Ok(vec![None; 256]);
+    }
+
+    let section_compound_tag = chunk
+        .sections()
+        .into_iter()
+        .find(|section| section.get_i8("Y").ok().map(i32::from) == Some(y >> 4));
+
+    let section = match section_compound_tag {
+        Some(section_compound_tag) => {
+            Section::from_compound_tag(section_compound_tag, SectionFormat::Modern)?
+        }
+        None => return Ok(vec![None; 256]),
+    };
+
+    Ok((0..256)
+        .map(|column| {
+            section
+                .biome(column % 16, (y & 15) as usize, column / 16)
+                .map(|biome| biome.to_owned())
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::biome_map::{biome_color, export_biome_map, BIOME_COLORS};
+    use crate::chunk::Chunk;
+    use crate::section::Section;
+    use crate::upgrade::upgrade_chunk;
+    use crate::version::LEVEL_WRAPPER_REMOVAL_DATA_VERSION;
+    use crate::AnvilChunkProvider;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_biome_colors_sorted() {
+        for pair in BIOME_COLORS.windows(2) {
+            assert!(pair[0].0 < pair[1].0, "{} is not sorted", pair[1].0);
+        }
+
+        assert_eq!(biome_color("minecraft:plains"), [141, 179, 96]);
+        assert_eq!(biome_color("mod:glade"), biome_color("mod:glade"));
+    }
+
+    #[test]
+    fn test_export_legacy_biomes() {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+        let biome_map = export_biome_map(&chunk_provider, (70, 40), (60, 30), 64).unwrap();
+
+        assert_eq!((biome_map.min_x, biome_map.min_z), (60, 30));
+        assert_eq!((biome_map.width, biome_map.length), (11, 11));
+        assert_eq!(biome_map.biome(59, 30), None);
+        assert_eq!(biome_map.biome(60, 41), None);
+
+        let biome = biome_map.biome(64, 32).unwrap().to_owned();
+        assert!(biome_map.legacy_ids().iter().all(|id| id.is_some()));
+
+        let counts = biome_map.counts();
+        assert_eq!(
+            counts.iter().map(|(_, count)| count).sum::<usize>(),
+            11 * 11
+        );
+        assert!(counts.iter().any(|(name, _)| *name == biome));
+
+        let pixels = biome_map.to_rgba();
+        assert_eq!(pixels.len(), 11 * 11 * 4);
+        assert!(pixels.chunks(4).all(|pixel| pixel[3] == 255));
+
+        // Region of chunks doesn't exist.
+        let biome_map = export_biome_map(&chunk_provider, (1000, 1000), (1003, 1003), 64).unwrap();
+        assert!(biome_map.indices().iter().all(|index| index.is_none()));
+        assert!(biome_map.palette().is_empty());
+    }
+
+    #[test]
+    fn test_export_section_biomes() {
+        let temp_dir = TempDir::new().unwrap();
+        let fixture_provider = AnvilChunkProvider::new("test/region");
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+
+        let mut chunk_compound_tag = fixture_provider.load_chunk(4, 2).unwrap();
+        upgrade_chunk(&mut chunk_compound_tag, LEVEL_WRAPPER_REMOVAL_DATA_VERSION).unwrap();
+
+        let mut chunk = Chunk::new(chunk_compound_tag);
+        let mut sections = chunk.read_sections().unwrap();
+        let section: &mut Section = sections.iter_mut().find(|section| section.y == 4).unwrap();
+        section.set_biome(0, 0, 0, "minecraft:cherry_grove");
+        chunk.write_section(section).unwrap();
+
+        chunk_provider
+            .save_chunk(4, 2, chunk.into_compound_tag())
+            .unwrap();
+
+        let biome_map = export_biome_map(&chunk_provider, (64, 32), (79, 47), 64).unwrap();
+        assert_eq!(biome_map.biome(64, 32), Some("minecraft:cherry_grove"));
+        assert_eq!(biome_map.legacy_ids()[0], None);
+        assert!(biome_map.biome(68, 32).is_some());
+
+        let biome_map = export_biome_map(&chunk_provider, (64, 32), (79, 47), 60).unwrap();
+        assert_ne!(biome_map.biome(64, 32), Some("minecraft:cherry_grove"));
+    }
+
+    #[cfg(feature = "render")]
+    #[test]
+    fn test_write_png() {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+        let biome_map = export_biome_map(&chunk_provider, (64, 32), (95, 47), 64).unwrap();
+
+        let mut png = Vec::new();
+        biome_map.write_png(&mut png).unwrap();
+
+        let decoder = png::Decoder::new(png.as_slice());
+        let reader = decoder.read_info().unwrap();
+        assert_eq!(reader.info().width, 32);
+        assert_eq!(reader.info().height, 16);
+    }
+}
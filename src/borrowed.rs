@@ -0,0 +1,436 @@
+//! Parsing chunk NBT into tags which borrow decompressed chunk data.
+//!
+//! Owned `CompoundTag` allocates string for every tag name and vector for every array.
+//! [`BorrowedCompound`] references names, strings and arrays inside of decompressed data
+//! instead, which suits read only analysis of many chunks. Int and long arrays are kept
+//! in big endian byte order and decoded when iterated.
+//!
+//! Chunk data is decompressed into buffers of thread, so chunk loaded with
+//! `AnvilChunkProvider::load_chunk_borrowed` is only available inside of closure.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::AnvilChunkProvider;
+//!
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//!
+//! let x_position = chunk_provider
+//!     .load_chunk_borrowed(4, 2, |chunk_compound| {
+//!         chunk_compound
+//!             .get_compound("Level")
+//!             .and_then(|level_compound| level_compound.get_i32("xPos"))
+//!     })
+//!     .unwrap();
+//!
+//! assert_eq!(x_position, Some(4));
+//! ```
+use byteorder::{BigEndian, ByteOrder, ReadBytesExt};
+use nbt::decode::TagDecodeError;
+use std::borrow::Cow;
+use std::io;
+
+/// Maximum nesting of compounds and lists, deeper data is rejected as invalid.
+const MAXIMUM_DEPTH: usize = 512;
+
+const END_TAG_TYPE: u8 = 0;
+const BYTE_TAG_TYPE: u8 = 1;
+const SHORT_TAG_TYPE: u8 = 2;
+const INT_TAG_TYPE: u8 = 3;
+const LONG_TAG_TYPE: u8 = 4;
+const FLOAT_TAG_TYPE: u8 = 5;
+const DOUBLE_TAG_TYPE: u8 = 6;
+const BYTE_ARRAY_TAG_TYPE: u8 = 7;
+const STRING_TAG_TYPE: u8 = 8;
+const LIST_TAG_TYPE: u8 = 9;
+const COMPOUND_TAG_TYPE: u8 = 10;
+const INT_ARRAY_TAG_TYPE: u8 = 11;
+const LONG_ARRAY_TAG_TYPE: u8 = 12;
+
+/// Tag which references data it was parsed from.
+#[derive(Debug, Clone)]
+pub enum BorrowedTag<'a> {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(&'a [u8]),
+    /// String which is only owned if it isn't valid UTF-8.
+    String(Cow<'a, str>),
+    List(Vec<BorrowedTag<'a>>),
+    Compound(BorrowedCompound<'a>),
+    /// Big endian ints.
+    IntArray(&'a [u8]),
+    /// Big endian longs.
+    LongArray(&'a [u8]),
+}
+
+/// Compound tag which references data it was parsed from.
+#[derive(Debug, Clone, Default)]
+pub struct BorrowedCompound<'a> {
+    /// Tags in order of data.
+    tags: Vec<(Cow<'a, str>, BorrowedTag<'a>)>,
+}
+
+impl<'a> BorrowedCompound<'a> {
+    /// Parses root compound tag of uncompressed NBT data.
+    pub fn parse(data: &'a [u8]) -> Result<Self, TagDecodeError> {
+        Ok(BorrowedCompound::parse_prefix(data)?.0)
+    }
+
+    /// Parses root compound tag at the start of data, returns amount of bytes after it.
+    pub(crate) fn parse_prefix(data: &'a [u8]) -> Result<(Self, usize), TagDecodeError> {
+        let mut parser = Parser { data };
+        let tag_type = parser.data.read_u8()?;
+
+        if tag_type != COMPOUND_TAG_TYPE {
+            return Err(invalid_data("Root must be compound tag").into());
+        }
+
+        parser.read_string()?;
+        let compound = parser.read_compound(0)?;
+
+        Ok((compound, parser.data.len()))
+    }
+
+    /// Returns tag with specified name.
+    pub fn get(&self, name: &str) -> Option<&BorrowedTag<'a>> {
+        self.tags
+            .iter()
+            .find(|(tag_name, _)| tag_name == name)
+            .map(|(_, tag)| tag)
+    }
+
+    pub fn get_i8(&self, name: &str) -> Option<i8> {
+        match self.get(name)? {
+            BorrowedTag::Byte(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_i16(&self, name: &str) -> Option<i16> {
+        match self.get(name)? {
+            BorrowedTag::Short(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_i32(&self, name: &str) -> Option<i32> {
+        match self.get(name)? {
+            BorrowedTag::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_i64(&self, name: &str) -> Option<i64> {
+        match self.get(name)? {
+            BorrowedTag::Long(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_f32(&self, name: &str) -> Option<f32> {
+        match self.get(name)? {
+            BorrowedTag::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_f64(&self, name: &str) -> Option<f64> {
+        match self.get(name)? {
+            BorrowedTag::Double(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_str(&self, name: &str) -> Option<&str> {
+        match self.get(name)? {
+            BorrowedTag::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_byte_array(&self, name: &str) -> Option<&'a [u8]> {
+        match self.get(name)? {
+            BorrowedTag::ByteArray(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns ints of array decoded while iterated.
+    pub fn get_int_array(&self, name: &str) -> Option<impl ExactSizeIterator<Item = i32> + 'a> {
+        match self.get(name)? {
+            BorrowedTag::IntArray(data) => Some(data.chunks_exact(4).map(BigEndian::read_i32)),
+            _ => None,
+        }
+    }
+
+    /// Returns longs of array decoded while iterated.
+    pub fn get_long_array(&self, name: &str) -> Option<impl ExactSizeIterator<Item = i64> + 'a> {
+        match self.get(name)? {
+            BorrowedTag::LongArray(data) => Some(data.chunks_exact(8).map(BigEndian::read_i64)),
+            _ => None,
+        }
+    }
+
+    pub fn get_compound(&self, name: &str) -> Option<&BorrowedCompound<'a>> {
+        match self.get(name)? {
+            BorrowedTag::Compound(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_list(&self, name: &str) -> Option<&[BorrowedTag<'a>]> {
+        match self.get(name)? {
+            BorrowedTag::List(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns compound tags which are stored inside list with specified name.
+    pub fn get_compound_list(&self, name: &str) -> impl Iterator<Item = &BorrowedCompound<'a>> {
+        self.get_list(name)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|tag| match tag {
+                BorrowedTag::Compound(compound) => Some(compound),
+                _ => None,
+            })
+    }
+
+    /// Returns names and tags in order of data.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &BorrowedTag<'a>)> {
+        self.tags.iter().map(|(name, tag)| (name.as_ref(), tag))
+    }
+
+    pub fn len(&self) -> usize {
+        self.tags.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+}
+
+struct Parser<'a> {
+    /// Data which isn't parsed yet.
+    data: &'a [u8],
+}
+
+impl<'a> Parser<'a> {
+    fn read_compound(&mut self, depth: usize) -> Result<BorrowedCompound<'a>, TagDecodeError> {
+        let mut tags = Vec::new();
+
+        loop {
+            let tag_type = self.data.read_u8()?;
+
+            if tag_type == END_TAG_TYPE {
+                return Ok(BorrowedCompound { tags });
+            }
+
+            let name = self.read_string()?;
+            let tag = self.read_tag(tag_type, depth + 1)?;
+
+            tags.push((name, tag));
+        }
+    }
+
+    fn read_tag(&mut self, tag_type: u8, depth: usize) -> Result<BorrowedTag<'a>, TagDecodeError> {
+        if depth > MAXIMUM_DEPTH {
+            return Err(invalid_data("Tags are nested too deep").into());
+        }
+
+        let tag = match tag_type {
+            BYTE_TAG_TYPE => BorrowedTag::Byte(self.data.read_i8()?),
+            SHORT_TAG_TYPE => BorrowedTag::Short(self.data.read_i16::<BigEndian>()?),
+            INT_TAG_TYPE => BorrowedTag::Int(self.data.read_i32::<BigEndian>()?),
+            LONG_TAG_TYPE => BorrowedTag::Long(self.data.read_i64::<BigEndian>()?),
+            FLOAT_TAG_TYPE => BorrowedTag::Float(self.data.read_f32::<BigEndian>()?),
+            DOUBLE_TAG_TYPE => BorrowedTag::Double(self.data.read_f64::<BigEndian>()?),
+            BYTE_ARRAY_TAG_TYPE => BorrowedTag::ByteArray(self.read_array(1)?),
+            STRING_TAG_TYPE => BorrowedTag::String(self.read_string()?),
+            LIST_TAG_TYPE => {
+                let element_type = self.data.read_u8()?;
+                let length = self.read_length()?;
+
+                // Empty lists are written with end tag type.
+                if length > 0 && element_type == END_TAG_TYPE {
+                    return Err(TagDecodeError::UnknownTagType {
+                        tag_type_id: element_type,
+                    });
+                }
+
+                // Each element takes at least one byte, except of empty compounds.
+                let mut tags = Vec::with_capacity(length.min(self.data.len()));
+
+                for _ in 0..length {
+                    tags.push(self.read_tag(element_type, depth + 1)?);
+                }
+
+                BorrowedTag::List(tags)
+            }
+            COMPOUND_TAG_TYPE => BorrowedTag::Compound(self.read_compound(depth)?),
+            INT_ARRAY_TAG_TYPE => BorrowedTag::IntArray(self.read_array(4)?),
+            LONG_ARRAY_TAG_TYPE => BorrowedTag::LongArray(self.read_array(8)?),
+            tag_type_id => return Err(TagDecodeError::UnknownTagType { tag_type_id }),
+        };
+
+        Ok(tag)
+    }
+
+    fn read_string(&mut self) -> Result<Cow<'a, str>, io::Error> {
+        let length = self.data.read_u16::<BigEndian>()?;
+        let bytes = self.take(length as usize)?;
+
+        // Names and strings are modified UTF-8 which is valid UTF-8 for almost every text.
+        Ok(String::from_utf8_lossy(bytes))
+    }
+
+    /// Reads array with length prefix and elements of specified length.
+    fn read_array(&mut self, element_length: usize) -> Result<&'a [u8], io::Error> {
+        let length = self.read_length()?;
+        let bytes_length = length
+            .checked_mul(element_length)
+            .ok_or_else(|| invalid_data("Array length overflows"))?;
+
+        self.take(bytes_length)
+    }
+
+    fn read_length(&mut self) -> Result<usize, io::Error> {
+        let length = self.data.read_i32::<BigEndian>()?;
+
+        if length < 0 {
+            return Err(invalid_data("Negative length"));
+        }
+
+        Ok(length as usize)
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], io::Error> {
+        if length > self.data.len() {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+
+        let (bytes, data) = self.data.split_at(length);
+        self.data = data;
+
+        Ok(bytes)
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::borrowed::{BorrowedCompound, BorrowedTag};
+    use nbt::decode::TagDecodeError;
+    use nbt::encode::write_compound_tag;
+    use nbt::CompoundTag;
+
+    fn encode(compound_tag: &CompoundTag) -> Vec<u8> {
+        let mut data = Vec::new();
+        write_compound_tag(&mut data, compound_tag).unwrap();
+
+        data
+    }
+
+    #[test]
+    fn test_parse() {
+        let mut section_compound_tag = CompoundTag::new();
+        section_compound_tag.insert_i8("Y", -2);
+        section_compound_tag.insert_i64_vec("BlockStates", vec![1, -1, i64::MAX]);
+
+        let mut level_compound_tag = CompoundTag::new();
+        level_compound_tag.insert_i32("xPos", 4);
+        level_compound_tag.insert_i16("Short", 300);
+        level_compound_tag.insert_i64("LastUpdate", 1 << 40);
+        level_compound_tag.insert_f32("Float", 0.5);
+        level_compound_tag.insert_f64("Double", -0.25);
+        level_compound_tag.insert_str("Status", "full");
+        level_compound_tag.insert_i8_vec("Biomes", vec![1, 2, -3]);
+        level_compound_tag.insert_i32_vec("HeightMap", vec![64, -64]);
+        level_compound_tag.insert_compound_tag_vec("Sections", vec![section_compound_tag]);
+        level_compound_tag.insert_compound_tag_vec("Entities", vec![]);
+
+        let mut compound_tag = CompoundTag::new();
+        compound_tag.insert_compound_tag("Level", level_compound_tag);
+
+        let data = encode(&compound_tag);
+        let compound = BorrowedCompound::parse(&data).unwrap();
+        let level_compound = compound.get_compound("Level").unwrap();
+
+        assert_eq!(level_compound.len(), 10);
+        assert_eq!(level_compound.get_i32("xPos"), Some(4));
+        assert_eq!(level_compound.get_i16("Short"), Some(300));
+        assert_eq!(level_compound.get_i64("LastUpdate"), Some(1 << 40));
+        assert_eq!(level_compound.get_f32("Float"), Some(0.5));
+        assert_eq!(level_compound.get_f64("Double"), Some(-0.25));
+        assert_eq!(level_compound.get_str("Status"), Some("full"));
+        assert_eq!(
+            level_compound.get_byte_array("Biomes"),
+            Some(&[1, 2, 253][..])
+        );
+        assert_eq!(
+            level_compound
+                .get_int_array("HeightMap")
+                .unwrap()
+                .collect::<Vec<_>>(),
+            vec![64, -64]
+        );
+        assert_eq!(level_compound.get_compound_list("Entities").count(), 0);
+        assert!(level_compound.get_i32("Status").is_none());
+
+        // Strings are borrowed from data.
+        match level_compound.get("Status") {
+            Some(BorrowedTag::String(value)) => {
+                assert!(matches!(value, std::borrow::Cow::Borrowed(_)))
+            }
+            tag => panic!("Expected string tag but got `{:?}`", tag),
+        }
+
+        let section_compound = level_compound.get_compound_list("Sections").next().unwrap();
+
+        assert_eq!(section_compound.get_i8("Y"), Some(-2));
+        assert_eq!(
+            section_compound
+                .get_long_array("BlockStates")
+                .unwrap()
+                .collect::<Vec<_>>(),
+            vec![1, -1, i64::MAX]
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        let mut compound_tag = CompoundTag::new();
+        compound_tag.insert_i32_vec("HeightMap", vec![64; 16]);
+
+        let data = encode(&compound_tag);
+
+        match BorrowedCompound::parse(&data[..data.len() - 10]) {
+            Err(TagDecodeError::IOError { .. }) => {}
+            result => panic!("Expected `IOError` but got `{:?}`", result),
+        }
+
+        match BorrowedCompound::parse(&[10, 0, 0, 13, 0, 0, 0]) {
+            Err(TagDecodeError::UnknownTagType { tag_type_id }) => assert_eq!(tag_type_id, 13),
+            result => panic!("Expected `UnknownTagType` but got `{:?}`", result),
+        }
+
+        // Root must be compound.
+        assert!(BorrowedCompound::parse(&[1, 0, 0, 5]).is_err());
+
+        // List of lists nested deeper than limit.
+        let mut data = vec![10, 0, 0, 9, 0, 0];
+
+        for _ in 0..1000 {
+            data.extend_from_slice(&[9, 0, 0, 0, 1]);
+        }
+
+        assert!(BorrowedCompound::parse(&data).is_err());
+    }
+}
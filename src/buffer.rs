@@ -0,0 +1,340 @@
+//! Buffers reused by chunk reads of thread.
+//!
+//! Reading chunk needs buffer for compressed data, buffer for decompressed data and
+//! zlib state, which are kept per thread between reads instead of being allocated for
+//! every chunk. Buffers which grew above [`MAXIMUM_RETAINED_CAPACITY`] are released
+//! after read, so single huge chunk doesn't keep memory for the thread lifetime.
+use crate::limits::{exceeds_nbt_depth, LimitExceeded, ParseLimits};
+use crate::parse::ParseIssue;
+#[cfg(feature = "zstd")]
+use crate::CUSTOM_COMPRESSION_TYPE;
+use crate::{ChunkLoadError, GZIP_COMPRESSION_TYPE, ZLIB_COMPRESSION_TYPE};
+use flate2::bufread::GzDecoder;
+use flate2::{Decompress, FlushDecompress, Status};
+use nbt::decode::{read_compound_tag, TagDecodeError};
+use nbt::CompoundTag;
+use std::cell::RefCell;
+use std::io;
+use std::io::{Cursor, Read};
+
+/// Maximum capacity of buffer kept between reads.
+const MAXIMUM_RETAINED_CAPACITY: usize = 4 * 1024 * 1024;
+/// Minimum amount of bytes reserved when decompressed buffer is full.
+const MINIMUM_RESERVE_BYTES_LENGTH: usize = 64 * 1024;
+
+thread_local! {
+    static CHUNK_BUFFERS: RefCell<ChunkBuffers> = RefCell::new(ChunkBuffers::new());
+}
+
+pub(crate) struct ChunkBuffers {
+    /// Compressed chunk data read from region file.
+    pub(crate) compressed: Vec<u8>,
+    /// Decompressed chunk data.
+    decompressed: Vec<u8>,
+    /// Zlib state, almost every chunk is zlib compressed.
+    zlib: Decompress,
+}
+
+impl ChunkBuffers {
+    fn new() -> Self {
+        ChunkBuffers {
+            compressed: Vec::new(),
+            decompressed: Vec::new(),
+            zlib: Decompress::new(true),
+        }
+    }
+
+    /// Decodes chunk from compressed data buffer.
+    pub(crate) fn decode_compressed(
+        &mut self,
+        compression_scheme: u8,
+    ) -> Result<CompoundTag, ChunkLoadError> {
+        decode(
+            &mut self.zlib,
+            &mut self.decompressed,
+            compression_scheme,
+            &self.compressed,
+        )
+    }
+
+    /// Decodes chunk from compressed data buffer, data following compressed data and
+    /// root tag is added to issues.
+    pub(crate) fn decode_compressed_checked(
+        &mut self,
+        compression_scheme: u8,
+        parse_limits: &ParseLimits,
+        parse_issues: &mut Vec<ParseIssue>,
+    ) -> Result<CompoundTag, ChunkLoadError> {
+        let data = self.decompress_compressed(compression_scheme, parse_limits, parse_issues)?;
+        let mut cursor = Cursor::new(data);
+        let compound_tag = read_compound_tag(&mut cursor)?;
+        let trailing_length = data.len() - cursor.position() as usize;
+
+        if trailing_length > 0 {
+            parse_issues.push(ParseIssue::TrailingTagData {
+                length: trailing_length,
+            });
+        }
+
+        Ok(compound_tag)
+    }
+
+    /// Decompresses chunk from compressed data buffer, returns decompressed data.
+    ///
+    /// Data following compressed data is added to issues. Data which exceeds length or
+    /// depth limit is rejected before it's decoded.
+    pub(crate) fn decompress_compressed(
+        &mut self,
+        compression_scheme: u8,
+        parse_limits: &ParseLimits,
+        parse_issues: &mut Vec<ParseIssue>,
+    ) -> Result<&[u8], ChunkLoadError> {
+        let trailing_length = decompress(
+            &mut self.zlib,
+            &mut self.decompressed,
+            compression_scheme,
+            &self.compressed,
+            parse_limits.max_chunk_length,
+        )?;
+
+        if let Some(max_nbt_depth) = parse_limits.max_nbt_depth {
+            if exceeds_nbt_depth(&self.decompressed, max_nbt_depth) {
+                return Err(LimitExceeded::NbtDepth { max_nbt_depth }.into());
+            }
+        }
+
+        if trailing_length > 0 {
+            parse_issues.push(ParseIssue::TrailingCompressedData {
+                length: trailing_length,
+            });
+        }
+
+        Ok(&self.decompressed)
+    }
+
+    /// Releases buffers which grew too big and clears the others.
+    fn release(&mut self) {
+        for buffer in [&mut self.compressed, &mut self.decompressed] {
+            if buffer.capacity() > MAXIMUM_RETAINED_CAPACITY {
+                *buffer = Vec::new();
+            } else {
+                buffer.clear();
+            }
+        }
+    }
+}
+
+/// Runs operation with buffers of current thread.
+///
+/// Nested call gets new buffers.
+pub(crate) fn with_chunk_buffers<T>(operation: impl FnOnce(&mut ChunkBuffers) -> T) -> T {
+    CHUNK_BUFFERS.with(|chunk_buffers| match chunk_buffers.try_borrow_mut() {
+        Ok(mut chunk_buffers) => {
+            let output = operation(&mut chunk_buffers);
+            chunk_buffers.release();
+
+            output
+        }
+        Err(_) => operation(&mut ChunkBuffers::new()),
+    })
+}
+
+/// Decodes chunk compound tag compressed with specified scheme using buffers of thread.
+pub(crate) fn decode_chunk(
+    compression_scheme: u8,
+    compressed_buffer: &[u8],
+) -> Result<CompoundTag, ChunkLoadError> {
+    with_chunk_buffers(|chunk_buffers| {
+        decode(
+            &mut chunk_buffers.zlib,
+            &mut chunk_buffers.decompressed,
+            compression_scheme,
+            compressed_buffer,
+        )
+    })
+}
+
+fn decode(
+    zlib: &mut Decompress,
+    decompressed: &mut Vec<u8>,
+    compression_scheme: u8,
+    compressed_buffer: &[u8],
+) -> Result<CompoundTag, ChunkLoadError> {
+    decompress(
+        zlib,
+        decompressed,
+        compression_scheme,
+        compressed_buffer,
+        None,
+    )?;
+
+    Ok(read_compound_tag(&mut Cursor::new(
+        decompressed.as_slice(),
+    ))?)
+}
+
+/// Decompresses chunk data compressed with specified scheme replacing buffer contents.
+///
+/// Returns amount of bytes which follow the end of compressed data.
+fn decompress(
+    zlib: &mut Decompress,
+    decompressed: &mut Vec<u8>,
+    compression_scheme: u8,
+    compressed_buffer: &[u8],
+    max_chunk_length: Option<usize>,
+) -> Result<usize, ChunkLoadError> {
+    decompressed.clear();
+
+    let max_length = max_chunk_length.unwrap_or(usize::MAX);
+
+    let decompress_result = match compression_scheme {
+        GZIP_COMPRESSION_TYPE => {
+            let mut input = compressed_buffer;
+
+            // Byte over maximum tells that data is longer than maximum.
+            GzDecoder::new(&mut input)
+                .take(max_length.saturating_add(1) as u64)
+                .read_to_end(decompressed)
+                .map(|_| input.len())
+        }
+        ZLIB_COMPRESSION_TYPE => inflate_zlib(zlib, compressed_buffer, decompressed, max_length)
+            .map(|_| compressed_buffer.len() - zlib.total_in() as usize),
+        #[cfg(feature = "zstd")]
+        CUSTOM_COMPRESSION_TYPE => Ok(crate::zstd_dictionary::decompress(
+            compressed_buffer,
+            decompressed,
+            max_length,
+        )?),
+        _ => return Err(ChunkLoadError::UnsupportedCompressionScheme { compression_scheme }),
+    };
+
+    if let Some(max_chunk_length) = max_chunk_length {
+        if decompressed.len() > max_chunk_length {
+            return Err(LimitExceeded::ChunkLength { max_chunk_length }.into());
+        }
+    }
+
+    // Corrupted compressed data is decode error, same as when tag is read from decoder.
+    Ok(decompress_result.map_err(TagDecodeError::from)?)
+}
+
+/// Decompresses zlib stream into buffer resetting zlib state first.
+///
+/// Stops once buffer is longer than maximum length.
+fn inflate_zlib(
+    zlib: &mut Decompress,
+    compressed_buffer: &[u8],
+    decompressed: &mut Vec<u8>,
+    max_length: usize,
+) -> Result<(), io::Error> {
+    zlib.reset(true);
+
+    loop {
+        if decompressed.len() > max_length {
+            return Ok(());
+        }
+
+        if decompressed.len() == decompressed.capacity() {
+            decompressed.reserve(MINIMUM_RESERVE_BYTES_LENGTH.max(compressed_buffer.len() * 4));
+        }
+
+        let total_in = zlib.total_in();
+        let total_out = zlib.total_out();
+        let input = compressed_buffer
+            .get(total_in as usize..)
+            .unwrap_or_default();
+
+        let status = zlib.decompress_vec(input, decompressed, FlushDecompress::None)?;
+
+        if status == Status::StreamEnd {
+            return Ok(());
+        }
+
+        let has_progress = zlib.total_in() != total_in || zlib.total_out() != total_out;
+
+        // Output has space left, so stream can't continue without more input.
+        if !has_progress && decompressed.len() < decompressed.capacity() {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::buffer::{decode_chunk, with_chunk_buffers, MAXIMUM_RETAINED_CAPACITY};
+    use crate::{ChunkLoadError, GZIP_COMPRESSION_TYPE, ZLIB_COMPRESSION_TYPE};
+    use nbt::encode::{write_gzip_compound_tag, write_zlib_compound_tag};
+    use nbt::CompoundTag;
+
+    fn compound_tag() -> CompoundTag {
+        let mut compound_tag = CompoundTag::new();
+        compound_tag.insert_i32("xPos", 4);
+        compound_tag.insert_i8_vec("Data", vec![7; 200_000]);
+
+        compound_tag
+    }
+
+    #[test]
+    fn test_decode_chunk() {
+        let mut zlib_buffer = Vec::new();
+        write_zlib_compound_tag(&mut zlib_buffer, &compound_tag()).unwrap();
+
+        let mut gzip_buffer = Vec::new();
+        write_gzip_compound_tag(&mut gzip_buffer, &compound_tag()).unwrap();
+
+        for _ in 0..2 {
+            let compound_tag = decode_chunk(ZLIB_COMPRESSION_TYPE, &zlib_buffer).unwrap();
+            assert_eq!(compound_tag.get_i32("xPos").unwrap(), 4);
+
+            let compound_tag = decode_chunk(GZIP_COMPRESSION_TYPE, &gzip_buffer).unwrap();
+            assert_eq!(compound_tag.get_i8_vec("Data").unwrap().len(), 200_000);
+        }
+    }
+
+    #[test]
+    fn test_decode_truncated_chunk() {
+        let mut zlib_buffer = Vec::new();
+        write_zlib_compound_tag(&mut zlib_buffer, &compound_tag()).unwrap();
+        zlib_buffer.truncate(zlib_buffer.len() / 2);
+
+        match decode_chunk(ZLIB_COMPRESSION_TYPE, &zlib_buffer) {
+            Err(ChunkLoadError::TagDecodeError { .. }) => {}
+            result => panic!("Expected `TagDecodeError` but got `{:?}`", result),
+        }
+
+        match decode_chunk(9, &zlib_buffer) {
+            Err(ChunkLoadError::UnsupportedCompressionScheme { compression_scheme }) => {
+                assert_eq!(compression_scheme, 9)
+            }
+            result => panic!(
+                "Expected `UnsupportedCompressionScheme` but got `{:?}`",
+                result
+            ),
+        }
+    }
+
+    #[test]
+    fn test_buffers_reused() {
+        with_chunk_buffers(|chunk_buffers| chunk_buffers.compressed.reserve(1024));
+
+        let capacity = with_chunk_buffers(|chunk_buffers| {
+            // Nested call can't borrow buffers of thread.
+            let nested_capacity =
+                with_chunk_buffers(|chunk_buffers| chunk_buffers.compressed.capacity());
+            assert_eq!(nested_capacity, 0);
+
+            chunk_buffers.compressed.capacity()
+        });
+
+        assert!(capacity >= 1024);
+
+        with_chunk_buffers(|chunk_buffers| {
+            chunk_buffers
+                .compressed
+                .reserve(MAXIMUM_RETAINED_CAPACITY + 1)
+        });
+
+        let capacity = with_chunk_buffers(|chunk_buffers| chunk_buffers.compressed.capacity());
+        assert_eq!(capacity, 0);
+    }
+}
@@ -0,0 +1,72 @@
+//! Cancellation of long-running world operations.
+//!
+//! Token is cloned into operation, while another thread, for example GUI or signal
+//! handler, cancels it. Operations check token before every chunk, so they stop
+//! after the chunk which is being processed and leave region files consistent.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::cancel::CancellationToken;
+//! use anvil_region::copy::{copy_provider_cancellable, CopyError};
+//! use anvil_region::AnvilChunkProvider;
+//! use tempfile::TempDir;
+//!
+//! let target_dir = TempDir::new().unwrap();
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//! let target_chunk_provider = AnvilChunkProvider::new(target_dir.path().to_str().unwrap());
+//!
+//! let cancellation_token = CancellationToken::new();
+//! cancellation_token.cancel();
+//!
+//! let copy_result = copy_provider_cancellable(
+//!     &chunk_provider,
+//!     &target_chunk_provider,
+//!     &cancellation_token,
+//!     |_, _, chunk_compound_tag| Some(chunk_compound_tag),
+//! );
+//!
+//! assert!(matches!(copy_result, Err(CopyError::Cancelled)));
+//! ```
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Shared flag which tells operations to stop.
+///
+/// Clones share the same flag, once cancelled token stays cancelled.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        CancellationToken::default()
+    }
+
+    /// Requests operations which use this token to stop.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cancel::CancellationToken;
+
+    #[test]
+    fn test_cancel_clone() {
+        let cancellation_token = CancellationToken::new();
+        let cloned_cancellation_token = cancellation_token.clone();
+
+        assert!(!cloned_cancellation_token.is_cancelled());
+
+        cancellation_token.cancel();
+
+        assert!(cloned_cancellation_token.is_cancelled());
+    }
+}
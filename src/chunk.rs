@@ -0,0 +1,481 @@
+//! Version agnostic access to chunk data.
+//!
+//! Before 1.18 chunk data is nested in `Level` compound tag and some tags have
+//! upper camel case names, since 1.18 chunk data is stored at root with snake case names.
+//! [`Chunk`] hides these differences.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::chunk::{Chunk, ChunkTag};
+//! use anvil_region::AnvilChunkProvider;
+//!
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//! let chunk = Chunk::new(chunk_provider.load_chunk(4, 2).unwrap());
+//!
+//! assert_eq!(chunk.position().unwrap(), (4, 2));
+//! assert_eq!(chunk.tag_name(ChunkTag::Sections), "Sections");
+//! assert!(!chunk.sections().is_empty());
+//! ```
+use crate::tag::{compound_tags, compound_tags_mut, get_tag, get_tag_mut};
+use crate::version::{CHUNK_STATUS_RENAME_DATA_VERSION, LEVEL_WRAPPER_REMOVAL_DATA_VERSION};
+use nbt::{CompoundTag, CompoundTagError, Tag};
+
+/// Name of compound tag which contains chunk data before 1.18.
+const LEVEL_TAG_NAME: &str = "Level";
+
+/// Chunk tags which names or location differ between formats.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ChunkTag {
+    /// List of sections.
+    Sections,
+    /// List of block entities.
+    BlockEntities,
+    /// List of entities. Since 1.17 entities are stored in separate region files.
+    Entities,
+    /// List of scheduled block ticks.
+    BlockTicks,
+    /// List of scheduled fluid ticks.
+    FluidTicks,
+    /// Structure starts and references.
+    Structures,
+    /// Structure starts inside of structures tag.
+    StructureStarts,
+    /// Structure references inside of structures tag.
+    StructureReferences,
+}
+
+impl ChunkTag {
+    /// Returns tag name in chunk with Level wrapper.
+    pub fn legacy_name(self) -> &'static str {
+        match self {
+            ChunkTag::Sections => "Sections",
+            ChunkTag::BlockEntities => "TileEntities",
+            ChunkTag::Entities => "Entities",
+            ChunkTag::BlockTicks => "TileTicks",
+            ChunkTag::FluidTicks => "LiquidTicks",
+            ChunkTag::Structures => "Structures",
+            ChunkTag::StructureStarts => "Starts",
+            ChunkTag::StructureReferences => "References",
+        }
+    }
+
+    /// Returns tag name in chunk without Level wrapper.
+    pub fn name(self) -> &'static str {
+        match self {
+            ChunkTag::Sections => "sections",
+            ChunkTag::BlockEntities => "block_entities",
+            ChunkTag::Entities => "entities",
+            ChunkTag::BlockTicks => "block_ticks",
+            ChunkTag::FluidTicks => "fluid_ticks",
+            ChunkTag::Structures => "structures",
+            ChunkTag::StructureStarts => "starts",
+            ChunkTag::StructureReferences => "References",
+        }
+    }
+}
+
+/// Generation stage of the chunk in order of generation.
+///
+/// Chunks which are not fully generated are called proto chunks and may miss
+/// sections, light or block entities.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum ChunkStatus {
+    Empty,
+    StructureStarts,
+    StructureReferences,
+    Biomes,
+    Noise,
+    Surface,
+    Carvers,
+    LiquidCarvers,
+    Features,
+    InitializeLight,
+    Light,
+    Spawn,
+    Heightmaps,
+    Full,
+}
+
+/// Chunk statuses with names used since 1.14.
+const CHUNK_STATUS_NAMES: &[(ChunkStatus, &str)] = &[
+    (ChunkStatus::Empty, "empty"),
+    (ChunkStatus::StructureStarts, "structure_starts"),
+    (ChunkStatus::StructureReferences, "structure_references"),
+    (ChunkStatus::Biomes, "biomes"),
+    (ChunkStatus::Noise, "noise"),
+    (ChunkStatus::Surface, "surface"),
+    (ChunkStatus::Carvers, "carvers"),
+    (ChunkStatus::LiquidCarvers, "liquid_carvers"),
+    (ChunkStatus::Features, "features"),
+    (ChunkStatus::InitializeLight, "initialize_light"),
+    (ChunkStatus::Light, "light"),
+    (ChunkStatus::Spawn, "spawn"),
+    (ChunkStatus::Heightmaps, "heightmaps"),
+    (ChunkStatus::Full, "full"),
+];
+
+/// Chunk statuses with names used before 1.14.
+const LEGACY_CHUNK_STATUS_NAMES: &[(ChunkStatus, &str)] = &[
+    (ChunkStatus::Empty, "empty"),
+    (ChunkStatus::Surface, "base"),
+    (ChunkStatus::Carvers, "carved"),
+    (ChunkStatus::LiquidCarvers, "liquid_carved"),
+    (ChunkStatus::Features, "decorated"),
+    (ChunkStatus::Light, "lighted"),
+    (ChunkStatus::Spawn, "mobs_spawned"),
+    (ChunkStatus::Heightmaps, "finalized"),
+    (ChunkStatus::Full, "fullchunk"),
+    (ChunkStatus::Full, "postprocessed"),
+];
+
+impl ChunkStatus {
+    /// Parses status name with or without namespace.
+    ///
+    /// Names used before 1.14 are also recognized.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim_start_matches("minecraft:");
+
+        ChunkStatus::from_modern_name(name).or_else(|| ChunkStatus::from_legacy_name(name))
+    }
+
+    /// Parses status name used since 1.14.
+    fn from_modern_name(name: &str) -> Option<Self> {
+        CHUNK_STATUS_NAMES
+            .iter()
+            .find(|(_, status_name)| *status_name == name)
+            .map(|(status, _)| *status)
+    }
+
+    /// Parses status name used before 1.14.
+    pub(crate) fn from_legacy_name(name: &str) -> Option<Self> {
+        LEGACY_CHUNK_STATUS_NAMES
+            .iter()
+            .find(|(_, status_name)| *status_name == name)
+            .map(|(status, _)| *status)
+    }
+
+    /// Returns status name without namespace.
+    pub fn name(self) -> &'static str {
+        CHUNK_STATUS_NAMES
+            .iter()
+            .find(|(status, _)| *status == self)
+            .map(|(_, name)| *name)
+            .unwrap_or("empty")
+    }
+
+    /// Returns status name used before 1.14, statuses which didn't exist map to previous one.
+    pub fn legacy_name(self) -> &'static str {
+        LEGACY_CHUNK_STATUS_NAMES
+            .iter()
+            .rev()
+            .find(|(status, _)| *status <= self)
+            .map(|(_, name)| *name)
+            .unwrap_or("empty")
+    }
+}
+
+/// Chunk compound tag with version agnostic accessors.
+#[derive(Debug, Clone)]
+pub struct Chunk {
+    compound_tag: CompoundTag,
+}
+
+impl Chunk {
+    pub fn new(compound_tag: CompoundTag) -> Self {
+        Chunk { compound_tag }
+    }
+
+    /// Returns chunk compound tag as it will be stored in region.
+    pub fn compound_tag(&self) -> &CompoundTag {
+        &self.compound_tag
+    }
+
+    pub fn into_compound_tag(self) -> CompoundTag {
+        self.compound_tag
+    }
+
+    /// Returns chunk data version if present.
+    pub fn data_version(&self) -> Option<i32> {
+        self.compound_tag.get_i32("DataVersion").ok()
+    }
+
+    /// Returns true if chunk data is nested in `Level` compound tag.
+    pub fn has_level_wrapper(&self) -> bool {
+        match self.data_version() {
+            Some(data_version) if data_version >= LEVEL_WRAPPER_REMOVAL_DATA_VERSION => false,
+            _ => self.compound_tag.get_compound_tag(LEVEL_TAG_NAME).is_ok(),
+        }
+    }
+
+    /// Returns compound tag which contains chunk data.
+    ///
+    /// This is `Level` compound tag before 1.18 and root compound tag since 1.18.
+    pub fn data(&self) -> &CompoundTag {
+        match self.compound_tag.get_compound_tag(LEVEL_TAG_NAME) {
+            Ok(level) if self.has_level_wrapper() => level,
+            _ => &self.compound_tag,
+        }
+    }
+
+    /// Returns mutable compound tag which contains chunk data.
+    pub fn data_mut(&mut self) -> &mut CompoundTag {
+        if self.has_level_wrapper() {
+            // Presence of compound tag is checked by `has_level_wrapper`.
+            return self
+                .compound_tag
+                .get_mut::<&mut CompoundTag>(LEVEL_TAG_NAME)
+                .unwrap();
+        }
+
+        &mut self.compound_tag
+    }
+
+    /// Returns tag name used by chunk format.
+    pub fn tag_name(&self, chunk_tag: ChunkTag) -> &'static str {
+        if self.has_level_wrapper() {
+            chunk_tag.legacy_name()
+        } else {
+            chunk_tag.name()
+        }
+    }
+
+    /// Returns chunk tag.
+    ///
+    /// Structure starts and references are looked up inside of structures tag.
+    pub fn get(&self, chunk_tag: ChunkTag) -> Option<&Tag> {
+        let name = self.tag_name(chunk_tag);
+
+        match chunk_tag {
+            ChunkTag::StructureStarts | ChunkTag::StructureReferences => {
+                let structures_name = self.tag_name(ChunkTag::Structures);
+                let structures = self.data().get_compound_tag(structures_name).ok()?;
+
+                get_tag(structures, name)
+            }
+            _ => get_tag(self.data(), name),
+        }
+    }
+
+    /// Returns mutable chunk tag.
+    pub fn get_mut(&mut self, chunk_tag: ChunkTag) -> Option<&mut Tag> {
+        let name = self.tag_name(chunk_tag);
+
+        match chunk_tag {
+            ChunkTag::StructureStarts | ChunkTag::StructureReferences => {
+                let structures_name = self.tag_name(ChunkTag::Structures);
+                let structures = self
+                    .data_mut()
+                    .get_mut::<&mut CompoundTag>(structures_name)
+                    .ok()?;
+
+                get_tag_mut(structures, name)
+            }
+            _ => get_tag_mut(self.data_mut(), name),
+        }
+    }
+
+    /// Inserts chunk tag using name of chunk format.
+    pub fn insert(&mut self, chunk_tag: ChunkTag, tag: impl Into<Tag>) {
+        let name = self.tag_name(chunk_tag);
+
+        match chunk_tag {
+            ChunkTag::StructureStarts | ChunkTag::StructureReferences => {
+                let structures_name = self.tag_name(ChunkTag::Structures);
+                let data = self.data_mut();
+
+                if !data.contains_key(structures_name) {
+                    data.insert_compound_tag(structures_name, CompoundTag::new());
+                }
+
+                if let Ok(structures) = data.get_mut::<&mut CompoundTag>(structures_name) {
+                    structures.insert(name, tag);
+                }
+            }
+            _ => self.data_mut().insert(name, tag),
+        }
+    }
+
+    /// Returns chunk position.
+    pub fn position(&self) -> Result<(i32, i32), CompoundTagError<'_>> {
+        let data = self.data();
+
+        Ok((data.get_i32("xPos")?, data.get_i32("zPos")?))
+    }
+
+    /// Returns raw chunk status name.
+    pub fn status_name(&self) -> Option<&str> {
+        self.data().get_str("Status").ok()
+    }
+
+    /// Returns chunk status, none if status is missing or unknown.
+    pub fn status(&self) -> Option<ChunkStatus> {
+        self.status_name().and_then(ChunkStatus::from_name)
+    }
+
+    /// Sets chunk status using naming of chunk format.
+    pub fn set_status(&mut self, status: ChunkStatus) {
+        let data_version = self.data_version().unwrap_or(0);
+
+        let status_name = if data_version < CHUNK_STATUS_RENAME_DATA_VERSION {
+            status.legacy_name().to_owned()
+        } else if self.has_level_wrapper() {
+            status.name().to_owned()
+        } else {
+            format!("minecraft:{}", status.name())
+        };
+
+        self.data_mut().insert_str("Status", status_name);
+    }
+
+    /// Returns true if chunk generation is finished.
+    pub fn is_fully_generated(&self) -> bool {
+        self.status() == Some(ChunkStatus::Full)
+    }
+
+    /// Returns true if chunk generation is at least at specified status.
+    pub fn has_status(&self, status: ChunkStatus) -> bool {
+        self.status()
+            .is_some_and(|chunk_status| chunk_status >= status)
+    }
+
+    /// Returns chunk sections.
+    pub fn sections(&self) -> Vec<&CompoundTag> {
+        compound_tags(self.data(), self.tag_name(ChunkTag::Sections)).collect()
+    }
+
+    /// Returns mutable chunk sections.
+    pub fn sections_mut(&mut self) -> impl Iterator<Item = &mut CompoundTag> {
+        let name = self.tag_name(ChunkTag::Sections);
+
+        compound_tags_mut(self.data_mut(), name)
+    }
+
+    /// Returns chunk block entities.
+    pub fn block_entities(&self) -> Vec<&CompoundTag> {
+        compound_tags(self.data(), self.tag_name(ChunkTag::BlockEntities)).collect()
+    }
+
+    /// Returns mutable chunk block entities.
+    pub fn block_entities_mut(&mut self) -> impl Iterator<Item = &mut CompoundTag> {
+        let name = self.tag_name(ChunkTag::BlockEntities);
+
+        compound_tags_mut(self.data_mut(), name)
+    }
+}
+
+impl From<CompoundTag> for Chunk {
+    fn from(compound_tag: CompoundTag) -> Self {
+        Chunk::new(compound_tag)
+    }
+}
+
+impl From<Chunk> for CompoundTag {
+    fn from(chunk: Chunk) -> Self {
+        chunk.into_compound_tag()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chunk::{Chunk, ChunkStatus, ChunkTag};
+    use crate::upgrade::upgrade_chunk;
+    use crate::AnvilChunkProvider;
+    use nbt::{CompoundTag, Tag};
+
+    fn assert_chunk_accessors(chunk: &mut Chunk) {
+        assert_eq!(chunk.position().unwrap(), (4, 2));
+        assert_eq!(chunk.sections().len(), 6);
+
+        for section in chunk.sections_mut() {
+            section.insert_i8("Touched", 1);
+        }
+
+        assert!(chunk
+            .sections()
+            .iter()
+            .all(|section| section.get_i8("Touched").unwrap() == 1));
+
+        match chunk.get(ChunkTag::BlockEntities) {
+            Some(Tag::List(_)) => {}
+            tag => panic!("Expected `List` but got `{:?}`", tag),
+        }
+
+        chunk.insert(ChunkTag::StructureStarts, CompoundTag::new());
+
+        match chunk.get(ChunkTag::StructureStarts) {
+            Some(Tag::Compound(_)) => {}
+            tag => panic!("Expected `Compound` but got `{:?}`", tag),
+        }
+    }
+
+    #[test]
+    fn test_chunk_status_names() {
+        assert_eq!(
+            ChunkStatus::from_name("minecraft:features"),
+            Some(ChunkStatus::Features)
+        );
+        assert_eq!(ChunkStatus::from_name("full"), Some(ChunkStatus::Full));
+        assert_eq!(
+            ChunkStatus::from_name("postprocessed"),
+            Some(ChunkStatus::Full)
+        );
+        assert_eq!(ChunkStatus::from_name("unknown"), None);
+        assert_eq!(ChunkStatus::Noise.legacy_name(), "empty");
+        assert_eq!(ChunkStatus::InitializeLight.legacy_name(), "decorated");
+        assert_eq!(ChunkStatus::Full.legacy_name(), "postprocessed");
+    }
+
+    #[test]
+    fn test_chunk_status() {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+        let mut chunk = Chunk::new(chunk_provider.load_chunk(4, 2).unwrap());
+
+        assert_eq!(chunk.status(), Some(ChunkStatus::Full));
+        assert!(chunk.is_fully_generated());
+
+        chunk.set_status(ChunkStatus::Features);
+
+        assert_eq!(chunk.status_name(), Some("decorated"));
+        assert!(chunk.has_status(ChunkStatus::Carvers));
+        assert!(!chunk.has_status(ChunkStatus::Light));
+
+        let mut chunk_compound_tag = chunk.into_compound_tag();
+        upgrade_chunk(&mut chunk_compound_tag, 2860).unwrap();
+
+        let mut chunk = Chunk::new(chunk_compound_tag);
+        chunk.set_status(ChunkStatus::Full);
+
+        assert_eq!(chunk.status_name(), Some("minecraft:full"));
+    }
+
+    #[test]
+    fn test_chunk_with_level_wrapper() {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+        let mut chunk = Chunk::new(chunk_provider.load_chunk(4, 2).unwrap());
+
+        assert!(chunk.has_level_wrapper());
+        assert_eq!(chunk.tag_name(ChunkTag::BlockEntities), "TileEntities");
+
+        assert_chunk_accessors(&mut chunk);
+    }
+
+    #[test]
+    fn test_chunk_without_level_wrapper() {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+        let mut chunk_compound_tag = chunk_provider.load_chunk(4, 2).unwrap();
+        upgrade_chunk(&mut chunk_compound_tag, 2860).unwrap();
+
+        let mut chunk = Chunk::new(chunk_compound_tag);
+
+        assert!(!chunk.has_level_wrapper());
+        assert_eq!(chunk.tag_name(ChunkTag::BlockEntities), "block_entities");
+
+        assert_chunk_accessors(&mut chunk);
+
+        let chunk_compound_tag = chunk.into_compound_tag();
+        let structures = chunk_compound_tag.get_compound_tag("structures").unwrap();
+
+        assert!(structures.contains_key("starts"));
+    }
+}
@@ -0,0 +1,1063 @@
+//! Command line tool for inspecting and repairing region files.
+//!
+//! `anvil-region` binary built with `cli` feature parses arguments into [`Command`]
+//! and runs it with [`run`]. Commands accept region file, region folder or world
+//! folder, which is recognized by its `level.dat`. Commands on world read terrain
+//! region folders of every dimension, repair, compact and recompress also process
+//! entity and point of interest region folders. Prune deletes entity and point of
+//! interest chunks together with pruned terrain chunks.
+//!
+//! ```text
+//! anvil-region info <path>
+//! anvil-region list-chunks <path>
+//! anvil-region extract-chunk <path> <chunk-x> <chunk-z> [--format snbt|json] [--dimension <name>]
+//! anvil-region validate <path>
+//! anvil-region repair <path> <output-path>
+//! anvil-region prune <path> <max-inhabited-time> [--older-than <days>] [--dry-run]
+//! anvil-region compact <path> [--dry-run]
+//! anvil-region recompress <path> [--dry-run]
+//! ```
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::cli::{run, Command};
+//!
+//! let args = vec!["list-chunks".to_owned(), "test/region/r.0.0.mca".to_owned()];
+//! let command = Command::parse(args).unwrap();
+//!
+//! let mut output = Vec::new();
+//! run(&command, &mut output).unwrap();
+//!
+//! assert!(String::from_utf8(output).unwrap().lines().any(|line| line == "4 2"));
+//! ```
+use crate::compact::{
+    compact_provider, compact_region, CompactError, CompactOptions, CompactReport,
+};
+use crate::dump::{compound_tag_to_json, DumpFormat};
+use crate::headers::RegionHeaders;
+use crate::level::{LEVEL_DAT_FILE, LEVEL_DAT_OLD_FILE};
+use crate::prune::{prune_provider, PruneError, PruneOptions};
+use crate::stats::{provider_stats, ProviderStats};
+use crate::validate::{validate_chunk, ViolationKind};
+use crate::world::{AnvilWorld, Dimension, WorldError, ENTITIES_FOLDER, POI_FOLDER, REGION_FOLDER};
+use crate::{json, region_position, snbt, AnvilChunkProvider, ChunkLoadError, ChunkSaveError};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Usage printed for help and invalid arguments.
+pub const USAGE: &str = "\
+Usage: anvil-region <command> [arguments]
+
+Path is region file, region folder or world folder.
+
+Commands:
+    info <path>                       Print chunk counts, sizes and data versions
+    list-chunks <path>                Print coordinates of stored chunks
+    extract-chunk <path> <x> <z>      Print chunk as SNBT or JSON
+        --format snbt|json            Output format, snbt by default
+        --dimension <name>            Dimension of world, minecraft:overworld by default
+    validate <path>                   Check that chunks can be read and have expected tags
+    repair <path> <output-path>       Copy chunks which can be read into output path
+    prune <path> <max-inhabited-time> Delete chunks inhabited less than ticks
+        --older-than <days>           Keep chunks saved in the last days, 0 by default
+        --dry-run                     Only print what would be deleted
+    compact <path>                    Remove free sectors from region files
+        --dry-run                     Only print how much space would be reclaimed
+    recompress <path>                 Compress chunks again and remove free sectors
+        --dry-run                     Only print how much space would be reclaimed
+    help                              Print this message";
+
+/// Possible errors of commands.
+#[derive(Debug)]
+pub enum CliError {
+    /// Arguments don't match any command.
+    Usage { message: String },
+    /// File can't be read or written.
+    IoError { io_error: io::Error },
+    /// Chunk can't be loaded.
+    ChunkLoadError { chunk_load_error: ChunkLoadError },
+    /// Chunk can't be saved.
+    ChunkSaveError { chunk_save_error: ChunkSaveError },
+    /// World folder can't be opened.
+    WorldError { world_error: WorldError },
+    /// Chunks can't be pruned.
+    PruneError { prune_error: PruneError },
+    /// Region files can't be compacted.
+    CompactError { compact_error: CompactError },
+    /// Validation found chunks which can't be read or have problems.
+    ProblemsFound { problems: usize },
+}
+
+impl From<io::Error> for CliError {
+    fn from(io_error: io::Error) -> Self {
+        CliError::IoError { io_error }
+    }
+}
+
+impl From<ChunkLoadError> for CliError {
+    fn from(chunk_load_error: ChunkLoadError) -> Self {
+        CliError::ChunkLoadError { chunk_load_error }
+    }
+}
+
+impl From<ChunkSaveError> for CliError {
+    fn from(chunk_save_error: ChunkSaveError) -> Self {
+        CliError::ChunkSaveError { chunk_save_error }
+    }
+}
+
+impl From<WorldError> for CliError {
+    fn from(world_error: WorldError) -> Self {
+        CliError::WorldError { world_error }
+    }
+}
+
+impl From<PruneError> for CliError {
+    fn from(prune_error: PruneError) -> Self {
+        CliError::PruneError { prune_error }
+    }
+}
+
+impl From<CompactError> for CliError {
+    fn from(compact_error: CompactError) -> Self {
+        CliError::CompactError { compact_error }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Usage { message } => write!(f, "{}", message),
+            CliError::IoError { io_error } => write!(f, "{}", io_error),
+            CliError::ChunkLoadError { chunk_load_error } => write!(f, "{}", chunk_load_error),
+            CliError::ChunkSaveError { chunk_save_error } => write!(f, "{}", chunk_save_error),
+            CliError::WorldError { world_error } => {
+                write!(f, "world can't be opened: {:?}", world_error)
+            }
+            CliError::PruneError { prune_error } => {
+                write!(f, "chunks can't be pruned: {:?}", prune_error)
+            }
+            CliError::CompactError { compact_error } => {
+                write!(f, "region files can't be compacted: {:?}", compact_error)
+            }
+            CliError::ProblemsFound { problems } => write!(f, "{} problems found", problems),
+        }
+    }
+}
+
+impl Error for CliError {}
+
+/// Command parsed from arguments.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Command {
+    Help,
+    Info {
+        path: PathBuf,
+    },
+    ListChunks {
+        path: PathBuf,
+    },
+    ExtractChunk {
+        path: PathBuf,
+        chunk_x: i32,
+        chunk_z: i32,
+        format: DumpFormat,
+        /// Dimension of world, ignored for region files and folders.
+        dimension: Dimension,
+    },
+    Validate {
+        path: PathBuf,
+    },
+    Repair {
+        path: PathBuf,
+        output_path: PathBuf,
+    },
+    Prune {
+        path: PathBuf,
+        /// Chunks with `InhabitedTime` in ticks below this value are pruned.
+        max_inhabited_time: i64,
+        /// Chunks saved in this amount of last days are kept.
+        older_than_days: u64,
+        dry_run: bool,
+    },
+    /// Compaction, with recompression for `recompress` command.
+    Compact {
+        path: PathBuf,
+        recompress: bool,
+        dry_run: bool,
+    },
+}
+
+impl Command {
+    /// Parses arguments without program name.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, CliError> {
+        let mut args = args.into_iter();
+        let command_name = args.next().ok_or_else(|| usage("missing command"))?;
+
+        let mut positional = Vec::new();
+        let mut format = DumpFormat::Snbt;
+        let mut dimension = Dimension::Overworld;
+        let mut older_than_days = 0;
+        let mut dry_run = false;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--format" => {
+                    format = match args.next().as_deref() {
+                        Some("snbt") => DumpFormat::Snbt,
+                        Some("json") => DumpFormat::Json,
+                        _ => return Err(usage("--format must be snbt or json")),
+                    }
+                }
+                "--dimension" => {
+                    let name = args
+                        .next()
+                        .ok_or_else(|| usage("--dimension requires name"))?;
+                    dimension = Dimension::from_name(&name);
+                }
+                "--older-than" => {
+                    older_than_days = args
+                        .next()
+                        .and_then(|days| days.parse().ok())
+                        .ok_or_else(|| usage("--older-than requires amount of days"))?;
+                }
+                "--dry-run" => dry_run = true,
+                _ if arg.starts_with("--") => {
+                    return Err(usage(&format!("unknown option {}", arg)));
+                }
+                _ => positional.push(arg),
+            }
+        }
+
+        let command = match (command_name.as_str(), positional.as_slice()) {
+            ("help", []) | ("--help", []) | ("-h", []) => Command::Help,
+            ("info", [path]) => Command::Info { path: path.into() },
+            ("list-chunks", [path]) => Command::ListChunks { path: path.into() },
+            ("extract-chunk", [path, chunk_x, chunk_z]) => Command::ExtractChunk {
+                path: path.into(),
+                chunk_x: parse_coordinate(chunk_x)?,
+                chunk_z: parse_coordinate(chunk_z)?,
+                format,
+                dimension,
+            },
+            ("validate", [path]) => Command::Validate { path: path.into() },
+            ("repair", [path, output_path]) => Command::Repair {
+                path: path.into(),
+                output_path: output_path.into(),
+            },
+            ("prune", [path, max_inhabited_time]) => Command::Prune {
+                path: path.into(),
+                max_inhabited_time: max_inhabited_time.parse().map_err(|_| {
+                    usage(&format!("{} is not an amount of ticks", max_inhabited_time))
+                })?,
+                older_than_days,
+                dry_run,
+            },
+            ("compact", [path]) => Command::Compact {
+                path: path.into(),
+                recompress: false,
+                dry_run,
+            },
+            ("recompress", [path]) => Command::Compact {
+                path: path.into(),
+                recompress: true,
+                dry_run,
+            },
+            ("help", _)
+            | ("info", _)
+            | ("list-chunks", _)
+            | ("extract-chunk", _)
+            | ("validate", _)
+            | ("repair", _)
+            | ("prune", _)
+            | ("compact", _)
+            | ("recompress", _) => {
+                return Err(usage(&format!("wrong arguments of {}", command_name)));
+            }
+            _ => return Err(usage(&format!("unknown command {}", command_name))),
+        };
+
+        Ok(command)
+    }
+}
+
+/// Runs command writing its output.
+///
+/// Validation which finds problems prints them and returns
+/// [`CliError::ProblemsFound`], so tool exits with failure code.
+pub fn run(command: &Command, output: &mut impl Write) -> Result<(), CliError> {
+    match command {
+        Command::Help => writeln!(output, "{}", USAGE)?,
+        Command::Info { path } => info(path, output)?,
+        Command::ListChunks { path } => list_chunks(path, output)?,
+        Command::ExtractChunk {
+            path,
+            chunk_x,
+            chunk_z,
+            format,
+            dimension,
+        } => extract_chunk(path, *chunk_x, *chunk_z, *format, dimension, output)?,
+        Command::Validate { path } => validate(path, output)?,
+        Command::Repair { path, output_path } => repair(path, output_path, output)?,
+        Command::Prune {
+            path,
+            max_inhabited_time,
+            older_than_days,
+            dry_run,
+        } => prune(
+            path,
+            *max_inhabited_time,
+            *older_than_days,
+            *dry_run,
+            output,
+        )?,
+        Command::Compact {
+            path,
+            recompress,
+            dry_run,
+        } => compact(path, *recompress, *dry_run, output)?,
+    }
+
+    Ok(())
+}
+
+/// What path given to command points to.
+enum Target {
+    RegionFile {
+        folder_path: PathBuf,
+        region_x: i32,
+        region_z: i32,
+    },
+    RegionFolder {
+        folder_path: PathBuf,
+    },
+    World {
+        world: AnvilWorld,
+    },
+}
+
+impl Target {
+    fn open(path: &Path) -> Result<Self, CliError> {
+        if path.is_file() {
+            let (region_x, region_z) = path
+                .file_name()
+                .and_then(|file_name| file_name.to_str())
+                .and_then(region_position)
+                .ok_or_else(|| usage(&format!("{} is not a region file", path.display())))?;
+
+            let folder_path = match path.parent() {
+                Some(folder_path) if !folder_path.as_os_str().is_empty() => folder_path.into(),
+                _ => PathBuf::from("."),
+            };
+
+            return Ok(Target::RegionFile {
+                folder_path,
+                region_x,
+                region_z,
+            });
+        }
+
+        if !path.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} not found", path.display()),
+            )
+            .into());
+        }
+
+        if path.join(LEVEL_DAT_FILE).is_file() || path.join(LEVEL_DAT_OLD_FILE).is_file() {
+            return Ok(Target::World {
+                world: AnvilWorld::open(path)?,
+            });
+        }
+
+        Ok(Target::RegionFolder {
+            folder_path: path.into(),
+        })
+    }
+
+    /// Returns terrain region folders, with dimension for worlds.
+    fn region_folders(&self) -> Result<Vec<RegionFolder>, CliError> {
+        match self {
+            Target::RegionFile {
+                folder_path,
+                region_x,
+                region_z,
+            } => Ok(vec![RegionFolder {
+                dimension: None,
+                folder_path: folder_path.clone(),
+                region_position: Some((*region_x, *region_z)),
+            }]),
+            Target::RegionFolder { folder_path } => Ok(vec![RegionFolder {
+                dimension: None,
+                folder_path: folder_path.clone(),
+                region_position: None,
+            }]),
+            Target::World { world } => Ok(world
+                .dimensions()?
+                .into_iter()
+                .map(|dimension| RegionFolder {
+                    folder_path: dimension
+                        .folder_path(world.folder_path())
+                        .join(REGION_FOLDER),
+                    dimension: Some(dimension),
+                    region_position: None,
+                })
+                .collect()),
+        }
+    }
+
+    /// Returns terrain region folders, and entity and point of interest region folders
+    /// of worlds.
+    fn all_region_folders(&self) -> Result<Vec<RegionFolder>, CliError> {
+        let world = match self {
+            Target::World { world } => world,
+            _ => return self.region_folders(),
+        };
+
+        let mut region_folders = Vec::new();
+
+        for dimension in world.dimensions()? {
+            let dimension_folder_path = dimension.folder_path(world.folder_path());
+
+            for folder_name in &[REGION_FOLDER, ENTITIES_FOLDER, POI_FOLDER] {
+                region_folders.push(RegionFolder {
+                    dimension: Some(dimension.clone()),
+                    folder_path: dimension_folder_path.join(folder_name),
+                    region_position: None,
+                });
+            }
+        }
+
+        Ok(region_folders)
+    }
+}
+
+/// Region folder read by command.
+struct RegionFolder {
+    dimension: Option<Dimension>,
+    folder_path: PathBuf,
+    /// Region which chunks are read, all regions of folder if none.
+    region_position: Option<(i32, i32)>,
+}
+
+impl RegionFolder {
+    fn chunk_provider(&self) -> AnvilChunkProvider<'_> {
+        AnvilChunkProvider::from_path(&self.folder_path)
+    }
+
+    fn chunk_positions(&self) -> Result<Vec<(i32, i32)>, io::Error> {
+        let mut chunk_positions = self.chunk_provider().chunk_positions()?;
+
+        if let Some(region_position) = self.region_position {
+            chunk_positions
+                .retain(|(chunk_x, chunk_z)| (chunk_x >> 5, chunk_z >> 5) == region_position);
+        }
+
+        Ok(chunk_positions)
+    }
+
+    /// Returns chunk position prefixed with dimension name for worlds.
+    fn chunk_label(&self, chunk_x: i32, chunk_z: i32) -> String {
+        match &self.dimension {
+            Some(dimension) => format!("{} {} {}", dimension.name(), chunk_x, chunk_z),
+            None => format!("{} {}", chunk_x, chunk_z),
+        }
+    }
+}
+
+fn info(path: &Path, output: &mut impl Write) -> Result<(), CliError> {
+    match Target::open(path)? {
+        Target::RegionFile {
+            folder_path,
+            region_x,
+            region_z,
+        } => {
+            let region_headers = RegionHeaders::open(path)?;
+            let file_length = fs::metadata(path)?.len();
+            let last_modified = region_headers
+                .chunk_positions()
+                .into_iter()
+                .filter_map(|(chunk_x, chunk_z)| {
+                    region_headers.chunk_last_modified(chunk_x, chunk_z)
+                })
+                .max();
+
+            writeln!(output, "region: {} {}", region_x, region_z)?;
+            writeln!(output, "folder: {}", folder_path.display())?;
+            writeln!(output, "chunks: {}", region_headers.chunk_positions().len())?;
+            writeln!(output, "file size: {} bytes", file_length)?;
+
+            if let Some(last_modified) = last_modified {
+                writeln!(output, "last modified: {}", last_modified)?;
+            }
+        }
+        Target::RegionFolder { folder_path } => {
+            let chunk_provider = AnvilChunkProvider::from_path(&folder_path);
+            write_provider_stats(&provider_stats(&chunk_provider)?, "", output)?;
+        }
+        Target::World { world } => {
+            if let Some(name) = world.level_data().name() {
+                writeln!(output, "name: {}", name)?;
+            }
+
+            for dimension in world.dimensions()? {
+                let world_dimension = world.dimension(&dimension);
+                let terrain_stats = provider_stats(&world_dimension.chunk_provider())?;
+
+                if terrain_stats.chunk_count == 0 {
+                    continue;
+                }
+
+                writeln!(output, "{}:", dimension.name())?;
+                write_provider_stats(&terrain_stats, "    ", output)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn write_provider_stats(
+    provider_stats: &ProviderStats,
+    indent: &str,
+    output: &mut impl Write,
+) -> Result<(), io::Error> {
+    writeln!(
+        output,
+        "{}regions: {}",
+        indent,
+        provider_stats.regions.len()
+    )?;
+    writeln!(output, "{}chunks: {}", indent, provider_stats.chunk_count)?;
+    writeln!(
+        output,
+        "{}compressed size: {} bytes",
+        indent, provider_stats.compressed_bytes
+    )?;
+    writeln!(
+        output,
+        "{}uncompressed size: {} bytes",
+        indent, provider_stats.uncompressed_bytes
+    )?;
+
+    if let Some(compression_ratio) = provider_stats.compression_ratio() {
+        writeln!(
+            output,
+            "{}compression ratio: {:.2}",
+            indent, compression_ratio
+        )?;
+    }
+
+    writeln!(
+        output,
+        "{}fragmentation: {:.1}%",
+        indent,
+        provider_stats.fragmentation() * 100.0
+    )?;
+
+    for (data_version, chunk_count) in &provider_stats.data_versions {
+        writeln!(
+            output,
+            "{}data version {}: {} chunks",
+            indent, data_version, chunk_count
+        )?;
+    }
+
+    if provider_stats.unreadable_chunks > 0 {
+        writeln!(
+            output,
+            "{}unreadable chunks: {}",
+            indent, provider_stats.unreadable_chunks
+        )?;
+    }
+
+    Ok(())
+}
+
+fn list_chunks(path: &Path, output: &mut impl Write) -> Result<(), CliError> {
+    for region_folder in Target::open(path)?.region_folders()? {
+        for (chunk_x, chunk_z) in region_folder.chunk_positions()? {
+            writeln!(output, "{}", region_folder.chunk_label(chunk_x, chunk_z))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn extract_chunk(
+    path: &Path,
+    chunk_x: i32,
+    chunk_z: i32,
+    format: DumpFormat,
+    dimension: &Dimension,
+    output: &mut impl Write,
+) -> Result<(), CliError> {
+    let folder_path = match Target::open(path)? {
+        Target::RegionFile {
+            folder_path,
+            region_x,
+            region_z,
+        } => {
+            if (chunk_x >> 5, chunk_z >> 5) != (region_x, region_z) {
+                return Err(usage(&format!(
+                    "chunk {} {} is not in region {} {}",
+                    chunk_x, chunk_z, region_x, region_z
+                )));
+            }
+
+            folder_path
+        }
+        Target::RegionFolder { folder_path } => folder_path,
+        Target::World { world } => dimension
+            .folder_path(world.folder_path())
+            .join(REGION_FOLDER),
+    };
+
+    let chunk_compound_tag =
+        AnvilChunkProvider::from_path(&folder_path).load_chunk(chunk_x, chunk_z)?;
+
+    let text = match format {
+        DumpFormat::Snbt => snbt::to_string_pretty(&chunk_compound_tag),
+        DumpFormat::Json => json::to_string_pretty(&compound_tag_to_json(&chunk_compound_tag)),
+    };
+
+    writeln!(output, "{}", text)?;
+
+    Ok(())
+}
+
+fn validate(path: &Path, output: &mut impl Write) -> Result<(), CliError> {
+    let mut checked_chunks = 0;
+    let mut problems = 0;
+
+    for region_folder in Target::open(path)?.region_folders()? {
+        let chunk_provider = region_folder.chunk_provider();
+
+        for (chunk_x, chunk_z) in region_folder.chunk_positions()? {
+            let chunk_label = region_folder.chunk_label(chunk_x, chunk_z);
+            checked_chunks += 1;
+
+            let chunk_compound_tag = match chunk_provider.load_chunk(chunk_x, chunk_z) {
+                Ok(chunk_compound_tag) => chunk_compound_tag,
+                Err(chunk_load_error) => {
+                    writeln!(output, "{}: {}", chunk_label, chunk_load_error)?;
+                    problems += 1;
+                    continue;
+                }
+            };
+
+            for violation in validate_chunk(&chunk_compound_tag) {
+                writeln!(
+                    output,
+                    "{}: {}: {}",
+                    chunk_label,
+                    violation.path,
+                    describe_violation(&violation.kind)
+                )?;
+                problems += 1;
+            }
+        }
+    }
+
+    writeln!(
+        output,
+        "{} chunks checked, {} problems found",
+        checked_chunks, problems
+    )?;
+
+    if problems > 0 {
+        return Err(CliError::ProblemsFound { problems });
+    }
+
+    Ok(())
+}
+
+fn describe_violation(violation_kind: &ViolationKind) -> String {
+    match violation_kind {
+        ViolationKind::MissingTag => "missing tag".to_owned(),
+        ViolationKind::WrongType { expected } => format!("expected {}", expected),
+        ViolationKind::WrongLength {
+            length,
+            expected_length,
+        } => format!("length {} instead of {}", length, expected_length),
+        ViolationKind::EmptyPalette => "empty palette".to_owned(),
+        ViolationKind::PaletteIndexOutOfBounds {
+            index,
+            palette_length,
+        } => format!("palette index {} out of {} entries", index, palette_length),
+    }
+}
+
+/// Copies chunks which can be loaded into output path with the same layout, so
+/// region file is copied into output folder, region folder into output folder and
+/// region folders of world into output world folder.
+///
+/// Other files of world like `level.dat` are not copied.
+fn repair(path: &Path, output_path: &Path, output: &mut impl Write) -> Result<(), CliError> {
+    let target = Target::open(path)?;
+
+    let mut copied_chunks = 0;
+    let mut skipped_chunks = 0;
+
+    for region_folder in target.all_region_folders()? {
+        let output_folder_path = match &target {
+            Target::World { world } => {
+                let relative_path = region_folder
+                    .folder_path
+                    .strip_prefix(world.folder_path())
+                    .unwrap_or(&region_folder.folder_path);
+
+                output_path.join(relative_path)
+            }
+            _ => output_path.to_path_buf(),
+        };
+
+        let chunk_provider = region_folder.chunk_provider();
+        let output_chunk_provider = AnvilChunkProvider::from_path(&output_folder_path);
+
+        for (chunk_x, chunk_z) in region_folder.chunk_positions()? {
+            match chunk_provider.load_chunk(chunk_x, chunk_z) {
+                Ok(chunk_compound_tag) => {
+                    fs::create_dir_all(&output_folder_path)?;
+                    output_chunk_provider.save_chunk(chunk_x, chunk_z, chunk_compound_tag)?;
+                    copied_chunks += 1;
+                }
+                Err(chunk_load_error) => {
+                    writeln!(
+                        output,
+                        "skipped {}: {}",
+                        region_folder.chunk_label(chunk_x, chunk_z),
+                        chunk_load_error
+                    )?;
+                    skipped_chunks += 1;
+                }
+            }
+        }
+    }
+
+    writeln!(
+        output,
+        "{} chunks copied, {} chunks skipped",
+        copied_chunks, skipped_chunks
+    )?;
+
+    Ok(())
+}
+
+/// Prunes terrain chunks, entity and point of interest chunks at their positions are
+/// deleted as well.
+fn prune(
+    path: &Path,
+    max_inhabited_time: i64,
+    older_than_days: u64,
+    dry_run: bool,
+    output: &mut impl Write,
+) -> Result<(), CliError> {
+    let target = Target::open(path)?;
+
+    if let Target::RegionFile { .. } = target {
+        return Err(usage("prune works on region folders and worlds"));
+    }
+
+    let modified_before = older_than_days
+        .checked_mul(24 * 60 * 60)
+        .and_then(|seconds| SystemTime::now().checked_sub(Duration::from_secs(seconds)))
+        .ok_or_else(|| {
+            usage(&format!(
+                "--older-than {} days is too long",
+                older_than_days
+            ))
+        })?;
+    let mut options = PruneOptions::new(max_inhabited_time, modified_before);
+    options.dry_run = dry_run;
+
+    let mut pruned_chunks = 0;
+    let mut reclaimable_bytes = 0;
+
+    for region_folder in target.region_folders()? {
+        let prune_report = prune_provider(&region_folder.chunk_provider(), &options)?;
+
+        if let (Target::World { world }, Some(dimension), false) =
+            (&target, &region_folder.dimension, dry_run)
+        {
+            let world_dimension = world.dimension(dimension);
+
+            for (chunk_x, chunk_z) in &prune_report.pruned_chunks {
+                world_dimension
+                    .entity_chunk_provider()
+                    .delete_chunk(*chunk_x, *chunk_z)?;
+                world_dimension
+                    .poi_chunk_provider()
+                    .delete_chunk(*chunk_x, *chunk_z)?;
+            }
+        }
+
+        for (chunk_x, chunk_z) in &prune_report.pruned_chunks {
+            writeln!(output, "{}", region_folder.chunk_label(*chunk_x, *chunk_z))?;
+        }
+
+        pruned_chunks += prune_report.pruned_chunks.len();
+        reclaimable_bytes += prune_report.reclaimable_bytes;
+    }
+
+    if dry_run {
+        writeln!(
+            output,
+            "{} chunks would be pruned, {} bytes of terrain chunks would be freed",
+            pruned_chunks, reclaimable_bytes
+        )?;
+    } else {
+        writeln!(
+            output,
+            "{} chunks pruned, {} bytes of terrain chunks freed, compact to shrink region files",
+            pruned_chunks, reclaimable_bytes
+        )?;
+    }
+
+    Ok(())
+}
+
+fn compact(
+    path: &Path,
+    recompress: bool,
+    dry_run: bool,
+    output: &mut impl Write,
+) -> Result<(), CliError> {
+    let options = CompactOptions {
+        recompress,
+        dry_run,
+    };
+
+    let mut compact_report = CompactReport::default();
+
+    for region_folder in Target::open(path)?.all_region_folders()? {
+        let chunk_provider = region_folder.chunk_provider();
+
+        compact_report.add(match region_folder.region_position {
+            Some((region_x, region_z)) => {
+                compact_region(&chunk_provider, region_x, region_z, &options)?
+            }
+            None => compact_provider(&chunk_provider, &options)?,
+        });
+    }
+
+    let verb = if dry_run { "would be " } else { "" };
+
+    writeln!(
+        output,
+        "{} regions {}compacted, {} chunks {}recompressed, {} bytes {}reclaimed",
+        compact_report.compacted_regions,
+        verb,
+        compact_report.recompressed_chunks,
+        verb,
+        compact_report.reclaimed_bytes(),
+        verb
+    )?;
+
+    Ok(())
+}
+
+fn parse_coordinate(arg: &str) -> Result<i32, CliError> {
+    arg.parse()
+        .map_err(|_| usage(&format!("{} is not a chunk coordinate", arg)))
+}
+
+fn usage(message: &str) -> CliError {
+    CliError::Usage {
+        message: message.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::{run, CliError, Command};
+    use crate::dump::DumpFormat;
+    use crate::world::Dimension;
+    use crate::AnvilChunkProvider;
+    use std::fs::{self, OpenOptions};
+    use std::io::{Read, Seek, SeekFrom, Write};
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn run_to_string(command: &Command) -> (Result<(), CliError>, String) {
+        let mut output = Vec::new();
+        let result = run(command, &mut output);
+
+        (result, String::from_utf8(output).unwrap())
+    }
+
+    /// Saves two chunks and breaks compression type of the second one.
+    fn corrupted_region(region_dir: &TempDir) {
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk_provider = AnvilChunkProvider::new(region_dir.path().to_str().unwrap());
+        let chunk_compound_tag = fixture_chunk_provider.load_chunk(4, 2).unwrap();
+
+        chunk_provider
+            .save_chunk(4, 2, chunk_compound_tag.clone())
+            .unwrap();
+        chunk_provider.save_chunk(5, 2, chunk_compound_tag).unwrap();
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(region_dir.path().join("r.0.0.mca"))
+            .unwrap();
+
+        let mut offset = [0; 4];
+        file.seek(SeekFrom::Start((5 + 2 * 32) * 4)).unwrap();
+        file.read_exact(&mut offset).unwrap();
+
+        let sector = u64::from_be_bytes([0, 0, 0, 0, 0, offset[0], offset[1], offset[2]]);
+        file.seek(SeekFrom::Start(sector * 4096 + 4)).unwrap();
+        file.write_all(&[42]).unwrap();
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Command::parse(args(&[
+                "extract-chunk",
+                "world",
+                "-3",
+                "7",
+                "--format",
+                "json",
+                "--dimension",
+                "minecraft:the_nether"
+            ]))
+            .unwrap(),
+            Command::ExtractChunk {
+                path: PathBuf::from("world"),
+                chunk_x: -3,
+                chunk_z: 7,
+                format: DumpFormat::Json,
+                dimension: Dimension::Nether,
+            }
+        );
+
+        assert!(matches!(
+            Command::parse(args(&["validate"])),
+            Err(CliError::Usage { .. })
+        ));
+        assert!(matches!(
+            Command::parse(args(&["extract-chunk", "world", "x", "7"])),
+            Err(CliError::Usage { .. })
+        ));
+        assert!(matches!(
+            Command::parse(args(&["defragment", "world"])),
+            Err(CliError::Usage { .. })
+        ));
+    }
+
+    #[test]
+    fn test_info_and_extract_chunk() {
+        let (result, text) = run_to_string(&Command::Info {
+            path: "test/region/r.0.0.mca".into(),
+        });
+        result.unwrap();
+        assert!(text.starts_with("region: 0 0\n"));
+
+        let (result, text) = run_to_string(&Command::ExtractChunk {
+            path: "test/region".into(),
+            chunk_x: 4,
+            chunk_z: 2,
+            format: DumpFormat::Snbt,
+            dimension: Dimension::Overworld,
+        });
+        result.unwrap();
+        assert!(text.contains("xPos: 4"));
+    }
+
+    #[test]
+    fn test_validate_and_repair() {
+        let region_dir = TempDir::new().unwrap();
+        let output_dir = TempDir::new().unwrap();
+        corrupted_region(&region_dir);
+
+        let (result, text) = run_to_string(&Command::Validate {
+            path: region_dir.path().into(),
+        });
+        assert!(matches!(
+            result,
+            Err(CliError::ProblemsFound { problems: 1 })
+        ));
+        assert!(text.starts_with("5 2: "));
+
+        let (result, text) = run_to_string(&Command::Repair {
+            path: region_dir.path().join("r.0.0.mca"),
+            output_path: output_dir.path().into(),
+        });
+        result.unwrap();
+        assert!(text.ends_with("1 chunks copied, 1 chunks skipped\n"));
+
+        let (result, _) = run_to_string(&Command::Validate {
+            path: output_dir.path().into(),
+        });
+        result.unwrap();
+    }
+
+    #[test]
+    fn test_prune_and_compact() {
+        let region_dir = TempDir::new().unwrap();
+        let region_path = region_dir.path().join("r.0.0.mca");
+        fs::copy("test/region/r.0.0.mca", &region_path).unwrap();
+
+        let chunk_provider = AnvilChunkProvider::new(region_dir.path().to_str().unwrap());
+        let chunk_count = chunk_provider.chunk_positions().unwrap().len();
+
+        let command = Command::parse(args(&[
+            "prune",
+            region_dir.path().to_str().unwrap(),
+            "9223372036854775807",
+            "--dry-run",
+        ]))
+        .unwrap();
+        let (result, text) = run_to_string(&command);
+        result.unwrap();
+        assert!(text.lines().any(|line| line == "4 2"));
+        assert!(text.contains(" chunks would be pruned, "));
+        assert_eq!(chunk_provider.chunk_positions().unwrap().len(), chunk_count);
+
+        chunk_provider.delete_chunk(5, 2).unwrap();
+
+        let (result, text) = run_to_string(&Command::Compact {
+            path: region_dir.path().into(),
+            recompress: false,
+            dry_run: false,
+        });
+        result.unwrap();
+        assert!(text.starts_with("1 regions compacted, 0 chunks recompressed"));
+        assert!(chunk_provider.load_chunk(6, 2).is_ok());
+    }
+
+    #[test]
+    fn test_prune_older_than_too_long() {
+        let region_dir = TempDir::new().unwrap();
+
+        for older_than_days in ["106752000000000", "18446744073709551615"] {
+            let command = Command::parse(args(&[
+                "prune",
+                region_dir.path().to_str().unwrap(),
+                "0",
+                "--older-than",
+                older_than_days,
+            ]))
+            .unwrap();
+
+            match run_to_string(&command).0 {
+                Err(CliError::Usage { message }) => assert!(message.ends_with("is too long")),
+                result => panic!("Expected `Usage` but got `{:?}`", result),
+            }
+        }
+    }
+}
@@ -0,0 +1,488 @@
+//! Copy and paste of selections between providers.
+//!
+//! [`Clipboard`] keeps blocks, block entities and entities of selection like WorldEdit
+//! `//copy`, and pastes them into the same or another world, possibly rotated around
+//! the vertical axis. Blocks are kept in [`StructureTemplate`], so clipboard can be
+//! saved as structure file and loaded by structure blocks.
+//!
+//! Rotation turns horizontal `facing`, `axis`, `rotation` of signs and banners and
+//! connections like `north` of fences and walls. Other direction dependent properties,
+//! like shapes of rails, are kept as they are.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::clipboard::{Clipboard, Rotation};
+//! use anvil_region::schematic::BlockSelection;
+//! use anvil_region::AnvilChunkProvider;
+//!
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//! let selection = BlockSelection::new((64, 0, 32), (79, 15, 39));
+//!
+//! let clipboard = Clipboard::copy(&chunk_provider, selection).unwrap();
+//! let clipboard = clipboard.rotated(Rotation::Clockwise90);
+//! assert_eq!(clipboard.size(), (8, 16, 16));
+//!
+//! # let region_dir = tempfile::TempDir::new().unwrap();
+//! # let chunk_provider = AnvilChunkProvider::new(region_dir.path().to_str().unwrap());
+//! let report = clipboard.paste(&chunk_provider, (-64, 0, -32)).unwrap();
+//! assert_eq!(report.created_chunks, vec![(0, 0)]);
+//! ```
+use crate::schematic::{BlockSelection, PasteReport};
+use crate::section::BlockState;
+use crate::structure_template::{
+    capture_entities, capture_structure, place_entities, place_structure, StructureBlock,
+    StructureEntity, StructureTemplate, StructureTemplateError,
+};
+use crate::AnvilChunkProvider;
+use nbt::Tag;
+
+/// Horizontal directions in clockwise order.
+const HORIZONTAL_DIRECTIONS: [&str; 4] = ["north", "east", "south", "west"];
+
+/// Rotation around the vertical axis seen from above.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Rotation {
+    #[default]
+    None,
+    Clockwise90,
+    Clockwise180,
+    CounterClockwise90,
+}
+
+impl Rotation {
+    /// Returns amount of clockwise quarter turns.
+    pub fn quarter_turns(self) -> usize {
+        match self {
+            Rotation::None => 0,
+            Rotation::Clockwise90 => 1,
+            Rotation::Clockwise180 => 2,
+            Rotation::CounterClockwise90 => 3,
+        }
+    }
+
+    /// Returns block state turned by rotation.
+    pub fn rotate_block_state(self, block_state: &BlockState) -> BlockState {
+        let quarter_turns = self.quarter_turns();
+
+        let properties = block_state
+            .properties
+            .iter()
+            .map(|(name, value)| match name.as_str() {
+                "facing" => (
+                    name.clone(),
+                    rotate_direction(value, quarter_turns)
+                        .unwrap_or(value)
+                        .to_owned(),
+                ),
+                "axis" if quarter_turns % 2 == 1 => {
+                    let axis = match value.as_str() {
+                        "x" => "z",
+                        "z" => "x",
+                        axis => axis,
+                    };
+
+                    (name.clone(), axis.to_owned())
+                }
+                // Standing signs, banners and heads have 16 rotations starting from south.
+                "rotation" => match value.parse::<usize>() {
+                    Ok(rotation) => (
+                        name.clone(),
+                        ((rotation + quarter_turns * 4) % 16).to_string(),
+                    ),
+                    Err(_) => (name.clone(), value.clone()),
+                },
+                _ => (
+                    rotate_direction(name, quarter_turns)
+                        .unwrap_or(name)
+                        .to_owned(),
+                    value.clone(),
+                ),
+            })
+            .collect();
+
+        BlockState {
+            name: block_state.name.clone(),
+            properties,
+        }
+    }
+
+    /// Returns block position inside of box with size turned by rotation.
+    fn rotate_position(
+        self,
+        (x, y, z): (i32, i32, i32),
+        (size_x, _, size_z): (i32, i32, i32),
+    ) -> (i32, i32, i32) {
+        match self {
+            Rotation::None => (x, y, z),
+            Rotation::Clockwise90 => (size_z - 1 - z, y, x),
+            Rotation::Clockwise180 => (size_x - 1 - x, y, size_z - 1 - z),
+            Rotation::CounterClockwise90 => (z, y, size_x - 1 - x),
+        }
+    }
+
+    /// Returns exact position inside of box with size turned by rotation.
+    fn rotate_exact_position(
+        self,
+        (x, y, z): (f64, f64, f64),
+        (size_x, _, size_z): (i32, i32, i32),
+    ) -> (f64, f64, f64) {
+        let (size_x, size_z) = (size_x as f64, size_z as f64);
+
+        match self {
+            Rotation::None => (x, y, z),
+            Rotation::Clockwise90 => (size_z - z, y, x),
+            Rotation::Clockwise180 => (size_x - x, y, size_z - z),
+            Rotation::CounterClockwise90 => (z, y, size_x - x),
+        }
+    }
+}
+
+/// Blocks and entities copied from selection.
+#[derive(Debug, Clone)]
+pub struct Clipboard {
+    /// Minimum corner of selection clipboard was copied from.
+    pub origin: (i32, i32, i32),
+    /// Blocks and entities relative to the minimum corner.
+    pub structure_template: StructureTemplate,
+}
+
+impl Clipboard {
+    /// Copies blocks, block entities and entities of selection.
+    ///
+    /// Entities are copied from terrain chunks which store them, that is before 1.17,
+    /// use [`Clipboard::copy_entities`] for newer worlds.
+    pub fn copy(
+        chunk_provider: &AnvilChunkProvider,
+        selection: BlockSelection,
+    ) -> Result<Self, StructureTemplateError> {
+        Ok(Clipboard {
+            origin: selection.min,
+            structure_template: capture_structure(chunk_provider, selection)?,
+        })
+    }
+
+    /// Copies entities of clipboard selection from provider of entity chunks.
+    ///
+    /// Entities are copied without rotation, so it's called before
+    /// [`Clipboard::rotated`]. Returns amount of copied entities.
+    pub fn copy_entities(
+        &mut self,
+        entity_chunk_provider: &AnvilChunkProvider,
+    ) -> Result<usize, StructureTemplateError> {
+        let entities = capture_entities(entity_chunk_provider, self.selection())?;
+        let copied_entities = entities.len();
+
+        self.structure_template.entities.extend(entities);
+
+        Ok(copied_entities)
+    }
+
+    /// Returns amount of blocks along X, Y and Z.
+    pub fn size(&self) -> (u32, u32, u32) {
+        self.structure_template.size
+    }
+
+    /// Returns selection clipboard was copied from, turned with clipboard.
+    pub fn selection(&self) -> BlockSelection {
+        let (origin_x, origin_y, origin_z) = self.origin;
+        let (size_x, size_y, size_z) = self.size();
+
+        BlockSelection::new(
+            self.origin,
+            (
+                origin_x + size_x as i32 - 1,
+                origin_y + size_y as i32 - 1,
+                origin_z + size_z as i32 - 1,
+            ),
+        )
+    }
+
+    /// Returns clipboard turned around the vertical axis, keeping its minimum corner.
+    pub fn rotated(&self, rotation: Rotation) -> Self {
+        let structure_template = &self.structure_template;
+        let (size_x, size_y, size_z) = structure_template.size;
+        let size = (size_x as i32, size_y as i32, size_z as i32);
+
+        let rotated_size = if rotation.quarter_turns() % 2 == 1 {
+            (size_z, size_y, size_x)
+        } else {
+            (size_x, size_y, size_z)
+        };
+
+        let palette = structure_template
+            .palette
+            .iter()
+            .map(|block_state| rotation.rotate_block_state(block_state))
+            .collect();
+
+        let mut blocks: Vec<StructureBlock> = structure_template
+            .blocks
+            .iter()
+            .map(|block| StructureBlock {
+                position: rotation.rotate_position(block.position, size),
+                state: block.state,
+                block_entity: block.block_entity.clone(),
+            })
+            .collect();
+
+        // Blocks are placed bottom up.
+        blocks.sort_by_key(|block| {
+            let (x, y, z) = block.position;
+            (y, z, x)
+        });
+
+        let entities = structure_template
+            .entities
+            .iter()
+            .map(|entity| rotate_entity(entity, rotation, size))
+            .collect();
+
+        Clipboard {
+            origin: self.origin,
+            structure_template: StructureTemplate {
+                data_version: structure_template.data_version,
+                size: rotated_size,
+                palette,
+                blocks,
+                entities,
+            },
+        }
+    }
+
+    /// Pastes blocks, block entities and entities at clipboard origin moved by offset.
+    ///
+    /// Missing chunks are created. Entities are placed into terrain chunks which store
+    /// them, that is before 1.17, use [`Clipboard::paste_entities`] for newer worlds.
+    pub fn paste(
+        &self,
+        chunk_provider: &AnvilChunkProvider,
+        offset: (i32, i32, i32),
+    ) -> Result<PasteReport, StructureTemplateError> {
+        place_structure(
+            chunk_provider,
+            &self.structure_template,
+            self.position(offset),
+        )
+    }
+
+    /// Pastes entities into provider of entity chunks at clipboard origin moved by offset.
+    ///
+    /// Returns amount of pasted entities.
+    pub fn paste_entities(
+        &self,
+        entity_chunk_provider: &AnvilChunkProvider,
+        offset: (i32, i32, i32),
+    ) -> Result<usize, StructureTemplateError> {
+        place_entities(
+            entity_chunk_provider,
+            &self.structure_template,
+            self.position(offset),
+        )
+    }
+
+    fn position(&self, (offset_x, offset_y, offset_z): (i32, i32, i32)) -> (i32, i32, i32) {
+        let (origin_x, origin_y, origin_z) = self.origin;
+
+        (
+            origin_x + offset_x,
+            origin_y + offset_y,
+            origin_z + offset_z,
+        )
+    }
+}
+
+fn rotate_direction(direction: &str, quarter_turns: usize) -> Option<&'static str> {
+    let index = HORIZONTAL_DIRECTIONS
+        .iter()
+        .position(|horizontal_direction| *horizontal_direction == direction)?;
+
+    Some(HORIZONTAL_DIRECTIONS[(index + quarter_turns) % 4])
+}
+
+/// Returns entity with position and yaw turned by rotation.
+fn rotate_entity(
+    entity: &StructureEntity,
+    rotation: Rotation,
+    size: (i32, i32, i32),
+) -> StructureEntity {
+    let mut rotated_entity = entity.entity.clone();
+
+    // Yaw is counted clockwise from south.
+    if let Ok(angles) = rotated_entity.get_mut::<&mut Vec<Tag>>("Rotation") {
+        if let Some(Tag::Float(yaw)) = angles.first_mut() {
+            *yaw = (*yaw + rotation.quarter_turns() as f32 * 90.0) % 360.0;
+        }
+    }
+
+    StructureEntity {
+        position: rotation.rotate_exact_position(entity.position, size),
+        block_position: rotation.rotate_position(entity.block_position, size),
+        entity: rotated_entity,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chunk::{Chunk, ChunkTag};
+    use crate::clipboard::{Clipboard, Rotation};
+    use crate::relocate::copy_chunk;
+    use crate::schematic::BlockSelection;
+    use crate::section::BlockState;
+    use crate::structure_template::{StructureEntity, StructureTemplate};
+    use crate::AnvilChunkProvider;
+    use nbt::{CompoundTag, Tag};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_rotate_block_state() {
+        let stairs = BlockState::new("minecraft:oak_stairs")
+            .with_property("facing", "west")
+            .with_property("half", "top");
+        let fence = BlockState::new("minecraft:oak_fence")
+            .with_property("north", "true")
+            .with_property("east", "false");
+
+        assert_eq!(
+            Rotation::Clockwise90.rotate_block_state(&stairs),
+            BlockState::new("minecraft:oak_stairs")
+                .with_property("facing", "north")
+                .with_property("half", "top")
+        );
+        assert_eq!(
+            Rotation::CounterClockwise90.rotate_block_state(&fence),
+            BlockState::new("minecraft:oak_fence")
+                .with_property("west", "true")
+                .with_property("north", "false")
+        );
+
+        let log = BlockState::new("minecraft:oak_log").with_property("axis", "x");
+        assert_eq!(
+            Rotation::Clockwise90
+                .rotate_block_state(&log)
+                .property("axis"),
+            Some("z")
+        );
+        assert_eq!(
+            Rotation::Clockwise180
+                .rotate_block_state(&log)
+                .property("axis"),
+            Some("x")
+        );
+
+        let sign = BlockState::new("minecraft:oak_sign").with_property("rotation", "14");
+        assert_eq!(
+            Rotation::Clockwise90
+                .rotate_block_state(&sign)
+                .property("rotation"),
+            Some("2")
+        );
+    }
+
+    #[test]
+    fn test_copy_and_paste_rotated() {
+        let temp_dir = TempDir::new().unwrap();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+        copy_chunk(&fixture_chunk_provider, (4, 2), &chunk_provider, (4, 2)).unwrap();
+
+        let stairs = BlockState::new("minecraft:oak_stairs").with_property("facing", "east");
+        let chest = BlockState::new("minecraft:chest").with_property("facing", "north");
+
+        let mut chunk = Chunk::new(chunk_provider.load_chunk(4, 2).unwrap());
+        chunk.set_block_state(0, 100, 0, stairs).unwrap();
+        chunk.set_block_state(1, 100, 2, chest).unwrap();
+
+        let mut block_entity = CompoundTag::new();
+        block_entity.insert_str("id", "minecraft:chest");
+        block_entity.insert_i32("x", 65);
+        block_entity.insert_i32("y", 100);
+        block_entity.insert_i32("z", 34);
+        chunk.insert(
+            ChunkTag::BlockEntities,
+            Tag::List(vec![Tag::Compound(block_entity)]),
+        );
+        chunk_provider
+            .save_chunk(4, 2, chunk.into_compound_tag())
+            .unwrap();
+
+        let selection = BlockSelection::new((64, 100, 32), (65, 100, 34));
+        let clipboard = Clipboard::copy(&chunk_provider, selection)
+            .unwrap()
+            .rotated(Rotation::Clockwise90);
+
+        assert_eq!(clipboard.size(), (3, 1, 2));
+        assert_eq!(
+            clipboard.selection(),
+            BlockSelection::new((64, 100, 32), (66, 100, 33))
+        );
+
+        let paste_dir = TempDir::new().unwrap();
+        let paste_chunk_provider = AnvilChunkProvider::new(paste_dir.path().to_str().unwrap());
+
+        let report = clipboard
+            .paste(&paste_chunk_provider, (-64, 0, -32))
+            .unwrap();
+        assert_eq!(report.pasted_blocks, 6);
+        assert_eq!(report.created_chunks, vec![(0, 0)]);
+
+        // Stairs in the north west corner go to the north east corner.
+        let chunk = Chunk::new(paste_chunk_provider.load_chunk(0, 0).unwrap());
+        assert_eq!(
+            chunk.block_state(2, 100, 0).unwrap().unwrap(),
+            BlockState::new("minecraft:oak_stairs").with_property("facing", "south")
+        );
+        assert_eq!(
+            chunk.block_state(0, 100, 1).unwrap().unwrap(),
+            BlockState::new("minecraft:chest").with_property("facing", "east")
+        );
+
+        let block_entities = chunk.block_entities();
+        assert_eq!(block_entities.len(), 1);
+        assert_eq!(block_entities[0].get_i32("x").unwrap(), 0);
+        assert_eq!(block_entities[0].get_i32("z").unwrap(), 1);
+    }
+
+    #[test]
+    fn test_rotate_entities() {
+        let mut entity = CompoundTag::new();
+        entity.insert_str("id", "minecraft:armor_stand");
+        entity.insert(
+            "Rotation",
+            Tag::List(vec![Tag::Float(270.0), Tag::Float(0.0)]),
+        );
+
+        let clipboard = Clipboard {
+            origin: (0, 0, 0),
+            structure_template: StructureTemplate {
+                data_version: 2586,
+                size: (4, 1, 2),
+                palette: Vec::new(),
+                blocks: Vec::new(),
+                entities: vec![StructureEntity {
+                    position: (0.5, 0.0, 0.25),
+                    block_position: (0, 0, 0),
+                    entity,
+                }],
+            },
+        };
+
+        let entity = &clipboard
+            .rotated(Rotation::Clockwise180)
+            .structure_template
+            .entities[0];
+        assert_eq!(entity.position, (3.5, 0.0, 1.75));
+        assert_eq!(entity.block_position, (3, 0, 1));
+
+        match entity
+            .entity
+            .get::<&Vec<Tag>>("Rotation")
+            .unwrap()
+            .as_slice()
+        {
+            [Tag::Float(yaw), _] => assert_eq!(*yaw, 90.0),
+            rotation => panic!("Expected `Float` yaw but got `{:?}`", rotation),
+        }
+    }
+}
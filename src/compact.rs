@@ -0,0 +1,343 @@
+//! Shrinking region files by removing free sectors and recompressing chunks.
+//!
+//! Region files never shrink: deleted chunks and chunks which moved after growing leave
+//! free sectors behind. Compaction writes chunks of region one after another into new
+//! file which replaces the old one, keeping save times. With recompression every chunk
+//! is decoded and encoded again with zlib, which also converts chunks stored with gzip.
+//!
+//! Region file is locked while compacted, so writes of other processes using this crate
+//! wait for compaction and then go to the compacted file. Region files must not be
+//! written by the game while compacted.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::compact::{compact_provider, CompactOptions};
+//! use anvil_region::AnvilChunkProvider;
+//!
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//! let options = CompactOptions::new().dry_run();
+//!
+//! if let Ok(compact_report) = compact_provider(&chunk_provider, &options) {
+//!     println!("Can reclaim {} bytes", compact_report.reclaimed_bytes());
+//! }
+//! ```
+use crate::region_slice::RegionSlice;
+use crate::{
+    decode_chunk, encode_chunk, open_locked, AnvilChunkProvider, AnvilRegion, ChunkLoadError,
+    REGION_CHUNKS, REGION_HEADER_BYTES_LENGTH, REGION_SECTOR_BYTES_LENGTH,
+};
+use byteorder::{BigEndian, WriteBytesExt};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::{fs, io};
+
+/// Maximum amount of sectors of chunk which fits into header entry.
+const MAX_CHUNK_SECTORS: usize = 255;
+
+/// Possible errors while compacting region files.
+#[derive(Debug)]
+pub enum CompactError {
+    /// Chunk can't be read or decoded, region file is left unchanged.
+    ChunkLoadError { chunk_load_error: ChunkLoadError },
+    /// Region file can't be read or written.
+    IoError { io_error: io::Error },
+}
+
+impl From<ChunkLoadError> for CompactError {
+    fn from(chunk_load_error: ChunkLoadError) -> Self {
+        CompactError::ChunkLoadError { chunk_load_error }
+    }
+}
+
+impl From<io::Error> for CompactError {
+    fn from(io_error: io::Error) -> Self {
+        CompactError::IoError { io_error }
+    }
+}
+
+/// How region files are compacted.
+#[derive(Debug, Clone, Default)]
+pub struct CompactOptions {
+    /// Decode and encode every chunk again.
+    pub recompress: bool,
+    /// Only report how much space would be reclaimed.
+    pub dry_run: bool,
+}
+
+impl CompactOptions {
+    pub fn new() -> Self {
+        CompactOptions::default()
+    }
+
+    pub fn recompress(mut self) -> Self {
+        self.recompress = true;
+        self
+    }
+
+    pub fn dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+}
+
+/// Result of compaction.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CompactReport {
+    /// Region files which were rewritten or would be rewritten in dry run.
+    pub compacted_regions: usize,
+    /// Chunks which were decoded and encoded again.
+    pub recompressed_chunks: usize,
+    /// Length of region files before compaction.
+    pub original_bytes: u64,
+    /// Length of region files after compaction.
+    pub compacted_bytes: u64,
+}
+
+impl CompactReport {
+    /// Returns amount of bytes freed by compaction.
+    pub fn reclaimed_bytes(&self) -> u64 {
+        self.original_bytes.saturating_sub(self.compacted_bytes)
+    }
+
+    pub(crate) fn add(&mut self, compact_report: CompactReport) {
+        self.compacted_regions += compact_report.compacted_regions;
+        self.recompressed_chunks += compact_report.recompressed_chunks;
+        self.original_bytes += compact_report.original_bytes;
+        self.compacted_bytes += compact_report.compacted_bytes;
+    }
+}
+
+/// Compacts every region file of provider.
+///
+/// Regions are compacted one by one, so regions before the one which failed stay
+/// compacted.
+pub fn compact_provider(
+    chunk_provider: &AnvilChunkProvider,
+    options: &CompactOptions,
+) -> Result<CompactReport, CompactError> {
+    let mut compact_report = CompactReport::default();
+
+    for region_file in chunk_provider.region_files()? {
+        compact_report.add(compact_region_file(
+            chunk_provider,
+            &region_file.path,
+            options,
+        )?);
+    }
+
+    Ok(compact_report)
+}
+
+/// Compacts region file at the specified region coordinates.
+///
+/// Missing region file is reported as empty.
+pub fn compact_region(
+    chunk_provider: &AnvilChunkProvider,
+    region_x: i32,
+    region_z: i32,
+    options: &CompactOptions,
+) -> Result<CompactReport, CompactError> {
+    let region_name = format!("r.{}.{}.mca", region_x, region_z);
+    let region_path = chunk_provider.folder_path.join(region_name);
+
+    if !region_path.exists() {
+        return Ok(CompactReport::default());
+    }
+
+    compact_region_file(chunk_provider, &region_path, options)
+}
+
+fn compact_region_file(
+    chunk_provider: &AnvilChunkProvider,
+    region_path: &Path,
+    options: &CompactOptions,
+) -> Result<CompactReport, CompactError> {
+    // Lock is held until file is closed after it was replaced, dry run only reads.
+    let mut region_file = if options.dry_run {
+        fs::File::open(region_path)?
+    } else {
+        open_locked(region_path)?
+    };
+    let mut data = Vec::new();
+    region_file.read_to_end(&mut data)?;
+
+    let (compacted_data, recompressed_chunks) = compacted_region(&data, options.recompress)?;
+
+    let mut compact_report = CompactReport {
+        compacted_regions: 0,
+        recompressed_chunks,
+        original_bytes: data.len() as u64,
+        compacted_bytes: compacted_data.len() as u64,
+    };
+
+    if compacted_data.len() >= data.len() && !options.recompress {
+        compact_report.compacted_bytes = compact_report.original_bytes;
+        return Ok(compact_report);
+    }
+
+    compact_report.compacted_regions = 1;
+
+    if options.dry_run {
+        return Ok(compact_report);
+    }
+
+    // Region file is replaced at once, so it's never left half written.
+    let compact_path = region_path.with_extension("mca.compact");
+    let mut file = fs::File::create(&compact_path)?;
+    file.write_all(&compacted_data)?;
+    file.sync_all()?;
+    drop(file);
+
+    fs::rename(&compact_path, region_path)?;
+    chunk_provider.invalidate_region_headers(region_path);
+
+    Ok(compact_report)
+}
+
+/// Returns region file contents with chunks placed right after each other in order of
+/// header and amount of recompressed chunks.
+fn compacted_region(data: &[u8], recompress: bool) -> Result<(Vec<u8>, usize), CompactError> {
+    let region = RegionSlice::new(data)?;
+    let sector_length = REGION_SECTOR_BYTES_LENGTH as usize;
+
+    let mut offsets = [0u32; REGION_CHUNKS];
+    let mut timestamps = [0u32; REGION_CHUNKS];
+    let mut sectors_data = Vec::new();
+    let mut recompressed_chunks = 0;
+
+    for (chunk_x, chunk_z) in region.chunk_positions() {
+        let index = AnvilRegion::metadata_index(chunk_x, chunk_z);
+        let (compression_scheme, compressed_buffer) = region.read_chunk_data(chunk_x, chunk_z)?;
+
+        let mut chunk_buffer = Vec::with_capacity(compressed_buffer.len() + 1);
+        chunk_buffer.push(compression_scheme);
+        chunk_buffer.extend_from_slice(compressed_buffer);
+
+        if recompress {
+            let chunk_compound_tag = decode_chunk(compression_scheme, compressed_buffer)?;
+            let encoded_buffer = encode_chunk(&chunk_compound_tag)?;
+
+            // Chunk which doesn't fit into region after encoding is kept as it was.
+            if (encoded_buffer.len() + 4).div_ceil(sector_length) <= MAX_CHUNK_SECTORS {
+                chunk_buffer = encoded_buffer;
+                recompressed_chunks += 1;
+            }
+        }
+
+        let sector_index = REGION_HEADER_BYTES_LENGTH as usize / sector_length
+            + sectors_data.len() / sector_length;
+        let sectors = (chunk_buffer.len() + 4).div_ceil(sector_length);
+
+        sectors_data.write_u32::<BigEndian>(chunk_buffer.len() as u32)?;
+        sectors_data.extend_from_slice(&chunk_buffer);
+        sectors_data.resize(
+            sectors_data.len().div_ceil(sector_length) * sector_length,
+            0,
+        );
+
+        offsets[index] = ((sector_index as u32) << 8) | sectors as u32;
+        timestamps[index] = region
+            .chunk_last_modified(chunk_x, chunk_z)
+            .unwrap_or_default();
+    }
+
+    let mut compacted_data =
+        Vec::with_capacity(REGION_HEADER_BYTES_LENGTH as usize + sectors_data.len());
+
+    for value in offsets.iter().chain(timestamps.iter()) {
+        compacted_data.write_u32::<BigEndian>(*value)?;
+    }
+
+    compacted_data.extend_from_slice(&sectors_data);
+
+    Ok((compacted_data, recompressed_chunks))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compact::{compact_provider, compact_region, CompactOptions};
+    use crate::AnvilChunkProvider;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn provider_with_free_sectors(temp_dir: &TempDir) -> AnvilChunkProvider<'_> {
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+
+        for chunk_x in 4..8 {
+            let chunk_compound_tag = fixture_chunk_provider.load_chunk(chunk_x, 2).unwrap();
+            chunk_provider
+                .save_chunk(chunk_x, 2, chunk_compound_tag)
+                .unwrap();
+        }
+
+        chunk_provider.delete_chunk(4, 2).unwrap();
+        chunk_provider.delete_chunk(6, 2).unwrap();
+
+        chunk_provider
+    }
+
+    #[test]
+    fn test_compact_provider_dry_run() {
+        let temp_dir = TempDir::new().unwrap();
+        let chunk_provider = provider_with_free_sectors(&temp_dir);
+        let region_path = temp_dir.path().join("r.0.0.mca");
+        let region_length = fs::metadata(&region_path).unwrap().len();
+
+        let options = CompactOptions::new().dry_run();
+        let compact_report = compact_provider(&chunk_provider, &options).unwrap();
+
+        assert_eq!(compact_report.compacted_regions, 1);
+        assert_eq!(compact_report.original_bytes, region_length);
+        assert!(compact_report.reclaimed_bytes() > 0);
+        assert_eq!(fs::metadata(&region_path).unwrap().len(), region_length);
+    }
+
+    #[test]
+    fn test_compact_provider() {
+        let temp_dir = TempDir::new().unwrap();
+        let chunk_provider = provider_with_free_sectors(&temp_dir);
+        let last_modified = chunk_provider.chunk_last_modified(5, 2).unwrap();
+
+        let compact_report = compact_provider(&chunk_provider, &CompactOptions::new()).unwrap();
+        let region_length = fs::metadata(temp_dir.path().join("r.0.0.mca"))
+            .unwrap()
+            .len();
+
+        assert_eq!(compact_report.compacted_bytes, region_length);
+        assert!(compact_report.reclaimed_bytes() > 0);
+        assert_eq!(
+            chunk_provider.chunk_positions().unwrap(),
+            vec![(5, 2), (7, 2)]
+        );
+        assert_eq!(
+            chunk_provider.chunk_last_modified(5, 2).unwrap(),
+            last_modified
+        );
+        assert!(chunk_provider.load_chunk(5, 2).is_ok());
+        assert!(chunk_provider.load_chunk(7, 2).is_ok());
+
+        // Compacted region has no free sectors left.
+        let compact_report = compact_provider(&chunk_provider, &CompactOptions::new()).unwrap();
+        assert_eq!(compact_report.compacted_regions, 0);
+        assert_eq!(compact_report.reclaimed_bytes(), 0);
+    }
+
+    #[test]
+    fn test_compact_region_recompress() {
+        let temp_dir = TempDir::new().unwrap();
+        let chunk_provider = provider_with_free_sectors(&temp_dir);
+
+        let options = CompactOptions::new().recompress();
+        let compact_report = compact_region(&chunk_provider, 0, 0, &options).unwrap();
+
+        assert_eq!(compact_report.compacted_regions, 1);
+        assert_eq!(compact_report.recompressed_chunks, 2);
+        assert!(chunk_provider.load_chunk(7, 2).is_ok());
+
+        let compact_report = compact_region(&chunk_provider, 1, 0, &options).unwrap();
+        assert_eq!(compact_report.compacted_regions, 0);
+    }
+}
@@ -0,0 +1,273 @@
+//! Chunk provider which can be shared between threads.
+//!
+//! Loads of chunks run concurrently, while saves and deletes hold region lock, so they
+//! are serialized with every other operation on the same region file. Operations on
+//! different region files never wait for each other.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::concurrent::ConcurrentAnvilChunkProvider;
+//! use std::thread;
+//!
+//! let chunk_provider = ConcurrentAnvilChunkProvider::new("test/region");
+//!
+//! thread::scope(|scope| {
+//!     for chunk_position in [(4, 2), (15, 3)] {
+//!         let chunk_provider = &chunk_provider;
+//!
+//!         scope.spawn(move || {
+//!             let (chunk_x, chunk_z) = chunk_position;
+//!             assert!(chunk_provider.load_chunk(chunk_x, chunk_z).is_ok());
+//!         });
+//!     }
+//! });
+//! ```
+use crate::hash::CoordinateHashMap;
+use crate::headers::RegionHeaders;
+use crate::metrics::{Metrics, NoopMetrics, ProviderActivity, ProviderCounters};
+use crate::snapshot::break_hard_link;
+use crate::{z_order_key, AnvilChunkProvider, AnvilRegion, ChunkLoadError, ChunkSaveError};
+use nbt::CompoundTag;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::{fs, io};
+
+/// Locks of region files keyed by region position.
+type RegionLocks = CoordinateHashMap<(i32, i32), Arc<RwLock<()>>>;
+
+/// Provider of chunks of region folder with per region locking.
+pub struct ConcurrentAnvilChunkProvider {
+    /// Folder where region files located.
+    folder_path: PathBuf,
+    /// Locks of region files which were accessed, keyed by region position.
+    region_locks: Mutex<RegionLocks>,
+    /// Activity counters shared by providers of every operation.
+    counters: Arc<ProviderCounters>,
+}
+
+impl ConcurrentAnvilChunkProvider {
+    pub fn new(folder_path: impl AsRef<Path>) -> Self {
+        ConcurrentAnvilChunkProvider {
+            folder_path: folder_path.as_ref().to_path_buf(),
+            region_locks: Mutex::new(CoordinateHashMap::default()),
+            counters: Arc::new(ProviderCounters::new(Arc::new(NoopMetrics))),
+        }
+    }
+
+    /// Sets metrics which loads and saves of every thread report into.
+    pub fn metrics(mut self, metrics: Arc<dyn Metrics>) -> Self {
+        self.counters = Arc::new(ProviderCounters::new(metrics));
+        self
+    }
+
+    /// Returns counts of loads, saves, errors and bytes read and written by every
+    /// thread since provider was created or its metrics were set.
+    ///
+    /// Headers are read by every operation, so no headers are cached.
+    pub fn stats(&self) -> ProviderActivity {
+        self.counters.activity(0)
+    }
+
+    pub fn folder_path(&self) -> &Path {
+        &self.folder_path
+    }
+
+    /// Loads chunk from the specified coordinates.
+    pub fn load_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<CompoundTag, ChunkLoadError> {
+        let region_lock = self.region_lock(chunk_x >> 5, chunk_z >> 5);
+        let _guard = region_lock
+            .read()
+            .unwrap_or_else(|error| error.into_inner());
+
+        self.chunk_provider().load_chunk(chunk_x, chunk_z)
+    }
+
+    /// Saves chunk data to the specified coordinates.
+    pub fn save_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        chunk_compound_tag: CompoundTag,
+    ) -> Result<(), ChunkSaveError> {
+        let region_lock = self.region_lock(chunk_x >> 5, chunk_z >> 5);
+        let _guard = region_lock
+            .write()
+            .unwrap_or_else(|error| error.into_inner());
+
+        self.chunk_provider()
+            .save_chunk(chunk_x, chunk_z, chunk_compound_tag)
+    }
+
+    /// Deletes chunk at the specified coordinates, returns false if chunk is not present.
+    pub fn delete_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<bool, ChunkSaveError> {
+        let region_lock = self.region_lock(chunk_x >> 5, chunk_z >> 5);
+        let _guard = region_lock
+            .write()
+            .unwrap_or_else(|error| error.into_inner());
+
+        self.chunk_provider().delete_chunk(chunk_x, chunk_z)
+    }
+
+    /// Returns true if chunk at the specified coordinates is present.
+    pub(crate) fn has_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<bool, io::Error> {
+        let region_lock = self.region_lock(chunk_x >> 5, chunk_z >> 5);
+        let _guard = region_lock
+            .read()
+            .unwrap_or_else(|error| error.into_inner());
+
+        let metadata = self.chunk_provider().chunk_metadata(chunk_x, chunk_z)?;
+
+        Ok(metadata.is_some())
+    }
+
+    /// Saves and deletes chunks of one region opening region file once.
+    ///
+    /// Chunks without compound tag are deleted. Chunks are written along Z-order curve,
+    /// so neighbouring chunks end up next to each other in region file.
+    pub(crate) fn write_region_chunks(
+        &self,
+        region_position: (i32, i32),
+        mut chunks: Vec<(i32, i32, Option<CompoundTag>)>,
+    ) -> Result<(), ChunkSaveError> {
+        let (region_x, region_z) = region_position;
+        let region_lock = self.region_lock(region_x, region_z);
+        let _guard = region_lock
+            .write()
+            .unwrap_or_else(|error| error.into_inner());
+
+        let region_name = format!("r.{}.{}.mca", region_x, region_z);
+        let region_path = self.folder_path.join(region_name);
+
+        let has_saves = chunks
+            .iter()
+            .any(|(_, _, chunk_compound_tag)| chunk_compound_tag.is_some());
+
+        // Deleting chunks of missing region doesn't need to create it.
+        if !has_saves && !region_path.exists() {
+            return Ok(());
+        }
+
+        if !self.folder_path.exists() {
+            fs::create_dir_all(&self.folder_path)?;
+        }
+
+        break_hard_link(&region_path)?;
+
+        let mut region = AnvilRegion::new(&region_path)?;
+
+        // Stable sort keeps order of repeated writes of the same chunk.
+        chunks.sort_by_key(|(chunk_x, chunk_z, _)| z_order_key(*chunk_x, *chunk_z));
+
+        region.write_locked(|region| {
+            for (chunk_x, chunk_z, chunk_compound_tag) in chunks {
+                let region_chunk_x = (chunk_x & 31) as u8;
+                let region_chunk_z = (chunk_z & 31) as u8;
+
+                match chunk_compound_tag {
+                    Some(chunk_compound_tag) => {
+                        region.write_chunk(region_chunk_x, region_chunk_z, chunk_compound_tag)?
+                    }
+                    None => {
+                        region.delete_chunk(region_chunk_x, region_chunk_z)?;
+                    }
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Returns coordinates of all chunks stored in the folder region files.
+    pub fn chunk_positions(&self) -> Result<Vec<(i32, i32)>, io::Error> {
+        let chunk_provider = self.chunk_provider();
+        let mut chunk_positions = Vec::new();
+
+        for region_file in chunk_provider.region_files()? {
+            let (region_x, region_z) = region_file.region_position;
+            let region_lock = self.region_lock(region_x, region_z);
+            let _guard = region_lock
+                .read()
+                .unwrap_or_else(|error| error.into_inner());
+
+            let region_headers = RegionHeaders::open(&region_file.path)?;
+
+            for (region_chunk_x, region_chunk_z) in region_headers.chunk_positions() {
+                let chunk_x = (region_x << 5) + region_chunk_x as i32;
+                let chunk_z = (region_z << 5) + region_chunk_z as i32;
+
+                chunk_positions.push((chunk_x, chunk_z));
+            }
+        }
+
+        chunk_positions.sort_unstable();
+
+        Ok(chunk_positions)
+    }
+
+    fn chunk_provider(&self) -> AnvilChunkProvider<'_> {
+        AnvilChunkProvider::from_path(&self.folder_path).counters(self.counters.clone())
+    }
+
+    fn region_lock(&self, region_x: i32, region_z: i32) -> Arc<RwLock<()>> {
+        let mut region_locks = self
+            .region_locks
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+
+        region_locks
+            .entry((region_x, region_z))
+            .or_default()
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::concurrent::ConcurrentAnvilChunkProvider;
+    use crate::AnvilChunkProvider;
+    use std::thread;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_concurrent_saves_to_one_region() {
+        let temp_dir = TempDir::new().unwrap();
+        let chunk_provider = ConcurrentAnvilChunkProvider::new(temp_dir.path());
+        let chunk_compound_tag = AnvilChunkProvider::new("test/region")
+            .load_chunk(4, 2)
+            .unwrap();
+
+        thread::scope(|scope| {
+            for chunk_x in 0..8 {
+                let chunk_provider = &chunk_provider;
+                let chunk_compound_tag = chunk_compound_tag.clone();
+
+                scope.spawn(move || {
+                    for chunk_z in 0..4 {
+                        chunk_provider
+                            .save_chunk(chunk_x, chunk_z, chunk_compound_tag.clone())
+                            .unwrap();
+                        chunk_provider.load_chunk(chunk_x, chunk_z).unwrap();
+                    }
+                });
+            }
+        });
+
+        let chunk_positions = chunk_provider.chunk_positions().unwrap();
+        let provider_activity = chunk_provider.stats();
+
+        assert_eq!(chunk_positions.len(), 32);
+        assert_eq!(provider_activity.chunks_saved, 32);
+        assert_eq!(provider_activity.chunks_loaded, 32);
+        assert!(chunk_provider.delete_chunk(7, 3).unwrap());
+        assert!(!chunk_provider.delete_chunk(7, 3).unwrap());
+        assert_eq!(chunk_provider.chunk_positions().unwrap().len(), 31);
+    }
+
+    #[test]
+    fn test_provider_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        assert_send_sync::<ConcurrentAnvilChunkProvider>();
+    }
+}
@@ -0,0 +1,152 @@
+//! Checked conversions between region, chunk and block coordinates.
+//!
+//! Region file names and positions stored in chunks come from untrusted data, so
+//! region coordinate like `r.99999999.0.mca` or `xPos` of `i32::MIN` would overflow
+//! when converted to chunk or block coordinates. Conversions here are computed in
+//! 64 bits and return [`CoordinateError::OutOfBounds`] if result doesn't fit into
+//! `i32`. Conversions to larger units, like chunk to region, never overflow and
+//! are plain shifts.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::coords::{chunk_block_position, region_chunk_position, CoordinateError};
+//!
+//! assert_eq!(region_chunk_position(-1, 2, 31, 0), Ok((-1, 64)));
+//! assert_eq!(chunk_block_position(1_875_000, 0), Ok((30_000_000, 0)));
+//!
+//! assert_eq!(
+//!     chunk_block_position(i32::MIN, 0),
+//!     Err(CoordinateError::OutOfBounds {
+//!         x: i32::MIN as i64 * 16,
+//!         z: 0,
+//!     })
+//! );
+//! ```
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+/// Possible errors of coordinate conversions.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CoordinateError {
+    /// Converted coordinates don't fit into `i32`.
+    OutOfBounds { x: i64, z: i64 },
+}
+
+impl fmt::Display for CoordinateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CoordinateError::OutOfBounds { x, z } => {
+                write!(f, "coordinates {} {} are out of bounds", x, z)
+            }
+        }
+    }
+}
+
+impl Error for CoordinateError {}
+
+/// Returns position of chunk inside of region.
+pub fn region_chunk_position(
+    region_x: i32,
+    region_z: i32,
+    region_chunk_x: u8,
+    region_chunk_z: u8,
+) -> Result<(i32, i32), CoordinateError> {
+    checked_position(
+        region_x as i64 * 32 + (region_chunk_x & 31) as i64,
+        region_z as i64 * 32 + (region_chunk_z & 31) as i64,
+    )
+}
+
+/// Returns true if every chunk of region has coordinates which fit into `i32`.
+pub fn is_region_in_bounds(region_x: i32, region_z: i32) -> bool {
+    region_chunk_position(region_x, region_z, 0, 0).is_ok()
+        && region_chunk_position(region_x, region_z, 31, 31).is_ok()
+}
+
+/// Returns position of the minimum block of chunk.
+pub fn chunk_block_position(chunk_x: i32, chunk_z: i32) -> Result<(i32, i32), CoordinateError> {
+    checked_position(chunk_x as i64 * 16, chunk_z as i64 * 16)
+}
+
+/// Returns position of region which contains chunk.
+pub fn chunk_region_position(chunk_x: i32, chunk_z: i32) -> (i32, i32) {
+    (chunk_x >> 5, chunk_z >> 5)
+}
+
+/// Returns position of chunk which contains block.
+pub fn block_chunk_position(block_x: i32, block_z: i32) -> (i32, i32) {
+    (block_x >> 4, block_z >> 4)
+}
+
+/// Returns offset which moves first position to second one.
+pub fn position_offset(
+    (x, z): (i32, i32),
+    (target_x, target_z): (i32, i32),
+) -> Result<(i32, i32), CoordinateError> {
+    checked_position(target_x as i64 - x as i64, target_z as i64 - z as i64)
+}
+
+/// Returns position moved by offset.
+pub fn offset_position(
+    (x, z): (i32, i32),
+    (offset_x, offset_z): (i32, i32),
+) -> Result<(i32, i32), CoordinateError> {
+    checked_position(x as i64 + offset_x as i64, z as i64 + offset_z as i64)
+}
+
+fn checked_position(x: i64, z: i64) -> Result<(i32, i32), CoordinateError> {
+    match (i32::try_from(x), i32::try_from(z)) {
+        (Ok(x), Ok(z)) => Ok((x, z)),
+        _ => Err(CoordinateError::OutOfBounds { x, z }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::coords::{
+        block_chunk_position, chunk_block_position, chunk_region_position, is_region_in_bounds,
+        offset_position, position_offset, region_chunk_position, CoordinateError,
+    };
+
+    #[test]
+    fn test_world_border() {
+        let (chunk_x, chunk_z) = block_chunk_position(29_999_999, -30_000_000);
+        assert_eq!((chunk_x, chunk_z), (1_874_999, -1_875_000));
+        assert_eq!(chunk_region_position(chunk_x, chunk_z), (58_593, -58_594));
+        assert_eq!(
+            chunk_block_position(chunk_x, chunk_z),
+            Ok((29_999_984, -30_000_000))
+        );
+        assert_eq!(
+            region_chunk_position(58_593, -58_594, 31, 0),
+            Ok((1_875_007, -1_875_008))
+        );
+    }
+
+    #[test]
+    fn test_i32_extremes() {
+        assert!(is_region_in_bounds(i32::MAX >> 5, i32::MIN >> 5));
+        assert!(!is_region_in_bounds((i32::MAX >> 5) + 1, 0));
+        assert!(!is_region_in_bounds(0, (i32::MIN >> 5) - 1));
+        assert_eq!(
+            region_chunk_position(i32::MAX >> 5, 0, 31, 0),
+            Ok((i32::MAX, 0))
+        );
+
+        assert!(chunk_block_position(i32::MAX / 16, i32::MIN / 16).is_ok());
+        assert!(chunk_block_position(i32::MAX, 0).is_err());
+
+        assert_eq!(
+            position_offset((i32::MIN, 0), (i32::MAX, 0)),
+            Err(CoordinateError::OutOfBounds {
+                x: u32::MAX as i64,
+                z: 0,
+            })
+        );
+        assert_eq!(position_offset((-5, 3), (5, -3)), Ok((10, -6)));
+        assert!(offset_position((i32::MAX, 0), (1, 0)).is_err());
+    }
+}
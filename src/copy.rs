@@ -0,0 +1,598 @@
+//! Copying worlds while filtering or transforming chunks.
+//!
+//! Every chunk of every region folder is passed through callback which may return it
+//! unchanged, modify it or drop it by returning none. Files which are not region files
+//! like `level.dat` and player data are copied as is, so target is a complete world.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::copy::copy_world;
+//! use anvil_region::relocate::copy_chunk;
+//! use anvil_region::AnvilChunkProvider;
+//! use tempfile::TempDir;
+//!
+//! let world_dir = TempDir::new().unwrap();
+//! let target_world_dir = TempDir::new().unwrap();
+//! let region_folder = world_dir.path().join("region");
+//!
+//! let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+//! let chunk_provider = AnvilChunkProvider::new(region_folder.to_str().unwrap());
+//! copy_chunk(&fixture_chunk_provider, (4, 2), &chunk_provider, (4, 2)).unwrap();
+//!
+//! // Strip entities stored in terrain chunks before 1.17.
+//! let copy_report = copy_world(world_dir.path(), target_world_dir.path(), |_, _, mut chunk_compound_tag| {
+//!     if let Ok(level) = chunk_compound_tag.get_mut::<&mut nbt::CompoundTag>("Level") {
+//!         level.insert("Entities", nbt::Tag::List(Vec::new()));
+//!     }
+//!
+//!     Some(chunk_compound_tag)
+//! })
+//! .unwrap();
+//!
+//! assert_eq!(copy_report.copied_chunks, 1);
+//! ```
+use crate::cancel::CancellationToken;
+use crate::progress::{ProgressReporter, ProgressTracker};
+use crate::{
+    region_position, z_order_key, AnvilChunkProvider, AnvilRegion, ChunkLoadError, ChunkSaveError,
+};
+use nbt::CompoundTag;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{fs, io};
+
+/// Lock file of running game which must not be copied.
+const SESSION_LOCK_FILE: &str = "session.lock";
+
+/// Possible errors while copying world.
+#[derive(Debug)]
+pub enum CopyError {
+    /// Target folder is source folder or is inside of it.
+    TargetIsSource,
+    /// Chunk can't be loaded.
+    ChunkLoadError { chunk_load_error: ChunkLoadError },
+    /// Chunk can't be saved.
+    ChunkSaveError { chunk_save_error: ChunkSaveError },
+    /// File can't be read or written.
+    IoError { io_error: io::Error },
+    /// Copy was cancelled, chunks copied before cancellation stay in target.
+    Cancelled,
+}
+
+impl From<ChunkLoadError> for CopyError {
+    fn from(chunk_load_error: ChunkLoadError) -> Self {
+        CopyError::ChunkLoadError { chunk_load_error }
+    }
+}
+
+impl From<ChunkSaveError> for CopyError {
+    fn from(chunk_save_error: ChunkSaveError) -> Self {
+        CopyError::ChunkSaveError { chunk_save_error }
+    }
+}
+
+impl From<io::Error> for CopyError {
+    fn from(io_error: io::Error) -> Self {
+        CopyError::IoError { io_error }
+    }
+}
+
+/// Result of copy.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CopyReport {
+    /// Chunks which were written to target.
+    pub copied_chunks: usize,
+    /// Chunks for which callback returned none.
+    pub dropped_chunks: usize,
+    /// Files other than region files which were copied.
+    pub copied_files: usize,
+}
+
+/// Copies chunks of provider into target provider through callback.
+///
+/// Callback receives chunk position and compound tag and returns compound tag to write.
+/// Chunks are copied region by region along Z-order curve, so neighbouring chunks are
+/// placed next to each other in target region files.
+pub fn copy_provider(
+    chunk_provider: &AnvilChunkProvider,
+    target_chunk_provider: &AnvilChunkProvider,
+    transform: impl FnMut(i32, i32, CompoundTag) -> Option<CompoundTag>,
+) -> Result<CopyReport, CopyError> {
+    copy_provider_cancellable(
+        chunk_provider,
+        target_chunk_provider,
+        &CancellationToken::new(),
+        transform,
+    )
+}
+
+/// Copies chunks of provider into target provider through callback until token is
+/// cancelled.
+pub fn copy_provider_cancellable(
+    chunk_provider: &AnvilChunkProvider,
+    target_chunk_provider: &AnvilChunkProvider,
+    cancellation_token: &CancellationToken,
+    mut transform: impl FnMut(i32, i32, CompoundTag) -> Option<CompoundTag>,
+) -> Result<CopyReport, CopyError> {
+    copy_chunks(
+        chunk_provider,
+        target_chunk_provider,
+        cancellation_token,
+        &mut ProgressTracker::new(None),
+        &mut transform,
+    )
+}
+
+/// Copies chunks of provider into target provider through callback until token is
+/// cancelled, reporting progress after every chunk.
+pub fn copy_provider_with_progress(
+    chunk_provider: &AnvilChunkProvider,
+    target_chunk_provider: &AnvilChunkProvider,
+    cancellation_token: &CancellationToken,
+    progress_reporter: &ProgressReporter,
+    mut transform: impl FnMut(i32, i32, CompoundTag) -> Option<CompoundTag>,
+) -> Result<CopyReport, CopyError> {
+    let mut progress_tracker = ProgressTracker::new(Some(progress_reporter.clone()));
+
+    for region_file in chunk_provider.region_files()? {
+        progress_tracker.add_region(&region_file.path);
+    }
+
+    let copy_report = copy_chunks(
+        chunk_provider,
+        target_chunk_provider,
+        cancellation_token,
+        &mut progress_tracker,
+        &mut transform,
+    )?;
+
+    progress_tracker.finish();
+
+    Ok(copy_report)
+}
+
+fn copy_chunks(
+    chunk_provider: &AnvilChunkProvider,
+    target_chunk_provider: &AnvilChunkProvider,
+    cancellation_token: &CancellationToken,
+    progress_tracker: &mut ProgressTracker,
+    transform: &mut impl FnMut(i32, i32, CompoundTag) -> Option<CompoundTag>,
+) -> Result<CopyReport, CopyError> {
+    if chunk_provider.folder_path == target_chunk_provider.folder_path {
+        return Err(CopyError::TargetIsSource);
+    }
+
+    let mut copy_report = CopyReport::default();
+    let mut chunk_positions = chunk_provider.chunk_positions()?;
+
+    // Target regions are filled one by one with neighbouring chunks next to each other.
+    chunk_positions.sort_unstable_by_key(|&(chunk_x, chunk_z)| z_order_key(chunk_x, chunk_z));
+
+    let mut region_position = None;
+
+    for (chunk_x, chunk_z) in chunk_positions {
+        if cancellation_token.is_cancelled() {
+            return Err(CopyError::Cancelled);
+        }
+
+        if region_position != Some((chunk_x >> 5, chunk_z >> 5)) {
+            region_position = Some((chunk_x >> 5, chunk_z >> 5));
+
+            let region_name = format!("r.{}.{}.mca", chunk_x >> 5, chunk_z >> 5);
+            progress_tracker.start_region(&chunk_provider.folder_path.join(region_name));
+        }
+
+        let chunk_compound_tag = chunk_provider.load_chunk(chunk_x, chunk_z)?;
+
+        match transform(chunk_x, chunk_z, chunk_compound_tag) {
+            Some(chunk_compound_tag) => {
+                target_chunk_provider.save_chunk(chunk_x, chunk_z, chunk_compound_tag)?;
+                copy_report.copied_chunks += 1;
+            }
+            None => copy_report.dropped_chunks += 1,
+        }
+
+        progress_tracker.chunk(chunk_x, chunk_z);
+    }
+
+    progress_tracker.finish_region();
+
+    Ok(copy_report)
+}
+
+/// Copies chunks of provider saved after the specified time into target provider.
+///
+/// Only save times in region headers are compared, chunks are copied still compressed
+/// without decoding, so repeated exports with time of the previous one keep a copy of
+/// world in sync cheaply. Chunks deleted from provider stay in target.
+pub fn export_modified_since(
+    chunk_provider: &AnvilChunkProvider,
+    modified_since: SystemTime,
+    target_chunk_provider: &AnvilChunkProvider,
+) -> Result<CopyReport, CopyError> {
+    if chunk_provider.folder_path == target_chunk_provider.folder_path {
+        return Err(CopyError::TargetIsSource);
+    }
+
+    let modified_since = modified_since
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+
+    let mut copy_report = CopyReport::default();
+
+    for region_file in chunk_provider.region_files()? {
+        let (region_x, region_z) = region_file.region_position;
+        let mut region = AnvilRegion::new(&region_file.path)?;
+
+        for (region_chunk_x, region_chunk_z) in region.chunk_positions() {
+            let metadata = region.get_metadata(region_chunk_x, region_chunk_z);
+
+            if metadata.last_modified_timestamp as u64 <= modified_since {
+                continue;
+            }
+
+            let (compression_scheme, compressed_buffer) =
+                region.read_chunk_data(region_chunk_x, region_chunk_z)?;
+
+            let mut chunk_buffer = Vec::with_capacity(compressed_buffer.len() + 1);
+            chunk_buffer.push(compression_scheme);
+            chunk_buffer.extend_from_slice(&compressed_buffer);
+
+            let chunk_x = (region_x << 5) + region_chunk_x as i32;
+            let chunk_z = (region_z << 5) + region_chunk_z as i32;
+
+            target_chunk_provider.save_chunk_buffer(chunk_x, chunk_z, &chunk_buffer)?;
+            copy_report.copied_chunks += 1;
+        }
+    }
+
+    Ok(copy_report)
+}
+
+/// Copies whole world folder passing chunks of every region folder through callback.
+///
+/// Region folders of all dimensions, including entity and point of interest folders,
+/// are streamed one chunk at a time, other files except `session.lock` are copied
+/// unchanged.
+pub fn copy_world(
+    world_folder_path: &Path,
+    target_world_folder_path: &Path,
+    transform: impl FnMut(i32, i32, CompoundTag) -> Option<CompoundTag>,
+) -> Result<CopyReport, CopyError> {
+    copy_world_cancellable(
+        world_folder_path,
+        target_world_folder_path,
+        &CancellationToken::new(),
+        transform,
+    )
+}
+
+/// Copies whole world folder passing chunks through callback until token is cancelled.
+pub fn copy_world_cancellable(
+    world_folder_path: &Path,
+    target_world_folder_path: &Path,
+    cancellation_token: &CancellationToken,
+    mut transform: impl FnMut(i32, i32, CompoundTag) -> Option<CompoundTag>,
+) -> Result<CopyReport, CopyError> {
+    copy_world_folder(
+        world_folder_path,
+        target_world_folder_path,
+        cancellation_token,
+        &mut ProgressTracker::new(None),
+        &mut transform,
+    )
+}
+
+/// Copies whole world folder passing chunks through callback until token is cancelled,
+/// reporting progress over region files of all region folders.
+pub fn copy_world_with_progress(
+    world_folder_path: &Path,
+    target_world_folder_path: &Path,
+    cancellation_token: &CancellationToken,
+    progress_reporter: &ProgressReporter,
+    mut transform: impl FnMut(i32, i32, CompoundTag) -> Option<CompoundTag>,
+) -> Result<CopyReport, CopyError> {
+    let mut progress_tracker = ProgressTracker::new(Some(progress_reporter.clone()));
+
+    copy_world_folder(
+        world_folder_path,
+        target_world_folder_path,
+        cancellation_token,
+        &mut progress_tracker,
+        &mut transform,
+    )
+}
+
+fn copy_world_folder(
+    world_folder_path: &Path,
+    target_world_folder_path: &Path,
+    cancellation_token: &CancellationToken,
+    progress_tracker: &mut ProgressTracker,
+    transform: &mut impl FnMut(i32, i32, CompoundTag) -> Option<CompoundTag>,
+) -> Result<CopyReport, CopyError> {
+    if target_world_folder_path.starts_with(world_folder_path) {
+        return Err(CopyError::TargetIsSource);
+    }
+
+    if progress_tracker.is_enabled() {
+        add_region_files(world_folder_path, progress_tracker)?;
+    }
+
+    let mut copy_report = CopyReport::default();
+
+    copy_folder(
+        world_folder_path,
+        target_world_folder_path,
+        cancellation_token,
+        progress_tracker,
+        transform,
+        &mut copy_report,
+    )?;
+
+    progress_tracker.finish();
+
+    Ok(copy_report)
+}
+
+/// Adds region files of folder and its subfolders to totals of progress.
+fn add_region_files(folder_path: &Path, progress_tracker: &mut ProgressTracker) -> io::Result<()> {
+    for entry in fs::read_dir(folder_path)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            add_region_files(&path, progress_tracker)?;
+        } else if path
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .and_then(region_position)
+            .is_some()
+        {
+            progress_tracker.add_region(&path);
+        }
+    }
+
+    Ok(())
+}
+
+fn copy_folder(
+    folder_path: &Path,
+    target_folder_path: &Path,
+    cancellation_token: &CancellationToken,
+    progress_tracker: &mut ProgressTracker,
+    transform: &mut impl FnMut(i32, i32, CompoundTag) -> Option<CompoundTag>,
+    copy_report: &mut CopyReport,
+) -> Result<(), CopyError> {
+    fs::create_dir_all(target_folder_path)?;
+
+    let mut has_region_files = false;
+
+    for entry in fs::read_dir(folder_path)? {
+        if cancellation_token.is_cancelled() {
+            return Err(CopyError::Cancelled);
+        }
+
+        let path = entry?.path();
+
+        let file_name = match path.file_name().and_then(|file_name| file_name.to_str()) {
+            Some(file_name) => file_name,
+            None => continue,
+        };
+
+        let target_path = target_folder_path.join(file_name);
+
+        if path.is_dir() {
+            copy_folder(
+                &path,
+                &target_path,
+                cancellation_token,
+                progress_tracker,
+                transform,
+                copy_report,
+            )?;
+        } else if region_position(file_name).is_some() {
+            has_region_files = true;
+        } else if file_name != SESSION_LOCK_FILE {
+            fs::copy(&path, &target_path)?;
+            copy_report.copied_files += 1;
+        }
+    }
+
+    if has_region_files {
+        let chunk_provider = AnvilChunkProvider::from_path(folder_path);
+
+        let target_chunk_provider = AnvilChunkProvider::from_path(target_folder_path);
+
+        let provider_report = copy_chunks(
+            &chunk_provider,
+            &target_chunk_provider,
+            cancellation_token,
+            progress_tracker,
+            transform,
+        )?;
+
+        copy_report.copied_chunks += provider_report.copied_chunks;
+        copy_report.dropped_chunks += provider_report.dropped_chunks;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cancel::CancellationToken;
+    use crate::copy::{
+        copy_provider, copy_world, copy_world_with_progress, export_modified_since, CopyError,
+        CopyReport,
+    };
+    use crate::progress::ProgressReporter;
+    use crate::relocate::copy_chunk;
+    use crate::touch::{set_timestamps, TimestampPolicy};
+    use crate::AnvilChunkProvider;
+    use std::fs;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_copy_world() {
+        let world_dir = TempDir::new().unwrap();
+        let target_world_dir = TempDir::new().unwrap();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+
+        for folder in &["region", "DIM-1/region"] {
+            let region_folder = world_dir.path().join(folder);
+            let chunk_provider = AnvilChunkProvider::new(region_folder.to_str().unwrap());
+
+            copy_chunk(&fixture_chunk_provider, (4, 2), &chunk_provider, (4, 2)).unwrap();
+            copy_chunk(&fixture_chunk_provider, (4, 2), &chunk_provider, (5, 2)).unwrap();
+        }
+
+        fs::write(world_dir.path().join("level.dat"), b"level").unwrap();
+        fs::write(world_dir.path().join("session.lock"), b"lock").unwrap();
+
+        let copy_report = copy_world(
+            world_dir.path(),
+            target_world_dir.path(),
+            |chunk_x, _, chunk_compound_tag| {
+                if chunk_x == 5 {
+                    return None;
+                }
+
+                Some(chunk_compound_tag)
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            copy_report,
+            CopyReport {
+                copied_chunks: 2,
+                dropped_chunks: 2,
+                copied_files: 1,
+            }
+        );
+
+        let nether_folder = target_world_dir.path().join("DIM-1/region");
+        let target_chunk_provider = AnvilChunkProvider::new(nether_folder.to_str().unwrap());
+
+        assert_eq!(
+            target_chunk_provider.chunk_positions().unwrap(),
+            vec![(4, 2)]
+        );
+        assert!(target_world_dir.path().join("level.dat").exists());
+        assert!(!target_world_dir.path().join("session.lock").exists());
+    }
+
+    #[test]
+    fn test_copy_into_itself() {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+
+        match copy_provider(&chunk_provider, &chunk_provider, |_, _, tag| Some(tag)) {
+            Err(CopyError::TargetIsSource) => {}
+            result => panic!("Expected `TargetIsSource` but got `{:?}`", result),
+        }
+
+        match export_modified_since(&chunk_provider, UNIX_EPOCH, &chunk_provider) {
+            Err(CopyError::TargetIsSource) => {}
+            result => panic!("Expected `TargetIsSource` but got `{:?}`", result),
+        }
+
+        let world_dir = TempDir::new().unwrap();
+        let target_world_folder = world_dir.path().join("backup");
+
+        match copy_world(world_dir.path(), &target_world_folder, |_, _, tag| {
+            Some(tag)
+        }) {
+            Err(CopyError::TargetIsSource) => {}
+            result => panic!("Expected `TargetIsSource` but got `{:?}`", result),
+        }
+    }
+
+    #[test]
+    fn test_copy_world_with_progress() {
+        let world_dir = TempDir::new().unwrap();
+        let target_world_dir = TempDir::new().unwrap();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+
+        for folder in &["region", "DIM1/region"] {
+            let region_folder = world_dir.path().join(folder);
+            let chunk_provider = AnvilChunkProvider::new(region_folder.to_str().unwrap());
+
+            copy_chunk(&fixture_chunk_provider, (4, 2), &chunk_provider, (4, 2)).unwrap();
+            copy_chunk(&fixture_chunk_provider, (4, 2), &chunk_provider, (40, 2)).unwrap();
+        }
+
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let reported = reports.clone();
+        let progress_reporter =
+            ProgressReporter::new(move |progress| reported.lock().unwrap().push(progress.clone()));
+
+        copy_world_with_progress(
+            world_dir.path(),
+            target_world_dir.path(),
+            &CancellationToken::new(),
+            &progress_reporter,
+            |_, _, chunk_compound_tag| Some(chunk_compound_tag),
+        )
+        .unwrap();
+
+        let reports = reports.lock().unwrap();
+
+        // Every chunk and every region is reported.
+        assert_eq!(reports.len(), 8);
+        assert!(reports.iter().all(|progress| progress.total_regions == 4));
+        assert!(reports
+            .windows(2)
+            .all(|pair| pair[0].processed_bytes <= pair[1].processed_bytes));
+
+        let last_progress = reports.last().unwrap();
+        assert_eq!(last_progress.processed_regions, 4);
+        assert_eq!(last_progress.processed_chunks, 4);
+        assert_eq!(last_progress.processed_bytes, last_progress.total_bytes);
+    }
+
+    #[test]
+    fn test_export_modified_since() {
+        let temp_dir = TempDir::new().unwrap();
+        let target_dir = TempDir::new().unwrap();
+        fs::copy("test/region/r.0.0.mca", temp_dir.path().join("r.0.0.mca")).unwrap();
+
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+        let target_chunk_provider = AnvilChunkProvider::new(target_dir.path().to_str().unwrap());
+        let fixture_chunks = chunk_provider.chunk_positions().unwrap().len();
+
+        // Fixture chunks are saved again by doctests, so their timestamps are set back.
+        let saved_at = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        set_timestamps(&chunk_provider, &TimestampPolicy::Fixed(saved_at)).unwrap();
+
+        let chunk_compound_tag = chunk_provider.load_chunk(4, 2).unwrap();
+        chunk_provider
+            .save_chunk(40, -3, chunk_compound_tag)
+            .unwrap();
+
+        let modified_since = SystemTime::now() - Duration::from_secs(3600);
+        let copy_report =
+            export_modified_since(&chunk_provider, modified_since, &target_chunk_provider).unwrap();
+
+        assert_eq!(copy_report.copied_chunks, 1);
+        assert_eq!(
+            target_chunk_provider.chunk_positions().unwrap(),
+            vec![(40, -3)]
+        );
+        assert!(target_chunk_provider.load_chunk(40, -3).is_ok());
+
+        let copy_report =
+            export_modified_since(&chunk_provider, UNIX_EPOCH, &target_chunk_provider).unwrap();
+        assert_eq!(copy_report.copied_chunks, fixture_chunks + 1);
+
+        let copy_report = export_modified_since(
+            &chunk_provider,
+            SystemTime::now() + Duration::from_secs(60),
+            &target_chunk_provider,
+        )
+        .unwrap();
+        assert_eq!(copy_report.copied_chunks, 0);
+    }
+}
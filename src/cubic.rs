@@ -0,0 +1,602 @@
+//! Storage of Cubic Chunks mod, which splits world into 16 blocks high cubes.
+//!
+//! Dimension folder contains `region2d` folder with columns in `x.z.2dr` files of
+//! 32×32 columns and `region3d` folder with cubes in `x.y.z.3dr` files of 16×16×16
+//! cubes. Both are RegionLib files: header of big endian entries with sector offset
+//! in upper 24 bits and sector count in lower 8 bits, followed by 512 bytes sectors.
+//! Every entry starts with big endian length and contains gzip compressed NBT.
+//!
+//! [`CubicChunkProvider`] implements [`CubeProvider`], columns are accessed through
+//! [`ChunkProvider`] methods with x and z coordinates.
+//!
+//! [`CubeProvider`]: crate::provider::CubeProvider
+//! [`ChunkProvider`]: crate::provider::ChunkProvider
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::cubic::CubicChunkProvider;
+//! use anvil_region::provider::{ChunkProvider, CubeProvider};
+//! use nbt::CompoundTag;
+//!
+//! # let world_dir = tempfile::TempDir::new().unwrap();
+//! # let folder = world_dir.path().to_str().unwrap();
+//! let chunk_provider = CubicChunkProvider::new(folder);
+//!
+//! let mut cube_compound_tag = CompoundTag::new();
+//! cube_compound_tag.insert_i32("y", -4);
+//! chunk_provider.save_cube(1, -4, 2, cube_compound_tag).unwrap();
+//!
+//! assert_eq!(chunk_provider.cube_positions().unwrap(), vec![(1, -4, 2)]);
+//! assert_eq!(chunk_provider.chunk_positions().unwrap(), vec![]);
+//! ```
+use crate::provider::{ChunkProvider, CubeProvider};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use nbt::decode::{read_gzip_compound_tag, TagDecodeError};
+use nbt::encode::write_gzip_compound_tag;
+use nbt::CompoundTag;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Folder of column regions inside dimension folder.
+pub const REGION_2D_FOLDER: &str = "region2d";
+/// Folder of cube regions inside dimension folder.
+pub const REGION_3D_FOLDER: &str = "region3d";
+
+/// Size of region file sector in bytes.
+const SECTOR_SIZE: u64 = 512;
+/// Maximum amount of sectors of one entry, stored in the lower 8 bits.
+const MAXIMUM_ENTRY_SECTORS: u64 = 255;
+
+/// Layout of region files of columns or cubes.
+struct RegionLayout {
+    folder: &'static str,
+    extension: &'static str,
+    /// Amount of bits of entry coordinates inside region along every axis.
+    bits: u32,
+    /// Amount of axes, 2 for columns and 3 for cubes.
+    axes: usize,
+}
+
+impl RegionLayout {
+    fn entry_count(&self) -> usize {
+        1 << (self.bits as usize * self.axes)
+    }
+
+    fn header_length(&self) -> u64 {
+        self.entry_count() as u64 * 4
+    }
+
+    fn header_sectors(&self) -> u64 {
+        self.header_length().div_ceil(SECTOR_SIZE)
+    }
+
+    fn region_file_name(&self, position: &[i32]) -> String {
+        let mut file_name = String::new();
+
+        for coordinate in position {
+            file_name.push_str(&(coordinate >> self.bits).to_string());
+            file_name.push('.');
+        }
+
+        file_name.push_str(self.extension);
+        file_name
+    }
+
+    fn entry_index(&self, position: &[i32]) -> usize {
+        let mask = (1 << self.bits) - 1;
+
+        position.iter().fold(0, |index, coordinate| {
+            index << self.bits | (coordinate & mask) as usize
+        })
+    }
+
+    /// Parses region coordinates from file name.
+    fn region_position(&self, file_name: &str) -> Option<Vec<i32>> {
+        let mut parts = file_name.split('.');
+        let mut region_position = Vec::with_capacity(self.axes);
+
+        for _ in 0..self.axes {
+            region_position.push(parts.next()?.parse().ok()?);
+        }
+
+        if parts.next() != Some(self.extension) || parts.next().is_some() {
+            return None;
+        }
+
+        Some(region_position)
+    }
+
+    /// Returns position of entry with index inside region.
+    fn entry_position(&self, region_position: &[i32], index: usize) -> Vec<i32> {
+        let mask = (1 << self.bits) - 1;
+
+        region_position
+            .iter()
+            .enumerate()
+            .map(|(axis, region_coordinate)| {
+                let shift = self.bits as usize * (self.axes - 1 - axis);
+                let coordinate = (index >> shift) as i32 & mask;
+
+                (region_coordinate << self.bits) + coordinate
+            })
+            .collect()
+    }
+}
+
+const COLUMN_LAYOUT: RegionLayout = RegionLayout {
+    folder: REGION_2D_FOLDER,
+    extension: "2dr",
+    bits: 5,
+    axes: 2,
+};
+
+const CUBE_LAYOUT: RegionLayout = RegionLayout {
+    folder: REGION_3D_FOLDER,
+    extension: "3dr",
+    bits: 4,
+    axes: 3,
+};
+
+/// Possible errors while loading or saving columns and cubes.
+#[derive(Debug)]
+pub enum CubicError {
+    /// Region file or its entry at specified coordinates not found.
+    NotFound,
+    /// I/O Error which happened while were reading region file.
+    ReadError { io_error: io::Error },
+    /// I/O Error which happened while were writing region file.
+    WriteError { io_error: io::Error },
+    /// Error while decoding binary data to NBT tag.
+    TagDecodeError { tag_decode_error: TagDecodeError },
+    /// Entry length overlaps sectors which are allocated to it.
+    ///
+    /// Region file are corrupted.
+    LengthExceedsMaximum {
+        /// Entry length.
+        length: u32,
+        /// Entry maximum expected length.
+        maximum_length: u32,
+    },
+    /// Compressed entry doesn't fit into maximum amount of sectors.
+    EntryTooLarge { length: usize },
+}
+
+impl From<TagDecodeError> for CubicError {
+    fn from(tag_decode_error: TagDecodeError) -> Self {
+        CubicError::TagDecodeError { tag_decode_error }
+    }
+}
+
+/// Provider of columns and cubes of Cubic Chunks dimension folder.
+pub struct CubicChunkProvider<'a> {
+    /// Dimension folder which contains `region2d` and `region3d` folders.
+    folder_path: &'a Path,
+}
+
+impl<'a> CubicChunkProvider<'a> {
+    pub fn new(folder: &'a str) -> Self {
+        CubicChunkProvider {
+            folder_path: Path::new(folder),
+        }
+    }
+
+    fn region_path(&self, layout: &RegionLayout, position: &[i32]) -> PathBuf {
+        self.folder_path
+            .join(layout.folder)
+            .join(layout.region_file_name(position))
+    }
+
+    fn load_entry(
+        &self,
+        layout: &RegionLayout,
+        position: &[i32],
+    ) -> Result<CompoundTag, CubicError> {
+        let region_path = self.region_path(layout, position);
+        let index = layout.entry_index(position);
+
+        let data = read_entry(&region_path, layout, index)
+            .map_err(|io_error| CubicError::ReadError { io_error })??;
+
+        Ok(read_gzip_compound_tag(&mut data.as_slice())?)
+    }
+
+    fn save_entry(
+        &self,
+        layout: &RegionLayout,
+        position: &[i32],
+        compound_tag: &CompoundTag,
+    ) -> Result<(), CubicError> {
+        let region_path = self.region_path(layout, position);
+        let index = layout.entry_index(position);
+
+        let mut data = Vec::new();
+        write_gzip_compound_tag(&mut data, compound_tag)
+            .map_err(|io_error| CubicError::WriteError { io_error })?;
+
+        if data.len() as u64 + 4 > MAXIMUM_ENTRY_SECTORS * SECTOR_SIZE {
+            return Err(CubicError::EntryTooLarge { length: data.len() });
+        }
+
+        write_entry(&region_path, layout, index, &data)
+            .map_err(|io_error| CubicError::WriteError { io_error })
+    }
+
+    fn delete_entry(&self, layout: &RegionLayout, position: &[i32]) -> Result<bool, CubicError> {
+        let region_path = self.region_path(layout, position);
+
+        if !region_path.exists() {
+            return Ok(false);
+        }
+
+        let write = || {
+            let mut file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(&region_path)?;
+            let header = read_header(&mut file, layout)?;
+            let index = layout.entry_index(position);
+
+            if header[index] == 0 {
+                return Ok(false);
+            }
+
+            file.seek(SeekFrom::Start(index as u64 * 4))?;
+            file.write_u32::<BigEndian>(0)?;
+
+            Ok(true)
+        };
+
+        write().map_err(|io_error| CubicError::WriteError { io_error })
+    }
+
+    fn entry_positions(&self, layout: &RegionLayout) -> Result<Vec<Vec<i32>>, io::Error> {
+        let mut positions = Vec::new();
+        let folder_path = self.folder_path.join(layout.folder);
+
+        if !folder_path.exists() {
+            return Ok(positions);
+        }
+
+        for entry in fs::read_dir(folder_path)? {
+            let path = entry?.path();
+
+            let region_position = match path
+                .file_name()
+                .and_then(|file_name| file_name.to_str())
+                .and_then(|file_name| layout.region_position(file_name))
+            {
+                Some(region_position) => region_position,
+                None => continue,
+            };
+
+            let header = read_header(&mut File::open(&path)?, layout)?;
+
+            for (index, location) in header.iter().enumerate() {
+                if *location != 0 {
+                    positions.push(layout.entry_position(&region_position, index));
+                }
+            }
+        }
+
+        positions.sort_unstable();
+
+        Ok(positions)
+    }
+}
+
+impl ChunkProvider for CubicChunkProvider<'_> {
+    type LoadError = CubicError;
+    type SaveError = CubicError;
+
+    fn load_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<CompoundTag, CubicError> {
+        self.load_entry(&COLUMN_LAYOUT, &[chunk_x, chunk_z])
+    }
+
+    fn save_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        chunk_compound_tag: CompoundTag,
+    ) -> Result<(), CubicError> {
+        self.save_entry(&COLUMN_LAYOUT, &[chunk_x, chunk_z], &chunk_compound_tag)
+    }
+
+    fn delete_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<bool, CubicError> {
+        self.delete_entry(&COLUMN_LAYOUT, &[chunk_x, chunk_z])
+    }
+
+    fn chunk_positions(&self) -> Result<Vec<(i32, i32)>, io::Error> {
+        let positions = self.entry_positions(&COLUMN_LAYOUT)?;
+
+        Ok(positions
+            .iter()
+            .map(|position| (position[0], position[1]))
+            .collect())
+    }
+}
+
+impl CubeProvider for CubicChunkProvider<'_> {
+    fn load_cube(&self, cube_x: i32, cube_y: i32, cube_z: i32) -> Result<CompoundTag, CubicError> {
+        self.load_entry(&CUBE_LAYOUT, &[cube_x, cube_y, cube_z])
+    }
+
+    fn save_cube(
+        &self,
+        cube_x: i32,
+        cube_y: i32,
+        cube_z: i32,
+        cube_compound_tag: CompoundTag,
+    ) -> Result<(), CubicError> {
+        self.save_entry(&CUBE_LAYOUT, &[cube_x, cube_y, cube_z], &cube_compound_tag)
+    }
+
+    fn delete_cube(&self, cube_x: i32, cube_y: i32, cube_z: i32) -> Result<bool, CubicError> {
+        self.delete_entry(&CUBE_LAYOUT, &[cube_x, cube_y, cube_z])
+    }
+
+    fn cube_positions(&self) -> Result<Vec<(i32, i32, i32)>, io::Error> {
+        let positions = self.entry_positions(&CUBE_LAYOUT)?;
+
+        Ok(positions
+            .iter()
+            .map(|position| (position[0], position[1], position[2]))
+            .collect())
+    }
+}
+
+/// Reads sector locations of region entries, header shorter than expected is
+/// padded with empty locations.
+fn read_header(file: &mut File, layout: &RegionLayout) -> Result<Vec<u32>, io::Error> {
+    let mut header = vec![0; layout.entry_count()];
+    let mut buffer = Vec::with_capacity(layout.header_length() as usize);
+
+    file.seek(SeekFrom::Start(0))?;
+    file.take(layout.header_length()).read_to_end(&mut buffer)?;
+
+    for (location, mut bytes) in header.iter_mut().zip(buffer.chunks_exact(4)) {
+        *location = bytes.read_u32::<BigEndian>()?;
+    }
+
+    Ok(header)
+}
+
+/// Reads entry data, outer error is I/O error and inner one is missing or corrupted entry.
+fn read_entry(
+    region_path: &Path,
+    layout: &RegionLayout,
+    index: usize,
+) -> Result<Result<Vec<u8>, CubicError>, io::Error> {
+    let mut file = match File::open(region_path) {
+        Ok(file) => file,
+        Err(io_error) if io_error.kind() == io::ErrorKind::NotFound => {
+            return Ok(Err(CubicError::NotFound))
+        }
+        Err(io_error) => return Err(io_error),
+    };
+
+    let location = read_header(&mut file, layout)?[index];
+
+    if location == 0 {
+        return Ok(Err(CubicError::NotFound));
+    }
+
+    let sector_offset = (location >> 8) as u64;
+    let sector_count = (location & 0xFF) as u64;
+
+    file.seek(SeekFrom::Start(sector_offset * SECTOR_SIZE))?;
+    let length = file.read_u32::<BigEndian>()?;
+    let maximum_length = (sector_count * SECTOR_SIZE).saturating_sub(4) as u32;
+
+    if length > maximum_length {
+        return Ok(Err(CubicError::LengthExceedsMaximum {
+            length,
+            maximum_length,
+        }));
+    }
+
+    let mut data = vec![0; length as usize];
+    file.read_exact(&mut data)?;
+
+    Ok(Ok(data))
+}
+
+/// Writes entry data into its current sectors when it fits, otherwise into the
+/// first free sectors which are large enough.
+fn write_entry(
+    region_path: &Path,
+    layout: &RegionLayout,
+    index: usize,
+    data: &[u8],
+) -> Result<(), io::Error> {
+    if let Some(parent_path) = region_path.parent() {
+        fs::create_dir_all(parent_path)?;
+    }
+
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(region_path)?;
+
+    let header = read_header(&mut file, layout)?;
+    let required_sectors = (data.len() as u64 + 4).div_ceil(SECTOR_SIZE);
+
+    let mut used_sectors = vec![true; layout.header_sectors() as usize];
+
+    for (entry_index, location) in header.iter().enumerate() {
+        if entry_index == index || *location == 0 {
+            continue;
+        }
+
+        let sector_offset = (location >> 8) as usize;
+        let sector_count = (location & 0xFF) as usize;
+
+        if used_sectors.len() < sector_offset + sector_count {
+            used_sectors.resize(sector_offset + sector_count, false);
+        }
+
+        for used_sector in &mut used_sectors[sector_offset..sector_offset + sector_count] {
+            *used_sector = true;
+        }
+    }
+
+    let mut sector_offset = used_sectors.len() as u64;
+    let mut free_sectors = 0;
+
+    for (sector, used) in used_sectors.iter().enumerate() {
+        if *used {
+            free_sectors = 0;
+            continue;
+        }
+
+        free_sectors += 1;
+
+        if free_sectors == required_sectors {
+            sector_offset = sector as u64 + 1 - required_sectors;
+            break;
+        }
+    }
+
+    let mut buffer = Vec::with_capacity((required_sectors * SECTOR_SIZE) as usize);
+    buffer.write_u32::<BigEndian>(data.len() as u32)?;
+    buffer.extend_from_slice(data);
+    buffer.resize((required_sectors * SECTOR_SIZE) as usize, 0);
+
+    // Header is padded to the whole sectors before the first entry is written.
+    if file.metadata()?.len() < layout.header_sectors() * SECTOR_SIZE {
+        file.set_len(layout.header_sectors() * SECTOR_SIZE)?;
+    }
+
+    file.seek(SeekFrom::Start(sector_offset * SECTOR_SIZE))?;
+    file.write_all(&buffer)?;
+
+    file.seek(SeekFrom::Start(index as u64 * 4))?;
+    file.write_u32::<BigEndian>((sector_offset as u32) << 8 | required_sectors as u32)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cubic::{CubicChunkProvider, CubicError, COLUMN_LAYOUT, CUBE_LAYOUT};
+    use crate::provider::{ChunkProvider, CubeProvider};
+    use nbt::CompoundTag;
+    use tempfile::TempDir;
+
+    fn cube(y: i32, payload_length: usize) -> CompoundTag {
+        let mut cube_compound_tag = CompoundTag::new();
+        cube_compound_tag.insert_i32("y", y);
+        // Random bytes are not compressed, so entry takes several sectors.
+        let mut state = 0x2545_f491_u32;
+        let payload = (0..payload_length)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                (state >> 24) as i8
+            })
+            .collect();
+        cube_compound_tag.insert_i8_vec("Payload", payload);
+
+        cube_compound_tag
+    }
+
+    #[test]
+    fn test_layout() {
+        assert_eq!(CUBE_LAYOUT.region_file_name(&[-1, 16, 31]), "-1.1.1.3dr");
+        assert_eq!(CUBE_LAYOUT.entry_index(&[1, 2, 3]), 0x123);
+        assert_eq!(
+            CUBE_LAYOUT.entry_position(&[-1, 1, 1], 0xF0F),
+            vec![-1, 16, 31]
+        );
+        assert_eq!(COLUMN_LAYOUT.region_file_name(&[-33, 5]), "-2.0.2dr");
+        assert_eq!(COLUMN_LAYOUT.entry_index(&[-33, 5]), 31 << 5 | 5);
+        assert_eq!(COLUMN_LAYOUT.region_position("-2.0.2dr"), Some(vec![-2, 0]));
+        assert_eq!(COLUMN_LAYOUT.region_position("-2.0.0.2dr"), None);
+        assert_eq!(COLUMN_LAYOUT.header_sectors(), 8);
+        assert_eq!(CUBE_LAYOUT.header_sectors(), 32);
+    }
+
+    #[test]
+    fn test_save_load_delete() {
+        let world_dir = TempDir::new().unwrap();
+        let chunk_provider = CubicChunkProvider::new(world_dir.path().to_str().unwrap());
+
+        chunk_provider.save_cube(3, -20, 7, cube(-20, 10)).unwrap();
+        chunk_provider.save_cube(3, 5, 7, cube(5, 10)).unwrap();
+
+        let mut column = CompoundTag::new();
+        column.insert_i32("x", 3);
+        chunk_provider.save_chunk(3, 7, column).unwrap();
+
+        let cube_compound_tag = chunk_provider.load_cube(3, -20, 7).unwrap();
+        assert_eq!(cube_compound_tag.get_i32("y").unwrap(), -20);
+        assert_eq!(
+            chunk_provider
+                .load_chunk(3, 7)
+                .unwrap()
+                .get_i32("x")
+                .unwrap(),
+            3
+        );
+
+        assert_eq!(
+            chunk_provider.cube_positions().unwrap(),
+            vec![(3, -20, 7), (3, 5, 7)]
+        );
+        assert_eq!(chunk_provider.chunk_positions().unwrap(), vec![(3, 7)]);
+
+        assert!(chunk_provider.delete_cube(3, -20, 7).unwrap());
+        assert!(!chunk_provider.delete_cube(3, -20, 7).unwrap());
+        assert!(matches!(
+            chunk_provider.load_cube(3, -20, 7),
+            Err(CubicError::NotFound)
+        ));
+        assert!(matches!(
+            chunk_provider.load_cube(100, 0, 0),
+            Err(CubicError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn test_sector_reuse() {
+        let world_dir = TempDir::new().unwrap();
+        let region_path = world_dir.path().join("region3d").join("0.0.0.3dr");
+        let chunk_provider = CubicChunkProvider::new(world_dir.path().to_str().unwrap());
+
+        chunk_provider.save_cube(0, 0, 0, cube(0, 2000)).unwrap();
+        chunk_provider.save_cube(0, 1, 0, cube(1, 10)).unwrap();
+        let length = std::fs::metadata(&region_path).unwrap().len();
+
+        // Smaller cube is written into sectors it already has.
+        chunk_provider.save_cube(0, 0, 0, cube(0, 10)).unwrap();
+        assert_eq!(std::fs::metadata(&region_path).unwrap().len(), length);
+
+        // Freed sectors are reused by the next cube.
+        chunk_provider.save_cube(0, 2, 0, cube(2, 10)).unwrap();
+        assert_eq!(std::fs::metadata(&region_path).unwrap().len(), length);
+
+        // Grown cube is moved after the last entry.
+        chunk_provider.save_cube(0, 1, 0, cube(1, 2000)).unwrap();
+        assert!(std::fs::metadata(&region_path).unwrap().len() > length);
+
+        for y in 0..3 {
+            let cube_compound_tag = chunk_provider.load_cube(0, y, 0).unwrap();
+            assert_eq!(cube_compound_tag.get_i32("y").unwrap(), y);
+        }
+    }
+
+    #[test]
+    fn test_entry_too_large() {
+        let world_dir = TempDir::new().unwrap();
+        let chunk_provider = CubicChunkProvider::new(world_dir.path().to_str().unwrap());
+
+        assert!(matches!(
+            chunk_provider.save_cube(0, 0, 0, cube(0, 200_000)),
+            Err(CubicError::EntryTooLarge { .. })
+        ));
+    }
+}
@@ -0,0 +1,68 @@
+//! World data files stored in `data` folder of dimension.
+//!
+//! Data files are gzip compressed NBT with `DataVersion` and `data` compound
+//! containing actual values.
+use crate::snapshot::break_hard_link;
+use nbt::decode::{read_gzip_compound_tag, TagDecodeError};
+use nbt::encode::write_gzip_compound_tag;
+use nbt::CompoundTag;
+use std::fs::File;
+use std::path::Path;
+use std::{fs, io};
+
+pub mod forced_chunks;
+pub mod id_counts;
+pub mod map;
+pub mod raids;
+pub mod scoreboard;
+
+/// Possible errors while reading or writing data files.
+#[derive(Debug)]
+pub enum DataFileError {
+    /// I/O Error which happened while were reading file.
+    ReadError { io_error: io::Error },
+    /// I/O Error which happened while were writing file.
+    WriteError { io_error: io::Error },
+    /// Error while decoding binary data to NBT tag.
+    TagDecodeError { tag_decode_error: TagDecodeError },
+    /// File tag is missing or has unexpected type.
+    InvalidTag {
+        /// Tag name.
+        name: String,
+    },
+}
+
+impl From<io::Error> for DataFileError {
+    fn from(io_error: io::Error) -> Self {
+        DataFileError::ReadError { io_error }
+    }
+}
+
+impl From<TagDecodeError> for DataFileError {
+    fn from(tag_decode_error: TagDecodeError) -> Self {
+        DataFileError::TagDecodeError { tag_decode_error }
+    }
+}
+
+/// Reads gzip compressed compound tag from file.
+pub fn read_data_file(path: &Path) -> Result<CompoundTag, DataFileError> {
+    let mut file = File::open(path)?;
+
+    Ok(read_gzip_compound_tag(&mut file)?)
+}
+
+/// Writes compound tag to file with gzip compression, creating parent folders.
+pub fn write_data_file(path: &Path, compound_tag: &CompoundTag) -> Result<(), DataFileError> {
+    let write = || {
+        if let Some(parent_path) = path.parent() {
+            fs::create_dir_all(parent_path)?;
+        }
+
+        break_hard_link(path)?;
+
+        let mut file = File::create(path)?;
+        write_gzip_compound_tag(&mut file, compound_tag)
+    };
+
+    write().map_err(|io_error| DataFileError::WriteError { io_error })
+}
@@ -0,0 +1,197 @@
+//! Chunks kept loaded by `/forceload` command.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::data::forced_chunks::ForcedChunks;
+//! use tempfile::TempDir;
+//!
+//! let world_dir = TempDir::new().unwrap();
+//! let path = world_dir.path().join("data/chunks.dat");
+//!
+//! let mut forced_chunks = ForcedChunks::load(&path).unwrap();
+//! forced_chunks.force(4, -2);
+//! forced_chunks.save(&path).unwrap();
+//!
+//! assert_eq!(ForcedChunks::load(&path).unwrap().positions(), vec![(4, -2)]);
+//! ```
+use crate::data::{read_data_file, write_data_file, DataFileError};
+use crate::packed::{pack_chunk_position, unpack_chunk_position};
+use nbt::CompoundTag;
+use std::path::Path;
+
+/// Path of forced chunks file relative to dimension folder.
+pub const FORCED_CHUNKS_FILE: &str = "data/chunks.dat";
+
+/// Contents of `chunks.dat`.
+#[derive(Debug, Clone)]
+pub struct ForcedChunks {
+    compound_tag: CompoundTag,
+}
+
+impl ForcedChunks {
+    /// Creates file contents without forced chunks.
+    pub fn new() -> Self {
+        let mut data = CompoundTag::new();
+        data.insert_i64_vec("Forced", Vec::new());
+
+        let mut compound_tag = CompoundTag::new();
+        compound_tag.insert_compound_tag("data", data);
+
+        ForcedChunks { compound_tag }
+    }
+
+    /// Reads forced chunks, file which doesn't exist is treated as empty.
+    pub fn load(path: &Path) -> Result<Self, DataFileError> {
+        if !path.exists() {
+            return Ok(ForcedChunks::new());
+        }
+
+        let mut compound_tag = read_data_file(path)?;
+
+        match compound_tag.get_mut::<&mut CompoundTag>("data") {
+            Ok(data) if !data.contains_key("Forced") => data.insert_i64_vec("Forced", Vec::new()),
+            Ok(data) if data.get_i64_vec("Forced").is_err() => {
+                return Err(DataFileError::InvalidTag {
+                    name: "Forced".to_owned(),
+                })
+            }
+            Ok(_) => {}
+            Err(_) => {
+                return Err(DataFileError::InvalidTag {
+                    name: "data".to_owned(),
+                })
+            }
+        }
+
+        Ok(ForcedChunks { compound_tag })
+    }
+
+    /// Writes forced chunks with other file tags preserved.
+    pub fn save(&self, path: &Path) -> Result<(), DataFileError> {
+        write_data_file(path, &self.compound_tag)
+    }
+
+    /// Returns whole file compound tag.
+    pub fn compound_tag(&self) -> &CompoundTag {
+        &self.compound_tag
+    }
+
+    /// Returns positions of forced chunks.
+    pub fn positions(&self) -> Vec<(i32, i32)> {
+        self.forced()
+            .iter()
+            .map(|chunk_position| unpack_chunk_position(*chunk_position))
+            .collect()
+    }
+
+    /// Returns true if chunk is forced.
+    pub fn contains(&self, chunk_x: i32, chunk_z: i32) -> bool {
+        self.forced()
+            .contains(&pack_chunk_position(chunk_x, chunk_z))
+    }
+
+    /// Forces chunk, returns false if it is already forced.
+    pub fn force(&mut self, chunk_x: i32, chunk_z: i32) -> bool {
+        if self.contains(chunk_x, chunk_z) {
+            return false;
+        }
+
+        self.forced_mut()
+            .push(pack_chunk_position(chunk_x, chunk_z));
+
+        true
+    }
+
+    /// Stops forcing chunk, returns false if it isn't forced.
+    pub fn unforce(&mut self, chunk_x: i32, chunk_z: i32) -> bool {
+        let chunk_position = pack_chunk_position(chunk_x, chunk_z);
+        let forced = self.forced_mut();
+        let length = forced.len();
+
+        forced.retain(|forced_chunk_position| *forced_chunk_position != chunk_position);
+
+        forced.len() != length
+    }
+
+    fn forced(&self) -> &Vec<i64> {
+        // Presence of tags is checked on creation.
+        self.compound_tag
+            .get_compound_tag("data")
+            .and_then(|data| data.get_i64_vec("Forced"))
+            .unwrap()
+    }
+
+    fn forced_mut(&mut self) -> &mut Vec<i64> {
+        self.compound_tag
+            .get_mut::<&mut CompoundTag>("data")
+            .and_then(|data| data.get_mut::<&mut Vec<i64>>("Forced"))
+            .unwrap()
+    }
+}
+
+impl Default for ForcedChunks {
+    fn default() -> Self {
+        ForcedChunks::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::forced_chunks::ForcedChunks;
+    use crate::data::{write_data_file, DataFileError};
+    use nbt::CompoundTag;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_force_and_unforce() {
+        let mut forced_chunks = ForcedChunks::new();
+
+        assert!(forced_chunks.force(1, 2));
+        assert!(!forced_chunks.force(1, 2));
+        assert!(forced_chunks.force(-5, 3));
+        assert!(forced_chunks.unforce(1, 2));
+        assert!(!forced_chunks.unforce(1, 2));
+
+        assert_eq!(forced_chunks.positions(), vec![(-5, 3)]);
+    }
+
+    #[test]
+    fn test_load_preserves_other_tags() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("chunks.dat");
+
+        let mut data = CompoundTag::new();
+        data.insert_i64_vec("Forced", vec![1 << 32 | 7]);
+
+        let mut compound_tag = CompoundTag::new();
+        compound_tag.insert_i32("DataVersion", 2586);
+        compound_tag.insert_compound_tag("data", data);
+        write_data_file(&path, &compound_tag).unwrap();
+
+        let mut forced_chunks = ForcedChunks::load(&path).unwrap();
+        assert_eq!(forced_chunks.positions(), vec![(7, 1)]);
+
+        forced_chunks.force(0, 0);
+        forced_chunks.save(&path).unwrap();
+
+        let forced_chunks = ForcedChunks::load(&path).unwrap();
+        assert_eq!(forced_chunks.positions(), vec![(7, 1), (0, 0)]);
+        assert_eq!(
+            forced_chunks.compound_tag().get_i32("DataVersion").unwrap(),
+            2586
+        );
+    }
+
+    #[test]
+    fn test_load_invalid_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("chunks.dat");
+        write_data_file(&path, &CompoundTag::new()).unwrap();
+
+        match ForcedChunks::load(&path) {
+            Err(DataFileError::InvalidTag { name }) => assert_eq!(name, "data"),
+            result => panic!("Expected `InvalidTag` but got `{:?}`", result),
+        }
+    }
+}
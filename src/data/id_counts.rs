@@ -0,0 +1,143 @@
+//! Last used ids of numbered data files like maps.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::data::id_counts::IdCounts;
+//! use tempfile::TempDir;
+//!
+//! let world_dir = TempDir::new().unwrap();
+//! let path = world_dir.path().join("data/idcounts.dat");
+//!
+//! let mut id_counts = IdCounts::load(&path).unwrap();
+//! assert_eq!(id_counts.allocate_map_id(), 0);
+//! assert_eq!(id_counts.allocate_map_id(), 1);
+//! id_counts.save(&path).unwrap();
+//!
+//! assert_eq!(IdCounts::load(&path).unwrap().map_id(), Some(1));
+//! ```
+use crate::data::{read_data_file, write_data_file, DataFileError};
+use crate::tag::set_tag;
+use nbt::{CompoundTag, Tag};
+use std::path::Path;
+
+/// Path of id counts file relative to overworld folder.
+pub const ID_COUNTS_FILE: &str = "data/idcounts.dat";
+/// Name of map id counter.
+const MAP_COUNTER: &str = "map";
+
+/// Contents of `idcounts.dat`.
+#[derive(Debug, Clone)]
+pub struct IdCounts {
+    compound_tag: CompoundTag,
+}
+
+impl IdCounts {
+    /// Creates file contents without used ids.
+    pub fn new() -> Self {
+        let mut compound_tag = CompoundTag::new();
+        compound_tag.insert_compound_tag("data", CompoundTag::new());
+
+        IdCounts { compound_tag }
+    }
+
+    /// Reads id counts, file which doesn't exist is treated as empty.
+    pub fn load(path: &Path) -> Result<Self, DataFileError> {
+        if !path.exists() {
+            return Ok(IdCounts::new());
+        }
+
+        let compound_tag = read_data_file(path)?;
+
+        if compound_tag.get_compound_tag("data").is_err() {
+            return Err(DataFileError::InvalidTag {
+                name: "data".to_owned(),
+            });
+        }
+
+        Ok(IdCounts { compound_tag })
+    }
+
+    /// Writes id counts with other file tags preserved.
+    pub fn save(&self, path: &Path) -> Result<(), DataFileError> {
+        write_data_file(path, &self.compound_tag)
+    }
+
+    /// Returns whole file compound tag.
+    pub fn compound_tag(&self) -> &CompoundTag {
+        &self.compound_tag
+    }
+
+    /// Returns last used id of counter, none if no id was used.
+    pub fn last_id(&self, name: &str) -> Option<i32> {
+        self.data().get_i32(name).ok()
+    }
+
+    /// Sets last used id of counter.
+    pub fn set_last_id(&mut self, name: &str, id: i32) {
+        set_tag(self.data_mut(), name, Tag::Int(id));
+    }
+
+    /// Increments counter and returns new id, first id is 0.
+    pub fn allocate_id(&mut self, name: &str) -> i32 {
+        let id = self.last_id(name).map_or(0, |last_id| last_id + 1);
+        self.set_last_id(name, id);
+
+        id
+    }
+
+    /// Returns last used map id.
+    pub fn map_id(&self) -> Option<i32> {
+        self.last_id(MAP_COUNTER)
+    }
+
+    /// Returns id for new map file.
+    pub fn allocate_map_id(&mut self) -> i32 {
+        self.allocate_id(MAP_COUNTER)
+    }
+
+    fn data(&self) -> &CompoundTag {
+        // Presence of tag is checked on creation.
+        self.compound_tag.get_compound_tag("data").unwrap()
+    }
+
+    fn data_mut(&mut self) -> &mut CompoundTag {
+        self.compound_tag
+            .get_mut::<&mut CompoundTag>("data")
+            .unwrap()
+    }
+}
+
+impl Default for IdCounts {
+    fn default() -> Self {
+        IdCounts::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::id_counts::IdCounts;
+    use crate::data::write_data_file;
+    use nbt::CompoundTag;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_allocate_id_continues_counter() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("idcounts.dat");
+
+        let mut data = CompoundTag::new();
+        data.insert_i32("map", 41);
+
+        let mut compound_tag = CompoundTag::new();
+        compound_tag.insert_compound_tag("data", data);
+        write_data_file(&path, &compound_tag).unwrap();
+
+        let mut id_counts = IdCounts::load(&path).unwrap();
+
+        assert_eq!(id_counts.allocate_map_id(), 42);
+        assert_eq!(id_counts.allocate_id("raid"), 0);
+        assert_eq!(id_counts.map_id(), Some(42));
+        assert_eq!(id_counts.last_id("raid"), Some(0));
+    }
+}
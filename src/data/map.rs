@@ -0,0 +1,359 @@
+//! In-game maps stored as `map_<id>.dat` files.
+//!
+//! Map keeps 128 by 128 color ids of area around its center, scaled by `1 << scale`
+//! blocks per pixel, and banners marked on it. New map ids are allocated from
+//! `idcounts.dat`.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::data::map::{create_map, map_path, MapData};
+//! use tempfile::TempDir;
+//!
+//! let world_dir = TempDir::new().unwrap();
+//!
+//! let mut map_data = MapData::new(0, (64, 64), "minecraft:overworld");
+//! map_data.set_color(0, 0, 34);
+//! map_data.set_locked(true);
+//!
+//! let id = create_map(world_dir.path(), &map_data).unwrap();
+//! let map_data = MapData::load(&map_path(world_dir.path(), id)).unwrap();
+//!
+//! assert_eq!(map_data.color(0, 0), 34);
+//! assert!(map_data.is_locked());
+//! ```
+use crate::data::id_counts::{IdCounts, ID_COUNTS_FILE};
+use crate::data::{read_data_file, write_data_file, DataFileError};
+use crate::tag::{compound_tags, get_tag, set_tag};
+use nbt::{CompoundTag, Tag};
+use std::path::{Path, PathBuf};
+
+/// Amount of pixels along each side of map.
+pub const MAP_SIZE: usize = 128;
+/// Amount of color ids stored in map.
+pub const MAP_COLORS: usize = MAP_SIZE * MAP_SIZE;
+
+/// Returns path of map file inside of overworld folder.
+pub fn map_path(world_folder_path: &Path, id: i32) -> PathBuf {
+    world_folder_path.join(format!("data/map_{}.dat", id))
+}
+
+/// Allocates new map id in `idcounts.dat` and writes map with it.
+pub fn create_map(world_folder_path: &Path, map_data: &MapData) -> Result<i32, DataFileError> {
+    let id_counts_path = world_folder_path.join(ID_COUNTS_FILE);
+
+    let mut id_counts = IdCounts::load(&id_counts_path)?;
+    let id = id_counts.allocate_map_id();
+
+    map_data.save(&map_path(world_folder_path, id))?;
+    id_counts.save(&id_counts_path)?;
+
+    Ok(id)
+}
+
+/// Banner marked on map.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MapBanner {
+    /// Block position of banner.
+    pub position: (i32, i32, i32),
+    /// Banner dye color like `white`.
+    pub color: String,
+    /// Custom name as JSON text component.
+    pub name: Option<String>,
+}
+
+impl MapBanner {
+    fn from_compound_tag(compound_tag: &CompoundTag) -> Option<Self> {
+        let position = compound_tag.get_compound_tag("Pos").ok()?;
+
+        Some(MapBanner {
+            position: (
+                position.get_i32("X").ok()?,
+                position.get_i32("Y").ok()?,
+                position.get_i32("Z").ok()?,
+            ),
+            color: compound_tag.get_str("Color").unwrap_or("white").to_owned(),
+            name: compound_tag.get_str("Name").ok().map(str::to_owned),
+        })
+    }
+
+    fn to_compound_tag(&self) -> CompoundTag {
+        let (x, y, z) = self.position;
+
+        let mut position = CompoundTag::new();
+        position.insert_i32("X", x);
+        position.insert_i32("Y", y);
+        position.insert_i32("Z", z);
+
+        let mut compound_tag = CompoundTag::new();
+        compound_tag.insert_str("Color", &self.color);
+
+        if let Some(name) = &self.name {
+            compound_tag.insert_str("Name", name);
+        }
+
+        compound_tag.insert_compound_tag("Pos", position);
+
+        compound_tag
+    }
+}
+
+/// Contents of `map_<id>.dat`.
+#[derive(Debug, Clone)]
+pub struct MapData {
+    compound_tag: CompoundTag,
+}
+
+impl MapData {
+    /// Creates unexplored map of dimension centered at block X and Z.
+    pub fn new(scale: i8, (x_center, z_center): (i32, i32), dimension: &str) -> Self {
+        let mut data = CompoundTag::new();
+        data.insert_i8("scale", scale);
+        data.insert_str("dimension", dimension);
+        data.insert_bool("trackingPosition", true);
+        data.insert_bool("unlimitedTracking", false);
+        data.insert_bool("locked", false);
+        data.insert_i32("xCenter", x_center);
+        data.insert_i32("zCenter", z_center);
+        data.insert("banners", Tag::List(Vec::new()));
+        data.insert("frames", Tag::List(Vec::new()));
+        data.insert_i8_vec("colors", vec![0; MAP_COLORS]);
+
+        let mut compound_tag = CompoundTag::new();
+        compound_tag.insert_compound_tag("data", data);
+
+        MapData { compound_tag }
+    }
+
+    /// Reads map file.
+    pub fn load(path: &Path) -> Result<Self, DataFileError> {
+        let compound_tag = read_data_file(path)?;
+
+        let data = match compound_tag.get_compound_tag("data") {
+            Ok(data) => data,
+            Err(_) => {
+                return Err(DataFileError::InvalidTag {
+                    name: "data".to_owned(),
+                })
+            }
+        };
+
+        match data.get_i8_vec("colors") {
+            Ok(colors) if colors.len() == MAP_COLORS => {}
+            _ => {
+                return Err(DataFileError::InvalidTag {
+                    name: "colors".to_owned(),
+                })
+            }
+        }
+
+        Ok(MapData { compound_tag })
+    }
+
+    /// Writes map with other file tags preserved.
+    pub fn save(&self, path: &Path) -> Result<(), DataFileError> {
+        write_data_file(path, &self.compound_tag)
+    }
+
+    /// Returns whole file compound tag.
+    pub fn compound_tag(&self) -> &CompoundTag {
+        &self.compound_tag
+    }
+
+    /// Returns scale from 0 to 4, each step doubles blocks per pixel.
+    pub fn scale(&self) -> i8 {
+        self.data().get_i8("scale").unwrap_or(0)
+    }
+
+    pub fn set_scale(&mut self, scale: i8) {
+        set_tag(self.data_mut(), "scale", Tag::Byte(scale));
+    }
+
+    /// Returns block X and Z of map center.
+    pub fn center(&self) -> (i32, i32) {
+        let data = self.data();
+
+        (
+            data.get_i32("xCenter").unwrap_or(0),
+            data.get_i32("zCenter").unwrap_or(0),
+        )
+    }
+
+    pub fn set_center(&mut self, x_center: i32, z_center: i32) {
+        let data = self.data_mut();
+
+        set_tag(data, "xCenter", Tag::Int(x_center));
+        set_tag(data, "zCenter", Tag::Int(z_center));
+    }
+
+    /// Returns name of dimension like `minecraft:the_nether`.
+    ///
+    /// Before 1.16 dimension is stored as number, which is converted to name.
+    pub fn dimension(&self) -> Option<String> {
+        match get_tag(self.data(), "dimension")? {
+            Tag::String(dimension) => Some(dimension.clone()),
+            Tag::Byte(-1) | Tag::Int(-1) => Some("minecraft:the_nether".to_owned()),
+            Tag::Byte(0) | Tag::Int(0) => Some("minecraft:overworld".to_owned()),
+            Tag::Byte(1) | Tag::Int(1) => Some("minecraft:the_end".to_owned()),
+            _ => None,
+        }
+    }
+
+    /// Returns true if map is locked in cartography table and no longer updates.
+    pub fn is_locked(&self) -> bool {
+        self.data().get_bool("locked").unwrap_or(false)
+    }
+
+    pub fn set_locked(&mut self, locked: bool) {
+        set_tag(self.data_mut(), "locked", Tag::Byte(locked as i8));
+    }
+
+    /// Returns color ids of pixels in rows along X.
+    pub fn colors(&self) -> &[i8] {
+        self.colors_vec()
+    }
+
+    /// Replaces color ids of all pixels.
+    pub fn set_colors(&mut self, colors: &[u8; MAP_COLORS]) {
+        let colors = colors.iter().map(|color| *color as i8).collect();
+
+        set_tag(self.data_mut(), "colors", Tag::ByteArray(colors));
+    }
+
+    /// Returns color id of pixel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if pixel is outside of map.
+    pub fn color(&self, x: usize, z: usize) -> u8 {
+        self.colors_vec()[Self::color_index(x, z)] as u8
+    }
+
+    /// Sets color id of pixel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if pixel is outside of map.
+    pub fn set_color(&mut self, x: usize, z: usize, color: u8) {
+        let index = Self::color_index(x, z);
+
+        self.colors_vec_mut()[index] = color as i8;
+    }
+
+    /// Returns banners marked on map, malformed entries are skipped.
+    pub fn banners(&self) -> Vec<MapBanner> {
+        compound_tags(self.data(), "banners")
+            .filter_map(MapBanner::from_compound_tag)
+            .collect()
+    }
+
+    /// Replaces banners marked on map.
+    pub fn set_banners(&mut self, banners: &[MapBanner]) {
+        let banners = banners
+            .iter()
+            .map(|banner| Tag::Compound(banner.to_compound_tag()))
+            .collect();
+
+        set_tag(self.data_mut(), "banners", Tag::List(banners));
+    }
+
+    fn color_index(x: usize, z: usize) -> usize {
+        assert!(MAP_SIZE > x, "Map x coordinate out of bounds");
+        assert!(MAP_SIZE > z, "Map z coordinate out of bounds");
+
+        x + z * MAP_SIZE
+    }
+
+    fn data(&self) -> &CompoundTag {
+        // Presence of tags is checked on creation.
+        self.compound_tag.get_compound_tag("data").unwrap()
+    }
+
+    fn data_mut(&mut self) -> &mut CompoundTag {
+        self.compound_tag
+            .get_mut::<&mut CompoundTag>("data")
+            .unwrap()
+    }
+
+    fn colors_vec(&self) -> &Vec<i8> {
+        self.data().get_i8_vec("colors").unwrap()
+    }
+
+    fn colors_vec_mut(&mut self) -> &mut Vec<i8> {
+        self.data_mut().get_mut::<&mut Vec<i8>>("colors").unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::id_counts::{IdCounts, ID_COUNTS_FILE};
+    use crate::data::map::{create_map, map_path, MapBanner, MapData, MAP_SIZE};
+    use crate::data::{write_data_file, DataFileError};
+    use nbt::CompoundTag;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_map_data_accessors() {
+        let mut map_data = MapData::new(2, (-64, 192), "minecraft:the_nether");
+
+        assert_eq!(map_data.scale(), 2);
+        assert_eq!(map_data.center(), (-64, 192));
+        assert_eq!(
+            map_data.dimension(),
+            Some("minecraft:the_nether".to_owned())
+        );
+        assert!(!map_data.is_locked());
+        assert!(map_data.colors().iter().all(|color| *color == 0));
+
+        map_data.set_color(MAP_SIZE - 1, 1, 200);
+        map_data.set_center(0, 0);
+        map_data.set_banners(&[MapBanner {
+            position: (10, 64, -3),
+            color: "red".to_owned(),
+            name: Some(r#"{"text":"Base"}"#.to_owned()),
+        }]);
+
+        assert_eq!(map_data.color(MAP_SIZE - 1, 1), 200);
+        assert_eq!(map_data.colors()[2 * MAP_SIZE - 1], 200u8 as i8);
+        assert_eq!(map_data.center(), (0, 0));
+        assert_eq!(map_data.banners()[0].position, (10, 64, -3));
+        assert_eq!(map_data.banners()[0].color, "red");
+    }
+
+    #[test]
+    #[should_panic(expected = "Map z coordinate out of bounds")]
+    fn test_color_out_of_bounds() {
+        MapData::new(0, (0, 0), "minecraft:overworld").color(0, MAP_SIZE);
+    }
+
+    #[test]
+    fn test_create_map_allocates_ids() {
+        let temp_dir = TempDir::new().unwrap();
+        let map_data = MapData::new(0, (0, 0), "minecraft:overworld");
+
+        assert_eq!(create_map(temp_dir.path(), &map_data).unwrap(), 0);
+        assert_eq!(create_map(temp_dir.path(), &map_data).unwrap(), 1);
+
+        let id_counts = IdCounts::load(&temp_dir.path().join(ID_COUNTS_FILE)).unwrap();
+        assert_eq!(id_counts.map_id(), Some(1));
+        assert!(MapData::load(&map_path(temp_dir.path(), 1)).is_ok());
+    }
+
+    #[test]
+    fn test_load_invalid_colors() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("map_0.dat");
+
+        let mut data = CompoundTag::new();
+        data.insert_i8_vec("colors", vec![0; 16]);
+
+        let mut compound_tag = CompoundTag::new();
+        compound_tag.insert_compound_tag("data", data);
+        write_data_file(&path, &compound_tag).unwrap();
+
+        match MapData::load(&path) {
+            Err(DataFileError::InvalidTag { name }) => assert_eq!(name, "colors"),
+            result => panic!("Expected `InvalidTag` but got `{:?}`", result),
+        }
+    }
+}
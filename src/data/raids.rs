@@ -0,0 +1,289 @@
+//! Village raids of dimension stored in `raids.dat`.
+//!
+//! End dimension uses `raids_end.dat` instead, every other dimension has `raids.dat`
+//! in its own `data` folder.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::data::raids::{raids_file, Raids};
+//! use anvil_region::world::Dimension;
+//! use tempfile::TempDir;
+//!
+//! let world_dir = TempDir::new().unwrap();
+//! let path = Dimension::Overworld
+//!     .folder_path(world_dir.path())
+//!     .join(raids_file(&Dimension::Overworld));
+//!
+//! let mut raids = Raids::load(&path).unwrap();
+//!
+//! for raid in raids.raids() {
+//!     println!("Raid {} at {:?}", raid.id(), raid.center());
+//! }
+//!
+//! raids.clear_raids();
+//! raids.save(&path).unwrap();
+//! ```
+use crate::data::{read_data_file, write_data_file, DataFileError};
+use crate::tag::{compound_tags, get_tag, set_tag};
+use crate::world::Dimension;
+use nbt::{CompoundTag, Tag};
+use std::path::Path;
+
+/// Path of raids file relative to dimension folder.
+pub const RAIDS_FILE: &str = "data/raids.dat";
+/// Path of raids file relative to end dimension folder.
+pub const END_RAIDS_FILE: &str = "data/raids_end.dat";
+
+/// Returns path of raids file relative to folder of dimension.
+pub fn raids_file(dimension: &Dimension) -> &'static str {
+    match dimension {
+        Dimension::End => END_RAIDS_FILE,
+        _ => RAIDS_FILE,
+    }
+}
+
+/// Single raid compound tag with typed accessors.
+#[derive(Debug, Clone)]
+pub struct Raid {
+    compound_tag: CompoundTag,
+}
+
+impl Raid {
+    pub fn from_compound_tag(compound_tag: CompoundTag) -> Self {
+        Raid { compound_tag }
+    }
+
+    pub fn compound_tag(&self) -> &CompoundTag {
+        &self.compound_tag
+    }
+
+    pub fn compound_tag_mut(&mut self) -> &mut CompoundTag {
+        &mut self.compound_tag
+    }
+
+    pub fn into_compound_tag(self) -> CompoundTag {
+        self.compound_tag
+    }
+
+    pub fn id(&self) -> i32 {
+        self.compound_tag.get_i32("Id").unwrap_or(0)
+    }
+
+    /// Returns block position raid is centered at.
+    pub fn center(&self) -> (i32, i32, i32) {
+        (
+            self.compound_tag.get_i32("CX").unwrap_or(0),
+            self.compound_tag.get_i32("CY").unwrap_or(0),
+            self.compound_tag.get_i32("CZ").unwrap_or(0),
+        )
+    }
+
+    /// Returns status like `ongoing`, `victory`, `loss` or `stopped`.
+    pub fn status(&self) -> Option<&str> {
+        match get_tag(&self.compound_tag, "Status")? {
+            Tag::String(status) => Some(status),
+            _ => None,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.compound_tag.get_bool("Active").unwrap_or(false)
+    }
+
+    pub fn bad_omen_level(&self) -> i32 {
+        self.compound_tag.get_i32("BadOmenLevel").unwrap_or(0)
+    }
+
+    /// Returns amount of waves spawned so far and total amount of waves.
+    pub fn waves(&self) -> (i32, i32) {
+        (
+            self.compound_tag.get_i32("GroupsSpawned").unwrap_or(0),
+            self.compound_tag.get_i32("NumGroups").unwrap_or(0),
+        )
+    }
+}
+
+/// Contents of `raids.dat`.
+#[derive(Debug, Clone)]
+pub struct Raids {
+    compound_tag: CompoundTag,
+}
+
+impl Raids {
+    /// Creates file contents without raids.
+    pub fn new() -> Self {
+        let mut data = CompoundTag::new();
+        data.insert("Raids", Tag::List(Vec::new()));
+        data.insert_i32("NextAvailableID", 1);
+        data.insert_i32("Tick", 0);
+
+        let mut compound_tag = CompoundTag::new();
+        compound_tag.insert_compound_tag("data", data);
+
+        Raids { compound_tag }
+    }
+
+    /// Reads raids, file which doesn't exist is treated as empty.
+    pub fn load(path: &Path) -> Result<Self, DataFileError> {
+        if !path.exists() {
+            return Ok(Raids::new());
+        }
+
+        let compound_tag = read_data_file(path)?;
+
+        if compound_tag.get_compound_tag("data").is_err() {
+            return Err(DataFileError::InvalidTag {
+                name: "data".to_owned(),
+            });
+        }
+
+        Ok(Raids { compound_tag })
+    }
+
+    /// Writes raids with other file tags preserved.
+    pub fn save(&self, path: &Path) -> Result<(), DataFileError> {
+        write_data_file(path, &self.compound_tag)
+    }
+
+    /// Returns whole file compound tag.
+    pub fn compound_tag(&self) -> &CompoundTag {
+        &self.compound_tag
+    }
+
+    /// Returns id which will be given to next raid.
+    pub fn next_available_id(&self) -> i32 {
+        self.data().get_i32("NextAvailableID").unwrap_or(1)
+    }
+
+    /// Returns raid manager time in ticks.
+    pub fn tick(&self) -> i32 {
+        self.data().get_i32("Tick").unwrap_or(0)
+    }
+
+    pub fn raids(&self) -> Vec<Raid> {
+        compound_tags(self.data(), "Raids")
+            .cloned()
+            .map(Raid::from_compound_tag)
+            .collect()
+    }
+
+    /// Replaces raids, next available id is raised above ids of new raids.
+    pub fn set_raids(&mut self, raids: Vec<Raid>) {
+        let next_available_id = raids
+            .iter()
+            .map(|raid| raid.id() + 1)
+            .fold(self.next_available_id(), i32::max);
+
+        let raids = raids
+            .into_iter()
+            .map(|raid| Tag::Compound(raid.into_compound_tag()))
+            .collect();
+
+        let data = self.data_mut();
+        set_tag(data, "Raids", Tag::List(raids));
+        set_tag(data, "NextAvailableID", Tag::Int(next_available_id));
+    }
+
+    /// Removes raid, returns false if there is no raid with id.
+    pub fn remove_raid(&mut self, id: i32) -> bool {
+        let mut raids = self.raids();
+        let length = raids.len();
+
+        raids.retain(|raid| raid.id() != id);
+
+        let removed = raids.len() != length;
+        self.set_raids(raids);
+
+        removed
+    }
+
+    /// Removes all raids keeping id counter.
+    pub fn clear_raids(&mut self) {
+        self.set_raids(Vec::new());
+    }
+
+    fn data(&self) -> &CompoundTag {
+        // Presence of tag is checked on creation.
+        self.compound_tag.get_compound_tag("data").unwrap()
+    }
+
+    fn data_mut(&mut self) -> &mut CompoundTag {
+        self.compound_tag
+            .get_mut::<&mut CompoundTag>("data")
+            .unwrap()
+    }
+}
+
+impl Default for Raids {
+    fn default() -> Self {
+        Raids::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::raids::{raids_file, Raid, Raids, END_RAIDS_FILE, RAIDS_FILE};
+    use crate::data::write_data_file;
+    use crate::snbt;
+    use crate::world::Dimension;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_raids_file() {
+        assert_eq!(raids_file(&Dimension::Overworld), RAIDS_FILE);
+        assert_eq!(raids_file(&Dimension::Nether), RAIDS_FILE);
+        assert_eq!(raids_file(&Dimension::End), END_RAIDS_FILE);
+    }
+
+    #[test]
+    fn test_load_preserves_unknown_tags() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("raids.dat");
+
+        let compound_tag = snbt::from_str(
+            r#"{
+                DataVersion: 2586,
+                data: {
+                    Raids: [
+                        {Id: 3, Status: "ongoing", Active: 1b, CX: 10, CY: 64, CZ: -20, GroupsSpawned: 2, NumGroups: 5, HeroesOfTheVillage: []},
+                        {Id: 4, Status: "victory", Active: 0b, CX: 0, CY: 70, CZ: 0}
+                    ],
+                    NextAvailableID: 5,
+                    Tick: 1200
+                }
+            }"#,
+        )
+        .unwrap();
+        write_data_file(&path, &compound_tag).unwrap();
+
+        let mut raids = Raids::load(&path).unwrap();
+        let raid = &raids.raids()[0];
+
+        assert_eq!(raids.tick(), 1200);
+        assert_eq!(raid.id(), 3);
+        assert_eq!(raid.status(), Some("ongoing"));
+        assert!(raid.is_active());
+        assert_eq!(raid.center(), (10, 64, -20));
+        assert_eq!(raid.waves(), (2, 5));
+
+        assert!(raids.remove_raid(4));
+        assert!(!raids.remove_raid(4));
+        raids.save(&path).unwrap();
+
+        assert_eq!(
+            snbt::to_string(Raids::load(&path).unwrap().compound_tag()),
+            r#"{DataVersion:2586,data:{Raids:[{Id:3,Status:"ongoing",Active:1b,CX:10,CY:64,CZ:-20,GroupsSpawned:2,NumGroups:5,HeroesOfTheVillage:[]}],NextAvailableID:5,Tick:1200}}"#
+        );
+    }
+
+    #[test]
+    fn test_set_raids_raises_next_id() {
+        let mut raids = Raids::new();
+        let raid = Raid::from_compound_tag(snbt::from_str("{Id: 9}").unwrap());
+
+        raids.set_raids(vec![raid]);
+
+        assert_eq!(raids.next_available_id(), 10);
+    }
+}
@@ -0,0 +1,509 @@
+//! Scoreboard objectives, scores and teams stored in `scoreboard.dat`.
+//!
+//! Scoreboard is shared by all dimensions and stored in `data` folder of overworld.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::data::scoreboard::{Scoreboard, SCOREBOARD_FILE};
+//! use tempfile::TempDir;
+//!
+//! let world_dir = TempDir::new().unwrap();
+//! let path = world_dir.path().join(SCOREBOARD_FILE);
+//!
+//! let mut scoreboard = Scoreboard::load(&path).unwrap();
+//! scoreboard.set_score("Notch", "deaths", 3);
+//! scoreboard.save(&path).unwrap();
+//!
+//! assert_eq!(Scoreboard::load(&path).unwrap().score("Notch", "deaths"), Some(3));
+//! ```
+use crate::data::{read_data_file, write_data_file, DataFileError};
+use crate::tag::{compound_tags, get_tag, remove_tag, set_tag};
+use nbt::{CompoundTag, Tag};
+use std::path::Path;
+
+/// Path of scoreboard file relative to overworld folder.
+pub const SCOREBOARD_FILE: &str = "data/scoreboard.dat";
+
+/// Scoreboard objective compound tag with typed accessors.
+#[derive(Debug, Clone)]
+pub struct Objective {
+    compound_tag: CompoundTag,
+}
+
+impl Objective {
+    /// Creates objective with display name equal to its name.
+    pub fn new(name: &str, criteria: &str) -> Self {
+        let mut compound_tag = CompoundTag::new();
+        compound_tag.insert_str("Name", name);
+        compound_tag.insert_str("CriteriaName", criteria);
+        compound_tag.insert_str("DisplayName", format!(r#"{{"text":"{}"}}"#, name));
+        compound_tag.insert_str("RenderType", "integer");
+
+        Objective { compound_tag }
+    }
+
+    pub fn from_compound_tag(compound_tag: CompoundTag) -> Self {
+        Objective { compound_tag }
+    }
+
+    pub fn compound_tag(&self) -> &CompoundTag {
+        &self.compound_tag
+    }
+
+    pub fn compound_tag_mut(&mut self) -> &mut CompoundTag {
+        &mut self.compound_tag
+    }
+
+    pub fn into_compound_tag(self) -> CompoundTag {
+        self.compound_tag
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        string_tag(&self.compound_tag, "Name")
+    }
+
+    /// Returns criteria like `dummy` or `minecraft.killed:minecraft.zombie`.
+    pub fn criteria(&self) -> Option<&str> {
+        string_tag(&self.compound_tag, "CriteriaName")
+    }
+
+    /// Returns display name as JSON text component.
+    pub fn display_name(&self) -> Option<&str> {
+        string_tag(&self.compound_tag, "DisplayName")
+    }
+}
+
+/// Score of single score holder in objective.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Score {
+    /// Player name or entity UUID.
+    pub owner: String,
+    pub objective: String,
+    pub value: i32,
+    /// Whether value can't be changed by `trigger` command.
+    pub locked: bool,
+}
+
+impl Score {
+    fn from_compound_tag(compound_tag: &CompoundTag) -> Option<Self> {
+        Some(Score {
+            owner: string_tag(compound_tag, "Name")?.to_owned(),
+            objective: string_tag(compound_tag, "Objective")?.to_owned(),
+            value: compound_tag.get_i32("Score").unwrap_or(0),
+            locked: compound_tag.get_bool("Locked").unwrap_or(false),
+        })
+    }
+
+    fn to_compound_tag(&self) -> CompoundTag {
+        let mut compound_tag = CompoundTag::new();
+        compound_tag.insert_str("Name", &self.owner);
+        compound_tag.insert_str("Objective", &self.objective);
+        compound_tag.insert_i32("Score", self.value);
+        compound_tag.insert_bool("Locked", self.locked);
+
+        compound_tag
+    }
+}
+
+/// Team compound tag with typed accessors.
+#[derive(Debug, Clone)]
+pub struct Team {
+    compound_tag: CompoundTag,
+}
+
+impl Team {
+    /// Creates team without players.
+    pub fn new(name: &str) -> Self {
+        let mut compound_tag = CompoundTag::new();
+        compound_tag.insert_str("Name", name);
+        compound_tag.insert_str("DisplayName", format!(r#"{{"text":"{}"}}"#, name));
+        compound_tag.insert("Players", Tag::List(Vec::new()));
+
+        Team { compound_tag }
+    }
+
+    pub fn from_compound_tag(compound_tag: CompoundTag) -> Self {
+        Team { compound_tag }
+    }
+
+    pub fn compound_tag(&self) -> &CompoundTag {
+        &self.compound_tag
+    }
+
+    pub fn compound_tag_mut(&mut self) -> &mut CompoundTag {
+        &mut self.compound_tag
+    }
+
+    pub fn into_compound_tag(self) -> CompoundTag {
+        self.compound_tag
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        string_tag(&self.compound_tag, "Name")
+    }
+
+    /// Returns color like `red`, none if team has no color.
+    pub fn color(&self) -> Option<&str> {
+        string_tag(&self.compound_tag, "TeamColor")
+    }
+
+    /// Returns names and UUIDs of team members.
+    pub fn players(&self) -> Vec<&str> {
+        match get_tag(&self.compound_tag, "Players") {
+            Some(Tag::List(players)) => players
+                .iter()
+                .filter_map(|player| match player {
+                    Tag::String(player) => Some(player.as_str()),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn set_players(&mut self, players: &[&str]) {
+        let players = players
+            .iter()
+            .map(|player| Tag::String((*player).to_owned()))
+            .collect();
+
+        set_tag(&mut self.compound_tag, "Players", Tag::List(players));
+    }
+}
+
+/// Contents of `scoreboard.dat`.
+#[derive(Debug, Clone)]
+pub struct Scoreboard {
+    compound_tag: CompoundTag,
+}
+
+impl Scoreboard {
+    /// Creates file contents without objectives and teams.
+    pub fn new() -> Self {
+        let mut data = CompoundTag::new();
+        data.insert("Objectives", Tag::List(Vec::new()));
+        data.insert("PlayerScores", Tag::List(Vec::new()));
+        data.insert("Teams", Tag::List(Vec::new()));
+        data.insert_compound_tag("DisplaySlots", CompoundTag::new());
+
+        let mut compound_tag = CompoundTag::new();
+        compound_tag.insert_compound_tag("data", data);
+
+        Scoreboard { compound_tag }
+    }
+
+    /// Reads scoreboard, file which doesn't exist is treated as empty.
+    pub fn load(path: &Path) -> Result<Self, DataFileError> {
+        if !path.exists() {
+            return Ok(Scoreboard::new());
+        }
+
+        let compound_tag = read_data_file(path)?;
+
+        if compound_tag.get_compound_tag("data").is_err() {
+            return Err(DataFileError::InvalidTag {
+                name: "data".to_owned(),
+            });
+        }
+
+        Ok(Scoreboard { compound_tag })
+    }
+
+    /// Writes scoreboard with other file tags preserved.
+    pub fn save(&self, path: &Path) -> Result<(), DataFileError> {
+        write_data_file(path, &self.compound_tag)
+    }
+
+    /// Returns whole file compound tag.
+    pub fn compound_tag(&self) -> &CompoundTag {
+        &self.compound_tag
+    }
+
+    pub fn objectives(&self) -> Vec<Objective> {
+        compound_tags(self.data(), "Objectives")
+            .cloned()
+            .map(Objective::from_compound_tag)
+            .collect()
+    }
+
+    pub fn set_objectives(&mut self, objectives: Vec<Objective>) {
+        let objectives = objectives
+            .into_iter()
+            .map(|objective| Tag::Compound(objective.into_compound_tag()))
+            .collect();
+
+        set_tag(self.data_mut(), "Objectives", Tag::List(objectives));
+    }
+
+    /// Adds objective or replaces objective with the same name.
+    pub fn add_objective(&mut self, objective: Objective) {
+        let mut objectives = self.objectives();
+
+        match objectives
+            .iter_mut()
+            .find(|existing| existing.name() == objective.name())
+        {
+            Some(existing) => *existing = objective,
+            None => objectives.push(objective),
+        }
+
+        self.set_objectives(objectives);
+    }
+
+    /// Removes objective with its scores and display slots, returns false if there is
+    /// no objective with name.
+    pub fn remove_objective(&mut self, name: &str) -> bool {
+        let mut objectives = self.objectives();
+        let length = objectives.len();
+
+        objectives.retain(|objective| objective.name() != Some(name));
+
+        if objectives.len() == length {
+            return false;
+        }
+
+        self.set_objectives(objectives);
+        self.retain_scores(|score| score.objective != name);
+
+        if let Ok(display_slots) = self.data_mut().get_mut::<&mut CompoundTag>("DisplaySlots") {
+            let slots: Vec<String> = display_slots
+                .iter()
+                .filter(|(_, tag)| matches!(tag, Tag::String(objective) if objective == name))
+                .map(|(slot, _)| slot.clone())
+                .collect();
+
+            for slot in slots {
+                remove_tag(display_slots, &slot);
+            }
+        }
+
+        true
+    }
+
+    /// Returns name of objective shown in display slot like `slot_1` for sidebar.
+    pub fn display_slot(&self, slot: &str) -> Option<&str> {
+        string_tag(self.data().get_compound_tag("DisplaySlots").ok()?, slot)
+    }
+
+    /// Returns scores of all score holders.
+    pub fn scores(&self) -> Vec<Score> {
+        compound_tags(self.data(), "PlayerScores")
+            .filter_map(Score::from_compound_tag)
+            .collect()
+    }
+
+    pub fn set_scores(&mut self, scores: Vec<Score>) {
+        let scores = scores
+            .iter()
+            .map(|score| Tag::Compound(score.to_compound_tag()))
+            .collect();
+
+        set_tag(self.data_mut(), "PlayerScores", Tag::List(scores));
+    }
+
+    /// Returns score of holder in objective.
+    pub fn score(&self, owner: &str, objective: &str) -> Option<i32> {
+        compound_tags(self.data(), "PlayerScores")
+            .filter_map(Score::from_compound_tag)
+            .find(|score| score.owner == owner && score.objective == objective)
+            .map(|score| score.value)
+    }
+
+    /// Sets score of holder in objective, adding score if needed.
+    pub fn set_score(&mut self, owner: &str, objective: &str, value: i32) {
+        let mut scores = self.scores();
+
+        match scores
+            .iter_mut()
+            .find(|score| score.owner == owner && score.objective == objective)
+        {
+            Some(score) => score.value = value,
+            None => scores.push(Score {
+                owner: owner.to_owned(),
+                objective: objective.to_owned(),
+                value,
+                locked: false,
+            }),
+        }
+
+        self.set_scores(scores);
+    }
+
+    /// Keeps only scores for which predicate returns true, returns number of removed scores.
+    pub fn retain_scores(&mut self, mut predicate: impl FnMut(&Score) -> bool) -> usize {
+        let mut scores = self.scores();
+        let length = scores.len();
+
+        scores.retain(|score| predicate(score));
+
+        let removed = length - scores.len();
+        self.set_scores(scores);
+
+        removed
+    }
+
+    pub fn teams(&self) -> Vec<Team> {
+        compound_tags(self.data(), "Teams")
+            .cloned()
+            .map(Team::from_compound_tag)
+            .collect()
+    }
+
+    pub fn set_teams(&mut self, teams: Vec<Team>) {
+        let teams = teams
+            .into_iter()
+            .map(|team| Tag::Compound(team.into_compound_tag()))
+            .collect();
+
+        set_tag(self.data_mut(), "Teams", Tag::List(teams));
+    }
+
+    /// Returns name of team holder is member of.
+    pub fn team_of(&self, owner: &str) -> Option<String> {
+        self.teams()
+            .iter()
+            .find(|team| team.players().contains(&owner))
+            .and_then(|team| team.name().map(str::to_owned))
+    }
+
+    /// Removes all scores and team memberships of holder, returns false if holder had none.
+    pub fn remove_holder(&mut self, owner: &str) -> bool {
+        let mut removed = self.retain_scores(|score| score.owner != owner) > 0;
+        let mut teams = self.teams();
+
+        for team in &mut teams {
+            let players = team.players();
+
+            if players.contains(&owner) {
+                let players: Vec<String> = players
+                    .into_iter()
+                    .filter(|player| *player != owner)
+                    .map(str::to_owned)
+                    .collect();
+                let players: Vec<&str> = players.iter().map(String::as_str).collect();
+
+                team.set_players(&players);
+                removed = true;
+            }
+        }
+
+        self.set_teams(teams);
+
+        removed
+    }
+
+    fn data(&self) -> &CompoundTag {
+        // Presence of tag is checked on creation.
+        self.compound_tag.get_compound_tag("data").unwrap()
+    }
+
+    fn data_mut(&mut self) -> &mut CompoundTag {
+        self.compound_tag
+            .get_mut::<&mut CompoundTag>("data")
+            .unwrap()
+    }
+}
+
+impl Default for Scoreboard {
+    fn default() -> Self {
+        Scoreboard::new()
+    }
+}
+
+fn string_tag<'a>(compound_tag: &'a CompoundTag, name: &str) -> Option<&'a str> {
+    match get_tag(compound_tag, name)? {
+        Tag::String(value) => Some(value),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::scoreboard::{Objective, Score, Scoreboard, Team};
+    use crate::data::write_data_file;
+    use crate::snbt;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_load_preserves_unknown_tags() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("scoreboard.dat");
+
+        let compound_tag = snbt::from_str(
+            r#"{
+                DataVersion: 2586,
+                data: {
+                    Objectives: [
+                        {Name: "deaths", CriteriaName: "deathCount", DisplayName: '{"text":"Deaths"}', RenderType: "integer"},
+                        {Name: "kills", CriteriaName: "playerKillCount", DisplayName: '{"text":"Kills"}', RenderType: "integer"}
+                    ],
+                    PlayerScores: [
+                        {Name: "Notch", Objective: "deaths", Score: 3, Locked: 0b},
+                        {Name: "Notch", Objective: "kills", Score: 7, Locked: 0b},
+                        {Name: "jeb_", Objective: "kills", Score: 1, Locked: 1b}
+                    ],
+                    Teams: [
+                        {Name: "red", DisplayName: '{"text":"Red"}', TeamColor: "red", Players: ["Notch", "jeb_"], AllowFriendlyFire: 1b}
+                    ],
+                    DisplaySlots: {slot_0: "kills", slot_1: "deaths"}
+                }
+            }"#,
+        )
+        .unwrap();
+        write_data_file(&path, &compound_tag).unwrap();
+
+        let mut scoreboard = Scoreboard::load(&path).unwrap();
+        let objective = &scoreboard.objectives()[0];
+        let team = &scoreboard.teams()[0];
+
+        assert_eq!(objective.name(), Some("deaths"));
+        assert_eq!(objective.criteria(), Some("deathCount"));
+        assert_eq!(objective.display_name(), Some(r#"{"text":"Deaths"}"#));
+        assert_eq!(team.color(), Some("red"));
+        assert_eq!(team.players(), vec!["Notch", "jeb_"]);
+        assert_eq!(scoreboard.score("Notch", "kills"), Some(7));
+        assert_eq!(scoreboard.team_of("jeb_"), Some("red".to_owned()));
+        assert_eq!(
+            scoreboard.scores()[2],
+            Score {
+                owner: "jeb_".to_owned(),
+                objective: "kills".to_owned(),
+                value: 1,
+                locked: true,
+            }
+        );
+
+        assert!(scoreboard.remove_objective("kills"));
+        assert!(!scoreboard.remove_objective("kills"));
+        assert!(scoreboard.remove_holder("jeb_"));
+        assert!(!scoreboard.remove_holder("jeb_"));
+        scoreboard.save(&path).unwrap();
+
+        assert_eq!(
+            snbt::to_string(Scoreboard::load(&path).unwrap().compound_tag()),
+            r#"{DataVersion:2586,data:{Objectives:[{Name:"deaths",CriteriaName:"deathCount",DisplayName:"{\"text\":\"Deaths\"}",RenderType:"integer"}],PlayerScores:[{Name:"Notch",Objective:"deaths",Score:3,Locked:0b}],Teams:[{Name:"red",DisplayName:"{\"text\":\"Red\"}",TeamColor:"red",Players:["Notch"],AllowFriendlyFire:1b}],DisplaySlots:{slot_1:"deaths"}}}"#
+        );
+    }
+
+    #[test]
+    fn test_set_score_and_add_objective() {
+        let mut scoreboard = Scoreboard::new();
+
+        scoreboard.add_objective(Objective::new("deaths", "deathCount"));
+        scoreboard.add_objective(Objective::new("deaths", "dummy"));
+        scoreboard.set_score("Notch", "deaths", 1);
+        scoreboard.set_score("Notch", "deaths", 2);
+
+        let mut team = Team::new("blue");
+        team.set_players(&["Notch"]);
+        scoreboard.set_teams(vec![team]);
+
+        assert_eq!(scoreboard.objectives().len(), 1);
+        assert_eq!(scoreboard.objectives()[0].criteria(), Some("dummy"));
+        assert_eq!(scoreboard.scores().len(), 1);
+        assert_eq!(scoreboard.score("Notch", "deaths"), Some(2));
+        assert_eq!(scoreboard.team_of("Notch"), Some("blue".to_owned()));
+        assert_eq!(scoreboard.display_slot("slot_1"), None);
+    }
+}
@@ -0,0 +1,723 @@
+//! Compact deltas between two versions of world.
+//!
+//! [`compute_delta`] compares compressed chunk payloads of every region file by hash,
+//! so delta contains only chunks which were saved with different contents, deleted
+//! chunks and changed files like `level.dat`. Chunks are stored still compressed.
+//! Servers can ship nightly deltas instead of full archives and mirrors apply them with
+//! [`apply_delta`].
+//!
+//! Every operation remembers hash of the data it replaces and delta is checked against
+//! the whole world before anything is written, so delta applied to another version of
+//! world fails without changing it.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::delta::{apply_delta, compute_delta, WorldDelta};
+//! use anvil_region::relocate::copy_chunk;
+//! use anvil_region::AnvilChunkProvider;
+//! use tempfile::TempDir;
+//!
+//! let old_world_dir = TempDir::new().unwrap();
+//! let new_world_dir = TempDir::new().unwrap();
+//! let new_region_folder = new_world_dir.path().join("region");
+//!
+//! let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+//! let chunk_provider = AnvilChunkProvider::new(new_region_folder.to_str().unwrap());
+//! copy_chunk(&fixture_chunk_provider, (4, 2), &chunk_provider, (4, 2)).unwrap();
+//!
+//! let world_delta = compute_delta(old_world_dir.path(), new_world_dir.path()).unwrap();
+//!
+//! let mut delta_bytes = Vec::new();
+//! world_delta.write_to(&mut delta_bytes).unwrap();
+//!
+//! let world_delta = WorldDelta::read_from(&delta_bytes[..]).unwrap();
+//! let delta_report = apply_delta(old_world_dir.path(), &world_delta).unwrap();
+//!
+//! assert_eq!(delta_report.saved_chunks, 1);
+//! ```
+use crate::backup::fnv_hash;
+use crate::snapshot::break_hard_link;
+use crate::{region_position, AnvilChunkProvider, AnvilRegion, ChunkLoadError, ChunkSaveError};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::{fs, io};
+
+/// Magic bytes at the start of serialized delta.
+const DELTA_MAGIC: &[u8; 4] = b"ANVD";
+/// Version of serialized delta format.
+const DELTA_VERSION: u8 = 1;
+
+const SAVE_CHUNK_OPERATION: u8 = 1;
+const DELETE_CHUNK_OPERATION: u8 = 2;
+const WRITE_FILE_OPERATION: u8 = 3;
+const DELETE_FILE_OPERATION: u8 = 4;
+
+/// Lock file of running game which is never compared.
+const SESSION_LOCK_FILE: &str = "session.lock";
+
+/// Possible errors while computing, reading or applying delta.
+#[derive(Debug)]
+pub enum DeltaError {
+    /// Serialized delta is malformed.
+    InvalidDelta { message: String },
+    /// Chunk or file of world isn't the one delta was computed from.
+    BaseMismatch {
+        path: PathBuf,
+        chunk: Option<(i32, i32)>,
+    },
+    /// Chunk data can't be read from region file.
+    ChunkLoadError { chunk_load_error: ChunkLoadError },
+    /// Chunk data can't be written to region file.
+    ChunkSaveError { chunk_save_error: ChunkSaveError },
+    /// File can't be read or written.
+    IoError { io_error: io::Error },
+}
+
+impl From<ChunkLoadError> for DeltaError {
+    fn from(chunk_load_error: ChunkLoadError) -> Self {
+        DeltaError::ChunkLoadError { chunk_load_error }
+    }
+}
+
+impl From<ChunkSaveError> for DeltaError {
+    fn from(chunk_save_error: ChunkSaveError) -> Self {
+        DeltaError::ChunkSaveError { chunk_save_error }
+    }
+}
+
+impl From<io::Error> for DeltaError {
+    fn from(io_error: io::Error) -> Self {
+        DeltaError::IoError { io_error }
+    }
+}
+
+/// Single change of world, paths are relative to world folder.
+///
+/// Base hash is hash of chunk buffer or file which is replaced, none if there is none.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum DeltaOperation {
+    /// Writes compressed chunk buffer which starts with compression type.
+    SaveChunk {
+        folder_path: PathBuf,
+        chunk_x: i32,
+        chunk_z: i32,
+        base_hash: Option<u64>,
+        chunk_buffer: Vec<u8>,
+    },
+    DeleteChunk {
+        folder_path: PathBuf,
+        chunk_x: i32,
+        chunk_z: i32,
+        base_hash: u64,
+    },
+    WriteFile {
+        path: PathBuf,
+        base_hash: Option<u64>,
+        data: Vec<u8>,
+    },
+    DeleteFile {
+        path: PathBuf,
+        base_hash: u64,
+    },
+}
+
+/// Changes which turn old version of world into new one.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct WorldDelta {
+    pub operations: Vec<DeltaOperation>,
+}
+
+/// Result of applying delta.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeltaReport {
+    pub saved_chunks: usize,
+    pub deleted_chunks: usize,
+    pub written_files: usize,
+    pub deleted_files: usize,
+}
+
+impl WorldDelta {
+    /// Returns true if versions of world are equal.
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// Serializes delta into writer.
+    pub fn write_to(&self, mut writer: impl Write) -> Result<(), io::Error> {
+        writer.write_all(DELTA_MAGIC)?;
+        writer.write_u8(DELTA_VERSION)?;
+        writer.write_u32::<BigEndian>(self.operations.len() as u32)?;
+
+        for operation in &self.operations {
+            match operation {
+                DeltaOperation::SaveChunk {
+                    folder_path,
+                    chunk_x,
+                    chunk_z,
+                    base_hash,
+                    chunk_buffer,
+                } => {
+                    writer.write_u8(SAVE_CHUNK_OPERATION)?;
+                    write_path(&mut writer, folder_path)?;
+                    writer.write_i32::<BigEndian>(*chunk_x)?;
+                    writer.write_i32::<BigEndian>(*chunk_z)?;
+                    write_base_hash(&mut writer, *base_hash)?;
+                    write_data(&mut writer, chunk_buffer)?;
+                }
+                DeltaOperation::DeleteChunk {
+                    folder_path,
+                    chunk_x,
+                    chunk_z,
+                    base_hash,
+                } => {
+                    writer.write_u8(DELETE_CHUNK_OPERATION)?;
+                    write_path(&mut writer, folder_path)?;
+                    writer.write_i32::<BigEndian>(*chunk_x)?;
+                    writer.write_i32::<BigEndian>(*chunk_z)?;
+                    writer.write_u64::<BigEndian>(*base_hash)?;
+                }
+                DeltaOperation::WriteFile {
+                    path,
+                    base_hash,
+                    data,
+                } => {
+                    writer.write_u8(WRITE_FILE_OPERATION)?;
+                    write_path(&mut writer, path)?;
+                    write_base_hash(&mut writer, *base_hash)?;
+                    write_data(&mut writer, data)?;
+                }
+                DeltaOperation::DeleteFile { path, base_hash } => {
+                    writer.write_u8(DELETE_FILE_OPERATION)?;
+                    write_path(&mut writer, path)?;
+                    writer.write_u64::<BigEndian>(*base_hash)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads delta serialized by [`write_to`](WorldDelta::write_to).
+    pub fn read_from(mut reader: impl Read) -> Result<Self, DeltaError> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+
+        if &magic != DELTA_MAGIC {
+            return Err(invalid_delta("Not a world delta"));
+        }
+
+        let version = reader.read_u8()?;
+
+        if version != DELTA_VERSION {
+            return Err(invalid_delta(format!("Unsupported version {}", version)));
+        }
+
+        let length = reader.read_u32::<BigEndian>()?;
+        let mut operations = Vec::new();
+
+        for _ in 0..length {
+            let operation = match reader.read_u8()? {
+                SAVE_CHUNK_OPERATION => DeltaOperation::SaveChunk {
+                    folder_path: read_path(&mut reader)?,
+                    chunk_x: reader.read_i32::<BigEndian>()?,
+                    chunk_z: reader.read_i32::<BigEndian>()?,
+                    base_hash: read_base_hash(&mut reader)?,
+                    chunk_buffer: read_data(&mut reader)?,
+                },
+                DELETE_CHUNK_OPERATION => DeltaOperation::DeleteChunk {
+                    folder_path: read_path(&mut reader)?,
+                    chunk_x: reader.read_i32::<BigEndian>()?,
+                    chunk_z: reader.read_i32::<BigEndian>()?,
+                    base_hash: reader.read_u64::<BigEndian>()?,
+                },
+                WRITE_FILE_OPERATION => DeltaOperation::WriteFile {
+                    path: read_path(&mut reader)?,
+                    base_hash: read_base_hash(&mut reader)?,
+                    data: read_data(&mut reader)?,
+                },
+                DELETE_FILE_OPERATION => DeltaOperation::DeleteFile {
+                    path: read_path(&mut reader)?,
+                    base_hash: reader.read_u64::<BigEndian>()?,
+                },
+                operation_type => {
+                    return Err(invalid_delta(format!(
+                        "Unknown operation type {}",
+                        operation_type
+                    )));
+                }
+            };
+
+            operations.push(operation);
+        }
+
+        Ok(WorldDelta { operations })
+    }
+}
+
+/// Computes delta which turns old world folder into new one.
+///
+/// Chunks are compared region file by region file, so only two regions are held in
+/// memory at once besides delta itself. Files other than region files are compared
+/// as a whole, `session.lock` is skipped.
+pub fn compute_delta(
+    old_world_folder_path: &Path,
+    new_world_folder_path: &Path,
+) -> Result<WorldDelta, DeltaError> {
+    let mut paths = BTreeSet::new();
+    world_files(old_world_folder_path, Path::new(""), &mut paths)?;
+    world_files(new_world_folder_path, Path::new(""), &mut paths)?;
+
+    let mut operations = Vec::new();
+
+    for path in paths {
+        let old_path = old_world_folder_path.join(&path);
+        let new_path = new_world_folder_path.join(&path);
+
+        let region_position = path
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .and_then(region_position);
+
+        if let Some((region_x, region_z)) = region_position {
+            let folder_path = path.parent().unwrap_or(Path::new("")).to_path_buf();
+            let old_chunks = region_chunks(&old_path)?;
+            let new_chunks = region_chunks(&new_path)?;
+
+            for (&(region_chunk_x, region_chunk_z), chunk_buffer) in &new_chunks {
+                let base_hash = old_chunks
+                    .get(&(region_chunk_x, region_chunk_z))
+                    .map(|old_chunk_buffer| fnv_hash(old_chunk_buffer));
+
+                if base_hash == Some(fnv_hash(chunk_buffer)) {
+                    continue;
+                }
+
+                operations.push(DeltaOperation::SaveChunk {
+                    folder_path: folder_path.clone(),
+                    chunk_x: (region_x << 5) + region_chunk_x as i32,
+                    chunk_z: (region_z << 5) + region_chunk_z as i32,
+                    base_hash,
+                    chunk_buffer: chunk_buffer.clone(),
+                });
+            }
+
+            for (&(region_chunk_x, region_chunk_z), old_chunk_buffer) in &old_chunks {
+                if !new_chunks.contains_key(&(region_chunk_x, region_chunk_z)) {
+                    operations.push(DeltaOperation::DeleteChunk {
+                        folder_path: folder_path.clone(),
+                        chunk_x: (region_x << 5) + region_chunk_x as i32,
+                        chunk_z: (region_z << 5) + region_chunk_z as i32,
+                        base_hash: fnv_hash(old_chunk_buffer),
+                    });
+                }
+            }
+        } else {
+            let base_hash = read_file(&old_path)?.map(|data| fnv_hash(&data));
+
+            match read_file(&new_path)? {
+                Some(data) if base_hash != Some(fnv_hash(&data)) => {
+                    operations.push(DeltaOperation::WriteFile {
+                        path,
+                        base_hash,
+                        data,
+                    });
+                }
+                Some(_) => {}
+                None => {
+                    if let Some(base_hash) = base_hash {
+                        operations.push(DeltaOperation::DeleteFile { path, base_hash });
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(WorldDelta { operations })
+}
+
+/// Applies delta to world folder.
+///
+/// Chunks and files replaced by delta are checked against their base hashes first, so
+/// world which isn't the old version delta was computed from is left unchanged. Region
+/// files emptied by deleted chunks are kept.
+pub fn apply_delta(
+    world_folder_path: &Path,
+    world_delta: &WorldDelta,
+) -> Result<DeltaReport, DeltaError> {
+    for operation in &world_delta.operations {
+        let (path, chunk, base_hash, current_hash) = match operation {
+            DeltaOperation::SaveChunk {
+                folder_path,
+                chunk_x,
+                chunk_z,
+                base_hash,
+                ..
+            } => (
+                folder_path,
+                Some((*chunk_x, *chunk_z)),
+                *base_hash,
+                chunk_hash(&world_folder_path.join(folder_path), *chunk_x, *chunk_z)?,
+            ),
+            DeltaOperation::DeleteChunk {
+                folder_path,
+                chunk_x,
+                chunk_z,
+                base_hash,
+            } => (
+                folder_path,
+                Some((*chunk_x, *chunk_z)),
+                Some(*base_hash),
+                chunk_hash(&world_folder_path.join(folder_path), *chunk_x, *chunk_z)?,
+            ),
+            DeltaOperation::WriteFile {
+                path, base_hash, ..
+            } => (
+                path,
+                None,
+                *base_hash,
+                read_file(&world_folder_path.join(path))?.map(|data| fnv_hash(&data)),
+            ),
+            DeltaOperation::DeleteFile { path, base_hash } => (
+                path,
+                None,
+                Some(*base_hash),
+                read_file(&world_folder_path.join(path))?.map(|data| fnv_hash(&data)),
+            ),
+        };
+
+        if current_hash != base_hash {
+            return Err(DeltaError::BaseMismatch {
+                path: path.clone(),
+                chunk,
+            });
+        }
+    }
+
+    let mut delta_report = DeltaReport::default();
+
+    for operation in &world_delta.operations {
+        match operation {
+            DeltaOperation::SaveChunk {
+                folder_path,
+                chunk_x,
+                chunk_z,
+                chunk_buffer,
+                ..
+            } => {
+                let folder_path = world_folder_path.join(folder_path);
+                let chunk_provider = AnvilChunkProvider::new(path_str(&folder_path)?);
+
+                chunk_provider.save_chunk_buffer(*chunk_x, *chunk_z, chunk_buffer)?;
+                delta_report.saved_chunks += 1;
+            }
+            DeltaOperation::DeleteChunk {
+                folder_path,
+                chunk_x,
+                chunk_z,
+                ..
+            } => {
+                let folder_path = world_folder_path.join(folder_path);
+                let chunk_provider = AnvilChunkProvider::new(path_str(&folder_path)?);
+
+                chunk_provider.delete_chunk(*chunk_x, *chunk_z)?;
+                delta_report.deleted_chunks += 1;
+            }
+            DeltaOperation::WriteFile { path, data, .. } => {
+                let path = world_folder_path.join(path);
+
+                if let Some(parent_path) = path.parent() {
+                    fs::create_dir_all(parent_path)?;
+                }
+
+                break_hard_link(&path)?;
+                fs::write(path, data)?;
+                delta_report.written_files += 1;
+            }
+            DeltaOperation::DeleteFile { path, .. } => {
+                fs::remove_file(world_folder_path.join(path))?;
+                delta_report.deleted_files += 1;
+            }
+        }
+    }
+
+    Ok(delta_report)
+}
+
+/// Collects paths of files of folder relative to world folder, missing folder has none.
+fn world_files(
+    folder_path: &Path,
+    relative_path: &Path,
+    paths: &mut BTreeSet<PathBuf>,
+) -> Result<(), io::Error> {
+    if !folder_path.is_dir() {
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(folder_path)? {
+        let path = entry?.path();
+
+        let file_name = match path.file_name() {
+            Some(file_name) => file_name,
+            None => continue,
+        };
+
+        if path.is_dir() {
+            world_files(&path, &relative_path.join(file_name), paths)?;
+        } else if file_name != SESSION_LOCK_FILE {
+            paths.insert(relative_path.join(file_name));
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads chunk buffers of region file keyed by position in region.
+///
+/// Region file is opened only if it exists, since opening creates it.
+fn region_chunks(region_path: &Path) -> Result<BTreeMap<(u8, u8), Vec<u8>>, DeltaError> {
+    let mut chunks = BTreeMap::new();
+
+    if !region_path.is_file() {
+        return Ok(chunks);
+    }
+
+    let mut region = AnvilRegion::new(region_path)?;
+
+    for (region_chunk_x, region_chunk_z) in region.chunk_positions() {
+        let (compression_scheme, compressed_buffer) =
+            region.read_chunk_data(region_chunk_x, region_chunk_z)?;
+
+        let mut chunk_buffer = Vec::with_capacity(compressed_buffer.len() + 1);
+        chunk_buffer.push(compression_scheme);
+        chunk_buffer.extend_from_slice(&compressed_buffer);
+
+        chunks.insert((region_chunk_x, region_chunk_z), chunk_buffer);
+    }
+
+    Ok(chunks)
+}
+
+/// Returns hash of chunk buffer of region folder, none if chunk is missing.
+fn chunk_hash(folder_path: &Path, chunk_x: i32, chunk_z: i32) -> Result<Option<u64>, DeltaError> {
+    let region_name = format!("r.{}.{}.mca", chunk_x >> 5, chunk_z >> 5);
+    let chunks = region_chunks(&folder_path.join(region_name))?;
+
+    let chunk_buffer = chunks.get(&((chunk_x & 31) as u8, (chunk_z & 31) as u8));
+
+    Ok(chunk_buffer.map(|chunk_buffer| fnv_hash(chunk_buffer)))
+}
+
+/// Reads file, none if it doesn't exist.
+fn read_file(path: &Path) -> Result<Option<Vec<u8>>, io::Error> {
+    match fs::read(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(io_error) if io_error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(io_error) => Err(io_error),
+    }
+}
+
+fn path_str(path: &Path) -> Result<&str, io::Error> {
+    path.to_str()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Path is not valid unicode"))
+}
+
+/// Writes relative path with forward slashes on every platform.
+fn write_path(writer: &mut impl Write, path: &Path) -> Result<(), io::Error> {
+    let mut components = Vec::new();
+
+    for component in path.components() {
+        components.push(path_str(component.as_os_str().as_ref())?);
+    }
+
+    let path = components.join("/");
+    writer.write_u16::<BigEndian>(path.len() as u16)?;
+    writer.write_all(path.as_bytes())
+}
+
+/// Reads relative path, paths leading outside of world folder are rejected.
+fn read_path(reader: &mut impl Read) -> Result<PathBuf, DeltaError> {
+    let length = reader.read_u16::<BigEndian>()?;
+    let mut bytes = vec![0; length as usize];
+    reader.read_exact(&mut bytes)?;
+
+    let path = String::from_utf8(bytes)
+        .map(PathBuf::from)
+        .map_err(|_| invalid_delta("Path is not valid unicode"))?;
+
+    let is_relative = path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+
+    if !is_relative {
+        return Err(invalid_delta(format!(
+            "Path {} is outside of world",
+            path.display()
+        )));
+    }
+
+    Ok(path)
+}
+
+fn write_base_hash(writer: &mut impl Write, base_hash: Option<u64>) -> Result<(), io::Error> {
+    match base_hash {
+        Some(base_hash) => {
+            writer.write_u8(1)?;
+            writer.write_u64::<BigEndian>(base_hash)
+        }
+        None => writer.write_u8(0),
+    }
+}
+
+fn read_base_hash(reader: &mut impl Read) -> Result<Option<u64>, DeltaError> {
+    match reader.read_u8()? {
+        0 => Ok(None),
+        1 => Ok(Some(reader.read_u64::<BigEndian>()?)),
+        _ => Err(invalid_delta("Invalid base hash")),
+    }
+}
+
+fn write_data(writer: &mut impl Write, data: &[u8]) -> Result<(), io::Error> {
+    writer.write_u32::<BigEndian>(data.len() as u32)?;
+    writer.write_all(data)
+}
+
+fn read_data(reader: &mut impl Read) -> Result<Vec<u8>, DeltaError> {
+    let length = reader.read_u32::<BigEndian>()?;
+    let mut data = Vec::new();
+
+    // Length isn't trusted for allocation, reading stops at the end of delta.
+    reader.take(length as u64).read_to_end(&mut data)?;
+
+    if data.len() != length as usize {
+        return Err(invalid_delta("Data is cut short"));
+    }
+
+    Ok(data)
+}
+
+fn invalid_delta(message: impl Into<String>) -> DeltaError {
+    DeltaError::InvalidDelta {
+        message: message.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::delta::{apply_delta, compute_delta, DeltaError, DeltaReport, WorldDelta};
+    use crate::relocate::copy_chunk;
+    use crate::snapshot::snapshot;
+    use crate::AnvilChunkProvider;
+    use nbt::CompoundTag;
+    use std::fs;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    /// Replaces file of snapshot without changing file it's linked to.
+    fn replace_file(path: &Path, data: &[u8]) {
+        fs::remove_file(path).unwrap();
+        fs::write(path, data).unwrap();
+    }
+
+    fn create_world(world_dir: &TempDir) {
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let region_folder = world_dir.path().join("region");
+        let chunk_provider = AnvilChunkProvider::new(region_folder.to_str().unwrap());
+
+        for chunk_x in 4..7 {
+            copy_chunk(
+                &fixture_chunk_provider,
+                (4, 2),
+                &chunk_provider,
+                (chunk_x, 2),
+            )
+            .unwrap();
+        }
+
+        fs::write(world_dir.path().join("level.dat"), b"level").unwrap();
+        fs::write(world_dir.path().join("icon.png"), b"icon").unwrap();
+    }
+
+    #[test]
+    fn test_compute_and_apply_delta() {
+        let old_world_dir = TempDir::new().unwrap();
+        let new_world_dir = TempDir::new().unwrap();
+        create_world(&old_world_dir);
+        snapshot(old_world_dir.path(), new_world_dir.path()).unwrap();
+
+        let region_folder = new_world_dir.path().join("region");
+        let chunk_provider = AnvilChunkProvider::new(region_folder.to_str().unwrap());
+        chunk_provider.save_chunk(4, 2, CompoundTag::new()).unwrap();
+        chunk_provider.delete_chunk(5, 2).unwrap();
+        chunk_provider
+            .save_chunk(-40, 2, CompoundTag::new())
+            .unwrap();
+        replace_file(&new_world_dir.path().join("level.dat"), b"changed");
+        fs::remove_file(new_world_dir.path().join("icon.png")).unwrap();
+        fs::create_dir(new_world_dir.path().join("data")).unwrap();
+        fs::write(new_world_dir.path().join("data/raids.dat"), b"raids").unwrap();
+
+        let world_delta = compute_delta(old_world_dir.path(), new_world_dir.path()).unwrap();
+        let mut delta_bytes = Vec::new();
+        world_delta.write_to(&mut delta_bytes).unwrap();
+
+        let world_delta = WorldDelta::read_from(&delta_bytes[..]).unwrap();
+        let delta_report = apply_delta(old_world_dir.path(), &world_delta).unwrap();
+
+        assert_eq!(
+            delta_report,
+            DeltaReport {
+                saved_chunks: 2,
+                deleted_chunks: 1,
+                written_files: 2,
+                deleted_files: 1,
+            }
+        );
+        assert!(compute_delta(old_world_dir.path(), new_world_dir.path())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_apply_delta_to_other_version() {
+        let old_world_dir = TempDir::new().unwrap();
+        let new_world_dir = TempDir::new().unwrap();
+        create_world(&old_world_dir);
+        snapshot(old_world_dir.path(), new_world_dir.path()).unwrap();
+
+        let region_folder = new_world_dir.path().join("region");
+        let chunk_provider = AnvilChunkProvider::new(region_folder.to_str().unwrap());
+        chunk_provider.save_chunk(4, 2, CompoundTag::new()).unwrap();
+        replace_file(&new_world_dir.path().join("level.dat"), b"changed");
+
+        let world_delta = compute_delta(old_world_dir.path(), new_world_dir.path()).unwrap();
+        assert_eq!(world_delta.operations.len(), 2);
+
+        // Delta was computed from old version, new version already has its changes.
+        match apply_delta(new_world_dir.path(), &world_delta) {
+            Err(DeltaError::BaseMismatch { .. }) => {}
+            result => panic!("Expected `BaseMismatch` but got `{:?}`", result),
+        }
+
+        assert_eq!(
+            fs::read(new_world_dir.path().join("level.dat")).unwrap(),
+            b"changed"
+        );
+    }
+
+    #[test]
+    fn test_read_invalid_delta() {
+        match WorldDelta::read_from(&b"ANVD\x01\x00\x00\x00\x01\x03\x00\x05../ab"[..]) {
+            Err(DeltaError::InvalidDelta { .. }) => {}
+            result => panic!("Expected `InvalidDelta` but got `{:?}`", result),
+        }
+
+        match WorldDelta::read_from(&b"PK\x03\x04\x01"[..]) {
+            Err(DeltaError::InvalidDelta { .. }) => {}
+            result => panic!("Expected `InvalidDelta` but got `{:?}`", result),
+        }
+    }
+}
@@ -0,0 +1,226 @@
+//! Structural comparison of chunks.
+//!
+//! Differences are reported with paths in syntax accepted by [`crate::path`], so the
+//! changed tag can be looked up or restored.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::diff::{diff, Difference};
+//! use anvil_region::snbt;
+//!
+//! let old = snbt::from_str("{Level: {InhabitedTime: 10L, Status: full}}").unwrap();
+//! let new = snbt::from_str("{Level: {InhabitedTime: 20L, Status: full}}").unwrap();
+//!
+//! let differences = diff(&old, &new);
+//!
+//! assert_eq!(differences.len(), 1);
+//! assert_eq!(differences[0].path(), "Level.InhabitedTime");
+//! ```
+use crate::path::{join_index, join_name};
+use crate::tag::get_tag;
+use nbt::{CompoundTag, Tag};
+
+/// Single difference between two compound tags.
+#[derive(Debug, Clone)]
+pub enum Difference {
+    /// Tag is present only in new compound.
+    Added { path: String, tag: Tag },
+    /// Tag is present only in old compound.
+    Removed { path: String, tag: Tag },
+    /// Tag value or type differs.
+    Changed {
+        path: String,
+        old_tag: Tag,
+        new_tag: Tag,
+    },
+}
+
+impl Difference {
+    /// Returns path to tag which differs.
+    pub fn path(&self) -> &str {
+        match self {
+            Difference::Added { path, .. } => path,
+            Difference::Removed { path, .. } => path,
+            Difference::Changed { path, .. } => path,
+        }
+    }
+}
+
+/// Returns differences between compound tags.
+///
+/// Nested compounds and lists are compared by element, so only deepest changed tags
+/// are reported. Arrays are reported as a whole. Within each compound or list removed
+/// tags go first, followed by changed and added tags in order of appearance.
+pub fn diff(old: &CompoundTag, new: &CompoundTag) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    diff_compound_tags("", old, new, &mut differences);
+
+    differences
+}
+
+/// Returns true if tags have same type and value.
+///
+/// Floating point values are compared bitwise so stored `NaN` equals itself.
+pub fn tags_equal(left: &Tag, right: &Tag) -> bool {
+    match (left, right) {
+        (Tag::Byte(left), Tag::Byte(right)) => left == right,
+        (Tag::Short(left), Tag::Short(right)) => left == right,
+        (Tag::Int(left), Tag::Int(right)) => left == right,
+        (Tag::Long(left), Tag::Long(right)) => left == right,
+        (Tag::Float(left), Tag::Float(right)) => left.to_bits() == right.to_bits(),
+        (Tag::Double(left), Tag::Double(right)) => left.to_bits() == right.to_bits(),
+        (Tag::ByteArray(left), Tag::ByteArray(right)) => left == right,
+        (Tag::String(left), Tag::String(right)) => left == right,
+        (Tag::IntArray(left), Tag::IntArray(right)) => left == right,
+        (Tag::LongArray(left), Tag::LongArray(right)) => left == right,
+        (Tag::List(left), Tag::List(right)) => {
+            left.len() == right.len()
+                && left
+                    .iter()
+                    .zip(right)
+                    .all(|(left, right)| tags_equal(left, right))
+        }
+        (Tag::Compound(left), Tag::Compound(right)) => compound_tags_equal(left, right),
+        _ => false,
+    }
+}
+
+/// Returns true if compound tags contain equal tags, order of tags is ignored.
+pub fn compound_tags_equal(left: &CompoundTag, right: &CompoundTag) -> bool {
+    left.iter().count() == right.iter().count()
+        && left.iter().all(|(name, left_tag)| {
+            get_tag(right, name).is_some_and(|right_tag| tags_equal(left_tag, right_tag))
+        })
+}
+
+fn diff_compound_tags(
+    path: &str,
+    old: &CompoundTag,
+    new: &CompoundTag,
+    differences: &mut Vec<Difference>,
+) {
+    for (name, old_tag) in old.iter() {
+        if get_tag(new, name).is_none() {
+            differences.push(Difference::Removed {
+                path: join_name(path, name),
+                tag: old_tag.clone(),
+            });
+        }
+    }
+
+    for (name, new_tag) in new.iter() {
+        let path = join_name(path, name);
+
+        match get_tag(old, name) {
+            Some(old_tag) => diff_tags(path, old_tag, new_tag, differences),
+            None => differences.push(Difference::Added {
+                path,
+                tag: new_tag.clone(),
+            }),
+        }
+    }
+}
+
+fn diff_tags(path: String, old_tag: &Tag, new_tag: &Tag, differences: &mut Vec<Difference>) {
+    match (old_tag, new_tag) {
+        (Tag::Compound(old), Tag::Compound(new)) => {
+            diff_compound_tags(&path, old, new, differences)
+        }
+        (Tag::List(old), Tag::List(new)) => {
+            for (index, old_tag) in old.iter().enumerate().skip(new.len()) {
+                differences.push(Difference::Removed {
+                    path: join_index(&path, index),
+                    tag: old_tag.clone(),
+                });
+            }
+
+            for (index, new_tag) in new.iter().enumerate() {
+                let path = join_index(&path, index);
+
+                match old.get(index) {
+                    Some(old_tag) => diff_tags(path, old_tag, new_tag, differences),
+                    None => differences.push(Difference::Added {
+                        path,
+                        tag: new_tag.clone(),
+                    }),
+                }
+            }
+        }
+        _ if !tags_equal(old_tag, new_tag) => differences.push(Difference::Changed {
+            path,
+            old_tag: old_tag.clone(),
+            new_tag: new_tag.clone(),
+        }),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diff::{compound_tags_equal, diff, Difference};
+    use crate::snbt;
+    use crate::AnvilChunkProvider;
+
+    #[test]
+    fn test_diff() {
+        let old = snbt::from_str(r#"{a: 1, b: {c: 2b, d: [1, 2, 3]}, e: [I; 1], f: 1}"#).unwrap();
+        let new = snbt::from_str(r#"{a: 1, b: {c: 3b, d: [1, 5]}, e: [I; 2], g: 1}"#).unwrap();
+
+        let differences: Vec<_> = diff(&old, &new)
+            .iter()
+            .map(|difference| match difference {
+                Difference::Added { path, tag } => {
+                    format!("+{} {}", path, snbt::tag_to_string(tag))
+                }
+                Difference::Removed { path, tag } => {
+                    format!("-{} {}", path, snbt::tag_to_string(tag))
+                }
+                Difference::Changed {
+                    path,
+                    old_tag,
+                    new_tag,
+                } => format!(
+                    "~{} {} {}",
+                    path,
+                    snbt::tag_to_string(old_tag),
+                    snbt::tag_to_string(new_tag)
+                ),
+            })
+            .collect();
+
+        assert_eq!(
+            differences,
+            vec![
+                "-f 1",
+                "~b.c 2b 3b",
+                "-b.d[2] 3",
+                "~b.d[1] 2 5",
+                "~e [I;1] [I;2]",
+                "+g 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_same_chunk() {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk_compound_tag = chunk_provider.load_chunk(4, 2).unwrap();
+
+        assert!(diff(&chunk_compound_tag, &chunk_compound_tag.clone()).is_empty());
+        assert!(compound_tags_equal(
+            &chunk_compound_tag,
+            &chunk_compound_tag.clone()
+        ));
+    }
+
+    #[test]
+    fn test_compound_tags_equal_ignores_order() {
+        let left = snbt::from_str("{a: 1, b: 2.0f}").unwrap();
+        let right = snbt::from_str("{b: 2.0f, a: 1}").unwrap();
+        let other = snbt::from_str("{b: 2.0f, a: 1b}").unwrap();
+
+        assert!(compound_tags_equal(&left, &right));
+        assert!(!compound_tags_equal(&left, &other));
+    }
+}
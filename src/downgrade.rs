@@ -0,0 +1,639 @@
+//! Best effort chunk downgrades to older formats.
+//!
+//! Downgrade reverts upgrade steps in descending order of data version. Data which
+//! cannot be represented in the target format is replaced or dropped and recorded
+//! in the report, so callers can decide whether result is acceptable. Ids renamed
+//! since the target version get their old names back.
+//!
+//! Supported targets are 1.14 and newer.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::downgrade::downgrade_chunk;
+//! use anvil_region::upgrade::upgrade_chunk;
+//! use anvil_region::AnvilChunkProvider;
+//!
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//! let mut chunk_compound_tag = chunk_provider.load_chunk(4, 2).unwrap();
+//!
+//! upgrade_chunk(&mut chunk_compound_tag, 2860).unwrap();
+//! let downgrade_report = downgrade_chunk(&mut chunk_compound_tag, 2586).unwrap();
+//!
+//! assert!(downgrade_report.is_lossless());
+//! assert!(chunk_compound_tag.contains_key("Level"));
+//! ```
+use crate::packed::{pack, palette_bits, unpack};
+use crate::remap::{apply_remap, IdRemap};
+use crate::section::{SECTION_BIOMES, SECTION_BLOCKS};
+use crate::tag::{compound_tags_mut, get_tag_mut, remove_tag, rename_tag};
+use crate::upgrade::legacy::biome_id;
+use crate::upgrade::{HEIGHTMAP_BITS, HEIGHTMAP_COLUMNS};
+use crate::version::{
+    chunk_data_version, BIOMES_3D_DATA_VERSION, CAVES_AND_CLIFFS_DATA_VERSION,
+    CHUNK_STATUS_RENAME_DATA_VERSION, LEVEL_WRAPPER_REMOVAL_DATA_VERSION,
+    PADDED_PACKED_ARRAYS_DATA_VERSION,
+};
+use nbt::{CompoundTag, CompoundTagError, Tag};
+
+/// Amount of sections in world 256 blocks high.
+const LEGACY_SECTIONS: i32 = 16;
+
+/// Possible errors while downgrading the chunk.
+#[derive(Debug)]
+pub enum ChunkDowngradeError {
+    /// Chunk was written by older version than downgrade target.
+    OlderDataVersion {
+        /// Chunk data version.
+        data_version: i32,
+        /// Downgrade target data version.
+        target_data_version: i32,
+    },
+    /// Downgrade to target data version is not supported.
+    UnsupportedTargetDataVersion {
+        /// Downgrade target data version.
+        target_data_version: i32,
+    },
+    /// Tag required by downgrade step is missing or has unexpected type.
+    InvalidTag {
+        /// Tag name.
+        name: String,
+    },
+}
+
+impl From<CompoundTagError<'_>> for ChunkDowngradeError {
+    fn from(compound_tag_error: CompoundTagError) -> Self {
+        let name = match compound_tag_error {
+            CompoundTagError::TagNotFound { name } => name,
+            CompoundTagError::TagWrongType { name, .. } => name,
+        };
+
+        ChunkDowngradeError::InvalidTag {
+            name: name.to_owned(),
+        }
+    }
+}
+
+/// Information which cannot be represented in the target format.
+#[derive(Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DowngradeReport {
+    /// Y of sections outside of target world height which blocks and biomes were dropped.
+    pub dropped_sections: Vec<i8>,
+    /// Block states unknown to target version and their replacements.
+    pub replaced_block_states: Vec<(String, String)>,
+    /// Biomes unknown to target version and their replacements.
+    pub replaced_biomes: Vec<(String, String)>,
+    /// Whether biomes lost vertical variation.
+    pub flattened_biomes: bool,
+}
+
+impl DowngradeReport {
+    /// Returns true if nothing was lost during downgrade.
+    pub fn is_lossless(&self) -> bool {
+        *self == DowngradeReport::default()
+    }
+
+    fn replaced_block_state(&mut self, name: &str, replacement: &str) {
+        let replaced_block_state = (name.to_owned(), replacement.to_owned());
+
+        if !self.replaced_block_states.contains(&replaced_block_state) {
+            self.replaced_block_states.push(replaced_block_state);
+        }
+    }
+
+    fn replaced_biome(&mut self, name: &str, replacement: &str) {
+        let replaced_biome = (name.to_owned(), replacement.to_owned());
+
+        if !self.replaced_biomes.contains(&replaced_biome) {
+            self.replaced_biomes.push(replaced_biome);
+        }
+    }
+}
+
+/// Step which converts chunk from the format of specified data version to the previous one.
+struct DowngradeStep {
+    /// Data version which introduced format.
+    data_version: i32,
+    /// Converts chunk to the previous format.
+    downgrade: fn(&mut CompoundTag, &mut DowngradeReport) -> Result<(), ChunkDowngradeError>,
+}
+
+/// Downgrade steps in descending order of data version.
+const DOWNGRADE_STEPS: &[DowngradeStep] = &[
+    DowngradeStep {
+        data_version: LEVEL_WRAPPER_REMOVAL_DATA_VERSION,
+        downgrade: restore_level_wrapper,
+    },
+    DowngradeStep {
+        data_version: CAVES_AND_CLIFFS_DATA_VERSION,
+        downgrade: replace_caves_and_cliffs_blocks,
+    },
+    DowngradeStep {
+        data_version: PADDED_PACKED_ARRAYS_DATA_VERSION,
+        downgrade: span_packed_arrays,
+    },
+    DowngradeStep {
+        data_version: BIOMES_3D_DATA_VERSION,
+        downgrade: flatten_biomes,
+    },
+];
+
+/// Downgrades chunk to the format of target data version.
+pub fn downgrade_chunk(
+    chunk_compound_tag: &mut CompoundTag,
+    target_data_version: i32,
+) -> Result<DowngradeReport, ChunkDowngradeError> {
+    let data_version = chunk_data_version(chunk_compound_tag).unwrap_or(0);
+    let mut report = DowngradeReport::default();
+
+    if target_data_version < CHUNK_STATUS_RENAME_DATA_VERSION {
+        return Err(ChunkDowngradeError::UnsupportedTargetDataVersion {
+            target_data_version,
+        });
+    }
+
+    if data_version < target_data_version {
+        return Err(ChunkDowngradeError::OlderDataVersion {
+            data_version,
+            target_data_version,
+        });
+    }
+
+    if data_version == target_data_version {
+        return Ok(report);
+    }
+
+    for step in DOWNGRADE_STEPS {
+        if step.data_version <= data_version && step.data_version > target_data_version {
+            (step.downgrade)(chunk_compound_tag, &mut report)?;
+        }
+    }
+
+    apply_remap(
+        chunk_compound_tag,
+        &IdRemap::between(data_version, target_data_version),
+    );
+    chunk_compound_tag.insert_i32("DataVersion", target_data_version);
+
+    Ok(report)
+}
+
+/// Moves chunk tags back under `Level` and biomes out of sections.
+fn restore_level_wrapper(
+    chunk_compound_tag: &mut CompoundTag,
+    report: &mut DowngradeReport,
+) -> Result<(), ChunkDowngradeError> {
+    let data_version = chunk_compound_tag.get_i32("DataVersion")?;
+    let root = std::mem::replace(chunk_compound_tag, CompoundTag::new());
+    let mut level = CompoundTag::new();
+
+    for (name, tag) in root {
+        if name != "DataVersion" {
+            level.insert(name, tag);
+        }
+    }
+
+    let mut biomes = vec![1; LEGACY_SECTIONS as usize * SECTION_BIOMES];
+
+    for section in compound_tags_mut(&mut level, "sections") {
+        let section_y = section.get_i8("Y").unwrap_or(0);
+        let in_range = (0..LEGACY_SECTIONS).contains(&(section_y as i32));
+
+        let (palette, data) = match remove_tag(section, "block_states") {
+            Some(Tag::Compound(mut block_states)) => {
+                let palette = match remove_tag(&mut block_states, "palette") {
+                    Some(Tag::List(palette)) => palette,
+                    _ => Vec::new(),
+                };
+
+                let data = match remove_tag(&mut block_states, "data") {
+                    Some(Tag::LongArray(data)) => data,
+                    _ => Vec::new(),
+                };
+
+                (palette, data)
+            }
+            _ => (Vec::new(), Vec::new()),
+        };
+
+        let section_biomes = remove_tag(section, "biomes");
+
+        if !in_range {
+            let only_air = palette.iter().all(|block_state| match block_state {
+                Tag::Compound(block_state) => {
+                    block_state.get_str("Name").unwrap_or_default() == "minecraft:air"
+                }
+                _ => true,
+            });
+
+            if !only_air {
+                report.dropped_sections.push(section_y);
+            }
+
+            continue;
+        }
+
+        if !palette.is_empty() {
+            let data = if palette.len() == 1 {
+                pack(&[0; SECTION_BLOCKS], 4, false)
+            } else {
+                data
+            };
+
+            section.insert("Palette", palette);
+            section.insert_i64_vec("BlockStates", data);
+        }
+
+        if let Some(Tag::Compound(section_biomes)) = section_biomes {
+            let offset = section_y as usize * SECTION_BIOMES;
+            let section_biome_ids = section_biome_ids(&section_biomes, report);
+
+            biomes[offset..offset + SECTION_BIOMES].copy_from_slice(&section_biome_ids);
+        }
+    }
+
+    if let Some(Tag::List(sections)) = get_tag_mut(&mut level, "sections") {
+        sections.retain(|section| match section {
+            Tag::Compound(section) => {
+                (0..LEGACY_SECTIONS).contains(&(section.get_i8("Y").unwrap_or(0) as i32))
+                    || section.contains_key("BlockLight")
+                    || section.contains_key("SkyLight")
+            }
+            _ => false,
+        });
+    }
+
+    if let Ok(structures) = level.get_mut::<&mut CompoundTag>("structures") {
+        rename_tag(structures, "starts", "Starts");
+    }
+
+    for (from, to) in &[
+        ("sections", "Sections"),
+        ("block_entities", "TileEntities"),
+        ("block_ticks", "TileTicks"),
+        ("fluid_ticks", "LiquidTicks"),
+        ("structures", "Structures"),
+    ] {
+        rename_tag(&mut level, from, to);
+    }
+
+    let status = level
+        .get_str("Status")
+        .ok()
+        .map(|status| status.trim_start_matches("minecraft:").to_owned());
+
+    if let Some(status) = status {
+        level.insert_str("Status", status);
+    }
+
+    // Heightmaps are relative to the world bottom which changed, game recomputes them.
+    remove_tag(&mut level, "Heightmaps");
+    remove_tag(&mut level, "yPos");
+
+    level.insert_i32_vec("Biomes", biomes);
+
+    chunk_compound_tag.insert_i32("DataVersion", data_version);
+    chunk_compound_tag.insert_compound_tag("Level", level);
+
+    Ok(())
+}
+
+/// Returns legacy biome ids of section biome cells.
+fn section_biome_ids(section_biomes: &CompoundTag, report: &mut DowngradeReport) -> Vec<i32> {
+    let palette = section_biomes.get_str_vec("palette").unwrap_or_default();
+    let bits = palette_bits(palette.len(), 0);
+    let empty_data = Vec::new();
+    let data = section_biomes.get_i64_vec("data").unwrap_or(&empty_data);
+
+    let palette_ids: Vec<i32> = palette
+        .iter()
+        .map(|name| match biome_id(name) {
+            Some(id) => id,
+            None => {
+                let replacement = biome_replacement(name);
+                report.replaced_biome(name, replacement);
+
+                biome_id(replacement).unwrap_or(1)
+            }
+        })
+        .collect();
+
+    unpack(data, bits, SECTION_BIOMES, false)
+        .into_iter()
+        .map(|index| palette_ids.get(index as usize).copied().unwrap_or(1))
+        .collect()
+}
+
+/// Returns similar biome for biomes introduced since 1.18.
+fn biome_replacement(name: &str) -> &'static str {
+    match name {
+        "minecraft:grove" => "minecraft:snowy_taiga",
+        "minecraft:snowy_slopes" | "minecraft:frozen_peaks" => "minecraft:snowy_plains",
+        "minecraft:jagged_peaks" | "minecraft:stony_peaks" => "minecraft:windswept_hills",
+        "minecraft:mangrove_swamp" => "minecraft:swamp",
+        "minecraft:pale_garden" => "minecraft:dark_forest",
+        _ => "minecraft:plains",
+    }
+}
+
+/// Replaces blocks which were introduced in 1.17.
+fn replace_caves_and_cliffs_blocks(
+    chunk_compound_tag: &mut CompoundTag,
+    report: &mut DowngradeReport,
+) -> Result<(), ChunkDowngradeError> {
+    let level = chunk_compound_tag.get_mut::<&mut CompoundTag>("Level")?;
+
+    for section in compound_tags_mut(level, "Sections") {
+        for block_state in compound_tags_mut(section, "Palette") {
+            let name = block_state.get_str("Name").unwrap_or_default().to_owned();
+
+            if let Some(replacement) = caves_and_cliffs_block_replacement(&name) {
+                report.replaced_block_state(&name, &replacement);
+
+                *block_state = CompoundTag::new();
+                block_state.insert_str("Name", replacement);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns replacement for block introduced in 1.17.
+fn caves_and_cliffs_block_replacement(name: &str) -> Option<String> {
+    let name = name.strip_prefix("minecraft:")?;
+
+    if let Some(ore) = name.strip_prefix("deepslate_") {
+        if ore.ends_with("_ore") && ore != "copper_ore" {
+            return Some(format!("minecraft:{}", ore));
+        }
+    }
+
+    let replacement = match name {
+        "rooted_dirt" => "dirt",
+        "moss_block" => "grass_block",
+        "tinted_glass" => "glass",
+        "powder_snow" => "snow_block",
+        "azalea_leaves" | "flowering_azalea_leaves" => "oak_leaves",
+        "water_cauldron" | "lava_cauldron" | "powder_snow_cauldron" => "cauldron",
+        "glow_lichen"
+        | "hanging_roots"
+        | "spore_blossom"
+        | "cave_vines"
+        | "cave_vines_plant"
+        | "small_dripleaf"
+        | "big_dripleaf"
+        | "big_dripleaf_stem"
+        | "pointed_dripstone"
+        | "amethyst_cluster"
+        | "large_amethyst_bud"
+        | "medium_amethyst_bud"
+        | "small_amethyst_bud"
+        | "moss_carpet"
+        | "azalea"
+        | "flowering_azalea"
+        | "light"
+        | "lightning_rod"
+        | "sculk_sensor"
+        | "candle" => "air",
+        "tuff" | "calcite" | "smooth_basalt" | "dripstone_block" | "amethyst_block"
+        | "budding_amethyst" | "raw_iron_block" | "raw_copper_block" | "raw_gold_block" => "stone",
+        _ if name.ends_with("candle_cake") => "cake",
+        _ if name.ends_with("_candle") => "air",
+        _ if name.contains("deepslate") || name.contains("copper") => "stone",
+        _ => return None,
+    };
+
+    Some(format!("minecraft:{}", replacement))
+}
+
+/// Repacks block states and heightmaps so values can span across longs.
+fn span_packed_arrays(
+    chunk_compound_tag: &mut CompoundTag,
+    _report: &mut DowngradeReport,
+) -> Result<(), ChunkDowngradeError> {
+    let level = chunk_compound_tag.get_mut::<&mut CompoundTag>("Level")?;
+
+    for section in compound_tags_mut(level, "Sections") {
+        let palette_length = match section.get_compound_tag_vec("Palette") {
+            Ok(palette) => palette.len(),
+            Err(_) => continue,
+        };
+
+        let bits = palette_bits(palette_length, 4);
+
+        if let Some(Tag::LongArray(block_states)) = get_tag_mut(section, "BlockStates") {
+            let indices = unpack(block_states, bits, SECTION_BLOCKS, false);
+            *block_states = pack(&indices, bits, true);
+        }
+    }
+
+    if let Ok(heightmaps) = level.get_mut::<&mut CompoundTag>("Heightmaps") {
+        for (_, heightmap) in heightmaps.iter_mut() {
+            if let Tag::LongArray(heightmap) = heightmap {
+                let heights = unpack(heightmap, HEIGHTMAP_BITS, HEIGHTMAP_COLUMNS, false);
+                *heightmap = pack(&heights, HEIGHTMAP_BITS, true);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Converts biome per 4x4x4 cell to biome per column.
+fn flatten_biomes(
+    chunk_compound_tag: &mut CompoundTag,
+    report: &mut DowngradeReport,
+) -> Result<(), ChunkDowngradeError> {
+    let level = chunk_compound_tag.get_mut::<&mut CompoundTag>("Level")?;
+
+    let biomes = match level.get_i32_vec("Biomes") {
+        Ok(biomes) if biomes.len() >= 1024 => biomes.clone(),
+        _ => return Ok(()),
+    };
+
+    // Biomes around sea level are the ones which are visible on surface.
+    let surface_layer = &biomes[16 * 16..17 * 16];
+    let mut biomes_2d = Vec::with_capacity(256);
+
+    for z in 0..16 {
+        for x in 0..16 {
+            biomes_2d.push(surface_layer[(z / 4) * 4 + x / 4]);
+        }
+    }
+
+    report.flattened_biomes |= biomes.chunks(16).any(|layer| layer != surface_layer);
+
+    level.insert_i32_vec("Biomes", biomes_2d);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::downgrade::{downgrade_chunk, ChunkDowngradeError};
+    use crate::packed::unpack;
+    use crate::upgrade::upgrade_chunk;
+    use crate::AnvilChunkProvider;
+    use nbt::CompoundTag;
+
+    fn modern_chunk() -> CompoundTag {
+        let mut deepslate = CompoundTag::new();
+        deepslate.insert_str("Name", "minecraft:deepslate");
+
+        let mut deepslate_ore = CompoundTag::new();
+        deepslate_ore.insert_str("Name", "minecraft:deepslate_iron_ore");
+
+        let mut section = CompoundTag::new();
+        let mut block_states = CompoundTag::new();
+        let mut biomes = CompoundTag::new();
+
+        block_states.insert_compound_tag_vec("palette", vec![deepslate]);
+        biomes.insert_str_vec("palette", vec!["minecraft:meadow"]);
+
+        section.insert_i8("Y", -1);
+        section.insert_compound_tag("block_states", block_states.clone());
+        section.insert_compound_tag("biomes", biomes.clone());
+
+        let mut surface_section = CompoundTag::new();
+        let mut surface_block_states = CompoundTag::new();
+        surface_block_states.insert_compound_tag_vec("palette", vec![deepslate_ore]);
+
+        surface_section.insert_i8("Y", 0);
+        surface_section.insert_compound_tag("block_states", surface_block_states);
+        surface_section.insert_compound_tag("biomes", biomes);
+
+        let mut chunk_compound_tag = CompoundTag::new();
+        chunk_compound_tag.insert_i32("DataVersion", 2860);
+        chunk_compound_tag.insert_i32("xPos", 3);
+        chunk_compound_tag.insert_i32("yPos", -4);
+        chunk_compound_tag.insert_i32("zPos", 5);
+        chunk_compound_tag.insert_str("Status", "minecraft:full");
+        chunk_compound_tag.insert_compound_tag_vec("sections", vec![section, surface_section]);
+        chunk_compound_tag.insert_compound_tag_vec("block_entities", Vec::new());
+
+        chunk_compound_tag
+    }
+
+    #[test]
+    fn test_downgrade_to_1_17() {
+        let mut chunk_compound_tag = modern_chunk();
+        let report = downgrade_chunk(&mut chunk_compound_tag, 2730).unwrap();
+
+        assert_eq!(report.dropped_sections, vec![-1]);
+        assert_eq!(
+            report.replaced_biomes,
+            vec![("minecraft:meadow".to_owned(), "minecraft:plains".to_owned())]
+        );
+        assert!(report.replaced_block_states.is_empty());
+
+        let level = chunk_compound_tag.get_compound_tag("Level").unwrap();
+
+        assert_eq!(chunk_compound_tag.get_i32("DataVersion").unwrap(), 2730);
+        assert_eq!(level.get_i32("xPos").unwrap(), 3);
+        assert_eq!(level.get_str("Status").unwrap(), "full");
+        assert!(level.contains_key("TileEntities"));
+        assert!(!level.contains_key("yPos"));
+        assert_eq!(level.get_i32_vec("Biomes").unwrap().len(), 1024);
+
+        let sections = level.get_compound_tag_vec("Sections").unwrap();
+
+        assert_eq!(sections.len(), 1);
+        assert_eq!(sections[0].get_i64_vec("BlockStates").unwrap().len(), 256);
+    }
+
+    #[test]
+    fn test_downgrade_to_1_16() {
+        let mut chunk_compound_tag = modern_chunk();
+        let report = downgrade_chunk(&mut chunk_compound_tag, 2586).unwrap();
+
+        assert_eq!(
+            report.replaced_block_states,
+            vec![(
+                "minecraft:deepslate_iron_ore".to_owned(),
+                "minecraft:iron_ore".to_owned()
+            )]
+        );
+        assert!(!report.flattened_biomes);
+
+        let level = chunk_compound_tag.get_compound_tag("Level").unwrap();
+        let section = level.get_compound_tag_vec("Sections").unwrap()[0];
+        let palette = section.get_compound_tag_vec("Palette").unwrap();
+
+        assert_eq!(palette[0].get_str("Name").unwrap(), "minecraft:iron_ore");
+        assert_eq!(level.get_i32_vec("Biomes").unwrap().len(), 1024);
+    }
+
+    #[test]
+    fn test_downgrade_to_1_14() {
+        let mut chunk_compound_tag = modern_chunk();
+        let report = downgrade_chunk(&mut chunk_compound_tag, 1976).unwrap();
+
+        let level = chunk_compound_tag.get_compound_tag("Level").unwrap();
+
+        assert!(!report.flattened_biomes);
+        assert!(!report.is_lossless());
+        assert_eq!(level.get_i32_vec("Biomes").unwrap().len(), 256);
+    }
+
+    #[test]
+    fn test_downgrade_roundtrip_fixture() {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+        let original_compound_tag = chunk_provider.load_chunk(4, 2).unwrap();
+        let mut chunk_compound_tag = original_compound_tag.clone();
+
+        upgrade_chunk(&mut chunk_compound_tag, 2860).unwrap();
+        let report = downgrade_chunk(&mut chunk_compound_tag, 1976).unwrap();
+
+        assert!(report.dropped_sections.is_empty());
+
+        let original_level = original_compound_tag.get_compound_tag("Level").unwrap();
+        let level = chunk_compound_tag.get_compound_tag("Level").unwrap();
+
+        let original_section = original_level.get_compound_tag_vec("Sections").unwrap()[0];
+        let section = level.get_compound_tag_vec("Sections").unwrap()[0];
+
+        let bits = 4;
+        let original_indices = unpack(
+            original_section.get_i64_vec("BlockStates").unwrap(),
+            bits,
+            4096,
+            true,
+        );
+        let indices = unpack(
+            section.get_i64_vec("BlockStates").unwrap(),
+            bits,
+            4096,
+            true,
+        );
+
+        assert_eq!(indices, original_indices);
+    }
+
+    #[test]
+    fn test_downgrade_older_data_version() {
+        let mut chunk_compound_tag = CompoundTag::new();
+        chunk_compound_tag.insert_i32("DataVersion", 1976);
+
+        match downgrade_chunk(&mut chunk_compound_tag, 2586) {
+            Err(ChunkDowngradeError::OlderDataVersion { .. }) => {}
+            result => panic!("Expected `OlderDataVersion` but got `{:?}`", result),
+        }
+    }
+
+    #[test]
+    fn test_downgrade_unsupported_target() {
+        let mut chunk_compound_tag = modern_chunk();
+
+        match downgrade_chunk(&mut chunk_compound_tag, 1343) {
+            Err(ChunkDowngradeError::UnsupportedTargetDataVersion {
+                target_data_version,
+            }) => assert_eq!(target_data_version, 1343),
+            result => panic!(
+                "Expected `UnsupportedTargetDataVersion` but got `{:?}`",
+                result
+            ),
+        }
+    }
+}
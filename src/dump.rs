@@ -0,0 +1,590 @@
+//! Dump of chunks into JSON or SNBT text files and import of them back.
+//!
+//! Every chunk becomes own file `r.x.z/c.x.z.json` or `r.x.z/c.x.z.snbt`, so chunks
+//! can be processed with `jq` or scripts and imported back into region files.
+//!
+//! JSON has no numeric types, so tags are encoded losslessly: compounds are objects,
+//! lists are arrays, ints are integers, doubles are numbers with fraction and strings
+//! are strings. Other tags are objects with single key of their type, for example
+//! `{"$byte": 1}`, `{"$long": 5}` or `{"$int_array": [1, 2]}`. Compound keys which
+//! start with `$` are written with additional `$`.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::dump::{dump_provider, import_provider, DumpFormat};
+//! use anvil_region::relocate::copy_chunk;
+//! use anvil_region::AnvilChunkProvider;
+//! use tempfile::TempDir;
+//!
+//! let region_dir = TempDir::new().unwrap();
+//! let dump_dir = TempDir::new().unwrap();
+//! let target_region_dir = TempDir::new().unwrap();
+//!
+//! let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+//! let chunk_provider = AnvilChunkProvider::new(region_dir.path().to_str().unwrap());
+//! copy_chunk(&fixture_chunk_provider, (4, 2), &chunk_provider, (4, 2)).unwrap();
+//!
+//! assert_eq!(dump_provider(&chunk_provider, dump_dir.path(), DumpFormat::Json).unwrap(), 1);
+//! assert!(dump_dir.path().join("r.0.0/c.4.2.json").exists());
+//!
+//! let target_chunk_provider = AnvilChunkProvider::new(target_region_dir.path().to_str().unwrap());
+//! assert_eq!(import_provider(dump_dir.path(), &target_chunk_provider).unwrap(), 1);
+//! assert!(target_chunk_provider.load_chunk(4, 2).is_ok());
+//! ```
+use crate::json::{self, JsonError, JsonValue};
+use crate::snbt::{self, SnbtError};
+use crate::{region_position, AnvilChunkProvider, ChunkLoadError, ChunkSaveError};
+use nbt::{CompoundTag, Tag};
+use std::convert::TryFrom;
+use std::path::Path;
+use std::{fs, io};
+
+/// Possible errors while dumping or importing chunks.
+#[derive(Debug)]
+pub enum DumpError {
+    /// Chunk can't be loaded.
+    ChunkLoadError { chunk_load_error: ChunkLoadError },
+    /// Chunk can't be saved.
+    ChunkSaveError { chunk_save_error: ChunkSaveError },
+    /// File can't be read or written.
+    IoError { io_error: io::Error },
+    /// Dumped file is not valid JSON.
+    JsonError { json_error: JsonError },
+    /// Dumped file is not valid SNBT.
+    SnbtError { snbt_error: SnbtError },
+    /// JSON value at path can't be converted into tag.
+    InvalidJsonValue { path: String },
+}
+
+impl From<ChunkLoadError> for DumpError {
+    fn from(chunk_load_error: ChunkLoadError) -> Self {
+        DumpError::ChunkLoadError { chunk_load_error }
+    }
+}
+
+impl From<ChunkSaveError> for DumpError {
+    fn from(chunk_save_error: ChunkSaveError) -> Self {
+        DumpError::ChunkSaveError { chunk_save_error }
+    }
+}
+
+impl From<io::Error> for DumpError {
+    fn from(io_error: io::Error) -> Self {
+        DumpError::IoError { io_error }
+    }
+}
+
+impl From<JsonError> for DumpError {
+    fn from(json_error: JsonError) -> Self {
+        DumpError::JsonError { json_error }
+    }
+}
+
+impl From<SnbtError> for DumpError {
+    fn from(snbt_error: SnbtError) -> Self {
+        DumpError::SnbtError { snbt_error }
+    }
+}
+
+/// Text format of dumped chunks.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum DumpFormat {
+    Json,
+    Snbt,
+}
+
+impl DumpFormat {
+    /// Extension of dumped chunk files.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DumpFormat::Json => "json",
+            DumpFormat::Snbt => "snbt",
+        }
+    }
+
+    fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "json" => Some(DumpFormat::Json),
+            "snbt" => Some(DumpFormat::Snbt),
+            _ => None,
+        }
+    }
+
+    fn render(self, compound_tag: &CompoundTag) -> String {
+        match self {
+            DumpFormat::Json => json::to_string_pretty(&compound_tag_to_json(compound_tag)),
+            DumpFormat::Snbt => snbt::to_string_pretty(compound_tag),
+        }
+    }
+
+    fn parse(self, text: &str) -> Result<CompoundTag, DumpError> {
+        match self {
+            DumpFormat::Json => json_to_compound_tag(&json::from_str(text)?),
+            DumpFormat::Snbt => Ok(snbt::from_str(text)?),
+        }
+    }
+}
+
+/// Converts compound tag into JSON object which converts back without losing types.
+pub fn compound_tag_to_json(compound_tag: &CompoundTag) -> JsonValue {
+    let entries = compound_tag
+        .iter()
+        .map(|(name, tag)| {
+            let key = if name.starts_with('$') {
+                format!("${}", name)
+            } else {
+                name.clone()
+            };
+
+            (key, tag_to_json(tag))
+        })
+        .collect();
+
+    JsonValue::Object(entries)
+}
+
+/// Converts tag into JSON value which converts back without losing type.
+pub fn tag_to_json(tag: &Tag) -> JsonValue {
+    match tag {
+        Tag::Byte(value) => typed_value("byte", JsonValue::Integer(*value as i64)),
+        Tag::Short(value) => typed_value("short", JsonValue::Integer(*value as i64)),
+        Tag::Int(value) => JsonValue::Integer(*value as i64),
+        Tag::Long(value) => typed_value("long", JsonValue::Integer(*value)),
+        Tag::Float(value) => typed_value("float", float_to_json(*value as f64)),
+        Tag::Double(value) if value.is_finite() => JsonValue::Float(*value),
+        Tag::Double(value) => typed_value("double", float_to_json(*value)),
+        Tag::ByteArray(values) => typed_value(
+            "byte_array",
+            JsonValue::Array(
+                values
+                    .iter()
+                    .map(|v| JsonValue::Integer(*v as i64))
+                    .collect(),
+            ),
+        ),
+        Tag::String(value) => JsonValue::String(value.clone()),
+        Tag::List(tags) => JsonValue::Array(tags.iter().map(tag_to_json).collect()),
+        Tag::Compound(compound_tag) => compound_tag_to_json(compound_tag),
+        Tag::IntArray(values) => typed_value(
+            "int_array",
+            JsonValue::Array(
+                values
+                    .iter()
+                    .map(|v| JsonValue::Integer(*v as i64))
+                    .collect(),
+            ),
+        ),
+        Tag::LongArray(values) => typed_value(
+            "long_array",
+            JsonValue::Array(values.iter().map(|v| JsonValue::Integer(*v)).collect()),
+        ),
+    }
+}
+
+fn typed_value(type_name: &str, json_value: JsonValue) -> JsonValue {
+    JsonValue::Object(vec![(format!("${}", type_name), json_value)])
+}
+
+/// JSON has no infinity and NaN, so they are written as strings.
+fn float_to_json(value: f64) -> JsonValue {
+    if value.is_nan() {
+        JsonValue::String("NaN".to_owned())
+    } else if value.is_infinite() && value > 0.0 {
+        JsonValue::String("Infinity".to_owned())
+    } else if value.is_infinite() {
+        JsonValue::String("-Infinity".to_owned())
+    } else {
+        JsonValue::Float(value)
+    }
+}
+
+/// Converts JSON object written by [`compound_tag_to_json`] back into compound tag.
+pub fn json_to_compound_tag(json_value: &JsonValue) -> Result<CompoundTag, DumpError> {
+    match json_to_tag(json_value)? {
+        Tag::Compound(compound_tag) => Ok(compound_tag),
+        _ => Err(DumpError::InvalidJsonValue {
+            path: String::new(),
+        }),
+    }
+}
+
+/// Converts JSON value written by [`tag_to_json`] back into tag.
+pub fn json_to_tag(json_value: &JsonValue) -> Result<Tag, DumpError> {
+    json_to_tag_at_path(json_value, "")
+}
+
+/// Converts JSON value, `path` of value is reported when it's invalid.
+fn json_to_tag_at_path(json_value: &JsonValue, path: &str) -> Result<Tag, DumpError> {
+    let invalid = || DumpError::InvalidJsonValue {
+        path: path.to_owned(),
+    };
+
+    match json_value {
+        JsonValue::Integer(value) => i32::try_from(*value).map(Tag::Int).map_err(|_| invalid()),
+        JsonValue::Float(value) => Ok(Tag::Double(*value)),
+        JsonValue::String(value) => Ok(Tag::String(value.clone())),
+        JsonValue::Array(values) => {
+            let mut tags = Vec::with_capacity(values.len());
+
+            for (index, value) in values.iter().enumerate() {
+                let tag = json_to_tag_at_path(value, &format!("{}[{}]", path, index))?;
+
+                if let Some(first_tag) = tags.first() {
+                    if std::mem::discriminant(first_tag) != std::mem::discriminant(&tag) {
+                        return Err(invalid());
+                    }
+                }
+
+                tags.push(tag);
+            }
+
+            Ok(Tag::List(tags))
+        }
+        JsonValue::Object(entries) => match entries.as_slice() {
+            [(key, value)] if key.starts_with('$') && !key.starts_with("$$") => {
+                typed_json_to_tag(&key[1..], value).ok_or_else(invalid)
+            }
+            _ => {
+                let mut compound_tag = CompoundTag::new();
+
+                for (key, value) in entries {
+                    let name = key.strip_prefix('$').unwrap_or(key);
+                    let tag_path = if path.is_empty() {
+                        name.to_owned()
+                    } else {
+                        format!("{}.{}", path, name)
+                    };
+
+                    compound_tag.insert(name, json_to_tag_at_path(value, &tag_path)?);
+                }
+
+                Ok(Tag::Compound(compound_tag))
+            }
+        },
+        JsonValue::Null | JsonValue::Bool(_) => Err(invalid()),
+    }
+}
+
+fn typed_json_to_tag(type_name: &str, json_value: &JsonValue) -> Option<Tag> {
+    let integers = || -> Option<Vec<i64>> {
+        match json_value {
+            JsonValue::Array(values) => values.iter().map(JsonValue::as_i64).collect(),
+            _ => None,
+        }
+    };
+
+    let tag = match type_name {
+        "byte" => Tag::Byte(i8::try_from(json_value.as_i64()?).ok()?),
+        "short" => Tag::Short(i16::try_from(json_value.as_i64()?).ok()?),
+        "long" => Tag::Long(json_value.as_i64()?),
+        "float" => Tag::Float(json_to_float(json_value)? as f32),
+        "double" => Tag::Double(json_to_float(json_value)?),
+        "byte_array" => Tag::ByteArray(
+            integers()?
+                .into_iter()
+                .map(|value| i8::try_from(value).ok())
+                .collect::<Option<_>>()?,
+        ),
+        "int_array" => Tag::IntArray(
+            integers()?
+                .into_iter()
+                .map(|value| i32::try_from(value).ok())
+                .collect::<Option<_>>()?,
+        ),
+        "long_array" => Tag::LongArray(integers()?),
+        _ => return None,
+    };
+
+    Some(tag)
+}
+
+fn json_to_float(json_value: &JsonValue) -> Option<f64> {
+    match json_value.as_str() {
+        Some("NaN") => Some(f64::NAN),
+        Some("Infinity") => Some(f64::INFINITY),
+        Some("-Infinity") => Some(f64::NEG_INFINITY),
+        Some(_) => None,
+        None => json_value.as_f64(),
+    }
+}
+
+/// Dumps every chunk of region into `r.x.z` folder inside of dump folder.
+///
+/// Returns number of dumped chunks.
+pub fn dump_region(
+    chunk_provider: &AnvilChunkProvider,
+    region_x: i32,
+    region_z: i32,
+    dump_folder_path: &Path,
+    format: DumpFormat,
+) -> Result<usize, DumpError> {
+    let mut dumped_chunks = 0;
+
+    for region_chunk_z in 0..32 {
+        for region_chunk_x in 0..32 {
+            let chunk_x = region_x * 32 + region_chunk_x;
+            let chunk_z = region_z * 32 + region_chunk_z;
+
+            let chunk_compound_tag = match chunk_provider.load_chunk(chunk_x, chunk_z) {
+                Ok(chunk_compound_tag) => chunk_compound_tag,
+                Err(ChunkLoadError::RegionNotFound { .. })
+                | Err(ChunkLoadError::ChunkNotFound { .. }) => continue,
+                Err(chunk_load_error) => return Err(chunk_load_error.into()),
+            };
+
+            let region_folder_path = dump_folder_path.join(format!("r.{}.{}", region_x, region_z));
+            fs::create_dir_all(&region_folder_path)?;
+
+            let file_name = format!("c.{}.{}.{}", chunk_x, chunk_z, format.extension());
+            fs::write(
+                region_folder_path.join(file_name),
+                format.render(&chunk_compound_tag),
+            )?;
+
+            dumped_chunks += 1;
+        }
+    }
+
+    Ok(dumped_chunks)
+}
+
+/// Dumps every chunk of provider into region folders inside of dump folder.
+///
+/// Returns number of dumped chunks.
+pub fn dump_provider(
+    chunk_provider: &AnvilChunkProvider,
+    dump_folder_path: &Path,
+    format: DumpFormat,
+) -> Result<usize, DumpError> {
+    let mut dumped_chunks = 0;
+
+    for entry in fs::read_dir(chunk_provider.folder_path)? {
+        let file_name = entry?.file_name();
+
+        if let Some((region_x, region_z)) = file_name.to_str().and_then(region_position) {
+            dumped_chunks +=
+                dump_region(chunk_provider, region_x, region_z, dump_folder_path, format)?;
+        }
+    }
+
+    Ok(dumped_chunks)
+}
+
+/// Dumps every region folder of world, including entity and point of interest folders.
+///
+/// Dump mirrors world folder structure, so chunks of `DIM-1/region` are dumped into
+/// `DIM-1/region` inside of dump folder. Files other than region files are not dumped.
+pub fn dump_world(
+    world_folder_path: &Path,
+    dump_folder_path: &Path,
+    format: DumpFormat,
+) -> Result<usize, DumpError> {
+    let mut dumped_chunks = 0;
+    let mut has_region_files = false;
+
+    for entry in fs::read_dir(world_folder_path)? {
+        let path = entry?.path();
+
+        let file_name = match path.file_name().and_then(|file_name| file_name.to_str()) {
+            Some(file_name) => file_name,
+            None => continue,
+        };
+
+        if path.is_dir() {
+            dumped_chunks += dump_world(&path, &dump_folder_path.join(file_name), format)?;
+        } else if region_position(file_name).is_some() {
+            has_region_files = true;
+        }
+    }
+
+    if has_region_files {
+        let chunk_provider = AnvilChunkProvider::from_path(world_folder_path);
+        dumped_chunks += dump_provider(&chunk_provider, dump_folder_path, format)?;
+    }
+
+    Ok(dumped_chunks)
+}
+
+/// Imports chunk files of region folders inside of dump folder into provider.
+///
+/// Format of every file is chosen by its extension. Returns number of imported chunks.
+pub fn import_provider(
+    dump_folder_path: &Path,
+    chunk_provider: &AnvilChunkProvider,
+) -> Result<usize, DumpError> {
+    let mut imported_chunks = 0;
+
+    for entry in fs::read_dir(dump_folder_path)? {
+        let path = entry?.path();
+
+        if !path.is_dir() || !is_region_folder(&path) {
+            continue;
+        }
+
+        for chunk_entry in fs::read_dir(&path)? {
+            let chunk_path = chunk_entry?.path();
+
+            let (chunk_x, chunk_z, format) = match chunk_path
+                .file_name()
+                .and_then(|file_name| file_name.to_str())
+                .and_then(chunk_file_position)
+            {
+                Some(chunk_file_position) => chunk_file_position,
+                None => continue,
+            };
+
+            let chunk_compound_tag = format.parse(&fs::read_to_string(&chunk_path)?)?;
+            chunk_provider.save_chunk(chunk_x, chunk_z, chunk_compound_tag)?;
+
+            imported_chunks += 1;
+        }
+    }
+
+    Ok(imported_chunks)
+}
+
+/// Imports dump of world written by [`dump_world`] into world folder.
+pub fn import_world(dump_folder_path: &Path, world_folder_path: &Path) -> Result<usize, DumpError> {
+    let mut imported_chunks = 0;
+    let mut has_region_folders = false;
+
+    for entry in fs::read_dir(dump_folder_path)? {
+        let path = entry?.path();
+
+        let file_name = match path.file_name().and_then(|file_name| file_name.to_str()) {
+            Some(file_name) => file_name,
+            None => continue,
+        };
+
+        if !path.is_dir() {
+            continue;
+        }
+
+        if is_region_folder(&path) {
+            has_region_folders = true;
+        } else {
+            imported_chunks += import_world(&path, &world_folder_path.join(file_name))?;
+        }
+    }
+
+    if has_region_folders {
+        fs::create_dir_all(world_folder_path)?;
+
+        let chunk_provider = AnvilChunkProvider::from_path(world_folder_path);
+        imported_chunks += import_provider(dump_folder_path, &chunk_provider)?;
+    }
+
+    Ok(imported_chunks)
+}
+
+/// Returns true if folder name is in format `r.x.z`.
+fn is_region_folder(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|file_name| file_name.to_str())
+        .and_then(|file_name| region_position(&format!("{}.mca", file_name)))
+        .is_some()
+}
+
+/// Parses chunk coordinates and format from file name in format `c.x.z.json`.
+fn chunk_file_position(file_name: &str) -> Option<(i32, i32, DumpFormat)> {
+    let mut parts = file_name.split('.');
+
+    if parts.next() != Some("c") {
+        return None;
+    }
+
+    let chunk_x = parts.next()?.parse().ok()?;
+    let chunk_z = parts.next()?.parse().ok()?;
+    let format = DumpFormat::from_extension(parts.next()?)?;
+
+    if parts.next().is_some() {
+        return None;
+    }
+
+    Some((chunk_x, chunk_z, format))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dump::{
+        compound_tag_to_json, dump_world, import_world, json_to_compound_tag, DumpError, DumpFormat,
+    };
+    use crate::json::{self, JsonValue};
+    use crate::relocate::copy_chunk;
+    use crate::snbt;
+    use crate::AnvilChunkProvider;
+    use nbt::{CompoundTag, Tag};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_json_round_trip() {
+        let mut compound_tag = CompoundTag::new();
+        compound_tag.insert_i8("byte", -3);
+        compound_tag.insert_i16("short", 300);
+        compound_tag.insert_i32("int", 7);
+        compound_tag.insert_i64("long", i64::MIN);
+        compound_tag.insert_f32("float", 0.1);
+        compound_tag.insert_f64("double", 2.0);
+        compound_tag.insert_f64("nan", f64::NAN);
+        compound_tag.insert_i8_vec("bytes", vec![1, -1]);
+        compound_tag.insert_i32_vec("ints", vec![]);
+        compound_tag.insert_i64_vec("longs", vec![i64::MAX]);
+        compound_tag.insert_str("$byte", "escaped");
+        compound_tag.insert("list", Tag::List(vec![Tag::Short(1), Tag::Short(2)]));
+
+        let json_value = compound_tag_to_json(&compound_tag);
+        assert_eq!(json_value.get("int"), Some(&JsonValue::Integer(7)));
+        assert_eq!(json_value.get("double"), Some(&JsonValue::Float(2.0)));
+        assert!(json_value.get("$$byte").is_some());
+
+        let json = json::to_string(&json_value);
+        let restored = json_to_compound_tag(&json::from_str(&json).unwrap()).unwrap();
+        let snbt = snbt::to_string(&compound_tag);
+
+        assert_eq!(snbt::to_string(&restored), snbt);
+        assert!(restored.get_f64("nan").unwrap().is_nan());
+    }
+
+    #[test]
+    fn test_json_invalid_value() {
+        let json_value = json::from_str(r#"{"Level": {"Sections": [{"Y": true}]}}"#).unwrap();
+
+        match json_to_compound_tag(&json_value) {
+            Err(DumpError::InvalidJsonValue { path }) => assert_eq!(path, "Level.Sections[0].Y"),
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_dump_and_import_world() {
+        let world_dir = TempDir::new().unwrap();
+        let dump_dir = TempDir::new().unwrap();
+        let target_world_dir = TempDir::new().unwrap();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+
+        let region_folder = world_dir.path().join("DIM-1/region");
+        let chunk_provider = AnvilChunkProvider::new(region_folder.to_str().unwrap());
+        copy_chunk(&fixture_chunk_provider, (4, 2), &chunk_provider, (-1, 40)).unwrap();
+
+        for format in &[DumpFormat::Json, DumpFormat::Snbt] {
+            let dumped_chunks = dump_world(world_dir.path(), dump_dir.path(), *format).unwrap();
+            assert_eq!(dumped_chunks, 1);
+
+            let chunk_path = format!("DIM-1/region/r.-1.1/c.-1.40.{}", format.extension());
+            assert!(dump_dir.path().join(chunk_path).exists());
+        }
+
+        // Both files are imported into the same chunk.
+        let imported_chunks = import_world(dump_dir.path(), target_world_dir.path()).unwrap();
+        assert_eq!(imported_chunks, 2);
+
+        let target_region_folder = target_world_dir.path().join("DIM-1/region");
+        let target_chunk_provider = AnvilChunkProvider::new(target_region_folder.to_str().unwrap());
+
+        assert_eq!(
+            snbt::to_string(&target_chunk_provider.load_chunk(-1, 40).unwrap()),
+            snbt::to_string(&chunk_provider.load_chunk(-1, 40).unwrap())
+        );
+    }
+}
@@ -0,0 +1,332 @@
+//! Detection of identical chunks in region folders.
+//!
+//! Chunks are grouped by hash of their compressed data, so exact copies are found
+//! without decoding anything. Chunks copied to other places by editing tools differ in
+//! their position and save times, with [`DuplicateOptions::ignore_positions`] chunks
+//! are decoded and compared without them. Groups of copies help to find copy-paste
+//! griefing and to estimate what storing every distinct chunk once would save.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::duplicates::{find_duplicate_chunks, DuplicateOptions};
+//! use anvil_region::AnvilChunkProvider;
+//!
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//! let options = DuplicateOptions::new().ignore_positions();
+//! let duplicate_report = find_duplicate_chunks(&chunk_provider, &options).unwrap();
+//!
+//! for duplicate_group in &duplicate_report.groups {
+//!     println!("{} copies: {:?}", duplicate_group.chunks.len(), duplicate_group.chunks);
+//! }
+//! ```
+use crate::backup::fnv_hash;
+use crate::buffer::decode_chunk;
+use crate::tag::{compound_tags_mut, get_tag_mut, remove_tag, set_tag};
+use crate::world::{AnvilWorld, Dimension};
+use crate::{AnvilChunkProvider, AnvilRegion};
+use nbt::encode::write_compound_tag;
+use nbt::{CompoundTag, Tag};
+use std::collections::HashMap;
+use std::io;
+
+/// Tags of chunk and `Level` compound which depend on position or time of save.
+const VOLATILE_TAGS: [&str; 8] = [
+    "xPos",
+    "yPos",
+    "zPos",
+    "Position",
+    "LastUpdate",
+    "InhabitedTime",
+    "Structures",
+    "structures",
+];
+/// Lists of block entities and scheduled ticks with absolute positions.
+const POSITIONED_LISTS: [&str; 6] = [
+    "block_entities",
+    "TileEntities",
+    "block_ticks",
+    "fluid_ticks",
+    "TileTicks",
+    "LiquidTicks",
+];
+
+/// How chunks are compared.
+#[derive(Debug, Clone, Default)]
+pub struct DuplicateOptions {
+    /// Compare decoded chunks without position, save times, structures, positions of
+    /// entities and absolute positions of block entities and ticks.
+    pub ignore_positions: bool,
+}
+
+impl DuplicateOptions {
+    pub fn new() -> Self {
+        DuplicateOptions::default()
+    }
+
+    pub fn ignore_positions(mut self) -> Self {
+        self.ignore_positions = true;
+        self
+    }
+}
+
+/// Chunks with the same hash.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DuplicateGroup {
+    /// FNV-1a hash of compared data.
+    pub hash: u64,
+    /// Sorted positions of at least two chunks.
+    pub chunks: Vec<(i32, i32)>,
+}
+
+/// Result of duplicate detection.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DuplicateReport {
+    pub scanned_chunks: usize,
+    /// Chunks which can't be read or decoded, they aren't in any group.
+    pub unreadable_chunks: usize,
+    /// Groups of identical chunks, the largest first.
+    pub groups: Vec<DuplicateGroup>,
+}
+
+impl DuplicateReport {
+    /// Returns amount of chunks which are copies of another chunk.
+    pub fn duplicate_chunks(&self) -> usize {
+        self.groups
+            .iter()
+            .map(|duplicate_group| duplicate_group.chunks.len() - 1)
+            .sum()
+    }
+}
+
+/// Finds groups of identical chunks of provider.
+pub fn find_duplicate_chunks(
+    chunk_provider: &AnvilChunkProvider,
+    options: &DuplicateOptions,
+) -> Result<DuplicateReport, io::Error> {
+    let mut duplicate_report = DuplicateReport::default();
+    let mut chunk_hashes: HashMap<u64, Vec<(i32, i32)>> = HashMap::new();
+
+    for region_file in chunk_provider.region_files()? {
+        let (region_x, region_z) = region_file.region_position;
+        let mut region = AnvilRegion::new(&region_file.path)?;
+
+        for (region_chunk_x, region_chunk_z) in region.chunk_positions() {
+            let chunk_x = (region_x << 5) + region_chunk_x as i32;
+            let chunk_z = (region_z << 5) + region_chunk_z as i32;
+
+            duplicate_report.scanned_chunks += 1;
+
+            let hash = region
+                .read_chunk_data(region_chunk_x, region_chunk_z)
+                .ok()
+                .and_then(|(compression_scheme, compressed_buffer)| {
+                    if !options.ignore_positions {
+                        return Some(fnv_hash(&compressed_buffer));
+                    }
+
+                    let mut chunk_compound_tag =
+                        decode_chunk(compression_scheme, &compressed_buffer).ok()?;
+                    strip_positions(&mut chunk_compound_tag);
+
+                    let mut buffer = Vec::new();
+                    write_compound_tag(&mut buffer, &chunk_compound_tag).ok()?;
+
+                    Some(fnv_hash(&buffer))
+                });
+
+            match hash {
+                Some(hash) => chunk_hashes
+                    .entry(hash)
+                    .or_default()
+                    .push((chunk_x, chunk_z)),
+                None => duplicate_report.unreadable_chunks += 1,
+            }
+        }
+    }
+
+    for (hash, mut chunks) in chunk_hashes {
+        if chunks.len() > 1 {
+            chunks.sort_unstable();
+            duplicate_report
+                .groups
+                .push(DuplicateGroup { hash, chunks });
+        }
+    }
+
+    duplicate_report.groups.sort_unstable_by(|first, second| {
+        second
+            .chunks
+            .len()
+            .cmp(&first.chunks.len())
+            .then_with(|| first.chunks.cmp(&second.chunks))
+    });
+
+    Ok(duplicate_report)
+}
+
+/// Finds groups of identical terrain chunks in every dimension of world.
+///
+/// Chunks are compared only with chunks of the same dimension.
+pub fn find_world_duplicate_chunks(
+    world: &AnvilWorld,
+    options: &DuplicateOptions,
+) -> Result<Vec<(Dimension, DuplicateReport)>, io::Error> {
+    let mut duplicate_reports = Vec::new();
+
+    for dimension in world.dimensions()? {
+        let world_dimension = world.dimension(&dimension);
+        let duplicate_report = find_duplicate_chunks(&world_dimension.chunk_provider(), options)?;
+
+        duplicate_reports.push((dimension, duplicate_report));
+    }
+
+    Ok(duplicate_reports)
+}
+
+/// Removes tags which depend on position and save time, block positions in lists are
+/// made relative to chunk.
+fn strip_positions(chunk_compound_tag: &mut CompoundTag) {
+    strip_compound_positions(chunk_compound_tag);
+
+    // Chunks before 1.18 keep everything in `Level` compound.
+    if let Some(Tag::Compound(level_compound_tag)) = get_tag_mut(chunk_compound_tag, "Level") {
+        strip_compound_positions(level_compound_tag);
+    }
+}
+
+fn strip_compound_positions(compound_tag: &mut CompoundTag) {
+    for name in VOLATILE_TAGS.iter() {
+        remove_tag(compound_tag, name);
+    }
+
+    for list_name in POSITIONED_LISTS.iter() {
+        for element in compound_tags_mut(compound_tag, list_name) {
+            for coordinate in ["x", "z"].iter() {
+                if let Ok(value) = element.get_i32(coordinate) {
+                    set_tag(element, coordinate, Tag::Int(value & 15));
+                }
+            }
+        }
+    }
+
+    for entity in compound_tags_mut(compound_tag, "Entities") {
+        strip_entity_positions(entity);
+    }
+}
+
+fn strip_entity_positions(entity: &mut CompoundTag) {
+    remove_tag(entity, "Pos");
+
+    // Hanging entities like paintings and item frames.
+    for name in ["TileX", "TileZ"].iter() {
+        if let Ok(value) = entity.get_i32(name) {
+            set_tag(entity, name, Tag::Int(value & 15));
+        }
+    }
+
+    for passenger in compound_tags_mut(entity, "Passengers") {
+        strip_entity_positions(passenger);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::duplicates::{find_duplicate_chunks, find_world_duplicate_chunks, DuplicateOptions};
+    use crate::level::LevelData;
+    use crate::relocate::copy_chunk;
+    use crate::world::{AnvilWorld, Dimension};
+    use crate::AnvilChunkProvider;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_find_duplicate_chunks() {
+        let region_dir = TempDir::new().unwrap();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk_provider = AnvilChunkProvider::new(region_dir.path().to_str().unwrap());
+
+        for &(chunk_x, chunk_z) in &[(4, 2), (15, 3)] {
+            let chunk_compound_tag = fixture_chunk_provider.load_chunk(chunk_x, chunk_z).unwrap();
+            chunk_provider
+                .save_chunk(chunk_x, chunk_z, chunk_compound_tag)
+                .unwrap();
+        }
+
+        // Chunk saved unchanged at other positions is an exact copy.
+        let chunk_compound_tag = fixture_chunk_provider.load_chunk(4, 2).unwrap();
+        for &chunk_x in &[40, -40] {
+            chunk_provider
+                .save_chunk(chunk_x, 2, chunk_compound_tag.clone())
+                .unwrap();
+        }
+
+        let duplicate_report =
+            find_duplicate_chunks(&chunk_provider, &DuplicateOptions::new()).unwrap();
+
+        assert_eq!(duplicate_report.scanned_chunks, 4);
+        assert_eq!(duplicate_report.groups.len(), 1);
+        assert_eq!(
+            duplicate_report.groups[0].chunks,
+            vec![(-40, 2), (4, 2), (40, 2)]
+        );
+        assert_eq!(duplicate_report.duplicate_chunks(), 2);
+    }
+
+    #[test]
+    fn test_find_duplicate_chunks_ignoring_positions() {
+        let region_dir = TempDir::new().unwrap();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk_provider = AnvilChunkProvider::new(region_dir.path().to_str().unwrap());
+
+        // Relocated chunks get their own position and positions of block entities.
+        for &(chunk_x, chunk_z) in &[(4, 2), (40, 7), (-3, -9)] {
+            copy_chunk(
+                &fixture_chunk_provider,
+                (4, 2),
+                &chunk_provider,
+                (chunk_x, chunk_z),
+            )
+            .unwrap();
+        }
+
+        let duplicate_report =
+            find_duplicate_chunks(&chunk_provider, &DuplicateOptions::new()).unwrap();
+        assert!(duplicate_report.groups.is_empty());
+
+        let options = DuplicateOptions::new().ignore_positions();
+        let duplicate_report = find_duplicate_chunks(&chunk_provider, &options).unwrap();
+
+        assert_eq!(duplicate_report.groups.len(), 1);
+        assert_eq!(duplicate_report.groups[0].chunks.len(), 3);
+    }
+
+    #[test]
+    fn test_find_world_duplicate_chunks() {
+        let world_dir = TempDir::new().unwrap();
+        LevelData::new().save(world_dir.path()).unwrap();
+
+        let world = AnvilWorld::open(world_dir.path()).unwrap();
+        let nether = world.nether();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk_compound_tag = fixture_chunk_provider.load_chunk(15, 3).unwrap();
+
+        for &chunk_x in &[0, 1] {
+            nether
+                .chunk_provider()
+                .save_chunk(chunk_x, 0, chunk_compound_tag.clone())
+                .unwrap();
+        }
+
+        let duplicate_reports =
+            find_world_duplicate_chunks(&world, &DuplicateOptions::new()).unwrap();
+        let (dimension, duplicate_report) = duplicate_reports
+            .iter()
+            .find(|(_, duplicate_report)| !duplicate_report.groups.is_empty())
+            .unwrap();
+
+        assert_eq!(*dimension, Dimension::Nether);
+        assert_eq!(duplicate_report.groups[0].chunks, vec![(0, 0), (1, 0)]);
+    }
+}
@@ -0,0 +1,310 @@
+//! Moving entities between terrain chunks and entity chunks.
+//!
+//! Since 1.17 entities are stored in separate region files in `entities` folder
+//! instead of `Entities` tag of terrain chunk. The game moves them lazily when chunk
+//! is loaded, tools which read entities from a whole world need them moved upfront.
+//! Downgrading world to 1.16 requires the reverse merge, otherwise all entities are lost.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::entities::split_entities;
+//! use anvil_region::AnvilChunkProvider;
+//!
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//! let mut chunk_compound_tag = chunk_provider.load_chunk(4, 2).unwrap();
+//!
+//! if let Some(entity_chunk) = split_entities(&mut chunk_compound_tag).unwrap() {
+//!     assert_eq!(entity_chunk.get_i32_vec("Position").unwrap(), &vec![4, 2]);
+//! }
+//! ```
+use crate::chunk::{Chunk, ChunkTag};
+use crate::relocate::chunk_position;
+use crate::tag::remove_tag;
+use crate::world::{ENTITIES_FOLDER, REGION_FOLDER};
+use crate::{AnvilChunkProvider, ChunkLoadError, ChunkSaveError};
+use nbt::{CompoundTag, Tag};
+use std::io;
+use std::path::Path;
+
+/// Possible errors while migrating entities.
+#[derive(Debug)]
+pub enum EntityMigrationError {
+    /// Chunk doesn't contain its position.
+    MissingPosition,
+    /// Chunk can't be loaded.
+    ChunkLoadError { chunk_load_error: ChunkLoadError },
+    /// Chunk can't be saved.
+    ChunkSaveError { chunk_save_error: ChunkSaveError },
+    /// Region folder can't be read.
+    ReadError { io_error: io::Error },
+}
+
+impl From<ChunkLoadError> for EntityMigrationError {
+    fn from(chunk_load_error: ChunkLoadError) -> Self {
+        EntityMigrationError::ChunkLoadError { chunk_load_error }
+    }
+}
+
+impl From<ChunkSaveError> for EntityMigrationError {
+    fn from(chunk_save_error: ChunkSaveError) -> Self {
+        EntityMigrationError::ChunkSaveError { chunk_save_error }
+    }
+}
+
+impl From<io::Error> for EntityMigrationError {
+    fn from(io_error: io::Error) -> Self {
+        EntityMigrationError::ReadError { io_error }
+    }
+}
+
+/// Removes entities from terrain chunk and returns entity chunk containing them.
+///
+/// Returns none if terrain chunk doesn't contain entities.
+pub fn split_entities(
+    chunk_compound_tag: &mut CompoundTag,
+) -> Result<Option<CompoundTag>, EntityMigrationError> {
+    let (chunk_x, chunk_z) =
+        chunk_position(chunk_compound_tag).ok_or(EntityMigrationError::MissingPosition)?;
+
+    let mut chunk = Chunk::new(std::mem::replace(chunk_compound_tag, CompoundTag::new()));
+    let name = chunk.tag_name(ChunkTag::Entities);
+    let entities = remove_tag(chunk.data_mut(), name);
+    let data_version = chunk.data_version();
+    *chunk_compound_tag = chunk.into_compound_tag();
+
+    let entities = match entities {
+        Some(Tag::List(entities)) if !entities.is_empty() => entities,
+        _ => return Ok(None),
+    };
+
+    let mut entity_chunk = CompoundTag::new();
+
+    if let Some(data_version) = data_version {
+        entity_chunk.insert_i32("DataVersion", data_version);
+    }
+
+    entity_chunk.insert_i32_vec("Position", vec![chunk_x, chunk_z]);
+    entity_chunk.insert("Entities", Tag::List(entities));
+
+    Ok(Some(entity_chunk))
+}
+
+/// Moves entities of entity chunk into terrain chunk.
+///
+/// Entities already present in terrain chunk are kept.
+pub fn merge_entities(chunk_compound_tag: &mut CompoundTag, entity_chunk: CompoundTag) {
+    let entities = entity_chunk
+        .into_iter()
+        .find(|(name, _)| name == "Entities")
+        .and_then(|(_, entities)| match entities {
+            Tag::List(entities) => Some(entities),
+            _ => None,
+        })
+        .unwrap_or_default();
+
+    let mut chunk = Chunk::new(std::mem::replace(chunk_compound_tag, CompoundTag::new()));
+    let name = chunk.tag_name(ChunkTag::Entities);
+    let data = chunk.data_mut();
+
+    match data.get_mut::<&mut Vec<Tag>>(name) {
+        Ok(chunk_entities) => chunk_entities.extend(entities),
+        Err(_) => data.insert(name, Tag::List(entities)),
+    }
+
+    *chunk_compound_tag = chunk.into_compound_tag();
+}
+
+/// Moves entities of every terrain chunk into entity chunks.
+///
+/// Entities are appended to existing entity chunks. Returns amount of chunks which
+/// had entities.
+pub fn split_provider_entities(
+    chunk_provider: &AnvilChunkProvider,
+    entity_chunk_provider: &AnvilChunkProvider,
+) -> Result<usize, EntityMigrationError> {
+    let mut split_chunks = 0;
+
+    for (chunk_x, chunk_z) in chunk_provider.chunk_positions()? {
+        let mut chunk_compound_tag = chunk_provider.load_chunk(chunk_x, chunk_z)?;
+
+        let mut entity_chunk = match split_entities(&mut chunk_compound_tag)? {
+            Some(entity_chunk) => entity_chunk,
+            None => continue,
+        };
+
+        if let Ok(mut existing_entity_chunk) = entity_chunk_provider.load_chunk(chunk_x, chunk_z) {
+            if let Ok(entities) = existing_entity_chunk.get_mut::<&mut Vec<Tag>>("Entities") {
+                if let Ok(new_entities) = entity_chunk.get_mut::<&mut Vec<Tag>>("Entities") {
+                    entities.append(new_entities);
+                }
+
+                entity_chunk = existing_entity_chunk;
+            }
+        }
+
+        entity_chunk_provider.save_chunk(chunk_x, chunk_z, entity_chunk)?;
+        chunk_provider.save_chunk(chunk_x, chunk_z, chunk_compound_tag)?;
+
+        split_chunks += 1;
+    }
+
+    Ok(split_chunks)
+}
+
+/// Moves entities of every entity chunk back into terrain chunks and deletes entity chunks.
+///
+/// Entity chunks without terrain chunk are kept. Returns amount of merged chunks.
+pub fn merge_provider_entities(
+    chunk_provider: &AnvilChunkProvider,
+    entity_chunk_provider: &AnvilChunkProvider,
+) -> Result<usize, EntityMigrationError> {
+    let mut merged_chunks = 0;
+
+    for (chunk_x, chunk_z) in entity_chunk_provider.chunk_positions()? {
+        let mut chunk_compound_tag = match chunk_provider.load_chunk(chunk_x, chunk_z) {
+            Ok(chunk_compound_tag) => chunk_compound_tag,
+            Err(ChunkLoadError::RegionNotFound { .. })
+            | Err(ChunkLoadError::ChunkNotFound { .. }) => continue,
+            Err(chunk_load_error) => return Err(chunk_load_error.into()),
+        };
+
+        let entity_chunk = entity_chunk_provider.load_chunk(chunk_x, chunk_z)?;
+        merge_entities(&mut chunk_compound_tag, entity_chunk);
+
+        chunk_provider.save_chunk(chunk_x, chunk_z, chunk_compound_tag)?;
+        entity_chunk_provider.delete_chunk(chunk_x, chunk_z)?;
+
+        merged_chunks += 1;
+    }
+
+    Ok(merged_chunks)
+}
+
+/// Moves entities of world terrain chunks into `entities` folder.
+pub fn split_world_entities(world_folder_path: &Path) -> Result<usize, EntityMigrationError> {
+    let region_folder_path = world_folder_path.join(REGION_FOLDER);
+    let entities_folder_path = world_folder_path.join(ENTITIES_FOLDER);
+
+    let chunk_provider = AnvilChunkProvider::from_path(&region_folder_path);
+
+    let entity_chunk_provider = AnvilChunkProvider::from_path(&entities_folder_path);
+
+    split_provider_entities(&chunk_provider, &entity_chunk_provider)
+}
+
+/// Moves entities of world `entities` folder back into terrain chunks.
+pub fn merge_world_entities(world_folder_path: &Path) -> Result<usize, EntityMigrationError> {
+    let region_folder_path = world_folder_path.join(REGION_FOLDER);
+    let entities_folder_path = world_folder_path.join(ENTITIES_FOLDER);
+
+    let chunk_provider = AnvilChunkProvider::from_path(&region_folder_path);
+
+    let entity_chunk_provider = AnvilChunkProvider::from_path(&entities_folder_path);
+
+    merge_provider_entities(&chunk_provider, &entity_chunk_provider)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entities::{
+        merge_entities, merge_world_entities, split_entities, split_world_entities,
+    };
+    use crate::relocate::copy_chunk;
+    use crate::AnvilChunkProvider;
+    use nbt::{CompoundTag, Tag};
+    use tempfile::TempDir;
+
+    fn entity(id: &str) -> Tag {
+        let mut entity = CompoundTag::new();
+        entity.insert_str("id", id);
+
+        Tag::Compound(entity)
+    }
+
+    fn chunk_with_entities(entities: Vec<Tag>) -> CompoundTag {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+        let mut chunk_compound_tag = chunk_provider.load_chunk(4, 2).unwrap();
+        let level_compound_tag = chunk_compound_tag
+            .get_mut::<&mut CompoundTag>("Level")
+            .unwrap();
+        level_compound_tag.insert("Entities", Tag::List(entities));
+
+        chunk_compound_tag
+    }
+
+    fn entities_length(compound_tag: &CompoundTag) -> usize {
+        let compound_tag = compound_tag
+            .get_compound_tag("Level")
+            .unwrap_or(compound_tag);
+
+        compound_tag
+            .get::<&Vec<Tag>>("Entities")
+            .map_or(0, |entities| entities.len())
+    }
+
+    #[test]
+    fn test_split_entities() {
+        let mut chunk_compound_tag = chunk_with_entities(vec![entity("minecraft:cow")]);
+
+        let entity_chunk = split_entities(&mut chunk_compound_tag).unwrap().unwrap();
+
+        assert_eq!(entity_chunk.get_i32_vec("Position").unwrap(), &vec![4, 2]);
+        assert_eq!(entity_chunk.get_i32("DataVersion").unwrap(), 1631);
+        assert_eq!(entities_length(&entity_chunk), 1);
+        assert_eq!(entities_length(&chunk_compound_tag), 0);
+        assert!(split_entities(&mut chunk_compound_tag).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_merge_entities() {
+        let mut chunk_compound_tag = chunk_with_entities(vec![entity("minecraft:cow")]);
+        let entity_chunk = split_entities(&mut chunk_with_entities(vec![
+            entity("minecraft:pig"),
+            entity("minecraft:sheep"),
+        ]))
+        .unwrap()
+        .unwrap();
+
+        merge_entities(&mut chunk_compound_tag, entity_chunk);
+
+        assert_eq!(entities_length(&chunk_compound_tag), 3);
+    }
+
+    #[test]
+    fn test_split_and_merge_world_entities() {
+        let world_dir = TempDir::new().unwrap();
+        let region_folder = world_dir.path().join("region");
+        let entities_folder = world_dir.path().join("entities");
+        let chunk_provider = AnvilChunkProvider::new(region_folder.to_str().unwrap());
+        let entity_chunk_provider = AnvilChunkProvider::new(entities_folder.to_str().unwrap());
+
+        chunk_provider
+            .save_chunk(4, 2, chunk_with_entities(vec![entity("minecraft:cow")]))
+            .unwrap();
+        copy_chunk(
+            &AnvilChunkProvider::new("test/region"),
+            (15, 3),
+            &chunk_provider,
+            (15, 3),
+        )
+        .unwrap();
+
+        assert_eq!(split_world_entities(world_dir.path()).unwrap(), 1);
+        assert_eq!(
+            entity_chunk_provider.chunk_positions().unwrap(),
+            vec![(4, 2)]
+        );
+        assert_eq!(
+            entities_length(&chunk_provider.load_chunk(4, 2).unwrap()),
+            0
+        );
+
+        assert_eq!(merge_world_entities(world_dir.path()).unwrap(), 1);
+        assert!(entity_chunk_provider.chunk_positions().unwrap().is_empty());
+        assert_eq!(
+            entities_length(&chunk_provider.load_chunk(4, 2).unwrap()),
+            1
+        );
+    }
+}
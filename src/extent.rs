@@ -0,0 +1,157 @@
+//! Area covered by chunks of provider.
+//!
+//! Extent is computed from region file names and headers without decompressing any
+//! chunk, so it is cheap enough to run before rendering or trimming whole worlds.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::extent::world_extent;
+//! use anvil_region::AnvilChunkProvider;
+//!
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//! let world_extent = world_extent(&chunk_provider).unwrap().unwrap();
+//!
+//! assert!(world_extent.contains_chunk(4, 2));
+//! assert!(world_extent.is_region_occupied(0, 0));
+//! ```
+use crate::AnvilChunkProvider;
+use std::io;
+
+/// Bounding box and occupancy of existing chunks.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WorldExtent {
+    /// Lowest chunk X and Z among existing chunks.
+    pub min_chunk: (i32, i32),
+    /// Highest chunk X and Z among existing chunks.
+    pub max_chunk: (i32, i32),
+    /// Amount of existing chunks.
+    pub chunk_count: usize,
+    /// Lowest region X and Z which contains chunks.
+    pub min_region: (i32, i32),
+    /// Width of region grid along X.
+    pub regions_width: usize,
+    /// Depth of region grid along Z.
+    pub regions_depth: usize,
+    /// Amount of chunks in every region of grid indexed by `z * regions_width + x`.
+    pub region_chunk_counts: Vec<u16>,
+}
+
+impl WorldExtent {
+    /// Returns true if chunk position is inside of bounding box.
+    pub fn contains_chunk(&self, chunk_x: i32, chunk_z: i32) -> bool {
+        chunk_x >= self.min_chunk.0
+            && chunk_x <= self.max_chunk.0
+            && chunk_z >= self.min_chunk.1
+            && chunk_z <= self.max_chunk.1
+    }
+
+    /// Returns lowest and highest block X and Z covered by chunks, both inclusive.
+    pub fn block_bounds(&self) -> ((i32, i32), (i32, i32)) {
+        (
+            (self.min_chunk.0 * 16, self.min_chunk.1 * 16),
+            (self.max_chunk.0 * 16 + 15, self.max_chunk.1 * 16 + 15),
+        )
+    }
+
+    /// Returns amount of chunks stored in region.
+    pub fn region_chunk_count(&self, region_x: i32, region_z: i32) -> u16 {
+        let x = region_x - self.min_region.0;
+        let z = region_z - self.min_region.1;
+
+        if x < 0 || z < 0 || x as usize >= self.regions_width || z as usize >= self.regions_depth {
+            return 0;
+        }
+
+        self.region_chunk_counts[z as usize * self.regions_width + x as usize]
+    }
+
+    /// Returns true if region contains at least one chunk.
+    pub fn is_region_occupied(&self, region_x: i32, region_z: i32) -> bool {
+        self.region_chunk_count(region_x, region_z) > 0
+    }
+}
+
+/// Returns extent of chunks stored in provider, none if it doesn't contain chunks.
+pub fn world_extent(chunk_provider: &AnvilChunkProvider) -> Result<Option<WorldExtent>, io::Error> {
+    let chunk_positions = chunk_provider.chunk_positions()?;
+
+    let (first_x, first_z) = match chunk_positions.first() {
+        Some(chunk_position) => *chunk_position,
+        None => return Ok(None),
+    };
+
+    let mut min_chunk = (first_x, first_z);
+    let mut max_chunk = (first_x, first_z);
+
+    for (chunk_x, chunk_z) in &chunk_positions {
+        min_chunk = (min_chunk.0.min(*chunk_x), min_chunk.1.min(*chunk_z));
+        max_chunk = (max_chunk.0.max(*chunk_x), max_chunk.1.max(*chunk_z));
+    }
+
+    let min_region = (min_chunk.0 >> 5, min_chunk.1 >> 5);
+    let regions_width = ((max_chunk.0 >> 5) - min_region.0 + 1) as usize;
+    let regions_depth = ((max_chunk.1 >> 5) - min_region.1 + 1) as usize;
+    let mut region_chunk_counts = vec![0; regions_width * regions_depth];
+
+    for (chunk_x, chunk_z) in &chunk_positions {
+        let x = ((chunk_x >> 5) - min_region.0) as usize;
+        let z = ((chunk_z >> 5) - min_region.1) as usize;
+
+        region_chunk_counts[z * regions_width + x] += 1;
+    }
+
+    Ok(Some(WorldExtent {
+        min_chunk,
+        max_chunk,
+        chunk_count: chunk_positions.len(),
+        min_region,
+        regions_width,
+        regions_depth,
+        region_chunk_counts,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::extent::world_extent;
+    use crate::relocate::copy_chunk;
+    use crate::AnvilChunkProvider;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_world_extent() {
+        let temp_dir = TempDir::new().unwrap();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+
+        assert_eq!(world_extent(&chunk_provider).unwrap(), None);
+
+        for (chunk_x, chunk_z) in &[(4, 2), (-40, 3), (70, -1)] {
+            copy_chunk(
+                &fixture_chunk_provider,
+                (4, 2),
+                &chunk_provider,
+                (*chunk_x, *chunk_z),
+            )
+            .unwrap();
+        }
+
+        let world_extent = world_extent(&chunk_provider).unwrap().unwrap();
+
+        assert_eq!(world_extent.min_chunk, (-40, -1));
+        assert_eq!(world_extent.max_chunk, (70, 3));
+        assert_eq!(world_extent.chunk_count, 3);
+        assert_eq!(world_extent.block_bounds(), ((-640, -16), (1135, 63)));
+        assert_eq!(world_extent.min_region, (-2, -1));
+        assert_eq!(
+            (world_extent.regions_width, world_extent.regions_depth),
+            (5, 2)
+        );
+        assert_eq!(world_extent.region_chunk_count(0, 0), 1);
+        assert!(world_extent.is_region_occupied(2, -1));
+        assert!(!world_extent.is_region_occupied(1, 0));
+        assert!(!world_extent.is_region_occupied(10, 10));
+    }
+}
@@ -0,0 +1,233 @@
+//! Loading and saving chunks as [`fastnbt::Value`] or serde types.
+//!
+//! Chunk data is decoded straight from decompressed bytes by fastnbt, so values keep
+//! every tag type without conversion through [`CompoundTag`].
+//!
+//! [`CompoundTag`]: nbt::CompoundTag
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::AnvilChunkProvider;
+//! use fastnbt::Value;
+//!
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//!
+//! let chunk_value = chunk_provider.load_chunk_value(4, 2).unwrap();
+//!
+//! if let Value::Compound(chunk) = chunk_value {
+//!     assert!(chunk.contains_key("Level"));
+//! }
+//! ```
+use crate::borrowed::BorrowedCompound;
+use crate::{
+    borrowed_chunk_position, check_position, AnvilChunkProvider, ChunkLoadError, ChunkSaveError,
+    ZLIB_COMPRESSION_TYPE,
+};
+use fastnbt::Value;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use nbt::decode::{read_compound_tag, TagDecodeError};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::{self, Cursor, Write};
+
+/// Possible errors while loading or saving chunk as fastnbt value.
+#[derive(Debug)]
+pub enum FastNbtError {
+    /// Chunk can't be loaded.
+    ChunkLoadError { chunk_load_error: ChunkLoadError },
+    /// Chunk can't be saved.
+    ChunkSaveError { chunk_save_error: ChunkSaveError },
+    /// Chunk can't be deserialized into type or value can't be serialized.
+    SerdeError {
+        fastnbt_error: fastnbt::error::Error,
+    },
+    /// Serialized value can't be decoded to move it to position it's saved at.
+    TagDecodeError { tag_decode_error: TagDecodeError },
+}
+
+impl From<ChunkLoadError> for FastNbtError {
+    fn from(chunk_load_error: ChunkLoadError) -> Self {
+        FastNbtError::ChunkLoadError { chunk_load_error }
+    }
+}
+
+impl From<ChunkSaveError> for FastNbtError {
+    fn from(chunk_save_error: ChunkSaveError) -> Self {
+        FastNbtError::ChunkSaveError { chunk_save_error }
+    }
+}
+
+impl From<io::Error> for FastNbtError {
+    fn from(io_error: io::Error) -> Self {
+        FastNbtError::ChunkSaveError {
+            chunk_save_error: io_error.into(),
+        }
+    }
+}
+
+impl From<fastnbt::error::Error> for FastNbtError {
+    fn from(fastnbt_error: fastnbt::error::Error) -> Self {
+        FastNbtError::SerdeError { fastnbt_error }
+    }
+}
+
+impl From<TagDecodeError> for FastNbtError {
+    fn from(tag_decode_error: TagDecodeError) -> Self {
+        FastNbtError::TagDecodeError { tag_decode_error }
+    }
+}
+
+impl AnvilChunkProvider<'_> {
+    /// Loads chunk from the specified coordinates as fastnbt value.
+    pub fn load_chunk_value(&self, chunk_x: i32, chunk_z: i32) -> Result<Value, FastNbtError> {
+        self.load_chunk_as(chunk_x, chunk_z)
+    }
+
+    /// Loads chunk from the specified coordinates deserializing it into type.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use anvil_region::AnvilChunkProvider;
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Chunk {
+    ///     #[serde(rename = "Level")]
+    ///     level: Level,
+    /// }
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Level {
+    ///     #[serde(rename = "xPos")]
+    ///     x_pos: i32,
+    /// }
+    ///
+    /// let chunk_provider = AnvilChunkProvider::new("test/region");
+    /// let chunk: Chunk = chunk_provider.load_chunk_as(4, 2).unwrap();
+    ///
+    /// assert_eq!(chunk.level.x_pos, 4);
+    /// ```
+    pub fn load_chunk_as<T: DeserializeOwned>(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> Result<T, FastNbtError> {
+        let result = self.read_chunk(
+            chunk_x,
+            chunk_z,
+            |chunk_buffers, compression_scheme, parse_issues| {
+                let data = chunk_buffers.decompress_compressed(
+                    compression_scheme,
+                    &self.parse_limits,
+                    parse_issues,
+                )?;
+
+                if self.check_positions {
+                    let (chunk_compound, _) = BorrowedCompound::parse_prefix(data)?;
+                    let stored_position = borrowed_chunk_position(&chunk_compound);
+                    check_position(chunk_x, chunk_z, stored_position, parse_issues);
+                }
+
+                Ok(fastnbt::from_bytes(data))
+            },
+        )?;
+
+        Ok(result?)
+    }
+
+    /// Saves fastnbt value to the specified coordinates.
+    pub fn save_chunk_value(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        chunk_value: &Value,
+    ) -> Result<(), FastNbtError> {
+        self.save_chunk_from(chunk_x, chunk_z, chunk_value)
+    }
+
+    /// Saves value serialized by fastnbt to the specified coordinates.
+    ///
+    /// When provider checks positions, serialized chunk is decoded to move it to
+    /// position it's saved at.
+    pub fn save_chunk_from<T: Serialize>(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        chunk_value: &T,
+    ) -> Result<(), FastNbtError> {
+        let data = fastnbt::to_bytes(chunk_value)?;
+
+        if self.check_positions {
+            let chunk_compound_tag = read_compound_tag(&mut Cursor::new(&data))?;
+            self.save_chunk(chunk_x, chunk_z, chunk_compound_tag)?;
+
+            return Ok(());
+        }
+
+        let mut encoder = ZlibEncoder::new(vec![ZLIB_COMPRESSION_TYPE], Compression::default());
+        encoder.write_all(&data)?;
+        let chunk_buffer = encoder.finish()?;
+
+        self.save_chunk_buffer(chunk_x, chunk_z, &chunk_buffer)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::AnvilChunkProvider;
+    use fastnbt::Value;
+    use serde::{Deserialize, Serialize};
+    use tempfile::TempDir;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct PositionChunk {
+        #[serde(rename = "xPos")]
+        x_pos: i32,
+        #[serde(rename = "zPos")]
+        z_pos: i32,
+        #[serde(rename = "Status")]
+        status: String,
+    }
+
+    #[test]
+    fn test_value_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+
+        let chunk_value = fixture_chunk_provider.load_chunk_value(4, 2).unwrap();
+        assert!(matches!(chunk_value, Value::Compound(_)));
+
+        chunk_provider.save_chunk_value(4, 2, &chunk_value).unwrap();
+
+        // Compounds of values are unordered, so saved chunk is compared as value.
+        assert_eq!(chunk_provider.load_chunk_value(4, 2).unwrap(), chunk_value);
+        assert!(chunk_provider.load_chunk(4, 2).is_ok());
+    }
+
+    #[test]
+    fn test_save_chunk_from() {
+        let temp_dir = TempDir::new().unwrap();
+        let chunk_provider =
+            AnvilChunkProvider::new(temp_dir.path().to_str().unwrap()).check_positions(true);
+
+        let chunk = PositionChunk {
+            x_pos: 1,
+            z_pos: 2,
+            status: "full".to_owned(),
+        };
+
+        // Chunk is moved to position it's saved at.
+        chunk_provider.save_chunk_from(3, 4, &chunk).unwrap();
+
+        let loaded_chunk: PositionChunk = chunk_provider.load_chunk_as(3, 4).unwrap();
+        assert_eq!(loaded_chunk.x_pos, 3);
+        assert_eq!(loaded_chunk.z_pos, 4);
+        assert_eq!(loaded_chunk.status, "full");
+    }
+}
@@ -0,0 +1,315 @@
+//! C interface to chunk provider of region folder.
+//!
+//! Provider is an opaque handle created by `anvil_provider_open` and released by
+//! `anvil_provider_free`. Chunks are exchanged as uncompressed NBT bytes, so any NBT
+//! library of the calling language can read them. Bytes returned by
+//! `anvil_load_chunk` are owned by caller and released by `anvil_bytes_free`.
+//!
+//! Every function returns [`AnvilStatus`], message of the last error of calling
+//! thread is returned by `anvil_last_error_message`. Header `include/anvil_region.h`
+//! is committed, cbindgen regenerates it into `OUT_DIR` when crate is built with `ffi`
+//! feature, so it is copied over after changing this module. Library for C linker is
+//! built by `cargo rustc --release --features ffi --crate-type cdylib`.
+use crate::{AnvilChunkProvider, ChunkLoadError};
+use nbt::decode::read_compound_tag;
+use nbt::encode::write_compound_tag;
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::path::PathBuf;
+use std::{ptr, slice};
+
+thread_local! {
+    static LAST_ERROR_MESSAGE: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Result of C interface function.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum AnvilStatus {
+    Ok = 0,
+    /// Pointer argument is null or string is not valid UTF-8.
+    InvalidArgument = 1,
+    /// Region file of chunk doesn't exist.
+    RegionNotFound = 2,
+    /// Chunk is not present in region file.
+    ChunkNotFound = 3,
+    /// Chunk can't be read or decoded.
+    LoadError = 4,
+    /// Bytes passed to save are not NBT compound.
+    InvalidNbt = 5,
+    /// Chunk can't be written.
+    SaveError = 6,
+}
+
+/// Opaque provider of chunks of region folder.
+pub struct AnvilProvider {
+    folder_path: PathBuf,
+}
+
+impl AnvilProvider {
+    fn chunk_provider(&self) -> AnvilChunkProvider<'_> {
+        AnvilChunkProvider::from_path(&self.folder_path)
+    }
+}
+
+/// Creates provider of region folder, returns null if path is null or not UTF-8.
+///
+/// # Safety
+///
+/// `folder_path` must be null or point to null terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn anvil_provider_open(folder_path: *const c_char) -> *mut AnvilProvider {
+    if folder_path.is_null() {
+        set_last_error_message("folder path is null");
+        return ptr::null_mut();
+    }
+
+    match CStr::from_ptr(folder_path).to_str() {
+        Ok(folder_path) => Box::into_raw(Box::new(AnvilProvider {
+            folder_path: PathBuf::from(folder_path),
+        })),
+        Err(_) => {
+            set_last_error_message("folder path is not valid UTF-8");
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Releases provider, null is ignored.
+///
+/// # Safety
+///
+/// `provider` must be null or returned by `anvil_provider_open` and not yet released.
+#[no_mangle]
+pub unsafe extern "C" fn anvil_provider_free(provider: *mut AnvilProvider) {
+    if !provider.is_null() {
+        drop(Box::from_raw(provider));
+    }
+}
+
+/// Loads chunk as uncompressed NBT bytes, which are released by `anvil_bytes_free`.
+///
+/// # Safety
+///
+/// `provider` must be returned by `anvil_provider_open`, `data` and `length` must
+/// point to writable memory.
+#[no_mangle]
+pub unsafe extern "C" fn anvil_load_chunk(
+    provider: *const AnvilProvider,
+    chunk_x: i32,
+    chunk_z: i32,
+    data: *mut *mut u8,
+    length: *mut usize,
+) -> AnvilStatus {
+    if provider.is_null() || data.is_null() || length.is_null() {
+        set_last_error_message("argument is null");
+        return AnvilStatus::InvalidArgument;
+    }
+
+    let chunk_compound_tag = match (*provider).chunk_provider().load_chunk(chunk_x, chunk_z) {
+        Ok(chunk_compound_tag) => chunk_compound_tag,
+        Err(chunk_load_error) => {
+            set_last_error_message(&chunk_load_error.to_string());
+
+            return match chunk_load_error {
+                ChunkLoadError::RegionNotFound { .. } => AnvilStatus::RegionNotFound,
+                ChunkLoadError::ChunkNotFound { .. } => AnvilStatus::ChunkNotFound,
+                _ => AnvilStatus::LoadError,
+            };
+        }
+    };
+
+    let mut buffer = Vec::new();
+
+    if let Err(io_error) = write_compound_tag(&mut buffer, &chunk_compound_tag) {
+        set_last_error_message(&io_error.to_string());
+        return AnvilStatus::LoadError;
+    }
+
+    let buffer = buffer.into_boxed_slice();
+    *length = buffer.len();
+    *data = Box::into_raw(buffer) as *mut u8;
+
+    AnvilStatus::Ok
+}
+
+/// Saves chunk from uncompressed NBT bytes of root compound.
+///
+/// # Safety
+///
+/// `provider` must be returned by `anvil_provider_open`, `data` must point to
+/// `length` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn anvil_save_chunk(
+    provider: *const AnvilProvider,
+    chunk_x: i32,
+    chunk_z: i32,
+    data: *const u8,
+    length: usize,
+) -> AnvilStatus {
+    if provider.is_null() || data.is_null() {
+        set_last_error_message("argument is null");
+        return AnvilStatus::InvalidArgument;
+    }
+
+    let mut data = slice::from_raw_parts(data, length);
+
+    let chunk_compound_tag = match read_compound_tag(&mut data) {
+        Ok(chunk_compound_tag) => chunk_compound_tag,
+        Err(tag_decode_error) => {
+            set_last_error_message(&tag_decode_error.to_string());
+            return AnvilStatus::InvalidNbt;
+        }
+    };
+
+    match (*provider)
+        .chunk_provider()
+        .save_chunk(chunk_x, chunk_z, chunk_compound_tag)
+    {
+        Ok(()) => AnvilStatus::Ok,
+        Err(chunk_save_error) => {
+            set_last_error_message(&chunk_save_error.to_string());
+            AnvilStatus::SaveError
+        }
+    }
+}
+
+/// Deletes chunk, chunk which is not present is reported as not found.
+///
+/// # Safety
+///
+/// `provider` must be returned by `anvil_provider_open`.
+#[no_mangle]
+pub unsafe extern "C" fn anvil_delete_chunk(
+    provider: *const AnvilProvider,
+    chunk_x: i32,
+    chunk_z: i32,
+) -> AnvilStatus {
+    if provider.is_null() {
+        set_last_error_message("argument is null");
+        return AnvilStatus::InvalidArgument;
+    }
+
+    match (*provider).chunk_provider().delete_chunk(chunk_x, chunk_z) {
+        Ok(true) => AnvilStatus::Ok,
+        Ok(false) => {
+            set_last_error_message("chunk not found");
+            AnvilStatus::ChunkNotFound
+        }
+        Err(chunk_save_error) => {
+            set_last_error_message(&chunk_save_error.to_string());
+            AnvilStatus::SaveError
+        }
+    }
+}
+
+/// Releases bytes returned by `anvil_load_chunk`, null is ignored.
+///
+/// # Safety
+///
+/// `data` and `length` must be returned by `anvil_load_chunk` and not yet released.
+#[no_mangle]
+pub unsafe extern "C" fn anvil_bytes_free(data: *mut u8, length: usize) {
+    if !data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(data, length)));
+    }
+}
+
+/// Returns message of the last error of calling thread, null if there was none.
+///
+/// Message is valid until the next error on the same thread.
+#[no_mangle]
+pub extern "C" fn anvil_last_error_message() -> *const c_char {
+    LAST_ERROR_MESSAGE.with(|last_error_message| {
+        last_error_message
+            .borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+fn set_last_error_message(message: &str) {
+    // Interior null can't be represented in C string.
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+
+    LAST_ERROR_MESSAGE.with(|last_error_message| {
+        *last_error_message.borrow_mut() = Some(message);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ffi::{
+        anvil_bytes_free, anvil_delete_chunk, anvil_last_error_message, anvil_load_chunk,
+        anvil_provider_free, anvil_provider_open, anvil_save_chunk, AnvilStatus,
+    };
+    use nbt::decode::read_compound_tag;
+    use std::ffi::{CStr, CString};
+    use std::{ptr, slice};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_load_save_delete() {
+        let temp_dir = TempDir::new().unwrap();
+        let fixture_path = CString::new("test/region").unwrap();
+        let folder_path = CString::new(temp_dir.path().to_str().unwrap()).unwrap();
+
+        unsafe {
+            let fixture_provider = anvil_provider_open(fixture_path.as_ptr());
+            let provider = anvil_provider_open(folder_path.as_ptr());
+
+            let mut data = ptr::null_mut();
+            let mut length = 0;
+            let status = anvil_load_chunk(fixture_provider, 4, 2, &mut data, &mut length);
+            assert_eq!(status, AnvilStatus::Ok);
+
+            let mut bytes = slice::from_raw_parts(data, length);
+            let chunk_compound_tag = read_compound_tag(&mut bytes).unwrap();
+            assert!(chunk_compound_tag.get_compound_tag("Level").is_ok());
+
+            assert_eq!(
+                anvil_save_chunk(provider, 4, 2, data, length),
+                AnvilStatus::Ok
+            );
+            anvil_bytes_free(data, length);
+
+            assert_eq!(anvil_delete_chunk(provider, 4, 2), AnvilStatus::Ok);
+            assert_eq!(
+                anvil_delete_chunk(provider, 4, 2),
+                AnvilStatus::ChunkNotFound
+            );
+            assert_eq!(
+                anvil_load_chunk(provider, 100, 100, &mut data, &mut length),
+                AnvilStatus::RegionNotFound
+            );
+
+            let message = CStr::from_ptr(anvil_last_error_message());
+            assert_eq!(message.to_str().unwrap(), "region 3 3 not found");
+
+            anvil_provider_free(fixture_provider);
+            anvil_provider_free(provider);
+        }
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        unsafe {
+            assert!(anvil_provider_open(ptr::null()).is_null());
+
+            let folder_path = CString::new("test/region").unwrap();
+            let provider = anvil_provider_open(folder_path.as_ptr());
+
+            assert_eq!(
+                anvil_save_chunk(provider, 0, 0, [1, 2, 3].as_ptr(), 3),
+                AnvilStatus::InvalidNbt
+            );
+            assert_eq!(
+                anvil_load_chunk(provider, 4, 2, ptr::null_mut(), ptr::null_mut()),
+                AnvilStatus::InvalidArgument
+            );
+
+            anvil_provider_free(provider);
+        }
+    }
+}
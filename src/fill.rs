@@ -0,0 +1,372 @@
+//! Bulk edits of blocks inside of box.
+//!
+//! [`fill`] sets every block of selection and [`replace`] changes blocks of one state
+//! into another, like WorldEdit `//set` and `//replace`. Only chunks and sections which
+//! intersect selection are read, and the palette of every section is looked up once
+//! instead of once per block.
+//!
+//! Missing chunks are skipped and selection is clipped to chunk height range. Block
+//! entities of changed blocks are removed. Light of changed chunks is invalidated for
+//! the game to recompute, heightmaps are left as they are.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::fill::{fill, replace};
+//! use anvil_region::schematic::BlockSelection;
+//! use anvil_region::section::BlockState;
+//! use anvil_region::AnvilChunkProvider;
+//!
+//! # let region_dir = tempfile::TempDir::new().unwrap();
+//! # let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+//! # let chunk_provider = AnvilChunkProvider::new(region_dir.path().to_str().unwrap());
+//! # anvil_region::relocate::copy_chunk(&fixture_chunk_provider, (4, 2), &chunk_provider, (4, 2)).unwrap();
+//! let selection = BlockSelection::new((64, 0, 32), (79, 15, 47));
+//! let stone = BlockState::new("minecraft:stone");
+//!
+//! replace(&chunk_provider, selection, &stone, &BlockState::new("minecraft:andesite")).unwrap();
+//!
+//! let fill_report = fill(&chunk_provider, selection, &stone).unwrap();
+//! assert_eq!(fill_report.modified_chunks, vec![(4, 2)]);
+//! ```
+use crate::chunk::{Chunk, ChunkTag};
+use crate::light::invalidate_light;
+use crate::schematic::{block_entity_position, BlockSelection};
+use crate::section::{
+    block_index, BlockState, PalettedContainer, Section, SectionError, SECTION_BLOCKS,
+};
+use crate::{AnvilChunkProvider, ChunkLoadError, ChunkSaveError};
+use nbt::Tag;
+use std::collections::HashSet;
+
+/// Possible errors while editing blocks.
+#[derive(Debug)]
+pub enum FillError {
+    /// Chunk can't be loaded.
+    ChunkLoadError { chunk_load_error: ChunkLoadError },
+    /// Chunk sections can't be read.
+    SectionError { section_error: SectionError },
+    /// Chunk can't be saved.
+    ChunkSaveError { chunk_save_error: ChunkSaveError },
+}
+
+impl From<ChunkLoadError> for FillError {
+    fn from(chunk_load_error: ChunkLoadError) -> Self {
+        FillError::ChunkLoadError { chunk_load_error }
+    }
+}
+
+impl From<SectionError> for FillError {
+    fn from(section_error: SectionError) -> Self {
+        FillError::SectionError { section_error }
+    }
+}
+
+impl From<ChunkSaveError> for FillError {
+    fn from(chunk_save_error: ChunkSaveError) -> Self {
+        FillError::ChunkSaveError { chunk_save_error }
+    }
+}
+
+/// Result of bulk edit.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FillReport {
+    /// Amount of blocks which state changed.
+    pub changed_blocks: usize,
+    /// Positions of chunks which were saved.
+    pub modified_chunks: Vec<(i32, i32)>,
+}
+
+/// Sets every block of selection to block state.
+pub fn fill(
+    chunk_provider: &AnvilChunkProvider,
+    selection: BlockSelection,
+    block_state: &BlockState,
+) -> Result<FillReport, FillError> {
+    edit_blocks(chunk_provider, selection, &BlockEdit::Fill { block_state })
+}
+
+/// Replaces blocks of selection which have state `from` with state `to`.
+///
+/// Properties must match exactly, so `minecraft:oak_stairs` facing east isn't
+/// replaced by state without properties.
+pub fn replace(
+    chunk_provider: &AnvilChunkProvider,
+    selection: BlockSelection,
+    from: &BlockState,
+    to: &BlockState,
+) -> Result<FillReport, FillError> {
+    edit_blocks(chunk_provider, selection, &BlockEdit::Replace { from, to })
+}
+
+enum BlockEdit<'a> {
+    Fill {
+        block_state: &'a BlockState,
+    },
+    Replace {
+        from: &'a BlockState,
+        to: &'a BlockState,
+    },
+}
+
+impl BlockEdit<'_> {
+    /// Returns true if edit changes block with state.
+    fn changes(&self, current_block_state: &BlockState) -> bool {
+        match self {
+            BlockEdit::Fill { block_state } => current_block_state != *block_state,
+            BlockEdit::Replace { from, to } => current_block_state == *from && from != to,
+        }
+    }
+
+    /// Applies edit to blocks at indices, returns amount of changed blocks.
+    fn apply(
+        &self,
+        block_states: &mut PalettedContainer<BlockState>,
+        indices: impl IntoIterator<Item = usize>,
+    ) -> usize {
+        match self {
+            BlockEdit::Fill { block_state } => {
+                block_states.set_many(indices, (*block_state).clone())
+            }
+            BlockEdit::Replace { from, to } => {
+                block_states.replace_many(indices, from, (*to).clone())
+            }
+        }
+    }
+}
+
+fn edit_blocks(
+    chunk_provider: &AnvilChunkProvider,
+    selection: BlockSelection,
+    block_edit: &BlockEdit,
+) -> Result<FillReport, FillError> {
+    let mut report = FillReport::default();
+
+    for chunk_x in selection.min.0 >> 4..=selection.max.0 >> 4 {
+        for chunk_z in selection.min.2 >> 4..=selection.max.2 >> 4 {
+            let mut chunk = match chunk_provider.load_chunk(chunk_x, chunk_z) {
+                Ok(chunk_compound_tag) => Chunk::new(chunk_compound_tag),
+                Err(ChunkLoadError::RegionNotFound { .. })
+                | Err(ChunkLoadError::ChunkNotFound { .. }) => continue,
+                Err(chunk_load_error) => return Err(chunk_load_error.into()),
+            };
+
+            let changed_blocks = edit_chunk(&mut chunk, (chunk_x, chunk_z), selection, block_edit)?;
+
+            if changed_blocks == 0 {
+                continue;
+            }
+
+            chunk_provider.save_chunk(chunk_x, chunk_z, chunk.into_compound_tag())?;
+
+            report.changed_blocks += changed_blocks;
+            report.modified_chunks.push((chunk_x, chunk_z));
+        }
+    }
+
+    Ok(report)
+}
+
+/// Edits blocks of selection inside of chunk, returns amount of changed blocks.
+fn edit_chunk(
+    chunk: &mut Chunk,
+    (chunk_x, chunk_z): (i32, i32),
+    selection: BlockSelection,
+    block_edit: &BlockEdit,
+) -> Result<usize, SectionError> {
+    let height_range = chunk.height_range();
+    let min_y = selection.min.1.max(height_range.min_y);
+    let max_y = selection.max.1.min(height_range.max_y() - 1);
+
+    if min_y > max_y {
+        return Ok(0);
+    }
+
+    let min_x = selection.min.0.max(chunk_x * 16) & 15;
+    let max_x = selection.max.0.min(chunk_x * 16 + 15) & 15;
+    let min_z = selection.min.2.max(chunk_z * 16) & 15;
+    let max_z = selection.max.2.min(chunk_z * 16 + 15) & 15;
+
+    let block_entity_positions: Vec<(i32, i32, i32)> = chunk
+        .block_entities()
+        .into_iter()
+        .map(block_entity_position)
+        .filter(|(x, y, z)| selection.contains((*x, *y, *z)) && (min_y..=max_y).contains(y))
+        .collect();
+
+    let section_format = chunk.section_format()?;
+    let mut changed_blocks = 0;
+    let mut changed_positions = HashSet::new();
+
+    for section_y in min_y >> 4..=max_y >> 4 {
+        let mut section = match chunk.section_compound_tag(section_y) {
+            Some(section_compound_tag) => {
+                Section::from_compound_tag(section_compound_tag, section_format)?
+            }
+            None => Section::new(section_y as i8),
+        };
+
+        // Section with only light contains air.
+        let block_states = section.block_states.get_or_insert_with(|| {
+            PalettedContainer::new(BlockState::new("minecraft:air"), SECTION_BLOCKS)
+        });
+
+        for &(x, y, z) in &block_entity_positions {
+            let index = block_index((x & 15) as usize, (y & 15) as usize, (z & 15) as usize);
+
+            let changes = block_states
+                .get(index)
+                .is_some_and(|block_state| block_edit.changes(block_state));
+
+            if y >> 4 == section_y && changes {
+                changed_positions.insert((x, y, z));
+            }
+        }
+
+        let section_min_y = min_y.max(section_y * 16) & 15;
+        let section_max_y = max_y.min(section_y * 16 + 15) & 15;
+
+        let indices = (section_min_y..=section_max_y).flat_map(|y| {
+            (min_z..=max_z).flat_map(move |z| {
+                (min_x..=max_x).map(move |x| block_index(x as usize, y as usize, z as usize))
+            })
+        });
+
+        let section_changed_blocks = block_edit.apply(block_states, indices);
+
+        if section_changed_blocks > 0 {
+            block_states.compact();
+            chunk.write_section(&section)?;
+            changed_blocks += section_changed_blocks;
+        }
+    }
+
+    if changed_blocks == 0 {
+        return Ok(0);
+    }
+
+    if let Some(Tag::List(tags)) = chunk.get_mut(ChunkTag::BlockEntities) {
+        tags.retain(|tag| match tag {
+            Tag::Compound(block_entity) => {
+                !changed_positions.contains(&block_entity_position(block_entity))
+            }
+            _ => true,
+        });
+    }
+
+    invalidate_light(chunk);
+
+    Ok(changed_blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chunk::{Chunk, ChunkTag};
+    use crate::fill::{fill, replace};
+    use crate::relocate::copy_chunk;
+    use crate::schematic::BlockSelection;
+    use crate::section::BlockState;
+    use crate::AnvilChunkProvider;
+    use nbt::{CompoundTag, Tag};
+    use tempfile::TempDir;
+
+    fn chunk_provider(temp_dir: &TempDir) -> AnvilChunkProvider<'_> {
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+        copy_chunk(&fixture_chunk_provider, (4, 2), &chunk_provider, (4, 2)).unwrap();
+
+        chunk_provider
+    }
+
+    #[test]
+    fn test_fill() {
+        let temp_dir = TempDir::new().unwrap();
+        let chunk_provider = chunk_provider(&temp_dir);
+        let glass = BlockState::new("minecraft:glass");
+
+        // Selection reaches into missing chunk and above the world.
+        let selection = BlockSelection::new((60, 250, 34), (66, 300, 35));
+        let fill_report = fill(&chunk_provider, selection, &glass).unwrap();
+
+        assert_eq!(fill_report.changed_blocks, 3 * 6 * 2);
+        assert_eq!(fill_report.modified_chunks, vec![(4, 2)]);
+
+        let chunk = Chunk::new(chunk_provider.load_chunk(4, 2).unwrap());
+        assert_eq!(chunk.block_state(2, 255, 3).unwrap().unwrap(), glass);
+        assert_ne!(chunk.block_state(3, 255, 3).unwrap().unwrap(), glass);
+        assert_ne!(chunk.block_state(2, 249, 3).unwrap().unwrap(), glass);
+
+        let fill_report = fill(&chunk_provider, selection, &glass).unwrap();
+
+        assert_eq!(fill_report.changed_blocks, 0);
+        assert!(fill_report.modified_chunks.is_empty());
+    }
+
+    #[test]
+    fn test_fill_entire_section() {
+        let temp_dir = TempDir::new().unwrap();
+        let chunk_provider = chunk_provider(&temp_dir);
+        let stone = BlockState::new("minecraft:stone");
+
+        let selection = BlockSelection::new((64, 16, 32), (79, 31, 47));
+        fill(&chunk_provider, selection, &stone).unwrap();
+
+        let chunk = Chunk::new(chunk_provider.load_chunk(4, 2).unwrap());
+        let section = chunk
+            .read_sections()
+            .unwrap()
+            .into_iter()
+            .find(|section| section.y == 1)
+            .unwrap();
+
+        assert_eq!(section.block_states.unwrap().palette(), &[stone]);
+    }
+
+    #[test]
+    fn test_replace() {
+        let temp_dir = TempDir::new().unwrap();
+        let chunk_provider = chunk_provider(&temp_dir);
+        let chest = BlockState::new("minecraft:chest");
+        let barrel = BlockState::new("minecraft:barrel");
+
+        let mut chunk = Chunk::new(chunk_provider.load_chunk(4, 2).unwrap());
+        let mut block_entities = Vec::new();
+
+        for x in 65..=66 {
+            chunk
+                .set_block_state((x & 15) as usize, 100, 1, chest.clone())
+                .unwrap();
+
+            let mut block_entity = CompoundTag::new();
+            block_entity.insert_str("id", "minecraft:chest");
+            block_entity.insert_i32("x", x);
+            block_entity.insert_i32("y", 100);
+            block_entity.insert_i32("z", 33);
+            block_entities.push(Tag::Compound(block_entity));
+        }
+
+        chunk.insert(ChunkTag::BlockEntities, Tag::List(block_entities));
+        chunk_provider
+            .save_chunk(4, 2, chunk.into_compound_tag())
+            .unwrap();
+
+        let selection = BlockSelection::new((60, 90, 30), (65, 110, 40));
+        let fill_report = replace(&chunk_provider, selection, &chest, &barrel).unwrap();
+
+        assert_eq!(fill_report.changed_blocks, 1);
+        assert_eq!(fill_report.modified_chunks, vec![(4, 2)]);
+
+        let chunk = Chunk::new(chunk_provider.load_chunk(4, 2).unwrap());
+        assert_eq!(chunk.block_state(1, 100, 1).unwrap().unwrap(), barrel);
+        assert_eq!(chunk.block_state(2, 100, 1).unwrap().unwrap(), chest);
+
+        // Block entity of replaced chest is removed.
+        let block_entities = chunk.block_entities();
+        assert_eq!(block_entities.len(), 1);
+        assert_eq!(block_entities[0].get_i32("x").unwrap(), 66);
+
+        let fill_report = replace(&chunk_provider, selection, &chest, &barrel).unwrap();
+        assert_eq!(fill_report.changed_blocks, 0);
+    }
+}
@@ -0,0 +1,478 @@
+//! Synthetic region files and worlds for tests.
+//!
+//! [`RegionFixture`] writes region file byte by byte instead of through provider, so
+//! besides valid chunks in every compression scheme it can contain oversized chunks
+//! and damage which the crate never writes itself. Output depends only on builder,
+//! chunk timestamps and payloads are fixed, so fixtures are reproducible.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::fixture::{ChunkCorruption, FixtureCompression, RegionFixture};
+//! use anvil_region::AnvilChunkProvider;
+//! use tempfile::TempDir;
+//!
+//! let region_dir = TempDir::new().unwrap();
+//!
+//! RegionFixture::new(0, 0)
+//!     .chunks(4)
+//!     .compression(FixtureCompression::Gzip)
+//!     .corrupt_chunk(3, 3, ChunkCorruption::OffsetPastEnd)
+//!     .write(region_dir.path())
+//!     .unwrap();
+//!
+//! let chunk_provider = AnvilChunkProvider::new(region_dir.path().to_str().unwrap());
+//! assert!(chunk_provider.load_chunk(0, 0).is_ok());
+//! assert!(chunk_provider.load_chunk(3, 3).is_err());
+//! ```
+use crate::data::DataFileError;
+use crate::level::LevelData;
+use crate::world::REGION_FOLDER;
+use crate::{
+    GZIP_COMPRESSION_TYPE, REGION_CHUNKS, REGION_HEADER_BYTES_LENGTH, REGION_SECTOR_BYTES_LENGTH,
+    UNCOMPRESSED_COMPRESSION_TYPE, ZLIB_COMPRESSION_TYPE,
+};
+use byteorder::{BigEndian, WriteBytesExt};
+use nbt::encode::{write_compound_tag, write_gzip_compound_tag, write_zlib_compound_tag};
+use nbt::CompoundTag;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Data version of fixture chunks, 1.13.2.
+pub const FIXTURE_DATA_VERSION: i32 = 1631;
+/// Save time of every fixture chunk in seconds since Unix epoch.
+pub const FIXTURE_TIMESTAMP: u32 = 1_600_000_000;
+
+/// Compression scheme of fixture chunks.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FixtureCompression {
+    Gzip,
+    Zlib,
+    Uncompressed,
+}
+
+impl FixtureCompression {
+    fn compression_type(self) -> u8 {
+        match self {
+            FixtureCompression::Gzip => GZIP_COMPRESSION_TYPE,
+            FixtureCompression::Zlib => ZLIB_COMPRESSION_TYPE,
+            FixtureCompression::Uncompressed => UNCOMPRESSED_COMPRESSION_TYPE,
+        }
+    }
+}
+
+/// Damage of chunk in region file.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ChunkCorruption {
+    /// Header offset points after the end of file.
+    OffsetPastEnd,
+    /// Header offset points into header.
+    OffsetInHeader,
+    /// Header offset points to sectors of the first chunk of region.
+    OverlappingSectors,
+    /// Length before chunk data is larger than its sectors.
+    LengthExceedsSectors,
+    /// Compression type of chunk data is not known.
+    UnknownCompression,
+    /// Compressed chunk data is cut in half.
+    TruncatedData,
+}
+
+/// Builder of region file with synthetic chunks.
+#[derive(Debug, Clone)]
+pub struct RegionFixture {
+    region_x: i32,
+    region_z: i32,
+    compression: FixtureCompression,
+    data_version: i32,
+    /// Length of incompressible payload keyed by region position of chunk.
+    chunks: BTreeMap<(u8, u8), usize>,
+    corruptions: BTreeMap<(u8, u8), ChunkCorruption>,
+}
+
+impl RegionFixture {
+    /// Creates region without chunks, compressed with zlib like the game does.
+    pub fn new(region_x: i32, region_z: i32) -> Self {
+        RegionFixture {
+            region_x,
+            region_z,
+            compression: FixtureCompression::Zlib,
+            data_version: FIXTURE_DATA_VERSION,
+            chunks: BTreeMap::new(),
+            corruptions: BTreeMap::new(),
+        }
+    }
+
+    /// Adds chunks in square of size from north-west corner of region, at most 32.
+    pub fn chunks(mut self, size: u8) -> Self {
+        for chunk_z in 0..size.min(32) {
+            for chunk_x in 0..size.min(32) {
+                self.chunks.entry((chunk_x, chunk_z)).or_insert(0);
+            }
+        }
+
+        self
+    }
+
+    /// Adds chunk at region coordinates.
+    pub fn chunk(mut self, chunk_x: u8, chunk_z: u8) -> Self {
+        self.chunks.entry((chunk_x & 31, chunk_z & 31)).or_insert(0);
+        self
+    }
+
+    /// Adds chunk with incompressible payload of length, so it spans many sectors.
+    pub fn oversized_chunk(mut self, chunk_x: u8, chunk_z: u8, payload_length: usize) -> Self {
+        self.chunks
+            .insert((chunk_x & 31, chunk_z & 31), payload_length);
+        self
+    }
+
+    /// Damages chunk at region coordinates, adding it if it's missing.
+    pub fn corrupt_chunk(mut self, chunk_x: u8, chunk_z: u8, corruption: ChunkCorruption) -> Self {
+        self = self.chunk(chunk_x, chunk_z);
+        self.corruptions
+            .insert((chunk_x & 31, chunk_z & 31), corruption);
+        self
+    }
+
+    pub fn compression(mut self, compression: FixtureCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn data_version(mut self, data_version: i32) -> Self {
+        self.data_version = data_version;
+        self
+    }
+
+    /// Returns compound tag of chunk at region coordinates as fixture writes it.
+    pub fn chunk_compound_tag(&self, chunk_x: u8, chunk_z: u8) -> CompoundTag {
+        let payload_length = self
+            .chunks
+            .get(&(chunk_x & 31, chunk_z & 31))
+            .copied()
+            .unwrap_or(0);
+
+        let chunk_x = (self.region_x << 5) + (chunk_x & 31) as i32;
+        let chunk_z = (self.region_z << 5) + (chunk_z & 31) as i32;
+
+        let mut level_compound_tag = CompoundTag::new();
+        level_compound_tag.insert_i32("xPos", chunk_x);
+        level_compound_tag.insert_i32("zPos", chunk_z);
+        level_compound_tag.insert_str("Status", "full");
+        level_compound_tag.insert_i64("InhabitedTime", 0);
+        level_compound_tag.insert_compound_tag_vec("Sections", Vec::new());
+
+        if payload_length > 0 {
+            level_compound_tag.insert_i8_vec("Payload", payload(chunk_x, chunk_z, payload_length));
+        }
+
+        let mut chunk_compound_tag = CompoundTag::new();
+        chunk_compound_tag.insert_i32("DataVersion", self.data_version);
+        chunk_compound_tag.insert_compound_tag("Level", level_compound_tag);
+
+        chunk_compound_tag
+    }
+
+    /// Returns contents of region file.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+        let mut offsets = [0u32; REGION_CHUNKS];
+        let mut bytes = vec![0; REGION_HEADER_BYTES_LENGTH as usize];
+        let sector_length = REGION_SECTOR_BYTES_LENGTH as usize;
+
+        // Chunks are laid out one after another in order of header.
+        let mut chunk_positions: Vec<_> = self.chunks.keys().copied().collect();
+        chunk_positions.sort_unstable_by_key(|&(chunk_x, chunk_z)| (chunk_z, chunk_x));
+
+        let mut first_offset = None;
+
+        for (chunk_x, chunk_z) in chunk_positions {
+            let corruption = self.corruptions.get(&(chunk_x, chunk_z)).copied();
+            let mut data = self.encode_chunk(chunk_x, chunk_z)?;
+
+            if corruption == Some(ChunkCorruption::TruncatedData) {
+                data.truncate(data.len() / 2);
+            }
+
+            let sector_index = bytes.len() / sector_length;
+            let sectors = (data.len() + 5).div_ceil(sector_length);
+
+            let length = match corruption {
+                Some(ChunkCorruption::LengthExceedsSectors) => (sectors * sector_length) as u32,
+                _ => data.len() as u32 + 1,
+            };
+            let compression_type = match corruption {
+                Some(ChunkCorruption::UnknownCompression) => 0x7F,
+                _ => self.compression.compression_type(),
+            };
+
+            bytes.write_u32::<BigEndian>(length)?;
+            bytes.write_u8(compression_type)?;
+            bytes.extend_from_slice(&data);
+            bytes.resize((sector_index + sectors) * sector_length, 0);
+
+            let first_offset = *first_offset.get_or_insert(sector_index as u32);
+            let sector_index = match corruption {
+                Some(ChunkCorruption::OffsetPastEnd) => 0xFF_FFFF,
+                Some(ChunkCorruption::OffsetInHeader) => 1,
+                Some(ChunkCorruption::OverlappingSectors) => first_offset,
+                _ => sector_index as u32,
+            };
+
+            offsets[chunk_z as usize * 32 + chunk_x as usize] =
+                (sector_index << 8) | sectors.min(255) as u32;
+        }
+
+        let mut header = &mut bytes[..REGION_HEADER_BYTES_LENGTH as usize];
+
+        for offset in offsets.iter() {
+            header.write_u32::<BigEndian>(*offset)?;
+        }
+
+        for offset in offsets.iter() {
+            let timestamp = if *offset == 0 { 0 } else { FIXTURE_TIMESTAMP };
+            header.write_u32::<BigEndian>(timestamp)?;
+        }
+
+        Ok(bytes)
+    }
+
+    /// Writes region file into folder, returns its path.
+    pub fn write(&self, folder_path: &Path) -> Result<PathBuf, io::Error> {
+        fs::create_dir_all(folder_path)?;
+
+        let region_name = format!("r.{}.{}.mca", self.region_x, self.region_z);
+        let region_path = folder_path.join(region_name);
+        fs::write(&region_path, self.to_bytes()?)?;
+
+        Ok(region_path)
+    }
+
+    fn encode_chunk(&self, chunk_x: u8, chunk_z: u8) -> Result<Vec<u8>, io::Error> {
+        let chunk_compound_tag = self.chunk_compound_tag(chunk_x, chunk_z);
+        let mut data = Vec::new();
+
+        match self.compression {
+            FixtureCompression::Gzip => write_gzip_compound_tag(&mut data, &chunk_compound_tag)?,
+            FixtureCompression::Zlib => write_zlib_compound_tag(&mut data, &chunk_compound_tag)?,
+            FixtureCompression::Uncompressed => write_compound_tag(&mut data, &chunk_compound_tag)?,
+        }
+
+        Ok(data)
+    }
+}
+
+/// Builder of world folder with `level.dat` and square of chunks around 0, 0.
+#[derive(Debug, Clone)]
+pub struct WorldFixture {
+    size: u32,
+    compression: FixtureCompression,
+    corruptions: BTreeMap<(i32, i32), ChunkCorruption>,
+}
+
+impl WorldFixture {
+    /// Creates world with size by size chunks from chunk 0, 0 towards south-east.
+    pub fn new(size: u32) -> Self {
+        WorldFixture {
+            size,
+            compression: FixtureCompression::Zlib,
+            corruptions: BTreeMap::new(),
+        }
+    }
+
+    pub fn compression(mut self, compression: FixtureCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Damages chunk at world coordinates, adding it if it's missing.
+    pub fn corrupt_chunk(
+        mut self,
+        chunk_x: i32,
+        chunk_z: i32,
+        corruption: ChunkCorruption,
+    ) -> Self {
+        self.corruptions.insert((chunk_x, chunk_z), corruption);
+        self
+    }
+
+    /// Returns region fixtures of overworld keyed by region position.
+    pub fn region_fixtures(&self) -> BTreeMap<(i32, i32), RegionFixture> {
+        let mut region_fixtures = BTreeMap::new();
+        let size = self.size.min(i32::MAX as u32) as i32;
+
+        let chunk_positions = (0..size)
+            .flat_map(|chunk_z| (0..size).map(move |chunk_x| (chunk_x, chunk_z)))
+            .chain(self.corruptions.keys().copied());
+
+        for (chunk_x, chunk_z) in chunk_positions {
+            let region_position = (chunk_x >> 5, chunk_z >> 5);
+            let region_fixture = region_fixtures.entry(region_position).or_insert_with(|| {
+                RegionFixture::new(region_position.0, region_position.1)
+                    .compression(self.compression)
+            });
+
+            let (region_chunk_x, region_chunk_z) = ((chunk_x & 31) as u8, (chunk_z & 31) as u8);
+
+            *region_fixture = match self.corruptions.get(&(chunk_x, chunk_z)) {
+                Some(corruption) => region_fixture.clone().corrupt_chunk(
+                    region_chunk_x,
+                    region_chunk_z,
+                    *corruption,
+                ),
+                None => region_fixture.clone().chunk(region_chunk_x, region_chunk_z),
+            };
+        }
+
+        region_fixtures
+    }
+
+    /// Writes `level.dat` and overworld region files into world folder.
+    pub fn write(&self, world_folder_path: &Path) -> Result<(), DataFileError> {
+        let write_error = |io_error| DataFileError::WriteError { io_error };
+
+        fs::create_dir_all(world_folder_path).map_err(write_error)?;
+
+        let mut level_data = LevelData::new();
+        level_data.data_mut().insert_str("LevelName", "Fixture");
+        level_data
+            .data_mut()
+            .insert_i32("DataVersion", FIXTURE_DATA_VERSION);
+        level_data.save(world_folder_path)?;
+
+        let region_folder_path = world_folder_path.join(REGION_FOLDER);
+
+        for region_fixture in self.region_fixtures().values() {
+            region_fixture
+                .write(&region_folder_path)
+                .map_err(write_error)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns pseudo-random bytes seeded by chunk position, which don't compress.
+fn payload(chunk_x: i32, chunk_z: i32, length: usize) -> Vec<i8> {
+    let mut state =
+        (((chunk_x as u32 as u64) << 32) | chunk_z as u32 as u64) ^ 0x9E37_79B9_7F4A_7C15;
+    let mut payload = Vec::with_capacity(length);
+
+    while payload.len() < length {
+        // Xorshift generator.
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        payload.push(state as i8);
+    }
+
+    payload
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fixture::{
+        ChunkCorruption, FixtureCompression, RegionFixture, WorldFixture, FIXTURE_TIMESTAMP,
+    };
+    use crate::parse::ParseMode;
+    use crate::world::{AnvilWorld, Dimension};
+    use crate::AnvilChunkProvider;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_region_fixture_compressions() {
+        for compression in &[FixtureCompression::Gzip, FixtureCompression::Zlib] {
+            let region_dir = TempDir::new().unwrap();
+            let region_fixture = RegionFixture::new(-1, 2)
+                .chunks(3)
+                .oversized_chunk(10, 10, 300 * 1024)
+                .compression(*compression);
+            region_fixture.write(region_dir.path()).unwrap();
+
+            let chunk_provider = AnvilChunkProvider::new(region_dir.path().to_str().unwrap());
+            assert_eq!(chunk_provider.chunk_positions().unwrap().len(), 10);
+
+            let chunk_compound_tag = chunk_provider.load_chunk(-30, 66).unwrap();
+            assert_eq!(
+                format!("{:?}", chunk_compound_tag),
+                format!("{:?}", region_fixture.chunk_compound_tag(2, 2))
+            );
+
+            let oversized_compound_tag = chunk_provider.load_chunk(-22, 74).unwrap();
+            assert_eq!(
+                format!("{:?}", oversized_compound_tag),
+                format!("{:?}", region_fixture.chunk_compound_tag(10, 10))
+            );
+            assert_eq!(
+                chunk_provider.chunk_last_modified(-22, 74).unwrap(),
+                Some(FIXTURE_TIMESTAMP)
+            );
+        }
+
+        // Provider doesn't load uncompressed chunks, but finds them.
+        let region_dir = TempDir::new().unwrap();
+        RegionFixture::new(0, 0)
+            .chunks(2)
+            .compression(FixtureCompression::Uncompressed)
+            .write(region_dir.path())
+            .unwrap();
+
+        let chunk_provider = AnvilChunkProvider::new(region_dir.path().to_str().unwrap());
+        assert_eq!(chunk_provider.chunk_positions().unwrap().len(), 4);
+
+        // Fixture is reproducible.
+        let region_fixture = RegionFixture::new(0, 0).oversized_chunk(0, 0, 1024);
+        assert_eq!(
+            region_fixture.to_bytes().unwrap(),
+            region_fixture.to_bytes().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_region_fixture_corruptions() {
+        let corruptions = [
+            ChunkCorruption::OffsetPastEnd,
+            ChunkCorruption::OffsetInHeader,
+            ChunkCorruption::LengthExceedsSectors,
+            ChunkCorruption::UnknownCompression,
+            ChunkCorruption::TruncatedData,
+        ];
+
+        let mut region_fixture = RegionFixture::new(0, 0).chunk(0, 0);
+
+        for (index, corruption) in corruptions.iter().enumerate() {
+            region_fixture = region_fixture.corrupt_chunk(index as u8 + 1, 0, *corruption);
+        }
+
+        let region_dir = TempDir::new().unwrap();
+        region_fixture.write(region_dir.path()).unwrap();
+
+        let chunk_provider = AnvilChunkProvider::new(region_dir.path().to_str().unwrap())
+            .parse_mode(ParseMode::Strict);
+        assert!(chunk_provider.load_chunk(0, 0).is_ok());
+
+        for (index, corruption) in corruptions.iter().enumerate() {
+            let result = chunk_provider.load_chunk(index as i32 + 1, 0);
+            assert!(result.is_err(), "{:?}", corruption);
+        }
+    }
+
+    #[test]
+    fn test_world_fixture() {
+        let world_dir = TempDir::new().unwrap();
+
+        WorldFixture::new(40)
+            .compression(FixtureCompression::Gzip)
+            .corrupt_chunk(-1, -1, ChunkCorruption::TruncatedData)
+            .write(world_dir.path())
+            .unwrap();
+
+        let world = AnvilWorld::open(world_dir.path()).unwrap();
+        let results: Vec<_> = world.iter_chunks(&Dimension::Overworld).unwrap().collect();
+
+        assert_eq!(results.len(), 40 * 40 + 1);
+        assert_eq!(results.iter().filter(|result| result.is_err()).count(), 1);
+    }
+}
@@ -0,0 +1,131 @@
+//! Fast hashing of chunk and region coordinates.
+//!
+//! Caches keyed by coordinates hash a pair of integers on every lookup, for which
+//! SipHash of standard maps is needlessly slow. [`CoordinateHasher`] multiplies and
+//! rotates words like FxHash of rustc. It isn't resistant to collision attacks, which
+//! doesn't matter for coordinates of chunks in memory, but coordinates coming from
+//! untrusted clients can be hashed with SipHash of standard maps by enabling `siphash`
+//! feature, which switches every cache of crate to [`CoordinateBuildHasher`] of it.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::hash::CoordinateHashMap;
+//!
+//! let mut chunk_names = CoordinateHashMap::default();
+//! chunk_names.insert((4, 2), "spawn");
+//!
+//! assert_eq!(chunk_names.get(&(4, 2)), Some(&"spawn"));
+//! ```
+use std::collections::{HashMap, HashSet};
+use std::hash::Hasher;
+
+/// Multiplier of FxHash, odd with bits spread over the whole word.
+const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+/// Builder of hashers used by coordinate maps and sets of crate.
+#[cfg(not(feature = "siphash"))]
+pub type CoordinateBuildHasher = std::hash::BuildHasherDefault<CoordinateHasher>;
+/// Builder of hashers used by coordinate maps and sets of crate.
+#[cfg(feature = "siphash")]
+pub type CoordinateBuildHasher = std::collections::hash_map::RandomState;
+
+/// Map keyed by coordinates.
+pub type CoordinateHashMap<K, V> = HashMap<K, V, CoordinateBuildHasher>;
+/// Set of coordinates.
+pub type CoordinateHashSet<K> = HashSet<K, CoordinateBuildHasher>;
+
+/// Hasher of coordinates, fast but not resistant to collision attacks.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CoordinateHasher {
+    hash: u64,
+}
+
+impl CoordinateHasher {
+    fn add_word(&mut self, word: u64) {
+        self.hash = (self.hash.rotate_left(5) ^ word).wrapping_mul(SEED);
+    }
+}
+
+impl Hasher for CoordinateHasher {
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(8);
+
+        for chunk in &mut chunks {
+            let mut word = [0; 8];
+            word.copy_from_slice(chunk);
+            self.add_word(u64::from_le_bytes(word));
+        }
+
+        for byte in chunks.remainder() {
+            self.add_word(*byte as u64);
+        }
+    }
+
+    fn write_u8(&mut self, value: u8) {
+        self.add_word(value as u64);
+    }
+
+    fn write_u16(&mut self, value: u16) {
+        self.add_word(value as u64);
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.add_word(value as u64);
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.add_word(value);
+    }
+
+    fn write_usize(&mut self, value: usize) {
+        self.add_word(value as u64);
+    }
+
+    fn write_i32(&mut self, value: i32) {
+        self.write_u32(value as u32);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hash::{CoordinateHashMap, CoordinateHashSet, CoordinateHasher};
+    use std::hash::{Hash, Hasher};
+
+    fn hash(value: impl Hash) -> u64 {
+        let mut hasher = CoordinateHasher::default();
+        value.hash(&mut hasher);
+
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_coordinates_hash() {
+        assert_eq!(hash((4, 2)), hash((4, 2)));
+        assert_ne!(hash((4, 2)), hash((2, 4)));
+        assert_ne!(hash((-1, 0)), hash((0, -1)));
+
+        let mut hashes = CoordinateHashSet::default();
+
+        for x in -32..32 {
+            for z in -32..32 {
+                hashes.insert(hash((x, z)));
+            }
+        }
+
+        assert_eq!(hashes.len(), 64 * 64);
+    }
+
+    #[test]
+    fn test_coordinate_hash_map() {
+        let mut map = CoordinateHashMap::default();
+        map.insert((4, 2), "chunk");
+
+        assert_eq!(map.get(&(4, 2)), Some(&"chunk"));
+        assert_eq!(map.get(&(2, 4)), None);
+    }
+}
@@ -0,0 +1,278 @@
+//! Reading only headers of region files.
+//!
+//! [`RegionHeaders`] reads 8 KB header of chunk offsets and timestamps without chunk
+//! data and without opening file for writing, so listing chunks, checking their
+//! presence and reading save times of thousands of region files costs one small read
+//! per file.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::headers::RegionHeaders;
+//!
+//! let region_headers = RegionHeaders::open("test/region/r.0.0.mca").unwrap();
+//!
+//! assert!(region_headers.has_chunk(4, 2));
+//! assert!(region_headers.chunk_last_modified(4, 2).is_some());
+//! ```
+use crate::metrics::Metrics;
+use crate::trace::trace_event;
+use crate::{AnvilChunkMetadata, AnvilRegion, REGION_CHUNKS, REGION_HEADER_BYTES_LENGTH};
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use std::{fs, io};
+
+/// Maximum amount of region headers cached by folder provider, about 12 KB each.
+pub const HEADER_CACHE_CAPACITY: usize = 1024;
+
+/// Chunk offsets and timestamps of region file.
+pub struct RegionHeaders {
+    /// Array of chunks metadata.
+    chunks_metadata: [AnvilChunkMetadata; REGION_CHUNKS],
+}
+
+impl RegionHeaders {
+    pub(crate) fn new(chunks_metadata: [AnvilChunkMetadata; REGION_CHUNKS]) -> Self {
+        RegionHeaders { chunks_metadata }
+    }
+
+    /// Reads header of region file from reader, nothing past header is read.
+    ///
+    /// Contents shorter than header are padded with zeros like when region file is
+    /// opened for writing, so missing part doesn't contain chunks.
+    pub fn read(reader: &mut impl Read) -> Result<Self, io::Error> {
+        AnvilRegion::open_headers_only(reader)
+    }
+
+    /// Reads header of region file at the specified path.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, io::Error> {
+        RegionHeaders::read(&mut File::open(path)?)
+    }
+
+    /// Returns true if chunk at the specified region coordinates is present.
+    pub fn has_chunk(&self, chunk_x: u8, chunk_z: u8) -> bool {
+        !self.metadata(chunk_x, chunk_z).is_empty()
+    }
+
+    /// Returns region coordinates of chunks which are present in region.
+    pub fn chunk_positions(&self) -> Vec<(u8, u8)> {
+        let mut chunk_positions = Vec::new();
+
+        for (index, metadata) in self.chunks_metadata.iter().enumerate() {
+            if !metadata.is_empty() {
+                chunk_positions.push(((index % 32) as u8, (index / 32) as u8));
+            }
+        }
+
+        chunk_positions
+    }
+
+    /// Returns time in seconds since Unix epoch when chunk was last saved.
+    ///
+    /// Returns none if chunk is not present.
+    pub fn chunk_last_modified(&self, chunk_x: u8, chunk_z: u8) -> Option<u32> {
+        let metadata = self.metadata(chunk_x, chunk_z);
+
+        if metadata.is_empty() {
+            return None;
+        }
+
+        Some(metadata.last_modified_timestamp)
+    }
+
+    /// Returns chunk metadata at specified coordinates.
+    pub(crate) fn metadata(&self, chunk_x: u8, chunk_z: u8) -> AnvilChunkMetadata {
+        self.chunks_metadata[AnvilRegion::metadata_index(chunk_x, chunk_z)]
+    }
+}
+
+/// Headers of region files keyed by path, oldest are evicted above capacity.
+pub(crate) struct HeaderCache {
+    entries: HashMap<PathBuf, CachedHeaders>,
+    /// Paths of cached headers, oldest first.
+    order: VecDeque<PathBuf>,
+}
+
+/// Header with modification time and length of file it was read from.
+struct CachedHeaders {
+    modified: SystemTime,
+    length: u64,
+    region_headers: Arc<RegionHeaders>,
+}
+
+impl HeaderCache {
+    pub(crate) fn new() -> Self {
+        HeaderCache {
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Returns header of region file, none if file doesn't exist.
+    ///
+    /// Cached header is reused while modification time and length of file stay the
+    /// same, so checking it costs one metadata call instead of reading header.
+    pub(crate) fn region_headers(
+        &mut self,
+        path: &Path,
+        metrics: &dyn Metrics,
+    ) -> Result<Option<Arc<RegionHeaders>>, io::Error> {
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(io_error) if io_error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(io_error) => return Err(io_error),
+        };
+
+        let modified = metadata.modified()?;
+        let length = metadata.len();
+
+        if let Some(cached_headers) = self.entries.get(path) {
+            if cached_headers.modified == modified && cached_headers.length == length {
+                trace_event!(path = %path.display(), "region header cache hit");
+                metrics.cache_hit();
+                return Ok(Some(cached_headers.region_headers.clone()));
+            }
+        }
+
+        trace_event!(path = %path.display(), "region header cache miss");
+        metrics.cache_miss();
+        metrics.bytes_read(length.min(REGION_HEADER_BYTES_LENGTH));
+
+        // File changed after metadata was read has newer modification time than
+        // cached one, so its header is read again next time.
+        let region_headers = Arc::new(RegionHeaders::open(path)?);
+
+        let cached_headers = CachedHeaders {
+            modified,
+            length,
+            region_headers: region_headers.clone(),
+        };
+
+        if self
+            .entries
+            .insert(path.to_path_buf(), cached_headers)
+            .is_none()
+        {
+            self.order.push_back(path.to_path_buf());
+        }
+
+        while self.entries.len() > HEADER_CACHE_CAPACITY {
+            match self.order.pop_front() {
+                Some(path) => {
+                    self.entries.remove(&path);
+                }
+                None => break,
+            }
+        }
+
+        Ok(Some(region_headers))
+    }
+
+    /// Returns amount of cached headers.
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Drops cached header of region file, for example after file was written.
+    ///
+    /// Modification time may not change when file is written twice quickly.
+    pub(crate) fn invalidate(&mut self, path: &Path) {
+        if self.entries.remove(path).is_some() {
+            self.order.retain(|cached_path| cached_path != path);
+        }
+    }
+
+    /// Drops all cached headers.
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::headers::{HeaderCache, RegionHeaders};
+    use crate::metrics::NoopMetrics;
+    use crate::AnvilRegion;
+    use std::fs;
+    use std::io::Read;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_open() {
+        let region_headers = RegionHeaders::open("test/region/r.0.0.mca").unwrap();
+        let region = AnvilRegion::new("test/region/r.0.0.mca").unwrap();
+
+        assert_eq!(region_headers.chunk_positions(), region.chunk_positions());
+        assert!(region_headers.has_chunk(15, 3));
+        assert!(!region_headers.has_chunk(15, 14));
+        assert!(region_headers.chunk_last_modified(15, 14).is_none());
+    }
+
+    #[test]
+    fn test_read_only_header() {
+        let data = fs::read("test/region/r.0.0.mca").unwrap();
+        let mut reader = data.as_slice();
+
+        let region_headers = RegionHeaders::read(&mut reader).unwrap();
+        assert!(region_headers.has_chunk(4, 2));
+
+        // Chunk data is left unread.
+        let mut remaining = Vec::new();
+        reader.read_to_end(&mut remaining).unwrap();
+        assert_eq!(remaining, &data[8192..]);
+    }
+
+    #[test]
+    fn test_read_short_header() {
+        let region_headers = RegionHeaders::read(&mut &[][..]).unwrap();
+        assert!(region_headers.chunk_positions().is_empty());
+
+        // Offset of chunk at 0, 0 without timestamps.
+        let region_headers = RegionHeaders::read(&mut &[0, 0, 2, 1][..]).unwrap();
+        assert_eq!(region_headers.chunk_positions(), vec![(0, 0)]);
+        assert_eq!(region_headers.chunk_last_modified(0, 0), Some(0));
+    }
+
+    #[test]
+    fn test_header_cache() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("r.0.0.mca");
+        let mut header_cache = HeaderCache::new();
+
+        assert!(header_cache
+            .region_headers(&path, &NoopMetrics)
+            .unwrap()
+            .is_none());
+
+        fs::copy("test/region/r.0.0.mca", &path).unwrap();
+
+        let region_headers = header_cache
+            .region_headers(&path, &NoopMetrics)
+            .unwrap()
+            .unwrap();
+        let cached_region_headers = header_cache
+            .region_headers(&path, &NoopMetrics)
+            .unwrap()
+            .unwrap();
+        assert!(Arc::ptr_eq(&region_headers, &cached_region_headers));
+
+        // Changed length is detected without invalidation.
+        fs::write(&path, []).unwrap();
+
+        let region_headers = header_cache
+            .region_headers(&path, &NoopMetrics)
+            .unwrap()
+            .unwrap();
+        assert!(region_headers.chunk_positions().is_empty());
+
+        header_cache.invalidate(&path);
+        assert!(header_cache.entries.is_empty());
+        assert!(header_cache.order.is_empty());
+    }
+}
@@ -0,0 +1,229 @@
+//! Compressed sizes and inhabited times of chunks of areas for heatmaps.
+//!
+//! [`area_heatmap`] reads every chunk of rectangle once, so tools can color chunks
+//! by their size to find bloated areas, like farms with thousands of entities, or by
+//! `InhabitedTime` to find areas where players spend their time.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::heatmap::region_heatmap;
+//! use anvil_region::AnvilChunkProvider;
+//!
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//! let heatmap = region_heatmap(&chunk_provider, 0, 0).unwrap();
+//!
+//! let chunk_heat = heatmap.chunk_heat(4, 2).unwrap();
+//! assert!(chunk_heat.compressed_bytes <= heatmap.max_compressed_bytes().unwrap());
+//! ```
+use crate::chunk::Chunk;
+use crate::{decode_chunk, AnvilChunkProvider, AnvilRegion, REGION_SECTOR_BYTES_LENGTH};
+use std::io;
+
+/// Size and inhabited time of single chunk.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChunkHeat {
+    /// Length of compressed chunk data, length of its sectors if data can't be read.
+    pub compressed_bytes: u32,
+    /// Ticks players spent near chunk, none if missing or chunk can't be decoded.
+    pub inhabited_time: Option<i64>,
+}
+
+/// Heat of chunks of rectangle, missing chunks have no heat.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChunkHeatmap {
+    /// Chunk X of the west edge.
+    pub min_chunk_x: i32,
+    /// Chunk Z of the north edge.
+    pub min_chunk_z: i32,
+    /// Amount of chunks along X.
+    pub width: usize,
+    /// Amount of chunks along Z.
+    pub length: usize,
+    /// Heat of every chunk row by row from north-west corner.
+    pub chunks: Vec<Option<ChunkHeat>>,
+}
+
+impl ChunkHeatmap {
+    /// Returns heat of chunk, none if it's outside of heatmap or missing.
+    pub fn chunk_heat(&self, chunk_x: i32, chunk_z: i32) -> Option<&ChunkHeat> {
+        if chunk_x < self.min_chunk_x || chunk_z < self.min_chunk_z {
+            return None;
+        }
+
+        let x = (chunk_x as i64 - self.min_chunk_x as i64) as usize;
+        let z = (chunk_z as i64 - self.min_chunk_z as i64) as usize;
+
+        if x >= self.width || z >= self.length {
+            return None;
+        }
+
+        self.chunks[z * self.width + x].as_ref()
+    }
+
+    /// Returns the largest compressed length, none if heatmap has no chunks.
+    pub fn max_compressed_bytes(&self) -> Option<u32> {
+        self.chunks
+            .iter()
+            .flatten()
+            .map(|chunk_heat| chunk_heat.compressed_bytes)
+            .max()
+    }
+
+    /// Returns the longest inhabited time, none if no chunk has it.
+    pub fn max_inhabited_time(&self) -> Option<i64> {
+        self.chunks
+            .iter()
+            .flatten()
+            .filter_map(|chunk_heat| chunk_heat.inhabited_time)
+            .max()
+    }
+}
+
+/// Collects heat of chunks of region at the specified region coordinates.
+pub fn region_heatmap(
+    chunk_provider: &AnvilChunkProvider,
+    region_x: i32,
+    region_z: i32,
+) -> Result<ChunkHeatmap, io::Error> {
+    let min_chunk = (region_x << 5, region_z << 5);
+    let max_chunk = (min_chunk.0 + 31, min_chunk.1 + 31);
+
+    area_heatmap(chunk_provider, min_chunk, max_chunk)
+}
+
+/// Collects heat of chunks between lowest and highest chunk X and Z inclusive.
+///
+/// Every chunk is decoded to read its `InhabitedTime`, chunks which can't be read
+/// are reported with length of their sectors.
+pub fn area_heatmap(
+    chunk_provider: &AnvilChunkProvider,
+    min_chunk: (i32, i32),
+    max_chunk: (i32, i32),
+) -> Result<ChunkHeatmap, io::Error> {
+    let (min_chunk_x, max_chunk_x) = (min_chunk.0.min(max_chunk.0), min_chunk.0.max(max_chunk.0));
+    let (min_chunk_z, max_chunk_z) = (min_chunk.1.min(max_chunk.1), min_chunk.1.max(max_chunk.1));
+    let width = (max_chunk_x as i64 - min_chunk_x as i64 + 1) as usize;
+    let length = (max_chunk_z as i64 - min_chunk_z as i64 + 1) as usize;
+
+    let mut chunks = vec![None; width * length];
+
+    for region_z in min_chunk_z >> 5..=max_chunk_z >> 5 {
+        for region_x in min_chunk_x >> 5..=max_chunk_x >> 5 {
+            let region_name = format!("r.{}.{}.mca", region_x, region_z);
+            let region_path = chunk_provider.folder_path.join(region_name);
+
+            // Region is opened only if it exists, since opening creates it.
+            if !region_path.is_file() {
+                continue;
+            }
+
+            let mut region = AnvilRegion::new(&region_path)?;
+
+            for (region_chunk_x, region_chunk_z) in region.chunk_positions() {
+                let chunk_x = (region_x << 5) + region_chunk_x as i32;
+                let chunk_z = (region_z << 5) + region_chunk_z as i32;
+
+                if chunk_x < min_chunk_x
+                    || chunk_x > max_chunk_x
+                    || chunk_z < min_chunk_z
+                    || chunk_z > max_chunk_z
+                {
+                    continue;
+                }
+
+                let chunk_heat = match region.read_chunk_data(region_chunk_x, region_chunk_z) {
+                    Ok((compression_scheme, compressed_buffer)) => ChunkHeat {
+                        compressed_bytes: compressed_buffer.len() as u32,
+                        inhabited_time: decode_chunk(compression_scheme, &compressed_buffer)
+                            .ok()
+                            .and_then(|chunk_compound_tag| {
+                                Chunk::new(chunk_compound_tag)
+                                    .data()
+                                    .get_i64("InhabitedTime")
+                                    .ok()
+                            }),
+                    },
+                    Err(_) => {
+                        let metadata = region.get_metadata(region_chunk_x, region_chunk_z);
+
+                        ChunkHeat {
+                            compressed_bytes: metadata.sectors as u32
+                                * REGION_SECTOR_BYTES_LENGTH as u32,
+                            inhabited_time: None,
+                        }
+                    }
+                };
+
+                let index =
+                    (chunk_z - min_chunk_z) as usize * width + (chunk_x - min_chunk_x) as usize;
+                chunks[index] = Some(chunk_heat);
+            }
+        }
+    }
+
+    Ok(ChunkHeatmap {
+        min_chunk_x,
+        min_chunk_z,
+        width,
+        length,
+        chunks,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::heatmap::{area_heatmap, region_heatmap};
+    use crate::AnvilChunkProvider;
+    use nbt::CompoundTag;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_area_heatmap() {
+        let temp_dir = TempDir::new().unwrap();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+        let mut chunk_compound_tag = fixture_chunk_provider.load_chunk(4, 2).unwrap();
+
+        for (chunk_x, inhabited_time) in &[(-1, 20), (0, 7000)] {
+            let level_compound_tag = chunk_compound_tag
+                .get_mut::<&mut CompoundTag>("Level")
+                .unwrap();
+            level_compound_tag.insert_i64("InhabitedTime", *inhabited_time);
+
+            chunk_provider
+                .save_chunk(*chunk_x, 0, chunk_compound_tag.clone())
+                .unwrap();
+        }
+
+        let heatmap = area_heatmap(&chunk_provider, (1, 1), (-2, 0)).unwrap();
+        assert_eq!((heatmap.min_chunk_x, heatmap.min_chunk_z), (-2, 0));
+        assert_eq!((heatmap.width, heatmap.length), (4, 2));
+        assert_eq!(heatmap.chunks.iter().flatten().count(), 2);
+        assert_eq!(heatmap.chunk_heat(-1, 0).unwrap().inhabited_time, Some(20));
+        assert_eq!(heatmap.max_inhabited_time(), Some(7000));
+        assert!(heatmap.chunk_heat(1, 0).is_none());
+        assert!(heatmap.chunk_heat(-3, 0).is_none());
+
+        // Region files are not created for areas without chunks.
+        let heatmap = area_heatmap(&chunk_provider, (64, 64), (65, 65)).unwrap();
+        assert!(heatmap.max_compressed_bytes().is_none());
+        assert!(!temp_dir.path().join("r.2.2.mca").exists());
+    }
+
+    #[test]
+    fn test_region_heatmap() {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+        let heatmap = region_heatmap(&chunk_provider, 0, 0).unwrap();
+
+        assert_eq!((heatmap.width, heatmap.length), (32, 32));
+        assert_eq!(
+            heatmap.chunks.iter().flatten().count(),
+            chunk_provider.chunk_positions().unwrap().len()
+        );
+        assert!(heatmap.chunk_heat(4, 2).unwrap().compressed_bytes > 0);
+        assert!(heatmap.chunk_heat(4, 2).unwrap().inhabited_time.is_some());
+    }
+}
@@ -0,0 +1,357 @@
+//! Vertical extent of chunks.
+//!
+//! Before 1.18 worlds are 256 blocks high starting from zero. Since 1.18 overworld
+//! spans from -64 to 320 and datapacks can change height of any dimension, so chunk
+//! stores Y of its lowest section in `yPos` tag. Heightmaps store heights relative
+//! to the bottom of the world with as many bits as the world height requires.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::chunk::Chunk;
+//! use anvil_region::section::BlockState;
+//! use anvil_region::AnvilChunkProvider;
+//!
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//! let mut chunk = Chunk::new(chunk_provider.load_chunk(4, 2).unwrap());
+//! let height_range = chunk.height_range();
+//!
+//! assert_eq!((height_range.min_y, height_range.max_y()), (0, 256));
+//!
+//! chunk.set_block_state(0, 255, 0, BlockState::new("minecraft:glass")).unwrap();
+//! assert!(chunk.set_block_state(0, -1, 0, BlockState::new("minecraft:glass")).is_err());
+//! ```
+use crate::chunk::Chunk;
+use crate::packed::{pack, unpack};
+use crate::section::{BlockState, Section, SectionError};
+use crate::tag::{get_tag, set_tag};
+use crate::upgrade::HEIGHTMAP_COLUMNS;
+use crate::version::LEVEL_WRAPPER_REMOVAL_DATA_VERSION;
+use nbt::{CompoundTag, Tag};
+
+/// Range of block Y coordinates which chunk can contain.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HeightRange {
+    /// Lowest block Y, multiple of 16.
+    pub min_y: i32,
+    /// Amount of blocks from bottom to top, multiple of 16.
+    pub height: u32,
+}
+
+impl HeightRange {
+    /// Height range of worlds before 1.18.
+    pub const LEGACY: HeightRange = HeightRange::new(0, 256);
+    /// Height range of overworld since 1.18.
+    pub const OVERWORLD: HeightRange = HeightRange::new(-64, 384);
+
+    pub const fn new(min_y: i32, height: u32) -> Self {
+        HeightRange { min_y, height }
+    }
+
+    /// Returns block Y above the highest block.
+    pub fn max_y(&self) -> i32 {
+        self.min_y + self.height as i32
+    }
+
+    /// Returns true if block Y is inside of range.
+    pub fn contains(&self, y: i32) -> bool {
+        y >= self.min_y && y < self.max_y()
+    }
+
+    /// Returns Y of the lowest section.
+    pub fn min_section_y(&self) -> i32 {
+        self.min_y >> 4
+    }
+
+    /// Returns Y of section above the highest section.
+    pub fn max_section_y(&self) -> i32 {
+        self.max_y() >> 4
+    }
+
+    /// Returns bits per heightmap column, enough to store heights from 0 to height inclusive.
+    pub fn heightmap_bits(&self) -> u32 {
+        32 - self.height.leading_zeros()
+    }
+}
+
+impl Chunk {
+    /// Returns height range of chunk.
+    ///
+    /// Bottom is taken from `yPos` tag when present. Chunk doesn't store its top, so
+    /// the highest section with blocks is used when it is above the default top for
+    /// data version, which detects worlds made higher by datapacks.
+    pub fn height_range(&self) -> HeightRange {
+        let modern = self
+            .data_version()
+            .is_some_and(|data_version| data_version >= LEVEL_WRAPPER_REMOVAL_DATA_VERSION);
+        let default_range = if modern {
+            HeightRange::OVERWORLD
+        } else {
+            HeightRange::LEGACY
+        };
+
+        let block_section_ys: Vec<i32> = self
+            .sections()
+            .into_iter()
+            .filter(|section| {
+                ["block_states", "Palette", "Blocks"]
+                    .iter()
+                    .any(|name| section.contains_key(name))
+            })
+            .filter_map(|section| section.get_i8("Y").ok())
+            .map(i32::from)
+            .collect();
+
+        let lowest_section_y = block_section_ys.iter().min().copied();
+        let highest_section_y = block_section_ys.iter().max().copied();
+
+        let min_section_y = match self.data().get_i32("yPos") {
+            Ok(min_section_y) => min_section_y,
+            Err(_) => lowest_section_y.map_or(default_range.min_section_y(), |section_y| {
+                section_y.min(default_range.min_section_y())
+            }),
+        };
+
+        let max_section_y = highest_section_y.map_or(default_range.max_section_y(), |section_y| {
+            (section_y + 1).max(default_range.max_section_y())
+        });
+
+        let sections = (max_section_y - min_section_y).max(1) as u32;
+
+        HeightRange::new(min_section_y * 16, sections * 16)
+    }
+
+    /// Returns block state at world Y and chunk relative X and Z.
+    ///
+    /// Returns none if section isn't stored or contains only light.
+    pub fn block_state(
+        &self,
+        x: usize,
+        y: i32,
+        z: usize,
+    ) -> Result<Option<BlockState>, SectionError> {
+        let section_format = self.section_format()?;
+
+        let section_compound_tag = match self.section_compound_tag(y >> 4) {
+            Some(section_compound_tag) => section_compound_tag,
+            None => return Ok(None),
+        };
+
+        let section = Section::from_compound_tag(section_compound_tag, section_format)?;
+
+        Ok(section.block_state(x, (y & 15) as usize, z).cloned())
+    }
+
+    /// Sets block state at world Y and chunk relative X and Z.
+    ///
+    /// Missing section inside of chunk height range is created filled with air.
+    pub fn set_block_state(
+        &mut self,
+        x: usize,
+        y: i32,
+        z: usize,
+        block_state: BlockState,
+    ) -> Result<(), SectionError> {
+        let height_range = self.height_range();
+
+        if !height_range.contains(y) {
+            return Err(SectionError::OutOfHeightRange { y, height_range });
+        }
+
+        let section_format = self.section_format()?;
+        let section_y = (y >> 4) as i8;
+
+        let mut section = match self.section_compound_tag(y >> 4) {
+            Some(section_compound_tag) => {
+                Section::from_compound_tag(section_compound_tag, section_format)?
+            }
+            None => Section::new(section_y),
+        };
+
+        section.set_block_state(x, (y & 15) as usize, z, block_state);
+        self.write_section(&section)
+    }
+
+    /// Returns world Y above the highest matching block of every column.
+    ///
+    /// Columns are indexed by `z * 16 + x`. Returns none if chunk doesn't have
+    /// heightmap with name like `MOTION_BLOCKING` or it is stored before the flattening.
+    pub fn heightmap(&self, name: &str) -> Option<Vec<i32>> {
+        let section_format = self.section_format().ok()?;
+        let height_range = self.height_range();
+
+        let heightmap = match get_tag(self.heightmaps()?, name)? {
+            Tag::LongArray(heightmap) => heightmap,
+            _ => return None,
+        };
+
+        let heights = unpack(
+            heightmap,
+            height_range.heightmap_bits(),
+            HEIGHTMAP_COLUMNS,
+            section_format.spanning(),
+        );
+
+        Some(
+            heights
+                .into_iter()
+                .map(|height| height as i32 + height_range.min_y)
+                .collect(),
+        )
+    }
+
+    /// Stores world Y above the highest matching block of every column.
+    ///
+    /// Returns error if chunk section format is unsupported, chunk isn't changed then.
+    pub fn set_heightmap(
+        &mut self,
+        name: &str,
+        heights: &[i32; HEIGHTMAP_COLUMNS],
+    ) -> Result<(), SectionError> {
+        let section_format = self.section_format()?;
+        let height_range = self.height_range();
+
+        let heights: Vec<u16> = heights
+            .iter()
+            .map(|height| (height - height_range.min_y).clamp(0, height_range.height as i32) as u16)
+            .collect();
+
+        let heightmap = pack(
+            &heights,
+            height_range.heightmap_bits(),
+            section_format.spanning(),
+        );
+
+        let data = self.data_mut();
+
+        if !data.contains_key("Heightmaps") {
+            data.insert_compound_tag("Heightmaps", CompoundTag::new());
+        }
+
+        if let Ok(heightmaps) = data.get_mut::<&mut CompoundTag>("Heightmaps") {
+            set_tag(heightmaps, name, Tag::LongArray(heightmap));
+        }
+
+        Ok(())
+    }
+
+    fn heightmaps(&self) -> Option<&CompoundTag> {
+        self.data().get_compound_tag("Heightmaps").ok()
+    }
+
+    pub(crate) fn section_compound_tag(&self, section_y: i32) -> Option<&CompoundTag> {
+        self.sections()
+            .into_iter()
+            .find(|section| section.get_i8("Y").ok().map(i32::from) == Some(section_y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chunk::Chunk;
+    use crate::height::HeightRange;
+    use crate::section::{BlockState, SectionError};
+    use crate::upgrade::upgrade_chunk;
+    use crate::AnvilChunkProvider;
+
+    fn fixture_chunk() -> Chunk {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+
+        Chunk::new(chunk_provider.load_chunk(4, 2).unwrap())
+    }
+
+    #[test]
+    fn test_height_range() {
+        assert_eq!(HeightRange::LEGACY.heightmap_bits(), 9);
+        assert_eq!(HeightRange::OVERWORLD.heightmap_bits(), 9);
+        assert_eq!(HeightRange::new(-2032, 4064).heightmap_bits(), 12);
+        assert_eq!(HeightRange::OVERWORLD.min_section_y(), -4);
+        assert_eq!(HeightRange::OVERWORLD.max_section_y(), 20);
+        assert!(HeightRange::OVERWORLD.contains(-64));
+        assert!(!HeightRange::OVERWORLD.contains(320));
+    }
+
+    #[test]
+    fn test_chunk_height_range() {
+        let chunk = fixture_chunk();
+        assert_eq!(chunk.height_range(), HeightRange::LEGACY);
+
+        let mut chunk_compound_tag = chunk.into_compound_tag();
+        upgrade_chunk(&mut chunk_compound_tag, 2860).unwrap();
+        chunk_compound_tag.insert_i32("yPos", -4);
+
+        let height_range = Chunk::new(chunk_compound_tag).height_range();
+        assert_eq!(height_range.min_y, -64);
+    }
+
+    #[test]
+    fn test_block_state_below_zero() {
+        let mut chunk_compound_tag = fixture_chunk().into_compound_tag();
+        upgrade_chunk(&mut chunk_compound_tag, 2860).unwrap();
+        chunk_compound_tag.insert_i32("yPos", -4);
+
+        let mut chunk = Chunk::new(chunk_compound_tag);
+        let deepslate = BlockState::new("minecraft:deepslate");
+
+        assert_eq!(chunk.block_state(3, -60, 5).unwrap(), None);
+        chunk.set_block_state(3, -60, 5, deepslate.clone()).unwrap();
+
+        assert_eq!(chunk.block_state(3, -60, 5).unwrap(), Some(deepslate));
+        assert_eq!(
+            chunk.block_state(3, -59, 5).unwrap(),
+            Some(BlockState::new("minecraft:air"))
+        );
+        assert_eq!(chunk.height_range().min_y, -64);
+
+        match chunk.set_block_state(0, -65, 0, BlockState::new("minecraft:stone")) {
+            Err(SectionError::OutOfHeightRange { y, .. }) => assert_eq!(y, -65),
+            result => panic!("Expected `OutOfHeightRange` but got `{:?}`", result),
+        }
+    }
+
+    #[test]
+    fn test_heightmap() {
+        let mut chunk = fixture_chunk();
+        let heights = chunk.heightmap("MOTION_BLOCKING").unwrap();
+
+        assert_eq!(heights.len(), 256);
+        assert!(heights.iter().all(|height| (0..=256).contains(height)));
+
+        let mut chunk_compound_tag = chunk.clone().into_compound_tag();
+        upgrade_chunk(&mut chunk_compound_tag, 2860).unwrap();
+        chunk_compound_tag.insert_i32("yPos", -4);
+        let mut modern_chunk = Chunk::new(chunk_compound_tag);
+
+        let mut new_heights = [0; 256];
+        new_heights[17] = -30;
+        new_heights[255] = 100;
+
+        for chunk in [&mut chunk, &mut modern_chunk] {
+            let min_y = chunk.height_range().min_y;
+            new_heights[0] = min_y;
+
+            chunk.set_heightmap("WORLD_SURFACE", &new_heights).unwrap();
+            let heights = chunk.heightmap("WORLD_SURFACE").unwrap();
+
+            assert_eq!(heights[0], min_y);
+            assert_eq!(heights[17], (-30).max(min_y));
+            assert_eq!(heights[255], 100);
+        }
+    }
+
+    #[test]
+    fn test_set_heightmap_unsupported_format() {
+        let mut chunk_compound_tag = fixture_chunk().into_compound_tag();
+        chunk_compound_tag.insert_i32("DataVersion", 1343);
+
+        let mut chunk = Chunk::new(chunk_compound_tag);
+
+        match chunk.set_heightmap("MOTION_BLOCKING", &[0; 256]) {
+            Err(SectionError::UnsupportedDataVersion { data_version }) => {
+                assert_eq!(data_version, 1343)
+            }
+            result => panic!("Expected `UnsupportedDataVersion` but got `{:?}`", result),
+        }
+    }
+}
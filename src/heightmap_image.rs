@@ -0,0 +1,273 @@
+//! Heightmap images of world areas for terrain analysis and 3D tools.
+//!
+//! [`export_heightmap`] reads heightmap of every chunk of rectangle, chunks before
+//! the flattening use their `HeightMap` array. Heights are scaled from height range
+//! into 8 or 16 bit grayscale, so tools importing images as displacement maps get
+//! the same scale for every export of the world.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::height::HeightRange;
+//! use anvil_region::heightmap_image::{export_heightmap, GrayscaleDepth};
+//! use anvil_region::AnvilChunkProvider;
+//!
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//! let heightmap = export_heightmap(&chunk_provider, (64, 32), (79, 47), "WORLD_SURFACE").unwrap();
+//!
+//! let height = heightmap.height(64, 32).unwrap();
+//! let pixels = heightmap.to_grayscale(HeightRange::LEGACY, GrayscaleDepth::Eight);
+//! assert_eq!(pixels[0] as i32, height * 255 / 256);
+//! ```
+use crate::chunk::Chunk;
+use crate::height::HeightRange;
+use crate::{AnvilChunkProvider, ChunkLoadError};
+
+/// Bits per pixel of grayscale image.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum GrayscaleDepth {
+    /// One byte per pixel.
+    Eight,
+    /// Two big endian bytes per pixel, as stored in PNG.
+    Sixteen,
+}
+
+/// Heights of block columns of rectangle, columns of missing chunks have no height.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct HeightmapImage {
+    /// Block X of the west edge.
+    pub min_x: i32,
+    /// Block Z of the north edge.
+    pub min_z: i32,
+    /// Amount of columns along X.
+    pub width: usize,
+    /// Amount of columns along Z.
+    pub length: usize,
+    /// World Y above the highest block of every column row by row from north-west corner.
+    pub heights: Vec<Option<i32>>,
+}
+
+impl HeightmapImage {
+    /// Returns height of column at block X and Z, none if it's outside of image or
+    /// its chunk is missing.
+    pub fn height(&self, x: i32, z: i32) -> Option<i32> {
+        if x < self.min_x || z < self.min_z {
+            return None;
+        }
+
+        let column_x = (x as i64 - self.min_x as i64) as usize;
+        let column_z = (z as i64 - self.min_z as i64) as usize;
+
+        if column_x >= self.width || column_z >= self.length {
+            return None;
+        }
+
+        self.heights[column_z * self.width + column_x]
+    }
+
+    /// Returns lowest and highest height of columns, none if no chunk was present.
+    pub fn height_bounds(&self) -> Option<(i32, i32)> {
+        let heights = self.heights.iter().flatten();
+        let min = heights.clone().min()?;
+        let max = heights.max()?;
+
+        Some((*min, *max))
+    }
+
+    /// Returns grayscale pixels where bottom of height range is black and top is white.
+    ///
+    /// Columns of missing chunks are black.
+    pub fn to_grayscale(&self, height_range: HeightRange, depth: GrayscaleDepth) -> Vec<u8> {
+        let maximum_value: i64 = match depth {
+            GrayscaleDepth::Eight => u8::MAX as i64,
+            GrayscaleDepth::Sixteen => u16::MAX as i64,
+        };
+
+        let mut pixels = Vec::with_capacity(self.heights.len() * 2);
+
+        for height in &self.heights {
+            let value = match height {
+                Some(height) => {
+                    let height = (*height as i64 - height_range.min_y as i64)
+                        .clamp(0, height_range.height as i64);
+
+                    height * maximum_value / height_range.height.max(1) as i64
+                }
+                None => 0,
+            };
+
+            match depth {
+                GrayscaleDepth::Eight => pixels.push(value as u8),
+                GrayscaleDepth::Sixteen => pixels.extend_from_slice(&(value as u16).to_be_bytes()),
+            }
+        }
+
+        pixels
+    }
+
+    /// Writes image as grayscale PNG with one pixel per column.
+    #[cfg(feature = "render")]
+    pub fn write_png<W: std::io::Write>(
+        &self,
+        writer: W,
+        height_range: HeightRange,
+        depth: GrayscaleDepth,
+    ) -> Result<(), png::EncodingError> {
+        let mut encoder = png::Encoder::new(writer, self.width as u32, self.length as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(match depth {
+            GrayscaleDepth::Eight => png::BitDepth::Eight,
+            GrayscaleDepth::Sixteen => png::BitDepth::Sixteen,
+        });
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.to_grayscale(height_range, depth))?;
+
+        writer.finish()
+    }
+}
+
+/// Exports heights of block columns between lowest and highest block X and Z inclusive
+/// from heightmap with name like `WORLD_SURFACE`.
+///
+/// Columns of chunks which don't have the heightmap have no height.
+pub fn export_heightmap(
+    chunk_provider: &AnvilChunkProvider,
+    min: (i32, i32),
+    max: (i32, i32),
+    heightmap: &str,
+) -> Result<HeightmapImage, ChunkLoadError> {
+    let (min_x, max_x) = (min.0.min(max.0), min.0.max(max.0));
+    let (min_z, max_z) = (min.1.min(max.1), min.1.max(max.1));
+    let width = (max_x as i64 - min_x as i64 + 1) as usize;
+    let length = (max_z as i64 - min_z as i64 + 1) as usize;
+
+    let mut heights = vec![None; width * length];
+
+    for chunk_z in min_z >> 4..=max_z >> 4 {
+        for chunk_x in min_x >> 4..=max_x >> 4 {
+            let chunk = match chunk_provider.load_chunk(chunk_x, chunk_z) {
+                Ok(chunk_compound_tag) => Chunk::new(chunk_compound_tag),
+                Err(ChunkLoadError::RegionNotFound { .. })
+                | Err(ChunkLoadError::ChunkNotFound { .. }) => continue,
+                Err(chunk_load_error) => return Err(chunk_load_error),
+            };
+
+            let column_heights = match chunk_heights(&chunk, heightmap) {
+                Some(column_heights) => column_heights,
+                None => continue,
+            };
+
+            for (column, height) in column_heights.into_iter().enumerate() {
+                let x = chunk_x * 16 + (column % 16) as i32;
+                let z = chunk_z * 16 + (column / 16) as i32;
+
+                if x >= min_x && x <= max_x && z >= min_z && z <= max_z {
+                    let index = (z - min_z) as usize * width + (x - min_x) as usize;
+                    heights[index] = Some(height);
+                }
+            }
+        }
+    }
+
+    Ok(HeightmapImage {
+        min_x,
+        min_z,
+        width,
+        length,
+        heights,
+    })
+}
+
+fn chunk_heights(chunk: &Chunk, heightmap: &str) -> Option<Vec<i32>> {
+    if let Some(heights) = chunk.heightmap(heightmap) {
+        return Some(heights);
+    }
+
+    // Before the flattening chunk has single heightmap of light blocking blocks.
+    match chunk.data().get_i32_vec("HeightMap") {
+        Ok(heights) if heights.len() == 256 => Some(heights.clone()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chunk::Chunk;
+    use crate::height::HeightRange;
+    use crate::heightmap_image::{export_heightmap, GrayscaleDepth};
+    use crate::AnvilChunkProvider;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_export_heightmap() {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk = Chunk::new(chunk_provider.load_chunk(4, 2).unwrap());
+        let chunk_heights = chunk.heightmap("WORLD_SURFACE").unwrap();
+
+        let heightmap =
+            export_heightmap(&chunk_provider, (66, 33), (60, 30), "WORLD_SURFACE").unwrap();
+        assert_eq!((heightmap.min_x, heightmap.min_z), (60, 30));
+        assert_eq!((heightmap.width, heightmap.length), (7, 4));
+        assert_eq!(heightmap.height(66, 33), Some(chunk_heights[16 + 2]));
+        assert_eq!(heightmap.height(67, 33), None);
+
+        let (min, max) = heightmap.height_bounds().unwrap();
+        assert!(min <= max);
+
+        let missing = export_heightmap(&chunk_provider, (64, 32), (79, 47), "MISSING").unwrap();
+        assert!(missing.height_bounds().is_none());
+    }
+
+    #[test]
+    fn test_to_grayscale() {
+        let temp_dir = TempDir::new().unwrap();
+        let fixture_provider = AnvilChunkProvider::new("test/region");
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+
+        let mut chunk = Chunk::new(fixture_provider.load_chunk(4, 2).unwrap());
+        let mut heights = [64; 256];
+        heights[1] = 0;
+        heights[2] = 256;
+        chunk.set_heightmap("WORLD_SURFACE", &heights).unwrap();
+        chunk_provider
+            .save_chunk(4, 2, chunk.into_compound_tag())
+            .unwrap();
+
+        let heightmap =
+            export_heightmap(&chunk_provider, (64, 32), (67, 32), "WORLD_SURFACE").unwrap();
+        assert_eq!(heightmap.height_bounds(), Some((0, 256)));
+
+        let pixels = heightmap.to_grayscale(HeightRange::LEGACY, GrayscaleDepth::Eight);
+        assert_eq!(pixels, vec![63, 0, 255, 63]);
+
+        let pixels = heightmap.to_grayscale(HeightRange::LEGACY, GrayscaleDepth::Sixteen);
+        assert_eq!(&pixels[..6], &[0x3F, 0xFF, 0, 0, 0xFF, 0xFF]);
+
+        let pixels = heightmap.to_grayscale(HeightRange::new(64, 192), GrayscaleDepth::Eight);
+        assert_eq!(pixels, vec![0, 0, 255, 0]);
+    }
+
+    #[cfg(feature = "render")]
+    #[test]
+    fn test_write_png() {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+        let heightmap =
+            export_heightmap(&chunk_provider, (64, 32), (95, 47), "WORLD_SURFACE").unwrap();
+
+        let mut png = Vec::new();
+        heightmap
+            .write_png(&mut png, HeightRange::LEGACY, GrayscaleDepth::Sixteen)
+            .unwrap();
+
+        let decoder = png::Decoder::new(png.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut pixels).unwrap();
+
+        assert_eq!(
+            pixels,
+            heightmap.to_grayscale(HeightRange::LEGACY, GrayscaleDepth::Sixteen)
+        );
+    }
+}
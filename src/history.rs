@@ -0,0 +1,338 @@
+//! Loading chunks as they were at a point in time from world, backups and deltas.
+//!
+//! [`HistoryChunkProvider`] knows versions of world ordered by time: the base world
+//! folder, generations of [`IncrementalBackup`] and [`WorldDelta`] applied on top of
+//! them. Chunk at a point in time is read from the latest version saved at or before
+//! it, deltas which don't touch the chunk are skipped. Comparing chunk before and after
+//! a time shows who broke what and when, which is invaluable for grief investigation.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::backup::IncrementalBackup;
+//! use anvil_region::history::HistoryChunkProvider;
+//! use anvil_region::relocate::copy_chunk;
+//! use anvil_region::AnvilChunkProvider;
+//! use std::time::{Duration, SystemTime};
+//! use tempfile::TempDir;
+//!
+//! let world_dir = TempDir::new().unwrap();
+//! let backup_dir = TempDir::new().unwrap();
+//! let region_folder = world_dir.path().join("region");
+//!
+//! let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+//! let chunk_provider = AnvilChunkProvider::new(region_folder.to_str().unwrap());
+//! copy_chunk(&fixture_chunk_provider, (4, 2), &chunk_provider, (4, 2)).unwrap();
+//!
+//! let backup = IncrementalBackup::new(backup_dir.path());
+//! backup.backup(world_dir.path()).unwrap();
+//!
+//! let backed_up_at = SystemTime::now();
+//! chunk_provider.delete_chunk(4, 2).unwrap();
+//!
+//! let saved_at = backed_up_at + Duration::from_secs(60);
+//! let history_chunk_provider = HistoryChunkProvider::new(world_dir.path(), saved_at)
+//!     .with_backup(&backup)
+//!     .unwrap();
+//!
+//! assert!(history_chunk_provider.load_chunk_at(4, 2, backed_up_at).is_ok());
+//! assert!(history_chunk_provider.load_chunk_at(4, 2, saved_at).is_err());
+//! ```
+use crate::backup::{BackupError, BackupManifest, IncrementalBackup};
+use crate::buffer::decode_chunk;
+use crate::delta::{DeltaOperation, WorldDelta};
+use crate::world::REGION_FOLDER;
+use crate::{AnvilRegion, ChunkLoadError};
+use nbt::CompoundTag;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Version of world saved at some time.
+#[derive(Debug)]
+enum WorldVersion {
+    /// World folder with all region files.
+    World { world_folder_path: PathBuf },
+    /// Generation of backup, region files are stored by it or older generations.
+    Generation {
+        backup: IncrementalBackup,
+        manifest: BackupManifest,
+    },
+    /// Changes applied to previous version.
+    Delta { world_delta: WorldDelta },
+}
+
+/// Provider of chunks of region folder at points in time.
+#[derive(Debug)]
+pub struct HistoryChunkProvider {
+    /// Region folder relative to world folder.
+    folder_path: PathBuf,
+    /// Versions with times they were saved at, sorted by time.
+    versions: Vec<(SystemTime, WorldVersion)>,
+}
+
+impl HistoryChunkProvider {
+    /// Creates provider of terrain chunks with base world folder saved at the specified
+    /// time.
+    pub fn new(world_folder_path: impl AsRef<Path>, saved_at: SystemTime) -> Self {
+        let world_version = WorldVersion::World {
+            world_folder_path: world_folder_path.as_ref().to_path_buf(),
+        };
+
+        HistoryChunkProvider {
+            folder_path: PathBuf::from(REGION_FOLDER),
+            versions: vec![(saved_at, world_version)],
+        }
+    }
+
+    /// Reads chunks of another region folder, like `DIM-1/region` or `entities`.
+    pub fn with_region_folder(mut self, folder_path: impl AsRef<Path>) -> Self {
+        self.folder_path = folder_path.as_ref().to_path_buf();
+        self
+    }
+
+    /// Adds every generation of backup at time it was created.
+    pub fn with_backup(mut self, backup: &IncrementalBackup) -> Result<Self, BackupError> {
+        for generation in backup.generations()? {
+            let manifest = backup.manifest(generation)?;
+            let created = UNIX_EPOCH + Duration::from_secs(manifest.created_timestamp);
+
+            let world_version = WorldVersion::Generation {
+                backup: backup.clone(),
+                manifest,
+            };
+
+            self.add_version(created, world_version);
+        }
+
+        Ok(self)
+    }
+
+    /// Adds delta applied at the specified time to version saved before it.
+    pub fn with_delta(mut self, applied_at: SystemTime, world_delta: WorldDelta) -> Self {
+        self.add_version(applied_at, WorldVersion::Delta { world_delta });
+        self
+    }
+
+    /// Loads chunk as it was at the specified point in time.
+    ///
+    /// Chunk which didn't exist at that time is reported as not found, same as point in
+    /// time before the oldest version.
+    pub fn load_chunk_at(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        point_in_time: SystemTime,
+    ) -> Result<CompoundTag, ChunkLoadError> {
+        let region_x = chunk_x >> 5;
+        let region_z = chunk_z >> 5;
+
+        let region_chunk_x = (chunk_x & 31) as u8;
+        let region_chunk_z = (chunk_z & 31) as u8;
+
+        let region_name = format!("r.{}.{}.mca", region_x, region_z);
+        let region_path = self.folder_path.join(region_name);
+
+        let versions = self
+            .versions
+            .iter()
+            .rev()
+            .skip_while(|(saved_at, _)| *saved_at > point_in_time);
+
+        for (_, world_version) in versions {
+            let region_path = match world_version {
+                WorldVersion::World { world_folder_path } => world_folder_path.join(&region_path),
+                WorldVersion::Generation { backup, manifest } => {
+                    match manifest
+                        .entries
+                        .binary_search_by(|entry| entry.path.cmp(&region_path))
+                    {
+                        Ok(index) => backup.stored_path(&manifest.entries[index]),
+                        Err(_) => {
+                            return Err(ChunkLoadError::RegionNotFound { region_x, region_z })
+                        }
+                    }
+                }
+                WorldVersion::Delta { world_delta } => {
+                    match self.delta_operation(world_delta, chunk_x, chunk_z) {
+                        Some(DeltaOperation::SaveChunk { chunk_buffer, .. }) => {
+                            return match chunk_buffer.split_first() {
+                                Some((compression_scheme, compressed_buffer)) => {
+                                    decode_chunk(*compression_scheme, compressed_buffer)
+                                }
+                                None => Err(ChunkLoadError::ReadError {
+                                    io_error: io::ErrorKind::UnexpectedEof.into(),
+                                }),
+                            };
+                        }
+                        Some(_) => {
+                            return Err(ChunkLoadError::ChunkNotFound { chunk_x, chunk_z });
+                        }
+                        None => continue,
+                    }
+                }
+            };
+
+            // Region is opened only if it exists, since opening creates it.
+            if !region_path.is_file() {
+                return Err(ChunkLoadError::RegionNotFound { region_x, region_z });
+            }
+
+            return AnvilRegion::new(&region_path)
+                .map_err(ChunkLoadError::from)
+                .and_then(|mut region| region.read_chunk(region_chunk_x, region_chunk_z))
+                .map_err(|chunk_load_error| {
+                    chunk_load_error.with_context(&region_path, chunk_x, chunk_z)
+                });
+        }
+
+        Err(ChunkLoadError::RegionNotFound { region_x, region_z })
+    }
+
+    /// Returns the last operation of delta which changes chunk of region folder.
+    fn delta_operation<'d>(
+        &self,
+        world_delta: &'d WorldDelta,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> Option<&'d DeltaOperation> {
+        world_delta
+            .operations
+            .iter()
+            .rev()
+            .find(|operation| match operation {
+                DeltaOperation::SaveChunk {
+                    folder_path,
+                    chunk_x: operation_chunk_x,
+                    chunk_z: operation_chunk_z,
+                    ..
+                }
+                | DeltaOperation::DeleteChunk {
+                    folder_path,
+                    chunk_x: operation_chunk_x,
+                    chunk_z: operation_chunk_z,
+                    ..
+                } => {
+                    *folder_path == self.folder_path
+                        && (*operation_chunk_x, *operation_chunk_z) == (chunk_x, chunk_z)
+                }
+                _ => false,
+            })
+    }
+
+    /// Inserts version after versions saved at the same time or before it.
+    fn add_version(&mut self, saved_at: SystemTime, world_version: WorldVersion) {
+        let index = self
+            .versions
+            .partition_point(|(version_saved_at, _)| *version_saved_at <= saved_at);
+
+        self.versions.insert(index, (saved_at, world_version));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backup::IncrementalBackup;
+    use crate::delta::compute_delta;
+    use crate::history::HistoryChunkProvider;
+    use crate::snapshot::snapshot;
+    use crate::{AnvilChunkProvider, ChunkLoadError};
+    use nbt::CompoundTag;
+    use std::time::{Duration, SystemTime};
+    use tempfile::TempDir;
+
+    fn chunk_with_marker(marker: i32) -> CompoundTag {
+        let mut chunk_compound_tag = CompoundTag::new();
+        chunk_compound_tag.insert_i32("Marker", marker);
+        chunk_compound_tag
+    }
+
+    #[test]
+    fn test_load_chunk_at_with_deltas() {
+        let base_world_dir = TempDir::new().unwrap();
+        let new_world_dir = TempDir::new().unwrap();
+        let base_region_folder = base_world_dir.path().join("region");
+        let base_chunk_provider = AnvilChunkProvider::new(base_region_folder.to_str().unwrap());
+        base_chunk_provider
+            .save_chunk(4, 2, chunk_with_marker(1))
+            .unwrap();
+        base_chunk_provider
+            .save_chunk(5, 2, chunk_with_marker(1))
+            .unwrap();
+
+        snapshot(base_world_dir.path(), new_world_dir.path()).unwrap();
+        let new_region_folder = new_world_dir.path().join("region");
+        let new_chunk_provider = AnvilChunkProvider::new(new_region_folder.to_str().unwrap());
+        new_chunk_provider
+            .save_chunk(4, 2, chunk_with_marker(2))
+            .unwrap();
+        new_chunk_provider.delete_chunk(5, 2).unwrap();
+
+        let world_delta = compute_delta(base_world_dir.path(), new_world_dir.path()).unwrap();
+
+        let base_time = SystemTime::now() - Duration::from_secs(7200);
+        let delta_time = base_time + Duration::from_secs(3600);
+        let history_chunk_provider = HistoryChunkProvider::new(base_world_dir.path(), base_time)
+            .with_delta(delta_time, world_delta);
+
+        let marker = |chunk_x, point_in_time| {
+            history_chunk_provider
+                .load_chunk_at(chunk_x, 2, point_in_time)
+                .map(|chunk_compound_tag| chunk_compound_tag.get_i32("Marker").unwrap())
+        };
+
+        assert_eq!(marker(4, base_time).unwrap(), 1);
+        assert_eq!(marker(4, delta_time).unwrap(), 2);
+        assert_eq!(marker(5, base_time).unwrap(), 1);
+
+        match marker(5, delta_time) {
+            Err(ChunkLoadError::ChunkNotFound { .. }) => {}
+            result => panic!("Expected `ChunkNotFound` but got `{:?}`", result),
+        }
+
+        match marker(4, base_time - Duration::from_secs(1)) {
+            Err(ChunkLoadError::RegionNotFound { .. }) => {}
+            result => panic!("Expected `RegionNotFound` but got `{:?}`", result),
+        }
+    }
+
+    #[test]
+    fn test_load_chunk_at_with_backup() {
+        let world_dir = TempDir::new().unwrap();
+        let backup_dir = TempDir::new().unwrap();
+        let region_folder = world_dir.path().join("DIM-1/region");
+        let chunk_provider = AnvilChunkProvider::new(region_folder.to_str().unwrap());
+        chunk_provider
+            .save_chunk(-40, 2, chunk_with_marker(1))
+            .unwrap();
+
+        let backup = IncrementalBackup::new(backup_dir.path());
+        backup.backup(world_dir.path()).unwrap();
+        let backup_time = SystemTime::now();
+
+        chunk_provider
+            .save_chunk(-40, 2, chunk_with_marker(2))
+            .unwrap();
+
+        let world_time = backup_time + Duration::from_secs(3600);
+        let history_chunk_provider = HistoryChunkProvider::new(world_dir.path(), world_time)
+            .with_region_folder("DIM-1/region")
+            .with_backup(&backup)
+            .unwrap();
+
+        let chunk_compound_tag = history_chunk_provider
+            .load_chunk_at(-40, 2, backup_time)
+            .unwrap();
+        assert_eq!(chunk_compound_tag.get_i32("Marker").unwrap(), 1);
+
+        let chunk_compound_tag = history_chunk_provider
+            .load_chunk_at(-40, 2, world_time)
+            .unwrap();
+        assert_eq!(chunk_compound_tag.get_i32("Marker").unwrap(), 2);
+
+        match history_chunk_provider.load_chunk_at(40, 2, backup_time) {
+            Err(ChunkLoadError::RegionNotFound { .. }) => {}
+            result => panic!("Expected `RegionNotFound` but got `{:?}`", result),
+        }
+    }
+}
@@ -0,0 +1,349 @@
+//! Chunk provider which keeps region files in memory.
+//!
+//! Provider doesn't use file system, threads or system clock, so it works where they
+//! aren't available, like in browsers on `wasm32-unknown-unknown`. Regions are filled
+//! from region file contents or world archive bytes fetched by application, and can be
+//! written back as region file contents.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::in_memory::InMemoryChunkProvider;
+//! use std::fs;
+//!
+//! let mut chunk_provider = InMemoryChunkProvider::new();
+//! chunk_provider
+//!     .insert_region(0, 0, &fs::read("test/region/r.0.0.mca").unwrap())
+//!     .unwrap();
+//!
+//! let chunk_compound_tag = chunk_provider.load_chunk(4, 2).unwrap();
+//! chunk_provider.save_chunk(40, 2, chunk_compound_tag).unwrap();
+//!
+//! assert!(chunk_provider.region_bytes(1, 0).is_some());
+//! ```
+use crate::hash::CoordinateHashMap;
+use crate::region_slice::RegionSlice;
+use crate::zip;
+use crate::{
+    current_timestamp, decode_chunk, encode_chunk, region_position, AnvilRegion, ChunkLoadError,
+    ChunkSaveError, CHUNK_MAXIMUM_BYTES_LENGTH, REGION_CHUNKS, REGION_HEADER_BYTES_LENGTH,
+    REGION_SECTOR_BYTES_LENGTH,
+};
+use nbt::CompoundTag;
+use std::io::Cursor;
+
+/// Compressed chunk as stored in region file.
+#[derive(Debug, Clone)]
+struct StoredChunk {
+    compression_scheme: u8,
+    compressed: Vec<u8>,
+    last_modified_timestamp: u32,
+}
+
+/// Chunks of region in order of header.
+type InMemoryRegion = Vec<Option<StoredChunk>>;
+
+/// Chunk provider over region files held in memory.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryChunkProvider {
+    regions: CoordinateHashMap<(i32, i32), InMemoryRegion>,
+}
+
+impl InMemoryChunkProvider {
+    pub fn new() -> Self {
+        InMemoryChunkProvider::default()
+    }
+
+    /// Reads region files of world archive contents.
+    ///
+    /// Region folder is path inside of archive like `world/region` or empty string
+    /// if region files are at archive root.
+    pub fn from_zip(zip_data: &[u8], region_folder: &str) -> Result<Self, ChunkLoadError> {
+        let region_folder = region_folder.trim_matches('/');
+        let mut reader = Cursor::new(zip_data);
+        let mut chunk_provider = InMemoryChunkProvider::new();
+
+        for entry in zip::entries(&mut reader)? {
+            let file_name = match region_folder {
+                "" => Some(entry.name.as_str()),
+                region_folder => entry
+                    .name
+                    .strip_prefix(region_folder)
+                    .and_then(|name| name.strip_prefix('/')),
+            };
+
+            if let Some((region_x, region_z)) = file_name.and_then(region_position) {
+                let data = zip::read_entry(&mut reader, &entry, u64::MAX)?;
+                chunk_provider.insert_region(region_x, region_z, &data)?;
+            }
+        }
+
+        Ok(chunk_provider)
+    }
+
+    /// Replaces region with chunks of region file contents.
+    ///
+    /// Compressed chunk data is copied as is, chunks are decoded only when loaded.
+    pub fn insert_region(
+        &mut self,
+        region_x: i32,
+        region_z: i32,
+        data: &[u8],
+    ) -> Result<(), ChunkLoadError> {
+        let region_slice = RegionSlice::new(data)?;
+        let mut region = vec![None; REGION_CHUNKS];
+
+        for (chunk_x, chunk_z) in region_slice.chunk_positions() {
+            let (compression_scheme, compressed) =
+                region_slice.read_chunk_data(chunk_x, chunk_z)?;
+
+            region[AnvilRegion::metadata_index(chunk_x, chunk_z)] = Some(StoredChunk {
+                compression_scheme,
+                compressed: compressed.to_vec(),
+                last_modified_timestamp: region_slice
+                    .chunk_last_modified(chunk_x, chunk_z)
+                    .unwrap_or_default(),
+            });
+        }
+
+        self.regions.insert((region_x, region_z), region);
+
+        Ok(())
+    }
+
+    /// Loads chunk from the specified coordinates.
+    pub fn load_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<CompoundTag, ChunkLoadError> {
+        let region_x = chunk_x >> 5;
+        let region_z = chunk_z >> 5;
+
+        let region_chunk_x = (chunk_x & 31) as u8;
+        let region_chunk_z = (chunk_z & 31) as u8;
+
+        let region = match self.regions.get(&(region_x, region_z)) {
+            Some(region) => region,
+            None => return Err(ChunkLoadError::RegionNotFound { region_x, region_z }),
+        };
+
+        match &region[AnvilRegion::metadata_index(region_chunk_x, region_chunk_z)] {
+            Some(stored_chunk) => {
+                decode_chunk(stored_chunk.compression_scheme, &stored_chunk.compressed)
+            }
+            None => Err(ChunkLoadError::ChunkNotFound { chunk_x, chunk_z }),
+        }
+    }
+
+    /// Saves chunk data to the specified coordinates.
+    pub fn save_chunk(
+        &mut self,
+        chunk_x: i32,
+        chunk_z: i32,
+        chunk_compound_tag: CompoundTag,
+    ) -> Result<(), ChunkSaveError> {
+        let chunk_buffer = encode_chunk(&chunk_compound_tag)?;
+
+        // 4 bytes for data length.
+        let length = (chunk_buffer.len() + 4) as u32;
+
+        if length > CHUNK_MAXIMUM_BYTES_LENGTH {
+            return Err(ChunkSaveError::LengthExceedsMaximum { length });
+        }
+
+        let region_chunk_x = (chunk_x & 31) as u8;
+        let region_chunk_z = (chunk_z & 31) as u8;
+
+        let region = self
+            .regions
+            .entry((chunk_x >> 5, chunk_z >> 5))
+            .or_insert_with(|| vec![None; REGION_CHUNKS]);
+
+        region[AnvilRegion::metadata_index(region_chunk_x, region_chunk_z)] = Some(StoredChunk {
+            compression_scheme: chunk_buffer[0],
+            compressed: chunk_buffer[1..].to_vec(),
+            last_modified_timestamp: current_timestamp(),
+        });
+
+        Ok(())
+    }
+
+    /// Deletes chunk at the specified coordinates.
+    ///
+    /// Returns false if chunk is not present.
+    pub fn delete_chunk(&mut self, chunk_x: i32, chunk_z: i32) -> bool {
+        let region_chunk_x = (chunk_x & 31) as u8;
+        let region_chunk_z = (chunk_z & 31) as u8;
+
+        match self.regions.get_mut(&(chunk_x >> 5, chunk_z >> 5)) {
+            Some(region) => region[AnvilRegion::metadata_index(region_chunk_x, region_chunk_z)]
+                .take()
+                .is_some(),
+            None => false,
+        }
+    }
+
+    /// Returns sorted coordinates of chunks which are present.
+    pub fn chunk_positions(&self) -> Vec<(i32, i32)> {
+        let mut chunk_positions = Vec::new();
+
+        for ((region_x, region_z), region) in &self.regions {
+            for (index, stored_chunk) in region.iter().enumerate() {
+                if stored_chunk.is_some() {
+                    let chunk_x = region_x * 32 + (index % 32) as i32;
+                    let chunk_z = region_z * 32 + (index / 32) as i32;
+
+                    chunk_positions.push((chunk_x, chunk_z));
+                }
+            }
+        }
+
+        chunk_positions.sort_unstable();
+        chunk_positions
+    }
+
+    /// Returns sorted coordinates of regions which were inserted or saved into.
+    pub fn region_positions(&self) -> Vec<(i32, i32)> {
+        let mut region_positions: Vec<_> = self.regions.keys().copied().collect();
+        region_positions.sort_unstable();
+
+        region_positions
+    }
+
+    /// Writes region as region file contents, none if region is not present.
+    ///
+    /// Chunks are placed one after another in order of header without free sectors.
+    pub fn region_bytes(&self, region_x: i32, region_z: i32) -> Option<Vec<u8>> {
+        let region = self.regions.get(&(region_x, region_z))?;
+        let sector_length = REGION_SECTOR_BYTES_LENGTH as usize;
+
+        let mut offsets = Vec::with_capacity(REGION_CHUNKS);
+        let mut timestamps = Vec::with_capacity(REGION_CHUNKS);
+        let mut sectors_data = Vec::new();
+        let mut sector_index = REGION_HEADER_BYTES_LENGTH as usize / sector_length;
+
+        for stored_chunk in region {
+            let stored_chunk = match stored_chunk {
+                Some(stored_chunk) => stored_chunk,
+                None => {
+                    offsets.push(0);
+                    timestamps.push(0);
+                    continue;
+                }
+            };
+
+            let start = sectors_data.len();
+            let length = stored_chunk.compressed.len() + 1;
+
+            sectors_data.extend_from_slice(&(length as u32).to_be_bytes());
+            sectors_data.push(stored_chunk.compression_scheme);
+            sectors_data.extend_from_slice(&stored_chunk.compressed);
+
+            let sectors = (sectors_data.len() - start).div_ceil(sector_length);
+            sectors_data.resize(start + sectors * sector_length, 0);
+
+            offsets.push((sector_index as u32) << 8 | sectors as u32);
+            timestamps.push(stored_chunk.last_modified_timestamp);
+            sector_index += sectors;
+        }
+
+        let mut data = Vec::with_capacity(REGION_HEADER_BYTES_LENGTH as usize + sectors_data.len());
+
+        for value in offsets.into_iter().chain(timestamps) {
+            data.extend_from_slice(&value.to_be_bytes());
+        }
+
+        data.extend_from_slice(&sectors_data);
+
+        Some(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::in_memory::InMemoryChunkProvider;
+    use crate::region_slice::RegionSlice;
+    use crate::snbt;
+    use crate::zip::write_zip;
+    use crate::{AnvilChunkProvider, ChunkLoadError};
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_load_save_and_write_region() {
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let mut chunk_provider = InMemoryChunkProvider::new();
+        chunk_provider
+            .insert_region(0, 0, &fs::read("test/region/r.0.0.mca").unwrap())
+            .unwrap();
+
+        let chunk_compound_tag = chunk_provider.load_chunk(4, 2).unwrap();
+        assert_eq!(
+            snbt::to_string(&chunk_compound_tag),
+            snbt::to_string(&fixture_chunk_provider.load_chunk(4, 2).unwrap())
+        );
+
+        chunk_provider
+            .save_chunk(-1, -1, chunk_compound_tag.clone())
+            .unwrap();
+        assert!(chunk_provider.delete_chunk(4, 2));
+        assert!(!chunk_provider.delete_chunk(4, 2));
+
+        assert!(matches!(
+            chunk_provider.load_chunk(4, 2),
+            Err(ChunkLoadError::ChunkNotFound {
+                chunk_x: 4,
+                chunk_z: 2
+            })
+        ));
+        assert!(matches!(
+            chunk_provider.load_chunk(100, 100),
+            Err(ChunkLoadError::RegionNotFound {
+                region_x: 3,
+                region_z: 3
+            })
+        ));
+        assert_eq!(chunk_provider.region_positions(), vec![(-1, -1), (0, 0)]);
+
+        let region_bytes = chunk_provider.region_bytes(-1, -1).unwrap();
+        let region_slice = RegionSlice::new(&region_bytes).unwrap();
+
+        assert_eq!(region_slice.chunk_positions(), vec![(31, 31)]);
+        assert_eq!(
+            snbt::to_string(&region_slice.read_chunk(31, 31).unwrap()),
+            snbt::to_string(&chunk_compound_tag)
+        );
+
+        // Written region is readable by file based provider.
+        let temp_dir = TempDir::new().unwrap();
+        let region_path = temp_dir.path().join("r.0.0.mca");
+        fs::write(&region_path, chunk_provider.region_bytes(0, 0).unwrap()).unwrap();
+
+        let file_chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+        let chunk_positions = file_chunk_provider.chunk_positions().unwrap();
+
+        assert_eq!(chunk_positions.len(), 276);
+        assert!(!chunk_positions.contains(&(4, 2)));
+        assert!(file_chunk_provider.load_chunk(15, 3).is_ok());
+    }
+
+    #[test]
+    fn test_from_zip() {
+        let temp_dir = TempDir::new().unwrap();
+        let zip_path = temp_dir.path().join("world.zip");
+        let region = fs::read("test/region/r.0.0.mca").unwrap();
+
+        write_zip(
+            &zip_path,
+            &[
+                ("world/level.dat", b"level"),
+                ("world/region/r.0.0.mca", &region),
+                ("world/DIM-1/region/r.1.0.mca", &region),
+            ],
+            8,
+        );
+
+        let zip_data = fs::read(&zip_path).unwrap();
+        let chunk_provider = InMemoryChunkProvider::from_zip(&zip_data, "world/region").unwrap();
+
+        assert_eq!(chunk_provider.region_positions(), vec![(0, 0)]);
+        assert!(chunk_provider.load_chunk(4, 2).is_ok());
+    }
+}
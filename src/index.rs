@@ -0,0 +1,261 @@
+//! Index of chunk presence across all region files of folder.
+//!
+//! [`ChunkIndex`] keeps bitmap of 1024 bits for every region file, 128 bytes per region,
+//! built from region headers once. Checking whether chunk exists doesn't touch region
+//! files afterwards, which matters for worlds with hundreds of thousands of regions.
+//! Index can be saved to file and loaded back, it must be kept up to date by caller
+//! with [`ChunkIndex::insert`] and [`ChunkIndex::remove`] or rebuilt after world changes.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::index::ChunkIndex;
+//! use anvil_region::AnvilChunkProvider;
+//!
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//! let chunk_index = ChunkIndex::build(&chunk_provider).unwrap();
+//!
+//! assert!(chunk_index.contains(4, 2));
+//! assert!(!chunk_index.contains(-100, 40));
+//! ```
+use crate::hash::CoordinateHashMap;
+use crate::headers::RegionHeaders;
+use crate::{AnvilChunkProvider, AnvilRegion};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// Identifies saved index file.
+const INDEX_MAGIC: u32 = 0x4143_4958;
+/// Amount of words in bitmap of one region.
+const REGION_BITMAP_WORDS: usize = 16;
+
+/// Presence bits of chunks of region in order of header.
+type RegionBitmap = [u64; REGION_BITMAP_WORDS];
+
+/// Presence of chunks keyed by region position.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ChunkIndex {
+    /// Bitmaps of regions which contain at least one chunk.
+    regions: CoordinateHashMap<(i32, i32), RegionBitmap>,
+}
+
+impl ChunkIndex {
+    pub fn new() -> Self {
+        ChunkIndex::default()
+    }
+
+    /// Builds index reading header of every region file of folder.
+    pub fn build(chunk_provider: &AnvilChunkProvider) -> Result<Self, io::Error> {
+        let mut chunk_index = ChunkIndex::new();
+
+        for region_file in chunk_provider.region_files()? {
+            let (region_x, region_z) = region_file.region_position;
+            let region_headers = RegionHeaders::open(&region_file.path)?;
+
+            for (region_chunk_x, region_chunk_z) in region_headers.chunk_positions() {
+                let chunk_x = (region_x << 5) + region_chunk_x as i32;
+                let chunk_z = (region_z << 5) + region_chunk_z as i32;
+
+                chunk_index.insert(chunk_x, chunk_z);
+            }
+        }
+
+        Ok(chunk_index)
+    }
+
+    /// Returns true if chunk at the specified coordinates is present.
+    pub fn contains(&self, chunk_x: i32, chunk_z: i32) -> bool {
+        let (word, bit) = bit_position(chunk_x, chunk_z);
+
+        match self.regions.get(&(chunk_x >> 5, chunk_z >> 5)) {
+            Some(region_bitmap) => region_bitmap[word] & bit != 0,
+            None => false,
+        }
+    }
+
+    /// Returns true if region at the specified coordinates contains any chunk.
+    pub fn contains_region(&self, region_x: i32, region_z: i32) -> bool {
+        self.regions.contains_key(&(region_x, region_z))
+    }
+
+    /// Marks chunk as present, returns false if it already was.
+    pub fn insert(&mut self, chunk_x: i32, chunk_z: i32) -> bool {
+        let (word, bit) = bit_position(chunk_x, chunk_z);
+        let region_bitmap = self
+            .regions
+            .entry((chunk_x >> 5, chunk_z >> 5))
+            .or_insert([0; REGION_BITMAP_WORDS]);
+
+        let is_new = region_bitmap[word] & bit == 0;
+        region_bitmap[word] |= bit;
+
+        is_new
+    }
+
+    /// Marks chunk as missing, returns false if it already was.
+    pub fn remove(&mut self, chunk_x: i32, chunk_z: i32) -> bool {
+        let region_position = (chunk_x >> 5, chunk_z >> 5);
+        let (word, bit) = bit_position(chunk_x, chunk_z);
+
+        let region_bitmap = match self.regions.get_mut(&region_position) {
+            Some(region_bitmap) => region_bitmap,
+            None => return false,
+        };
+
+        let was_present = region_bitmap[word] & bit != 0;
+        region_bitmap[word] &= !bit;
+
+        if region_bitmap.iter().all(|word| *word == 0) {
+            self.regions.remove(&region_position);
+        }
+
+        was_present
+    }
+
+    /// Returns amount of present chunks.
+    pub fn len(&self) -> usize {
+        self.regions
+            .values()
+            .flat_map(|region_bitmap| region_bitmap.iter())
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+
+    /// Saves index to file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), io::Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+
+        writer.flush()
+    }
+
+    /// Loads index which was saved with [`save`].
+    ///
+    /// [`save`]: ChunkIndex::save
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, io::Error> {
+        ChunkIndex::read(&mut BufReader::new(File::open(path)?))
+    }
+
+    fn write(&self, writer: &mut impl Write) -> Result<(), io::Error> {
+        let mut region_positions: Vec<_> = self.regions.keys().copied().collect();
+        region_positions.sort_unstable();
+
+        writer.write_u32::<BigEndian>(INDEX_MAGIC)?;
+        writer.write_u32::<BigEndian>(region_positions.len() as u32)?;
+
+        for region_position in region_positions {
+            let (region_x, region_z) = region_position;
+
+            writer.write_i32::<BigEndian>(region_x)?;
+            writer.write_i32::<BigEndian>(region_z)?;
+
+            for word in &self.regions[&region_position] {
+                writer.write_u64::<BigEndian>(*word)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read(reader: &mut impl Read) -> Result<Self, io::Error> {
+        if reader.read_u32::<BigEndian>()? != INDEX_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "File is not chunk index",
+            ));
+        }
+
+        let region_count = reader.read_u32::<BigEndian>()?;
+        let mut chunk_index = ChunkIndex::new();
+
+        for _ in 0..region_count {
+            let region_x = reader.read_i32::<BigEndian>()?;
+            let region_z = reader.read_i32::<BigEndian>()?;
+            let mut region_bitmap = [0; REGION_BITMAP_WORDS];
+
+            for word in region_bitmap.iter_mut() {
+                *word = reader.read_u64::<BigEndian>()?;
+            }
+
+            if region_bitmap.iter().any(|word| *word != 0) {
+                chunk_index
+                    .regions
+                    .insert((region_x, region_z), region_bitmap);
+            }
+        }
+
+        Ok(chunk_index)
+    }
+}
+
+/// Returns word and bit mask of chunk in bitmap of its region.
+fn bit_position(chunk_x: i32, chunk_z: i32) -> (usize, u64) {
+    let index = AnvilRegion::metadata_index((chunk_x & 31) as u8, (chunk_z & 31) as u8);
+
+    (index / 64, 1 << (index % 64))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::index::ChunkIndex;
+    use crate::AnvilChunkProvider;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_build() {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk_positions = chunk_provider.chunk_positions().unwrap();
+        let chunk_index = ChunkIndex::build(&chunk_provider).unwrap();
+
+        assert_eq!(chunk_index.len(), chunk_positions.len());
+        assert!(chunk_index.contains(15, 3));
+        assert!(!chunk_index.contains(15, 14));
+        assert!(chunk_index.contains_region(0, 0));
+        assert!(!chunk_index.contains_region(-1, 0));
+
+        for (chunk_x, chunk_z) in chunk_positions {
+            assert!(chunk_index.contains(chunk_x, chunk_z));
+        }
+    }
+
+    #[test]
+    fn test_insert_remove() {
+        let mut chunk_index = ChunkIndex::new();
+
+        assert!(chunk_index.insert(-1, -33));
+        assert!(!chunk_index.insert(-1, -33));
+        assert!(chunk_index.insert(63, 0));
+        assert!(chunk_index.contains(-1, -33));
+        assert!(!chunk_index.contains(-1, -1));
+        assert_eq!(chunk_index.len(), 2);
+
+        assert!(chunk_index.remove(-1, -33));
+        assert!(!chunk_index.remove(-1, -33));
+        assert!(!chunk_index.contains_region(-1, -2));
+        assert_eq!(chunk_index.len(), 1);
+    }
+
+    #[test]
+    fn test_save_load() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("chunks.idx");
+
+        let mut chunk_index = ChunkIndex::new();
+        chunk_index.insert(4, 2);
+        chunk_index.insert(-40, 1000);
+        chunk_index.save(&index_path).unwrap();
+
+        assert_eq!(ChunkIndex::load(&index_path).unwrap(), chunk_index);
+
+        fs::write(&index_path, b"not an index").unwrap();
+        assert!(ChunkIndex::load(&index_path).is_err());
+    }
+}
@@ -0,0 +1,370 @@
+//! Chunk provider which logs writes to journal before writing region files.
+//!
+//! Region file is changed in place, so power loss in the middle of save may leave its
+//! header pointing into half-written sectors. [`JournaledChunkProvider`] first appends
+//! every save or delete to journal file of region folder and syncs it, then applies it
+//! to region file and clears journal. Journal left by interrupted write is replayed
+//! when provider is opened again. Record which was cut short by crash fails its
+//! checksum and is dropped together with the write it described, since that write
+//! never reached region file.
+//!
+//! Journal is shared by the whole folder, so writes of journaled provider are
+//! serialized. Writes wait for two syncs, which makes them noticeably slower than
+//! writes of plain provider.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::journal::JournaledChunkProvider;
+//! use nbt::CompoundTag;
+//! use tempfile::TempDir;
+//!
+//! let region_dir = TempDir::new().unwrap();
+//! let chunk_provider = JournaledChunkProvider::open(region_dir.path()).unwrap();
+//!
+//! chunk_provider.save_chunk(4, 2, CompoundTag::new()).unwrap();
+//! assert!(chunk_provider.load_chunk(4, 2).is_ok());
+//! ```
+use crate::concurrent::ConcurrentAnvilChunkProvider;
+use crate::{ChunkLoadError, ChunkSaveError};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use flate2::Crc;
+use nbt::decode::read_zlib_compound_tag;
+use nbt::encode::write_zlib_compound_tag;
+use nbt::CompoundTag;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Journal file inside of region folder.
+pub const JOURNAL_FILE: &str = "region.journal";
+
+/// Record of chunk save, followed by compressed chunk data.
+const SAVE_RECORD_TYPE: u8 = 1;
+/// Record of chunk delete.
+const DELETE_RECORD_TYPE: u8 = 2;
+
+/// Provider of chunks of region folder which journals writes.
+pub struct JournaledChunkProvider {
+    chunk_provider: ConcurrentAnvilChunkProvider,
+    /// Held for the whole write, from appending record to clearing journal.
+    journal_file: Mutex<File>,
+}
+
+/// Chunk write described by journal record.
+struct JournalRecord {
+    chunk_x: i32,
+    chunk_z: i32,
+    /// Chunk data, none if chunk is deleted.
+    chunk_compound_tag: Option<CompoundTag>,
+}
+
+impl JournaledChunkProvider {
+    /// Opens provider of region folder, replaying writes of journal left by previous
+    /// provider which was interrupted.
+    pub fn open(folder_path: impl AsRef<Path>) -> Result<Self, ChunkSaveError> {
+        let folder_path = folder_path.as_ref();
+        fs::create_dir_all(folder_path)?;
+
+        let mut journal_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(folder_path.join(JOURNAL_FILE))?;
+
+        let chunk_provider = ConcurrentAnvilChunkProvider::new(folder_path);
+
+        for journal_record in read_journal(&mut journal_file)? {
+            apply(&chunk_provider, journal_record)?;
+        }
+
+        clear_journal(&mut journal_file)?;
+
+        Ok(JournaledChunkProvider {
+            chunk_provider,
+            journal_file: Mutex::new(journal_file),
+        })
+    }
+
+    pub fn folder_path(&self) -> &Path {
+        self.chunk_provider.folder_path()
+    }
+
+    /// Loads chunk from the specified coordinates.
+    pub fn load_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<CompoundTag, ChunkLoadError> {
+        self.chunk_provider.load_chunk(chunk_x, chunk_z)
+    }
+
+    /// Saves chunk at the specified coordinates once it's journaled.
+    pub fn save_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        chunk_compound_tag: CompoundTag,
+    ) -> Result<(), ChunkSaveError> {
+        self.write(JournalRecord {
+            chunk_x,
+            chunk_z,
+            chunk_compound_tag: Some(chunk_compound_tag),
+        })
+        .map(|_| ())
+    }
+
+    /// Deletes chunk at the specified coordinates once it's journaled, returns false
+    /// if chunk is not present.
+    pub fn delete_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<bool, ChunkSaveError> {
+        self.write(JournalRecord {
+            chunk_x,
+            chunk_z,
+            chunk_compound_tag: None,
+        })
+    }
+
+    fn write(&self, journal_record: JournalRecord) -> Result<bool, ChunkSaveError> {
+        let mut journal_file = self
+            .journal_file
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+
+        append_record(&mut journal_file, &journal_record)?;
+        let result = apply(&self.chunk_provider, journal_record)?;
+        clear_journal(&mut journal_file)?;
+
+        Ok(result)
+    }
+}
+
+/// Appends record with checksum to the end of journal and syncs it.
+fn append_record(
+    journal_file: &mut File,
+    journal_record: &JournalRecord,
+) -> Result<(), ChunkSaveError> {
+    let mut buffer = Vec::new();
+
+    match &journal_record.chunk_compound_tag {
+        Some(chunk_compound_tag) => {
+            let mut data = Vec::new();
+            write_zlib_compound_tag(&mut data, chunk_compound_tag)?;
+
+            buffer.write_u8(SAVE_RECORD_TYPE)?;
+            buffer.write_i32::<BigEndian>(journal_record.chunk_x)?;
+            buffer.write_i32::<BigEndian>(journal_record.chunk_z)?;
+            buffer.write_u32::<BigEndian>(data.len() as u32)?;
+            buffer.extend_from_slice(&data);
+        }
+        None => {
+            buffer.write_u8(DELETE_RECORD_TYPE)?;
+            buffer.write_i32::<BigEndian>(journal_record.chunk_x)?;
+            buffer.write_i32::<BigEndian>(journal_record.chunk_z)?;
+        }
+    }
+
+    let mut crc = Crc::new();
+    crc.update(&buffer);
+    buffer.write_u32::<BigEndian>(crc.sum())?;
+
+    journal_file.seek(SeekFrom::End(0))?;
+    journal_file.write_all(&buffer)?;
+    journal_file.sync_data()?;
+
+    Ok(())
+}
+
+/// Reads complete records of journal, record which is cut short or fails checksum
+/// ends journal.
+fn read_journal(journal_file: &mut File) -> Result<Vec<JournalRecord>, io::Error> {
+    let mut journal = Vec::new();
+    journal_file.seek(SeekFrom::Start(0))?;
+    journal_file.read_to_end(&mut journal)?;
+
+    let mut reader = Cursor::new(&journal[..]);
+    let mut journal_records = Vec::new();
+
+    while (reader.position() as usize) < journal.len() {
+        let record_start = reader.position() as usize;
+
+        let (chunk_x, chunk_z, data) = match read_record(&mut reader) {
+            Ok(record) => record,
+            Err(_) => break,
+        };
+
+        let record_end = reader.position() as usize;
+
+        let checksum = match reader.read_u32::<BigEndian>() {
+            Ok(checksum) => checksum,
+            Err(_) => break,
+        };
+
+        let mut crc = Crc::new();
+        crc.update(&journal[record_start..record_end]);
+
+        if crc.sum() != checksum {
+            break;
+        }
+
+        let chunk_compound_tag = match data {
+            Some(mut data) => Some(read_zlib_compound_tag(&mut data).map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, "Journaled chunk is corrupted")
+            })?),
+            None => None,
+        };
+
+        journal_records.push(JournalRecord {
+            chunk_x,
+            chunk_z,
+            chunk_compound_tag,
+        });
+    }
+
+    Ok(journal_records)
+}
+
+/// Reads chunk position and compressed chunk data of record, data is none for delete.
+fn read_record<'j>(
+    reader: &mut Cursor<&'j [u8]>,
+) -> Result<(i32, i32, Option<&'j [u8]>), io::Error> {
+    let record_type = reader.read_u8()?;
+    let chunk_x = reader.read_i32::<BigEndian>()?;
+    let chunk_z = reader.read_i32::<BigEndian>()?;
+
+    let data = match record_type {
+        SAVE_RECORD_TYPE => {
+            let length = reader.read_u32::<BigEndian>()? as usize;
+            let journal: &'j [u8] = reader.get_ref();
+            let position = reader.position() as usize;
+            let data = journal
+                .get(position..position + length)
+                .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+
+            reader.set_position((position + length) as u64);
+
+            Some(data)
+        }
+        DELETE_RECORD_TYPE => None,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Unknown journal record type",
+            ))
+        }
+    };
+
+    Ok((chunk_x, chunk_z, data))
+}
+
+/// Applies journaled write to region file and syncs it, returns whether deleted chunk
+/// was present.
+fn apply(
+    chunk_provider: &ConcurrentAnvilChunkProvider,
+    journal_record: JournalRecord,
+) -> Result<bool, ChunkSaveError> {
+    let JournalRecord {
+        chunk_x,
+        chunk_z,
+        chunk_compound_tag,
+    } = journal_record;
+
+    let result = match chunk_compound_tag {
+        Some(chunk_compound_tag) => chunk_provider
+            .save_chunk(chunk_x, chunk_z, chunk_compound_tag)
+            .map(|()| true)?,
+        None => chunk_provider.delete_chunk(chunk_x, chunk_z)?,
+    };
+
+    let region_name = format!("r.{}.{}.mca", chunk_x >> 5, chunk_z >> 5);
+    let region_path = chunk_provider.folder_path().join(region_name);
+
+    if region_path.exists() {
+        File::open(region_path)?.sync_all()?;
+    }
+
+    Ok(result)
+}
+
+fn clear_journal(journal_file: &mut File) -> Result<(), io::Error> {
+    journal_file.set_len(0)?;
+    journal_file.sync_data()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::journal::{append_record, JournalRecord, JournaledChunkProvider, JOURNAL_FILE};
+    use crate::AnvilChunkProvider;
+    use nbt::CompoundTag;
+    use std::fs::{self, OpenOptions};
+    use tempfile::TempDir;
+
+    fn save_record(chunk_x: i32, chunk_z: i32, value: i32) -> JournalRecord {
+        let mut chunk_compound_tag = CompoundTag::new();
+        chunk_compound_tag.insert_i32("Value", value);
+
+        JournalRecord {
+            chunk_x,
+            chunk_z,
+            chunk_compound_tag: Some(chunk_compound_tag),
+        }
+    }
+
+    #[test]
+    fn test_save_delete() {
+        let temp_dir = TempDir::new().unwrap();
+        let chunk_provider = JournaledChunkProvider::open(temp_dir.path()).unwrap();
+
+        chunk_provider.save_chunk(4, 2, CompoundTag::new()).unwrap();
+        assert!(chunk_provider.load_chunk(4, 2).is_ok());
+        assert!(chunk_provider.delete_chunk(4, 2).unwrap());
+        assert!(!chunk_provider.delete_chunk(4, 2).unwrap());
+
+        let journal_path = temp_dir.path().join(JOURNAL_FILE);
+        assert_eq!(fs::metadata(journal_path).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_recovery() {
+        let temp_dir = TempDir::new().unwrap();
+        let folder = temp_dir.path().to_str().unwrap();
+
+        AnvilChunkProvider::new(folder)
+            .save_chunk(1, 1, CompoundTag::new())
+            .unwrap();
+
+        // Journal of provider which crashed after journaling writes, the last record
+        // is torn.
+        let mut journal_file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(temp_dir.path().join(JOURNAL_FILE))
+            .unwrap();
+
+        append_record(&mut journal_file, &save_record(4, 2, 1)).unwrap();
+        append_record(
+            &mut journal_file,
+            &JournalRecord {
+                chunk_x: 1,
+                chunk_z: 1,
+                chunk_compound_tag: None,
+            },
+        )
+        .unwrap();
+        append_record(&mut journal_file, &save_record(4, 2, 2)).unwrap();
+        append_record(&mut journal_file, &save_record(70, 70, 3)).unwrap();
+
+        let journal_length = journal_file.metadata().unwrap().len();
+        journal_file.set_len(journal_length - 3).unwrap();
+        drop(journal_file);
+
+        let chunk_provider = JournaledChunkProvider::open(temp_dir.path()).unwrap();
+        let chunk_compound_tag = chunk_provider.load_chunk(4, 2).unwrap();
+
+        assert_eq!(chunk_compound_tag.get_i32("Value").unwrap(), 2);
+        assert!(chunk_provider.load_chunk(1, 1).is_err());
+        assert!(chunk_provider.load_chunk(70, 70).is_err());
+
+        let journal_path = temp_dir.path().join(JOURNAL_FILE);
+        assert_eq!(fs::metadata(journal_path).unwrap().len(), 0);
+    }
+}
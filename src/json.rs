@@ -0,0 +1,562 @@
+//! Minimal JSON used by player advancements and statistics files.
+//!
+//! Objects keep order of their entries, so rewritten files differ from originals only
+//! by changed values.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::json::{self, JsonValue};
+//!
+//! let mut json_value = json::from_str(r#"{"DataVersion": 2586, "done": false}"#).unwrap();
+//! json_value.insert("done", JsonValue::Bool(true));
+//!
+//! assert_eq!(json::to_string(&json_value), r#"{"DataVersion":2586,"done":true}"#);
+//! ```
+
+/// Possible errors while parsing JSON.
+#[derive(Debug)]
+pub enum JsonError {
+    /// Text ended before value was complete.
+    UnexpectedEnd,
+    /// Character at byte position can't appear there.
+    UnexpectedCharacter { position: usize, character: char },
+    /// Number or escape sequence at byte position is malformed.
+    InvalidLiteral { position: usize },
+}
+
+/// JSON value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    /// Number without fraction and exponent which fits into 64 bits.
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    /// Object entries in order of appearance.
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    /// Creates object without entries.
+    pub fn object() -> Self {
+        JsonValue::Object(Vec::new())
+    }
+
+    /// Returns value of object entry, none if value is not an object or has no such key.
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(entries) => entries
+                .iter()
+                .find(|(entry_key, _)| entry_key == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut JsonValue> {
+        match self {
+            JsonValue::Object(entries) => entries
+                .iter_mut()
+                .find(|(entry_key, _)| entry_key == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Replaces object entry in place or appends it, does nothing with non-object values.
+    pub fn insert(&mut self, key: &str, value: JsonValue) {
+        if let Some(old_value) = self.get_mut(key) {
+            *old_value = value;
+            return;
+        }
+
+        if let JsonValue::Object(entries) = self {
+            entries.push((key.to_owned(), value));
+        }
+    }
+
+    /// Removes object entry preserving order of the remaining entries.
+    pub fn remove(&mut self, key: &str) -> Option<JsonValue> {
+        match self {
+            JsonValue::Object(entries) => {
+                let index = entries.iter().position(|(entry_key, _)| entry_key == key)?;
+
+                Some(entries.remove(index).1)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns object entries.
+    pub fn entries(&self) -> &[(String, JsonValue)] {
+        match self {
+            JsonValue::Object(entries) => entries,
+            _ => &[],
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            JsonValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            JsonValue::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            JsonValue::Integer(value) => Some(*value as f64),
+            JsonValue::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// Indentation used for each nesting level by pretty printing, same as the game uses.
+const INDENT: &str = "  ";
+
+/// Renders value as single line JSON.
+pub fn to_string(json_value: &JsonValue) -> String {
+    let mut json = String::new();
+    write_value(&mut json, json_value, None);
+
+    json
+}
+
+/// Renders value as JSON with every object and array entry on own line.
+pub fn to_string_pretty(json_value: &JsonValue) -> String {
+    let mut json = String::new();
+    write_value(&mut json, json_value, Some(0));
+
+    json
+}
+
+/// Writes value, `depth` is a nesting level when pretty printing.
+fn write_value(json: &mut String, json_value: &JsonValue, depth: Option<usize>) {
+    match json_value {
+        JsonValue::Null => json.push_str("null"),
+        JsonValue::Bool(value) => json.push_str(&value.to_string()),
+        JsonValue::Integer(value) => json.push_str(&value.to_string()),
+        JsonValue::Float(value) if value.is_finite() => json.push_str(&format!("{:?}", value)),
+        JsonValue::Float(_) => json.push_str("null"),
+        JsonValue::String(value) => write_quoted(json, value),
+        JsonValue::Array(values) => {
+            write_entries(json, '[', ']', values, depth, |json, value, depth| {
+                write_value(json, value, depth)
+            })
+        }
+        JsonValue::Object(entries) => write_entries(
+            json,
+            '{',
+            '}',
+            entries,
+            depth,
+            |json, (key, value), depth| {
+                write_quoted(json, key);
+                json.push(':');
+
+                if depth.is_some() {
+                    json.push(' ');
+                }
+
+                write_value(json, value, depth)
+            },
+        ),
+    }
+}
+
+fn write_entries<T>(
+    json: &mut String,
+    open: char,
+    close: char,
+    entries: &[T],
+    depth: Option<usize>,
+    write_entry: impl Fn(&mut String, &T, Option<usize>),
+) {
+    json.push(open);
+
+    if entries.is_empty() {
+        json.push(close);
+        return;
+    }
+
+    for (index, entry) in entries.iter().enumerate() {
+        if index > 0 {
+            json.push(',');
+        }
+
+        if let Some(depth) = depth {
+            json.push('\n');
+            json.push_str(&INDENT.repeat(depth + 1));
+        }
+
+        write_entry(json, entry, depth.map(|depth| depth + 1));
+    }
+
+    if let Some(depth) = depth {
+        json.push('\n');
+        json.push_str(&INDENT.repeat(depth));
+    }
+
+    json.push(close);
+}
+
+fn write_quoted(json: &mut String, value: &str) {
+    json.push('"');
+
+    for character in value.chars() {
+        match character {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            character if (character as u32) < 0x20 => {
+                json.push_str(&format!("\\u{:04x}", character as u32))
+            }
+            character => json.push(character),
+        }
+    }
+
+    json.push('"');
+}
+
+/// Parses JSON text.
+pub fn from_str(json: &str) -> Result<JsonValue, JsonError> {
+    let mut parser = Parser { json, position: 0 };
+
+    let json_value = parser.read_value()?;
+    parser.skip_whitespace();
+
+    match parser.peek() {
+        Some(character) => Err(parser.unexpected_character(character)),
+        None => Ok(json_value),
+    }
+}
+
+/// Recursive descent parser over JSON text.
+struct Parser<'a> {
+    json: &'a str,
+    /// Byte position of the next character.
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.json[self.position..].chars().next()
+    }
+
+    fn next(&mut self) -> Result<char, JsonError> {
+        let character = self.peek().ok_or(JsonError::UnexpectedEnd)?;
+        self.position += character.len_utf8();
+
+        Ok(character)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(character) = self.peek() {
+            if !character.is_whitespace() {
+                break;
+            }
+
+            self.position += character.len_utf8();
+        }
+    }
+
+    fn unexpected_character(&self, character: char) -> JsonError {
+        JsonError::UnexpectedCharacter {
+            position: self.position,
+            character,
+        }
+    }
+
+    /// Skips whitespace and consumes expected character.
+    fn expect(&mut self, expected: char) -> Result<(), JsonError> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some(character) if character == expected => {
+                self.position += character.len_utf8();
+                Ok(())
+            }
+            Some(character) => Err(self.unexpected_character(character)),
+            None => Err(JsonError::UnexpectedEnd),
+        }
+    }
+
+    /// Skips whitespace and consumes character if it matches.
+    fn consume(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+
+        if self.peek() == Some(expected) {
+            self.position += expected.len_utf8();
+            return true;
+        }
+
+        false
+    }
+
+    fn read_value(&mut self) -> Result<JsonValue, JsonError> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some('{') => self.read_object(),
+            Some('[') => self.read_array(),
+            Some('"') => Ok(JsonValue::String(self.read_quoted()?)),
+            Some('-') | Some('0'..='9') => self.read_number(),
+            Some(character) if character.is_ascii_alphabetic() => self.read_keyword(),
+            Some(character) => Err(self.unexpected_character(character)),
+            None => Err(JsonError::UnexpectedEnd),
+        }
+    }
+
+    fn read_object(&mut self) -> Result<JsonValue, JsonError> {
+        self.expect('{')?;
+        let mut entries = Vec::new();
+
+        if self.consume('}') {
+            return Ok(JsonValue::Object(entries));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let key = self.read_quoted()?;
+
+            self.expect(':')?;
+            entries.push((key, self.read_value()?));
+
+            if !self.consume(',') {
+                break;
+            }
+        }
+
+        self.expect('}')?;
+
+        Ok(JsonValue::Object(entries))
+    }
+
+    fn read_array(&mut self) -> Result<JsonValue, JsonError> {
+        self.expect('[')?;
+        let mut values = Vec::new();
+
+        if self.consume(']') {
+            return Ok(JsonValue::Array(values));
+        }
+
+        loop {
+            values.push(self.read_value()?);
+
+            if !self.consume(',') {
+                break;
+            }
+        }
+
+        self.expect(']')?;
+
+        Ok(JsonValue::Array(values))
+    }
+
+    fn read_quoted(&mut self) -> Result<String, JsonError> {
+        self.expect('"')?;
+        let mut value = String::new();
+
+        loop {
+            match self.next()? {
+                '"' => return Ok(value),
+                '\\' => {
+                    let position = self.position;
+
+                    let character = match self.next()? {
+                        '"' => '"',
+                        '\\' => '\\',
+                        '/' => '/',
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        'u' => self.read_unicode_escape(position)?,
+                        _ => return Err(JsonError::InvalidLiteral { position }),
+                    };
+
+                    value.push(character);
+                }
+                character => value.push(character),
+            }
+        }
+    }
+
+    /// Reads hex digits of `\u` escape, surrogate pairs are combined.
+    fn read_unicode_escape(&mut self, position: usize) -> Result<char, JsonError> {
+        let high = self.read_hex_digits(position)?;
+
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high).ok_or(JsonError::InvalidLiteral { position });
+        }
+
+        if self.next()? != '\\' || self.next()? != 'u' {
+            return Err(JsonError::InvalidLiteral { position });
+        }
+
+        let low = self.read_hex_digits(position)?;
+
+        if !(0xDC00..0xE000).contains(&low) {
+            return Err(JsonError::InvalidLiteral { position });
+        }
+
+        char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+            .ok_or(JsonError::InvalidLiteral { position })
+    }
+
+    fn read_hex_digits(&mut self, position: usize) -> Result<u32, JsonError> {
+        let digits = self
+            .json
+            .get(self.position..self.position + 4)
+            .ok_or(JsonError::UnexpectedEnd)?;
+        let value =
+            u32::from_str_radix(digits, 16).map_err(|_| JsonError::InvalidLiteral { position })?;
+
+        self.position += 4;
+
+        Ok(value)
+    }
+
+    fn read_number(&mut self) -> Result<JsonValue, JsonError> {
+        let position = self.position;
+
+        while let Some(character) = self.peek() {
+            if !matches!(character, '0'..='9' | '-' | '+' | '.' | 'e' | 'E') {
+                break;
+            }
+
+            self.position += 1;
+        }
+
+        let literal = &self.json[position..self.position];
+        let is_integer = !literal.contains(['.', 'e', 'E']);
+
+        if is_integer {
+            if let Ok(value) = literal.parse() {
+                return Ok(JsonValue::Integer(value));
+            }
+        }
+
+        literal
+            .parse()
+            .map(JsonValue::Float)
+            .map_err(|_| JsonError::InvalidLiteral { position })
+    }
+
+    fn read_keyword(&mut self) -> Result<JsonValue, JsonError> {
+        let position = self.position;
+
+        while self
+            .peek()
+            .is_some_and(|character| character.is_ascii_alphabetic())
+        {
+            self.position += 1;
+        }
+
+        match &self.json[position..self.position] {
+            "null" => Ok(JsonValue::Null),
+            "true" => Ok(JsonValue::Bool(true)),
+            "false" => Ok(JsonValue::Bool(false)),
+            _ => Err(JsonError::InvalidLiteral { position }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json::{from_str, to_string, to_string_pretty, JsonError, JsonValue};
+
+    #[test]
+    fn test_roundtrip() {
+        let json = r#"{"a":[1,-2.5,1e20,true,null],"b":{"c":"x\"y\\z\né"},"d":{},"e":[]}"#;
+        let json_value = from_str(json).unwrap();
+
+        assert_eq!(json_value.get("a").unwrap(), &{
+            JsonValue::Array(vec![
+                JsonValue::Integer(1),
+                JsonValue::Float(-2.5),
+                JsonValue::Float(1e20),
+                JsonValue::Bool(true),
+                JsonValue::Null,
+            ])
+        });
+        assert_eq!(
+            json_value.get("b").unwrap().get("c").unwrap().as_str(),
+            Some("x\"y\\z\né")
+        );
+        assert_eq!(
+            to_string(&json_value),
+            r#"{"a":[1,-2.5,1e20,true,null],"b":{"c":"x\"y\\z\né"},"d":{},"e":[]}"#
+        );
+    }
+
+    #[test]
+    fn test_to_string_pretty() {
+        let json_value = from_str(r#"{"a": {"b": [1, 2]}, "c": {}}"#).unwrap();
+
+        assert_eq!(
+            to_string_pretty(&json_value),
+            "{\n  \"a\": {\n    \"b\": [\n      1,\n      2\n    ]\n  },\n  \"c\": {}\n}"
+        );
+    }
+
+    #[test]
+    fn test_insert_and_remove_keep_order() {
+        let mut json_value = from_str(r#"{"a": 1, "b": 2, "c": 3}"#).unwrap();
+
+        json_value.insert("a", JsonValue::Integer(10));
+        json_value.insert("d", JsonValue::Integer(4));
+        assert_eq!(json_value.remove("b"), Some(JsonValue::Integer(2)));
+        assert_eq!(json_value.remove("b"), None);
+
+        assert_eq!(to_string(&json_value), r#"{"a":10,"c":3,"d":4}"#);
+    }
+
+    #[test]
+    fn test_surrogate_pair() {
+        let json_value = from_str(r#""😀""#).unwrap();
+
+        assert_eq!(json_value.as_str(), Some("😀"));
+    }
+
+    #[test]
+    fn test_invalid_json() {
+        match from_str(r#"{"a": tru}"#) {
+            Err(JsonError::InvalidLiteral { position }) => assert_eq!(position, 6),
+            result => panic!("Expected `InvalidLiteral` but got `{:?}`", result),
+        }
+
+        match from_str(r#"{"a": 1"#) {
+            Err(JsonError::UnexpectedEnd) => {}
+            result => panic!("Expected `UnexpectedEnd` but got `{:?}`", result),
+        }
+
+        match from_str("[1] 2") {
+            Err(JsonError::UnexpectedCharacter {
+                position,
+                character,
+            }) => assert_eq!((position, character), (4, '2')),
+            result => panic!("Expected `UnexpectedCharacter` but got `{:?}`", result),
+        }
+    }
+}
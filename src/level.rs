@@ -0,0 +1,446 @@
+//! Typed access to `level.dat`.
+//!
+//! Values are read from and written to `Data` compound in place, so tags which have
+//! no accessor are saved back unchanged. When `level.dat` is missing or damaged the
+//! previous copy in `level.dat_old` is used, like the game does.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::level::LevelData;
+//! use tempfile::TempDir;
+//!
+//! let world_dir = TempDir::new().unwrap();
+//!
+//! let mut level_data = LevelData::new();
+//! level_data.set_name("Survival");
+//! level_data.set_game_rule("keepInventory", "true");
+//! level_data.save(world_dir.path()).unwrap();
+//!
+//! let level_data = LevelData::load(world_dir.path()).unwrap();
+//! assert_eq!(level_data.name(), Some("Survival"));
+//! assert_eq!(level_data.game_rule("keepInventory"), Some("true"));
+//! ```
+use crate::data::{read_data_file, write_data_file, DataFileError};
+use crate::tag::{get_tag, set_tag};
+use nbt::{CompoundTag, Tag};
+use std::fs;
+use std::path::Path;
+
+/// World file with global world settings.
+pub const LEVEL_DAT_FILE: &str = "level.dat";
+/// Previous copy of `level.dat` kept by the game.
+pub const LEVEL_DAT_OLD_FILE: &str = "level.dat_old";
+/// Temporary file which new `level.dat` is written to before replacing it.
+const LEVEL_DAT_NEW_FILE: &str = "level.dat_new";
+/// Name of compound tag which contains level values.
+const DATA_TAG_NAME: &str = "Data";
+
+/// World border settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorldBorder {
+    pub center_x: f64,
+    pub center_z: f64,
+    /// Border side length in blocks.
+    pub size: f64,
+    /// Damage per block player is outside of safe zone.
+    pub damage_per_block: f64,
+    /// Distance outside of border in which player takes no damage.
+    pub safe_zone: f64,
+    /// Distance to border when warning is shown.
+    pub warning_blocks: f64,
+    /// Time in seconds before shrinking border reaches player when warning is shown.
+    pub warning_time: f64,
+}
+
+impl Default for WorldBorder {
+    /// Returns border which is used by the game when level doesn't contain one.
+    fn default() -> Self {
+        WorldBorder {
+            center_x: 0.0,
+            center_z: 0.0,
+            size: 59_999_968.0,
+            damage_per_block: 0.2,
+            safe_zone: 5.0,
+            warning_blocks: 5.0,
+            warning_time: 15.0,
+        }
+    }
+}
+
+/// Version of the game which last saved world.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct VersionInfo {
+    /// Data version.
+    pub id: i32,
+    /// Version name, for example `1.16.5`.
+    pub name: String,
+    pub snapshot: bool,
+}
+
+/// `level.dat` compound tag with typed accessors.
+#[derive(Debug, Clone)]
+pub struct LevelData {
+    compound_tag: CompoundTag,
+}
+
+impl LevelData {
+    /// Creates level without any values.
+    pub fn new() -> Self {
+        let mut compound_tag = CompoundTag::new();
+        compound_tag.insert_compound_tag(DATA_TAG_NAME, CompoundTag::new());
+
+        LevelData { compound_tag }
+    }
+
+    /// Wraps `level.dat` compound tag, which must contain `Data` compound.
+    pub fn from_compound_tag(compound_tag: CompoundTag) -> Result<Self, DataFileError> {
+        if compound_tag.get_compound_tag(DATA_TAG_NAME).is_err() {
+            return Err(DataFileError::InvalidTag {
+                name: DATA_TAG_NAME.to_owned(),
+            });
+        }
+
+        Ok(LevelData { compound_tag })
+    }
+
+    /// Reads `level.dat` of world folder, falling back to `level.dat_old`.
+    pub fn load(world_folder_path: &Path) -> Result<Self, DataFileError> {
+        let load = |name: &str| {
+            read_data_file(&world_folder_path.join(name)).and_then(LevelData::from_compound_tag)
+        };
+
+        load(LEVEL_DAT_FILE).or_else(|error| load(LEVEL_DAT_OLD_FILE).map_err(|_| error))
+    }
+
+    /// Writes `level.dat` of world folder, keeping previous one as `level.dat_old`.
+    pub fn save(&self, world_folder_path: &Path) -> Result<(), DataFileError> {
+        let level_dat_path = world_folder_path.join(LEVEL_DAT_FILE);
+        let level_dat_new_path = world_folder_path.join(LEVEL_DAT_NEW_FILE);
+
+        write_data_file(&level_dat_new_path, &self.compound_tag)?;
+
+        let replace = || {
+            if level_dat_path.exists() {
+                fs::rename(&level_dat_path, world_folder_path.join(LEVEL_DAT_OLD_FILE))?;
+            }
+
+            fs::rename(&level_dat_new_path, &level_dat_path)
+        };
+
+        replace().map_err(|io_error| DataFileError::WriteError { io_error })
+    }
+
+    /// Returns whole `level.dat` compound tag.
+    pub fn compound_tag(&self) -> &CompoundTag {
+        &self.compound_tag
+    }
+
+    pub fn into_compound_tag(self) -> CompoundTag {
+        self.compound_tag
+    }
+
+    /// Returns `Data` compound tag.
+    pub fn data(&self) -> &CompoundTag {
+        // Presence of compound tag is checked on creation.
+        self.compound_tag.get_compound_tag(DATA_TAG_NAME).unwrap()
+    }
+
+    pub fn data_mut(&mut self) -> &mut CompoundTag {
+        self.compound_tag
+            .get_mut::<&mut CompoundTag>(DATA_TAG_NAME)
+            .unwrap()
+    }
+
+    /// Returns world name shown in world list.
+    pub fn name(&self) -> Option<&str> {
+        self.data().get_str("LevelName").ok()
+    }
+
+    pub fn set_name(&mut self, name: &str) {
+        set_tag(self.data_mut(), "LevelName", Tag::String(name.to_owned()));
+    }
+
+    /// Returns world seed.
+    ///
+    /// Since 1.16 seed is stored in `WorldGenSettings` instead of `RandomSeed`.
+    pub fn seed(&self) -> Option<i64> {
+        let data = self.data();
+
+        match data.get_compound_tag("WorldGenSettings") {
+            Ok(world_gen_settings) => world_gen_settings.get_i64("seed").ok(),
+            Err(_) => data.get_i64("RandomSeed").ok(),
+        }
+    }
+
+    pub fn set_seed(&mut self, seed: i64) {
+        let data = self.data_mut();
+
+        match data.get_mut::<&mut CompoundTag>("WorldGenSettings") {
+            Ok(world_gen_settings) => set_tag(world_gen_settings, "seed", Tag::Long(seed)),
+            Err(_) => set_tag(data, "RandomSeed", Tag::Long(seed)),
+        }
+    }
+
+    /// Returns world spawn block coordinates.
+    pub fn spawn(&self) -> Option<(i32, i32, i32)> {
+        let data = self.data();
+
+        Some((
+            data.get_i32("SpawnX").ok()?,
+            data.get_i32("SpawnY").ok()?,
+            data.get_i32("SpawnZ").ok()?,
+        ))
+    }
+
+    pub fn set_spawn(&mut self, x: i32, y: i32, z: i32) {
+        let data = self.data_mut();
+
+        set_tag(data, "SpawnX", Tag::Int(x));
+        set_tag(data, "SpawnY", Tag::Int(y));
+        set_tag(data, "SpawnZ", Tag::Int(z));
+    }
+
+    /// Returns game rule value like `true` or `3`.
+    pub fn game_rule(&self, name: &str) -> Option<&str> {
+        let game_rules = self.data().get_compound_tag("GameRules").ok()?;
+
+        match get_tag(game_rules, name)? {
+            Tag::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns names and values of every game rule stored in level.
+    pub fn game_rules(&self) -> Vec<(&str, &str)> {
+        let game_rules = match self.data().get_compound_tag("GameRules") {
+            Ok(game_rules) => game_rules,
+            Err(_) => return Vec::new(),
+        };
+
+        game_rules
+            .iter()
+            .filter_map(|(name, value)| match value {
+                Tag::String(value) => Some((name.as_str(), value.as_str())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn set_game_rule(&mut self, name: &str, value: &str) {
+        let data = self.data_mut();
+
+        if !data.contains_key("GameRules") {
+            data.insert_compound_tag("GameRules", CompoundTag::new());
+        }
+
+        if let Ok(game_rules) = data.get_mut::<&mut CompoundTag>("GameRules") {
+            set_tag(game_rules, name, Tag::String(value.to_owned()));
+        }
+    }
+
+    /// Returns world border, missing values are filled with defaults.
+    pub fn world_border(&self) -> WorldBorder {
+        let data = self.data();
+        let default = WorldBorder::default();
+        let value = |name: &str, default: f64| data.get_f64(name).unwrap_or(default);
+
+        WorldBorder {
+            center_x: value("BorderCenterX", default.center_x),
+            center_z: value("BorderCenterZ", default.center_z),
+            size: value("BorderSize", default.size),
+            damage_per_block: value("BorderDamagePerBlock", default.damage_per_block),
+            safe_zone: value("BorderSafeZone", default.safe_zone),
+            warning_blocks: value("BorderWarningBlocks", default.warning_blocks),
+            warning_time: value("BorderWarningTime", default.warning_time),
+        }
+    }
+
+    /// Sets world border, stopping border which is currently shrinking or growing.
+    pub fn set_world_border(&mut self, world_border: &WorldBorder) {
+        let data = self.data_mut();
+
+        for (name, value) in &[
+            ("BorderCenterX", world_border.center_x),
+            ("BorderCenterZ", world_border.center_z),
+            ("BorderSize", world_border.size),
+            ("BorderDamagePerBlock", world_border.damage_per_block),
+            ("BorderSafeZone", world_border.safe_zone),
+            ("BorderWarningBlocks", world_border.warning_blocks),
+            ("BorderWarningTime", world_border.warning_time),
+            ("BorderSizeLerpTarget", world_border.size),
+        ] {
+            set_tag(data, name, Tag::Double(*value));
+        }
+
+        set_tag(data, "BorderSizeLerpTime", Tag::Long(0));
+    }
+
+    /// Returns identifiers of dimensions registered in world generation settings.
+    ///
+    /// Present since 1.16, includes vanilla dimensions and dimensions added by datapacks.
+    pub fn dimension_names(&self) -> Vec<&str> {
+        self.data()
+            .get_compound_tag("WorldGenSettings")
+            .and_then(|world_gen_settings| world_gen_settings.get_compound_tag("dimensions"))
+            .map_or_else(
+                |_| Vec::new(),
+                |dimensions| dimensions.iter().map(|(name, _)| name.as_str()).collect(),
+            )
+    }
+
+    /// Returns data version of the game which last saved world.
+    pub fn data_version(&self) -> Option<i32> {
+        self.data().get_i32("DataVersion").ok()
+    }
+
+    /// Returns version of the game which last saved world, present since 1.9.
+    pub fn version(&self) -> Option<VersionInfo> {
+        let version = self.data().get_compound_tag("Version").ok()?;
+
+        Some(VersionInfo {
+            id: version.get_i32("Id").ok()?,
+            name: version.get_str("Name").ok()?.to_owned(),
+            snapshot: version.get_bool("Snapshot").unwrap_or(false),
+        })
+    }
+
+    /// Returns last time world was played in milliseconds since unix epoch.
+    pub fn last_played(&self) -> Option<i64> {
+        match get_tag(self.data(), "LastPlayed") {
+            Some(Tag::Long(last_played)) => Some(*last_played),
+            _ => None,
+        }
+    }
+}
+
+impl Default for LevelData {
+    fn default() -> Self {
+        LevelData::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{read_data_file, write_data_file, DataFileError};
+    use crate::level::{LevelData, VersionInfo, WorldBorder};
+    use crate::snbt;
+    use nbt::CompoundTag;
+    use tempfile::TempDir;
+
+    fn modern_level_data() -> LevelData {
+        let compound_tag = snbt::from_str(
+            r#"{Data: {
+                LevelName: "World",
+                DataVersion: 2586,
+                Version: {Id: 2586, Name: "1.16.5", Snapshot: 0b},
+                WorldGenSettings: {
+                    seed: -42L,
+                    bonus_chest: 0b,
+                    dimensions: {"minecraft:overworld": {}, "mymod:mining": {}}
+                },
+                SpawnX: 10, SpawnY: 64, SpawnZ: -3,
+                GameRules: {doDaylightCycle: "false", randomTickSpeed: "3"},
+                BorderSize: 1000.0d,
+                ModData: {mod: 1}
+            }}"#,
+        )
+        .unwrap();
+
+        LevelData::from_compound_tag(compound_tag).unwrap()
+    }
+
+    #[test]
+    fn test_accessors() {
+        let level_data = modern_level_data();
+
+        assert_eq!(level_data.name(), Some("World"));
+        assert_eq!(level_data.seed(), Some(-42));
+        assert_eq!(level_data.spawn(), Some((10, 64, -3)));
+        assert_eq!(level_data.game_rule("doDaylightCycle"), Some("false"));
+        assert_eq!(
+            level_data.game_rules(),
+            vec![("doDaylightCycle", "false"), ("randomTickSpeed", "3")]
+        );
+        assert_eq!(
+            level_data.dimension_names(),
+            vec!["minecraft:overworld", "mymod:mining"]
+        );
+        assert_eq!(level_data.data_version(), Some(2586));
+        assert_eq!(
+            level_data.version(),
+            Some(VersionInfo {
+                id: 2586,
+                name: "1.16.5".to_owned(),
+                snapshot: false,
+            })
+        );
+        assert_eq!(
+            level_data.world_border(),
+            WorldBorder {
+                size: 1000.0,
+                ..WorldBorder::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_set_seed() {
+        let mut level_data = modern_level_data();
+        level_data.set_seed(7);
+
+        assert_eq!(level_data.seed(), Some(7));
+        assert!(!level_data.data().contains_key("RandomSeed"));
+
+        let mut level_data = LevelData::new();
+        level_data.set_seed(7);
+
+        assert_eq!(level_data.data().get_i64("RandomSeed").unwrap(), 7);
+    }
+
+    #[test]
+    fn test_save_preserves_unknown_tags() {
+        let world_dir = TempDir::new().unwrap();
+        let mut level_data = modern_level_data();
+
+        level_data.save(world_dir.path()).unwrap();
+        level_data.set_spawn(0, 70, 0);
+        level_data.save(world_dir.path()).unwrap();
+
+        let loaded_level_data = LevelData::load(world_dir.path()).unwrap();
+        assert_eq!(loaded_level_data.spawn(), Some((0, 70, 0)));
+        assert_eq!(
+            snbt::to_string(loaded_level_data.compound_tag()),
+            snbt::to_string(level_data.compound_tag())
+        );
+
+        let old_compound_tag = read_data_file(&world_dir.path().join("level.dat_old")).unwrap();
+        let old_level_data = LevelData::from_compound_tag(old_compound_tag).unwrap();
+        assert_eq!(old_level_data.spawn(), Some((10, 64, -3)));
+    }
+
+    #[test]
+    fn test_load_falls_back_to_old() {
+        let world_dir = TempDir::new().unwrap();
+
+        modern_level_data().save(world_dir.path()).unwrap();
+        std::fs::rename(
+            world_dir.path().join("level.dat"),
+            world_dir.path().join("level.dat_old"),
+        )
+        .unwrap();
+
+        let level_data = LevelData::load(world_dir.path()).unwrap();
+        assert_eq!(level_data.name(), Some("World"));
+
+        write_data_file(&world_dir.path().join("level.dat"), &CompoundTag::new()).unwrap();
+        assert!(LevelData::load(world_dir.path()).is_ok());
+
+        std::fs::remove_file(world_dir.path().join("level.dat_old")).unwrap();
+
+        match LevelData::load(world_dir.path()) {
+            Err(DataFileError::InvalidTag { name }) => assert_eq!(name, "Data"),
+            result => panic!("Expected `InvalidTag` but got `{:?}`", result),
+        }
+    }
+}
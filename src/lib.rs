@@ -37,17 +37,128 @@
 //!
 //! chunk_provider.save_chunk(31, 16, chunk_compound_tag);
 //! ```
+use crate::borrowed::BorrowedCompound;
+use crate::buffer::{decode_chunk, with_chunk_buffers, ChunkBuffers};
+use crate::chunk::{Chunk, ChunkStatus};
+use crate::headers::{HeaderCache, RegionHeaders};
+use crate::limits::{LimitExceeded, ParseLimits};
+use crate::metrics::{Metrics, NoopMetrics, ProviderActivity, ProviderCounters};
+use crate::parse::{ParseIssue, ParseMode, ParseWarning};
+use crate::quota::QuotaExceeded;
+use crate::snapshot::break_hard_link;
+use crate::trace::{enter_span, trace_event};
 use bitvec::prelude::*;
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use nbt::decode::TagDecodeError;
-use nbt::decode::{read_gzip_compound_tag, read_zlib_compound_tag};
 use nbt::encode::write_zlib_compound_tag;
 use nbt::CompoundTag;
+use std::error::Error;
 use std::fs::{File, OpenOptions};
-use std::io::{Cursor, Read, Seek, SeekFrom, Write};
-use std::path::Path;
-use std::time::{SystemTime, UNIX_EPOCH};
-use std::{fs, io};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use std::{fmt, fs, io, mem};
+
+pub mod alpha;
+pub mod async_provider;
+pub mod backup;
+#[cfg(feature = "bedrock")]
+pub mod bedrock;
+pub mod biome_map;
+pub mod borrowed;
+mod buffer;
+pub mod cancel;
+pub mod chunk;
+#[cfg(feature = "cli")]
+pub mod cli;
+pub mod clipboard;
+pub mod compact;
+pub mod concurrent;
+pub mod coords;
+pub mod copy;
+#[cfg(feature = "cubic")]
+pub mod cubic;
+pub mod data;
+pub mod delta;
+pub mod diff;
+pub mod downgrade;
+pub mod dump;
+pub mod duplicates;
+pub mod entities;
+pub mod extent;
+#[cfg(feature = "fastnbt")]
+pub mod fastnbt_interop;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod fill;
+#[cfg(feature = "test-util")]
+pub mod fixture;
+pub mod hash;
+pub mod headers;
+pub mod heatmap;
+pub mod height;
+pub mod heightmap_image;
+pub mod history;
+pub mod in_memory;
+pub mod index;
+pub mod journal;
+pub mod json;
+pub mod level;
+pub mod light;
+pub mod limits;
+pub mod loader;
+pub mod memory;
+pub mod merge;
+pub mod metadata;
+pub mod metrics;
+#[cfg(feature = "mock")]
+pub mod mock;
+mod packed;
+pub mod parse;
+pub mod path;
+pub mod player;
+pub mod prefetch;
+pub mod progress;
+#[cfg(feature = "proptest")]
+pub mod proptest_interop;
+pub mod provider;
+pub mod prune;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod quota;
+pub mod region_reader;
+pub mod region_slice;
+pub mod relocate;
+pub mod remap;
+#[cfg(feature = "render")]
+pub mod render;
+pub mod retry;
+pub mod roundtrip;
+pub mod schematic;
+pub mod search;
+pub mod section;
+pub mod sector_map;
+pub mod snapshot;
+pub mod snbt;
+pub mod spatial;
+pub mod stats;
+pub mod structure;
+pub mod structure_template;
+mod tag;
+pub mod touch;
+mod trace;
+pub mod transaction;
+pub mod trim;
+pub mod upgrade;
+pub mod validate;
+pub mod version;
+pub mod world;
+pub mod write_back;
+mod zip;
+#[cfg(feature = "zstd")]
+pub mod zstd_dictionary;
 
 /// Amount of chunks in region.
 const REGION_CHUNKS: usize = 1024;
@@ -63,14 +174,22 @@ const CHUNK_MAXIMUM_BYTES_LENGTH: u32 = REGION_SECTOR_BYTES_LENGTH as u32 * 256;
 const GZIP_COMPRESSION_TYPE: u8 = 1;
 /// Zlib compression type value.
 const ZLIB_COMPRESSION_TYPE: u8 = 2;
+/// Uncompressed type value.
+const UNCOMPRESSED_COMPRESSION_TYPE: u8 = 3;
+/// Custom compression type value, followed by namespaced id of algorithm.
+#[cfg(feature = "zstd")]
+const CUSTOM_COMPRESSION_TYPE: u8 = 127;
 
 /// Possible errors while loading the chunk.
 #[derive(Debug)]
 pub enum ChunkLoadError {
     /// Region at specified coordinates not found.
     RegionNotFound { region_x: i32, region_z: i32 },
-    /// Chunk at specified coordinates inside region not found.
-    ChunkNotFound { chunk_x: u8, chunk_z: u8 },
+    /// Chunk at specified coordinates not found.
+    ///
+    /// Providers report absolute chunk coordinates, readers of single region file
+    /// report coordinates inside of region.
+    ChunkNotFound { chunk_x: i32, chunk_z: i32 },
     /// Chunk length overlaps declared maximum.
     ///
     /// This should not occur under normal conditions.
@@ -99,6 +218,110 @@ pub enum ChunkLoadError {
     ///
     /// Region file are corrupted or a developer error in the NBT library.
     TagDecodeError { tag_decode_error: TagDecodeError },
+    /// Chunk data is irregular and provider is in strict parse mode.
+    MalformedChunk { parse_issue: ParseIssue },
+    /// Loading chunk would exceed resource limit of provider.
+    LimitExceeded { limit_exceeded: LimitExceeded },
+    /// Error of reading chunk from region file with path of file and absolute chunk
+    /// coordinates, added by providers.
+    InRegionFile {
+        region_path: PathBuf,
+        chunk_x: i32,
+        chunk_z: i32,
+        error: Box<ChunkLoadError>,
+    },
+}
+
+impl ChunkLoadError {
+    /// Returns error without region file context.
+    pub fn without_context(&self) -> &ChunkLoadError {
+        match self {
+            ChunkLoadError::InRegionFile { error, .. } => error.without_context(),
+            chunk_load_error => chunk_load_error,
+        }
+    }
+
+    /// Adds region file path and absolute chunk coordinates to error of reading chunk.
+    ///
+    /// Missing chunk gets absolute coordinates instead, missing region and exceeded
+    /// limit don't depend on region file and are returned as is.
+    pub(crate) fn with_context(self, region_path: &Path, chunk_x: i32, chunk_z: i32) -> Self {
+        match self {
+            ChunkLoadError::ChunkNotFound { .. } => {
+                ChunkLoadError::ChunkNotFound { chunk_x, chunk_z }
+            }
+            ChunkLoadError::RegionNotFound { .. }
+            | ChunkLoadError::LimitExceeded { .. }
+            | ChunkLoadError::InRegionFile { .. } => self,
+            chunk_load_error => ChunkLoadError::InRegionFile {
+                region_path: region_path.to_path_buf(),
+                chunk_x,
+                chunk_z,
+                error: Box::new(chunk_load_error),
+            },
+        }
+    }
+}
+
+impl fmt::Display for ChunkLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChunkLoadError::RegionNotFound { region_x, region_z } => {
+                write!(f, "region {} {} not found", region_x, region_z)
+            }
+            ChunkLoadError::ChunkNotFound { chunk_x, chunk_z } => {
+                write!(f, "chunk {} {} not found", chunk_x, chunk_z)
+            }
+            ChunkLoadError::LengthExceedsMaximum {
+                length,
+                maximum_length,
+            } => write!(
+                f,
+                "chunk length {} exceeds maximum {}",
+                length, maximum_length
+            ),
+            ChunkLoadError::UnsupportedCompressionScheme { compression_scheme } => {
+                write!(f, "unsupported compression scheme {}", compression_scheme)
+            }
+            ChunkLoadError::ReadError { io_error } => {
+                write!(f, "failed to read chunk: {}", io_error)
+            }
+            ChunkLoadError::TagDecodeError { tag_decode_error } => {
+                write!(f, "failed to decode chunk: {}", tag_decode_error)
+            }
+            ChunkLoadError::MalformedChunk { parse_issue } => {
+                write!(f, "malformed chunk: {:?}", parse_issue)
+            }
+            ChunkLoadError::LimitExceeded { limit_exceeded } => {
+                write!(f, "limit exceeded: {}", limit_exceeded)
+            }
+            ChunkLoadError::InRegionFile {
+                region_path,
+                chunk_x,
+                chunk_z,
+                error,
+            } => write!(
+                f,
+                "chunk {} {} of {}: {}",
+                chunk_x,
+                chunk_z,
+                region_path.display(),
+                error
+            ),
+        }
+    }
+}
+
+impl Error for ChunkLoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ChunkLoadError::ReadError { io_error } => Some(io_error),
+            ChunkLoadError::TagDecodeError { tag_decode_error } => Some(tag_decode_error),
+            ChunkLoadError::LimitExceeded { limit_exceeded } => Some(limit_exceeded),
+            ChunkLoadError::InRegionFile { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
 }
 
 impl From<io::Error> for ChunkLoadError {
@@ -113,6 +336,12 @@ impl From<TagDecodeError> for ChunkLoadError {
     }
 }
 
+impl From<LimitExceeded> for ChunkLoadError {
+    fn from(limit_exceeded: LimitExceeded) -> Self {
+        ChunkLoadError::LimitExceeded { limit_exceeded }
+    }
+}
+
 /// Possible errors while saving the chunk.
 #[derive(Debug)]
 pub enum ChunkSaveError {
@@ -125,6 +354,94 @@ pub enum ChunkSaveError {
     },
     /// I/O Error which happened while were writing chunk data to region file.
     WriteError { io_error: io::Error },
+    /// Provider is read only, for example provider of zip archive.
+    UnsupportedOperation {
+        /// Name of provider method.
+        operation: &'static str,
+    },
+    /// Saving chunk would exceed storage quota of provider.
+    QuotaExceeded { quota_exceeded: QuotaExceeded },
+    /// Error of writing chunk to region file with path of file and absolute chunk
+    /// coordinates, added by providers.
+    InRegionFile {
+        region_path: PathBuf,
+        chunk_x: i32,
+        chunk_z: i32,
+        error: Box<ChunkSaveError>,
+    },
+}
+
+impl ChunkSaveError {
+    /// Returns error without region file context.
+    pub fn without_context(&self) -> &ChunkSaveError {
+        match self {
+            ChunkSaveError::InRegionFile { error, .. } => error.without_context(),
+            chunk_save_error => chunk_save_error,
+        }
+    }
+
+    /// Adds region file path and absolute chunk coordinates to error of writing chunk.
+    ///
+    /// Unsupported operation and exceeded quota don't depend on region file and are
+    /// returned as is.
+    pub(crate) fn with_context(self, region_path: &Path, chunk_x: i32, chunk_z: i32) -> Self {
+        match self {
+            ChunkSaveError::UnsupportedOperation { .. }
+            | ChunkSaveError::QuotaExceeded { .. }
+            | ChunkSaveError::InRegionFile { .. } => self,
+            chunk_save_error => ChunkSaveError::InRegionFile {
+                region_path: region_path.to_path_buf(),
+                chunk_x,
+                chunk_z,
+                error: Box::new(chunk_save_error),
+            },
+        }
+    }
+}
+
+impl fmt::Display for ChunkSaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChunkSaveError::LengthExceedsMaximum { length } => write!(
+                f,
+                "chunk length {} exceeds maximum {}",
+                length, CHUNK_MAXIMUM_BYTES_LENGTH
+            ),
+            ChunkSaveError::WriteError { io_error } => {
+                write!(f, "failed to write chunk: {}", io_error)
+            }
+            ChunkSaveError::UnsupportedOperation { operation } => {
+                write!(f, "{} is not supported by read only provider", operation)
+            }
+            ChunkSaveError::QuotaExceeded { quota_exceeded } => {
+                write!(f, "quota exceeded: {}", quota_exceeded)
+            }
+            ChunkSaveError::InRegionFile {
+                region_path,
+                chunk_x,
+                chunk_z,
+                error,
+            } => write!(
+                f,
+                "chunk {} {} of {}: {}",
+                chunk_x,
+                chunk_z,
+                region_path.display(),
+                error
+            ),
+        }
+    }
+}
+
+impl Error for ChunkSaveError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ChunkSaveError::WriteError { io_error } => Some(io_error),
+            ChunkSaveError::QuotaExceeded { quota_exceeded } => Some(quota_exceeded),
+            ChunkSaveError::InRegionFile { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
 }
 
 impl From<io::Error> for ChunkSaveError {
@@ -133,16 +450,185 @@ impl From<io::Error> for ChunkSaveError {
     }
 }
 
+impl From<QuotaExceeded> for ChunkSaveError {
+    fn from(quota_exceeded: QuotaExceeded) -> Self {
+        ChunkSaveError::QuotaExceeded { quota_exceeded }
+    }
+}
+
+/// I/O error of file or folder with its path.
+///
+/// Provider methods which return [`io::Error`] put it inside of error of the same kind,
+/// path is found by downcasting [`io::Error::get_ref`].
+#[derive(Debug)]
+pub struct PathIoError {
+    pub path: PathBuf,
+    pub io_error: io::Error,
+}
+
+impl PathIoError {
+    /// Wraps I/O error into error of the same kind with path.
+    pub(crate) fn wrap(path: &Path, io_error: io::Error) -> io::Error {
+        let kind = io_error.kind();
+        let path_io_error = PathIoError {
+            path: path.to_path_buf(),
+            io_error,
+        };
+
+        io::Error::new(kind, path_io_error)
+    }
+}
+
+impl fmt::Display for PathIoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.io_error)
+    }
+}
+
+impl Error for PathIoError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.io_error)
+    }
+}
+
 pub struct AnvilChunkProvider<'a> {
     /// Folder where region files located.
     folder_path: &'a Path,
+    /// Headers of region files which were read, reused until file changes.
+    header_cache: Mutex<HeaderCache>,
+    parse_mode: ParseMode,
+    /// Irregularities tolerated in lenient mode which weren't taken yet.
+    parse_warnings: Mutex<Vec<ParseWarning>>,
+    /// Whether position stored in chunk is compared with position of its header slot.
+    check_positions: bool,
+    parse_limits: ParseLimits,
+    /// When parse limits were set, timeout is counted from it.
+    parse_limits_set: Instant,
+    /// Amount of chunks which were loaded against chunks limit.
+    loaded_chunk_count: AtomicUsize,
+    /// Activity counters which forward events to metrics set by user.
+    metrics: Arc<ProviderCounters>,
+    /// Dictionary which saved chunks are compressed with instead of zlib.
+    #[cfg(feature = "zstd")]
+    zstd_dictionary: Option<Arc<zstd_dictionary::ZstdDictionary>>,
 }
 
 impl<'a> AnvilChunkProvider<'a> {
     pub fn new(folder: &'a str) -> Self {
         let folder_path = Path::new(folder);
 
-        AnvilChunkProvider { folder_path }
+        AnvilChunkProvider::from_path(folder_path)
+    }
+
+    pub(crate) fn from_path(folder_path: &'a Path) -> Self {
+        AnvilChunkProvider {
+            folder_path,
+            header_cache: Mutex::new(HeaderCache::new()),
+            parse_mode: ParseMode::default(),
+            parse_warnings: Mutex::new(Vec::new()),
+            check_positions: false,
+            parse_limits: ParseLimits::default(),
+            parse_limits_set: Instant::now(),
+            loaded_chunk_count: AtomicUsize::new(0),
+            metrics: Arc::new(ProviderCounters::new(Arc::new(NoopMetrics))),
+            #[cfg(feature = "zstd")]
+            zstd_dictionary: None,
+        }
+    }
+
+    /// Creates provider for region files from untrusted source, like files uploaded to
+    /// web service, in strict parse mode with [`ParseLimits::untrusted`].
+    pub fn from_untrusted(folder: &'a str) -> Self {
+        AnvilChunkProvider::new(folder)
+            .parse_mode(ParseMode::Strict)
+            .parse_limits(ParseLimits::untrusted())
+    }
+
+    /// Sets how irregular chunk data is treated, lenient by default.
+    pub fn parse_mode(mut self, parse_mode: ParseMode) -> Self {
+        self.parse_mode = parse_mode;
+        self
+    }
+
+    /// Sets whether `xPos` and `zPos` of chunk are checked against position of its slot
+    /// in region header, disabled by default.
+    ///
+    /// Loaded chunk with other position is malformed according to parse mode. Saved
+    /// chunk with other position is moved to position it's saved at, see
+    /// [`relocate_chunk`]. Chunks which don't store position aren't checked.
+    ///
+    /// [`relocate_chunk`]: relocate::relocate_chunk
+    pub fn check_positions(mut self, check_positions: bool) -> Self {
+        self.check_positions = check_positions;
+        self
+    }
+
+    /// Sets resource limits of loads, see [`limits`]. Timeout and chunks limit are
+    /// counted from this call.
+    pub fn parse_limits(mut self, parse_limits: ParseLimits) -> Self {
+        self.parse_limits = parse_limits;
+        self.parse_limits_set = Instant::now();
+        self.loaded_chunk_count = AtomicUsize::new(0);
+        self
+    }
+
+    /// Sets metrics which loads, saves and header cache report into, see [`metrics`].
+    pub fn metrics(mut self, metrics: Arc<dyn Metrics>) -> Self {
+        self.metrics = Arc::new(ProviderCounters::new(metrics));
+        self
+    }
+
+    /// Sets zstd dictionary which saved chunks are compressed with and registers it for
+    /// reads, see [`zstd_dictionary`].
+    #[cfg(feature = "zstd")]
+    pub fn zstd_dictionary(mut self, dictionary: Arc<zstd_dictionary::ZstdDictionary>) -> Self {
+        zstd_dictionary::register(dictionary.clone());
+        self.zstd_dictionary = Some(dictionary);
+        self
+    }
+
+    /// Sets counters shared with other providers, like providers created by
+    /// [`ConcurrentAnvilChunkProvider`] for every operation.
+    ///
+    /// [`ConcurrentAnvilChunkProvider`]: concurrent::ConcurrentAnvilChunkProvider
+    pub(crate) fn counters(mut self, counters: Arc<ProviderCounters>) -> Self {
+        self.metrics = counters;
+        self
+    }
+
+    /// Returns counts of loads, saves, errors, header cache and bytes read and written
+    /// since provider was created or its metrics were set.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use anvil_region::AnvilChunkProvider;
+    ///
+    /// let chunk_provider = AnvilChunkProvider::new("test/region");
+    /// chunk_provider.load_chunk(4, 2).unwrap();
+    ///
+    /// let provider_activity = chunk_provider.stats();
+    /// assert_eq!(provider_activity.chunks_loaded, 1);
+    /// assert_eq!(provider_activity.cached_headers, 1);
+    /// ```
+    pub fn stats(&self) -> ProviderActivity {
+        let cached_headers = self
+            .header_cache
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .len();
+
+        self.metrics.activity(cached_headers)
+    }
+
+    /// Returns irregularities tolerated by loads in lenient mode since the last call.
+    pub fn take_parse_warnings(&self) -> Vec<ParseWarning> {
+        mem::take(
+            &mut *self
+                .parse_warnings
+                .lock()
+                .unwrap_or_else(|error| error.into_inner()),
+        )
     }
 
     /// Load chunks from the specified coordinates.
@@ -161,23 +647,231 @@ impl<'a> AnvilChunkProvider<'a> {
     /// assert_eq!(level_compound_tag.get_i32("zPos").unwrap(), 2);
     /// ```
     pub fn load_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<CompoundTag, ChunkLoadError> {
+        self.read_chunk(
+            chunk_x,
+            chunk_z,
+            |chunk_buffers, compression_scheme, parse_issues| {
+                let chunk_compound_tag = chunk_buffers.decode_compressed_checked(
+                    compression_scheme,
+                    &self.parse_limits,
+                    parse_issues,
+                )?;
+
+                if self.check_positions {
+                    let stored_position = relocate::chunk_position(&chunk_compound_tag);
+                    check_position(chunk_x, chunk_z, stored_position, parse_issues);
+                }
+
+                Ok(chunk_compound_tag)
+            },
+        )
+        .inspect_err(|chunk_load_error| self.metrics.load_failed(chunk_load_error))
+    }
+
+    /// Loads chunk from the specified coordinates into tags borrowing decompressed data.
+    ///
+    /// Chunk is only available inside of operation, which result is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use anvil_region::AnvilChunkProvider;
+    ///
+    /// let chunk_provider = AnvilChunkProvider::new("test/region");
+    ///
+    /// let status = chunk_provider
+    ///     .load_chunk_borrowed(4, 2, |chunk_compound| {
+    ///         let level_compound = chunk_compound.get_compound("Level").unwrap();
+    ///
+    ///         level_compound.get_str("Status").map(String::from)
+    ///     })
+    ///     .unwrap();
+    ///
+    /// assert!(status.is_some());
+    /// ```
+    pub fn load_chunk_borrowed<T>(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        operation: impl FnOnce(&BorrowedCompound<'_>) -> T,
+    ) -> Result<T, ChunkLoadError> {
+        self.read_chunk(
+            chunk_x,
+            chunk_z,
+            |chunk_buffers, compression_scheme, parse_issues| {
+                let data = chunk_buffers.decompress_compressed(
+                    compression_scheme,
+                    &self.parse_limits,
+                    parse_issues,
+                )?;
+                let (chunk_compound, trailing_length) = BorrowedCompound::parse_prefix(data)?;
+
+                if trailing_length > 0 {
+                    parse_issues.push(ParseIssue::TrailingTagData {
+                        length: trailing_length,
+                    });
+                }
+
+                if self.check_positions {
+                    let stored_position = borrowed_chunk_position(&chunk_compound);
+                    check_position(chunk_x, chunk_z, stored_position, parse_issues);
+                }
+
+                Ok(operation(&chunk_compound))
+            },
+        )
+        .inspect_err(|chunk_load_error| self.metrics.load_failed(chunk_load_error))
+    }
+
+    /// Reads compressed chunk data into buffers of thread using cached region header
+    /// and decodes it with operation, which adds irregularities it finds to issues.
+    fn read_chunk<T>(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        operation: impl FnOnce(&mut ChunkBuffers, u8, &mut Vec<ParseIssue>) -> Result<T, ChunkLoadError>,
+    ) -> Result<T, ChunkLoadError> {
+        enter_span!("read_chunk", chunk_x, chunk_z);
+
+        let region_name = format!("r.{}.{}.mca", chunk_x >> 5, chunk_z >> 5);
+        let region_path = self.folder_path.join(region_name);
+
+        self.read_region_chunk(&region_path, chunk_x, chunk_z, operation)
+            .map_err(|chunk_load_error| {
+                chunk_load_error.with_context(&region_path, chunk_x, chunk_z)
+            })
+    }
+
+    /// Reads and decodes chunk like [`read_chunk`] from region file at path.
+    ///
+    /// [`read_chunk`]: AnvilChunkProvider::read_chunk
+    fn read_region_chunk<T>(
+        &self,
+        region_path: &Path,
+        chunk_x: i32,
+        chunk_z: i32,
+        operation: impl FnOnce(&mut ChunkBuffers, u8, &mut Vec<ParseIssue>) -> Result<T, ChunkLoadError>,
+    ) -> Result<T, ChunkLoadError> {
         let region_x = chunk_x >> 5;
         let region_z = chunk_z >> 5;
 
         let region_chunk_x = (chunk_x & 31) as u8;
         let region_chunk_z = (chunk_z & 31) as u8;
 
-        let region_name = format!("r.{}.{}.mca", region_x, region_z);
-        let region_path = self.folder_path.join(region_name);
+        let region_headers = match self.region_headers(region_path)? {
+            Some(region_headers) => region_headers,
+            None => return Err(ChunkLoadError::RegionNotFound { region_x, region_z }),
+        };
 
-        if !region_path.exists() {
-            return Err(ChunkLoadError::RegionNotFound { region_x, region_z });
+        let metadata = region_headers.metadata(region_chunk_x, region_chunk_z);
+
+        if metadata.is_empty() {
+            return Err(ChunkLoadError::ChunkNotFound { chunk_x, chunk_z });
         }
 
-        // TODO: Cache region files.
-        let mut region = AnvilRegion::new(region_path)?;
+        self.count_loaded_chunk()?;
+
+        let mut file = File::open(region_path)?;
+        let mut parse_issues = Vec::new();
+
+        let output = with_chunk_buffers(|chunk_buffers| {
+            let compression_scheme = read_chunk_data_checked(
+                &mut file,
+                metadata,
+                &mut chunk_buffers.compressed,
+                self.parse_mode,
+                &mut parse_issues,
+            )?;
+
+            trace_event!(length = chunk_buffers.compressed.len(), "chunk read");
+
+            // Length and compression type precede compressed data.
+            self.metrics
+                .bytes_read(chunk_buffers.compressed.len() as u64 + 5);
+
+            let decode_started = Instant::now();
+            let output = operation(chunk_buffers, compression_scheme, &mut parse_issues);
+            self.metrics.decompression_time(decode_started.elapsed());
+
+            output
+        })?;
+
+        if parse_issues.is_empty() {
+            self.metrics.chunk_loaded();
+            return Ok(output);
+        }
+
+        match self.parse_mode {
+            ParseMode::Strict => Err(ChunkLoadError::MalformedChunk {
+                parse_issue: parse_issues.remove(0),
+            }),
+            ParseMode::Lenient => {
+                let parse_warnings = parse_issues.into_iter().map(|parse_issue| ParseWarning {
+                    chunk_x,
+                    chunk_z,
+                    parse_issue,
+                });
+
+                self.parse_warnings
+                    .lock()
+                    .unwrap_or_else(|error| error.into_inner())
+                    .extend(parse_warnings);
+
+                self.metrics.chunk_loaded();
+                Ok(output)
+            }
+        }
+    }
+
+    /// Counts chunk load against parse limits, fails if it exceeds them.
+    fn count_loaded_chunk(&self) -> Result<(), LimitExceeded> {
+        if let Some(timeout) = self.parse_limits.timeout {
+            if self.parse_limits_set.elapsed() > timeout {
+                return Err(LimitExceeded::Timeout { timeout });
+            }
+        }
+
+        if let Some(max_chunks) = self.parse_limits.max_chunks {
+            if self.loaded_chunk_count.fetch_add(1, Ordering::Relaxed) >= max_chunks {
+                return Err(LimitExceeded::Chunks { max_chunks });
+            }
+        }
 
-        region.read_chunk(region_chunk_x, region_chunk_z)
+        Ok(())
+    }
+
+    /// Returns cached header of region file, none if file doesn't exist.
+    fn region_headers(&self, region_path: &Path) -> Result<Option<Arc<RegionHeaders>>, io::Error> {
+        self.header_cache
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .region_headers(region_path, &*self.metrics)
+    }
+
+    /// Drops cached header of region file after it was written.
+    fn invalidate_region_headers(&self, region_path: &Path) {
+        self.header_cache
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .invalidate(region_path);
+    }
+
+    /// Drops cached header of region file, so the next operation reads it again.
+    ///
+    /// Cached header is checked against modification time and length of file, which
+    /// may stay the same when other process rewrites region file quickly.
+    pub fn invalidate(&self, region_x: i32, region_z: i32) {
+        let region_name = format!("r.{}.{}.mca", region_x, region_z);
+
+        self.invalidate_region_headers(&self.folder_path.join(region_name));
+    }
+
+    /// Drops cached headers of all region files.
+    pub fn invalidate_all(&self) {
+        self.header_cache
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .clear();
     }
 
     /// Saves chunk data to the specified coordinates.
@@ -205,12 +899,46 @@ impl<'a> AnvilChunkProvider<'a> {
         &self,
         chunk_x: i32,
         chunk_z: i32,
-        chunk_compound_tag: CompoundTag,
+        mut chunk_compound_tag: CompoundTag,
     ) -> Result<(), ChunkSaveError> {
-        if !self.folder_path.exists() {
-            fs::create_dir(self.folder_path)?;
+        if self.check_positions {
+            if let Some((stored_chunk_x, stored_chunk_z)) =
+                relocate::chunk_position(&chunk_compound_tag)
+            {
+                relocate::offset_chunk(
+                    &mut chunk_compound_tag,
+                    chunk_x - stored_chunk_x,
+                    chunk_z - stored_chunk_z,
+                );
+            }
         }
 
+        let chunk_buffer = self.encode_chunk(&chunk_compound_tag)?;
+
+        self.save_chunk_buffer(chunk_x, chunk_z, &chunk_buffer)
+    }
+
+    /// Compresses chunk with zstd dictionary if provider has one or with zlib.
+    fn encode_chunk(&self, chunk_compound_tag: &CompoundTag) -> Result<Vec<u8>, io::Error> {
+        #[cfg(feature = "zstd")]
+        if let Some(dictionary) = &self.zstd_dictionary {
+            return zstd_dictionary::encode_chunk(dictionary, chunk_compound_tag);
+        }
+
+        encode_chunk(chunk_compound_tag)
+    }
+
+    /// Saves chunk data already compressed into buffer which starts with compression type.
+    pub(crate) fn save_chunk_buffer(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        chunk_buffer: &[u8],
+    ) -> Result<(), ChunkSaveError> {
+        enter_span!("write_chunk", chunk_x, chunk_z, length = chunk_buffer.len());
+
+        self.create_folder()?;
+
         let region_x = chunk_x >> 5;
         let region_z = chunk_z >> 5;
 
@@ -221,31 +949,589 @@ impl<'a> AnvilChunkProvider<'a> {
         let region_path = self.folder_path.join(region_name);
 
         // TODO: Cache region files.
-        let mut region = AnvilRegion::new(region_path)?;
+        let result = break_hard_link(&region_path)
+            .and_then(|_| AnvilRegion::new(&region_path))
+            .map_err(ChunkSaveError::from)
+            .and_then(|mut region| {
+                region.write_locked(|region| {
+                    region.write_chunk_buffer(region_chunk_x, region_chunk_z, chunk_buffer)
+                })
+            })
+            .map_err(|chunk_save_error| {
+                chunk_save_error.with_context(&region_path, chunk_x, chunk_z)
+            });
 
-        region.write_chunk(region_chunk_x, region_chunk_z, chunk_compound_tag)
+        self.invalidate_region_headers(&region_path);
+
+        if result.is_ok() {
+            trace_event!("chunk written");
+
+            // Length precedes compression type and compressed data.
+            self.metrics.bytes_written(chunk_buffer.len() as u64 + 4);
+            self.metrics.chunk_saved();
+        } else {
+            self.metrics.save_failed();
+        }
+
+        result
     }
+
+    /// Deletes chunk at the specified coordinates.
+    ///
+    /// Sectors used by chunk become free for other chunks, region file is not shrunk.
+    /// Returns false if chunk is not present.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use anvil_region::AnvilChunkProvider;
+    ///
+    /// let chunk_provider = AnvilChunkProvider::new("test/region");
+    ///
+    /// assert!(!chunk_provider.delete_chunk(-1, -1).unwrap());
+    /// ```
+    pub fn delete_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<bool, ChunkSaveError> {
+        enter_span!("delete_chunk", chunk_x, chunk_z);
+
+        let region_x = chunk_x >> 5;
+        let region_z = chunk_z >> 5;
+
+        let region_chunk_x = (chunk_x & 31) as u8;
+        let region_chunk_z = (chunk_z & 31) as u8;
+
+        let region_name = format!("r.{}.{}.mca", region_x, region_z);
+        let region_path = self.folder_path.join(region_name);
+
+        if !region_path.exists() {
+            return Ok(false);
+        }
+
+        let result = break_hard_link(&region_path)
+            .and_then(|_| AnvilRegion::new(&region_path))
+            .and_then(|mut region| {
+                region.write_locked(|region| region.delete_chunk(region_chunk_x, region_chunk_z))
+            });
+
+        self.invalidate_region_headers(&region_path);
+
+        result.map_err(|io_error| {
+            ChunkSaveError::from(io_error).with_context(&region_path, chunk_x, chunk_z)
+        })
+    }
+
+    /// Creates region file at the specified region coordinates with space for chunks of
+    /// expected total length in bytes, for example before world generation fills it.
+    ///
+    /// Space after header is written with zeros, so file system allocates it at once
+    /// instead of growing file chunk by chunk, and saved chunks take these free sectors
+    /// first. Region file which exists is extended if it's shorter. Returns false if
+    /// region file was already long enough.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use anvil_region::AnvilChunkProvider;
+    /// use tempfile::TempDir;
+    ///
+    /// let region_dir = TempDir::new().unwrap();
+    /// let chunk_provider = AnvilChunkProvider::new(region_dir.path().to_str().unwrap());
+    ///
+    /// assert!(chunk_provider.preallocate_region(0, 0, 4 * 1024 * 1024).unwrap());
+    /// assert!(chunk_provider.chunk_positions().unwrap().is_empty());
+    /// ```
+    pub fn preallocate_region(
+        &self,
+        region_x: i32,
+        region_z: i32,
+        length: u64,
+    ) -> Result<bool, io::Error> {
+        self.create_folder()?;
+
+        let region_name = format!("r.{}.{}.mca", region_x, region_z);
+        let region_path = self.folder_path.join(region_name);
+
+        let result = break_hard_link(&region_path)
+            .and_then(|_| AnvilRegion::new(&region_path))
+            .and_then(|mut region| region.write_locked(|region| region.preallocate(length)));
+
+        self.invalidate_region_headers(&region_path);
+
+        result.map_err(|io_error| PathIoError::wrap(&region_path, io_error))
+    }
+
+    /// Creates region folder if it doesn't exist.
+    fn create_folder(&self) -> Result<(), io::Error> {
+        if !self.folder_path.exists() {
+            fs::create_dir_all(self.folder_path)
+                .map_err(|io_error| PathIoError::wrap(self.folder_path, io_error))?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns time in seconds since Unix epoch when chunk was last saved.
+    ///
+    /// Returns none if chunk is not present.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use anvil_region::AnvilChunkProvider;
+    ///
+    /// let chunk_provider = AnvilChunkProvider::new("test/region");
+    ///
+    /// assert!(chunk_provider.chunk_last_modified(4, 2).unwrap().is_some());
+    /// ```
+    pub fn chunk_last_modified(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> Result<Option<u32>, io::Error> {
+        let metadata = self.chunk_metadata(chunk_x, chunk_z)?;
+
+        Ok(metadata.map(|metadata| metadata.last_modified_timestamp))
+    }
+
+    /// Returns header metadata of chunk, none if chunk is not present.
+    pub(crate) fn chunk_metadata(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> Result<Option<AnvilChunkMetadata>, io::Error> {
+        let region_x = chunk_x >> 5;
+        let region_z = chunk_z >> 5;
+
+        let region_chunk_x = (chunk_x & 31) as u8;
+        let region_chunk_z = (chunk_z & 31) as u8;
+
+        let region_name = format!("r.{}.{}.mca", region_x, region_z);
+        let region_path = self.folder_path.join(region_name);
+
+        let region_headers = match self.region_headers(&region_path)? {
+            Some(region_headers) => region_headers,
+            None => return Ok(None),
+        };
+
+        let metadata = region_headers.metadata(region_chunk_x, region_chunk_z);
+
+        if metadata.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(metadata))
+    }
+
+    /// Returns coordinates of all chunks stored in the folder region files.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use anvil_region::AnvilChunkProvider;
+    ///
+    /// let chunk_provider = AnvilChunkProvider::new("test/region");
+    /// let chunk_positions = chunk_provider.chunk_positions().unwrap();
+    ///
+    /// assert!(chunk_positions.contains(&(4, 2)));
+    /// ```
+    pub fn chunk_positions(&self) -> Result<Vec<(i32, i32)>, io::Error> {
+        let mut chunk_positions = Vec::new();
+
+        if !self.folder_path.exists() {
+            return Ok(chunk_positions);
+        }
+
+        let folder_io_error = |io_error| PathIoError::wrap(self.folder_path, io_error);
+
+        for entry in fs::read_dir(self.folder_path).map_err(folder_io_error)? {
+            let path = entry.map_err(folder_io_error)?.path();
+
+            let (region_x, region_z) = match path
+                .file_name()
+                .and_then(|file_name| file_name.to_str())
+                .and_then(region_position)
+            {
+                Some(region_position) => region_position,
+                None => continue,
+            };
+
+            let region_headers = self
+                .region_headers(&path)
+                .map_err(|io_error| PathIoError::wrap(&path, io_error))?;
+
+            let region_headers = match region_headers {
+                Some(region_headers) => region_headers,
+                None => continue,
+            };
+
+            for (region_chunk_x, region_chunk_z) in region_headers.chunk_positions() {
+                let chunk_x = (region_x << 5) + region_chunk_x as i32;
+                let chunk_z = (region_z << 5) + region_chunk_z as i32;
+
+                chunk_positions.push((chunk_x, chunk_z));
+            }
+        }
+
+        chunk_positions.sort_unstable();
+
+        Ok(chunk_positions)
+    }
+
+    /// Returns region files of folder in ascending order of position.
+    pub(crate) fn region_files(&self) -> Result<Vec<RegionFile>, io::Error> {
+        let mut region_files = Vec::new();
+
+        if !self.folder_path.exists() {
+            return Ok(region_files);
+        }
+
+        let folder_io_error = |io_error| PathIoError::wrap(self.folder_path, io_error);
+
+        for entry in fs::read_dir(self.folder_path).map_err(folder_io_error)? {
+            let path = entry.map_err(folder_io_error)?.path();
+
+            let region_position = path
+                .file_name()
+                .and_then(|file_name| file_name.to_str())
+                .and_then(region_position);
+
+            if let Some(region_position) = region_position {
+                region_files.push(RegionFile {
+                    path,
+                    region_position,
+                });
+            }
+        }
+
+        region_files.sort_unstable_by_key(|region_file| region_file.region_position);
+
+        Ok(region_files)
+    }
+
+    /// Deletes region files which don't contain any chunk.
+    ///
+    /// Returns amount of deleted region files.
+    pub fn delete_empty_regions(&self) -> Result<usize, io::Error> {
+        if !self.folder_path.exists() {
+            return Ok(0);
+        }
+
+        let mut deleted_regions = 0;
+
+        for entry in fs::read_dir(self.folder_path)? {
+            let path = entry?.path();
+
+            let is_region = path
+                .file_name()
+                .and_then(|file_name| file_name.to_str())
+                .and_then(region_position)
+                .is_some();
+
+            if !is_region {
+                continue;
+            }
+
+            let is_empty = match self.region_headers(&path)? {
+                Some(region_headers) => region_headers.chunk_positions().is_empty(),
+                None => continue,
+            };
+
+            if is_empty {
+                fs::remove_file(&path)?;
+                self.invalidate_region_headers(&path);
+                deleted_regions += 1;
+            }
+        }
+
+        Ok(deleted_regions)
+    }
+
+    /// Returns sorted positions of chunks which generation reached specified status.
+    ///
+    /// Chunks without known status are skipped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use anvil_region::chunk::ChunkStatus;
+    /// use anvil_region::AnvilChunkProvider;
+    ///
+    /// let chunk_provider = AnvilChunkProvider::new("test/region");
+    /// let chunk_positions = chunk_provider
+    ///     .chunk_positions_with_status(ChunkStatus::Full)
+    ///     .unwrap();
+    ///
+    /// assert!(chunk_positions.contains(&(4, 2)));
+    /// ```
+    pub fn chunk_positions_with_status(
+        &self,
+        status: ChunkStatus,
+    ) -> Result<Vec<(i32, i32)>, ChunkLoadError> {
+        let mut chunk_positions = Vec::new();
+
+        for (chunk_x, chunk_z) in self.chunk_positions()? {
+            let chunk = Chunk::new(self.load_chunk(chunk_x, chunk_z)?);
+
+            if chunk.has_status(status) {
+                chunk_positions.push((chunk_x, chunk_z));
+            }
+        }
+
+        Ok(chunk_positions)
+    }
+}
+
+/// Reads compressed data of chunk described by metadata into buffer replacing its contents.
+///
+/// Returns compression scheme.
+fn read_chunk_data_at(
+    reader: &mut (impl Read + Seek),
+    metadata: AnvilChunkMetadata,
+    compressed_buffer: &mut Vec<u8>,
+) -> Result<u8, ChunkLoadError> {
+    read_chunk_data_checked(
+        reader,
+        metadata,
+        compressed_buffer,
+        ParseMode::Strict,
+        &mut Vec::new(),
+    )
+}
+
+/// Adds issue if position stored in chunk differs from position of its slot.
+fn check_position(
+    chunk_x: i32,
+    chunk_z: i32,
+    stored_position: Option<(i32, i32)>,
+    parse_issues: &mut Vec<ParseIssue>,
+) {
+    if let Some((stored_chunk_x, stored_chunk_z)) = stored_position {
+        if (stored_chunk_x, stored_chunk_z) != (chunk_x, chunk_z) {
+            parse_issues.push(ParseIssue::PositionMismatch {
+                stored_chunk_x,
+                stored_chunk_z,
+            });
+        }
+    }
+}
+
+/// Returns position stored in borrowed terrain or entity chunk like
+/// [`relocate::chunk_position`].
+fn borrowed_chunk_position(chunk_compound: &BorrowedCompound<'_>) -> Option<(i32, i32)> {
+    if let Some(mut position) = chunk_compound.get_int_array("Position") {
+        if let (2, Some(chunk_x), Some(chunk_z)) =
+            (position.len(), position.next(), position.next())
+        {
+            return Some((chunk_x, chunk_z));
+        }
+    }
+
+    let data = chunk_compound
+        .get_compound("Level")
+        .unwrap_or(chunk_compound);
+
+    Some((data.get_i32("xPos")?, data.get_i32("zPos")?))
+}
+
+/// Reads compression scheme and compressed chunk data like [`read_chunk_data_at`]
+/// adding irregularities to issues.
+///
+/// In lenient mode declared length which exceeds sectors or file is cut instead of
+/// failing.
+fn read_chunk_data_checked(
+    reader: &mut (impl Read + Seek),
+    metadata: AnvilChunkMetadata,
+    compressed_buffer: &mut Vec<u8>,
+    parse_mode: ParseMode,
+    parse_issues: &mut Vec<ParseIssue>,
+) -> Result<u8, ChunkLoadError> {
+    let seek_offset = metadata.sector_index as u64 * REGION_SECTOR_BYTES_LENGTH as u64;
+    let maximum_length = (metadata.sectors as u32 * REGION_SECTOR_BYTES_LENGTH as u32)
+        .min(CHUNK_MAXIMUM_BYTES_LENGTH);
+
+    if metadata.sector_index < 2 {
+        parse_issues.push(ParseIssue::SectorsOverlapHeader {
+            sector_index: metadata.sector_index,
+        });
+    }
+
+    reader.seek(SeekFrom::Start(seek_offset))?;
+    let mut length = reader.read_u32::<BigEndian>()?;
+
+    if length > maximum_length {
+        if parse_mode == ParseMode::Strict {
+            return Err(ChunkLoadError::LengthExceedsMaximum {
+                length,
+                maximum_length,
+            });
+        }
+
+        parse_issues.push(ParseIssue::LengthExceedsSectors {
+            length,
+            sectors_length: maximum_length,
+        });
+
+        // Length itself takes 4 bytes of sectors.
+        length = maximum_length - 4;
+    }
+
+    let compression_scheme = reader.read_u8()?;
+    let compressed_length = length.saturating_sub(1);
+
+    compressed_buffer.clear();
+    reader
+        .take(compressed_length as u64)
+        .read_to_end(compressed_buffer)?;
+
+    if compressed_buffer.len() < compressed_length as usize {
+        if parse_mode == ParseMode::Strict {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+
+        parse_issues.push(ParseIssue::TruncatedData {
+            length,
+            available_length: compressed_buffer.len() as u32 + 1,
+        });
+    }
+
+    Ok(compression_scheme)
+}
+
+/// Region file found in folder.
+pub(crate) struct RegionFile {
+    pub(crate) path: PathBuf,
+    pub(crate) region_position: (i32, i32),
+}
+
+/// Returns key which orders chunks by region, then along Z-order curve inside of region.
+///
+/// Neighbouring chunks get close keys, so chunks written in this order are placed next
+/// to each other in region file, which compresses better when world is archived.
+pub(crate) fn z_order_key(chunk_x: i32, chunk_z: i32) -> (i32, i32, u16) {
+    let mut index = 0;
+
+    for bit in 0..5 {
+        index |= ((chunk_x >> bit) & 1) << (2 * bit);
+        index |= ((chunk_z >> bit) & 1) << (2 * bit + 1);
+    }
+
+    (chunk_x >> 5, chunk_z >> 5, index as u16)
+}
+
+/// Takes exclusive advisory lock of file, waiting until other holders release it.
+///
+/// Returns false if file system doesn't support locks, file isn't locked then.
+pub(crate) fn lock_exclusive(file: &File) -> Result<bool, io::Error> {
+    match file.lock() {
+        Ok(()) => Ok(true),
+        Err(io_error) if io_error.kind() == io::ErrorKind::Unsupported => Ok(false),
+        Err(io_error) => Err(io_error),
+    }
+}
+
+/// Opens existing region file holding exclusive advisory lock of it.
+///
+/// Other process can rename new file over region file while waiting for lock, lock of
+/// replaced file protects nothing then, so file at path is opened and locked again.
+pub(crate) fn open_locked(path: &Path) -> Result<File, io::Error> {
+    loop {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+
+        if !lock_exclusive(&file)? || is_same_file(&file, path)? {
+            return Ok(file);
+        }
+    }
+}
+
+/// Returns true if path still refers to opened file.
+fn is_same_file(file: &File, path: &Path) -> Result<bool, io::Error> {
+    match fs::metadata(path) {
+        Ok(metadata) => Ok(is_same_metadata(&file.metadata()?, &metadata)),
+        Err(io_error) if io_error.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(io_error) => Err(io_error),
+    }
+}
+
+/// Returns true if metadata were read from the same file.
+#[cfg(unix)]
+pub(crate) fn is_same_metadata(metadata: &fs::Metadata, other_metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    metadata.dev() == other_metadata.dev() && metadata.ino() == other_metadata.ino()
+}
+
+/// Identity of files isn't available on other platforms, file is assumed to be the same.
+#[cfg(not(unix))]
+pub(crate) fn is_same_metadata(_metadata: &fs::Metadata, _other_metadata: &fs::Metadata) -> bool {
+    true
+}
+
+/// Returns current time in seconds since Unix epoch.
+///
+/// Browsers have no system clock on `wasm32-unknown-unknown`, zero is returned there.
+pub(crate) fn current_timestamp() -> u32 {
+    if cfg!(all(target_arch = "wasm32", target_os = "unknown")) {
+        return 0;
+    }
+
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+
+    time.as_secs() as u32
+}
+
+/// Compresses chunk with zlib into buffer which starts with compression type.
+fn encode_chunk(chunk_compound_tag: &CompoundTag) -> Result<Vec<u8>, io::Error> {
+    let mut buffer = Vec::new();
+
+    buffer.write_u8(ZLIB_COMPRESSION_TYPE)?;
+    write_zlib_compound_tag(&mut buffer, chunk_compound_tag)?;
+
+    Ok(buffer)
+}
+
+/// Parses region coordinates from file name in format `r.x.z.mca`.
+fn region_position(file_name: &str) -> Option<(i32, i32)> {
+    let mut parts = file_name.split('.');
+
+    if parts.next() != Some("r") {
+        return None;
+    }
+
+    let region_x = parts.next()?.parse().ok()?;
+    let region_z = parts.next()?.parse().ok()?;
+
+    if parts.next() != Some("mca") || parts.next().is_some() {
+        return None;
+    }
+
+    // Chunk coordinates of region must fit into i32.
+    if !coords::is_region_in_bounds(region_x, region_z) {
+        return None;
+    }
+
+    Some((region_x, region_z))
 }
 
 /// Region represents a 32x32 group of chunks.
 struct AnvilRegion {
+    /// Path from which file was opened.
+    path: PathBuf,
     /// File in which region are stored.
     file: File,
     /// Array of chunks metadata.
     chunks_metadata: [AnvilChunkMetadata; REGION_CHUNKS],
     /// Used sectors for chunks data.
-    used_sectors: BitVec,
+    used_sectors: BitVec<Msb0, u8>,
 }
 
 /// Chunk metadata are stored in header.
 #[derive(Copy, Clone, Default, Debug, Eq, PartialEq)]
-struct AnvilChunkMetadata {
+pub(crate) struct AnvilChunkMetadata {
     /// Sector index from which starts chunk data.
-    sector_index: u32,
+    pub(crate) sector_index: u32,
     /// Amount of sectors used to store chunk.
-    sectors: u8,
+    pub(crate) sectors: u8,
     /// Last time in seconds when chunk was modified.
-    last_modified_timestamp: u32,
+    pub(crate) last_modified_timestamp: u32,
 }
 
 impl AnvilChunkMetadata {
@@ -258,10 +1544,7 @@ impl AnvilChunkMetadata {
     }
 
     fn update_last_modified_timestamp(&mut self) {
-        let system_time = SystemTime::now();
-        let time = system_time.duration_since(UNIX_EPOCH).unwrap();
-
-        self.last_modified_timestamp = time.as_secs() as u32
+        self.last_modified_timestamp = current_timestamp();
     }
 
     fn is_empty(&self) -> bool {
@@ -271,37 +1554,92 @@ impl AnvilChunkMetadata {
 
 impl AnvilRegion {
     fn new<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
-        let mut file = OpenOptions::new()
+        let mut file = Self::open_file(path.as_ref())?;
+
+        trace_event!(path = %path.as_ref().display(), "region opened");
+
+        let chunks_metadata = Self::read_header(&mut file)?;
+        let total_sectors = file.metadata()?.len() as u32 / REGION_SECTOR_BYTES_LENGTH as u32;
+        let free_sectors = Self::used_sectors(total_sectors, &chunks_metadata);
+
+        let region = AnvilRegion {
+            path: path.as_ref().to_path_buf(),
+            file,
+            chunks_metadata,
+            used_sectors: free_sectors,
+        };
+
+        Ok(region)
+    }
+
+    /// Opens region file, creating it and extending it to the length of the header if
+    /// necessary.
+    fn open_file(path: &Path) -> Result<File, io::Error> {
+        let file = OpenOptions::new()
             .write(true)
             .read(true)
             .create(true)
+            .truncate(false)
             .open(path)?;
 
-        // If necessary, extend the file length to the length of the header.
         if REGION_HEADER_BYTES_LENGTH > file.metadata()?.len() {
             file.set_len(REGION_HEADER_BYTES_LENGTH)?;
         }
 
-        let chunks_metadata = Self::read_header(&mut file)?;
-        let total_sectors = file.metadata()?.len() as u32 / REGION_SECTOR_BYTES_LENGTH as u32;
-        let free_sectors = Self::used_sectors(total_sectors, &chunks_metadata);
+        Ok(file)
+    }
 
-        let region = AnvilRegion {
-            file,
-            chunks_metadata,
-            used_sectors: free_sectors,
-        };
+    /// Runs write operation holding exclusive advisory lock of region file, so writes
+    /// of other processes using this crate don't interleave with it.
+    ///
+    /// Header is read again under lock, since other process could change file after
+    /// it was opened. If file was replaced by rename meanwhile, for example by commit of
+    /// transaction or compaction, file at path is opened again, since writes to replaced
+    /// file would be lost. Lock is released when operation returns.
+    fn write_locked<T, E: From<io::Error>>(
+        &mut self,
+        operation: impl FnOnce(&mut Self) -> Result<T, E>,
+    ) -> Result<T, E> {
+        let mut is_locked = lock_exclusive(&self.file)?;
+
+        while is_locked && !is_same_file(&self.file, &self.path)? {
+            // Lock of replaced file is released when it's closed.
+            self.file = Self::open_file(&self.path)?;
+            is_locked = lock_exclusive(&self.file)?;
+        }
 
-        Ok(region)
+        let result = self
+            .reload_header()
+            .map_err(E::from)
+            .and_then(|()| operation(self));
+
+        if is_locked {
+            // Lock is released when file is closed anyway.
+            let _ = self.file.unlock();
+        }
+
+        result
+    }
+
+    fn reload_header(&mut self) -> Result<(), io::Error> {
+        self.file.seek(SeekFrom::Start(0))?;
+        self.chunks_metadata = Self::read_header(&mut self.file)?;
+
+        let total_sectors = self.file.metadata()?.len() as u32 / REGION_SECTOR_BYTES_LENGTH as u32;
+        self.used_sectors = Self::used_sectors(total_sectors, &self.chunks_metadata);
+
+        Ok(())
     }
 
     /// First 8KB of file are header of 1024 offsets and 1024 timestamps.
-    fn read_header(file: &mut File) -> Result<[AnvilChunkMetadata; REGION_CHUNKS], io::Error> {
+    fn read_header(
+        reader: &mut impl Read,
+    ) -> Result<[AnvilChunkMetadata; REGION_CHUNKS], io::Error> {
         let mut chunks_metadata = [Default::default(); REGION_CHUNKS];
         let mut values = [0u32; REGION_CHUNKS_METADATA_LENGTH];
 
-        for index in 0..REGION_CHUNKS_METADATA_LENGTH {
-            values[index] = file.read_u32::<BigEndian>()?;
+        for value in values.iter_mut() {
+            *value = reader.read_u32::<BigEndian>()?;
         }
 
         for index in 0..REGION_CHUNKS {
@@ -315,12 +1653,30 @@ impl AnvilRegion {
             chunks_metadata[index] = metadata;
         }
 
-        return Ok(chunks_metadata);
+        Ok(chunks_metadata)
+    }
+
+    /// Reads only header of region file, chunk data isn't read.
+    ///
+    /// Header shorter than 8KB is padded with zeros, same as file opened for writing.
+    fn open_headers_only(reader: &mut impl Read) -> Result<RegionHeaders, io::Error> {
+        let mut header = Vec::with_capacity(REGION_HEADER_BYTES_LENGTH as usize);
+        reader
+            .take(REGION_HEADER_BYTES_LENGTH)
+            .read_to_end(&mut header)?;
+        header.resize(REGION_HEADER_BYTES_LENGTH as usize, 0);
+
+        let chunks_metadata = Self::read_header(&mut header.as_slice())?;
+
+        Ok(RegionHeaders::new(chunks_metadata))
     }
 
     /// Calculates used sectors.
-    fn used_sectors(total_sectors: u32, chunks_metadata: &[AnvilChunkMetadata]) -> BitVec {
-        let mut used_sectors = bitvec![0; total_sectors as usize];
+    fn used_sectors(
+        total_sectors: u32,
+        chunks_metadata: &[AnvilChunkMetadata],
+    ) -> BitVec<Msb0, u8> {
+        let mut used_sectors = bitvec![Msb0, u8; 0; total_sectors as usize];
 
         used_sectors.set(0, true);
         used_sectors.set(1, true);
@@ -330,8 +1686,9 @@ impl AnvilRegion {
                 continue;
             }
 
-            let start_index = metadata.sector_index as usize;
-            let end_index = start_index + metadata.sectors as usize;
+            // Corrupted header may point past the end of file, reading such chunk fails.
+            let start_index = (metadata.sector_index as usize).min(used_sectors.len());
+            let end_index = (start_index + metadata.sectors as usize).min(used_sectors.len());
 
             for index in start_index..end_index {
                 used_sectors.set(index, true);
@@ -342,37 +1699,46 @@ impl AnvilRegion {
     }
 
     fn read_chunk(&mut self, chunk_x: u8, chunk_z: u8) -> Result<CompoundTag, ChunkLoadError> {
-        let metadata = self.get_metadata(chunk_x, chunk_z);
+        with_chunk_buffers(|chunk_buffers| {
+            let compression_scheme =
+                self.read_chunk_data_into(chunk_x, chunk_z, &mut chunk_buffers.compressed)?;
 
-        if metadata.is_empty() {
-            return Err(ChunkLoadError::ChunkNotFound { chunk_x, chunk_z });
-        }
+            chunk_buffers.decode_compressed(compression_scheme)
+        })
+    }
 
-        let seek_offset = metadata.sector_index as u64 * REGION_SECTOR_BYTES_LENGTH as u64;
-        let maximum_length = (metadata.sectors as u32 * REGION_SECTOR_BYTES_LENGTH as u32)
-            .min(CHUNK_MAXIMUM_BYTES_LENGTH);
+    /// Reads compression scheme and compressed chunk data.
+    fn read_chunk_data(
+        &mut self,
+        chunk_x: u8,
+        chunk_z: u8,
+    ) -> Result<(u8, Vec<u8>), ChunkLoadError> {
+        let mut compressed_buffer = Vec::new();
+        let compression_scheme =
+            self.read_chunk_data_into(chunk_x, chunk_z, &mut compressed_buffer)?;
 
-        self.file.seek(SeekFrom::Start(seek_offset))?;
-        let length = self.file.read_u32::<BigEndian>()?;
+        Ok((compression_scheme, compressed_buffer))
+    }
 
-        if length > maximum_length {
-            return Err(ChunkLoadError::LengthExceedsMaximum {
-                length,
-                maximum_length,
+    /// Reads compressed chunk data into buffer replacing its contents.
+    ///
+    /// Returns compression scheme.
+    fn read_chunk_data_into(
+        &mut self,
+        chunk_x: u8,
+        chunk_z: u8,
+        compressed_buffer: &mut Vec<u8>,
+    ) -> Result<u8, ChunkLoadError> {
+        let metadata = self.get_metadata(chunk_x, chunk_z);
+
+        if metadata.is_empty() {
+            return Err(ChunkLoadError::ChunkNotFound {
+                chunk_x: chunk_x as i32,
+                chunk_z: chunk_z as i32,
             });
         }
 
-        let compression_scheme = self.file.read_u8()?;
-        let mut compressed_buffer = vec![0u8; (length - 1) as usize];
-        self.file.read_exact(&mut compressed_buffer)?;
-
-        let mut cursor = Cursor::new(&compressed_buffer);
-
-        match compression_scheme {
-            GZIP_COMPRESSION_TYPE => Ok(read_gzip_compound_tag(&mut cursor)?),
-            ZLIB_COMPRESSION_TYPE => Ok(read_zlib_compound_tag(&mut cursor)?),
-            _ => Err(ChunkLoadError::UnsupportedCompressionScheme { compression_scheme }),
-        }
+        read_chunk_data_at(&mut self.file, metadata, compressed_buffer)
     }
 
     fn write_chunk(
@@ -381,11 +1747,18 @@ impl AnvilRegion {
         chunk_z: u8,
         chunk_compound_tag: CompoundTag,
     ) -> Result<(), ChunkSaveError> {
-        let mut buffer = Vec::new();
+        let buffer = encode_chunk(&chunk_compound_tag)?;
 
-        buffer.write_u8(ZLIB_COMPRESSION_TYPE)?;
-        write_zlib_compound_tag(&mut buffer, chunk_compound_tag)?;
+        self.write_chunk_buffer(chunk_x, chunk_z, &buffer)
+    }
 
+    /// Writes chunk data compressed into buffer which starts with compression type.
+    fn write_chunk_buffer(
+        &mut self,
+        chunk_x: u8,
+        chunk_z: u8,
+        buffer: &[u8],
+    ) -> Result<(), ChunkSaveError> {
         // 4 bytes for data length.
         let length = (buffer.len() + 4) as u32;
 
@@ -398,7 +1771,7 @@ impl AnvilRegion {
 
         self.file.seek(SeekFrom::Start(seek_offset))?;
         self.file.write_u32::<BigEndian>(buffer.len() as u32)?;
-        self.file.write_all(&buffer)?;
+        self.file.write_all(buffer)?;
 
         // Padding to align sector.
         let padding = REGION_SECTOR_BYTES_LENGTH - length as u16 % REGION_SECTOR_BYTES_LENGTH;
@@ -413,6 +1786,67 @@ impl AnvilRegion {
         Ok(())
     }
 
+    /// Extends file with zeroed free sectors up to length rounded up to sectors.
+    ///
+    /// Returns false if file is already long enough.
+    fn preallocate(&mut self, length: u64) -> Result<bool, io::Error> {
+        let sector_length = REGION_SECTOR_BYTES_LENGTH as u64;
+        let length = length.div_ceil(sector_length) * sector_length;
+        let file_length = self.file.metadata()?.len();
+
+        if length <= file_length {
+            return Ok(false);
+        }
+
+        // Zeros are written instead of setting length, which leaves hole in sparse file.
+        let zeros = [0; 64 * 1024];
+        let mut position = file_length;
+
+        self.file.seek(SeekFrom::Start(position))?;
+
+        while position < length {
+            let write_length = (length - position).min(zeros.len() as u64) as usize;
+            self.file.write_all(&zeros[..write_length])?;
+            position += write_length as u64;
+        }
+
+        let sectors = (length / sector_length - file_length / sector_length) as usize;
+        self.used_sectors
+            .extend(std::iter::repeat_n(false, sectors));
+
+        Ok(true)
+    }
+
+    /// Removes chunk from region releasing its sectors.
+    ///
+    /// Returns false if chunk is not present.
+    fn delete_chunk(&mut self, chunk_x: u8, chunk_z: u8) -> Result<bool, io::Error> {
+        let metadata = self.get_metadata(chunk_x, chunk_z);
+
+        if metadata.is_empty() {
+            return Ok(false);
+        }
+
+        self.release_sectors(metadata);
+
+        self.update_metadata(chunk_x, chunk_z, AnvilChunkMetadata::default())?;
+
+        Ok(true)
+    }
+
+    /// Returns coordinates of chunks which are present in region.
+    fn chunk_positions(&self) -> Vec<(u8, u8)> {
+        let mut chunk_positions = Vec::new();
+
+        for (index, metadata) in self.chunks_metadata.iter().enumerate() {
+            if !metadata.is_empty() {
+                chunk_positions.push(((index % 32) as u8, (index / 32) as u8));
+            }
+        }
+
+        chunk_positions
+    }
+
     fn metadata_index(chunk_x: u8, chunk_z: u8) -> usize {
         assert!(32 > chunk_x, "Region chunk x coordinate out of bounds");
         assert!(32 > chunk_z, "Region chunk y coordinate out of bounds");
@@ -425,6 +1859,16 @@ impl AnvilRegion {
         self.chunks_metadata[Self::metadata_index(chunk_x, chunk_z)]
     }
 
+    /// Marks sectors of chunk as free, sectors past the end of file are skipped.
+    fn release_sectors(&mut self, metadata: AnvilChunkMetadata) {
+        let start_index = (metadata.sector_index as usize).min(self.used_sectors.len());
+        let end_index = (start_index + metadata.sectors as usize).min(self.used_sectors.len());
+
+        for sector_index in start_index..end_index {
+            self.used_sectors.set(sector_index, false);
+        }
+    }
+
     /// Finds a place where chunk data of a given length can be put.
     ///
     /// If cannot find a place to put chunk data will extend file.
@@ -437,16 +1881,16 @@ impl AnvilRegion {
         let sectors_required = (chunk_length / REGION_SECTOR_BYTES_LENGTH as u32) as u8 + 1;
         let metadata = self.get_metadata(chunk_x, chunk_z);
 
+        let is_inside_file =
+            metadata.sector_index as usize + metadata.sectors as usize <= self.used_sectors.len();
+
         // Can place chunk in the old sectors.
-        if metadata.sectors == sectors_required {
+        if metadata.sectors == sectors_required && is_inside_file {
             return Ok(metadata);
         }
 
         // Release used sectors.
-        for i in 0..metadata.sectors {
-            let sector_index = metadata.sector_index as usize + i as usize;
-            self.used_sectors.set(sector_index, false);
-        }
+        self.release_sectors(metadata);
 
         let file_length = self.file.metadata()?.len();
         let total_sectors = file_length / REGION_SECTOR_BYTES_LENGTH as u64;
@@ -464,7 +1908,7 @@ impl AnvilRegion {
 
             // Can put chunk in gap.
             if sectors_free == sectors_required {
-                let put_sector_index = sector_index as u32 - sectors_free as u32;
+                let put_sector_index = sector_index as u32 + 1 - sectors_free as u32;
 
                 // Acquire used sectors.
                 for i in 0..sectors_free {
@@ -486,11 +1930,11 @@ impl AnvilRegion {
             self.used_sectors.push(true);
         }
 
-        return Ok(AnvilChunkMetadata::new(
+        Ok(AnvilChunkMetadata::new(
             total_sectors as u32 - sectors_free as u32,
             sectors_required,
             0,
-        ));
+        ))
     }
 
     /// Updates chunk metadata.
@@ -521,14 +1965,23 @@ impl AnvilRegion {
 
 #[cfg(test)]
 mod tests {
+    use crate::limits::{LimitExceeded, ParseLimits};
+    use crate::parse::{ParseIssue, ParseMode};
     use crate::{
-        AnvilChunkMetadata, AnvilChunkProvider, AnvilRegion, ChunkLoadError,
-        REGION_HEADER_BYTES_LENGTH, REGION_SECTOR_BYTES_LENGTH,
+        open_locked, region_position, z_order_key, AnvilChunkMetadata, AnvilChunkProvider,
+        AnvilRegion, ChunkLoadError, ChunkSaveError, PathIoError, REGION_HEADER_BYTES_LENGTH,
+        REGION_SECTOR_BYTES_LENGTH,
     };
     use nbt::CompoundTag;
+    use std::error::Error;
+    use std::fs;
+    use std::io;
     use std::io::Read;
     use std::path::Path;
-    use tempfile::NamedTempFile;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+    use tempfile::{NamedTempFile, TempDir};
 
     #[test]
     fn test_empty_header_write() {
@@ -552,7 +2005,7 @@ mod tests {
 
     #[test]
     fn test_header_read() {
-        let expected_data = vec![
+        let expected_data = [
             AnvilChunkMetadata::new(61, 2, 1570215508),
             AnvilChunkMetadata::new(102, 2, 1570215511),
             AnvilChunkMetadata::new(177, 2, 1570215515),
@@ -770,7 +2223,9 @@ mod tests {
 
         region.write_chunk(15, 15, write_compound_tag).unwrap();
 
+        assert!(region.used_sectors.get(3).unwrap());
         assert!(region.used_sectors.get(4).unwrap());
+        assert_eq!(region.get_metadata(15, 15).sector_index, 3);
         assert_eq!(file.as_file().metadata().unwrap().len(), length);
         assert_eq!(region.used_sectors.len(), 5);
     }
@@ -811,6 +2266,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_delete_chunk() {
+        let file = NamedTempFile::new().unwrap();
+        let mut region = AnvilRegion::new(file.path()).unwrap();
+
+        let mut write_compound_tag = CompoundTag::new();
+        write_compound_tag.insert_bool("test_bool", true);
+
+        region.write_chunk(15, 15, write_compound_tag).unwrap();
+
+        assert!(region.delete_chunk(15, 15).unwrap());
+        assert!(!region.delete_chunk(15, 15).unwrap());
+        assert!(!region.used_sectors.get(2).unwrap());
+
+        let region = AnvilRegion::new(file.path()).unwrap();
+
+        assert!(region.get_metadata(15, 15).is_empty());
+        assert!(region.chunk_positions().is_empty());
+    }
+
     #[test]
     fn test_used_sectors_only_header() {
         let empty_chunks_metadata = Vec::new();
@@ -842,4 +2317,501 @@ mod tests {
         assert_eq!(used_vec[1], 0b10000000);
     }
 
+    #[test]
+    fn test_region_position() {
+        assert_eq!(region_position("r.0.0.mca"), Some((0, 0)));
+        assert_eq!(region_position("r.-3.12.mca"), Some((-3, 12)));
+        assert_eq!(region_position("r.0.0.mcr"), None);
+        assert_eq!(region_position("r.a.0.mca"), None);
+        assert_eq!(region_position("r.0.0.mca.tmp"), None);
+        assert_eq!(region_position("r.67108864.0.mca"), None);
+        assert_eq!(
+            region_position("r.67108863.-67108864.mca"),
+            Some((67108863, -67108864))
+        );
+    }
+
+    #[test]
+    fn test_z_order_key() {
+        let mut chunk_positions = vec![(2, 0), (1, 1), (32, 0), (0, 1), (1, 0), (0, 0), (-1, 0)];
+        chunk_positions.sort_unstable_by_key(|&(chunk_x, chunk_z)| z_order_key(chunk_x, chunk_z));
+
+        assert_eq!(
+            chunk_positions,
+            vec![(-1, 0), (0, 0), (1, 0), (0, 1), (1, 1), (2, 0), (32, 0)]
+        );
+        assert_eq!(z_order_key(31, 31), (0, 0, 1023));
+    }
+
+    #[test]
+    fn test_chunk_positions() {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk_positions = chunk_provider.chunk_positions().unwrap();
+
+        assert!(chunk_positions.contains(&(15, 3)));
+        assert!(!chunk_positions.contains(&(15, 14)));
+    }
+
+    #[test]
+    fn test_chunk_positions_no_folder() {
+        let chunk_provider = AnvilChunkProvider::new("no-folder");
+
+        assert!(chunk_provider.chunk_positions().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cached_headers_after_save() {
+        let temp_dir = TempDir::new().unwrap();
+        let folder = temp_dir.path().to_str().unwrap();
+        let chunk_provider = AnvilChunkProvider::new(folder);
+
+        let mut chunk_compound_tag = CompoundTag::new();
+        chunk_compound_tag.insert_i32("xPos", 1);
+
+        chunk_provider.save_chunk(1, 0, chunk_compound_tag).unwrap();
+        assert_eq!(chunk_provider.chunk_positions().unwrap(), vec![(1, 0)]);
+
+        // Header cached by listing is dropped by save.
+        chunk_provider.save_chunk(2, 0, CompoundTag::new()).unwrap();
+        assert_eq!(
+            chunk_provider.chunk_positions().unwrap(),
+            vec![(1, 0), (2, 0)]
+        );
+
+        let chunk_compound_tag = chunk_provider.load_chunk(1, 0).unwrap();
+        assert_eq!(chunk_compound_tag.get_i32("xPos").unwrap(), 1);
+
+        assert!(chunk_provider.delete_chunk(1, 0).unwrap());
+        assert!(chunk_provider.chunk_last_modified(1, 0).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_invalidate() {
+        let temp_dir = TempDir::new().unwrap();
+        let folder = temp_dir.path().to_str().unwrap();
+        let chunk_provider = AnvilChunkProvider::new(folder);
+
+        chunk_provider.save_chunk(0, 0, CompoundTag::new()).unwrap();
+        chunk_provider
+            .save_chunk(32, 0, CompoundTag::new())
+            .unwrap();
+        chunk_provider.chunk_positions().unwrap();
+
+        let cached_headers = || chunk_provider.header_cache.lock().unwrap().len();
+        assert_eq!(cached_headers(), 2);
+
+        chunk_provider.invalidate(1, 0);
+        assert_eq!(cached_headers(), 1);
+
+        chunk_provider.invalidate_all();
+        assert_eq!(cached_headers(), 0);
+    }
+
+    #[test]
+    fn test_header_past_end_of_file() {
+        let file = NamedTempFile::new().unwrap();
+        let mut header = vec![0; REGION_HEADER_BYTES_LENGTH as usize];
+        header[..4].copy_from_slice(&[0, 0, 100, 2]);
+        fs::write(file.path(), header).unwrap();
+
+        let mut region = AnvilRegion::new(file.path()).unwrap();
+
+        match region.read_chunk(0, 0) {
+            Err(ChunkLoadError::ReadError { io_error }) => {
+                assert_eq!(io_error.kind(), io::ErrorKind::UnexpectedEof)
+            }
+            result => panic!("Expected `ReadError` but got `{:?}`", result),
+        }
+
+        region.write_chunk(0, 0, CompoundTag::new()).unwrap();
+        assert!(region.read_chunk(0, 0).is_ok());
+        assert!(region.delete_chunk(0, 0).unwrap());
+    }
+
+    #[test]
+    fn test_error_display() {
+        let chunk_load_error = ChunkLoadError::ChunkNotFound {
+            chunk_x: 4,
+            chunk_z: 2,
+        };
+        assert_eq!(chunk_load_error.to_string(), "chunk 4 2 not found");
+
+        let io_error = io::Error::from(io::ErrorKind::UnexpectedEof);
+        let chunk_save_error = ChunkSaveError::from(io_error);
+        assert!(chunk_save_error.source().is_some());
+
+        let chunk_save_error =
+            chunk_save_error.with_context(Path::new("region/r.-1.0.mca"), -20, 4);
+        assert_eq!(
+            chunk_save_error.to_string(),
+            "chunk -20 4 of region/r.-1.0.mca: failed to write chunk: unexpected end of file"
+        );
+        assert!(matches!(
+            chunk_save_error.without_context(),
+            ChunkSaveError::WriteError { .. }
+        ));
+    }
+
+    #[test]
+    fn test_error_context() {
+        let temp_dir = TempDir::new().unwrap();
+        let folder = temp_dir.path().to_str().unwrap();
+        let chunk_provider = AnvilChunkProvider::new(folder);
+        let region_path = temp_dir.path().join("r.1.-1.mca");
+
+        // Region file which can't be opened.
+        fs::create_dir(&region_path).unwrap();
+
+        match chunk_provider.save_chunk(40, -3, CompoundTag::new()) {
+            Err(ChunkSaveError::InRegionFile {
+                region_path: error_region_path,
+                chunk_x,
+                chunk_z,
+                error,
+            }) => {
+                assert_eq!(error_region_path, region_path);
+                assert_eq!((chunk_x, chunk_z), (40, -3));
+                assert!(matches!(*error, ChunkSaveError::WriteError { .. }));
+            }
+            result => panic!("Expected `InRegionFile` but got `{:?}`", result),
+        }
+
+        match chunk_provider.load_chunk(40, -3) {
+            Err(ChunkLoadError::InRegionFile { error, .. }) => {
+                assert!(matches!(*error, ChunkLoadError::ReadError { .. }));
+            }
+            result => panic!("Expected `InRegionFile` but got `{:?}`", result),
+        }
+
+        let io_error = chunk_provider.chunk_positions().unwrap_err();
+        assert_eq!(error_path(&io_error), region_path);
+
+        let io_error = chunk_provider.preallocate_region(1, -1, 8192).unwrap_err();
+        assert_eq!(error_path(&io_error), region_path);
+
+        fs::remove_dir(&region_path).unwrap();
+        chunk_provider
+            .save_chunk(40, -3, CompoundTag::new())
+            .unwrap();
+
+        // Missing chunk is reported with absolute coordinates.
+        match chunk_provider.load_chunk(41, -3) {
+            Err(ChunkLoadError::ChunkNotFound { chunk_x, chunk_z }) => {
+                assert_eq!((chunk_x, chunk_z), (41, -3));
+            }
+            result => panic!("Expected `ChunkNotFound` but got `{:?}`", result),
+        }
+
+        // Folder which can't be created.
+        let folder_path = region_path.join("region");
+        let chunk_provider = AnvilChunkProvider::new(folder_path.to_str().unwrap());
+
+        match chunk_provider.save_chunk(4, 2, CompoundTag::new()) {
+            Err(ChunkSaveError::WriteError { io_error }) => {
+                assert_eq!(error_path(&io_error), folder_path);
+            }
+            result => panic!("Expected `WriteError` but got `{:?}`", result),
+        }
+    }
+
+    /// Returns path of file or folder which I/O error is about.
+    fn error_path(io_error: &io::Error) -> &Path {
+        let path_io_error = io_error
+            .get_ref()
+            .and_then(|error| error.downcast_ref::<PathIoError>())
+            .unwrap();
+
+        &path_io_error.path
+    }
+
+    /// Saves chunk into temporary folder and replaces its declared length.
+    fn save_chunk_with_length(temp_dir: &TempDir, length: impl FnOnce(u32) -> u32) {
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+        let mut chunk_compound_tag = CompoundTag::new();
+        chunk_compound_tag.insert_i32("xPos", 4);
+        chunk_provider.save_chunk(4, 2, chunk_compound_tag).unwrap();
+
+        let region_path = temp_dir.path().join("r.0.0.mca");
+        let mut region_data = fs::read(&region_path).unwrap();
+        let length_range =
+            REGION_HEADER_BYTES_LENGTH as usize..REGION_HEADER_BYTES_LENGTH as usize + 4;
+        let mut length_bytes = [0; 4];
+        length_bytes.copy_from_slice(&region_data[length_range.clone()]);
+
+        let length = length(u32::from_be_bytes(length_bytes));
+        region_data[length_range].copy_from_slice(&length.to_be_bytes());
+        fs::write(&region_path, region_data).unwrap();
+    }
+
+    #[test]
+    fn test_parse_mode_trailing_padding() {
+        let temp_dir = TempDir::new().unwrap();
+        save_chunk_with_length(&temp_dir, |length| length + 10);
+        let folder = temp_dir.path().to_str().unwrap();
+
+        for chunk_provider in &[
+            AnvilChunkProvider::new(folder).parse_mode(ParseMode::Strict),
+            AnvilChunkProvider::from_untrusted(folder),
+        ] {
+            match chunk_provider
+                .load_chunk(4, 2)
+                .as_ref()
+                .map_err(ChunkLoadError::without_context)
+            {
+                Err(ChunkLoadError::MalformedChunk { parse_issue }) => {
+                    assert_eq!(
+                        *parse_issue,
+                        ParseIssue::TrailingCompressedData { length: 10 }
+                    )
+                }
+                result => panic!("Expected `MalformedChunk` but got `{:?}`", result),
+            }
+        }
+
+        // Lenient by default.
+        let chunk_provider = AnvilChunkProvider::new(folder);
+        let chunk_compound_tag = chunk_provider.load_chunk(4, 2).unwrap();
+        assert_eq!(chunk_compound_tag.get_i32("xPos").unwrap(), 4);
+
+        let parse_warnings = chunk_provider.take_parse_warnings();
+        assert_eq!(parse_warnings.len(), 1);
+        assert_eq!(
+            (parse_warnings[0].chunk_x, parse_warnings[0].chunk_z),
+            (4, 2)
+        );
+        assert!(chunk_provider.take_parse_warnings().is_empty());
+
+        let xpos = chunk_provider
+            .load_chunk_borrowed(4, 2, |chunk_compound| chunk_compound.get_i32("xPos"))
+            .unwrap();
+        assert_eq!(xpos, Some(4));
+        assert_eq!(chunk_provider.take_parse_warnings().len(), 1);
+    }
+
+    #[test]
+    fn test_parse_mode_length_exceeds_sectors() {
+        let temp_dir = TempDir::new().unwrap();
+        save_chunk_with_length(&temp_dir, |_| REGION_SECTOR_BYTES_LENGTH as u32 + 100);
+        let folder = temp_dir.path().to_str().unwrap();
+
+        match AnvilChunkProvider::new(folder)
+            .parse_mode(ParseMode::Strict)
+            .load_chunk(4, 2)
+        {
+            Err(ChunkLoadError::InRegionFile {
+                region_path,
+                chunk_x,
+                chunk_z,
+                error,
+            }) => {
+                assert_eq!(region_path, temp_dir.path().join("r.0.0.mca"));
+                assert_eq!((chunk_x, chunk_z), (4, 2));
+                assert!(matches!(
+                    *error,
+                    ChunkLoadError::LengthExceedsMaximum { .. }
+                ));
+            }
+            result => panic!("Expected `InRegionFile` but got `{:?}`", result),
+        }
+
+        let chunk_provider = AnvilChunkProvider::new(folder).parse_mode(ParseMode::Lenient);
+        assert!(chunk_provider.load_chunk(4, 2).is_ok());
+
+        let parse_issues: Vec<_> = chunk_provider
+            .take_parse_warnings()
+            .into_iter()
+            .map(|parse_warning| parse_warning.parse_issue)
+            .collect();
+
+        assert!(parse_issues.contains(&ParseIssue::LengthExceedsSectors {
+            length: REGION_SECTOR_BYTES_LENGTH as u32 + 100,
+            sectors_length: REGION_SECTOR_BYTES_LENGTH as u32,
+        }));
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        let temp_dir = TempDir::new().unwrap();
+        let folder = temp_dir.path().to_str().unwrap();
+        let chunk_provider = AnvilChunkProvider::new(folder);
+
+        // Compresses to a few kilobytes.
+        let mut chunk_compound_tag = CompoundTag::new();
+        chunk_compound_tag.insert_i8_vec("Data", vec![0; 2 * 1024 * 1024]);
+        chunk_provider.save_chunk(4, 2, chunk_compound_tag).unwrap();
+
+        let mut chunk_compound_tag = CompoundTag::new();
+
+        for _ in 0..100 {
+            let mut parent_compound_tag = CompoundTag::new();
+            parent_compound_tag.insert_compound_tag("Child", chunk_compound_tag);
+            chunk_compound_tag = parent_compound_tag;
+        }
+
+        chunk_provider.save_chunk(5, 2, chunk_compound_tag).unwrap();
+        chunk_provider.save_chunk(6, 2, CompoundTag::new()).unwrap();
+
+        let chunk_provider = AnvilChunkProvider::from_untrusted(folder);
+        assert!(chunk_provider.load_chunk(4, 2).is_ok());
+        assert!(chunk_provider.load_chunk(6, 2).is_ok());
+
+        match chunk_provider.load_chunk_borrowed(5, 2, |_| ()) {
+            Err(ChunkLoadError::LimitExceeded {
+                limit_exceeded: LimitExceeded::NbtDepth { max_nbt_depth: 64 },
+            }) => {}
+            result => panic!("Expected `NbtDepth` but got `{:?}`", result),
+        }
+
+        let mut parse_limits = ParseLimits::untrusted();
+        parse_limits.max_chunk_length = Some(1024 * 1024);
+        let chunk_provider = AnvilChunkProvider::new(folder).parse_limits(parse_limits.clone());
+
+        match chunk_provider.load_chunk(4, 2) {
+            Err(ChunkLoadError::LimitExceeded {
+                limit_exceeded: LimitExceeded::ChunkLength { .. },
+            }) => {}
+            result => panic!("Expected `ChunkLength` but got `{:?}`", result),
+        }
+
+        parse_limits.timeout = Some(Duration::from_millis(1));
+        let chunk_provider = AnvilChunkProvider::new(folder).parse_limits(parse_limits);
+        thread::sleep(Duration::from_millis(10));
+
+        match chunk_provider.load_chunk(6, 2) {
+            Err(ChunkLoadError::LimitExceeded {
+                limit_exceeded: LimitExceeded::Timeout { .. },
+            }) => {}
+            result => panic!("Expected `Timeout` but got `{:?}`", result),
+        }
+    }
+
+    #[test]
+    fn test_check_positions() {
+        let temp_dir = TempDir::new().unwrap();
+        let folder = temp_dir.path().to_str().unwrap();
+
+        let mut chunk_compound_tag = CompoundTag::new();
+        chunk_compound_tag.insert_i32("xPos", 1);
+        chunk_compound_tag.insert_i32("zPos", 1);
+        AnvilChunkProvider::new(folder)
+            .save_chunk(4, 2, chunk_compound_tag.clone())
+            .unwrap();
+
+        assert!(AnvilChunkProvider::new(folder).load_chunk(4, 2).is_ok());
+
+        let chunk_provider = AnvilChunkProvider::new(folder)
+            .parse_mode(ParseMode::Strict)
+            .check_positions(true);
+        let position_mismatch = ParseIssue::PositionMismatch {
+            stored_chunk_x: 1,
+            stored_chunk_z: 1,
+        };
+
+        match chunk_provider
+            .load_chunk(4, 2)
+            .as_ref()
+            .map_err(ChunkLoadError::without_context)
+        {
+            Err(ChunkLoadError::MalformedChunk { parse_issue }) => {
+                assert_eq!(*parse_issue, position_mismatch)
+            }
+            result => panic!("Expected `MalformedChunk` but got `{:?}`", result),
+        }
+
+        let chunk_provider = chunk_provider.parse_mode(ParseMode::Lenient);
+        assert!(chunk_provider.load_chunk_borrowed(4, 2, |_| ()).is_ok());
+        assert_eq!(
+            chunk_provider.take_parse_warnings()[0].parse_issue,
+            position_mismatch
+        );
+
+        chunk_provider.save_chunk(4, 2, chunk_compound_tag).unwrap();
+        let chunk_compound_tag = chunk_provider.load_chunk(4, 2).unwrap();
+
+        assert_eq!(chunk_compound_tag.get_i32("xPos").unwrap(), 4);
+        assert_eq!(chunk_compound_tag.get_i32("zPos").unwrap(), 2);
+        assert!(chunk_provider.take_parse_warnings().is_empty());
+    }
+
+    #[test]
+    fn test_write_waits_for_region_lock() {
+        let temp_dir = TempDir::new().unwrap();
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+        chunk_provider.save_chunk(4, 2, CompoundTag::new()).unwrap();
+
+        // Lock of other process, locks of separately opened files conflict as well.
+        let region_file = fs::File::open(temp_dir.path().join("r.0.0.mca")).unwrap();
+        region_file.lock().unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                chunk_provider.save_chunk(5, 2, CompoundTag::new()).unwrap();
+                sender.send(()).unwrap();
+            });
+
+            assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+            region_file.unlock().unwrap();
+            receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+        });
+
+        assert!(chunk_provider.load_chunk(5, 2).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_after_region_file_replaced() {
+        let temp_dir = TempDir::new().unwrap();
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+        chunk_provider.save_chunk(4, 2, CompoundTag::new()).unwrap();
+
+        let region_path = temp_dir.path().join("r.0.0.mca");
+        let replacement_path = temp_dir.path().join("r.0.0.mca.replacement");
+
+        // Other process replacing region file while holding lock of it.
+        let region_file = open_locked(&region_path).unwrap();
+        let (sender, receiver) = mpsc::channel();
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                chunk_provider.save_chunk(5, 2, CompoundTag::new()).unwrap();
+                sender.send(()).unwrap();
+            });
+
+            assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+            fs::copy(&region_path, &replacement_path).unwrap();
+            fs::rename(&replacement_path, &region_path).unwrap();
+            drop(region_file);
+            receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+        });
+
+        assert!(chunk_provider.load_chunk(4, 2).is_ok());
+        assert!(chunk_provider.load_chunk(5, 2).is_ok());
+    }
+
+    #[test]
+    fn test_preallocate_region() {
+        let temp_dir = TempDir::new().unwrap();
+        let folder = temp_dir.path().to_str().unwrap();
+        let chunk_provider = AnvilChunkProvider::new(folder);
+        let region_path = temp_dir.path().join("r.0.-1.mca");
+
+        assert!(chunk_provider.preallocate_region(0, -1, 100_000).unwrap());
+        assert!(!chunk_provider.preallocate_region(0, -1, 8192).unwrap());
+        assert_eq!(fs::metadata(&region_path).unwrap().len(), 25 * 4096);
+
+        let mut chunk_compound_tag = CompoundTag::new();
+        chunk_compound_tag.insert_i32("xPos", 3);
+
+        // Chunk takes free sectors after header instead of growing file.
+        chunk_provider
+            .save_chunk(3, -1, chunk_compound_tag)
+            .unwrap();
+        assert_eq!(fs::metadata(&region_path).unwrap().len(), 25 * 4096);
+
+        let region = AnvilRegion::new(&region_path).unwrap();
+        assert_eq!(region.get_metadata(3, 31).sector_index, 2);
+        assert_eq!(chunk_provider.chunk_positions().unwrap(), vec![(3, -1)]);
+    }
 }
@@ -0,0 +1,232 @@
+//! Block and sky light stored per section.
+//!
+//! Light is stored as 4 bits per block in `BlockLight` and `SkyLight` byte arrays.
+//! Editors which change terrain can use [`recompute_skylight`] to fix the most visible
+//! light glitches, or [`invalidate_light`] to make the game relight the chunk on load.
+use crate::chunk::Chunk;
+use crate::section::{block_index, SectionError, SECTION_BLOCKS};
+use crate::tag::remove_tag;
+use std::cmp::Reverse;
+
+/// Maximum light level.
+pub const MAX_LIGHT: u8 = 15;
+
+/// Light level for every block of the section packed as 4 bits per block.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct NibbleArray {
+    data: Vec<u8>,
+}
+
+impl NibbleArray {
+    /// Creates array with every block at light level 0.
+    pub fn new() -> Self {
+        NibbleArray::filled(0)
+    }
+
+    /// Creates array with every block at specified light level.
+    pub fn filled(value: u8) -> Self {
+        let value = value & 15;
+
+        NibbleArray {
+            data: vec![value << 4 | value; SECTION_BLOCKS / 2],
+        }
+    }
+
+    /// Creates array from stored bytes, returns none if length doesn't match section.
+    pub fn from_bytes(bytes: &[i8]) -> Option<Self> {
+        if bytes.len() != SECTION_BLOCKS / 2 {
+            return None;
+        }
+
+        Some(NibbleArray {
+            data: bytes.iter().map(|byte| *byte as u8).collect(),
+        })
+    }
+
+    /// Returns bytes as they are stored in section.
+    pub fn to_bytes(&self) -> Vec<i8> {
+        self.data.iter().map(|byte| *byte as i8).collect()
+    }
+
+    /// Returns light level at section relative coordinates.
+    pub fn get(&self, x: usize, y: usize, z: usize) -> u8 {
+        let index = block_index(x, y, z);
+        let byte = self.data[index / 2];
+
+        if index & 1 == 0 {
+            byte & 15
+        } else {
+            byte >> 4
+        }
+    }
+
+    /// Sets light level at section relative coordinates.
+    pub fn set(&mut self, x: usize, y: usize, z: usize, value: u8) {
+        let index = block_index(x, y, z);
+        let byte = &mut self.data[index / 2];
+
+        if index & 1 == 0 {
+            *byte = (*byte & 0xF0) | (value & 15);
+        } else {
+            *byte = (*byte & 0x0F) | (value & 15) << 4;
+        }
+    }
+}
+
+impl Default for NibbleArray {
+    fn default() -> Self {
+        NibbleArray::new()
+    }
+}
+
+/// Returns how much light block absorbs, approximated from block name.
+fn block_opacity(name: &str) -> u8 {
+    let name = name.trim_start_matches("minecraft:");
+
+    if name.ends_with("air")
+        || name.contains("glass")
+        || name.ends_with("torch")
+        || name.ends_with("sapling")
+        || name.ends_with("button")
+        || name.ends_with("pressure_plate")
+        || name.ends_with("rail")
+        || name.ends_with("sign")
+        || name.ends_with("carpet")
+        || name.ends_with("flower")
+        || name.ends_with("mushroom")
+    {
+        return 0;
+    }
+
+    match name {
+        "grass" | "tall_grass" | "fern" | "large_fern" | "dead_bush" | "dandelion" | "poppy"
+        | "blue_orchid" | "allium" | "azure_bluet" | "red_tulip" | "orange_tulip"
+        | "white_tulip" | "pink_tulip" | "oxeye_daisy" | "sunflower" | "lilac" | "rose_bush"
+        | "peony" | "sugar_cane" | "vine" | "ladder" | "lever" | "redstone_wire" | "snow"
+        | "barrier" | "light" | "wheat" | "carrots" | "potatoes" | "beetroots" | "kelp"
+        | "kelp_plant" | "seagrass" | "tall_seagrass" | "iron_bars" | "fire" | "lily_pad" => 0,
+        "water" | "bubble_column" | "ice" | "frosted_ice" | "cobweb" | "slime_block"
+        | "honey_block" => 1,
+        _ if name.ends_with("leaves") => 1,
+        _ => MAX_LIGHT,
+    }
+}
+
+/// Recomputes sky light of every chunk section.
+///
+/// Light only travels straight down from the top of the chunk, spreading to the sides
+/// is not simulated, so caves near openings can be darker than in game. This is
+/// enough to avoid black terrain or bright underground after edits.
+pub fn recompute_skylight(chunk: &mut Chunk) -> Result<(), SectionError> {
+    let mut sections = chunk.read_sections()?;
+    sections.sort_by_key(|section| Reverse(section.y));
+
+    let mut column_light = [MAX_LIGHT; 256];
+
+    for section in &mut sections {
+        let mut sky_light = NibbleArray::new();
+
+        for y in (0..16).rev() {
+            for z in 0..16 {
+                for x in 0..16 {
+                    let opacity = section
+                        .block_state(x, y, z)
+                        .map_or(0, |block_state| block_opacity(&block_state.name));
+
+                    let light = &mut column_light[z * 16 + x];
+                    *light = light.saturating_sub(opacity);
+
+                    sky_light.set(x, y, z, *light);
+                }
+            }
+        }
+
+        section.sky_light = Some(sky_light);
+        chunk.write_section(section)?;
+    }
+
+    Ok(())
+}
+
+/// Removes stored light and marks chunk as not lit so the game relights it on load.
+pub fn invalidate_light(chunk: &mut Chunk) {
+    for section in chunk.sections_mut() {
+        remove_tag(section, "BlockLight");
+        remove_tag(section, "SkyLight");
+    }
+
+    chunk.data_mut().insert_i8("isLightOn", 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chunk::Chunk;
+    use crate::light::{invalidate_light, recompute_skylight, NibbleArray};
+    use crate::section::{BlockState, Section};
+    use nbt::CompoundTag;
+
+    #[test]
+    fn test_nibble_array() {
+        let mut nibble_array = NibbleArray::new();
+        nibble_array.set(0, 0, 0, 15);
+        nibble_array.set(1, 0, 0, 7);
+
+        assert_eq!(nibble_array.get(0, 0, 0), 15);
+        assert_eq!(nibble_array.get(1, 0, 0), 7);
+        assert_eq!(nibble_array.get(2, 0, 0), 0);
+        assert_eq!(nibble_array.to_bytes()[0], 0x7F);
+        assert!(NibbleArray::from_bytes(&[0; 16]).is_none());
+    }
+
+    fn chunk_with_section(section: &Section) -> Chunk {
+        let mut level = CompoundTag::new();
+        level.insert_compound_tag_vec("Sections", Vec::new());
+
+        let mut chunk_compound_tag = CompoundTag::new();
+        chunk_compound_tag.insert_i32("DataVersion", 2586);
+        chunk_compound_tag.insert_compound_tag("Level", level);
+
+        let mut chunk = Chunk::new(chunk_compound_tag);
+        chunk.write_section(section).unwrap();
+
+        chunk
+    }
+
+    #[test]
+    fn test_recompute_skylight() {
+        let mut section = Section::new(0);
+
+        for x in 0..16 {
+            for z in 0..16 {
+                section.set_block_state(x, 8, z, BlockState::new("minecraft:stone"));
+            }
+        }
+
+        section.set_block_state(0, 8, 0, BlockState::new("minecraft:glass"));
+        section.set_block_state(1, 8, 0, BlockState::new("minecraft:water"));
+
+        let mut chunk = chunk_with_section(&section);
+        recompute_skylight(&mut chunk).unwrap();
+
+        let sections = chunk.read_sections().unwrap();
+        let sky_light = sections[0].sky_light.as_ref().unwrap();
+
+        assert_eq!(sky_light.get(5, 15, 5), 15);
+        assert_eq!(sky_light.get(5, 8, 5), 0);
+        assert_eq!(sky_light.get(5, 0, 5), 0);
+        assert_eq!(sky_light.get(0, 0, 0), 15);
+        assert_eq!(sky_light.get(1, 0, 0), 14);
+    }
+
+    #[test]
+    fn test_invalidate_light() {
+        let mut section = Section::new(0);
+        section.sky_light = Some(NibbleArray::filled(15));
+
+        let mut chunk = chunk_with_section(&section);
+        invalidate_light(&mut chunk);
+
+        assert!(chunk.read_sections().unwrap()[0].sky_light.is_none());
+        assert_eq!(chunk.data().get_i8("isLightOn").unwrap(), 0);
+    }
+}
@@ -0,0 +1,294 @@
+//! Resource limits for parsing region files from untrusted sources.
+//!
+//! Chunk of 1 MB compressed data may decompress to gigabytes, NBT nested a few
+//! thousand levels deep overflows stack of decoder, and archive with millions of
+//! chunks keeps service busy for hours. Services which parse uploaded region files and
+//! world archives should use [`ParseLimits::untrusted`], with which parsing stops
+//! with [`LimitExceeded`] error instead. Limits are disabled by default.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::limits::{LimitExceeded, ParseLimits};
+//! use anvil_region::{AnvilChunkProvider, ChunkLoadError};
+//!
+//! let mut parse_limits = ParseLimits::untrusted();
+//! parse_limits.max_chunks = Some(1);
+//!
+//! let chunk_provider = AnvilChunkProvider::new("test/region").parse_limits(parse_limits);
+//! assert!(chunk_provider.load_chunk(4, 2).is_ok());
+//!
+//! match chunk_provider.load_chunk(4, 2) {
+//!     Err(ChunkLoadError::LimitExceeded {
+//!         limit_exceeded: LimitExceeded::Chunks { max_chunks: 1 },
+//!     }) => {}
+//!     result => panic!("Expected `LimitExceeded` but got `{:?}`", result),
+//! }
+//! ```
+use byteorder::{BigEndian, ReadBytesExt};
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+/// Maximum amount of chunks loaded by provider with untrusted limits.
+pub const UNTRUSTED_MAX_CHUNKS: usize = 32 * 1024;
+/// Maximum nesting of compounds and lists with untrusted limits.
+pub const UNTRUSTED_MAX_NBT_DEPTH: usize = 64;
+/// Maximum length of decompressed chunk with untrusted limits.
+pub const UNTRUSTED_MAX_CHUNK_LENGTH: usize = 16 * 1024 * 1024;
+/// Maximum time provider with untrusted limits loads chunks for.
+pub const UNTRUSTED_TIMEOUT: Duration = Duration::from_secs(30);
+/// Maximum length of extracted archive with untrusted limits.
+pub const UNTRUSTED_MAX_ARCHIVE_LENGTH: u64 = 1024 * 1024 * 1024;
+
+const END_TAG_TYPE: u8 = 0;
+const BYTE_TAG_TYPE: u8 = 1;
+const SHORT_TAG_TYPE: u8 = 2;
+const INT_TAG_TYPE: u8 = 3;
+const LONG_TAG_TYPE: u8 = 4;
+const FLOAT_TAG_TYPE: u8 = 5;
+const DOUBLE_TAG_TYPE: u8 = 6;
+const BYTE_ARRAY_TAG_TYPE: u8 = 7;
+const STRING_TAG_TYPE: u8 = 8;
+const LIST_TAG_TYPE: u8 = 9;
+const COMPOUND_TAG_TYPE: u8 = 10;
+const INT_ARRAY_TAG_TYPE: u8 = 11;
+const LONG_ARRAY_TAG_TYPE: u8 = 12;
+
+/// Limits of resources parsing may use, none of them is enforced if not set.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ParseLimits {
+    /// Amount of chunks provider loads, including failed loads.
+    pub max_chunks: Option<usize>,
+    /// Nesting of compounds and lists in chunk, root compound included.
+    pub max_nbt_depth: Option<usize>,
+    /// Length of decompressed chunk data, which is allocated for every chunk.
+    pub max_chunk_length: Option<usize>,
+    /// Time provider loads chunks for, counted from setting limits.
+    pub timeout: Option<Duration>,
+    /// Total length of files extracted from world archive.
+    pub max_archive_length: Option<u64>,
+}
+
+impl ParseLimits {
+    /// Limits which are never reached by worlds made by the game.
+    pub fn untrusted() -> Self {
+        ParseLimits {
+            max_chunks: Some(UNTRUSTED_MAX_CHUNKS),
+            max_nbt_depth: Some(UNTRUSTED_MAX_NBT_DEPTH),
+            max_chunk_length: Some(UNTRUSTED_MAX_CHUNK_LENGTH),
+            timeout: Some(UNTRUSTED_TIMEOUT),
+            max_archive_length: Some(UNTRUSTED_MAX_ARCHIVE_LENGTH),
+        }
+    }
+}
+
+/// Limit which parsing reached.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LimitExceeded {
+    Chunks { max_chunks: usize },
+    NbtDepth { max_nbt_depth: usize },
+    ChunkLength { max_chunk_length: usize },
+    Timeout { timeout: Duration },
+    ArchiveLength { max_archive_length: u64 },
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitExceeded::Chunks { max_chunks } => {
+                write!(f, "more than {} chunks loaded", max_chunks)
+            }
+            LimitExceeded::NbtDepth { max_nbt_depth } => {
+                write!(f, "tags nested deeper than {}", max_nbt_depth)
+            }
+            LimitExceeded::ChunkLength { max_chunk_length } => {
+                write!(
+                    f,
+                    "chunk decompressed to more than {} bytes",
+                    max_chunk_length
+                )
+            }
+            LimitExceeded::Timeout { timeout } => {
+                write!(f, "chunks loaded for longer than {:?}", timeout)
+            }
+            LimitExceeded::ArchiveLength { max_archive_length } => {
+                write!(
+                    f,
+                    "archive extracts to more than {} bytes",
+                    max_archive_length
+                )
+            }
+        }
+    }
+}
+
+impl Error for LimitExceeded {}
+
+/// List or compound which is being scanned.
+enum Container {
+    Compound,
+    List { element_type: u8, remaining: usize },
+}
+
+/// Returns true if tags of uncompressed NBT data are nested deeper than maximum.
+///
+/// Data is scanned without recursion or allocation per tag. Malformed data isn't
+/// reported here, it fails to decode afterwards.
+pub(crate) fn exceeds_nbt_depth(data: &[u8], max_nbt_depth: usize) -> bool {
+    scan_nbt_depth(data, max_nbt_depth).unwrap_or(false)
+}
+
+fn scan_nbt_depth(mut data: &[u8], max_nbt_depth: usize) -> Option<bool> {
+    if data.read_u8().ok()? != COMPOUND_TAG_TYPE {
+        return None;
+    }
+
+    skip_string(&mut data)?;
+    let mut containers = vec![Container::Compound];
+
+    loop {
+        let tag_type = match containers.last_mut() {
+            None => return Some(false),
+            Some(Container::Compound) => {
+                let tag_type = data.read_u8().ok()?;
+
+                if tag_type == END_TAG_TYPE {
+                    containers.pop();
+                    continue;
+                }
+
+                skip_string(&mut data)?;
+                tag_type
+            }
+            Some(Container::List {
+                element_type,
+                remaining,
+            }) => {
+                if *remaining == 0 {
+                    containers.pop();
+                    continue;
+                }
+
+                *remaining -= 1;
+                *element_type
+            }
+        };
+
+        match tag_type {
+            LIST_TAG_TYPE => {
+                let element_type = data.read_u8().ok()?;
+                let length = read_length(&mut data)?;
+
+                // Elements of end type take no bytes, such list fails to decode anyway.
+                let remaining = if element_type == END_TAG_TYPE {
+                    0
+                } else {
+                    length
+                };
+
+                containers.push(Container::List {
+                    element_type,
+                    remaining,
+                });
+            }
+            COMPOUND_TAG_TYPE => containers.push(Container::Compound),
+            _ => skip_payload(&mut data, tag_type)?,
+        }
+
+        if containers.len() > max_nbt_depth {
+            return Some(true);
+        }
+    }
+}
+
+/// Skips payload of tag which isn't list or compound.
+fn skip_payload(data: &mut &[u8], tag_type: u8) -> Option<()> {
+    let length = match tag_type {
+        BYTE_TAG_TYPE => 1,
+        SHORT_TAG_TYPE => 2,
+        INT_TAG_TYPE | FLOAT_TAG_TYPE => 4,
+        LONG_TAG_TYPE | DOUBLE_TAG_TYPE => 8,
+        BYTE_ARRAY_TAG_TYPE => read_length(data)?,
+        INT_ARRAY_TAG_TYPE => read_length(data)?.checked_mul(4)?,
+        LONG_ARRAY_TAG_TYPE => read_length(data)?.checked_mul(8)?,
+        STRING_TAG_TYPE => return skip_string(data),
+        _ => return None,
+    };
+
+    skip(data, length)
+}
+
+fn skip_string(data: &mut &[u8]) -> Option<()> {
+    let length = data.read_u16::<BigEndian>().ok()?;
+
+    skip(data, length as usize)
+}
+
+fn read_length(data: &mut &[u8]) -> Option<usize> {
+    let length = data.read_i32::<BigEndian>().ok()?;
+
+    if length < 0 {
+        return None;
+    }
+
+    Some(length as usize)
+}
+
+fn skip(data: &mut &[u8], length: usize) -> Option<()> {
+    *data = data.get(length..)?;
+
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::limits::exceeds_nbt_depth;
+    use nbt::encode::write_compound_tag;
+    use nbt::CompoundTag;
+
+    fn nested_compound_tag(depth: usize) -> CompoundTag {
+        let mut compound_tag = CompoundTag::new();
+        compound_tag.insert_str("Name", "minecraft:stone");
+
+        for _ in 1..depth {
+            let mut parent_compound_tag = CompoundTag::new();
+            parent_compound_tag.insert_i32_vec("Data", vec![1, 2, 3]);
+            parent_compound_tag.insert_compound_tag_vec("Children", vec![compound_tag]);
+            compound_tag = parent_compound_tag;
+        }
+
+        compound_tag
+    }
+
+    fn encode(compound_tag: &CompoundTag) -> Vec<u8> {
+        let mut data = Vec::new();
+        write_compound_tag(&mut data, compound_tag).unwrap();
+
+        data
+    }
+
+    #[test]
+    fn test_exceeds_nbt_depth() {
+        // Every level below root is list with compound in it.
+        let data = encode(&nested_compound_tag(5));
+
+        assert!(!exceeds_nbt_depth(&data, 9));
+        assert!(exceeds_nbt_depth(&data, 8));
+    }
+
+    #[test]
+    fn test_exceeds_nbt_depth_malformed() {
+        // Lists of single list nested far deeper than limit, without ends.
+        let mut data = vec![10, 0, 0, 9, 0, 0];
+
+        for _ in 0..10_000 {
+            data.extend_from_slice(&[9, 0, 0, 0, 1]);
+        }
+
+        assert!(exceeds_nbt_depth(&data, 64));
+        assert!(!exceeds_nbt_depth(&data[..100], 64));
+        assert!(!exceeds_nbt_depth(&[9, 0, 0], 64));
+    }
+}
@@ -0,0 +1,358 @@
+//! Loading chunks on worker threads in order of priority.
+//!
+//! Requests carry priority, requests with lower value are served first, so using
+//! distance from camera as priority loads chunks from the center of view outwards.
+//! Repeated requests of pending chunk only raise its priority, requests of chunk which
+//! is being loaded are ignored. Loaded chunks are received in order of completion.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::loader::ChunkLoader;
+//!
+//! let chunk_loader = ChunkLoader::new("test/region", 2);
+//!
+//! for chunk_x in 0..8 {
+//!     let distance = (chunk_x - 4i32).unsigned_abs();
+//!     chunk_loader.request(chunk_x, 2, distance);
+//! }
+//!
+//! let loaded_chunk = chunk_loader.recv().unwrap();
+//! println!("{} {}", loaded_chunk.chunk_x, loaded_chunk.chunk_z);
+//! ```
+use crate::concurrent::ConcurrentAnvilChunkProvider;
+use crate::hash::{CoordinateHashMap, CoordinateHashSet};
+use crate::ChunkLoadError;
+use nbt::CompoundTag;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Pending request ordered by priority value, then by sequence number, with chunk position.
+type QueuedRequest = Reverse<(u32, u64, (i32, i32))>;
+
+/// Result of chunk request.
+#[derive(Debug)]
+pub struct LoadedChunk {
+    pub chunk_x: i32,
+    pub chunk_z: i32,
+    pub result: Result<CompoundTag, ChunkLoadError>,
+}
+
+/// Loader of chunks of region folder with priority queue of requests.
+pub struct ChunkLoader {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+    loaded_receiver: Receiver<LoadedChunk>,
+}
+
+/// State shared with worker threads.
+struct Shared {
+    chunk_provider: ConcurrentAnvilChunkProvider,
+    queue: Mutex<RequestQueue>,
+    /// Notified when request is queued or loader is dropped.
+    queue_condvar: Condvar,
+}
+
+struct RequestQueue {
+    /// Priority of pending requests keyed by chunk position.
+    pending: CoordinateHashMap<(i32, i32), u32>,
+    /// Pending requests, lowest priority value and oldest request first.
+    ///
+    /// Entries which don't match priority in pending requests are stale and skipped.
+    heap: BinaryHeap<QueuedRequest>,
+    /// Chunks which are being loaded by workers.
+    in_flight: CoordinateHashSet<(i32, i32)>,
+    /// Sequence number of the next request.
+    sequence: u64,
+    closed: bool,
+}
+
+impl ChunkLoader {
+    /// Creates loader with specified amount of worker threads, at least one.
+    pub fn new(folder_path: impl AsRef<Path>, worker_count: usize) -> Self {
+        let shared = Arc::new(Shared {
+            chunk_provider: ConcurrentAnvilChunkProvider::new(folder_path),
+            queue: Mutex::new(RequestQueue::new()),
+            queue_condvar: Condvar::new(),
+        });
+
+        let (loaded_sender, loaded_receiver) = mpsc::channel();
+
+        let workers = (0..worker_count.max(1))
+            .map(|_| {
+                let shared = shared.clone();
+                let loaded_sender = loaded_sender.clone();
+
+                thread::spawn(move || shared.run_worker(loaded_sender))
+            })
+            .collect();
+
+        ChunkLoader {
+            shared,
+            workers,
+            loaded_receiver,
+        }
+    }
+
+    pub fn folder_path(&self) -> &Path {
+        self.shared.chunk_provider.folder_path()
+    }
+
+    /// Queues load of chunk at the specified coordinates.
+    ///
+    /// If chunk is already pending, its priority is raised to lower of two values.
+    /// Returns false if request was merged with pending or in-flight request.
+    pub fn request(&self, chunk_x: i32, chunk_z: i32, priority: u32) -> bool {
+        let chunk_position = (chunk_x, chunk_z);
+        let mut queue = self.shared.queue();
+
+        if queue.in_flight.contains(&chunk_position) {
+            return false;
+        }
+
+        let is_new = match queue.pending.get(&chunk_position) {
+            Some(&pending_priority) if pending_priority <= priority => return false,
+            Some(_) => false,
+            None => true,
+        };
+
+        queue.push(chunk_position, priority);
+        drop(queue);
+        self.shared.queue_condvar.notify_one();
+
+        is_new
+    }
+
+    /// Removes pending request, returns false if chunk is not pending.
+    ///
+    /// Chunk which is being loaded is still received.
+    pub fn cancel(&self, chunk_x: i32, chunk_z: i32) -> bool {
+        self.shared
+            .queue()
+            .pending
+            .remove(&(chunk_x, chunk_z))
+            .is_some()
+    }
+
+    /// Removes all pending requests.
+    pub fn cancel_all(&self) {
+        let mut queue = self.shared.queue();
+
+        queue.pending.clear();
+        queue.heap.clear();
+    }
+
+    /// Returns amount of requests which are pending or being loaded.
+    pub fn queued_count(&self) -> usize {
+        let queue = self.shared.queue();
+
+        queue.pending.len() + queue.in_flight.len()
+    }
+
+    /// Waits for the next loaded chunk.
+    ///
+    /// Blocks forever if nothing is queued. Returns none once every worker has exited,
+    /// like after workers panicked.
+    pub fn recv(&self) -> Option<LoadedChunk> {
+        // Workers hold senders until they exit.
+        self.loaded_receiver.recv().ok()
+    }
+
+    /// Waits for the next loaded chunk up to timeout.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<LoadedChunk> {
+        match self.loaded_receiver.recv_timeout(timeout) {
+            Ok(loaded_chunk) => Some(loaded_chunk),
+            Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => None,
+        }
+    }
+
+    /// Returns loaded chunk if there is one without waiting.
+    pub fn try_recv(&self) -> Option<LoadedChunk> {
+        self.loaded_receiver.try_recv().ok()
+    }
+}
+
+impl Drop for ChunkLoader {
+    /// Stops workers after chunks which are being loaded, pending requests are dropped.
+    fn drop(&mut self) {
+        self.shared.queue().closed = true;
+        self.shared.queue_condvar.notify_all();
+
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Shared {
+    fn queue(&self) -> MutexGuard<'_, RequestQueue> {
+        self.queue.lock().unwrap_or_else(|error| error.into_inner())
+    }
+
+    fn run_worker(&self, loaded_sender: Sender<LoadedChunk>) {
+        loop {
+            let chunk_position = {
+                let mut queue = self.queue();
+
+                loop {
+                    if queue.closed {
+                        return;
+                    }
+
+                    if let Some(chunk_position) = queue.pop() {
+                        break chunk_position;
+                    }
+
+                    queue = self
+                        .queue_condvar
+                        .wait(queue)
+                        .unwrap_or_else(|error| error.into_inner());
+                }
+            };
+
+            let (chunk_x, chunk_z) = chunk_position;
+            let result = self.chunk_provider.load_chunk(chunk_x, chunk_z);
+
+            self.queue().in_flight.remove(&chunk_position);
+
+            let loaded_chunk = LoadedChunk {
+                chunk_x,
+                chunk_z,
+                result,
+            };
+
+            // Receiver is only dropped together with loader.
+            let _ = loaded_sender.send(loaded_chunk);
+        }
+    }
+}
+
+impl RequestQueue {
+    fn new() -> Self {
+        RequestQueue {
+            pending: CoordinateHashMap::default(),
+            heap: BinaryHeap::new(),
+            in_flight: CoordinateHashSet::default(),
+            sequence: 0,
+            closed: false,
+        }
+    }
+
+    /// Sets priority of pending request.
+    fn push(&mut self, chunk_position: (i32, i32), priority: u32) {
+        self.pending.insert(chunk_position, priority);
+        self.sequence += 1;
+        self.heap
+            .push(Reverse((priority, self.sequence, chunk_position)));
+    }
+
+    /// Takes pending request with the lowest priority value and marks it in-flight.
+    fn pop(&mut self) -> Option<(i32, i32)> {
+        while let Some(Reverse((priority, _, chunk_position))) = self.heap.pop() {
+            if self.pending.get(&chunk_position) != Some(&priority) {
+                continue;
+            }
+
+            self.pending.remove(&chunk_position);
+            self.in_flight.insert(chunk_position);
+
+            return Some(chunk_position);
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::loader::{ChunkLoader, RequestQueue};
+    use std::time::Duration;
+
+    #[test]
+    fn test_request_queue_order() {
+        let mut queue = RequestQueue::new();
+
+        for (chunk_position, priority) in [((0, 0), 5), ((1, 0), 2), ((2, 0), 2), ((3, 0), 9)] {
+            queue.push(chunk_position, priority);
+        }
+
+        // Raised priority leaves stale entry in heap.
+        queue.push((3, 0), 1);
+
+        let mut chunk_positions = Vec::new();
+
+        while let Some(chunk_position) = queue.pop() {
+            chunk_positions.push(chunk_position);
+        }
+
+        assert_eq!(chunk_positions, vec![(3, 0), (1, 0), (2, 0), (0, 0)]);
+        assert_eq!(queue.in_flight.len(), 4);
+    }
+
+    #[test]
+    fn test_load_chunks() {
+        let loader = ChunkLoader::new("test/region", 2);
+
+        assert!(loader.request(4, 2, 1));
+        assert!(loader.request(15, 3, 0));
+        assert!(loader.request(15, 14, 2));
+
+        let mut loaded_chunks = Vec::new();
+
+        for _ in 0..3 {
+            let loaded_chunk = loader.recv_timeout(Duration::from_secs(10)).unwrap();
+            loaded_chunks.push((
+                loaded_chunk.chunk_x,
+                loaded_chunk.chunk_z,
+                loaded_chunk.result.is_ok(),
+            ));
+        }
+
+        loaded_chunks.sort_unstable();
+
+        assert_eq!(
+            loaded_chunks,
+            vec![(4, 2, true), (15, 3, true), (15, 14, false)]
+        );
+        assert_eq!(loader.queued_count(), 0);
+        assert!(loader.try_recv().is_none());
+    }
+
+    #[test]
+    fn test_recv_after_workers_exited() {
+        let loader = ChunkLoader::new("test/region", 2);
+
+        loader.shared.queue().closed = true;
+        loader.shared.queue_condvar.notify_all();
+
+        assert!(loader.recv().is_none());
+    }
+
+    #[test]
+    fn test_request_dedup() {
+        let loader = ChunkLoader::new("test/region", 1);
+        let mut queue = loader.shared.queue();
+
+        // Chunk is being loaded.
+        queue.in_flight.insert((4, 2));
+        drop(queue);
+
+        assert!(!loader.request(4, 2, 0));
+
+        let mut queue = loader.shared.queue();
+        queue.in_flight.clear();
+        queue.closed = true;
+        drop(queue);
+
+        assert!(loader.request(15, 3, 5));
+        assert!(!loader.request(15, 3, 7));
+        assert!(!loader.request(15, 3, 1));
+        assert!(loader.cancel(15, 3));
+        assert!(!loader.cancel(15, 3));
+    }
+}
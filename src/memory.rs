@@ -0,0 +1,303 @@
+//! Memory budget shared by caches of several providers.
+//!
+//! [`MemoryBudget`] accounts memory held by chunk cache of `PrefetchChunkProvider`,
+//! dirty chunks of `WriteBackChunkProvider` and compressed regions cached by
+//! `AsyncZipChunkProvider` which were configured with it. Once their total exceeds
+//! the limit, the largest of them releases memory until total is under the limit
+//! again: cached chunks and regions are evicted, dirty chunks are flushed. Amounts are
+//! estimates of heap memory held by chunk data, bookkeeping isn't counted.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::memory::MemoryBudget;
+//! use anvil_region::prefetch::{PrefetchChunkProvider, PrefetchOptions};
+//! use anvil_region::write_back::{WriteBackChunkProvider, WriteBackOptions};
+//! use tempfile::TempDir;
+//!
+//! let memory_budget = MemoryBudget::new(64 * 1024 * 1024);
+//!
+//! let prefetch_options = PrefetchOptions::default().memory_budget(memory_budget.clone());
+//! let chunk_provider = PrefetchChunkProvider::new("test/region", prefetch_options);
+//! let chunk_compound_tag = chunk_provider.load_chunk(4, 2).unwrap();
+//!
+//! let region_dir = TempDir::new().unwrap();
+//! let write_back_options = WriteBackOptions::default().memory_budget(memory_budget.clone());
+//! let write_back_provider = WriteBackChunkProvider::new(region_dir.path(), write_back_options);
+//! write_back_provider.save_chunk(4, 2, chunk_compound_tag).unwrap();
+//!
+//! assert!(memory_budget.used() <= memory_budget.limit());
+//! ```
+use nbt::{CompoundTag, Tag};
+use std::cmp::Reverse;
+use std::fmt;
+use std::mem;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+
+/// Limit of total memory of caches registered with it, clones share accounting.
+#[derive(Clone)]
+pub struct MemoryBudget {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    limit: usize,
+    used: AtomicUsize,
+    next_consumer_id: AtomicU64,
+    consumers: Mutex<Vec<Consumer>>,
+    /// Held while memory is released, so concurrent callers don't release it twice.
+    release_lock: Mutex<()>,
+}
+
+struct Consumer {
+    id: u64,
+    usage: Arc<AtomicUsize>,
+    consumer: Weak<dyn MemoryConsumer>,
+}
+
+/// Cache which can release memory accounted by budget.
+pub(crate) trait MemoryConsumer: Send + Sync {
+    /// Releases at least specified amount of bytes if it holds that much, updating
+    /// usage of its registration.
+    ///
+    /// Called without locks of any consumer held.
+    fn release_memory(&self, bytes: usize);
+}
+
+/// Memory usage of one consumer, unregistered when dropped.
+pub(crate) struct MemoryRegistration {
+    memory_budget: MemoryBudget,
+    id: u64,
+    usage: Arc<AtomicUsize>,
+}
+
+impl MemoryBudget {
+    /// Creates budget with limit in bytes.
+    pub fn new(limit: usize) -> Self {
+        let shared = Shared {
+            limit,
+            used: AtomicUsize::new(0),
+            next_consumer_id: AtomicU64::new(0),
+            consumers: Mutex::new(Vec::new()),
+            release_lock: Mutex::new(()),
+        };
+
+        MemoryBudget {
+            shared: Arc::new(shared),
+        }
+    }
+
+    /// Returns limit in bytes.
+    pub fn limit(&self) -> usize {
+        self.shared.limit
+    }
+
+    /// Returns amount of bytes held by registered caches.
+    pub fn used(&self) -> usize {
+        self.shared.used.load(Ordering::SeqCst)
+    }
+
+    /// Returns amount of registered caches.
+    pub fn consumer_count(&self) -> usize {
+        self.consumers().len()
+    }
+
+    /// Registers consumer with zero usage.
+    pub(crate) fn register(&self, consumer: Weak<dyn MemoryConsumer>) -> MemoryRegistration {
+        let id = self.shared.next_consumer_id.fetch_add(1, Ordering::Relaxed);
+        let usage = Arc::new(AtomicUsize::new(0));
+
+        self.consumers().push(Consumer {
+            id,
+            usage: usage.clone(),
+            consumer,
+        });
+
+        MemoryRegistration {
+            memory_budget: self.clone(),
+            id,
+            usage,
+        }
+    }
+
+    /// Releases memory of consumers, the largest first, while used memory exceeds limit.
+    ///
+    /// Must be called without locks of any consumer held.
+    pub(crate) fn enforce(&self) {
+        if self.used() <= self.limit() {
+            return;
+        }
+
+        let _release_guard = self
+            .shared
+            .release_lock
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+
+        let mut consumers: Vec<_> = self
+            .consumers()
+            .iter()
+            .map(|consumer| (consumer.usage.clone(), consumer.consumer.clone()))
+            .collect();
+
+        consumers.sort_by_key(|(usage, _)| Reverse(usage.load(Ordering::SeqCst)));
+
+        for (_, consumer) in consumers {
+            let used = self.used();
+
+            if used <= self.limit() {
+                break;
+            }
+
+            // Consumer which is being dropped has nothing to release.
+            if let Some(consumer) = consumer.upgrade() {
+                consumer.release_memory(used - self.limit());
+            }
+        }
+    }
+
+    fn consumers(&self) -> MutexGuard<'_, Vec<Consumer>> {
+        self.shared
+            .consumers
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+impl fmt::Debug for MemoryBudget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryBudget")
+            .field("limit", &self.limit())
+            .field("used", &self.used())
+            .finish()
+    }
+}
+
+impl MemoryRegistration {
+    pub(crate) fn memory_budget(&self) -> &MemoryBudget {
+        &self.memory_budget
+    }
+
+    /// Replaces usage of consumer with specified amount of bytes.
+    pub(crate) fn set_usage(&self, bytes: usize) {
+        let previous_bytes = self.usage.swap(bytes, Ordering::SeqCst);
+        let used = &self.memory_budget.shared.used;
+
+        if bytes > previous_bytes {
+            used.fetch_add(bytes - previous_bytes, Ordering::SeqCst);
+        } else {
+            used.fetch_sub(previous_bytes - bytes, Ordering::SeqCst);
+        }
+    }
+}
+
+impl Drop for MemoryRegistration {
+    fn drop(&mut self) {
+        self.set_usage(0);
+        self.memory_budget
+            .consumers()
+            .retain(|consumer| consumer.id != self.id);
+    }
+}
+
+/// Returns estimated amount of heap bytes held by compound tag.
+pub(crate) fn compound_tag_size(compound_tag: &CompoundTag) -> usize {
+    compound_tag
+        .iter()
+        .map(|(name, tag)| name.len() + mem::size_of::<(String, Tag)>() + tag_size(tag))
+        .sum()
+}
+
+fn tag_size(tag: &Tag) -> usize {
+    match tag {
+        Tag::ByteArray(values) => values.len(),
+        Tag::String(value) => value.len(),
+        Tag::List(tags) => tags
+            .iter()
+            .map(|tag| mem::size_of::<Tag>() + tag_size(tag))
+            .sum(),
+        Tag::Compound(compound_tag) => compound_tag_size(compound_tag),
+        Tag::IntArray(values) => values.len() * 4,
+        Tag::LongArray(values) => values.len() * 8,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::memory::{compound_tag_size, MemoryBudget, MemoryConsumer, MemoryRegistration};
+    use nbt::CompoundTag;
+    use std::sync::atomic::Ordering;
+    use std::sync::{Arc, Mutex, Weak};
+
+    /// Consumer which releases everything it holds.
+    struct TestConsumer {
+        registration: Mutex<Option<MemoryRegistration>>,
+    }
+
+    impl TestConsumer {
+        fn new(memory_budget: &MemoryBudget, usage: usize) -> Arc<Self> {
+            let consumer = Arc::new(TestConsumer {
+                registration: Mutex::new(None),
+            });
+
+            let weak_consumer: Weak<TestConsumer> = Arc::downgrade(&consumer);
+            let registration = memory_budget.register(weak_consumer);
+            registration.set_usage(usage);
+
+            *consumer.registration.lock().unwrap() = Some(registration);
+
+            consumer
+        }
+
+        fn usage(&self) -> usize {
+            let registration = self.registration.lock().unwrap();
+            let registration = registration.as_ref().unwrap();
+
+            registration.usage.load(Ordering::SeqCst)
+        }
+    }
+
+    impl MemoryConsumer for TestConsumer {
+        fn release_memory(&self, _bytes: usize) {
+            if let Some(registration) = &*self.registration.lock().unwrap() {
+                registration.set_usage(0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_enforce() {
+        let memory_budget = MemoryBudget::new(1000);
+
+        let small_consumer = TestConsumer::new(&memory_budget, 400);
+        let large_consumer = TestConsumer::new(&memory_budget, 900);
+
+        assert_eq!(memory_budget.used(), 1300);
+        assert_eq!(memory_budget.consumer_count(), 2);
+
+        memory_budget.enforce();
+
+        assert_eq!(memory_budget.used(), 400);
+        assert_eq!(small_consumer.usage(), 400);
+        assert_eq!(large_consumer.usage(), 0);
+
+        drop(small_consumer);
+
+        assert_eq!(memory_budget.used(), 0);
+        assert_eq!(memory_budget.consumer_count(), 1);
+    }
+
+    #[test]
+    fn test_compound_tag_size() {
+        let mut compound_tag = CompoundTag::new();
+        compound_tag.insert_i64_vec("Data", vec![0; 1000]);
+
+        let mut nested_compound_tag = CompoundTag::new();
+        nested_compound_tag.insert_compound_tag("Level", compound_tag.clone());
+
+        assert!(compound_tag_size(&compound_tag) >= 8000);
+        assert!(compound_tag_size(&nested_compound_tag) > compound_tag_size(&compound_tag));
+    }
+}
@@ -0,0 +1,331 @@
+//! Copying chunks of one world into another.
+//!
+//! Chunks present in both worlds are resolved by [`MergePolicy`]. Chunks can be shifted
+//! on the way, which stitches maps made separately into one world.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::merge::{merge, MergePolicy};
+//! use anvil_region::AnvilChunkProvider;
+//! use tempfile::TempDir;
+//!
+//! let target_dir = TempDir::new().unwrap();
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//! let target_chunk_provider = AnvilChunkProvider::new(target_dir.path().to_str().unwrap());
+//!
+//! let merge_report = merge(&chunk_provider, &target_chunk_provider, MergePolicy::Skip, (64, 0)).unwrap();
+//!
+//! assert_eq!(merge_report.skipped_chunks, 0);
+//! assert!(target_chunk_provider.load_chunk(68, 2).is_ok());
+//! ```
+use crate::relocate::{copy_chunk, RelocateError};
+use crate::world::{ENTITIES_FOLDER, POI_FOLDER, REGION_FOLDER};
+use crate::AnvilChunkProvider;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// How chunk present in both source and target is resolved.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MergePolicy {
+    /// Target chunk is kept.
+    Skip,
+    /// Target chunk is replaced by source chunk.
+    Overwrite,
+    /// Chunk with later modification time in region header is kept, target wins ties.
+    Newest,
+}
+
+/// Result of merge.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MergeReport {
+    /// Chunks which were copied into target.
+    pub copied_chunks: usize,
+    /// Chunks which were kept in target by policy.
+    pub skipped_chunks: usize,
+}
+
+/// Copies every chunk of provider into target provider shifted by offset in chunks.
+pub fn merge(
+    chunk_provider: &AnvilChunkProvider,
+    target_chunk_provider: &AnvilChunkProvider,
+    policy: MergePolicy,
+    offset: (i32, i32),
+) -> Result<MergeReport, RelocateError> {
+    let (merge_report, _) = merge_positions(
+        chunk_provider,
+        target_chunk_provider,
+        offset,
+        |chunk_position, target_chunk_position| {
+            should_copy(
+                chunk_provider,
+                chunk_position,
+                target_chunk_provider,
+                target_chunk_position,
+                policy,
+            )
+        },
+    )?;
+
+    Ok(merge_report)
+}
+
+/// Merges terrain, entity and point of interest chunks of world folder into target world.
+///
+/// Policy is applied to terrain chunks and the same decision is used for entity and
+/// point of interest chunks at that position, so they always stay together.
+pub fn merge_world(
+    world_folder_path: &Path,
+    target_world_folder_path: &Path,
+    policy: MergePolicy,
+    offset: (i32, i32),
+) -> Result<MergeReport, RelocateError> {
+    let region_folder_path = world_folder_path.join(REGION_FOLDER);
+    let target_region_folder_path = target_world_folder_path.join(REGION_FOLDER);
+
+    let chunk_provider = AnvilChunkProvider::from_path(&region_folder_path);
+
+    let target_chunk_provider = AnvilChunkProvider::from_path(&target_region_folder_path);
+
+    let (merge_report, copied_positions) = merge_positions(
+        &chunk_provider,
+        &target_chunk_provider,
+        offset,
+        |chunk_position, target_chunk_position| {
+            should_copy(
+                &chunk_provider,
+                chunk_position,
+                &target_chunk_provider,
+                target_chunk_position,
+                policy,
+            )
+        },
+    )?;
+
+    let terrain_positions: HashSet<(i32, i32)> =
+        chunk_provider.chunk_positions()?.into_iter().collect();
+
+    for folder in &[ENTITIES_FOLDER, POI_FOLDER] {
+        let folder_path = world_folder_path.join(folder);
+        let target_folder_path = target_world_folder_path.join(folder);
+
+        let chunk_provider = AnvilChunkProvider::from_path(&folder_path);
+
+        let target_chunk_provider = AnvilChunkProvider::from_path(&target_folder_path);
+
+        merge_positions(
+            &chunk_provider,
+            &target_chunk_provider,
+            offset,
+            |chunk_position, target_chunk_position| {
+                if terrain_positions.contains(&chunk_position) {
+                    return Ok(copied_positions.contains(&chunk_position));
+                }
+
+                should_copy(
+                    &chunk_provider,
+                    chunk_position,
+                    &target_chunk_provider,
+                    target_chunk_position,
+                    policy,
+                )
+            },
+        )?;
+    }
+
+    Ok(merge_report)
+}
+
+/// Copies chunks for which callback returns true, returns report and copied source positions.
+fn merge_positions(
+    chunk_provider: &AnvilChunkProvider,
+    target_chunk_provider: &AnvilChunkProvider,
+    (offset_x, offset_z): (i32, i32),
+    mut should_copy: impl FnMut((i32, i32), (i32, i32)) -> Result<bool, RelocateError>,
+) -> Result<(MergeReport, HashSet<(i32, i32)>), RelocateError> {
+    if chunk_provider.folder_path == target_chunk_provider.folder_path {
+        return Err(RelocateError::TargetIsSource);
+    }
+
+    let mut merge_report = MergeReport::default();
+    let mut copied_positions = HashSet::new();
+
+    for (chunk_x, chunk_z) in chunk_provider.chunk_positions()? {
+        let target_chunk_position = (chunk_x + offset_x, chunk_z + offset_z);
+
+        if !should_copy((chunk_x, chunk_z), target_chunk_position)? {
+            merge_report.skipped_chunks += 1;
+            continue;
+        }
+
+        copy_chunk(
+            chunk_provider,
+            (chunk_x, chunk_z),
+            target_chunk_provider,
+            target_chunk_position,
+        )?;
+
+        copied_positions.insert((chunk_x, chunk_z));
+        merge_report.copied_chunks += 1;
+    }
+
+    Ok((merge_report, copied_positions))
+}
+
+fn should_copy(
+    chunk_provider: &AnvilChunkProvider,
+    (chunk_x, chunk_z): (i32, i32),
+    target_chunk_provider: &AnvilChunkProvider,
+    (target_chunk_x, target_chunk_z): (i32, i32),
+    policy: MergePolicy,
+) -> Result<bool, RelocateError> {
+    let target_last_modified =
+        match target_chunk_provider.chunk_last_modified(target_chunk_x, target_chunk_z)? {
+            Some(target_last_modified) => target_last_modified,
+            None => return Ok(true),
+        };
+
+    let should_copy = match policy {
+        MergePolicy::Skip => false,
+        MergePolicy::Overwrite => true,
+        MergePolicy::Newest => chunk_provider
+            .chunk_last_modified(chunk_x, chunk_z)?
+            .is_some_and(|last_modified| last_modified > target_last_modified),
+    };
+
+    Ok(should_copy)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::merge::{merge, merge_world, MergePolicy, MergeReport};
+    use crate::relocate::{copy_chunk, RelocateError};
+    use crate::AnvilChunkProvider;
+    use nbt::CompoundTag;
+    use tempfile::TempDir;
+
+    fn chunk(chunk_x: i32, chunk_z: i32, inhabited_time: i64) -> CompoundTag {
+        let mut level = CompoundTag::new();
+        level.insert_i32("xPos", chunk_x);
+        level.insert_i32("zPos", chunk_z);
+        level.insert_i64("InhabitedTime", inhabited_time);
+
+        let mut chunk_compound_tag = CompoundTag::new();
+        chunk_compound_tag.insert_compound_tag("Level", level);
+
+        chunk_compound_tag
+    }
+
+    fn inhabited_time(chunk_provider: &AnvilChunkProvider, chunk_x: i32, chunk_z: i32) -> i64 {
+        let chunk_compound_tag = chunk_provider.load_chunk(chunk_x, chunk_z).unwrap();
+
+        chunk_compound_tag
+            .get_compound_tag("Level")
+            .unwrap()
+            .get_i64("InhabitedTime")
+            .unwrap()
+    }
+
+    #[test]
+    fn test_merge_policies() {
+        for (policy, expected_inhabited_time, copied_chunks) in &[
+            (MergePolicy::Skip, 2, 1),
+            (MergePolicy::Overwrite, 1, 2),
+            (MergePolicy::Newest, 2, 1),
+        ] {
+            let source_dir = TempDir::new().unwrap();
+            let target_dir = TempDir::new().unwrap();
+            let chunk_provider = AnvilChunkProvider::new(source_dir.path().to_str().unwrap());
+            let target_chunk_provider =
+                AnvilChunkProvider::new(target_dir.path().to_str().unwrap());
+
+            chunk_provider.save_chunk(0, 0, chunk(0, 0, 1)).unwrap();
+            chunk_provider.save_chunk(1, 0, chunk(1, 0, 1)).unwrap();
+            target_chunk_provider
+                .save_chunk(2, 0, chunk(2, 0, 2))
+                .unwrap();
+
+            let merge_report =
+                merge(&chunk_provider, &target_chunk_provider, *policy, (1, 0)).unwrap();
+
+            assert_eq!(
+                merge_report,
+                MergeReport {
+                    copied_chunks: *copied_chunks,
+                    skipped_chunks: 2 - copied_chunks,
+                }
+            );
+            assert_eq!(inhabited_time(&target_chunk_provider, 1, 0), 1);
+            assert_eq!(
+                inhabited_time(&target_chunk_provider, 2, 0),
+                *expected_inhabited_time
+            );
+        }
+    }
+
+    #[test]
+    fn test_merge_into_itself() {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+
+        match merge(&chunk_provider, &chunk_provider, MergePolicy::Skip, (0, 0)) {
+            Err(RelocateError::TargetIsSource) => {}
+            result => panic!("Expected `TargetIsSource` but got `{:?}`", result),
+        }
+    }
+
+    #[test]
+    fn test_merge_world_keeps_entities_with_terrain() {
+        let world_dir = TempDir::new().unwrap();
+        let target_world_dir = TempDir::new().unwrap();
+        let folder = |dir: &TempDir, name: &str| dir.path().join(name);
+
+        let region_folder = folder(&world_dir, "region");
+        let entities_folder = folder(&world_dir, "entities");
+        let target_region_folder = folder(&target_world_dir, "region");
+        let target_entities_folder = folder(&target_world_dir, "entities");
+
+        let chunk_provider = AnvilChunkProvider::new(region_folder.to_str().unwrap());
+        let entity_chunk_provider = AnvilChunkProvider::new(entities_folder.to_str().unwrap());
+        let target_chunk_provider = AnvilChunkProvider::new(target_region_folder.to_str().unwrap());
+        let target_entity_chunk_provider =
+            AnvilChunkProvider::new(target_entities_folder.to_str().unwrap());
+
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        copy_chunk(&fixture_chunk_provider, (4, 2), &chunk_provider, (4, 2)).unwrap();
+        copy_chunk(&fixture_chunk_provider, (15, 3), &chunk_provider, (15, 3)).unwrap();
+        copy_chunk(
+            &fixture_chunk_provider,
+            (4, 2),
+            &target_chunk_provider,
+            (4, 2),
+        )
+        .unwrap();
+
+        let mut entity_chunk = CompoundTag::new();
+        entity_chunk.insert_i32_vec("Position", vec![4, 2]);
+        entity_chunk_provider
+            .save_chunk(4, 2, entity_chunk.clone())
+            .unwrap();
+        entity_chunk.insert_i32_vec("Position", vec![15, 3]);
+        entity_chunk_provider
+            .save_chunk(15, 3, entity_chunk)
+            .unwrap();
+
+        let merge_report = merge_world(
+            world_dir.path(),
+            target_world_dir.path(),
+            MergePolicy::Skip,
+            (0, 0),
+        )
+        .unwrap();
+
+        assert_eq!(merge_report.copied_chunks, 1);
+        assert_eq!(merge_report.skipped_chunks, 1);
+        assert_eq!(
+            target_entity_chunk_provider.chunk_positions().unwrap(),
+            vec![(15, 3)]
+        );
+    }
+}
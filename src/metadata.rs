@@ -0,0 +1,165 @@
+//! Summary of world for world pickers and server panels.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::level::LevelData;
+//! use anvil_region::metadata::WorldMetadata;
+//! use anvil_region::world::AnvilWorld;
+//! use tempfile::TempDir;
+//!
+//! let world_dir = TempDir::new().unwrap();
+//! let mut level_data = LevelData::new();
+//! level_data.set_name("Survival");
+//! level_data.save(world_dir.path()).unwrap();
+//!
+//! let world = AnvilWorld::open(world_dir.path()).unwrap();
+//! let world_metadata = WorldMetadata::read(&world).unwrap();
+//!
+//! assert_eq!(world_metadata.name.as_deref(), Some("Survival"));
+//! assert_eq!(world_metadata.dimensions.len(), 3);
+//! ```
+use crate::version::closest_release;
+use crate::world::{AnvilWorld, Dimension};
+use std::path::Path;
+use std::{fs, io};
+
+/// Values of `level.dat` and region listings of world.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WorldMetadata {
+    pub name: Option<String>,
+    pub seed: Option<i64>,
+    /// Game version which saved world, from `Version` tag or derived from `DataVersion`.
+    pub version: Option<String>,
+    pub data_version: Option<i32>,
+    /// Last time world was played in milliseconds since unix epoch.
+    pub last_played: Option<i64>,
+    pub spawn: Option<(i32, i32, i32)>,
+    pub dimensions: Vec<DimensionMetadata>,
+    /// Sum of sizes of all files in world folder.
+    pub size_on_disk: u64,
+}
+
+/// Region listing of dimension.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DimensionMetadata {
+    pub dimension: Dimension,
+    /// Amount of terrain region files.
+    pub region_files: usize,
+}
+
+impl WorldMetadata {
+    /// Reads summary of opened world, walks world folder to measure its size.
+    pub fn read(world: &AnvilWorld) -> Result<Self, io::Error> {
+        let level_data = world.level_data();
+        let data_version = level_data.data_version();
+
+        let version = level_data
+            .version()
+            .map(|version_info| version_info.name)
+            .or_else(|| {
+                data_version
+                    .and_then(closest_release)
+                    .map(|release| release.name.to_owned())
+            });
+
+        let mut dimensions = Vec::new();
+
+        for dimension in world.dimensions()? {
+            let region_files = world
+                .dimension(&dimension)
+                .chunk_provider()
+                .region_files()?
+                .len();
+
+            dimensions.push(DimensionMetadata {
+                dimension,
+                region_files,
+            });
+        }
+
+        Ok(WorldMetadata {
+            name: level_data.name().map(str::to_owned),
+            seed: level_data.seed(),
+            version,
+            data_version,
+            last_played: level_data.last_played(),
+            spawn: level_data.spawn(),
+            dimensions,
+            size_on_disk: folder_size(world.folder_path())?,
+        })
+    }
+}
+
+fn folder_size(folder_path: &Path) -> Result<u64, io::Error> {
+    let mut size = 0;
+
+    for entry in fs::read_dir(folder_path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+
+        if metadata.is_dir() {
+            size += folder_size(&entry.path())?;
+        } else {
+            size += metadata.len();
+        }
+    }
+
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::level::LevelData;
+    use crate::metadata::WorldMetadata;
+    use crate::relocate::copy_chunk;
+    use crate::world::{AnvilWorld, Dimension};
+    use crate::AnvilChunkProvider;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_read_world_metadata() {
+        let world_dir = TempDir::new().unwrap();
+        let mut level_data = LevelData::new();
+        level_data.set_seed(-42);
+        level_data.set_spawn(8, 70, -8);
+        level_data.data_mut().insert_i32("DataVersion", 2586);
+        level_data.save(world_dir.path()).unwrap();
+
+        let world = AnvilWorld::open(world_dir.path()).unwrap();
+        copy_chunk(
+            &AnvilChunkProvider::new("test/region"),
+            (4, 2),
+            &world.end().chunk_provider(),
+            (4, 2),
+        )
+        .unwrap();
+
+        let world_metadata = WorldMetadata::read(&world).unwrap();
+        let region_files: Vec<_> = world_metadata
+            .dimensions
+            .iter()
+            .map(|dimension_metadata| {
+                (
+                    dimension_metadata.dimension.clone(),
+                    dimension_metadata.region_files,
+                )
+            })
+            .collect();
+
+        assert_eq!(world_metadata.seed, Some(-42));
+        assert_eq!(world_metadata.spawn, Some((8, 70, -8)));
+        assert_eq!(world_metadata.version.as_deref(), Some("1.16.5"));
+        assert_eq!(
+            region_files,
+            vec![
+                (Dimension::Overworld, 0),
+                (Dimension::Nether, 0),
+                (Dimension::End, 1),
+            ]
+        );
+        assert!(world_metadata.size_on_disk > 8192);
+    }
+}
@@ -0,0 +1,314 @@
+//! Instrumentation of chunk loads, saves, header cache and file I/O.
+//!
+//! Providers report into [`Metrics`] which does nothing by default. Services embedding
+//! the crate implement it on top of their metrics library, for example Prometheus
+//! counters and histograms, or use [`CountingMetrics`] and export its snapshot.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::metrics::CountingMetrics;
+//! use anvil_region::AnvilChunkProvider;
+//! use std::sync::Arc;
+//!
+//! let metrics = Arc::new(CountingMetrics::new());
+//! let chunk_provider = AnvilChunkProvider::new("test/region").metrics(metrics.clone());
+//!
+//! chunk_provider.load_chunk(4, 2).unwrap();
+//! chunk_provider.load_chunk(4, 2).unwrap();
+//!
+//! let metrics_snapshot = metrics.snapshot();
+//! assert_eq!(metrics_snapshot.chunks_loaded, 2);
+//! assert_eq!(metrics_snapshot.cache_misses, 1);
+//! assert_eq!(metrics_snapshot.cache_hits, 1);
+//! ```
+use crate::ChunkLoadError;
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Receiver of provider events, every method does nothing by default.
+///
+/// Methods are called from threads which load and save chunks, so implementation
+/// must be cheap and must not block.
+pub trait Metrics: Send + Sync {
+    /// Chunk was loaded and decoded.
+    fn chunk_loaded(&self) {}
+
+    /// Chunk was saved.
+    fn chunk_saved(&self) {}
+
+    /// Region header was found in cache.
+    fn cache_hit(&self) {}
+
+    /// Region header was read from file.
+    fn cache_miss(&self) {}
+
+    /// Bytes of headers and compressed chunks were read from region files.
+    fn bytes_read(&self, _bytes: u64) {}
+
+    /// Bytes of compressed chunks were written to region files.
+    fn bytes_written(&self, _bytes: u64) {}
+
+    /// Chunk was decompressed and decoded in duration.
+    fn decompression_time(&self, _duration: Duration) {}
+}
+
+/// Metrics which ignore every event, used by providers by default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopMetrics;
+
+impl Metrics for NoopMetrics {}
+
+/// Metrics which count events in atomic counters.
+#[derive(Debug, Default)]
+pub struct CountingMetrics {
+    chunks_loaded: AtomicU64,
+    chunks_saved: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+    decompression_nanos: AtomicU64,
+}
+
+/// Values of counters of [`CountingMetrics`].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MetricsSnapshot {
+    pub chunks_loaded: u64,
+    pub chunks_saved: u64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    /// Total time spent decompressing and decoding chunks.
+    pub decompression_time: Duration,
+}
+
+impl CountingMetrics {
+    pub fn new() -> Self {
+        CountingMetrics::default()
+    }
+
+    /// Returns current values of counters.
+    ///
+    /// Counters are read one by one, so snapshot taken while chunks are loaded may
+    /// be off by the events which happened meanwhile.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            chunks_loaded: self.chunks_loaded.load(Ordering::Relaxed),
+            chunks_saved: self.chunks_saved.load(Ordering::Relaxed),
+            cache_hits: self.cache_hits.load(Ordering::Relaxed),
+            cache_misses: self.cache_misses.load(Ordering::Relaxed),
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+            decompression_time: Duration::from_nanos(
+                self.decompression_nanos.load(Ordering::Relaxed),
+            ),
+        }
+    }
+}
+
+impl Metrics for CountingMetrics {
+    fn chunk_loaded(&self) {
+        self.chunks_loaded.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn chunk_saved(&self) {
+        self.chunks_saved.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn cache_hit(&self) {
+        self.cache_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn cache_miss(&self) {
+        self.cache_misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn bytes_read(&self, bytes: u64) {
+        self.bytes_read.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    fn bytes_written(&self, bytes: u64) {
+        self.bytes_written.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    fn decompression_time(&self, duration: Duration) {
+        let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+        self.decompression_nanos.fetch_add(nanos, Ordering::Relaxed);
+    }
+}
+
+/// Activity of provider since its creation, returned by `stats` of providers.
+///
+/// Counters are kept regardless of metrics set on provider, so two snapshots can be
+/// compared to measure effect of cache or batch tuning.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProviderActivity {
+    pub chunks_loaded: u64,
+    pub chunks_saved: u64,
+    /// Loads of chunks or regions which don't exist.
+    pub chunks_missing: u64,
+    /// Loads which failed for other reasons than missing chunk.
+    pub load_errors: u64,
+    pub save_errors: u64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    /// Region headers currently held by header cache.
+    pub cached_headers: usize,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+}
+
+/// Counters of provider activity which forward every event to metrics of provider.
+pub(crate) struct ProviderCounters {
+    metrics: Arc<dyn Metrics>,
+    counting_metrics: CountingMetrics,
+    chunks_missing: AtomicU64,
+    load_errors: AtomicU64,
+    save_errors: AtomicU64,
+}
+
+impl ProviderCounters {
+    pub(crate) fn new(metrics: Arc<dyn Metrics>) -> Self {
+        ProviderCounters {
+            metrics,
+            counting_metrics: CountingMetrics::new(),
+            chunks_missing: AtomicU64::new(0),
+            load_errors: AtomicU64::new(0),
+            save_errors: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn load_failed(&self, chunk_load_error: &ChunkLoadError) {
+        match chunk_load_error {
+            ChunkLoadError::RegionNotFound { .. } | ChunkLoadError::ChunkNotFound { .. } => {
+                self.chunks_missing.fetch_add(1, Ordering::Relaxed)
+            }
+            _ => self.load_errors.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+
+    pub(crate) fn save_failed(&self) {
+        self.save_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn activity(&self, cached_headers: usize) -> ProviderActivity {
+        let metrics_snapshot = self.counting_metrics.snapshot();
+
+        ProviderActivity {
+            chunks_loaded: metrics_snapshot.chunks_loaded,
+            chunks_saved: metrics_snapshot.chunks_saved,
+            chunks_missing: self.chunks_missing.load(Ordering::Relaxed),
+            load_errors: self.load_errors.load(Ordering::Relaxed),
+            save_errors: self.save_errors.load(Ordering::Relaxed),
+            cache_hits: metrics_snapshot.cache_hits,
+            cache_misses: metrics_snapshot.cache_misses,
+            cached_headers,
+            bytes_read: metrics_snapshot.bytes_read,
+            bytes_written: metrics_snapshot.bytes_written,
+        }
+    }
+}
+
+impl Metrics for ProviderCounters {
+    fn chunk_loaded(&self) {
+        self.counting_metrics.chunk_loaded();
+        self.metrics.chunk_loaded();
+    }
+
+    fn chunk_saved(&self) {
+        self.counting_metrics.chunk_saved();
+        self.metrics.chunk_saved();
+    }
+
+    fn cache_hit(&self) {
+        self.counting_metrics.cache_hit();
+        self.metrics.cache_hit();
+    }
+
+    fn cache_miss(&self) {
+        self.counting_metrics.cache_miss();
+        self.metrics.cache_miss();
+    }
+
+    fn bytes_read(&self, bytes: u64) {
+        self.counting_metrics.bytes_read(bytes);
+        self.metrics.bytes_read(bytes);
+    }
+
+    fn bytes_written(&self, bytes: u64) {
+        self.counting_metrics.bytes_written(bytes);
+        self.metrics.bytes_written(bytes);
+    }
+
+    fn decompression_time(&self, duration: Duration) {
+        self.metrics.decompression_time(duration);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metrics::CountingMetrics;
+    use crate::AnvilChunkProvider;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_counting_metrics() {
+        let temp_dir = TempDir::new().unwrap();
+        let metrics = Arc::new(CountingMetrics::new());
+
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk_provider =
+            AnvilChunkProvider::new(temp_dir.path().to_str().unwrap()).metrics(metrics.clone());
+
+        let chunk_compound_tag = fixture_chunk_provider.load_chunk(4, 2).unwrap();
+        chunk_provider.save_chunk(4, 2, chunk_compound_tag).unwrap();
+
+        let metrics_snapshot = metrics.snapshot();
+        assert_eq!(metrics_snapshot.chunks_saved, 1);
+        assert!(metrics_snapshot.bytes_written > 0);
+        assert_eq!(metrics_snapshot.chunks_loaded, 0);
+
+        chunk_provider.load_chunk(4, 2).unwrap();
+        assert!(chunk_provider.load_chunk(5, 2).is_err());
+
+        let metrics_snapshot = metrics.snapshot();
+        assert_eq!(metrics_snapshot.chunks_loaded, 1);
+        assert_eq!(metrics_snapshot.cache_misses, 1);
+        assert_eq!(metrics_snapshot.cache_hits, 1);
+        assert!(metrics_snapshot.bytes_read > metrics_snapshot.bytes_written);
+        assert!(metrics_snapshot.decompression_time.as_nanos() > 0);
+    }
+
+    #[test]
+    fn test_provider_stats() {
+        let temp_dir = TempDir::new().unwrap();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+
+        let chunk_compound_tag = fixture_chunk_provider.load_chunk(4, 2).unwrap();
+        chunk_provider.save_chunk(4, 2, chunk_compound_tag).unwrap();
+        let saved_stats = chunk_provider.stats();
+
+        chunk_provider.load_chunk(4, 2).unwrap();
+        assert!(chunk_provider.load_chunk(5, 2).is_err());
+        assert!(chunk_provider.load_chunk(100, 2).is_err());
+        let loaded_stats = chunk_provider.stats();
+
+        assert_eq!(saved_stats.chunks_saved, 1);
+        assert_eq!(saved_stats.chunks_loaded, 0);
+        assert_eq!(saved_stats.cached_headers, 0);
+        assert_eq!(loaded_stats.chunks_loaded, 1);
+        assert_eq!(loaded_stats.chunks_missing, 2);
+        assert_eq!(loaded_stats.load_errors, 0);
+        assert_eq!(loaded_stats.cached_headers, 1);
+        assert!(loaded_stats.bytes_read > saved_stats.bytes_read);
+        assert_eq!(loaded_stats.bytes_written, saved_stats.bytes_written);
+    }
+}
@@ -0,0 +1,348 @@
+//! Chunk provider with injected failures for tests of applications.
+//!
+//! [`MockChunkProvider`] keeps chunks in memory and fails operations on chunks which
+//! were programmed to fail, always or only a few times, delays every operation by
+//! latency and returns corrupted tags, so error handling of code written against
+//! [`ChunkProvider`] can be tested without damaged region files.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::mock::{Fault, MockChunkProvider};
+//! use anvil_region::provider::ChunkProvider;
+//! use anvil_region::ChunkLoadError;
+//! use nbt::CompoundTag;
+//! use std::io;
+//!
+//! let chunk_provider = MockChunkProvider::new();
+//! chunk_provider.save_chunk(4, 2, CompoundTag::new()).unwrap();
+//!
+//! // The first load fails as if disk were busy, the second one succeeds.
+//! let fault = Fault::IoError { kind: io::ErrorKind::Interrupted };
+//! chunk_provider.fail_load_times(4, 2, fault, 1);
+//!
+//! assert!(matches!(chunk_provider.load_chunk(4, 2), Err(ChunkLoadError::ReadError { .. })));
+//! assert!(chunk_provider.load_chunk(4, 2).is_ok());
+//! ```
+use crate::hash::CoordinateHashMap;
+use crate::provider::ChunkProvider;
+use crate::{AnvilChunkProvider, ChunkLoadError, ChunkSaveError};
+use nbt::decode::TagDecodeError;
+use nbt::{CompoundTag, Tag};
+use std::io;
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+/// Compression scheme id reported by loads with [`Fault::UnsupportedCompressionScheme`].
+pub const MOCK_COMPRESSION_SCHEME: u8 = 0x7F;
+
+/// Failure of load of chunk.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Fault {
+    /// Load fails with [`ChunkLoadError::ReadError`] of kind.
+    IoError { kind: io::ErrorKind },
+    /// Load fails with [`ChunkLoadError::TagDecodeError`] as if data were damaged.
+    TagDecodeError,
+    /// Load fails with [`ChunkLoadError::UnsupportedCompressionScheme`].
+    UnsupportedCompressionScheme,
+    /// Load succeeds, but every tag of root compound of chunk is replaced with string,
+    /// so reading any field fails with wrong type.
+    CorruptedTag,
+}
+
+/// Fault with amount of operations it's left for, none for every operation.
+#[derive(Debug, Clone)]
+struct InjectedFault<T> {
+    fault: T,
+    remaining: Option<usize>,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    chunks: CoordinateHashMap<(i32, i32), CompoundTag>,
+    load_faults: CoordinateHashMap<(i32, i32), InjectedFault<Fault>>,
+    save_faults: CoordinateHashMap<(i32, i32), InjectedFault<io::ErrorKind>>,
+}
+
+/// In memory chunk provider which fails operations on programmed coordinates.
+#[derive(Debug, Default)]
+pub struct MockChunkProvider {
+    state: Mutex<MockState>,
+    latency: Duration,
+}
+
+impl MockChunkProvider {
+    pub fn new() -> Self {
+        MockChunkProvider::default()
+    }
+
+    /// Creates provider with copies of all chunks of region folder.
+    pub fn from_provider(chunk_provider: &AnvilChunkProvider) -> Result<Self, ChunkLoadError> {
+        let mock_chunk_provider = MockChunkProvider::new();
+
+        for (chunk_x, chunk_z) in chunk_provider.chunk_positions()? {
+            let chunk_compound_tag = chunk_provider.load_chunk(chunk_x, chunk_z)?;
+            mock_chunk_provider.insert_chunk(chunk_x, chunk_z, chunk_compound_tag);
+        }
+
+        Ok(mock_chunk_provider)
+    }
+
+    /// Delays every operation by duration, like storage on slow disk or network.
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Stores chunk without latency and injected failures.
+    pub fn insert_chunk(&self, chunk_x: i32, chunk_z: i32, chunk_compound_tag: CompoundTag) {
+        self.state()
+            .chunks
+            .insert((chunk_x, chunk_z), chunk_compound_tag);
+    }
+
+    /// Makes every load of chunk fail with fault.
+    pub fn fail_load(&self, chunk_x: i32, chunk_z: i32, fault: Fault) {
+        self.state().load_faults.insert(
+            (chunk_x, chunk_z),
+            InjectedFault {
+                fault,
+                remaining: None,
+            },
+        );
+    }
+
+    /// Makes the next loads of chunk fail with fault, following loads succeed.
+    pub fn fail_load_times(&self, chunk_x: i32, chunk_z: i32, fault: Fault, times: usize) {
+        self.state().load_faults.insert(
+            (chunk_x, chunk_z),
+            InjectedFault {
+                fault,
+                remaining: Some(times),
+            },
+        );
+    }
+
+    /// Makes every save and delete of chunk fail with I/O error of kind.
+    pub fn fail_save(&self, chunk_x: i32, chunk_z: i32, kind: io::ErrorKind) {
+        self.state().save_faults.insert(
+            (chunk_x, chunk_z),
+            InjectedFault {
+                fault: kind,
+                remaining: None,
+            },
+        );
+    }
+
+    /// Makes the next saves and deletes of chunk fail with I/O error of kind.
+    pub fn fail_save_times(&self, chunk_x: i32, chunk_z: i32, kind: io::ErrorKind, times: usize) {
+        self.state().save_faults.insert(
+            (chunk_x, chunk_z),
+            InjectedFault {
+                fault: kind,
+                remaining: Some(times),
+            },
+        );
+    }
+
+    /// Removes all injected failures, stored chunks are kept.
+    pub fn clear_faults(&self) {
+        let mut state = self.state();
+        state.load_faults.clear();
+        state.save_faults.clear();
+    }
+
+    fn state(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(|error| error.into_inner())
+    }
+
+    fn delay(&self) {
+        if self.latency > Duration::from_secs(0) {
+            thread::sleep(self.latency);
+        }
+    }
+
+    /// Takes fault of save or delete of chunk if it's injected.
+    fn save_fault(&self, chunk_x: i32, chunk_z: i32) -> Result<(), ChunkSaveError> {
+        match take_fault(&mut self.state().save_faults, (chunk_x, chunk_z)) {
+            Some(kind) => Err(ChunkSaveError::WriteError {
+                io_error: io::Error::new(kind, "injected failure"),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Takes fault at position, removing it after its last operation.
+fn take_fault<T: Clone>(
+    faults: &mut CoordinateHashMap<(i32, i32), InjectedFault<T>>,
+    position: (i32, i32),
+) -> Option<T> {
+    let injected_fault = faults.get_mut(&position)?;
+    let fault = injected_fault.fault.clone();
+
+    if let Some(remaining) = &mut injected_fault.remaining {
+        // Fault injected for zero operations never fails.
+        if *remaining == 0 {
+            faults.remove(&position);
+            return None;
+        }
+
+        *remaining -= 1;
+
+        if *remaining == 0 {
+            faults.remove(&position);
+        }
+    }
+
+    Some(fault)
+}
+
+/// Replaces every tag of root compound with string.
+fn corrupt(chunk_compound_tag: &CompoundTag) -> CompoundTag {
+    let mut corrupted_compound_tag = CompoundTag::new();
+
+    for (name, _) in chunk_compound_tag.iter() {
+        corrupted_compound_tag.insert(name, Tag::String("corrupted".to_owned()));
+    }
+
+    corrupted_compound_tag
+}
+
+impl ChunkProvider for MockChunkProvider {
+    type LoadError = ChunkLoadError;
+    type SaveError = ChunkSaveError;
+
+    fn load_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<CompoundTag, ChunkLoadError> {
+        self.delay();
+
+        let mut state = self.state();
+        let fault = take_fault(&mut state.load_faults, (chunk_x, chunk_z));
+        let empty_compound_tag = CompoundTag::new();
+
+        let chunk_compound_tag = match state.chunks.get(&(chunk_x, chunk_z)) {
+            Some(chunk_compound_tag) => chunk_compound_tag,
+            None if fault.is_none() => {
+                return Err(ChunkLoadError::ChunkNotFound { chunk_x, chunk_z })
+            }
+            None => &empty_compound_tag,
+        };
+
+        match fault {
+            None => Ok(chunk_compound_tag.clone()),
+            Some(Fault::IoError { kind }) => Err(ChunkLoadError::ReadError {
+                io_error: io::Error::new(kind, "injected failure"),
+            }),
+            Some(Fault::TagDecodeError) => Err(ChunkLoadError::TagDecodeError {
+                tag_decode_error: TagDecodeError::UnknownTagType { tag_type_id: 0xFF },
+            }),
+            Some(Fault::UnsupportedCompressionScheme) => {
+                Err(ChunkLoadError::UnsupportedCompressionScheme {
+                    compression_scheme: MOCK_COMPRESSION_SCHEME,
+                })
+            }
+            Some(Fault::CorruptedTag) => Ok(corrupt(chunk_compound_tag)),
+        }
+    }
+
+    fn save_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        chunk_compound_tag: CompoundTag,
+    ) -> Result<(), ChunkSaveError> {
+        self.delay();
+        self.save_fault(chunk_x, chunk_z)?;
+        self.insert_chunk(chunk_x, chunk_z, chunk_compound_tag);
+
+        Ok(())
+    }
+
+    fn delete_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<bool, ChunkSaveError> {
+        self.delay();
+        self.save_fault(chunk_x, chunk_z)?;
+
+        Ok(self.state().chunks.remove(&(chunk_x, chunk_z)).is_some())
+    }
+
+    fn chunk_positions(&self) -> Result<Vec<(i32, i32)>, io::Error> {
+        self.delay();
+
+        let mut chunk_positions: Vec<_> = self.state().chunks.keys().copied().collect();
+        chunk_positions.sort_unstable();
+
+        Ok(chunk_positions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mock::{Fault, MockChunkProvider, MOCK_COMPRESSION_SCHEME};
+    use crate::provider::ChunkProvider;
+    use crate::{AnvilChunkProvider, ChunkLoadError, ChunkSaveError};
+    use nbt::CompoundTag;
+    use std::io;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_load_faults() {
+        let chunk_provider =
+            MockChunkProvider::from_provider(&AnvilChunkProvider::new("test/region")).unwrap();
+
+        chunk_provider.fail_load(4, 2, Fault::TagDecodeError);
+        chunk_provider.fail_load_times(5, 2, Fault::UnsupportedCompressionScheme, 2);
+        chunk_provider.fail_load(6, 2, Fault::CorruptedTag);
+
+        for _ in 0..2 {
+            assert!(matches!(
+                chunk_provider.load_chunk(4, 2),
+                Err(ChunkLoadError::TagDecodeError { .. })
+            ));
+            assert!(matches!(
+                chunk_provider.load_chunk(5, 2),
+                Err(ChunkLoadError::UnsupportedCompressionScheme {
+                    compression_scheme: MOCK_COMPRESSION_SCHEME
+                })
+            ));
+        }
+
+        assert!(chunk_provider.load_chunk(5, 2).is_ok());
+
+        let corrupted_compound_tag = chunk_provider.load_chunk(6, 2).unwrap();
+        assert!(corrupted_compound_tag.get_compound_tag("Level").is_err());
+        assert_eq!(
+            corrupted_compound_tag.get_str("Level").unwrap(),
+            "corrupted"
+        );
+
+        chunk_provider.clear_faults();
+        assert!(chunk_provider.load_chunk(4, 2).is_ok());
+        assert!(matches!(
+            chunk_provider.load_chunk(100, 100),
+            Err(ChunkLoadError::ChunkNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_save_faults_and_latency() {
+        let chunk_provider = MockChunkProvider::new().latency(Duration::from_millis(5));
+        chunk_provider.fail_save_times(0, 0, io::ErrorKind::PermissionDenied, 1);
+
+        let started = Instant::now();
+
+        match chunk_provider.save_chunk(0, 0, CompoundTag::new()) {
+            Err(ChunkSaveError::WriteError { io_error }) => {
+                assert_eq!(io_error.kind(), io::ErrorKind::PermissionDenied)
+            }
+            result => panic!("Expected `WriteError` but got `{:?}`", result),
+        }
+
+        chunk_provider.save_chunk(0, 0, CompoundTag::new()).unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(10));
+
+        chunk_provider.fail_save(0, 0, io::ErrorKind::Other);
+        assert!(chunk_provider.delete_chunk(0, 0).is_err());
+        assert_eq!(chunk_provider.chunk_positions().unwrap(), vec![(0, 0)]);
+    }
+}
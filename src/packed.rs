@@ -0,0 +1,165 @@
+//! Packed long arrays used to store block states, biomes and heightmaps.
+//!
+//! Before 1.16 values could span across two longs, since 1.16 each long
+//! contains only whole values and remaining high bits are unused.
+
+/// Returns amount of bits required to address palette of specified length.
+///
+/// Single entry palette doesn't require any bits.
+pub(crate) fn palette_bits(palette_length: usize, minimum_bits: u32) -> u32 {
+    let bits = usize::BITS - palette_length.saturating_sub(1).leading_zeros();
+
+    bits.max(minimum_bits)
+}
+
+/// Returns amount of longs required to pack values.
+pub(crate) fn packed_length(bits: u32, length: usize, spanning: bool) -> usize {
+    if bits == 0 {
+        return 0;
+    }
+
+    if spanning {
+        (length * bits as usize).div_ceil(64)
+    } else {
+        length.div_ceil((64 / bits) as usize)
+    }
+}
+
+/// Unpacks values from long array.
+///
+/// Missing longs are treated as zeroes.
+pub(crate) fn unpack(data: &[i64], bits: u32, length: usize, spanning: bool) -> Vec<u16> {
+    unpacked(data, bits, length, spanning).collect()
+}
+
+/// Returns iterator over values packed into long array without allocating them.
+///
+/// Missing longs are treated as zeroes.
+pub(crate) fn unpacked(
+    data: &[i64],
+    bits: u32,
+    length: usize,
+    spanning: bool,
+) -> impl Iterator<Item = u16> + '_ {
+    let mask = if bits == 0 { 0 } else { (1u64 << bits) - 1 };
+    let long = move |index: usize| data.get(index).copied().unwrap_or(0) as u64;
+
+    (0..length).map(move |index| {
+        if bits == 0 {
+            return 0;
+        }
+
+        let packed = if spanning {
+            let bit_index = index * bits as usize;
+            let long_index = bit_index / 64;
+            let offset = (bit_index % 64) as u32;
+            let mut packed = long(long_index) >> offset;
+
+            if offset + bits > 64 {
+                packed |= long(long_index + 1) << (64 - offset);
+            }
+
+            packed
+        } else {
+            let values_per_long = (64 / bits) as usize;
+            let offset = (index % values_per_long) as u32 * bits;
+
+            long(index / values_per_long) >> offset
+        };
+
+        (packed & mask) as u16
+    })
+}
+
+/// Packs values into long array.
+pub(crate) fn pack(values: &[u16], bits: u32, spanning: bool) -> Vec<i64> {
+    let mut data = vec![0u64; packed_length(bits, values.len(), spanning)];
+
+    if bits == 0 {
+        return Vec::new();
+    }
+
+    let mask = (1u64 << bits) - 1;
+
+    for (index, value) in values.iter().enumerate() {
+        let value = *value as u64 & mask;
+
+        if spanning {
+            let bit_index = index * bits as usize;
+            let long_index = bit_index / 64;
+            let offset = (bit_index % 64) as u32;
+
+            data[long_index] |= value << offset;
+
+            if offset + bits > 64 {
+                data[long_index + 1] |= value >> (64 - offset);
+            }
+        } else {
+            let values_per_long = (64 / bits) as usize;
+            let offset = (index % values_per_long) as u32 * bits;
+
+            data[index / values_per_long] |= value << offset;
+        }
+    }
+
+    data.into_iter().map(|long| long as i64).collect()
+}
+
+/// Packs chunk position into long as stored in forced chunks and structure references.
+pub(crate) fn pack_chunk_position(chunk_x: i32, chunk_z: i32) -> i64 {
+    (chunk_z as i64) << 32 | chunk_x as u32 as i64
+}
+
+/// Unpacks chunk position from long.
+pub(crate) fn unpack_chunk_position(chunk_position: i64) -> (i32, i32) {
+    (chunk_position as i32, (chunk_position >> 32) as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::packed::{pack, packed_length, palette_bits, unpack};
+
+    #[test]
+    fn test_palette_bits() {
+        assert_eq!(palette_bits(1, 0), 0);
+        assert_eq!(palette_bits(2, 0), 1);
+        assert_eq!(palette_bits(2, 4), 4);
+        assert_eq!(palette_bits(16, 4), 4);
+        assert_eq!(palette_bits(17, 4), 5);
+    }
+
+    #[test]
+    fn test_packed_length() {
+        assert_eq!(packed_length(4, 4096, true), 256);
+        assert_eq!(packed_length(5, 4096, true), 320);
+        assert_eq!(packed_length(5, 4096, false), 342);
+        assert_eq!(packed_length(9, 256, false), 37);
+        assert_eq!(packed_length(0, 64, false), 0);
+    }
+
+    #[test]
+    fn test_pack_unpack_spanning() {
+        let values: Vec<u16> = (0..4096).map(|i| (i % 31) as u16).collect();
+        let data = pack(&values, 5, true);
+
+        assert_eq!(data.len(), 320);
+        assert_eq!(unpack(&data, 5, 4096, true), values);
+    }
+
+    #[test]
+    fn test_pack_unpack_not_spanning() {
+        let values: Vec<u16> = (0..256).map(|i| (i * 3 % 300) as u16).collect();
+        let data = pack(&values, 9, false);
+
+        assert_eq!(data.len(), 37);
+        assert_eq!(unpack(&data, 9, 256, false), values);
+    }
+
+    #[test]
+    fn test_unpack_fixture_block_states() {
+        // 0x1111111111111111 is a 4 bits spanning array saying that all blocks use palette index 1.
+        let data = vec![1229782938247303441i64; 256];
+
+        assert!(unpack(&data, 4, 4096, true).iter().all(|value| *value == 1));
+    }
+}
@@ -0,0 +1,67 @@
+//! How strictly chunk provider treats irregular region files.
+//!
+//! Region files written by old game versions and third-party tools often deviate from
+//! format in ways which don't lose data, like zero padding after compressed data or
+//! declared length which is a little longer than sectors of chunk. [`ParseMode::Strict`]
+//! rejects such chunks with `MalformedChunk` error, [`ParseMode::Lenient`] reads what
+//! can be read and records [`ParseWarning`] which can be taken from provider.
+//!
+//! Providers are lenient unless strict mode is set, provider created by
+//! [`AnvilChunkProvider::from_untrusted`] is strict.
+//!
+//! [`AnvilChunkProvider::from_untrusted`]: crate::AnvilChunkProvider::from_untrusted
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::parse::ParseMode;
+//! use anvil_region::AnvilChunkProvider;
+//!
+//! let chunk_provider = AnvilChunkProvider::new("test/region").parse_mode(ParseMode::Strict);
+//!
+//! assert!(chunk_provider.load_chunk(4, 2).is_ok());
+//! assert!(chunk_provider.take_parse_warnings().is_empty());
+//! ```
+
+/// Treatment of recoverable irregularities of chunk data.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ParseMode {
+    /// Chunk with any irregularity fails to load.
+    Strict,
+    /// Recoverable irregularities are recorded as warnings and chunk is loaded.
+    #[default]
+    Lenient,
+}
+
+/// Irregularity of chunk data.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ParseIssue {
+    /// Region header places chunk inside of header.
+    SectorsOverlapHeader { sector_index: u32 },
+    /// Declared length is longer than sectors of chunk, data is read up to the end of
+    /// sectors.
+    LengthExceedsSectors { length: u32, sectors_length: u32 },
+    /// Region file ends before declared length, available data is read.
+    TruncatedData { length: u32, available_length: u32 },
+    /// Bytes follow the end of compressed data, usually zero padding.
+    TrailingCompressedData { length: usize },
+    /// Bytes follow root tag in decompressed data.
+    TrailingTagData { length: usize },
+    /// Position stored in chunk differs from position of its slot in region header,
+    /// only checked if enabled.
+    PositionMismatch {
+        stored_chunk_x: i32,
+        stored_chunk_z: i32,
+    },
+}
+
+/// Irregularity which was tolerated while loading chunk in lenient mode.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParseWarning {
+    pub chunk_x: i32,
+    pub chunk_z: i32,
+    pub parse_issue: ParseIssue,
+}
@@ -0,0 +1,398 @@
+//! Access to deeply nested tags by path.
+//!
+//! Path consists of compound tag names separated by dots, list elements are selected
+//! by index in brackets. Names containing dots or brackets can be quoted.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::path::{get_path, set_path};
+//! use anvil_region::snbt;
+//! use nbt::Tag;
+//!
+//! let mut compound_tag = snbt::from_str(r#"{Level: {Sections: [{Y: 0b}, {Y: 1b}]}}"#).unwrap();
+//!
+//! set_path(&mut compound_tag, "Level.Sections[1].Y", Tag::Byte(5)).unwrap();
+//!
+//! match get_path(&compound_tag, "Level.Sections[1].Y").unwrap() {
+//!     Tag::Byte(y) => assert_eq!(*y, 5),
+//!     tag => panic!("Unexpected tag {:?}", tag),
+//! }
+//! ```
+use crate::tag::{get_tag, get_tag_mut, set_tag};
+use nbt::{CompoundTag, Tag};
+
+/// Possible errors while accessing tags by path.
+#[derive(Debug)]
+pub enum PathError {
+    /// Path can't be parsed, position is a byte offset in path.
+    InvalidSyntax { position: usize },
+    /// Compound doesn't contain tag with name.
+    TagNotFound { name: String },
+    /// List doesn't contain element with index.
+    IndexOutOfBounds { index: usize, length: usize },
+    /// Name is used on tag which is not a compound.
+    NotCompound { name: String },
+    /// Index is used on tag which is not a list.
+    NotList { index: usize },
+}
+
+/// Part of path which selects nested tag.
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Segment {
+    Name(String),
+    Index(usize),
+}
+
+/// Returns tag at path.
+pub fn get_path<'a>(compound_tag: &'a CompoundTag, path: &str) -> Result<&'a Tag, PathError> {
+    let segments = parse_path(path)?;
+    let (first_segment, segments) = segments.split_first().unwrap();
+
+    let mut tag = select_name(compound_tag, first_segment)?;
+
+    for segment in segments {
+        tag = match (segment, tag) {
+            (Segment::Name(_), Tag::Compound(compound_tag)) => select_name(compound_tag, segment)?,
+            (Segment::Name(name), _) => return Err(PathError::NotCompound { name: name.clone() }),
+            (Segment::Index(index), Tag::List(tags)) => select_index(tags, *index)?,
+            (Segment::Index(index), _) => return Err(PathError::NotList { index: *index }),
+        };
+    }
+
+    Ok(tag)
+}
+
+/// Returns mutable tag at path.
+pub fn get_path_mut<'a>(
+    compound_tag: &'a mut CompoundTag,
+    path: &str,
+) -> Result<&'a mut Tag, PathError> {
+    let segments = parse_path(path)?;
+    let (first_segment, segments) = segments.split_first().unwrap();
+
+    let mut tag = select_name_mut(compound_tag, first_segment)?;
+
+    for segment in segments {
+        tag = match (segment, tag) {
+            (Segment::Name(_), Tag::Compound(compound_tag)) => {
+                select_name_mut(compound_tag, segment)?
+            }
+            (Segment::Name(name), _) => return Err(PathError::NotCompound { name: name.clone() }),
+            (Segment::Index(index), Tag::List(tags)) => select_index_mut(tags, *index)?,
+            (Segment::Index(index), _) => return Err(PathError::NotList { index: *index }),
+        };
+    }
+
+    Ok(tag)
+}
+
+/// Sets tag at path.
+///
+/// Tag which path points to may be missing if its parent is a compound, then tag is
+/// added. Existing tags are replaced in place keeping order of compound tags.
+pub fn set_path(
+    compound_tag: &mut CompoundTag,
+    path: &str,
+    tag: impl Into<Tag>,
+) -> Result<(), PathError> {
+    let mut segments = parse_path(path)?;
+    let last_segment = segments.pop().unwrap();
+
+    let parent_tag = match segments.last() {
+        Some(_) => {
+            let parent_path_length = parent_path_length(path);
+            Some(get_path_mut(compound_tag, &path[..parent_path_length])?)
+        }
+        None => None,
+    };
+
+    match (last_segment, parent_tag) {
+        (Segment::Name(name), None) => set_tag(compound_tag, &name, tag.into()),
+        (Segment::Name(name), Some(Tag::Compound(compound_tag))) => {
+            set_tag(compound_tag, &name, tag.into())
+        }
+        (Segment::Name(name), Some(_)) => return Err(PathError::NotCompound { name }),
+        (Segment::Index(index), Some(Tag::List(tags))) => {
+            *select_index_mut(tags, index)? = tag.into()
+        }
+        (Segment::Index(index), _) => return Err(PathError::NotList { index }),
+    }
+
+    Ok(())
+}
+
+fn select_name<'a>(compound_tag: &'a CompoundTag, segment: &Segment) -> Result<&'a Tag, PathError> {
+    match segment {
+        Segment::Name(name) => {
+            get_tag(compound_tag, name).ok_or_else(|| PathError::TagNotFound { name: name.clone() })
+        }
+        Segment::Index(index) => Err(PathError::NotList { index: *index }),
+    }
+}
+
+fn select_name_mut<'a>(
+    compound_tag: &'a mut CompoundTag,
+    segment: &Segment,
+) -> Result<&'a mut Tag, PathError> {
+    match segment {
+        Segment::Name(name) => get_tag_mut(compound_tag, name)
+            .ok_or_else(|| PathError::TagNotFound { name: name.clone() }),
+        Segment::Index(index) => Err(PathError::NotList { index: *index }),
+    }
+}
+
+fn select_index(tags: &[Tag], index: usize) -> Result<&Tag, PathError> {
+    let length = tags.len();
+
+    tags.get(index)
+        .ok_or(PathError::IndexOutOfBounds { index, length })
+}
+
+fn select_index_mut(tags: &mut [Tag], index: usize) -> Result<&mut Tag, PathError> {
+    let length = tags.len();
+
+    tags.get_mut(index)
+        .ok_or(PathError::IndexOutOfBounds { index, length })
+}
+
+/// Appends name to path, quoting it when needed.
+pub(crate) fn join_name(path: &str, name: &str) -> String {
+    let is_plain = !name.is_empty() && !name.contains(['.', '[', '"']);
+
+    let name = if is_plain {
+        name.to_owned()
+    } else {
+        format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+    };
+
+    if path.is_empty() {
+        name
+    } else {
+        format!("{}.{}", path, name)
+    }
+}
+
+/// Appends list index to path.
+pub(crate) fn join_index(path: &str, index: usize) -> String {
+    format!("{}[{}]", path, index)
+}
+
+/// Splits path into segments, path always starts with a name.
+fn parse_path(path: &str) -> Result<Vec<Segment>, PathError> {
+    let mut segments = Vec::new();
+    let mut characters = path.char_indices().peekable();
+
+    loop {
+        let name = match characters.peek() {
+            Some((_, '"')) => {
+                characters.next();
+                let mut name = String::new();
+
+                loop {
+                    match characters.next() {
+                        Some((_, '\\')) => match characters.next() {
+                            Some((_, character)) => name.push(character),
+                            None => {
+                                return Err(PathError::InvalidSyntax {
+                                    position: path.len(),
+                                })
+                            }
+                        },
+                        Some((_, '"')) => break,
+                        Some((_, character)) => name.push(character),
+                        None => {
+                            return Err(PathError::InvalidSyntax {
+                                position: path.len(),
+                            })
+                        }
+                    }
+                }
+
+                name
+            }
+            _ => {
+                let mut name = String::new();
+
+                while let Some((_, character)) = characters.peek() {
+                    if *character == '.' || *character == '[' {
+                        break;
+                    }
+
+                    name.push(*character);
+                    characters.next();
+                }
+
+                name
+            }
+        };
+
+        if name.is_empty() {
+            let position = characters
+                .peek()
+                .map_or(path.len(), |(position, _)| *position);
+            return Err(PathError::InvalidSyntax { position });
+        }
+
+        segments.push(Segment::Name(name));
+
+        while let Some((position, '[')) = characters.peek().cloned() {
+            characters.next();
+            let mut index = String::new();
+            let mut closed = false;
+
+            for (_, character) in &mut characters {
+                if character == ']' {
+                    closed = true;
+                    break;
+                }
+
+                index.push(character);
+            }
+
+            let index = match index.parse() {
+                Ok(index) if closed => index,
+                _ => return Err(PathError::InvalidSyntax { position }),
+            };
+
+            segments.push(Segment::Index(index));
+        }
+
+        match characters.next() {
+            Some((_, '.')) => continue,
+            Some((position, _)) => return Err(PathError::InvalidSyntax { position }),
+            None => break,
+        }
+    }
+
+    Ok(segments)
+}
+
+/// Returns length of path without its last segment.
+fn parent_path_length(path: &str) -> usize {
+    let mut parent_path_length = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+
+    for (position, character) in path.char_indices() {
+        match character {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '.' | '[' if !quoted => parent_path_length = position,
+            _ => {}
+        }
+    }
+
+    parent_path_length
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::path::{
+        get_path, get_path_mut, join_index, join_name, parse_path, set_path, PathError, Segment,
+    };
+    use crate::snbt;
+    use nbt::Tag;
+
+    #[test]
+    fn test_parse_path() {
+        assert_eq!(
+            parse_path(r#"sections[3].block_states."a.b"[0][1]"#).unwrap(),
+            vec![
+                Segment::Name("sections".to_owned()),
+                Segment::Index(3),
+                Segment::Name("block_states".to_owned()),
+                Segment::Name("a.b".to_owned()),
+                Segment::Index(0),
+                Segment::Index(1),
+            ]
+        );
+
+        match parse_path("a..b") {
+            Err(PathError::InvalidSyntax { position: 2 }) => {}
+            result => panic!("Expected `InvalidSyntax` but got `{:?}`", result),
+        }
+
+        match parse_path("a[x]") {
+            Err(PathError::InvalidSyntax { position: 1 }) => {}
+            result => panic!("Expected `InvalidSyntax` but got `{:?}`", result),
+        }
+    }
+
+    #[test]
+    fn test_join_name() {
+        let path = join_index(&join_name("a", "b.c"), 2);
+
+        assert_eq!(join_name("", "a"), "a");
+        assert_eq!(path, r#"a."b.c"[2]"#);
+        assert_eq!(
+            parse_path(&path).unwrap(),
+            vec![
+                Segment::Name("a".to_owned()),
+                Segment::Name("b.c".to_owned()),
+                Segment::Index(2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_path() {
+        let compound_tag = snbt::from_str(r#"{sections: [{Y: 0b}, {Y: 1b}]}"#).unwrap();
+
+        match get_path(&compound_tag, "sections[1].Y") {
+            Ok(Tag::Byte(1)) => {}
+            result => panic!("Expected `Byte` but got `{:?}`", result),
+        }
+
+        match get_path(&compound_tag, "sections[2].Y") {
+            Err(PathError::IndexOutOfBounds {
+                index: 2,
+                length: 2,
+            }) => {}
+            result => panic!("Expected `IndexOutOfBounds` but got `{:?}`", result),
+        }
+
+        match get_path(&compound_tag, "sections.Y") {
+            Err(PathError::NotCompound { name }) => assert_eq!(name, "Y"),
+            result => panic!("Expected `NotCompound` but got `{:?}`", result),
+        }
+
+        match get_path(&compound_tag, "sections[0].X") {
+            Err(PathError::TagNotFound { name }) => assert_eq!(name, "X"),
+            result => panic!("Expected `TagNotFound` but got `{:?}`", result),
+        }
+    }
+
+    #[test]
+    fn test_set_path() {
+        let mut compound_tag = snbt::from_str(r#"{a: {b: 1, c: [1, 2]}}"#).unwrap();
+
+        set_path(&mut compound_tag, "a.b", 5).unwrap();
+        set_path(&mut compound_tag, "a.c[0]", 7).unwrap();
+        set_path(&mut compound_tag, "a.d", "new".to_owned()).unwrap();
+        set_path(&mut compound_tag, "e", 1i64).unwrap();
+
+        assert_eq!(
+            snbt::to_string(&compound_tag),
+            r#"{a:{b:5,c:[7,2],d:"new"},e:1L}"#
+        );
+
+        match set_path(&mut compound_tag, "a.c[2]", 1) {
+            Err(PathError::IndexOutOfBounds { .. }) => {}
+            result => panic!("Expected `IndexOutOfBounds` but got `{:?}`", result),
+        }
+
+        match set_path(&mut compound_tag, "a.x.y", 1) {
+            Err(PathError::TagNotFound { name }) => assert_eq!(name, "x"),
+            result => panic!("Expected `TagNotFound` but got `{:?}`", result),
+        }
+
+        *get_path_mut(&mut compound_tag, "a.b").unwrap() = Tag::Byte(0);
+
+        assert_eq!(
+            snbt::tag_to_string(get_path(&compound_tag, "a.b").unwrap()),
+            "0b"
+        );
+    }
+}
@@ -0,0 +1,346 @@
+//! Player data stored in `playerdata` folder of world.
+//!
+//! Every player who joined world has `<uuid>.dat` file with position, inventory,
+//! ender chest and other state. Singleplayer host is stored in `level.dat` instead
+//! while the world is open and copied to player data on save. Advancements and
+//! statistics are JSON files with the same name in [`advancements`] and [`stats`]
+//! modules.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::player::{PlayerData, PlayerDataProvider};
+//! use tempfile::TempDir;
+//!
+//! let world_dir = TempDir::new().unwrap();
+//! let player_data_folder = world_dir.path().join("playerdata");
+//! let player_data_provider = PlayerDataProvider::new(player_data_folder.to_str().unwrap());
+//!
+//! let uuid = "069a79f4-44e9-4726-a5be-fca90e38aaf5";
+//! let mut player_data = PlayerData::new();
+//! player_data.set_position(0.5, 100.0, 0.5);
+//! player_data_provider.save_player(uuid, &player_data).unwrap();
+//!
+//! for uuid in player_data_provider.player_uuids().unwrap() {
+//!     let player_data = player_data_provider.load_player(&uuid).unwrap();
+//!     assert_eq!(player_data.position(), Some((0.5, 100.0, 0.5)));
+//! }
+//! ```
+use crate::data::{read_data_file, write_data_file, DataFileError};
+use crate::json::{self, JsonError, JsonValue};
+use crate::snapshot::break_hard_link;
+use crate::tag::{compound_tags, compound_tags_mut, get_tag, set_tag};
+use nbt::{CompoundTag, Tag};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub mod advancements;
+pub mod stats;
+
+/// Possible errors while reading or writing player JSON files.
+#[derive(Debug)]
+pub enum PlayerJsonError {
+    /// I/O Error which happened while were reading file.
+    ReadError { io_error: io::Error },
+    /// I/O Error which happened while were writing file.
+    WriteError { io_error: io::Error },
+    /// File is not valid JSON.
+    JsonError { json_error: JsonError },
+    /// File is valid JSON but not an object.
+    NotObject,
+}
+
+impl From<io::Error> for PlayerJsonError {
+    fn from(io_error: io::Error) -> Self {
+        PlayerJsonError::ReadError { io_error }
+    }
+}
+
+impl From<JsonError> for PlayerJsonError {
+    fn from(json_error: JsonError) -> Self {
+        PlayerJsonError::JsonError { json_error }
+    }
+}
+
+/// Player data compound tag with typed accessors.
+#[derive(Debug, Clone)]
+pub struct PlayerData {
+    compound_tag: CompoundTag,
+}
+
+impl PlayerData {
+    /// Creates player data without any values.
+    pub fn new() -> Self {
+        PlayerData {
+            compound_tag: CompoundTag::new(),
+        }
+    }
+
+    pub fn from_compound_tag(compound_tag: CompoundTag) -> Self {
+        PlayerData { compound_tag }
+    }
+
+    pub fn compound_tag(&self) -> &CompoundTag {
+        &self.compound_tag
+    }
+
+    pub fn compound_tag_mut(&mut self) -> &mut CompoundTag {
+        &mut self.compound_tag
+    }
+
+    pub fn into_compound_tag(self) -> CompoundTag {
+        self.compound_tag
+    }
+
+    /// Returns player position.
+    pub fn position(&self) -> Option<(f64, f64, f64)> {
+        let position = match get_tag(&self.compound_tag, "Pos")? {
+            Tag::List(position) => position,
+            _ => return None,
+        };
+
+        match position.as_slice() {
+            [Tag::Double(x), Tag::Double(y), Tag::Double(z)] => Some((*x, *y, *z)),
+            _ => None,
+        }
+    }
+
+    /// Sets player position, player keeps looking in the same direction.
+    pub fn set_position(&mut self, x: f64, y: f64, z: f64) {
+        let position = vec![Tag::Double(x), Tag::Double(y), Tag::Double(z)];
+
+        set_tag(&mut self.compound_tag, "Pos", Tag::List(position));
+    }
+
+    /// Returns name of dimension player is in like `minecraft:the_nether`.
+    ///
+    /// Before 1.16 dimension is stored as number, which is converted to name.
+    pub fn dimension(&self) -> Option<String> {
+        match get_tag(&self.compound_tag, "Dimension")? {
+            Tag::String(dimension) => Some(dimension.clone()),
+            Tag::Int(-1) => Some("minecraft:the_nether".to_owned()),
+            Tag::Int(0) => Some("minecraft:overworld".to_owned()),
+            Tag::Int(1) => Some("minecraft:the_end".to_owned()),
+            _ => None,
+        }
+    }
+
+    /// Returns inventory item stacks with `Slot` tag.
+    pub fn inventory(&self) -> Vec<&CompoundTag> {
+        compound_tags(&self.compound_tag, "Inventory").collect()
+    }
+
+    pub fn inventory_mut(&mut self) -> impl Iterator<Item = &mut CompoundTag> {
+        compound_tags_mut(&mut self.compound_tag, "Inventory")
+    }
+
+    /// Replaces inventory item stacks.
+    pub fn set_inventory(&mut self, items: Vec<CompoundTag>) {
+        let items = items.into_iter().map(Tag::Compound).collect();
+
+        set_tag(&mut self.compound_tag, "Inventory", Tag::List(items));
+    }
+
+    /// Returns ender chest item stacks with `Slot` tag.
+    pub fn ender_chest(&self) -> Vec<&CompoundTag> {
+        compound_tags(&self.compound_tag, "EnderItems").collect()
+    }
+
+    pub fn ender_chest_mut(&mut self) -> impl Iterator<Item = &mut CompoundTag> {
+        compound_tags_mut(&mut self.compound_tag, "EnderItems")
+    }
+
+    /// Replaces ender chest item stacks.
+    pub fn set_ender_chest(&mut self, items: Vec<CompoundTag>) {
+        let items = items.into_iter().map(Tag::Compound).collect();
+
+        set_tag(&mut self.compound_tag, "EnderItems", Tag::List(items));
+    }
+}
+
+impl Default for PlayerData {
+    fn default() -> Self {
+        PlayerData::new()
+    }
+}
+
+pub struct PlayerDataProvider<'a> {
+    /// Folder where player data files located.
+    pub(crate) folder_path: &'a Path,
+}
+
+impl<'a> PlayerDataProvider<'a> {
+    pub fn new(folder: &'a str) -> Self {
+        let folder_path = Path::new(folder);
+
+        PlayerDataProvider { folder_path }
+    }
+
+    /// Returns UUIDs of players which have data file in ascending order.
+    pub fn player_uuids(&self) -> Result<Vec<String>, io::Error> {
+        player_uuids(self.folder_path, "dat")
+    }
+
+    /// Reads player data.
+    pub fn load_player(&self, uuid: &str) -> Result<PlayerData, DataFileError> {
+        let compound_tag = read_data_file(&self.player_path(uuid))?;
+
+        Ok(PlayerData::from_compound_tag(compound_tag))
+    }
+
+    /// Writes player data, creating player data folder if needed.
+    pub fn save_player(&self, uuid: &str, player_data: &PlayerData) -> Result<(), DataFileError> {
+        write_data_file(&self.player_path(uuid), &player_data.compound_tag)
+    }
+
+    /// Deletes player data, returns false if player doesn't have data file.
+    pub fn delete_player(&self, uuid: &str) -> Result<bool, io::Error> {
+        delete_file(&self.player_path(uuid))
+    }
+
+    fn player_path(&self, uuid: &str) -> PathBuf {
+        self.folder_path.join(format!("{}.dat", uuid))
+    }
+}
+
+/// Returns sorted stems of files with extension in folder, missing folder has none.
+fn player_uuids(folder_path: &Path, file_extension: &str) -> Result<Vec<String>, io::Error> {
+    if !folder_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut uuids = Vec::new();
+
+    for entry in fs::read_dir(folder_path)? {
+        let path = entry?.path();
+
+        if path
+            .extension()
+            .is_some_and(|extension| extension == file_extension)
+        {
+            if let Some(uuid) = path.file_stem().and_then(|stem| stem.to_str()) {
+                uuids.push(uuid.to_owned());
+            }
+        }
+    }
+
+    uuids.sort();
+
+    Ok(uuids)
+}
+
+/// Deletes file, returns false if it doesn't exist.
+fn delete_file(path: &Path) -> Result<bool, io::Error> {
+    if !path.exists() {
+        return Ok(false);
+    }
+
+    fs::remove_file(path)?;
+
+    Ok(true)
+}
+
+/// Reads JSON object from file.
+fn read_json_file(path: &Path) -> Result<JsonValue, PlayerJsonError> {
+    let json_value = json::from_str(&fs::read_to_string(path)?)?;
+
+    match json_value {
+        JsonValue::Object(_) => Ok(json_value),
+        _ => Err(PlayerJsonError::NotObject),
+    }
+}
+
+/// Writes JSON text to file, creating parent folders.
+fn write_json_file(path: &Path, json: &str) -> Result<(), PlayerJsonError> {
+    let write = || {
+        if let Some(parent_path) = path.parent() {
+            fs::create_dir_all(parent_path)?;
+        }
+
+        break_hard_link(path)?;
+        fs::write(path, json)
+    };
+
+    write().map_err(|io_error| PlayerJsonError::WriteError { io_error })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::player::{PlayerData, PlayerDataProvider};
+    use crate::snbt;
+    use tempfile::TempDir;
+
+    fn player_data() -> PlayerData {
+        let compound_tag = snbt::from_str(
+            r#"{
+                Pos: [10.5d, 64.0d, -3.5d],
+                Dimension: -1,
+                Inventory: [{Slot: 0b, id: "minecraft:diamond_sword", Count: 1b}],
+                EnderItems: [],
+                XpLevel: 30
+            }"#,
+        )
+        .unwrap();
+
+        PlayerData::from_compound_tag(compound_tag)
+    }
+
+    #[test]
+    fn test_player_data_accessors() {
+        let mut player_data = player_data();
+
+        assert_eq!(player_data.position(), Some((10.5, 64.0, -3.5)));
+        assert_eq!(
+            player_data.dimension(),
+            Some("minecraft:the_nether".to_owned())
+        );
+        assert_eq!(
+            player_data.inventory()[0].get_str("id").unwrap(),
+            "minecraft:diamond_sword"
+        );
+        assert!(player_data.ender_chest().is_empty());
+
+        let items = player_data.inventory().into_iter().cloned().collect();
+        player_data.set_ender_chest(items);
+        player_data.set_inventory(Vec::new());
+        player_data.set_position(0.0, 80.0, 0.0);
+
+        assert_eq!(player_data.position(), Some((0.0, 80.0, 0.0)));
+        assert!(player_data.inventory().is_empty());
+        assert_eq!(player_data.ender_chest().len(), 1);
+        assert_eq!(
+            snbt::to_string(player_data.compound_tag()),
+            r#"{Pos:[0.0d,80.0d,0.0d],Dimension:-1,Inventory:[],EnderItems:[{Slot:0b,id:"minecraft:diamond_sword",Count:1b}],XpLevel:30}"#
+        );
+    }
+
+    #[test]
+    fn test_player_data_provider() {
+        let temp_dir = TempDir::new().unwrap();
+        let folder = temp_dir.path().join("playerdata");
+        let player_data_provider = PlayerDataProvider::new(folder.to_str().unwrap());
+        let uuid = "069a79f4-44e9-4726-a5be-fca90e38aaf5";
+
+        assert!(player_data_provider.player_uuids().unwrap().is_empty());
+
+        player_data_provider
+            .save_player(uuid, &player_data())
+            .unwrap();
+
+        assert_eq!(player_data_provider.player_uuids().unwrap(), vec![uuid]);
+        assert_eq!(
+            player_data_provider
+                .load_player(uuid)
+                .unwrap()
+                .compound_tag()
+                .get_i32("XpLevel")
+                .unwrap(),
+            30
+        );
+
+        assert!(player_data_provider.delete_player(uuid).unwrap());
+        assert!(!player_data_provider.delete_player(uuid).unwrap());
+        assert!(player_data_provider.player_uuids().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,232 @@
+//! Player advancements stored in `advancements/<uuid>.json` files of world.
+//!
+//! File is an object with progress of every started advancement keyed by its id and
+//! `DataVersion` of game which wrote it.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::player::advancements::AdvancementsProvider;
+//! use tempfile::TempDir;
+//!
+//! let world_dir = TempDir::new().unwrap();
+//! let advancements_folder = world_dir.path().join("advancements");
+//! let advancements_provider = AdvancementsProvider::new(advancements_folder.to_str().unwrap());
+//!
+//! for uuid in advancements_provider.player_uuids().unwrap() {
+//!     let mut advancements = advancements_provider.load_player(&uuid).unwrap();
+//!     advancements.revoke("minecraft:end/kill_dragon");
+//!     advancements_provider.save_player(&uuid, &advancements).unwrap();
+//! }
+//! ```
+use crate::json::{self, JsonValue};
+use crate::player::{delete_file, player_uuids, read_json_file, write_json_file, PlayerJsonError};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Name of tag with version of game which wrote file.
+const DATA_VERSION_KEY: &str = "DataVersion";
+
+/// Advancements file object with typed accessors.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Advancements {
+    json_value: JsonValue,
+}
+
+impl Advancements {
+    /// Creates advancements without any progress.
+    pub fn new() -> Self {
+        Advancements {
+            json_value: JsonValue::object(),
+        }
+    }
+
+    /// Wraps JSON value, values other than objects are replaced by empty object.
+    pub fn from_json_value(json_value: JsonValue) -> Self {
+        match json_value {
+            JsonValue::Object(_) => Advancements { json_value },
+            _ => Advancements::new(),
+        }
+    }
+
+    pub fn json_value(&self) -> &JsonValue {
+        &self.json_value
+    }
+
+    pub fn into_json_value(self) -> JsonValue {
+        self.json_value
+    }
+
+    pub fn data_version(&self) -> Option<i32> {
+        let data_version = self.json_value.get(DATA_VERSION_KEY)?.as_i64()?;
+
+        Some(data_version as i32)
+    }
+
+    /// Returns ids of advancements and recipes with any progress in file order.
+    pub fn advancement_ids(&self) -> Vec<&str> {
+        self.json_value
+            .entries()
+            .iter()
+            .filter(|(key, _)| key != DATA_VERSION_KEY)
+            .map(|(key, _)| key.as_str())
+            .collect()
+    }
+
+    /// Returns whether advancement is completed.
+    pub fn is_done(&self, id: &str) -> bool {
+        self.json_value
+            .get(id)
+            .and_then(|progress| progress.get("done"))
+            .and_then(JsonValue::as_bool)
+            .unwrap_or(false)
+    }
+
+    /// Returns obtained criteria of advancement with time they were obtained at
+    /// like `2021-01-15 18:30:00 +0100`.
+    pub fn criteria(&self, id: &str) -> Vec<(&str, &str)> {
+        let criteria = match self
+            .json_value
+            .get(id)
+            .and_then(|progress| progress.get("criteria"))
+        {
+            Some(criteria) => criteria,
+            None => return Vec::new(),
+        };
+
+        criteria
+            .entries()
+            .iter()
+            .filter_map(|(criterion, obtained)| Some((criterion.as_str(), obtained.as_str()?)))
+            .collect()
+    }
+
+    /// Removes advancement progress, returns false if there is no progress.
+    pub fn revoke(&mut self, id: &str) -> bool {
+        id != DATA_VERSION_KEY && self.json_value.remove(id).is_some()
+    }
+}
+
+impl Default for Advancements {
+    fn default() -> Self {
+        Advancements::new()
+    }
+}
+
+pub struct AdvancementsProvider<'a> {
+    /// Folder where advancements files located.
+    pub(crate) folder_path: &'a Path,
+}
+
+impl<'a> AdvancementsProvider<'a> {
+    pub fn new(folder: &'a str) -> Self {
+        let folder_path = Path::new(folder);
+
+        AdvancementsProvider { folder_path }
+    }
+
+    /// Returns UUIDs of players which have advancements file in ascending order.
+    pub fn player_uuids(&self) -> Result<Vec<String>, io::Error> {
+        player_uuids(self.folder_path, "json")
+    }
+
+    /// Reads advancements of player.
+    pub fn load_player(&self, uuid: &str) -> Result<Advancements, PlayerJsonError> {
+        let json_value = read_json_file(&self.player_path(uuid))?;
+
+        Ok(Advancements::from_json_value(json_value))
+    }
+
+    /// Writes advancements pretty printed like the game does, creating folder if needed.
+    pub fn save_player(
+        &self,
+        uuid: &str,
+        advancements: &Advancements,
+    ) -> Result<(), PlayerJsonError> {
+        let json = json::to_string_pretty(&advancements.json_value);
+
+        write_json_file(&self.player_path(uuid), &json)
+    }
+
+    /// Deletes advancements file, returns false if player doesn't have it.
+    pub fn delete_player(&self, uuid: &str) -> Result<bool, io::Error> {
+        delete_file(&self.player_path(uuid))
+    }
+
+    fn player_path(&self, uuid: &str) -> PathBuf {
+        self.folder_path.join(format!("{}.json", uuid))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::player::advancements::AdvancementsProvider;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_advancements_provider() {
+        let temp_dir = TempDir::new().unwrap();
+        let folder = temp_dir.path().join("advancements");
+        let advancements_provider = AdvancementsProvider::new(folder.to_str().unwrap());
+        let uuid = "069a79f4-44e9-4726-a5be-fca90e38aaf5";
+
+        fs::create_dir(&folder).unwrap();
+        fs::write(
+            folder.join(format!("{}.json", uuid)),
+            r#"{
+  "minecraft:story/root": {
+    "criteria": {
+      "crafting_table": "2021-01-15 18:30:00 +0100"
+    },
+    "done": true
+  },
+  "minecraft:story/mine_stone": {
+    "criteria": {},
+    "done": false
+  },
+  "DataVersion": 2586
+}"#,
+        )
+        .unwrap();
+
+        assert_eq!(advancements_provider.player_uuids().unwrap(), vec![uuid]);
+
+        let mut advancements = advancements_provider.load_player(uuid).unwrap();
+
+        assert_eq!(advancements.data_version(), Some(2586));
+        assert_eq!(
+            advancements.advancement_ids(),
+            vec!["minecraft:story/root", "minecraft:story/mine_stone"]
+        );
+        assert!(advancements.is_done("minecraft:story/root"));
+        assert!(!advancements.is_done("minecraft:story/mine_stone"));
+        assert_eq!(
+            advancements.criteria("minecraft:story/root"),
+            vec![("crafting_table", "2021-01-15 18:30:00 +0100")]
+        );
+
+        assert!(advancements.revoke("minecraft:story/mine_stone"));
+        assert!(!advancements.revoke("minecraft:story/mine_stone"));
+        assert!(!advancements.revoke("DataVersion"));
+        advancements_provider
+            .save_player(uuid, &advancements)
+            .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(folder.join(format!("{}.json", uuid))).unwrap(),
+            r#"{
+  "minecraft:story/root": {
+    "criteria": {
+      "crafting_table": "2021-01-15 18:30:00 +0100"
+    },
+    "done": true
+  },
+  "DataVersion": 2586
+}"#
+        );
+
+        assert!(advancements_provider.delete_player(uuid).unwrap());
+        assert!(advancements_provider.player_uuids().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,225 @@
+//! Player statistics stored in `stats/<uuid>.json` files of world.
+//!
+//! Since 1.13 statistics are grouped by category like `minecraft:custom` or
+//! `minecraft:mined` inside of `stats` object, older flat files are not supported.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::player::stats::{Statistics, StatsProvider};
+//! use tempfile::TempDir;
+//!
+//! let world_dir = TempDir::new().unwrap();
+//! let stats_folder = world_dir.path().join("stats");
+//! let stats_provider = StatsProvider::new(stats_folder.to_str().unwrap());
+//!
+//! let uuid = "069a79f4-44e9-4726-a5be-fca90e38aaf5";
+//! let mut statistics = Statistics::new();
+//! statistics.set_stat("minecraft:custom", "minecraft:deaths", 3);
+//! stats_provider.save_player(uuid, &statistics).unwrap();
+//!
+//! let statistics = stats_provider.load_player(uuid).unwrap();
+//! assert_eq!(statistics.stat("minecraft:custom", "minecraft:deaths"), Some(3));
+//! ```
+use crate::json::{self, JsonValue};
+use crate::player::{delete_file, player_uuids, read_json_file, write_json_file, PlayerJsonError};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Statistics file object with typed accessors.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statistics {
+    json_value: JsonValue,
+}
+
+impl Statistics {
+    /// Creates statistics without any values.
+    pub fn new() -> Self {
+        let mut json_value = JsonValue::object();
+        json_value.insert("stats", JsonValue::object());
+
+        Statistics { json_value }
+    }
+
+    /// Wraps JSON value, values other than objects are replaced by empty statistics.
+    pub fn from_json_value(json_value: JsonValue) -> Self {
+        match json_value {
+            JsonValue::Object(_) => Statistics { json_value },
+            _ => Statistics::new(),
+        }
+    }
+
+    pub fn json_value(&self) -> &JsonValue {
+        &self.json_value
+    }
+
+    pub fn into_json_value(self) -> JsonValue {
+        self.json_value
+    }
+
+    pub fn data_version(&self) -> Option<i32> {
+        let data_version = self.json_value.get("DataVersion")?.as_i64()?;
+
+        Some(data_version as i32)
+    }
+
+    /// Returns statistic categories in file order.
+    pub fn categories(&self) -> Vec<&str> {
+        self.stats()
+            .map(JsonValue::entries)
+            .unwrap_or_default()
+            .iter()
+            .map(|(category, _)| category.as_str())
+            .collect()
+    }
+
+    /// Returns names and values of statistics in category.
+    pub fn category_stats(&self, category: &str) -> Vec<(&str, i64)> {
+        let category = match self.stats().and_then(|stats| stats.get(category)) {
+            Some(category) => category,
+            None => return Vec::new(),
+        };
+
+        category
+            .entries()
+            .iter()
+            .filter_map(|(name, value)| Some((name.as_str(), value.as_i64()?)))
+            .collect()
+    }
+
+    /// Returns value of statistic like `minecraft:play_one_minute` in `minecraft:custom`.
+    pub fn stat(&self, category: &str, name: &str) -> Option<i64> {
+        self.stats()?.get(category)?.get(name)?.as_i64()
+    }
+
+    /// Sets value of statistic, creating category if needed.
+    pub fn set_stat(&mut self, category: &str, name: &str, value: i64) {
+        if self.stats().is_none() {
+            self.json_value.insert("stats", JsonValue::object());
+        }
+
+        // Presence of object is ensured above.
+        let stats = self.json_value.get_mut("stats").unwrap();
+
+        if stats.get(category).is_none() {
+            stats.insert(category, JsonValue::object());
+        }
+
+        let category = stats.get_mut(category).unwrap();
+        category.insert(name, JsonValue::Integer(value));
+    }
+
+    fn stats(&self) -> Option<&JsonValue> {
+        match self.json_value.get("stats")? {
+            stats @ JsonValue::Object(_) => Some(stats),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Statistics {
+    fn default() -> Self {
+        Statistics::new()
+    }
+}
+
+pub struct StatsProvider<'a> {
+    /// Folder where statistics files located.
+    pub(crate) folder_path: &'a Path,
+}
+
+impl<'a> StatsProvider<'a> {
+    pub fn new(folder: &'a str) -> Self {
+        let folder_path = Path::new(folder);
+
+        StatsProvider { folder_path }
+    }
+
+    /// Returns UUIDs of players which have statistics file in ascending order.
+    pub fn player_uuids(&self) -> Result<Vec<String>, io::Error> {
+        player_uuids(self.folder_path, "json")
+    }
+
+    /// Reads statistics of player.
+    pub fn load_player(&self, uuid: &str) -> Result<Statistics, PlayerJsonError> {
+        let json_value = read_json_file(&self.player_path(uuid))?;
+
+        Ok(Statistics::from_json_value(json_value))
+    }
+
+    /// Writes statistics on single line like the game does, creating folder if needed.
+    pub fn save_player(&self, uuid: &str, statistics: &Statistics) -> Result<(), PlayerJsonError> {
+        let json = json::to_string(&statistics.json_value);
+
+        write_json_file(&self.player_path(uuid), &json)
+    }
+
+    /// Deletes statistics file, returns false if player doesn't have it.
+    pub fn delete_player(&self, uuid: &str) -> Result<bool, io::Error> {
+        delete_file(&self.player_path(uuid))
+    }
+
+    fn player_path(&self, uuid: &str) -> PathBuf {
+        self.folder_path.join(format!("{}.json", uuid))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::player::stats::StatsProvider;
+    use crate::player::PlayerJsonError;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_stats_provider() {
+        let temp_dir = TempDir::new().unwrap();
+        let folder = temp_dir.path().join("stats");
+        let stats_provider = StatsProvider::new(folder.to_str().unwrap());
+        let uuid = "069a79f4-44e9-4726-a5be-fca90e38aaf5";
+        let path = folder.join(format!("{}.json", uuid));
+
+        fs::create_dir(&folder).unwrap();
+        fs::write(
+            &path,
+            r#"{"stats":{"minecraft:custom":{"minecraft:play_one_minute":72000,"minecraft:jump":15},"minecraft:mined":{"minecraft:stone":64}},"DataVersion":2586}"#,
+        )
+        .unwrap();
+
+        let mut statistics = stats_provider.load_player(uuid).unwrap();
+
+        assert_eq!(statistics.data_version(), Some(2586));
+        assert_eq!(
+            statistics.categories(),
+            vec!["minecraft:custom", "minecraft:mined"]
+        );
+        assert_eq!(
+            statistics.category_stats("minecraft:mined"),
+            vec![("minecraft:stone", 64)]
+        );
+        assert_eq!(
+            statistics.stat("minecraft:custom", "minecraft:jump"),
+            Some(15)
+        );
+        assert_eq!(
+            statistics.stat("minecraft:killed", "minecraft:zombie"),
+            None
+        );
+
+        statistics.set_stat("minecraft:custom", "minecraft:jump", 16);
+        statistics.set_stat("minecraft:killed", "minecraft:zombie", 2);
+        stats_provider.save_player(uuid, &statistics).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            r#"{"stats":{"minecraft:custom":{"minecraft:play_one_minute":72000,"minecraft:jump":16},"minecraft:mined":{"minecraft:stone":64},"minecraft:killed":{"minecraft:zombie":2}},"DataVersion":2586}"#
+        );
+
+        fs::write(&path, "[]").unwrap();
+
+        match stats_provider.load_player(uuid) {
+            Err(PlayerJsonError::NotObject) => {}
+            result => panic!("Expected `NotObject` but got `{:?}`", result),
+        }
+    }
+}
@@ -0,0 +1,537 @@
+//! Chunk provider which loads chunks around requested ones in background.
+//!
+//! Every [`load_chunk`] queues chunks within [`radius`] of requested chunk, and
+//! optionally the rest of its region, to be loaded into memory cache by background
+//! thread. Renderers and pathfinders request chunks next to each other, so following
+//! loads are usually served from cache without reading region files.
+//!
+//! [`load_chunk`]: PrefetchChunkProvider::load_chunk
+//! [`radius`]: PrefetchOptions::radius
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::prefetch::{PrefetchChunkProvider, PrefetchOptions};
+//!
+//! let chunk_provider = PrefetchChunkProvider::new("test/region", PrefetchOptions::default());
+//!
+//! // Neighbors of chunk start loading in background.
+//! assert!(chunk_provider.load_chunk(4, 2).is_ok());
+//! assert!(chunk_provider.load_chunk(5, 2).is_ok());
+//! ```
+use crate::concurrent::ConcurrentAnvilChunkProvider;
+use crate::hash::{CoordinateHashMap, CoordinateHashSet};
+use crate::memory::{compound_tag_size, MemoryBudget, MemoryConsumer, MemoryRegistration};
+use crate::{ChunkLoadError, ChunkSaveError};
+use nbt::CompoundTag;
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::thread::{self, JoinHandle};
+
+/// Default maximum amount of cached chunks.
+pub const DEFAULT_CACHE_CAPACITY: usize = 4096;
+
+/// Which chunks are loaded in background.
+#[derive(Debug, Clone)]
+pub struct PrefetchOptions {
+    /// Maximum amount of cached chunks, oldest cached chunks are evicted first.
+    pub cache_capacity: usize,
+    /// Chunks within this distance from requested chunk are prefetched.
+    pub radius: i32,
+    /// Prefetch all chunks of region of requested chunk.
+    pub prefetch_region: bool,
+    /// Budget which accounts cached chunks together with caches of other providers.
+    pub memory_budget: Option<MemoryBudget>,
+}
+
+impl PrefetchOptions {
+    pub fn new(cache_capacity: usize, radius: i32) -> Self {
+        PrefetchOptions {
+            cache_capacity,
+            radius,
+            prefetch_region: false,
+            memory_budget: None,
+        }
+    }
+
+    /// Enables prefetching of whole region of requested chunk.
+    pub fn prefetch_region(mut self) -> Self {
+        self.prefetch_region = true;
+        self
+    }
+
+    /// Accounts cached chunks in memory budget, oldest of them are evicted when it's
+    /// exceeded.
+    pub fn memory_budget(mut self, memory_budget: MemoryBudget) -> Self {
+        self.memory_budget = Some(memory_budget);
+        self
+    }
+}
+
+impl Default for PrefetchOptions {
+    fn default() -> Self {
+        PrefetchOptions::new(DEFAULT_CACHE_CAPACITY, 1)
+    }
+}
+
+/// Provider of chunks of region folder which prefetches neighbor chunks.
+pub struct PrefetchChunkProvider {
+    shared: Arc<Shared>,
+    /// Sends positions of requested chunks to prefetch thread.
+    prefetch_sender: Option<Sender<(i32, i32)>>,
+    prefetch_thread: Option<JoinHandle<()>>,
+}
+
+/// State shared with prefetch thread.
+struct Shared {
+    chunk_provider: ConcurrentAnvilChunkProvider,
+    options: PrefetchOptions,
+    cache: Mutex<ChunkCache>,
+    /// Set when provider is dropped, so queued prefetches are skipped.
+    closed: AtomicBool,
+}
+
+struct ChunkCache {
+    chunks: CoordinateHashMap<(i32, i32), CompoundTag>,
+    /// Positions of cached chunks, oldest first.
+    order: VecDeque<(i32, i32)>,
+    /// Regions which were already prefetched whole.
+    prefetched_regions: CoordinateHashSet<(i32, i32)>,
+    /// Incremented on every save and delete, prefetched chunk is discarded if it
+    /// changed while being loaded.
+    generation: u64,
+    /// Estimated amount of bytes held by cached chunks.
+    memory_usage: usize,
+    memory_registration: Option<MemoryRegistration>,
+}
+
+impl PrefetchChunkProvider {
+    pub fn new(folder_path: impl AsRef<Path>, options: PrefetchOptions) -> Self {
+        let shared = Arc::new_cyclic(|weak_shared: &Weak<Shared>| {
+            let memory_registration = options
+                .memory_budget
+                .as_ref()
+                .map(|memory_budget| memory_budget.register(weak_shared.clone()));
+
+            let cache = ChunkCache {
+                chunks: CoordinateHashMap::default(),
+                order: VecDeque::new(),
+                prefetched_regions: CoordinateHashSet::default(),
+                generation: 0,
+                memory_usage: 0,
+                memory_registration,
+            };
+
+            Shared {
+                chunk_provider: ConcurrentAnvilChunkProvider::new(folder_path),
+                options,
+                cache: Mutex::new(cache),
+                closed: AtomicBool::new(false),
+            }
+        });
+
+        let (prefetch_sender, prefetch_receiver) = mpsc::channel();
+
+        let prefetch_thread = {
+            let shared = shared.clone();
+            thread::spawn(move || shared.run_prefetch_thread(prefetch_receiver))
+        };
+
+        PrefetchChunkProvider {
+            shared,
+            prefetch_sender: Some(prefetch_sender),
+            prefetch_thread: Some(prefetch_thread),
+        }
+    }
+
+    pub fn folder_path(&self) -> &Path {
+        self.shared.chunk_provider.folder_path()
+    }
+
+    /// Loads chunk from the specified coordinates and queues prefetch of its neighbors.
+    pub fn load_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<CompoundTag, ChunkLoadError> {
+        if let Some(prefetch_sender) = &self.prefetch_sender {
+            // Thread only stops when provider is dropped.
+            let _ = prefetch_sender.send((chunk_x, chunk_z));
+        }
+
+        if let Some(chunk_compound_tag) = self.shared.cache().chunks.get(&(chunk_x, chunk_z)) {
+            return Ok(chunk_compound_tag.clone());
+        }
+
+        self.shared.load_chunk(chunk_x, chunk_z)
+    }
+
+    /// Saves chunk data to the specified coordinates, removing cached chunk.
+    pub fn save_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        chunk_compound_tag: CompoundTag,
+    ) -> Result<(), ChunkSaveError> {
+        let result = self
+            .shared
+            .chunk_provider
+            .save_chunk(chunk_x, chunk_z, chunk_compound_tag);
+
+        self.shared.invalidate(chunk_x, chunk_z);
+
+        result
+    }
+
+    /// Deletes chunk at the specified coordinates, returns false if chunk is not present.
+    pub fn delete_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<bool, ChunkSaveError> {
+        let result = self.shared.chunk_provider.delete_chunk(chunk_x, chunk_z);
+        self.shared.invalidate(chunk_x, chunk_z);
+
+        result
+    }
+
+    /// Returns amount of chunks in cache.
+    pub fn cached_chunk_count(&self) -> usize {
+        self.shared.cache().chunks.len()
+    }
+
+    /// Removes all chunks from cache.
+    pub fn clear_cache(&self) {
+        let mut cache = self.shared.cache();
+
+        while cache.evict_oldest() {}
+        cache.prefetched_regions.clear();
+    }
+
+    /// Removes chunks of region from cache, so the next loads read them from region
+    /// file modified by other process.
+    ///
+    /// Prefetches which are in progress are discarded.
+    pub fn invalidate(&self, region_x: i32, region_z: i32) {
+        let mut cache = self.shared.cache();
+        let region_chunks: Vec<(i32, i32)> = cache
+            .order
+            .iter()
+            .copied()
+            .filter(|(chunk_x, chunk_z)| (chunk_x >> 5, chunk_z >> 5) == (region_x, region_z))
+            .collect();
+
+        cache.generation += 1;
+        cache.prefetched_regions.remove(&(region_x, region_z));
+
+        for chunk_position in region_chunks {
+            cache.remove(chunk_position);
+        }
+    }
+
+    /// Removes all chunks from cache like [`clear_cache`] discarding prefetches which
+    /// are in progress.
+    ///
+    /// [`clear_cache`]: PrefetchChunkProvider::clear_cache
+    pub fn invalidate_all(&self) {
+        self.shared.cache().generation += 1;
+        self.clear_cache();
+    }
+}
+
+impl Drop for PrefetchChunkProvider {
+    /// Stops prefetch thread skipping queued prefetches.
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Relaxed);
+        self.prefetch_sender = None;
+
+        if let Some(prefetch_thread) = self.prefetch_thread.take() {
+            let _ = prefetch_thread.join();
+        }
+    }
+}
+
+impl Shared {
+    fn cache(&self) -> MutexGuard<'_, ChunkCache> {
+        self.cache.lock().unwrap_or_else(|error| error.into_inner())
+    }
+
+    /// Loads chunk from region file and puts it in cache unless it was changed meanwhile.
+    fn load_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<CompoundTag, ChunkLoadError> {
+        let generation = self.cache().generation;
+        let chunk_compound_tag = self.chunk_provider.load_chunk(chunk_x, chunk_z)?;
+        let mut cache = self.cache();
+
+        if cache.generation == generation {
+            cache.insert(
+                (chunk_x, chunk_z),
+                chunk_compound_tag.clone(),
+                self.options.cache_capacity,
+            );
+        }
+
+        drop(cache);
+
+        if let Some(memory_budget) = &self.options.memory_budget {
+            memory_budget.enforce();
+        }
+
+        Ok(chunk_compound_tag)
+    }
+
+    /// Removes changed chunk from cache and discards prefetches which are in progress.
+    fn invalidate(&self, chunk_x: i32, chunk_z: i32) {
+        let mut cache = self.cache();
+
+        cache.generation += 1;
+        cache.remove((chunk_x, chunk_z));
+    }
+
+    fn run_prefetch_thread(&self, prefetch_receiver: Receiver<(i32, i32)>) {
+        for (chunk_x, chunk_z) in prefetch_receiver {
+            if self.closed.load(Ordering::Relaxed) {
+                return;
+            }
+
+            let radius = self.options.radius;
+
+            for offset_z in -radius..=radius {
+                for offset_x in -radius..=radius {
+                    self.prefetch_chunk(chunk_x + offset_x, chunk_z + offset_z);
+                }
+            }
+
+            let region_position = (chunk_x >> 5, chunk_z >> 5);
+
+            if self.options.prefetch_region
+                && self.cache().prefetched_regions.insert(region_position)
+            {
+                let (region_x, region_z) = region_position;
+
+                for region_chunk_z in 0..32 {
+                    for region_chunk_x in 0..32 {
+                        if self.closed.load(Ordering::Relaxed) {
+                            return;
+                        }
+
+                        self.prefetch_chunk(
+                            (region_x << 5) + region_chunk_x,
+                            (region_z << 5) + region_chunk_z,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Loads chunk into cache if it isn't cached, missing chunks are ignored.
+    fn prefetch_chunk(&self, chunk_x: i32, chunk_z: i32) {
+        if self.cache().chunks.contains_key(&(chunk_x, chunk_z)) {
+            return;
+        }
+
+        let _ = self.load_chunk(chunk_x, chunk_z);
+    }
+}
+
+impl ChunkCache {
+    /// Puts chunk in cache evicting oldest chunks above capacity.
+    fn insert(
+        &mut self,
+        chunk_position: (i32, i32),
+        chunk_compound_tag: CompoundTag,
+        capacity: usize,
+    ) {
+        self.memory_usage += compound_tag_size(&chunk_compound_tag);
+
+        match self.chunks.insert(chunk_position, chunk_compound_tag) {
+            Some(previous_compound_tag) => {
+                self.memory_usage -= compound_tag_size(&previous_compound_tag)
+            }
+            None => self.order.push_back(chunk_position),
+        }
+
+        while self.chunks.len() > capacity {
+            if !self.evict_oldest() {
+                break;
+            }
+        }
+
+        self.update_memory_usage();
+    }
+
+    fn remove(&mut self, chunk_position: (i32, i32)) {
+        if let Some(chunk_compound_tag) = self.chunks.remove(&chunk_position) {
+            self.order
+                .retain(|cached_position| *cached_position != chunk_position);
+            self.memory_usage -= compound_tag_size(&chunk_compound_tag);
+            self.update_memory_usage();
+        }
+    }
+
+    /// Removes the oldest cached chunk, returns false if cache is empty.
+    fn evict_oldest(&mut self) -> bool {
+        let chunk_position = match self.order.pop_front() {
+            Some(chunk_position) => chunk_position,
+            None => return false,
+        };
+
+        if let Some(chunk_compound_tag) = self.chunks.remove(&chunk_position) {
+            self.memory_usage -= compound_tag_size(&chunk_compound_tag);
+        }
+
+        self.update_memory_usage();
+
+        true
+    }
+
+    fn update_memory_usage(&self) {
+        if let Some(memory_registration) = &self.memory_registration {
+            memory_registration.set_usage(self.memory_usage);
+        }
+    }
+}
+
+impl MemoryConsumer for Shared {
+    /// Evicts the oldest cached chunks.
+    fn release_memory(&self, bytes: usize) {
+        let mut cache = self.cache();
+        let target_usage = cache.memory_usage.saturating_sub(bytes);
+
+        while cache.memory_usage > target_usage && cache.evict_oldest() {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::memory::{compound_tag_size, MemoryBudget};
+    use crate::prefetch::{PrefetchChunkProvider, PrefetchOptions};
+    use crate::relocate::copy_chunk;
+    use crate::AnvilChunkProvider;
+    use std::thread;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn wait_for_cached_chunks(chunk_provider: &PrefetchChunkProvider, cached_chunk_count: usize) {
+        for _ in 0..500 {
+            if chunk_provider.cached_chunk_count() >= cached_chunk_count {
+                break;
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(chunk_provider.cached_chunk_count(), cached_chunk_count);
+    }
+
+    #[test]
+    fn test_prefetch_neighbors() {
+        let temp_dir = TempDir::new().unwrap();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let folder_chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+
+        for chunk_position in &[(0, 0), (1, 1), (2, 2), (5, 5)] {
+            copy_chunk(
+                &fixture_chunk_provider,
+                (4, 2),
+                &folder_chunk_provider,
+                *chunk_position,
+            )
+            .unwrap();
+        }
+
+        let chunk_provider =
+            PrefetchChunkProvider::new(temp_dir.path(), PrefetchOptions::default());
+
+        assert!(chunk_provider.load_chunk(0, 0).is_ok());
+        wait_for_cached_chunks(&chunk_provider, 2);
+
+        assert!(chunk_provider.load_chunk(1, 1).is_ok());
+        wait_for_cached_chunks(&chunk_provider, 3);
+
+        assert!(chunk_provider.delete_chunk(2, 2).unwrap());
+        assert_eq!(chunk_provider.cached_chunk_count(), 2);
+        assert!(chunk_provider.load_chunk(2, 2).is_err());
+    }
+
+    #[test]
+    fn test_prefetch_region() {
+        let temp_dir = TempDir::new().unwrap();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let folder_chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+
+        for chunk_position in &[(0, 0), (20, 20), (31, 31), (32, 0)] {
+            copy_chunk(
+                &fixture_chunk_provider,
+                (4, 2),
+                &folder_chunk_provider,
+                *chunk_position,
+            )
+            .unwrap();
+        }
+
+        let options = PrefetchOptions::new(16, 0).prefetch_region();
+        let chunk_provider = PrefetchChunkProvider::new(temp_dir.path(), options);
+
+        assert!(chunk_provider.load_chunk(0, 0).is_ok());
+        wait_for_cached_chunks(&chunk_provider, 3);
+
+        chunk_provider.clear_cache();
+        assert_eq!(chunk_provider.cached_chunk_count(), 0);
+    }
+
+    #[test]
+    fn test_invalidate() {
+        let temp_dir = TempDir::new().unwrap();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let folder_chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+
+        for chunk_position in &[(0, 0), (1, 1), (40, 0)] {
+            copy_chunk(
+                &fixture_chunk_provider,
+                (4, 2),
+                &folder_chunk_provider,
+                *chunk_position,
+            )
+            .unwrap();
+        }
+
+        let options = PrefetchOptions::new(16, 0);
+        let chunk_provider = PrefetchChunkProvider::new(temp_dir.path(), options);
+
+        for &(chunk_x, chunk_z) in &[(0, 0), (1, 1), (40, 0)] {
+            assert!(chunk_provider.load_chunk(chunk_x, chunk_z).is_ok());
+        }
+
+        wait_for_cached_chunks(&chunk_provider, 3);
+
+        // Chunk deleted by other provider is still served from cache.
+        assert!(folder_chunk_provider.delete_chunk(0, 0).unwrap());
+        assert!(chunk_provider.load_chunk(0, 0).is_ok());
+
+        chunk_provider.invalidate(0, 0);
+        assert_eq!(chunk_provider.cached_chunk_count(), 1);
+        assert!(chunk_provider.load_chunk(0, 0).is_err());
+
+        chunk_provider.invalidate_all();
+        assert_eq!(chunk_provider.cached_chunk_count(), 0);
+    }
+
+    #[test]
+    fn test_memory_budget() {
+        let chunk_compound_tag = AnvilChunkProvider::new("test/region")
+            .load_chunk(4, 2)
+            .unwrap();
+
+        // Budget fits one chunk, the oldest one is evicted for the next.
+        let memory_budget = MemoryBudget::new(compound_tag_size(&chunk_compound_tag) * 3 / 2);
+        let options = PrefetchOptions::new(16, 0).memory_budget(memory_budget.clone());
+        let chunk_provider = PrefetchChunkProvider::new("test/region", options);
+
+        assert!(chunk_provider.load_chunk(4, 2).is_ok());
+        wait_for_cached_chunks(&chunk_provider, 1);
+        assert!(memory_budget.used() > 0);
+
+        assert!(chunk_provider.load_chunk(15, 3).is_ok());
+        wait_for_cached_chunks(&chunk_provider, 1);
+        assert!(memory_budget.used() <= memory_budget.limit());
+
+        chunk_provider.clear_cache();
+        assert_eq!(memory_budget.used(), 0);
+    }
+}
@@ -0,0 +1,255 @@
+//! Progress reporting of long-running world operations.
+//!
+//! Reporter is cloned into operation and its callback is called after every chunk
+//! and every finished region, so command line tools and GUIs can draw progress bars
+//! and estimate remaining time. Totals are known before the first chunk, because
+//! operations list region files first. Bytes are sizes of region files on disk and
+//! are added to processed bytes once the whole region is processed.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::cancel::CancellationToken;
+//! use anvil_region::copy::copy_provider_with_progress;
+//! use anvil_region::progress::ProgressReporter;
+//! use anvil_region::AnvilChunkProvider;
+//! use tempfile::TempDir;
+//!
+//! let target_dir = TempDir::new().unwrap();
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//! let target_chunk_provider = AnvilChunkProvider::new(target_dir.path().to_str().unwrap());
+//!
+//! let progress_reporter = ProgressReporter::new(|progress| {
+//!     if let Some(fraction) = progress.fraction() {
+//!         println!("{:.0}% of {} regions", fraction * 100.0, progress.total_regions);
+//!     }
+//! });
+//!
+//! copy_provider_with_progress(
+//!     &chunk_provider,
+//!     &target_chunk_provider,
+//!     &CancellationToken::new(),
+//!     &progress_reporter,
+//!     |_, _, chunk_compound_tag| Some(chunk_compound_tag),
+//! )
+//! .unwrap();
+//! ```
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Snapshot of operation progress passed to reporter.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Progress {
+    /// Regions which were fully processed.
+    pub processed_regions: usize,
+    /// Regions which operation processes in total.
+    pub total_regions: usize,
+    /// Chunks which were processed so far.
+    pub processed_chunks: usize,
+    /// Position of chunk which is being processed, none after region was finished.
+    pub chunk_position: Option<(i32, i32)>,
+    /// Bytes of region files which were fully processed.
+    pub processed_bytes: u64,
+    /// Bytes of all region files which operation processes.
+    pub total_bytes: u64,
+}
+
+impl Progress {
+    /// Returns processed part of operation between 0 and 1 by bytes, or by regions
+    /// when region files are empty, none if there is nothing to process.
+    pub fn fraction(&self) -> Option<f64> {
+        if self.total_bytes > 0 {
+            return Some(self.processed_bytes as f64 / self.total_bytes as f64);
+        }
+
+        if self.total_regions > 0 {
+            return Some(self.processed_regions as f64 / self.total_regions as f64);
+        }
+
+        None
+    }
+}
+
+/// Callback which receives progress of operation.
+///
+/// Clones share the same callback, which may be called from other thread than the
+/// one which started operation.
+#[derive(Clone)]
+pub struct ProgressReporter {
+    callback: Arc<dyn Fn(&Progress) + Send + Sync>,
+}
+
+impl ProgressReporter {
+    pub fn new(callback: impl Fn(&Progress) + Send + Sync + 'static) -> Self {
+        ProgressReporter {
+            callback: Arc::new(callback),
+        }
+    }
+
+    pub fn report(&self, progress: &Progress) {
+        (self.callback)(progress)
+    }
+}
+
+impl fmt::Debug for ProgressReporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgressReporter").finish_non_exhaustive()
+    }
+}
+
+/// Counts processed regions and chunks of operation, does nothing without reporter.
+pub(crate) struct ProgressTracker {
+    progress_reporter: Option<ProgressReporter>,
+    progress: Progress,
+    /// Bytes of region which is being processed.
+    region_bytes: Option<u64>,
+}
+
+impl ProgressTracker {
+    pub(crate) fn new(progress_reporter: Option<ProgressReporter>) -> Self {
+        ProgressTracker {
+            progress_reporter,
+            progress: Progress::default(),
+            region_bytes: None,
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.progress_reporter.is_some()
+    }
+
+    /// Adds region file to totals.
+    pub(crate) fn add_region(&mut self, region_path: &Path) {
+        if self.is_enabled() {
+            self.progress.total_regions += 1;
+            self.progress.total_bytes += region_file_length(region_path);
+        }
+    }
+
+    /// Finishes previous region and starts counting chunks of region file.
+    pub(crate) fn start_region(&mut self, region_path: &Path) {
+        if self.is_enabled() {
+            self.finish_region();
+            self.region_bytes = Some(region_file_length(region_path));
+        }
+    }
+
+    /// Reports chunk which is being processed.
+    pub(crate) fn chunk(&mut self, chunk_x: i32, chunk_z: i32) {
+        if let Some(progress_reporter) = &self.progress_reporter {
+            self.progress.processed_chunks += 1;
+            self.progress.chunk_position = Some((chunk_x, chunk_z));
+            progress_reporter.report(&self.progress);
+        }
+    }
+
+    /// Reports region which was started last as processed.
+    pub(crate) fn finish_region(&mut self) {
+        if let (Some(progress_reporter), Some(region_bytes)) =
+            (&self.progress_reporter, self.region_bytes.take())
+        {
+            self.progress.processed_regions += 1;
+            self.progress.processed_bytes += region_bytes;
+            self.progress.chunk_position = None;
+            progress_reporter.report(&self.progress);
+        }
+    }
+
+    /// Finishes operation, regions without chunks which were never started are
+    /// reported as processed.
+    pub(crate) fn finish(&mut self) {
+        self.finish_region();
+
+        if let Some(progress_reporter) = &self.progress_reporter {
+            if self.progress.processed_regions < self.progress.total_regions
+                || self.progress.processed_bytes < self.progress.total_bytes
+            {
+                self.progress.processed_regions = self.progress.total_regions;
+                self.progress.processed_bytes = self.progress.total_bytes;
+                self.progress.chunk_position = None;
+                progress_reporter.report(&self.progress);
+            }
+        }
+    }
+}
+
+fn region_file_length(region_path: &Path) -> u64 {
+    fs::metadata(region_path).map_or(0, |metadata| metadata.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::progress::{Progress, ProgressReporter, ProgressTracker};
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_progress_tracker() {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let reported = reports.clone();
+        let progress_reporter =
+            ProgressReporter::new(move |progress| reported.lock().unwrap().push(progress.clone()));
+
+        let region_path = Path::new("test/region/r.0.0.mca");
+        let region_bytes = region_path.metadata().unwrap().len();
+
+        let mut progress_tracker = ProgressTracker::new(Some(progress_reporter));
+        progress_tracker.add_region(region_path);
+        progress_tracker.add_region(region_path);
+        progress_tracker.start_region(region_path);
+        progress_tracker.chunk(4, 2);
+        progress_tracker.start_region(region_path);
+        progress_tracker.finish_region();
+        progress_tracker.finish_region();
+        progress_tracker.finish();
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 3);
+        assert_eq!(reports[0].chunk_position, Some((4, 2)));
+        assert_eq!(reports[0].fraction(), Some(0.0));
+        assert_eq!(
+            reports[2],
+            Progress {
+                processed_regions: 2,
+                total_regions: 2,
+                processed_chunks: 1,
+                chunk_position: None,
+                processed_bytes: region_bytes * 2,
+                total_bytes: region_bytes * 2,
+            }
+        );
+        assert_eq!(reports[2].fraction(), Some(1.0));
+    }
+
+    #[test]
+    fn test_finish_reports_regions_without_chunks() {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let reported = reports.clone();
+        let progress_reporter =
+            ProgressReporter::new(move |progress| reported.lock().unwrap().push(progress.clone()));
+
+        let mut progress_tracker = ProgressTracker::new(Some(progress_reporter));
+        progress_tracker.add_region(Path::new("test/region/r.0.0.mca"));
+        progress_tracker.finish();
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].processed_regions, 1);
+        assert_eq!(reports[0].fraction(), Some(1.0));
+    }
+
+    #[test]
+    fn test_fraction() {
+        let progress = Progress {
+            processed_regions: 1,
+            total_regions: 4,
+            ..Progress::default()
+        };
+
+        assert_eq!(progress.fraction(), Some(0.25));
+        assert_eq!(Progress::default().fraction(), None);
+    }
+}
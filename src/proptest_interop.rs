@@ -0,0 +1,282 @@
+//! Property-based testing of pipelines with [`proptest`] strategies of tags and chunks.
+//!
+//! Strategies generate tags of every type, nested compounds and lists, and chunks with
+//! such tags added next to vanilla ones, like chunks saved by modded servers. Assert
+//! helpers pass them through the same encoding and decoding as region files, so users
+//! can check their own transformations keep data the crate is able to store.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::proptest_interop::{arb_chunk, assert_region_roundtrip};
+//! use proptest::prelude::*;
+//! use proptest::test_runner::TestRunner;
+//!
+//! let mut test_runner = TestRunner::new(ProptestConfig::with_cases(4));
+//!
+//! test_runner
+//!     .run(&(0..64i32, 0..64i32, arb_chunk()), |(chunk_x, chunk_z, chunk_compound_tag)| {
+//!         assert_region_roundtrip(&[(chunk_x, chunk_z, chunk_compound_tag)]);
+//!         Ok(())
+//!     })
+//!     .unwrap();
+//! ```
+use crate::diff::diff;
+use crate::in_memory::InMemoryChunkProvider;
+use crate::roundtrip::verify_compound_tag_roundtrip;
+use nbt::{CompoundTag, Tag};
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+/// Maximum nesting of generated compounds and lists.
+pub const MAX_DEPTH: u32 = 4;
+/// Maximum amount of tags of generated compound or list.
+pub const MAX_LENGTH: usize = 8;
+
+/// Strategy of names of tags, vanilla style and namespaced like mods use.
+pub fn arb_name() -> impl Strategy<Value = String> {
+    prop_oneof!["[A-Za-z][A-Za-z0-9_]{0,11}", "[a-z]{1,8}:[a-z_]{1,8}"]
+}
+
+/// Strategy of tags which aren't compounds or lists.
+///
+/// Floats are finite, since NaN isn't equal to itself and can't be compared.
+pub fn arb_primitive_tag() -> impl Strategy<Value = Tag> {
+    prop_oneof![
+        any::<i8>().prop_map(Tag::Byte),
+        any::<i16>().prop_map(Tag::Short),
+        any::<i32>().prop_map(Tag::Int),
+        any::<i64>().prop_map(Tag::Long),
+        (-1e30f32..1e30f32).prop_map(Tag::Float),
+        (-1e300f64..1e300f64).prop_map(Tag::Double),
+        "\\PC{0,16}".prop_map(Tag::String),
+        vec(any::<i8>(), 0..64).prop_map(Tag::ByteArray),
+        vec(any::<i32>(), 0..64).prop_map(Tag::IntArray),
+        vec(any::<i64>(), 0..64).prop_map(Tag::LongArray),
+    ]
+}
+
+/// Strategy of tags of every type, nested at most [`MAX_DEPTH`] levels deep.
+///
+/// Elements of list have the same type, as NBT requires.
+pub fn arb_tag() -> impl Strategy<Value = Tag> {
+    arb_primitive_tag().prop_recursive(MAX_DEPTH, 64, MAX_LENGTH as u32, |inner| {
+        prop_oneof![
+            vec((arb_name(), inner.clone()), 0..MAX_LENGTH)
+                .prop_map(|tags| Tag::Compound(compound_tag(tags))),
+            vec(any::<i32>().prop_map(Tag::Int), 0..MAX_LENGTH).prop_map(Tag::List),
+            vec("\\PC{0,8}".prop_map(Tag::String), 0..MAX_LENGTH).prop_map(Tag::List),
+            vec(vec((arb_name(), inner), 0..MAX_LENGTH), 0..4).prop_map(|compounds| {
+                Tag::List(
+                    compounds
+                        .into_iter()
+                        .map(|tags| Tag::Compound(compound_tag(tags)))
+                        .collect(),
+                )
+            }),
+        ]
+    })
+}
+
+/// Strategy of compound tags with arbitrary tags.
+pub fn arb_compound_tag() -> impl Strategy<Value = CompoundTag> {
+    vec((arb_name(), arb_tag()), 0..MAX_LENGTH).prop_map(compound_tag)
+}
+
+/// Strategy of chunks in format before 1.18 with sections and arbitrary tags added
+/// to root and `Level` compounds.
+pub fn arb_chunk() -> impl Strategy<Value = CompoundTag> {
+    let arb_section = (
+        -4i8..20,
+        vec("minecraft:[a-z_]{1,12}", 1..8),
+        vec(any::<i64>(), 0..16),
+    );
+
+    (
+        1..4000i32,
+        vec(arb_section, 0..4),
+        arb_compound_tag(),
+        arb_compound_tag(),
+    )
+        .prop_map(|(data_version, sections, level_tags, root_tags)| {
+            let mut level_compound_tag = CompoundTag::new();
+            level_compound_tag.insert_str("Status", "full");
+
+            let sections: Vec<_> = sections
+                .into_iter()
+                .map(|(y, names, block_states)| {
+                    let palette: Vec<_> = names
+                        .into_iter()
+                        .map(|name| {
+                            let mut block_state = CompoundTag::new();
+                            block_state.insert_str("Name", name);
+                            block_state
+                        })
+                        .collect();
+
+                    let mut section = CompoundTag::new();
+                    section.insert_i8("Y", y);
+                    section.insert_compound_tag_vec("Palette", palette);
+                    section.insert_i64_vec("BlockStates", block_states);
+                    section
+                })
+                .collect();
+            level_compound_tag.insert_compound_tag_vec("Sections", sections);
+            extend(&mut level_compound_tag, level_tags);
+
+            let mut chunk_compound_tag = CompoundTag::new();
+            chunk_compound_tag.insert_i32("DataVersion", data_version);
+            chunk_compound_tag.insert_compound_tag("Level", level_compound_tag);
+            extend(&mut chunk_compound_tag, root_tags);
+
+            chunk_compound_tag
+        })
+}
+
+/// Asserts that compound tag is encoded and decoded without any change.
+///
+/// # Panics
+///
+/// Panics with differences if compound tag changes or can't be encoded.
+pub fn assert_compound_tag_roundtrip(compound_tag: &CompoundTag) {
+    let roundtrip_report = match verify_compound_tag_roundtrip(compound_tag) {
+        Ok(roundtrip_report) => roundtrip_report,
+        Err(roundtrip_error) => panic!("round trip failed: {:?}", roundtrip_error),
+    };
+
+    assert!(
+        roundtrip_report.is_faithful(),
+        "compound tag changed in round trip: {:?}",
+        roundtrip_report
+    );
+}
+
+/// Asserts that chunks saved into region files and loaded from their contents are
+/// the same, chunks at equal positions are overwritten by the last one.
+///
+/// # Panics
+///
+/// Panics with position and differences of the first chunk which changed.
+pub fn assert_region_roundtrip(chunks: &[(i32, i32, CompoundTag)]) {
+    let mut chunk_provider = InMemoryChunkProvider::new();
+
+    for (chunk_x, chunk_z, chunk_compound_tag) in chunks {
+        if let Err(chunk_save_error) =
+            chunk_provider.save_chunk(*chunk_x, *chunk_z, chunk_compound_tag.clone())
+        {
+            panic!(
+                "chunk {} {} can't be saved: {:?}",
+                chunk_x, chunk_z, chunk_save_error
+            );
+        }
+    }
+
+    // Chunks are loaded from region file contents, not from provider which saved them.
+    let mut loaded_chunk_provider = InMemoryChunkProvider::new();
+
+    for (region_x, region_z) in chunk_provider.region_positions() {
+        let region_bytes = chunk_provider.region_bytes(region_x, region_z).unwrap();
+
+        if let Err(chunk_load_error) =
+            loaded_chunk_provider.insert_region(region_x, region_z, &region_bytes)
+        {
+            panic!(
+                "region {} {} can't be read: {:?}",
+                region_x, region_z, chunk_load_error
+            );
+        }
+    }
+
+    for (index, (chunk_x, chunk_z, chunk_compound_tag)) in chunks.iter().enumerate() {
+        let overwritten = chunks[index + 1..]
+            .iter()
+            .any(|(other_x, other_z, _)| (other_x, other_z) == (chunk_x, chunk_z));
+
+        if overwritten {
+            continue;
+        }
+
+        let loaded_compound_tag = match loaded_chunk_provider.load_chunk(*chunk_x, *chunk_z) {
+            Ok(loaded_compound_tag) => loaded_compound_tag,
+            Err(chunk_load_error) => panic!(
+                "chunk {} {} can't be loaded: {:?}",
+                chunk_x, chunk_z, chunk_load_error
+            ),
+        };
+
+        let differences = diff(chunk_compound_tag, &loaded_compound_tag);
+
+        assert!(
+            differences.is_empty(),
+            "chunk {} {} changed in round trip: {:?}",
+            chunk_x,
+            chunk_z,
+            differences
+        );
+    }
+}
+
+/// Collects named tags into compound, later tags replace earlier ones with same name.
+fn compound_tag(tags: Vec<(String, Tag)>) -> CompoundTag {
+    let mut compound_tag = CompoundTag::new();
+
+    for (name, tag) in tags {
+        compound_tag.insert(name, tag);
+    }
+
+    compound_tag
+}
+
+/// Adds tags which aren't present in compound yet.
+fn extend(compound_tag: &mut CompoundTag, tags: CompoundTag) {
+    for (name, tag) in tags.iter() {
+        if !compound_tag.contains_key(name) {
+            compound_tag.insert(name, tag.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::proptest_interop::{
+        arb_chunk, arb_compound_tag, arb_tag, assert_compound_tag_roundtrip,
+        assert_region_roundtrip,
+    };
+    use nbt::Tag;
+    use proptest::prelude::*;
+
+    fn has_homogeneous_lists(tag: &Tag) -> bool {
+        match tag {
+            Tag::List(tags) => {
+                tags.windows(2).all(|pair| {
+                    std::mem::discriminant(&pair[0]) == std::mem::discriminant(&pair[1])
+                }) && tags.iter().all(has_homogeneous_lists)
+            }
+            Tag::Compound(compound_tag) => compound_tag
+                .iter()
+                .all(|(_, tag)| has_homogeneous_lists(tag)),
+            _ => true,
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn test_arb_tag_lists(tag in arb_tag()) {
+            prop_assert!(has_homogeneous_lists(&tag));
+        }
+
+        #[test]
+        fn test_compound_tag_roundtrip(compound_tag in arb_compound_tag()) {
+            assert_compound_tag_roundtrip(&compound_tag);
+        }
+
+        #[test]
+        fn test_region_roundtrip(
+            chunks in proptest::collection::vec((-40..40i32, -40..40i32, arb_chunk()), 1..6)
+        ) {
+            assert_region_roundtrip(&chunks);
+        }
+    }
+}
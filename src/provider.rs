@@ -0,0 +1,97 @@
+//! Traits of chunk storages, so tools can work with region layouts other than Anvil.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::provider::ChunkProvider;
+//! use anvil_region::AnvilChunkProvider;
+//!
+//! fn count_chunks<P: ChunkProvider>(chunk_provider: &P) -> usize {
+//!     chunk_provider.chunk_positions().map_or(0, |positions| positions.len())
+//! }
+//!
+//! assert!(count_chunks(&AnvilChunkProvider::new("test/region")) > 0);
+//! ```
+use crate::{AnvilChunkProvider, ChunkLoadError, ChunkSaveError};
+use nbt::CompoundTag;
+use std::io;
+
+/// Storage of chunk columns addressed by x and z coordinates.
+pub trait ChunkProvider {
+    /// Error while loading chunk.
+    type LoadError;
+    /// Error while saving or deleting chunk.
+    type SaveError;
+
+    /// Loads chunk from the specified coordinates.
+    fn load_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<CompoundTag, Self::LoadError>;
+
+    /// Saves chunk to the specified coordinates.
+    fn save_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        chunk_compound_tag: CompoundTag,
+    ) -> Result<(), Self::SaveError>;
+
+    /// Deletes chunk, returns false if chunk is not present.
+    fn delete_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<bool, Self::SaveError>;
+
+    /// Returns coordinates of chunks which are present.
+    fn chunk_positions(&self) -> Result<Vec<(i32, i32)>, io::Error>;
+}
+
+impl ChunkProvider for AnvilChunkProvider<'_> {
+    type LoadError = ChunkLoadError;
+    type SaveError = ChunkSaveError;
+
+    fn load_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<CompoundTag, ChunkLoadError> {
+        AnvilChunkProvider::load_chunk(self, chunk_x, chunk_z)
+    }
+
+    fn save_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        chunk_compound_tag: CompoundTag,
+    ) -> Result<(), ChunkSaveError> {
+        AnvilChunkProvider::save_chunk(self, chunk_x, chunk_z, chunk_compound_tag)
+    }
+
+    fn delete_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<bool, ChunkSaveError> {
+        AnvilChunkProvider::delete_chunk(self, chunk_x, chunk_z)
+    }
+
+    fn chunk_positions(&self) -> Result<Vec<(i32, i32)>, io::Error> {
+        AnvilChunkProvider::chunk_positions(self)
+    }
+}
+
+/// Storage of cubes, which extends chunk columns with y coordinate.
+///
+/// Every column of cube storage keeps data of the whole column like biomes and
+/// heightmaps, while blocks are stored in 16 blocks high cubes.
+pub trait CubeProvider: ChunkProvider {
+    /// Loads cube from the specified coordinates.
+    fn load_cube(
+        &self,
+        cube_x: i32,
+        cube_y: i32,
+        cube_z: i32,
+    ) -> Result<CompoundTag, Self::LoadError>;
+
+    /// Saves cube to the specified coordinates.
+    fn save_cube(
+        &self,
+        cube_x: i32,
+        cube_y: i32,
+        cube_z: i32,
+        cube_compound_tag: CompoundTag,
+    ) -> Result<(), Self::SaveError>;
+
+    /// Deletes cube, returns false if cube is not present.
+    fn delete_cube(&self, cube_x: i32, cube_y: i32, cube_z: i32) -> Result<bool, Self::SaveError>;
+
+    /// Returns coordinates of cubes which are present.
+    fn cube_positions(&self) -> Result<Vec<(i32, i32, i32)>, io::Error>;
+}
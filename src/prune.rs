@@ -0,0 +1,286 @@
+//! Removing chunks which players barely visited.
+//!
+//! Worlds grow with chunks generated while flying through, which are rarely visited
+//! again. Such chunks have low `InhabitedTime` and are regenerated by the game when
+//! missing, so deleting them frees disk space without losing player builds.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::prune::{prune_provider, PruneOptions};
+//! use anvil_region::AnvilChunkProvider;
+//! use std::time::SystemTime;
+//!
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//! let options = PruneOptions::new(600, SystemTime::now()).dry_run();
+//!
+//! if let Ok(prune_report) = prune_provider(&chunk_provider, &options) {
+//!     println!("Can reclaim {} bytes", prune_report.reclaimable_bytes);
+//! }
+//! ```
+use crate::cancel::CancellationToken;
+use crate::chunk::Chunk;
+use crate::progress::{ProgressReporter, ProgressTracker};
+use crate::{
+    z_order_key, AnvilChunkProvider, ChunkLoadError, ChunkSaveError, REGION_SECTOR_BYTES_LENGTH,
+};
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Possible errors while pruning chunks.
+#[derive(Debug)]
+pub enum PruneError {
+    /// Chunk can't be loaded.
+    ChunkLoadError { chunk_load_error: ChunkLoadError },
+    /// Chunk can't be deleted.
+    ChunkSaveError { chunk_save_error: ChunkSaveError },
+    /// Region folder can't be read.
+    ReadError { io_error: io::Error },
+    /// Pruning was cancelled, chunks pruned before cancellation stay deleted.
+    Cancelled,
+}
+
+impl From<ChunkLoadError> for PruneError {
+    fn from(chunk_load_error: ChunkLoadError) -> Self {
+        PruneError::ChunkLoadError { chunk_load_error }
+    }
+}
+
+impl From<ChunkSaveError> for PruneError {
+    fn from(chunk_save_error: ChunkSaveError) -> Self {
+        PruneError::ChunkSaveError { chunk_save_error }
+    }
+}
+
+impl From<io::Error> for PruneError {
+    fn from(io_error: io::Error) -> Self {
+        PruneError::ReadError { io_error }
+    }
+}
+
+/// Conditions which chunk must satisfy to be pruned.
+#[derive(Debug, Clone)]
+pub struct PruneOptions {
+    /// Chunks with `InhabitedTime` in ticks below this value are pruned.
+    pub max_inhabited_time: i64,
+    /// Chunks saved at or after this time are kept.
+    pub modified_before: SystemTime,
+    /// Only report chunks which would be pruned.
+    pub dry_run: bool,
+    /// Token which stops pruning when cancelled.
+    pub cancellation_token: Option<CancellationToken>,
+    /// Reporter which receives progress after every chunk.
+    pub progress_reporter: Option<ProgressReporter>,
+}
+
+impl PruneOptions {
+    pub fn new(max_inhabited_time: i64, modified_before: SystemTime) -> Self {
+        PruneOptions {
+            max_inhabited_time,
+            modified_before,
+            dry_run: false,
+            cancellation_token: None,
+            progress_reporter: None,
+        }
+    }
+
+    /// Enables dry run mode in which chunks are not deleted.
+    pub fn dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+
+    /// Stops pruning with [`PruneError::Cancelled`] once token is cancelled.
+    pub fn cancellable(mut self, cancellation_token: CancellationToken) -> Self {
+        self.cancellation_token = Some(cancellation_token);
+        self
+    }
+
+    /// Reports progress of pruning to reporter.
+    pub fn with_progress(mut self, progress_reporter: ProgressReporter) -> Self {
+        self.progress_reporter = Some(progress_reporter);
+        self
+    }
+}
+
+/// Result of pruning.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PruneReport {
+    /// Sorted positions of pruned chunks.
+    pub pruned_chunks: Vec<(i32, i32)>,
+    /// Amount of bytes in region files used by pruned chunks.
+    pub reclaimable_bytes: u64,
+}
+
+/// Deletes chunks which aren't inhabited long enough and weren't saved recently.
+///
+/// Chunks without `InhabitedTime` are kept. Region files are not shrunk, freed
+/// sectors are reused by following saves.
+pub fn prune_provider(
+    chunk_provider: &AnvilChunkProvider,
+    options: &PruneOptions,
+) -> Result<PruneReport, PruneError> {
+    let modified_before = options
+        .modified_before
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+
+    let mut prune_report = PruneReport::default();
+    let mut progress_tracker = ProgressTracker::new(options.progress_reporter.clone());
+
+    for region_file in chunk_provider.region_files()? {
+        progress_tracker.add_region(&region_file.path);
+    }
+
+    let mut chunk_positions = chunk_provider.chunk_positions()?;
+
+    // Chunks are visited region by region, so progress advances region after region.
+    chunk_positions.sort_unstable_by_key(|&(chunk_x, chunk_z)| z_order_key(chunk_x, chunk_z));
+
+    let mut region_position = None;
+
+    for (chunk_x, chunk_z) in chunk_positions {
+        if let Some(cancellation_token) = &options.cancellation_token {
+            if cancellation_token.is_cancelled() {
+                return Err(PruneError::Cancelled);
+            }
+        }
+
+        if region_position != Some((chunk_x >> 5, chunk_z >> 5)) {
+            region_position = Some((chunk_x >> 5, chunk_z >> 5));
+
+            let region_name = format!("r.{}.{}.mca", chunk_x >> 5, chunk_z >> 5);
+            progress_tracker.start_region(&chunk_provider.folder_path.join(region_name));
+        }
+
+        progress_tracker.chunk(chunk_x, chunk_z);
+
+        let metadata = match chunk_provider.chunk_metadata(chunk_x, chunk_z)? {
+            Some(metadata) => metadata,
+            None => continue,
+        };
+
+        if metadata.last_modified_timestamp as u64 >= modified_before {
+            continue;
+        }
+
+        let chunk = Chunk::new(chunk_provider.load_chunk(chunk_x, chunk_z)?);
+
+        match chunk.data().get_i64("InhabitedTime") {
+            Ok(inhabited_time) if inhabited_time < options.max_inhabited_time => {}
+            _ => continue,
+        }
+
+        if !options.dry_run {
+            chunk_provider.delete_chunk(chunk_x, chunk_z)?;
+        }
+
+        prune_report.pruned_chunks.push((chunk_x, chunk_z));
+        prune_report.reclaimable_bytes +=
+            metadata.sectors as u64 * REGION_SECTOR_BYTES_LENGTH as u64;
+    }
+
+    progress_tracker.finish();
+    prune_report.pruned_chunks.sort_unstable();
+
+    Ok(prune_report)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cancel::CancellationToken;
+    use crate::prune::{prune_provider, PruneError, PruneOptions};
+    use crate::AnvilChunkProvider;
+    use nbt::CompoundTag;
+    use std::time::{Duration, SystemTime};
+    use tempfile::TempDir;
+
+    fn provider_with_chunks(temp_dir: &TempDir, inhabited_times: &[(i32, i32, i64)]) {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+        let target_chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+        let mut chunk_compound_tag = chunk_provider.load_chunk(4, 2).unwrap();
+
+        for (chunk_x, chunk_z, inhabited_time) in inhabited_times {
+            let level_compound_tag = chunk_compound_tag
+                .get_mut::<&mut CompoundTag>("Level")
+                .unwrap();
+            level_compound_tag.insert_i32("xPos", *chunk_x);
+            level_compound_tag.insert_i32("zPos", *chunk_z);
+            level_compound_tag.insert_i64("InhabitedTime", *inhabited_time);
+
+            target_chunk_provider
+                .save_chunk(*chunk_x, *chunk_z, chunk_compound_tag.clone())
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_prune_provider_dry_run() {
+        let temp_dir = TempDir::new().unwrap();
+        provider_with_chunks(&temp_dir, &[(0, 0, 10), (1, 0, 5000)]);
+
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+        let modified_before = SystemTime::now() + Duration::from_secs(60);
+        let options = PruneOptions::new(600, modified_before).dry_run();
+
+        let prune_report = prune_provider(&chunk_provider, &options).unwrap();
+
+        assert_eq!(prune_report.pruned_chunks, vec![(0, 0)]);
+        assert!(prune_report.reclaimable_bytes > 0);
+        assert_eq!(prune_report.reclaimable_bytes % 4096, 0);
+        assert_eq!(chunk_provider.chunk_positions().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_prune_provider() {
+        let temp_dir = TempDir::new().unwrap();
+        provider_with_chunks(&temp_dir, &[(0, 0, 10), (1, 0, 5000), (2, 0, 0)]);
+
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+        let modified_before = SystemTime::now() + Duration::from_secs(60);
+        let options = PruneOptions::new(600, modified_before);
+
+        let prune_report = prune_provider(&chunk_provider, &options).unwrap();
+
+        assert_eq!(prune_report.pruned_chunks, vec![(0, 0), (2, 0)]);
+        assert_eq!(chunk_provider.chunk_positions().unwrap(), vec![(1, 0)]);
+        assert!(chunk_provider.load_chunk(1, 0).is_ok());
+    }
+
+    #[test]
+    fn test_prune_provider_keeps_recent_chunks() {
+        let temp_dir = TempDir::new().unwrap();
+        provider_with_chunks(&temp_dir, &[(0, 0, 10)]);
+
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+        let modified_before = SystemTime::now() - Duration::from_secs(3600);
+        let options = PruneOptions::new(600, modified_before);
+
+        let prune_report = prune_provider(&chunk_provider, &options).unwrap();
+
+        assert!(prune_report.pruned_chunks.is_empty());
+        assert_eq!(chunk_provider.chunk_positions().unwrap(), vec![(0, 0)]);
+    }
+
+    #[test]
+    fn test_prune_provider_cancelled() {
+        let temp_dir = TempDir::new().unwrap();
+        provider_with_chunks(&temp_dir, &[(0, 0, 10)]);
+
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+        let modified_before = SystemTime::now() + Duration::from_secs(60);
+        let cancellation_token = CancellationToken::new();
+        let options =
+            PruneOptions::new(600, modified_before).cancellable(cancellation_token.clone());
+
+        cancellation_token.cancel();
+
+        match prune_provider(&chunk_provider, &options) {
+            Err(PruneError::Cancelled) => {}
+            result => panic!("Expected `Cancelled` but got `{:?}`", result),
+        }
+
+        assert_eq!(chunk_provider.chunk_positions().unwrap(), vec![(0, 0)]);
+    }
+}
@@ -0,0 +1,307 @@
+//! Python module exposing chunk providers, built by maturin from `pyproject.toml`.
+//!
+//! Chunks are loaded as bytes of uncompressed NBT or as dicts. Dicts follow typed
+//! JSON encoding of [`dump`] module: ints are `Int` tags, floats are `Double` tags
+//! and other number types are single entry dicts like `{"$byte": 1}`, so chunks are
+//! saved back without losing types.
+//!
+//! [`dump`]: crate::dump
+//!
+//! ```python
+//! import anvil_region
+//!
+//! provider = anvil_region.ChunkProvider("world/region")
+//! chunk = provider.load_chunk(4, 2)
+//! chunk["Level"]["LastUpdate"] = {"$long": 0}
+//! provider.save_chunk(4, 2, chunk)
+//! ```
+use crate::dump::{compound_tag_to_json, json_to_compound_tag, DumpError};
+use crate::json::JsonValue;
+use crate::stats::provider_stats;
+use crate::{AnvilChunkProvider, ChunkLoadError, ChunkSaveError};
+use nbt::decode::read_compound_tag;
+use nbt::encode::write_compound_tag;
+use nbt::CompoundTag;
+use pyo3::exceptions::{PyIOError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyBytes, PyDict, PyFloat, PyInt, PyList, PyString, PyTuple};
+use std::io;
+use std::path::PathBuf;
+
+/// Provider of chunks of region folder.
+#[pyclass(name = "ChunkProvider")]
+pub struct PyChunkProvider {
+    folder_path: PathBuf,
+}
+
+impl PyChunkProvider {
+    fn chunk_provider(&self) -> AnvilChunkProvider<'_> {
+        AnvilChunkProvider::from_path(&self.folder_path)
+    }
+
+    /// Loads chunk, none if region or chunk is not present.
+    fn load(&self, chunk_x: i32, chunk_z: i32) -> PyResult<Option<CompoundTag>> {
+        match self.chunk_provider().load_chunk(chunk_x, chunk_z) {
+            Ok(chunk_compound_tag) => Ok(Some(chunk_compound_tag)),
+            Err(ChunkLoadError::RegionNotFound { .. })
+            | Err(ChunkLoadError::ChunkNotFound { .. }) => Ok(None),
+            Err(chunk_load_error) => Err(PyIOError::new_err(chunk_load_error.to_string())),
+        }
+    }
+
+    fn save(&self, chunk_x: i32, chunk_z: i32, chunk_compound_tag: CompoundTag) -> PyResult<()> {
+        self.chunk_provider()
+            .save_chunk(chunk_x, chunk_z, chunk_compound_tag)
+            .map_err(save_error)
+    }
+}
+
+#[pymethods]
+impl PyChunkProvider {
+    #[new]
+    fn new(folder_path: PathBuf) -> Self {
+        PyChunkProvider { folder_path }
+    }
+
+    #[getter]
+    fn folder_path(&self) -> PathBuf {
+        self.folder_path.clone()
+    }
+
+    /// Loads chunk as dict, none if chunk is not present.
+    fn load_chunk(&self, py: Python<'_>, chunk_x: i32, chunk_z: i32) -> PyResult<PyObject> {
+        match self.load(chunk_x, chunk_z)? {
+            Some(chunk_compound_tag) => {
+                json_to_python(py, &compound_tag_to_json(&chunk_compound_tag))
+            }
+            None => Ok(py.None()),
+        }
+    }
+
+    /// Loads chunk as bytes of uncompressed NBT, none if chunk is not present.
+    fn load_chunk_bytes(&self, py: Python<'_>, chunk_x: i32, chunk_z: i32) -> PyResult<PyObject> {
+        let chunk_compound_tag = match self.load(chunk_x, chunk_z)? {
+            Some(chunk_compound_tag) => chunk_compound_tag,
+            None => return Ok(py.None()),
+        };
+
+        let mut data = Vec::new();
+        write_compound_tag(&mut data, &chunk_compound_tag).map_err(io_error)?;
+
+        Ok(PyBytes::new(py, &data).into_any().unbind())
+    }
+
+    /// Saves chunk from dict in format returned by `load_chunk`.
+    fn save_chunk(&self, chunk_x: i32, chunk_z: i32, chunk: &Bound<'_, PyAny>) -> PyResult<()> {
+        let chunk_compound_tag =
+            json_to_compound_tag(&python_to_json(chunk)?).map_err(dump_error)?;
+
+        self.save(chunk_x, chunk_z, chunk_compound_tag)
+    }
+
+    /// Saves chunk from bytes of uncompressed NBT.
+    fn save_chunk_bytes(&self, chunk_x: i32, chunk_z: i32, data: &[u8]) -> PyResult<()> {
+        let chunk_compound_tag = read_compound_tag(&mut &data[..])
+            .map_err(|tag_decode_error| PyValueError::new_err(tag_decode_error.to_string()))?;
+
+        self.save(chunk_x, chunk_z, chunk_compound_tag)
+    }
+
+    /// Deletes chunk, returns false if chunk is not present.
+    fn delete_chunk(&self, chunk_x: i32, chunk_z: i32) -> PyResult<bool> {
+        self.chunk_provider()
+            .delete_chunk(chunk_x, chunk_z)
+            .map_err(save_error)
+    }
+
+    /// Returns coordinates of chunks which are present.
+    fn chunk_positions(&self) -> PyResult<Vec<(i32, i32)>> {
+        self.chunk_provider().chunk_positions().map_err(io_error)
+    }
+
+    /// Returns time in seconds since Unix epoch when chunk was last saved.
+    fn chunk_last_modified(&self, chunk_x: i32, chunk_z: i32) -> PyResult<Option<u32>> {
+        self.chunk_provider()
+            .chunk_last_modified(chunk_x, chunk_z)
+            .map_err(io_error)
+    }
+
+    /// Returns statistics of chunks and sector usage of every region file.
+    fn stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let provider_stats = provider_stats(&self.chunk_provider()).map_err(io_error)?;
+        let stats = PyDict::new(py);
+
+        stats.set_item("chunk_count", provider_stats.chunk_count)?;
+        stats.set_item("compressed_bytes", provider_stats.compressed_bytes)?;
+        stats.set_item("uncompressed_bytes", provider_stats.uncompressed_bytes)?;
+        stats.set_item("data_versions", provider_stats.data_versions.clone())?;
+        stats.set_item("unreadable_chunks", provider_stats.unreadable_chunks)?;
+        stats.set_item("fragmentation", provider_stats.fragmentation())?;
+
+        let regions = PyList::empty(py);
+
+        for region_stats in &provider_stats.regions {
+            let region = PyDict::new(py);
+            region.set_item("region_x", region_stats.region_x)?;
+            region.set_item("region_z", region_stats.region_z)?;
+            region.set_item("chunk_count", region_stats.chunk_count)?;
+            region.set_item("total_sectors", region_stats.total_sectors)?;
+            region.set_item("free_sectors", region_stats.free_sectors)?;
+            region.set_item("fragmentation", region_stats.fragmentation())?;
+            regions.append(region)?;
+        }
+
+        stats.set_item("regions", regions)?;
+
+        Ok(stats)
+    }
+}
+
+/// Module `anvil_region` of Python.
+#[pymodule]
+fn anvil_region(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyChunkProvider>()
+}
+
+fn json_to_python(py: Python<'_>, json_value: &JsonValue) -> PyResult<PyObject> {
+    let object = match json_value {
+        JsonValue::Null => py.None(),
+        JsonValue::Bool(value) => PyBool::new(py, *value).to_owned().into_any().unbind(),
+        JsonValue::Integer(value) => value.into_pyobject(py)?.into_any().unbind(),
+        JsonValue::Float(value) => PyFloat::new(py, *value).into_any().unbind(),
+        JsonValue::String(value) => PyString::new(py, value).into_any().unbind(),
+        JsonValue::Array(values) => {
+            let list = PyList::empty(py);
+
+            for value in values {
+                list.append(json_to_python(py, value)?)?;
+            }
+
+            list.into_any().unbind()
+        }
+        JsonValue::Object(entries) => {
+            let dict = PyDict::new(py);
+
+            for (key, value) in entries {
+                dict.set_item(key, json_to_python(py, value)?)?;
+            }
+
+            dict.into_any().unbind()
+        }
+    };
+
+    Ok(object)
+}
+
+fn python_to_json(object: &Bound<'_, PyAny>) -> PyResult<JsonValue> {
+    // Bool is checked first since it's subclass of int.
+    if object.is_none() {
+        Ok(JsonValue::Null)
+    } else if let Ok(value) = object.downcast::<PyBool>() {
+        Ok(JsonValue::Bool(value.is_true()))
+    } else if object.is_instance_of::<PyInt>() {
+        Ok(JsonValue::Integer(object.extract()?))
+    } else if object.is_instance_of::<PyFloat>() {
+        Ok(JsonValue::Float(object.extract()?))
+    } else if let Ok(value) = object.downcast::<PyString>() {
+        Ok(JsonValue::String(value.to_str()?.to_owned()))
+    } else if let Ok(dict) = object.downcast::<PyDict>() {
+        let mut entries = Vec::with_capacity(dict.len());
+
+        for (key, value) in dict.iter() {
+            entries.push((key.extract()?, python_to_json(&value)?));
+        }
+
+        Ok(JsonValue::Object(entries))
+    } else if object.is_instance_of::<PyList>() || object.is_instance_of::<PyTuple>() {
+        let values = object
+            .try_iter()?
+            .map(|value| python_to_json(&value?))
+            .collect::<PyResult<_>>()?;
+
+        Ok(JsonValue::Array(values))
+    } else {
+        Err(PyTypeError::new_err(format!(
+            "{} can't be converted into tag",
+            object.get_type().name()?
+        )))
+    }
+}
+
+fn io_error(io_error: io::Error) -> PyErr {
+    PyIOError::new_err(io_error.to_string())
+}
+
+fn save_error(chunk_save_error: ChunkSaveError) -> PyErr {
+    PyIOError::new_err(chunk_save_error.to_string())
+}
+
+fn dump_error(dump_error: DumpError) -> PyErr {
+    match dump_error {
+        DumpError::InvalidJsonValue { path } => {
+            PyValueError::new_err(format!("value at {:?} can't be converted into tag", path))
+        }
+        dump_error => PyValueError::new_err(format!("{:?}", dump_error)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::python::PyChunkProvider;
+    use pyo3::types::{PyAnyMethods, PyDict, PyDictMethods};
+    use pyo3::Python;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_load_and_save_dict() {
+        pyo3::prepare_freethreaded_python();
+
+        let temp_dir = TempDir::new().unwrap();
+        let fixture_provider = PyChunkProvider::new(PathBuf::from("test/region"));
+        let provider = PyChunkProvider::new(temp_dir.path().to_path_buf());
+
+        Python::with_gil(|py| {
+            let chunk = fixture_provider.load_chunk(py, 4, 2).unwrap();
+            let chunk = chunk.bind(py).downcast::<PyDict>().unwrap();
+            let level = chunk.get_item("Level").unwrap().unwrap();
+
+            assert_eq!(level.get_item("xPos").unwrap().extract::<i32>().unwrap(), 4);
+            assert!(level
+                .get_item("LastUpdate")
+                .unwrap()
+                .downcast::<PyDict>()
+                .unwrap()
+                .contains("$long")
+                .unwrap());
+
+            provider.save_chunk(4, 2, chunk.as_any()).unwrap();
+
+            let bytes = provider.load_chunk_bytes(py, 4, 2).unwrap();
+            let fixture_bytes = fixture_provider.load_chunk_bytes(py, 4, 2).unwrap();
+            assert!(bytes.bind(py).eq(fixture_bytes.bind(py)).unwrap());
+
+            assert!(fixture_provider
+                .load_chunk(py, 100, 100)
+                .unwrap()
+                .is_none(py));
+        });
+    }
+
+    #[test]
+    fn test_invalid_dict() {
+        pyo3::prepare_freethreaded_python();
+
+        let temp_dir = TempDir::new().unwrap();
+        let provider = PyChunkProvider::new(temp_dir.path().to_path_buf());
+
+        Python::with_gil(|py| {
+            let chunk = PyDict::new(py);
+            chunk.set_item("Level", vec![1.5]).unwrap();
+            chunk.set_item("Flag", true).unwrap();
+
+            let error = provider.save_chunk(0, 0, chunk.as_any()).unwrap_err();
+            assert!(error.to_string().contains("Flag"));
+        });
+    }
+}
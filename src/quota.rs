@@ -0,0 +1,639 @@
+//! Chunk provider decorator which caps size and chunk count of region folders.
+//!
+//! Hosting platforms give every user world a limited amount of storage.
+//! [`QuotaChunkProvider`] wraps provider of region files, counts their lengths and
+//! chunks into [`StorageQuota`] and rejects saves which would exceed its limits with
+//! [`QuotaExceeded`] error, so the cap holds no matter what server writes. Providers of
+//! terrain, entity and point of interest folders of every dimension can share one
+//! quota to cap the whole world. Files other than region files aren't counted.
+//!
+//! Region files never shrink, so deleting chunks frees chunk count, but not bytes until
+//! region files are compacted.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::provider::ChunkProvider;
+//! use anvil_region::quota::{QuotaChunkProvider, QuotaExceeded, QuotaLimits, StorageQuota};
+//! use anvil_region::{AnvilChunkProvider, ChunkSaveError};
+//! use nbt::CompoundTag;
+//! use std::sync::Arc;
+//! use tempfile::TempDir;
+//!
+//! let region_dir = TempDir::new().unwrap();
+//! let quota = Arc::new(StorageQuota::new(QuotaLimits::new().max_chunks(1)));
+//! let anvil_chunk_provider = AnvilChunkProvider::new(region_dir.path().to_str().unwrap());
+//! let chunk_provider = QuotaChunkProvider::new(anvil_chunk_provider, quota).unwrap();
+//!
+//! chunk_provider.save_chunk(4, 2, CompoundTag::new()).unwrap();
+//!
+//! match chunk_provider.save_chunk(5, 2, CompoundTag::new()) {
+//!     Err(ChunkSaveError::QuotaExceeded {
+//!         quota_exceeded: QuotaExceeded::Chunks { max_chunks: 1 },
+//!     }) => {}
+//!     result => panic!("Expected `QuotaExceeded` but got `{:?}`", result),
+//! }
+//! ```
+use crate::hash::{CoordinateHashMap, CoordinateHashSet};
+use crate::provider::ChunkProvider;
+use crate::{
+    encode_chunk, AnvilChunkProvider, REGION_HEADER_BYTES_LENGTH, REGION_SECTOR_BYTES_LENGTH,
+};
+use nbt::CompoundTag;
+use std::error::Error;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::{fmt, fs, io};
+
+/// Limits of quota, none of them is enforced if not set.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QuotaLimits {
+    /// Total length of region files.
+    pub max_bytes: Option<u64>,
+    /// Total amount of chunks.
+    pub max_chunks: Option<usize>,
+}
+
+impl QuotaLimits {
+    pub fn new() -> Self {
+        QuotaLimits::default()
+    }
+
+    pub fn max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    pub fn max_chunks(mut self, max_chunks: usize) -> Self {
+        self.max_chunks = Some(max_chunks);
+        self
+    }
+}
+
+/// Limit which save would exceed.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum QuotaExceeded {
+    Bytes { max_bytes: u64 },
+    Chunks { max_chunks: usize },
+}
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuotaExceeded::Bytes { max_bytes } => {
+                write!(f, "region files would grow past {} bytes", max_bytes)
+            }
+            QuotaExceeded::Chunks { max_chunks } => {
+                write!(f, "more than {} chunks would be stored", max_chunks)
+            }
+        }
+    }
+}
+
+impl Error for QuotaExceeded {}
+
+/// Storage used by region folders.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QuotaUsage {
+    /// Total length of region files.
+    pub bytes: u64,
+    /// Total amount of chunks.
+    pub chunks: usize,
+}
+
+/// Usage of quota with usage reserved by saves which are being written.
+#[derive(Debug, Default)]
+struct QuotaState {
+    usage: QuotaUsage,
+    reserved: QuotaUsage,
+}
+
+/// Limits and usage shared by providers of quota.
+#[derive(Debug)]
+pub struct StorageQuota {
+    limits: QuotaLimits,
+    /// Held only while usage is reserved or counted, not while chunks are written.
+    state: Mutex<QuotaState>,
+}
+
+impl StorageQuota {
+    pub fn new(limits: QuotaLimits) -> Self {
+        StorageQuota {
+            limits,
+            state: Mutex::new(QuotaState::default()),
+        }
+    }
+
+    pub fn limits(&self) -> &QuotaLimits {
+        &self.limits
+    }
+
+    /// Returns storage used by region folders of open providers.
+    pub fn usage(&self) -> QuotaUsage {
+        self.lock_state().usage
+    }
+
+    fn lock_state(&self) -> MutexGuard<'_, QuotaState> {
+        self.state.lock().unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+/// Chunk provider which stores chunks in region files, so quota can count their lengths.
+pub trait RegionStorage: ChunkProvider {
+    /// Returns length of region file at the specified region coordinates, zero if it
+    /// doesn't exist.
+    fn region_length(&self, region_x: i32, region_z: i32) -> Result<u64, io::Error>;
+
+    /// Returns region coordinates of region files which exist.
+    fn region_positions(&self) -> Result<Vec<(i32, i32)>, io::Error>;
+
+    /// Returns true if chunk at the specified coordinates is present.
+    fn has_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<bool, io::Error>;
+}
+
+impl RegionStorage for AnvilChunkProvider<'_> {
+    fn region_length(&self, region_x: i32, region_z: i32) -> Result<u64, io::Error> {
+        let region_name = format!("r.{}.{}.mca", region_x, region_z);
+
+        match fs::metadata(self.folder_path.join(region_name)) {
+            Ok(metadata) => Ok(metadata.len()),
+            Err(io_error) if io_error.kind() == io::ErrorKind::NotFound => Ok(0),
+            Err(io_error) => Err(io_error),
+        }
+    }
+
+    fn region_positions(&self) -> Result<Vec<(i32, i32)>, io::Error> {
+        let region_files = self.region_files()?;
+
+        Ok(region_files
+            .into_iter()
+            .map(|region_file| region_file.region_position)
+            .collect())
+    }
+
+    fn has_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<bool, io::Error> {
+        Ok(self.chunk_last_modified(chunk_x, chunk_z)?.is_some())
+    }
+}
+
+/// Usage of provider with lengths of region files it was counted from.
+#[derive(Debug, Default)]
+struct ProviderUsage {
+    usage: QuotaUsage,
+    region_lengths: CoordinateHashMap<(i32, i32), u64>,
+    /// Chunks which saves reserved new chunk, counted by save which reserved it.
+    new_chunks: CoordinateHashSet<(i32, i32)>,
+}
+
+/// Chunk provider which enforces storage quota on saves of wrapped provider.
+pub struct QuotaChunkProvider<P: RegionStorage> {
+    chunk_provider: P,
+    quota: Arc<StorageQuota>,
+    /// Usage of this provider, locked after usage of quota when both are.
+    usage: Mutex<ProviderUsage>,
+}
+
+impl<P: RegionStorage> QuotaChunkProvider<P> {
+    /// Wraps provider and counts its region files into quota.
+    ///
+    /// Region files which are already there count even if they exceed limits, so only
+    /// following saves are rejected. Usage is removed from quota when provider is
+    /// dropped, region files must not be wrapped by two providers of the same quota.
+    pub fn new(chunk_provider: P, quota: Arc<StorageQuota>) -> Result<Self, io::Error> {
+        let mut usage = ProviderUsage::default();
+        usage.usage.chunks = chunk_provider.chunk_positions()?.len();
+
+        for (region_x, region_z) in chunk_provider.region_positions()? {
+            let region_length = chunk_provider.region_length(region_x, region_z)?;
+
+            usage.usage.bytes += region_length;
+            usage
+                .region_lengths
+                .insert((region_x, region_z), region_length);
+        }
+
+        let mut quota_state = quota.lock_state();
+        quota_state.usage.bytes += usage.usage.bytes;
+        quota_state.usage.chunks += usage.usage.chunks;
+        drop(quota_state);
+
+        Ok(QuotaChunkProvider {
+            chunk_provider,
+            quota,
+            usage: Mutex::new(usage),
+        })
+    }
+
+    /// Returns wrapped provider.
+    pub fn get_ref(&self) -> &P {
+        &self.chunk_provider
+    }
+
+    pub fn quota(&self) -> &Arc<StorageQuota> {
+        &self.quota
+    }
+
+    /// Returns storage used by region files of provider.
+    pub fn usage(&self) -> QuotaUsage {
+        self.lock_usage().usage
+    }
+
+    fn lock_usage(&self) -> MutexGuard<'_, ProviderUsage> {
+        self.usage.lock().unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+impl<P> QuotaChunkProvider<P>
+where
+    P: RegionStorage,
+    P::SaveError: From<QuotaExceeded> + From<io::Error>,
+{
+    /// Reserves usage of chunk save, unless it would exceed quota.
+    ///
+    /// Returns reserved usage, which chunk count is one if save stores new chunk.
+    fn reserve(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        chunk_length: u64,
+    ) -> Result<QuotaUsage, P::SaveError> {
+        let region_length = self
+            .chunk_provider
+            .region_length(chunk_x >> 5, chunk_z >> 5)?;
+        let has_chunk = self.chunk_provider.has_chunk(chunk_x, chunk_z)?;
+
+        let mut quota_state = self.quota.lock_state();
+        let mut usage = self.lock_usage();
+
+        // Concurrent save of the same new chunk counts it.
+        let is_new_chunk = !has_chunk && !usage.new_chunks.contains(&(chunk_x, chunk_z));
+
+        if let Some(max_chunks) = self.quota.limits.max_chunks {
+            let chunks = quota_state.usage.chunks + quota_state.reserved.chunks;
+
+            if is_new_chunk && chunks >= max_chunks {
+                return Err(QuotaExceeded::Chunks { max_chunks }.into());
+            }
+        }
+
+        let sector_length = REGION_SECTOR_BYTES_LENGTH as u64;
+        let mut growth = (chunk_length + 4).div_ceil(sector_length) * sector_length;
+
+        if region_length == 0 {
+            growth += REGION_HEADER_BYTES_LENGTH;
+        }
+
+        if let Some(max_bytes) = self.quota.limits.max_bytes {
+            if quota_state.usage.bytes + quota_state.reserved.bytes + growth > max_bytes {
+                return Err(QuotaExceeded::Bytes { max_bytes }.into());
+            }
+        }
+
+        let reserved = QuotaUsage {
+            bytes: growth,
+            chunks: is_new_chunk as usize,
+        };
+
+        quota_state.reserved.bytes += reserved.bytes;
+        quota_state.reserved.chunks += reserved.chunks;
+
+        if is_new_chunk {
+            usage.new_chunks.insert((chunk_x, chunk_z));
+        }
+
+        Ok(reserved)
+    }
+
+    /// Releases reserved usage and counts usage of region file after save.
+    fn reconcile(&self, chunk_x: i32, chunk_z: i32, reserved: QuotaUsage) {
+        let region_position = (chunk_x >> 5, chunk_z >> 5);
+
+        let mut quota_state = self.quota.lock_state();
+        let mut usage = self.lock_usage();
+
+        quota_state.reserved.bytes -= reserved.bytes;
+        quota_state.reserved.chunks -= reserved.chunks;
+
+        // Region file may grow even if save fails half way. Growth is counted from
+        // length counted last time, so concurrent saves to region count it once.
+        let counted_length = usage
+            .region_lengths
+            .get(&region_position)
+            .copied()
+            .unwrap_or(0);
+        let region_length = self
+            .chunk_provider
+            .region_length(region_position.0, region_position.1)
+            .unwrap_or(counted_length)
+            .max(counted_length);
+        let grown_bytes = region_length - counted_length;
+
+        usage.region_lengths.insert(region_position, region_length);
+        usage.usage.bytes += grown_bytes;
+        quota_state.usage.bytes += grown_bytes;
+
+        if reserved.chunks > 0 {
+            usage.new_chunks.remove(&(chunk_x, chunk_z));
+
+            let has_chunk = self
+                .chunk_provider
+                .has_chunk(chunk_x, chunk_z)
+                .unwrap_or(false);
+
+            if has_chunk {
+                usage.usage.chunks += 1;
+                quota_state.usage.chunks += 1;
+            }
+        }
+    }
+}
+
+impl<P> ChunkProvider for QuotaChunkProvider<P>
+where
+    P: RegionStorage,
+    P::SaveError: From<QuotaExceeded> + From<io::Error>,
+{
+    type LoadError = P::LoadError;
+    type SaveError = P::SaveError;
+
+    fn load_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<CompoundTag, P::LoadError> {
+        self.chunk_provider.load_chunk(chunk_x, chunk_z)
+    }
+
+    /// Saves chunk to the specified coordinates, unless it would exceed quota.
+    ///
+    /// Chunk is encoded to estimate its length. Region file is expected to grow by
+    /// sectors of the whole chunk, so save is rejected near the limit even if chunk
+    /// would fit into free sectors.
+    fn save_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        chunk_compound_tag: CompoundTag,
+    ) -> Result<(), P::SaveError> {
+        let chunk_length = encode_chunk(&chunk_compound_tag)?.len() as u64;
+        let reserved = self.reserve(chunk_x, chunk_z, chunk_length)?;
+
+        let result = self
+            .chunk_provider
+            .save_chunk(chunk_x, chunk_z, chunk_compound_tag);
+
+        self.reconcile(chunk_x, chunk_z, reserved);
+
+        result
+    }
+
+    fn delete_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<bool, P::SaveError> {
+        let deleted = self.chunk_provider.delete_chunk(chunk_x, chunk_z)?;
+
+        if deleted {
+            let mut quota_state = self.quota.lock_state();
+            let mut usage = self.lock_usage();
+
+            usage.usage.chunks = usage.usage.chunks.saturating_sub(1);
+            quota_state.usage.chunks = quota_state.usage.chunks.saturating_sub(1);
+        }
+
+        Ok(deleted)
+    }
+
+    fn chunk_positions(&self) -> Result<Vec<(i32, i32)>, io::Error> {
+        self.chunk_provider.chunk_positions()
+    }
+}
+
+impl<P: RegionStorage> Drop for QuotaChunkProvider<P> {
+    fn drop(&mut self) {
+        let usage = self.usage();
+        let mut quota_state = self.quota.lock_state();
+
+        quota_state.usage.bytes = quota_state.usage.bytes.saturating_sub(usage.bytes);
+        quota_state.usage.chunks = quota_state.usage.chunks.saturating_sub(usage.chunks);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::provider::ChunkProvider;
+    use crate::quota::{
+        QuotaChunkProvider, QuotaExceeded, QuotaLimits, RegionStorage, StorageQuota,
+    };
+    use crate::{AnvilChunkProvider, ChunkLoadError, ChunkSaveError};
+    use nbt::CompoundTag;
+    use std::fs;
+    use std::io;
+    use std::sync::{Arc, Condvar, Mutex};
+    use std::thread;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    /// Provider which saves only after given amount of saves are being written at once.
+    struct GatedChunkProvider<'a> {
+        chunk_provider: AnvilChunkProvider<'a>,
+        gate: (Mutex<usize>, Condvar),
+        saves: usize,
+    }
+
+    impl ChunkProvider for GatedChunkProvider<'_> {
+        type LoadError = ChunkLoadError;
+        type SaveError = ChunkSaveError;
+
+        fn load_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<CompoundTag, ChunkLoadError> {
+            self.chunk_provider.load_chunk(chunk_x, chunk_z)
+        }
+
+        fn save_chunk(
+            &self,
+            chunk_x: i32,
+            chunk_z: i32,
+            chunk_compound_tag: CompoundTag,
+        ) -> Result<(), ChunkSaveError> {
+            let (entered, condvar) = &self.gate;
+            let mut entered = entered.lock().unwrap();
+            *entered += 1;
+            condvar.notify_all();
+
+            let (entered, timeout) = condvar
+                .wait_timeout_while(entered, Duration::from_secs(10), |entered| {
+                    *entered < self.saves
+                })
+                .unwrap();
+            drop(entered);
+
+            if timeout.timed_out() {
+                return Err(io::Error::from(io::ErrorKind::TimedOut).into());
+            }
+
+            self.chunk_provider
+                .save_chunk(chunk_x, chunk_z, chunk_compound_tag)
+        }
+
+        fn delete_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<bool, ChunkSaveError> {
+            self.chunk_provider.delete_chunk(chunk_x, chunk_z)
+        }
+
+        fn chunk_positions(&self) -> Result<Vec<(i32, i32)>, io::Error> {
+            self.chunk_provider.chunk_positions()
+        }
+    }
+
+    impl RegionStorage for GatedChunkProvider<'_> {
+        fn region_length(&self, region_x: i32, region_z: i32) -> Result<u64, io::Error> {
+            self.chunk_provider.region_length(region_x, region_z)
+        }
+
+        fn region_positions(&self) -> Result<Vec<(i32, i32)>, io::Error> {
+            self.chunk_provider.region_positions()
+        }
+
+        fn has_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<bool, io::Error> {
+            self.chunk_provider.has_chunk(chunk_x, chunk_z)
+        }
+    }
+
+    fn open<'a>(
+        region_dir: &'a TempDir,
+        quota: &Arc<StorageQuota>,
+    ) -> QuotaChunkProvider<AnvilChunkProvider<'a>> {
+        let chunk_provider = AnvilChunkProvider::new(region_dir.path().to_str().unwrap());
+
+        QuotaChunkProvider::new(chunk_provider, quota.clone()).unwrap()
+    }
+
+    #[test]
+    fn test_max_bytes() {
+        let region_dir = TempDir::new().unwrap();
+        let chunk_compound_tag = AnvilChunkProvider::new("test/region")
+            .load_chunk(4, 2)
+            .unwrap();
+
+        let quota = Arc::new(StorageQuota::new(QuotaLimits::new().max_bytes(16 * 1024)));
+        let chunk_provider = open(&region_dir, &quota);
+
+        chunk_provider
+            .save_chunk(4, 2, chunk_compound_tag.clone())
+            .unwrap();
+
+        let region_length = fs::metadata(region_dir.path().join("r.0.0.mca"))
+            .unwrap()
+            .len();
+        assert_eq!(quota.usage().bytes, region_length);
+        assert_eq!(quota.usage().chunks, 1);
+
+        for chunk_x in 5..40 {
+            match chunk_provider.save_chunk(chunk_x, 2, chunk_compound_tag.clone()) {
+                Ok(()) => continue,
+                Err(ChunkSaveError::QuotaExceeded {
+                    quota_exceeded: QuotaExceeded::Bytes { max_bytes },
+                }) => assert_eq!(max_bytes, 16 * 1024),
+                result => panic!("Expected `QuotaExceeded` but got `{:?}`", result),
+            }
+
+            break;
+        }
+
+        let region_length = fs::metadata(region_dir.path().join("r.0.0.mca"))
+            .unwrap()
+            .len();
+        assert!(region_length <= 16 * 1024);
+        assert_eq!(chunk_provider.usage().bytes, region_length);
+    }
+
+    #[test]
+    fn test_max_chunks() {
+        let region_dir = TempDir::new().unwrap();
+        let chunk_compound_tag = AnvilChunkProvider::new("test/region")
+            .load_chunk(4, 2)
+            .unwrap();
+
+        let quota = Arc::new(StorageQuota::new(QuotaLimits::new().max_chunks(2)));
+        let chunk_provider = open(&region_dir, &quota);
+
+        chunk_provider
+            .save_chunk(4, 2, chunk_compound_tag.clone())
+            .unwrap();
+        chunk_provider
+            .save_chunk(-40, 2, chunk_compound_tag.clone())
+            .unwrap();
+
+        // Overwriting chunk doesn't add to chunk count.
+        chunk_provider
+            .save_chunk(4, 2, chunk_compound_tag.clone())
+            .unwrap();
+
+        match chunk_provider.save_chunk(5, 2, chunk_compound_tag.clone()) {
+            Err(ChunkSaveError::QuotaExceeded {
+                quota_exceeded: QuotaExceeded::Chunks { max_chunks: 2 },
+            }) => {}
+            result => panic!("Expected `QuotaExceeded` but got `{:?}`", result),
+        }
+
+        assert!(chunk_provider.delete_chunk(4, 2).unwrap());
+        chunk_provider.save_chunk(5, 2, chunk_compound_tag).unwrap();
+        assert_eq!(quota.usage().chunks, 2);
+    }
+
+    #[test]
+    fn test_shared_quota() {
+        let region_dir = TempDir::new().unwrap();
+        let entities_dir = TempDir::new().unwrap();
+        fs::copy("test/region/r.0.0.mca", region_dir.path().join("r.0.0.mca")).unwrap();
+
+        let quota = Arc::new(StorageQuota::new(QuotaLimits::new()));
+        let chunk_provider = open(&region_dir, &quota);
+        let fixture_usage = chunk_provider.usage();
+        assert!(fixture_usage.chunks > 0);
+
+        let entity_chunk_provider = open(&entities_dir, &quota);
+        entity_chunk_provider
+            .save_chunk(4, 2, CompoundTag::new())
+            .unwrap();
+
+        assert_eq!(quota.usage().chunks, fixture_usage.chunks + 1);
+
+        drop(chunk_provider);
+        assert_eq!(quota.usage(), entity_chunk_provider.usage());
+    }
+
+    #[test]
+    fn test_saves_written_concurrently() {
+        let region_dir = TempDir::new().unwrap();
+        let folder = region_dir.path().to_str().unwrap();
+        let quota = Arc::new(StorageQuota::new(QuotaLimits::new().max_chunks(10)));
+        let gated_chunk_provider = GatedChunkProvider {
+            chunk_provider: AnvilChunkProvider::new(folder),
+            gate: (Mutex::new(0), Condvar::new()),
+            saves: 2,
+        };
+        let chunk_provider = QuotaChunkProvider::new(gated_chunk_provider, quota.clone()).unwrap();
+
+        // Saves wait for each other, so quota must not be locked while writing.
+        thread::scope(|scope| {
+            for chunk_x in [4, 40] {
+                let chunk_provider = &chunk_provider;
+
+                scope.spawn(move || {
+                    chunk_provider
+                        .save_chunk(chunk_x, 2, CompoundTag::new())
+                        .unwrap();
+                });
+            }
+        });
+
+        let region_lengths: u64 = [(0, 0), (1, 0)]
+            .iter()
+            .map(|&(region_x, region_z)| {
+                chunk_provider
+                    .get_ref()
+                    .region_length(region_x, region_z)
+                    .unwrap()
+            })
+            .sum();
+
+        assert_eq!(quota.usage().chunks, 2);
+        assert_eq!(quota.usage().bytes, region_lengths);
+        assert_eq!(quota.usage(), chunk_provider.usage());
+    }
+}
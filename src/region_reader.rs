@@ -0,0 +1,171 @@
+//! Reading region files from any seekable source.
+//!
+//! [`RegionReader`] reads chunks from source which implements only [`Read`] and
+//! [`Seek`], for example region file opened read only or [`std::io::Cursor`] over
+//! region extracted from archive, without requiring it to be writable. Source is given
+//! back with [`RegionReader::into_inner`] once reading is done.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::region_reader::RegionReader;
+//! use std::fs;
+//! use std::io::Cursor;
+//!
+//! let data = fs::read("test/region/r.0.0.mca").unwrap();
+//! let mut region = RegionReader::new(Cursor::new(data)).unwrap();
+//!
+//! for (chunk_x, chunk_z) in region.chunk_positions() {
+//!     assert!(region.read_chunk(chunk_x, chunk_z).is_ok());
+//! }
+//!
+//! let data = region.into_inner().into_inner();
+//! ```
+use crate::buffer::with_chunk_buffers;
+use crate::headers::RegionHeaders;
+use crate::sector_map::SectorMap;
+use crate::{read_chunk_data_at, ChunkLoadError};
+use nbt::CompoundTag;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Region file source with parsed header.
+pub struct RegionReader<R> {
+    reader: R,
+    region_headers: RegionHeaders,
+}
+
+impl<R: Read + Seek> RegionReader<R> {
+    /// Reads header of region file from the start of source.
+    ///
+    /// Source shorter than header is padded with zeros like by [`RegionHeaders::read`].
+    pub fn new(mut reader: R) -> Result<Self, io::Error> {
+        reader.seek(SeekFrom::Start(0))?;
+        let region_headers = RegionHeaders::read(&mut reader)?;
+
+        Ok(RegionReader {
+            reader,
+            region_headers,
+        })
+    }
+
+    /// Reads chunk at the specified region coordinates.
+    pub fn read_chunk(&mut self, chunk_x: u8, chunk_z: u8) -> Result<CompoundTag, ChunkLoadError> {
+        let metadata = self.region_headers.metadata(chunk_x, chunk_z);
+
+        if metadata.is_empty() {
+            return Err(ChunkLoadError::ChunkNotFound {
+                chunk_x: chunk_x as i32,
+                chunk_z: chunk_z as i32,
+            });
+        }
+
+        with_chunk_buffers(|chunk_buffers| {
+            let compression_scheme =
+                read_chunk_data_at(&mut self.reader, metadata, &mut chunk_buffers.compressed)?;
+
+            chunk_buffers.decode_compressed(compression_scheme)
+        })
+    }
+
+    /// Returns region coordinates of chunks which are present in region.
+    pub fn chunk_positions(&self) -> Vec<(u8, u8)> {
+        self.region_headers.chunk_positions()
+    }
+
+    /// Returns time in seconds since Unix epoch when chunk was last saved.
+    ///
+    /// Returns none if chunk is not present.
+    pub fn chunk_last_modified(&self, chunk_x: u8, chunk_z: u8) -> Option<u32> {
+        self.region_headers.chunk_last_modified(chunk_x, chunk_z)
+    }
+
+    /// Returns header of region.
+    pub fn headers(&self) -> &RegionHeaders {
+        &self.region_headers
+    }
+
+    /// Reads usage of every sector of region from the start of source.
+    pub fn sector_map(&mut self) -> Result<SectorMap, io::Error> {
+        self.reader.seek(SeekFrom::Start(0))?;
+
+        SectorMap::read(&mut self.reader)
+    }
+
+    /// Returns source, its position is wherever the last read left it.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl RegionReader<File> {
+    /// Opens region file at the specified path for reading only.
+    ///
+    /// Unlike writing, missing file isn't created.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, io::Error> {
+        RegionReader::new(File::open(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::region_reader::RegionReader;
+    use crate::sector_map::SectorMap;
+    use crate::{AnvilRegion, ChunkLoadError};
+    use std::fs;
+    use std::io::Cursor;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_read_chunk() {
+        let mut region_reader = RegionReader::open("test/region/r.0.0.mca").unwrap();
+        let region = AnvilRegion::new("test/region/r.0.0.mca").unwrap();
+
+        assert_eq!(region_reader.chunk_positions(), region.chunk_positions());
+
+        let compound_tag = region_reader.read_chunk(15, 3).unwrap();
+        let level_compound_tag = compound_tag.get_compound_tag("Level").unwrap();
+
+        assert_eq!(level_compound_tag.get_i32("xPos").unwrap(), 15);
+        assert_eq!(level_compound_tag.get_i32("zPos").unwrap(), 3);
+
+        match region_reader.read_chunk(15, 14) {
+            Err(ChunkLoadError::ChunkNotFound { chunk_x, chunk_z }) => {
+                assert_eq!(chunk_x, 15);
+                assert_eq!(chunk_z, 14);
+            }
+            result => panic!("Expected `ChunkNotFound` but got `{:?}`", result),
+        }
+
+        assert!(region_reader.chunk_last_modified(15, 3).is_some());
+        assert!(region_reader.chunk_last_modified(15, 14).is_none());
+    }
+
+    #[test]
+    fn test_into_inner() {
+        let data = fs::read("test/region/r.0.0.mca").unwrap();
+        let mut cursor = Cursor::new(data.clone());
+        cursor.set_position(100);
+
+        // Header is read from the start whatever position source has.
+        let mut region_reader = RegionReader::new(cursor).unwrap();
+        assert!(region_reader.read_chunk(4, 2).is_ok());
+        assert_eq!(
+            region_reader.sector_map().unwrap(),
+            SectorMap::open("test/region/r.0.0.mca").unwrap()
+        );
+
+        assert_eq!(region_reader.into_inner().into_inner(), data);
+    }
+
+    #[test]
+    fn test_open_missing_file() {
+        let region_dir = TempDir::new().unwrap();
+        let region_path = region_dir.path().join("r.0.0.mca");
+
+        assert!(RegionReader::open(&region_path).is_err());
+        assert!(!region_path.exists());
+    }
+}
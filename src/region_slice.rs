@@ -0,0 +1,210 @@
+//! Reading region file contents which are already in memory.
+//!
+//! [`RegionSlice`] parses header and chunks straight from byte slice, for example memory
+//! mapped region file, so reading chunks doesn't make read calls and doesn't copy
+//! compressed chunk data. With `mmap` feature `MappedRegionFile` maps region file
+//! into memory.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::region_slice::RegionSlice;
+//! use std::fs;
+//!
+//! let data = fs::read("test/region/r.0.0.mca").unwrap();
+//! let region = RegionSlice::new(&data).unwrap();
+//!
+//! for (chunk_x, chunk_z) in region.chunk_positions() {
+//!     assert!(region.read_chunk(chunk_x, chunk_z).is_ok());
+//! }
+//! ```
+use crate::{
+    decode_chunk, AnvilChunkMetadata, AnvilRegion, ChunkLoadError, CHUNK_MAXIMUM_BYTES_LENGTH,
+    REGION_CHUNKS, REGION_SECTOR_BYTES_LENGTH,
+};
+use byteorder::{BigEndian, ReadBytesExt};
+use nbt::CompoundTag;
+use std::io;
+#[cfg(feature = "mmap")]
+use std::{fs::File, path::Path};
+
+/// Region file contents with parsed header.
+pub struct RegionSlice<'a> {
+    data: &'a [u8],
+    /// Array of chunks metadata.
+    chunks_metadata: [AnvilChunkMetadata; REGION_CHUNKS],
+}
+
+impl<'a> RegionSlice<'a> {
+    /// Parses header of region file contents.
+    ///
+    /// Returns error if contents are shorter than header.
+    pub fn new(data: &'a [u8]) -> Result<Self, io::Error> {
+        let chunks_metadata = AnvilRegion::read_header(&mut &data[..])?;
+
+        Ok(RegionSlice {
+            data,
+            chunks_metadata,
+        })
+    }
+
+    /// Reads chunk at the specified region coordinates.
+    pub fn read_chunk(&self, chunk_x: u8, chunk_z: u8) -> Result<CompoundTag, ChunkLoadError> {
+        let (compression_scheme, compressed_buffer) = self.read_chunk_data(chunk_x, chunk_z)?;
+
+        decode_chunk(compression_scheme, compressed_buffer)
+    }
+
+    /// Returns compression scheme and compressed chunk data borrowed from contents.
+    pub(crate) fn read_chunk_data(
+        &self,
+        chunk_x: u8,
+        chunk_z: u8,
+    ) -> Result<(u8, &'a [u8]), ChunkLoadError> {
+        let metadata = self.chunks_metadata[AnvilRegion::metadata_index(chunk_x, chunk_z)];
+
+        if metadata.is_empty() {
+            return Err(ChunkLoadError::ChunkNotFound {
+                chunk_x: chunk_x as i32,
+                chunk_z: chunk_z as i32,
+            });
+        }
+
+        let offset = metadata.sector_index as usize * REGION_SECTOR_BYTES_LENGTH as usize;
+
+        read_sectors_chunk_data(self.data.get(offset..).unwrap_or_default(), metadata)
+    }
+
+    /// Returns region coordinates of chunks which are present in region.
+    pub fn chunk_positions(&self) -> Vec<(u8, u8)> {
+        let mut chunk_positions = Vec::new();
+
+        for (index, metadata) in self.chunks_metadata.iter().enumerate() {
+            if !metadata.is_empty() {
+                chunk_positions.push(((index % 32) as u8, (index / 32) as u8));
+            }
+        }
+
+        chunk_positions
+    }
+
+    /// Returns time in seconds since Unix epoch when chunk was last saved.
+    ///
+    /// Returns none if chunk is not present.
+    pub fn chunk_last_modified(&self, chunk_x: u8, chunk_z: u8) -> Option<u32> {
+        let metadata = self.chunks_metadata[AnvilRegion::metadata_index(chunk_x, chunk_z)];
+
+        if metadata.is_empty() {
+            return None;
+        }
+
+        Some(metadata.last_modified_timestamp)
+    }
+}
+
+/// Returns compression scheme and compressed chunk data from data starting at the first
+/// sector of chunk.
+pub(crate) fn read_sectors_chunk_data(
+    sectors: &[u8],
+    metadata: AnvilChunkMetadata,
+) -> Result<(u8, &[u8]), ChunkLoadError> {
+    let maximum_length = (metadata.sectors as u32 * REGION_SECTOR_BYTES_LENGTH as u32)
+        .min(CHUNK_MAXIMUM_BYTES_LENGTH);
+
+    let mut cursor = sectors;
+    let length = cursor.read_u32::<BigEndian>()?;
+
+    if length > maximum_length {
+        return Err(ChunkLoadError::LengthExceedsMaximum {
+            length,
+            maximum_length,
+        });
+    }
+
+    let compression_scheme = cursor.read_u8()?;
+    let compressed_buffer = cursor
+        .get(..(length as usize).saturating_sub(1))
+        .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+
+    Ok((compression_scheme, compressed_buffer))
+}
+
+/// Region file mapped into memory for reading.
+#[cfg(feature = "mmap")]
+pub struct MappedRegionFile {
+    mmap: memmap2::Mmap,
+}
+
+#[cfg(feature = "mmap")]
+impl MappedRegionFile {
+    /// Maps region file into memory.
+    ///
+    /// File must not be truncated while mapped, for example by game which is running,
+    /// otherwise reading it terminates the process.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, io::Error> {
+        let file = File::open(path)?;
+        // Mapping is read only, modification of file is documented above.
+        let mmap = unsafe { memmap2::Mmap::map(&file)? };
+
+        Ok(MappedRegionFile { mmap })
+    }
+
+    /// Parses header of mapped region file.
+    pub fn region(&self) -> Result<RegionSlice<'_>, io::Error> {
+        RegionSlice::new(&self.mmap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::region_slice::RegionSlice;
+    use crate::{AnvilRegion, ChunkLoadError};
+    use std::fs;
+
+    #[test]
+    fn test_read_chunk() {
+        let data = fs::read("test/region/r.0.0.mca").unwrap();
+        let region_slice = RegionSlice::new(&data).unwrap();
+        let region = AnvilRegion::new("test/region/r.0.0.mca").unwrap();
+
+        assert_eq!(region_slice.chunk_positions(), region.chunk_positions());
+
+        let compound_tag = region_slice.read_chunk(15, 3).unwrap();
+        let level_compound_tag = compound_tag.get_compound_tag("Level").unwrap();
+
+        assert_eq!(level_compound_tag.get_i32("xPos").unwrap(), 15);
+        assert_eq!(level_compound_tag.get_i32("zPos").unwrap(), 3);
+
+        match region_slice.read_chunk(15, 14) {
+            Err(ChunkLoadError::ChunkNotFound { chunk_x, chunk_z }) => {
+                assert_eq!(chunk_x, 15);
+                assert_eq!(chunk_z, 14);
+            }
+            result => panic!("Expected `ChunkNotFound` but got `{:?}`", result),
+        }
+
+        assert!(region_slice.chunk_last_modified(15, 3).is_some());
+        assert!(region_slice.chunk_last_modified(15, 14).is_none());
+    }
+
+    #[test]
+    fn test_truncated_region() {
+        let data = fs::read("test/region/r.0.0.mca").unwrap();
+
+        assert!(RegionSlice::new(&data[..100]).is_err());
+
+        let region_slice = RegionSlice::new(&data[..8192]).unwrap();
+        assert!(region_slice.read_chunk(15, 3).is_err());
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_mapped_region_file() {
+        use crate::region_slice::MappedRegionFile;
+
+        let mapped_region_file = MappedRegionFile::open("test/region/r.0.0.mca").unwrap();
+        let region_slice = mapped_region_file.region().unwrap();
+
+        assert!(region_slice.read_chunk(4, 2).is_ok());
+    }
+}
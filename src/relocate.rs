@@ -0,0 +1,583 @@
+//! Moving chunks to other coordinates.
+//!
+//! Besides the chunk position, chunk data contains absolute block coordinates of
+//! block entities, entities, scheduled ticks and structures. All of them are shifted,
+//! otherwise the game treats the chunk as corrupted or leaves ghost entities behind.
+//!
+//! Relocation works with terrain chunks, entity chunks (stored in `entities` folder
+//! since 1.17) and point of interest chunks (stored in `poi` folder).
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::relocate::copy_chunk;
+//! use anvil_region::AnvilChunkProvider;
+//! use tempfile::TempDir;
+//!
+//! let target_dir = TempDir::new().unwrap();
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//! let target_chunk_provider = AnvilChunkProvider::new(target_dir.path().to_str().unwrap());
+//!
+//! copy_chunk(&chunk_provider, (4, 2), &target_chunk_provider, (100, -7)).unwrap();
+//!
+//! let chunk_compound_tag = target_chunk_provider.load_chunk(100, -7).unwrap();
+//! let level_compound_tag = chunk_compound_tag.get_compound_tag("Level").unwrap();
+//!
+//! assert_eq!(level_compound_tag.get_i32("xPos").unwrap(), 100);
+//! assert_eq!(level_compound_tag.get_i32("zPos").unwrap(), -7);
+//! ```
+use crate::chunk::{Chunk, ChunkTag};
+use crate::coords::{self, CoordinateError};
+use crate::packed::{pack_chunk_position, unpack_chunk_position};
+use crate::tag::{compound_tags_mut, get_tag_mut};
+use crate::{AnvilChunkProvider, ChunkLoadError, ChunkSaveError};
+use nbt::{CompoundTag, Tag};
+use std::io;
+use std::path::Path;
+
+/// Possible errors while relocating chunks.
+#[derive(Debug)]
+pub enum RelocateError {
+    /// Chunk doesn't contain its position.
+    MissingPosition,
+    /// Chunk can't be loaded.
+    ChunkLoadError { chunk_load_error: ChunkLoadError },
+    /// Chunk can't be saved.
+    ChunkSaveError { chunk_save_error: ChunkSaveError },
+    /// Region folder can't be read.
+    ReadError { io_error: io::Error },
+    /// Chunks can't be moved within the same folder because they overwrite each other.
+    TargetIsSource,
+    /// Block coordinates of chunk at target position or offset don't fit into `i32`.
+    CoordinateError { coordinate_error: CoordinateError },
+}
+
+impl From<ChunkLoadError> for RelocateError {
+    fn from(chunk_load_error: ChunkLoadError) -> Self {
+        RelocateError::ChunkLoadError { chunk_load_error }
+    }
+}
+
+impl From<ChunkSaveError> for RelocateError {
+    fn from(chunk_save_error: ChunkSaveError) -> Self {
+        RelocateError::ChunkSaveError { chunk_save_error }
+    }
+}
+
+impl From<CoordinateError> for RelocateError {
+    fn from(coordinate_error: CoordinateError) -> Self {
+        RelocateError::CoordinateError { coordinate_error }
+    }
+}
+
+impl From<io::Error> for RelocateError {
+    fn from(io_error: io::Error) -> Self {
+        RelocateError::ReadError { io_error }
+    }
+}
+
+/// Folders of the world which contain region files with chunk coordinates.
+pub const WORLD_REGION_FOLDERS: &[&str] = &["region", "entities", "poi"];
+
+/// Returns position stored in terrain or entity chunk.
+pub fn chunk_position(chunk_compound_tag: &CompoundTag) -> Option<(i32, i32)> {
+    if let Ok(position) = chunk_compound_tag.get_i32_vec("Position") {
+        if let [chunk_x, chunk_z] = position[..] {
+            return Some((chunk_x, chunk_z));
+        }
+    }
+
+    let data = chunk_compound_tag
+        .get_compound_tag("Level")
+        .unwrap_or(chunk_compound_tag);
+
+    Some((data.get_i32("xPos").ok()?, data.get_i32("zPos").ok()?))
+}
+
+/// Moves chunk to specified position.
+///
+/// Point of interest chunks don't store their position, use [`offset_chunk`] for them.
+pub fn relocate_chunk(
+    chunk_compound_tag: &mut CompoundTag,
+    chunk_x: i32,
+    chunk_z: i32,
+) -> Result<(), RelocateError> {
+    let current_chunk_position =
+        chunk_position(chunk_compound_tag).ok_or(RelocateError::MissingPosition)?;
+    let (offset_x, offset_z) = checked_offset(current_chunk_position, (chunk_x, chunk_z))?;
+
+    offset_chunk(chunk_compound_tag, offset_x, offset_z);
+
+    Ok(())
+}
+
+/// Returns chunk offset between positions checking that it and its block offset fit
+/// into `i32`.
+fn checked_offset(
+    chunk_position: (i32, i32),
+    target_chunk_position: (i32, i32),
+) -> Result<(i32, i32), CoordinateError> {
+    coords::chunk_block_position(target_chunk_position.0, target_chunk_position.1)?;
+    let (offset_x, offset_z) = coords::position_offset(chunk_position, target_chunk_position)?;
+    coords::chunk_block_position(offset_x, offset_z)?;
+
+    Ok((offset_x, offset_z))
+}
+
+/// Moves chunk by specified amount of chunks.
+pub fn offset_chunk(chunk_compound_tag: &mut CompoundTag, offset_x: i32, offset_z: i32) {
+    if offset_x == 0 && offset_z == 0 {
+        return;
+    }
+
+    let block_offset_x = offset_x * 16;
+    let block_offset_z = offset_z * 16;
+
+    // Entity chunk.
+    if let Some(Tag::IntArray(position)) = get_tag_mut(chunk_compound_tag, "Position") {
+        if let [chunk_x, chunk_z] = &mut position[..] {
+            *chunk_x += offset_x;
+            *chunk_z += offset_z;
+        }
+    }
+
+    for entity in compound_tags_mut(chunk_compound_tag, "Entities") {
+        offset_entity(entity, block_offset_x, block_offset_z);
+    }
+
+    // Point of interest chunk.
+    if let Ok(sections) = chunk_compound_tag.get_mut::<&mut CompoundTag>("Sections") {
+        for (_, section) in sections.iter_mut() {
+            if let Tag::Compound(section) = section {
+                for record in compound_tags_mut(section, "Records") {
+                    offset_block_position(record, "pos", block_offset_x, block_offset_z);
+                }
+            }
+        }
+    }
+
+    let mut chunk = Chunk::new(std::mem::replace(chunk_compound_tag, CompoundTag::new()));
+
+    offset_terrain_chunk(&mut chunk, offset_x, offset_z);
+
+    *chunk_compound_tag = chunk.into_compound_tag();
+}
+
+fn offset_terrain_chunk(chunk: &mut Chunk, offset_x: i32, offset_z: i32) {
+    let block_offset_x = offset_x * 16;
+    let block_offset_z = offset_z * 16;
+
+    let data = chunk.data_mut();
+
+    offset_int(data, "xPos", offset_x);
+    offset_int(data, "zPos", offset_z);
+
+    // Entities of entity chunk are stored in `Entities` at root and are already moved.
+    let entities_name = chunk.tag_name(ChunkTag::Entities);
+
+    for entity in compound_tags_mut(chunk.data_mut(), entities_name) {
+        offset_entity(entity, block_offset_x, block_offset_z);
+    }
+
+    for chunk_tag in &[
+        ChunkTag::BlockEntities,
+        ChunkTag::BlockTicks,
+        ChunkTag::FluidTicks,
+    ] {
+        let name = chunk.tag_name(*chunk_tag);
+
+        for compound_tag in compound_tags_mut(chunk.data_mut(), name) {
+            offset_int(compound_tag, "x", block_offset_x);
+            offset_int(compound_tag, "z", block_offset_z);
+        }
+    }
+
+    chunk.offset_structures(offset_x, offset_z);
+}
+
+fn offset_entity(entity: &mut CompoundTag, block_offset_x: i32, block_offset_z: i32) {
+    if let Some(Tag::List(position)) = get_tag_mut(entity, "Pos") {
+        if let [Tag::Double(x), _, Tag::Double(z)] = &mut position[..] {
+            *x += block_offset_x as f64;
+            *z += block_offset_z as f64;
+        }
+    }
+
+    // Hanging entities like paintings and item frames.
+    offset_int(entity, "TileX", block_offset_x);
+    offset_int(entity, "TileZ", block_offset_z);
+
+    for passenger in compound_tags_mut(entity, "Passengers") {
+        offset_entity(passenger, block_offset_x, block_offset_z);
+    }
+}
+
+pub(crate) fn offset_bounding_box(
+    compound_tag: &mut CompoundTag,
+    block_offset_x: i32,
+    block_offset_z: i32,
+) {
+    if let Some(Tag::IntArray(bounding_box)) = get_tag_mut(compound_tag, "BB") {
+        if let [min_x, _, min_z, max_x, _, max_z] = &mut bounding_box[..] {
+            *min_x += block_offset_x;
+            *min_z += block_offset_z;
+            *max_x += block_offset_x;
+            *max_z += block_offset_z;
+        }
+    }
+}
+
+fn offset_block_position(
+    compound_tag: &mut CompoundTag,
+    name: &str,
+    block_offset_x: i32,
+    block_offset_z: i32,
+) {
+    if let Some(Tag::IntArray(position)) = get_tag_mut(compound_tag, name) {
+        if let [x, _, z] = &mut position[..] {
+            *x += block_offset_x;
+            *z += block_offset_z;
+        }
+    }
+}
+
+pub(crate) fn offset_int(compound_tag: &mut CompoundTag, name: &str, offset: i32) {
+    if let Some(Tag::Int(value)) = get_tag_mut(compound_tag, name) {
+        *value += offset;
+    }
+}
+
+/// Chunk positions in structure references store x in low and z in high 32 bits.
+pub(crate) fn offset_packed_chunk_position(
+    chunk_position: i64,
+    offset_x: i32,
+    offset_z: i32,
+) -> i64 {
+    let (chunk_x, chunk_z) = unpack_chunk_position(chunk_position);
+
+    pack_chunk_position(chunk_x + offset_x, chunk_z + offset_z)
+}
+
+/// Copies chunk to another position of target provider fixing coordinates inside of chunk.
+pub fn copy_chunk(
+    chunk_provider: &AnvilChunkProvider,
+    (chunk_x, chunk_z): (i32, i32),
+    target_chunk_provider: &AnvilChunkProvider,
+    (target_chunk_x, target_chunk_z): (i32, i32),
+) -> Result<(), RelocateError> {
+    let mut chunk_compound_tag = chunk_provider.load_chunk(chunk_x, chunk_z)?;
+
+    match chunk_position(&chunk_compound_tag) {
+        Some(_) => relocate_chunk(&mut chunk_compound_tag, target_chunk_x, target_chunk_z)?,
+        None => {
+            let (offset_x, offset_z) =
+                checked_offset((chunk_x, chunk_z), (target_chunk_x, target_chunk_z))?;
+
+            offset_chunk(&mut chunk_compound_tag, offset_x, offset_z)
+        }
+    }
+
+    target_chunk_provider.save_chunk(target_chunk_x, target_chunk_z, chunk_compound_tag)?;
+
+    Ok(())
+}
+
+/// Copies every chunk of provider into target provider shifting it by specified amount of chunks.
+///
+/// Returns amount of copied chunks.
+pub fn relocate_provider(
+    chunk_provider: &AnvilChunkProvider,
+    target_chunk_provider: &AnvilChunkProvider,
+    offset_x: i32,
+    offset_z: i32,
+) -> Result<usize, RelocateError> {
+    if chunk_provider.folder_path == target_chunk_provider.folder_path {
+        return Err(RelocateError::TargetIsSource);
+    }
+
+    let chunk_positions = chunk_provider.chunk_positions()?;
+
+    for (chunk_x, chunk_z) in &chunk_positions {
+        copy_chunk(
+            chunk_provider,
+            (*chunk_x, *chunk_z),
+            target_chunk_provider,
+            (chunk_x + offset_x, chunk_z + offset_z),
+        )?;
+    }
+
+    Ok(chunk_positions.len())
+}
+
+/// Copies terrain, entity and point of interest chunks of the world into target world
+/// shifting them by specified amount of chunks.
+///
+/// Returns amount of copied chunks.
+///
+/// # Example
+///
+/// ```
+/// use anvil_region::relocate::{copy_chunk, relocate_world};
+/// use anvil_region::AnvilChunkProvider;
+/// use tempfile::TempDir;
+///
+/// let world_dir = TempDir::new().unwrap();
+/// let target_world_dir = TempDir::new().unwrap();
+///
+/// let region_folder = world_dir.path().join("region");
+/// let target_region_folder = target_world_dir.path().join("region");
+///
+/// let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+/// let chunk_provider = AnvilChunkProvider::new(region_folder.to_str().unwrap());
+/// let target_chunk_provider = AnvilChunkProvider::new(target_region_folder.to_str().unwrap());
+///
+/// copy_chunk(&fixture_chunk_provider, (4, 2), &chunk_provider, (4, 2)).unwrap();
+/// relocate_world(world_dir.path(), target_world_dir.path(), 32, 0).unwrap();
+///
+/// let chunk_compound_tag = target_chunk_provider.load_chunk(36, 2).unwrap();
+/// let level_compound_tag = chunk_compound_tag.get_compound_tag("Level").unwrap();
+///
+/// assert_eq!(level_compound_tag.get_i32("xPos").unwrap(), 36);
+/// ```
+pub fn relocate_world(
+    world_folder_path: &Path,
+    target_world_folder_path: &Path,
+    offset_x: i32,
+    offset_z: i32,
+) -> Result<usize, RelocateError> {
+    let mut relocated_chunks = 0;
+
+    for folder in WORLD_REGION_FOLDERS {
+        let region_folder_path = world_folder_path.join(folder);
+        let target_region_folder_path = target_world_folder_path.join(folder);
+
+        if !region_folder_path.exists() {
+            continue;
+        }
+
+        let chunk_provider = AnvilChunkProvider::from_path(&region_folder_path);
+
+        let target_chunk_provider = AnvilChunkProvider::from_path(&target_region_folder_path);
+
+        relocated_chunks +=
+            relocate_provider(&chunk_provider, &target_chunk_provider, offset_x, offset_z)?;
+    }
+
+    Ok(relocated_chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::relocate::{
+        copy_chunk, offset_chunk, offset_packed_chunk_position, relocate_chunk, relocate_provider,
+        RelocateError,
+    };
+    use crate::upgrade::upgrade_chunk;
+    use crate::AnvilChunkProvider;
+    use nbt::{CompoundTag, Tag};
+    use tempfile::TempDir;
+
+    fn entity(x: f64, z: f64) -> CompoundTag {
+        let mut entity = CompoundTag::new();
+        entity.insert(
+            "Pos",
+            Tag::List(vec![Tag::Double(x), Tag::Double(64.0), Tag::Double(z)]),
+        );
+
+        entity
+    }
+
+    fn entity_position(entity: &CompoundTag) -> (f64, f64) {
+        match entity.get::<&Vec<Tag>>("Pos").unwrap()[..] {
+            [Tag::Double(x), _, Tag::Double(z)] => (x, z),
+            ref position => panic!("Expected doubles but got `{:?}`", position),
+        }
+    }
+
+    #[test]
+    fn test_offset_packed_chunk_position() {
+        let chunk_position = (-3i64) << 32 | (5u32 as i64);
+
+        assert_eq!(
+            offset_packed_chunk_position(chunk_position, -10, 4),
+            1i64 << 32 | ((-5i32) as u32 as i64)
+        );
+    }
+
+    #[test]
+    fn test_relocate_chunk() {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+        let mut chunk_compound_tag = chunk_provider.load_chunk(4, 2).unwrap();
+
+        let mut entity = entity(70.5, 40.5);
+        entity.insert_compound_tag_vec("Passengers", vec![self::entity(70.5, 40.5)]);
+
+        let level = chunk_compound_tag
+            .get_mut::<&mut CompoundTag>("Level")
+            .unwrap();
+        level.insert_compound_tag_vec("Entities", vec![entity]);
+
+        let mut block_entity = CompoundTag::new();
+        block_entity.insert_i32("x", 65);
+        block_entity.insert_i32("y", 10);
+        block_entity.insert_i32("z", 33);
+        level.insert_compound_tag_vec("TileEntities", vec![block_entity]);
+
+        relocate_chunk(&mut chunk_compound_tag, 5, 0).unwrap();
+
+        let level = chunk_compound_tag.get_compound_tag("Level").unwrap();
+        let entity = level.get_compound_tag_vec("Entities").unwrap()[0];
+        let block_entity = level.get_compound_tag_vec("TileEntities").unwrap()[0];
+        let passenger = entity.get_compound_tag_vec("Passengers").unwrap()[0];
+
+        assert_eq!(level.get_i32("xPos").unwrap(), 5);
+        assert_eq!(level.get_i32("zPos").unwrap(), 0);
+        assert_eq!(entity_position(entity), (86.5, 8.5));
+        assert_eq!(entity_position(passenger), (86.5, 8.5));
+        assert_eq!(block_entity.get_i32("x").unwrap(), 81);
+        assert_eq!(block_entity.get_i32("y").unwrap(), 10);
+        assert_eq!(block_entity.get_i32("z").unwrap(), 1);
+    }
+
+    #[test]
+    fn test_relocate_chunk_out_of_bounds() {
+        let mut chunk_compound_tag = CompoundTag::new();
+        chunk_compound_tag.insert_i32("xPos", i32::MIN);
+        chunk_compound_tag.insert_i32("zPos", 0);
+
+        match relocate_chunk(&mut chunk_compound_tag, 0, 0) {
+            Err(RelocateError::CoordinateError { .. }) => {}
+            result => panic!("Expected `CoordinateError` but got `{:?}`", result),
+        }
+
+        assert_eq!(chunk_compound_tag.get_i32("xPos").unwrap(), i32::MIN);
+        assert!(relocate_chunk(&mut chunk_compound_tag, i32::MAX, 0).is_err());
+        assert!(relocate_chunk(&mut chunk_compound_tag, -1_875_000, 0).is_err());
+    }
+
+    #[test]
+    fn test_relocate_modern_chunk_structures() {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+        let mut chunk_compound_tag = chunk_provider.load_chunk(4, 2).unwrap();
+        upgrade_chunk(&mut chunk_compound_tag, 2860).unwrap();
+
+        let mut start = CompoundTag::new();
+        start.insert_i32("ChunkX", 4);
+        start.insert_i32("ChunkZ", 2);
+        start.insert_i32_vec("BB", vec![64, 0, 32, 80, 10, 48]);
+
+        let mut starts = CompoundTag::new();
+        starts.insert_compound_tag("minecraft:village", start);
+
+        let mut references = CompoundTag::new();
+        references.insert_i64_vec("minecraft:village", vec![2i64 << 32 | 4]);
+
+        let mut structures = CompoundTag::new();
+        structures.insert_compound_tag("starts", starts);
+        structures.insert_compound_tag("References", references);
+        chunk_compound_tag.insert_compound_tag("structures", structures);
+
+        relocate_chunk(&mut chunk_compound_tag, 6, 3).unwrap();
+
+        let structures = chunk_compound_tag.get_compound_tag("structures").unwrap();
+        let start = structures
+            .get_compound_tag("starts")
+            .unwrap()
+            .get_compound_tag("minecraft:village")
+            .unwrap();
+        let references = structures.get_compound_tag("References").unwrap();
+
+        assert_eq!(chunk_compound_tag.get_i32("xPos").unwrap(), 6);
+        assert_eq!(start.get_i32("ChunkX").unwrap(), 6);
+        assert_eq!(
+            start.get_i32_vec("BB").unwrap(),
+            &vec![96, 0, 48, 112, 10, 64]
+        );
+        assert_eq!(
+            references.get_i64_vec("minecraft:village").unwrap(),
+            &vec![3i64 << 32 | 6]
+        );
+    }
+
+    #[test]
+    fn test_offset_entity_and_poi_chunks() {
+        let mut entity_chunk = CompoundTag::new();
+        entity_chunk.insert_i32_vec("Position", vec![1, 1]);
+        entity_chunk.insert_compound_tag_vec("Entities", vec![entity(20.0, 20.0)]);
+
+        relocate_chunk(&mut entity_chunk, 0, 2).unwrap();
+
+        let entity = entity_chunk.get_compound_tag_vec("Entities").unwrap()[0];
+
+        assert_eq!(entity_chunk.get_i32_vec("Position").unwrap(), &vec![0, 2]);
+        assert_eq!(entity_position(entity), (4.0, 36.0));
+
+        let mut record = CompoundTag::new();
+        record.insert_i32_vec("pos", vec![20, 64, 20]);
+
+        let mut section = CompoundTag::new();
+        section.insert_compound_tag_vec("Records", vec![record]);
+
+        let mut sections = CompoundTag::new();
+        sections.insert_compound_tag("4", section);
+
+        let mut poi_chunk = CompoundTag::new();
+        poi_chunk.insert_compound_tag("Sections", sections);
+
+        offset_chunk(&mut poi_chunk, 1, -1);
+
+        let record = poi_chunk
+            .get_compound_tag("Sections")
+            .unwrap()
+            .get_compound_tag("4")
+            .unwrap()
+            .get_compound_tag_vec("Records")
+            .unwrap()[0];
+
+        assert_eq!(record.get_i32_vec("pos").unwrap(), &vec![36, 64, 4]);
+    }
+
+    #[test]
+    fn test_relocate_provider() {
+        let source_dir = TempDir::new().unwrap();
+        let target_dir = TempDir::new().unwrap();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk_provider = AnvilChunkProvider::new(source_dir.path().to_str().unwrap());
+        let target_chunk_provider = AnvilChunkProvider::new(target_dir.path().to_str().unwrap());
+
+        for chunk_position in &[(4, 2), (15, 3)] {
+            copy_chunk(
+                &fixture_chunk_provider,
+                *chunk_position,
+                &chunk_provider,
+                *chunk_position,
+            )
+            .unwrap();
+        }
+
+        let relocated_chunks =
+            relocate_provider(&chunk_provider, &target_chunk_provider, -40, 3).unwrap();
+
+        assert_eq!(relocated_chunks, 2);
+        assert_eq!(
+            target_chunk_provider.chunk_positions().unwrap(),
+            vec![(-36, 5), (-25, 6)]
+        );
+
+        let chunk_compound_tag = target_chunk_provider.load_chunk(-36, 5).unwrap();
+        let level = chunk_compound_tag.get_compound_tag("Level").unwrap();
+
+        assert_eq!(level.get_i32("xPos").unwrap(), -36);
+        assert_eq!(level.get_i32("zPos").unwrap(), 5);
+    }
+
+    #[test]
+    fn test_relocate_provider_target_is_source() {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+
+        match relocate_provider(&chunk_provider, &chunk_provider, 1, 1) {
+            Err(RelocateError::TargetIsSource) => {}
+            result => panic!("Expected `TargetIsSource` but got `{:?}`", result),
+        }
+    }
+}
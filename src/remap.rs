@@ -0,0 +1,429 @@
+//! Renames of block, item and entity ids.
+//!
+//! Game renames ids from time to time, like `minecraft:zombie_pigman` which became
+//! `minecraft:zombified_piglin` in 1.16, and data fixers of the game rename them when
+//! chunk is loaded. [`IdRemap::between`] collects vanilla renames between two data
+//! versions in either direction, so upgrades and downgrades keep ids known to the
+//! target version. Modded worlds use [`IdRemap::rename`] to fix ids of renamed or
+//! replaced mods.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::remap::{apply_remap, IdKind, IdRemap};
+//! use anvil_region::AnvilChunkProvider;
+//!
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//! let mut chunk_compound_tag = chunk_provider.load_chunk(4, 2).unwrap();
+//!
+//! let remap = IdRemap::between(1343, 2586)
+//!     .rename(IdKind::Block, "minecraft:stone", "examplemod:marble");
+//!
+//! assert_eq!(remap.renamed_id(IdKind::Entity, "minecraft:zombie_pigman"), Some("minecraft:zombified_piglin"));
+//! assert!(apply_remap(&mut chunk_compound_tag, &remap) > 0);
+//! ```
+use crate::tag::{compound_tags_mut, get_tag_mut};
+use nbt::{CompoundTag, Tag};
+use std::collections::HashMap;
+
+/// Kind of renamed id.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IdKind {
+    /// Block name in section palettes and scheduled ticks.
+    Block,
+    /// Block entity `id`.
+    BlockEntity,
+    /// Entity `id`.
+    Entity,
+    /// Item `id` inside of block entities and entities.
+    Item,
+}
+
+/// Id renamed by release.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct IdRename {
+    /// Data version of release which renamed id.
+    pub data_version: i32,
+    pub kind: IdKind,
+    pub from: &'static str,
+    pub to: &'static str,
+}
+
+impl IdRename {
+    const fn new(data_version: i32, kind: IdKind, from: &'static str, to: &'static str) -> Self {
+        IdRename {
+            data_version,
+            kind,
+            from,
+            to,
+        }
+    }
+}
+
+/// Vanilla renames after the flattening in ascending order of data version.
+pub const ID_RENAMES: &[IdRename] = &[
+    // 1.14
+    IdRename::new(1952, IdKind::Block, "minecraft:sign", "minecraft:oak_sign"),
+    IdRename::new(1952, IdKind::Item, "minecraft:sign", "minecraft:oak_sign"),
+    IdRename::new(
+        1952,
+        IdKind::Block,
+        "minecraft:wall_sign",
+        "minecraft:oak_wall_sign",
+    ),
+    IdRename::new(
+        1952,
+        IdKind::Item,
+        "minecraft:rose_red",
+        "minecraft:red_dye",
+    ),
+    IdRename::new(
+        1952,
+        IdKind::Item,
+        "minecraft:dandelion_yellow",
+        "minecraft:yellow_dye",
+    ),
+    IdRename::new(
+        1952,
+        IdKind::Item,
+        "minecraft:cactus_green",
+        "minecraft:green_dye",
+    ),
+    // 1.16
+    IdRename::new(
+        2566,
+        IdKind::Entity,
+        "minecraft:zombie_pigman",
+        "minecraft:zombified_piglin",
+    ),
+    IdRename::new(
+        2566,
+        IdKind::Item,
+        "minecraft:zombie_pigman_spawn_egg",
+        "minecraft:zombified_piglin_spawn_egg",
+    ),
+    // 1.17
+    IdRename::new(
+        2724,
+        IdKind::Block,
+        "minecraft:grass_path",
+        "minecraft:dirt_path",
+    ),
+    IdRename::new(
+        2724,
+        IdKind::Item,
+        "minecraft:grass_path",
+        "minecraft:dirt_path",
+    ),
+    // 1.20.3
+    IdRename::new(
+        3698,
+        IdKind::Block,
+        "minecraft:grass",
+        "minecraft:short_grass",
+    ),
+    IdRename::new(
+        3698,
+        IdKind::Item,
+        "minecraft:grass",
+        "minecraft:short_grass",
+    ),
+    // 1.20.5
+    IdRename::new(
+        3837,
+        IdKind::Item,
+        "minecraft:scute",
+        "minecraft:turtle_scute",
+    ),
+];
+
+/// Table of ids to rename.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct IdRemap {
+    renames: HashMap<(IdKind, String), String>,
+}
+
+impl IdRemap {
+    pub fn new() -> Self {
+        IdRemap::default()
+    }
+
+    /// Creates remap with vanilla renames which convert ids of data version into ids
+    /// of target data version, reverting renames when target is older.
+    pub fn between(data_version: i32, target_data_version: i32) -> Self {
+        let mut remap = IdRemap::new();
+
+        if data_version < target_data_version {
+            for id_rename in ID_RENAMES {
+                if id_rename.data_version > data_version
+                    && id_rename.data_version <= target_data_version
+                {
+                    remap = remap.rename(id_rename.kind, id_rename.from, id_rename.to);
+                }
+            }
+        } else {
+            for id_rename in ID_RENAMES.iter().rev() {
+                if id_rename.data_version <= data_version
+                    && id_rename.data_version > target_data_version
+                {
+                    remap = remap.rename(id_rename.kind, id_rename.to, id_rename.from);
+                }
+            }
+        }
+
+        remap
+    }
+
+    /// Adds rename, which also applies to ids already renamed into `from`.
+    pub fn rename(mut self, kind: IdKind, from: &str, to: &str) -> Self {
+        for ((rename_kind, _), rename_to) in self.renames.iter_mut() {
+            if *rename_kind == kind && rename_to == from {
+                *rename_to = to.to_owned();
+            }
+        }
+
+        self.renames
+            .entry((kind, from.to_owned()))
+            .or_insert_with(|| to.to_owned());
+        self.renames
+            .retain(|(_, rename_from), rename_to| rename_from != rename_to);
+
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.renames.is_empty()
+    }
+
+    /// Returns new id if id is renamed.
+    pub fn renamed_id(&self, kind: IdKind, id: &str) -> Option<&str> {
+        self.renames
+            .get(&(kind, id.to_owned()))
+            .map(|renamed_id| renamed_id.as_str())
+    }
+
+    /// Renames string tag if its value is renamed, returns true if it was.
+    fn rename_tag(&self, compound_tag: &mut CompoundTag, name: &str, kind: IdKind) -> bool {
+        if let Some(Tag::String(id)) = get_tag_mut(compound_tag, name) {
+            if let Some(renamed_id) = self.renamed_id(kind, id) {
+                *id = renamed_id.to_owned();
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+/// Renames ids in terrain or entity chunk, returns amount of renamed ids.
+///
+/// Blocks are renamed in section palettes and scheduled ticks, items are looked up in
+/// every compound with `id` nested inside of block entities and entities.
+pub fn apply_remap(chunk_compound_tag: &mut CompoundTag, remap: &IdRemap) -> usize {
+    if remap.is_empty() {
+        return 0;
+    }
+
+    let data = if chunk_compound_tag.contains_key("Level") {
+        match chunk_compound_tag.get_mut::<&mut CompoundTag>("Level") {
+            Ok(level) => level,
+            Err(_) => return 0,
+        }
+    } else {
+        chunk_compound_tag
+    };
+
+    let mut renamed_ids = 0;
+
+    for sections_name in ["Sections", "sections"].iter() {
+        for section in compound_tags_mut(data, sections_name) {
+            let palette = if section.contains_key("block_states") {
+                match section.get_mut::<&mut CompoundTag>("block_states") {
+                    Ok(block_states) => compound_tags_mut(block_states, "palette"),
+                    Err(_) => continue,
+                }
+            } else {
+                compound_tags_mut(section, "Palette")
+            };
+
+            for block_state in palette {
+                renamed_ids += remap.rename_tag(block_state, "Name", IdKind::Block) as usize;
+            }
+        }
+    }
+
+    for ticks_name in ["TileTicks", "block_ticks"].iter() {
+        for tick in compound_tags_mut(data, ticks_name) {
+            renamed_ids += remap.rename_tag(tick, "i", IdKind::Block) as usize;
+        }
+    }
+
+    for block_entities_name in ["TileEntities", "block_entities"].iter() {
+        for block_entity in compound_tags_mut(data, block_entities_name) {
+            renamed_ids += remap.rename_tag(block_entity, "id", IdKind::BlockEntity) as usize;
+            renamed_ids += remap_items(block_entity, remap);
+        }
+    }
+
+    for entities_name in ["Entities", "entities"].iter() {
+        for entity in compound_tags_mut(data, entities_name) {
+            renamed_ids += remap_entity(entity, remap);
+        }
+    }
+
+    renamed_ids
+}
+
+fn remap_entity(entity: &mut CompoundTag, remap: &IdRemap) -> usize {
+    let mut renamed_ids = remap.rename_tag(entity, "id", IdKind::Entity) as usize;
+
+    for passenger in compound_tags_mut(entity, "Passengers") {
+        renamed_ids += remap_entity(passenger, remap);
+    }
+
+    renamed_ids + remap_items(entity, remap)
+}
+
+/// Renames ids of compounds nested inside of compound as items, passengers are skipped.
+fn remap_items(compound_tag: &mut CompoundTag, remap: &IdRemap) -> usize {
+    compound_tag
+        .iter_mut()
+        .filter(|(name, _)| name.as_str() != "Passengers")
+        .map(|(_, tag)| remap_item_tag(tag, remap))
+        .sum()
+}
+
+fn remap_item_tag(tag: &mut Tag, remap: &IdRemap) -> usize {
+    match tag {
+        Tag::Compound(compound_tag) => {
+            remap.rename_tag(compound_tag, "id", IdKind::Item) as usize
+                + remap_items(compound_tag, remap)
+        }
+        Tag::List(tags) => tags.iter_mut().map(|tag| remap_item_tag(tag, remap)).sum(),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::remap::{apply_remap, IdKind, IdRemap};
+    use crate::section::BlockState;
+    use nbt::{CompoundTag, Tag};
+
+    fn item(id: &str) -> CompoundTag {
+        let mut item = CompoundTag::new();
+        item.insert_str("id", id);
+        item.insert_i8("Count", 1);
+
+        item
+    }
+
+    #[test]
+    fn test_remap_between() {
+        let remap = IdRemap::between(1631, 3700);
+
+        assert_eq!(
+            remap.renamed_id(IdKind::Block, "minecraft:grass_path"),
+            Some("minecraft:dirt_path")
+        );
+        assert_eq!(
+            remap.renamed_id(IdKind::Item, "minecraft:sign"),
+            Some("minecraft:oak_sign")
+        );
+        assert_eq!(remap.renamed_id(IdKind::Entity, "minecraft:sign"), None);
+        assert_eq!(remap.renamed_id(IdKind::Block, "minecraft:stone"), None);
+
+        let remap = IdRemap::between(3700, 2586);
+
+        assert_eq!(
+            remap.renamed_id(IdKind::Block, "minecraft:short_grass"),
+            Some("minecraft:grass")
+        );
+        assert_eq!(
+            remap.renamed_id(IdKind::Entity, "minecraft:zombified_piglin"),
+            None
+        );
+        assert!(IdRemap::between(2586, 2586).is_empty());
+    }
+
+    #[test]
+    fn test_rename_chain() {
+        let remap = IdRemap::new()
+            .rename(IdKind::Block, "oldmod:ore", "newmod:ore")
+            .rename(IdKind::Block, "newmod:ore", "minecraft:iron_ore")
+            .rename(IdKind::Block, "minecraft:iron_ore", "oldmod:ore");
+
+        assert_eq!(
+            remap.renamed_id(IdKind::Block, "newmod:ore"),
+            Some("oldmod:ore")
+        );
+        assert_eq!(
+            remap.renamed_id(IdKind::Block, "minecraft:iron_ore"),
+            Some("oldmod:ore")
+        );
+        // Rename back into itself is dropped.
+        assert_eq!(remap.renamed_id(IdKind::Block, "oldmod:ore"), None);
+    }
+
+    #[test]
+    fn test_apply_remap() {
+        let mut block_states = CompoundTag::new();
+        block_states.insert_compound_tag_vec(
+            "palette",
+            vec![
+                BlockState::new("minecraft:air").to_compound_tag(),
+                BlockState::new("minecraft:grass_path").to_compound_tag(),
+            ],
+        );
+
+        let mut section = CompoundTag::new();
+        section.insert_compound_tag("block_states", block_states);
+
+        let mut chest = CompoundTag::new();
+        chest.insert_str("id", "minecraft:chest");
+        chest.insert_compound_tag_vec("Items", vec![item("minecraft:zombie_pigman_spawn_egg")]);
+
+        let mut pigman = CompoundTag::new();
+        pigman.insert_str("id", "minecraft:zombie_pigman");
+        pigman.insert(
+            "HandItems",
+            Tag::List(vec![Tag::Compound(item("minecraft:sign"))]),
+        );
+
+        let mut chicken = CompoundTag::new();
+        chicken.insert_str("id", "minecraft:chicken");
+        chicken.insert_compound_tag_vec("Passengers", vec![pigman]);
+
+        let mut level = CompoundTag::new();
+        level.insert_compound_tag_vec("Sections", vec![section]);
+        level.insert_compound_tag_vec("TileEntities", vec![chest]);
+        level.insert_compound_tag_vec("Entities", vec![chicken]);
+
+        let mut chunk_compound_tag = CompoundTag::new();
+        chunk_compound_tag.insert_compound_tag("Level", level);
+
+        let remap = IdRemap::between(2230, 2730);
+        assert_eq!(apply_remap(&mut chunk_compound_tag, &remap), 3);
+
+        let level = chunk_compound_tag.get_compound_tag("Level").unwrap();
+        let section = level.get_compound_tag_vec("Sections").unwrap()[0];
+        let palette = section
+            .get_compound_tag("block_states")
+            .unwrap()
+            .get_compound_tag_vec("palette")
+            .unwrap();
+        assert_eq!(palette[1].get_str("Name").unwrap(), "minecraft:dirt_path");
+
+        let chicken = level.get_compound_tag_vec("Entities").unwrap()[0];
+        let pigman = chicken.get_compound_tag_vec("Passengers").unwrap()[0];
+        assert_eq!(pigman.get_str("id").unwrap(), "minecraft:zombified_piglin");
+        // Sign was renamed before 1.16.
+        assert_eq!(
+            pigman.get_compound_tag_vec("HandItems").unwrap()[0]
+                .get_str("id")
+                .unwrap(),
+            "minecraft:sign"
+        );
+    }
+}
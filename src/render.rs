@@ -0,0 +1,565 @@
+//! Top-down map rendering of regions into PNG tiles.
+//!
+//! Every region is rendered into 512×512 tile where pixel is the top visible block
+//! of column, found through chunk heightmap. Colors come from built-in table of
+//! common blocks, blocks which aren't in it are colored by name like `_leaves` or
+//! `_planks`, foliage has the same color in every biome. Pixels which are higher
+//! than their northern neighbour are lighter and lower ones darker, like in-game maps.
+//!
+//! Chunks before the flattening are upgraded in memory before rendering.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::render::{render_provider, RenderOptions};
+//! use anvil_region::AnvilChunkProvider;
+//!
+//! # let tiles_dir = tempfile::TempDir::new().unwrap();
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//! let options = RenderOptions::default().heightmap("MOTION_BLOCKING");
+//!
+//! let report = render_provider(&chunk_provider, tiles_dir.path(), &options).unwrap();
+//! assert_eq!(report.tiles, vec![(0, 0)]);
+//! assert!(tiles_dir.path().join("r.0.0.png").exists());
+//! ```
+use crate::chunk::Chunk;
+use crate::schematic::is_air;
+use crate::section::{BlockState, SectionError};
+use crate::upgrade::{upgrade_chunk, ChunkUpgradeError};
+use crate::version::{chunk_data_version, FLATTENING_DATA_VERSION};
+use crate::{AnvilChunkProvider, ChunkLoadError};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+/// Width and height of region tile in pixels.
+pub const TILE_SIZE: usize = 512;
+/// Color of blocks which are neither in table nor recognized by name.
+pub const DEFAULT_COLOR: [u8; 3] = [128, 128, 128];
+
+/// Colors of common blocks sorted by name without namespace.
+const BLOCK_COLORS: &[(&str, [u8; 3])] = &[
+    ("acacia_leaves", [74, 112, 34]),
+    ("andesite", [136, 136, 137]),
+    ("bamboo", [93, 144, 19]),
+    ("basalt", [80, 81, 86]),
+    ("bedrock", [85, 85, 85]),
+    ("birch_leaves", [98, 128, 62]),
+    ("blackstone", [42, 36, 41]),
+    ("blue_ice", [116, 167, 253]),
+    ("bricks", [150, 97, 83]),
+    ("cactus", [85, 127, 43]),
+    ("calcite", [223, 224, 220]),
+    ("clay", [160, 166, 179]),
+    ("coarse_dirt", [119, 85, 59]),
+    ("cobblestone", [127, 127, 127]),
+    ("deepslate", [80, 80, 82]),
+    ("diorite", [188, 188, 188]),
+    ("dirt", [134, 96, 67]),
+    ("dirt_path", [148, 121, 65]),
+    ("end_stone", [219, 222, 158]),
+    ("farmland", [81, 44, 15]),
+    ("glass", [175, 213, 219]),
+    ("granite", [149, 103, 85]),
+    ("grass", [94, 148, 52]),
+    ("grass_block", [109, 153, 68]),
+    ("grass_path", [148, 121, 65]),
+    ("gravel", [131, 127, 126]),
+    ("ice", [145, 183, 253]),
+    ("kelp", [87, 130, 42]),
+    ("lava", [207, 92, 20]),
+    ("lily_pad", [32, 128, 48]),
+    ("magma_block", [142, 63, 31]),
+    ("moss_block", [89, 109, 45]),
+    ("mossy_cobblestone", [110, 118, 94]),
+    ("mud", [60, 57, 61]),
+    ("mycelium", [111, 98, 101]),
+    ("netherrack", [97, 38, 38]),
+    ("obsidian", [15, 11, 25]),
+    ("packed_ice", [141, 180, 250]),
+    ("podzol", [91, 63, 24]),
+    ("powder_snow", [248, 253, 253]),
+    ("pumpkin", [198, 118, 24]),
+    ("red_sand", [190, 102, 33]),
+    ("red_sandstone", [186, 99, 29]),
+    ("rooted_dirt", [144, 103, 76]),
+    ("sand", [219, 207, 163]),
+    ("sandstone", [216, 203, 155]),
+    ("seagrass", [39, 110, 28]),
+    ("snow", [249, 254, 254]),
+    ("snow_block", [249, 254, 254]),
+    ("soul_sand", [81, 62, 50]),
+    ("soul_soil", [75, 57, 46]),
+    ("stone", [125, 125, 125]),
+    ("sugar_cane", [148, 192, 101]),
+    ("tall_grass", [94, 148, 52]),
+    ("terracotta", [152, 94, 67]),
+    ("tuff", [108, 109, 102]),
+    ("vine", [59, 110, 33]),
+    ("water", [63, 118, 228]),
+    ("white_concrete", [207, 213, 214]),
+];
+
+/// Colors of name suffixes, checked in order when block isn't in table.
+const SUFFIX_COLORS: &[(&str, [u8; 3])] = &[
+    ("_leaves", [60, 120, 40]),
+    ("_log", [102, 81, 51]),
+    ("_wood", [102, 81, 51]),
+    ("_planks", [162, 130, 78]),
+    ("_slab", [150, 150, 150]),
+    ("_stairs", [150, 150, 150]),
+    ("_ore", [125, 125, 125]),
+    ("_flower", [200, 180, 60]),
+    ("_sapling", [70, 120, 40]),
+    ("_coral", [200, 100, 120]),
+];
+
+/// Possible errors while rendering tiles.
+#[derive(Debug)]
+pub enum RenderError {
+    /// Chunk can't be loaded.
+    ChunkLoadError { chunk_load_error: ChunkLoadError },
+    /// Chunk before the flattening can't be upgraded.
+    ChunkUpgradeError {
+        chunk_upgrade_error: ChunkUpgradeError,
+    },
+    /// Chunk sections can't be read.
+    SectionError { section_error: SectionError },
+    /// Tile can't be written.
+    WriteError { io_error: io::Error },
+    /// Tile can't be encoded as PNG.
+    EncodingError { encoding_error: png::EncodingError },
+}
+
+impl From<ChunkLoadError> for RenderError {
+    fn from(chunk_load_error: ChunkLoadError) -> Self {
+        RenderError::ChunkLoadError { chunk_load_error }
+    }
+}
+
+impl From<ChunkUpgradeError> for RenderError {
+    fn from(chunk_upgrade_error: ChunkUpgradeError) -> Self {
+        RenderError::ChunkUpgradeError {
+            chunk_upgrade_error,
+        }
+    }
+}
+
+impl From<SectionError> for RenderError {
+    fn from(section_error: SectionError) -> Self {
+        RenderError::SectionError { section_error }
+    }
+}
+
+impl From<io::Error> for RenderError {
+    fn from(io_error: io::Error) -> Self {
+        RenderError::WriteError { io_error }
+    }
+}
+
+impl From<png::EncodingError> for RenderError {
+    fn from(encoding_error: png::EncodingError) -> Self {
+        RenderError::EncodingError { encoding_error }
+    }
+}
+
+/// Options of rendering.
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    heightmap: String,
+    height_shading: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            heightmap: "WORLD_SURFACE".to_owned(),
+            height_shading: true,
+        }
+    }
+}
+
+impl RenderOptions {
+    /// Heightmap which selects top visible blocks, `WORLD_SURFACE` by default.
+    ///
+    /// `MOTION_BLOCKING` skips flowers and grass, `OCEAN_FLOOR` shows blocks under water.
+    pub fn heightmap(mut self, name: &str) -> Self {
+        self.heightmap = name.to_owned();
+        self
+    }
+
+    /// Renders blocks with their own colors, without shading by height.
+    pub fn without_height_shading(mut self) -> Self {
+        self.height_shading = false;
+        self
+    }
+}
+
+/// Top visible block of column.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TopBlock {
+    /// World Y of block.
+    pub y: i32,
+    pub block_state: BlockState,
+}
+
+/// Rendered region with RGBA pixels, empty columns are transparent.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Tile {
+    pub region_x: i32,
+    pub region_z: i32,
+    /// RGBA pixels row by row, from north-west corner of region.
+    pub pixels: Vec<u8>,
+}
+
+impl Tile {
+    fn new(region_x: i32, region_z: i32) -> Self {
+        Tile {
+            region_x,
+            region_z,
+            pixels: vec![0; TILE_SIZE * TILE_SIZE * 4],
+        }
+    }
+
+    /// Returns RGBA color of pixel at region relative block X and Z.
+    pub fn pixel(&self, x: usize, z: usize) -> [u8; 4] {
+        let index = (z * TILE_SIZE + x) * 4;
+        let mut pixel = [0; 4];
+        pixel.copy_from_slice(&self.pixels[index..index + 4]);
+
+        pixel
+    }
+
+    fn set_pixel(&mut self, x: usize, z: usize, pixel: [u8; 4]) {
+        let index = (z * TILE_SIZE + x) * 4;
+        self.pixels[index..index + 4].copy_from_slice(&pixel);
+    }
+
+    /// Writes tile as PNG image.
+    pub fn write_png<W: io::Write>(&self, writer: W) -> Result<(), RenderError> {
+        let mut encoder = png::Encoder::new(writer, TILE_SIZE as u32, TILE_SIZE as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()?;
+
+        Ok(())
+    }
+
+    /// Saves tile as PNG file, creating parent folders.
+    pub fn save_png(&self, path: &Path) -> Result<(), RenderError> {
+        if let Some(parent_path) = path.parent() {
+            fs::create_dir_all(parent_path)?;
+        }
+
+        self.write_png(BufWriter::new(File::create(path)?))
+    }
+}
+
+/// Result of rendering provider.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct RenderReport {
+    /// Positions of regions which were rendered.
+    pub tiles: Vec<(i32, i32)>,
+    /// Amount of chunks which were rendered.
+    pub rendered_chunks: usize,
+}
+
+/// Returns color of block, none for air and blocks which are not visible on map.
+pub fn block_color(block_state: &BlockState) -> Option<[u8; 3]> {
+    if is_air(block_state) || block_state.name == "minecraft:barrier" {
+        return None;
+    }
+
+    let name = block_state
+        .name
+        .strip_prefix("minecraft:")
+        .unwrap_or(&block_state.name);
+
+    if let Ok(index) = BLOCK_COLORS.binary_search_by(|(block_name, _)| (*block_name).cmp(name)) {
+        return Some(BLOCK_COLORS[index].1);
+    }
+
+    let color = SUFFIX_COLORS
+        .iter()
+        .find(|(suffix, _)| name.ends_with(suffix))
+        .map_or(DEFAULT_COLOR, |(_, color)| *color);
+
+    Some(color)
+}
+
+/// Returns top visible block of every column indexed by `z * 16 + x`.
+///
+/// Heights are taken from heightmap and found by scanning sections from the top
+/// when chunk has no such heightmap. Chunk must be at least 1.13.
+pub fn top_blocks(chunk: &Chunk, heightmap: &str) -> Result<Vec<Option<TopBlock>>, SectionError> {
+    let sections = chunk.read_sections()?;
+    let heights = chunk.heightmap(heightmap);
+    let mut top_blocks = Vec::with_capacity(256);
+
+    let block_state = |x: usize, y: i32, z: usize| {
+        sections
+            .iter()
+            .find(|section| section.y as i32 == y >> 4)
+            .and_then(|section| section.block_state(x, (y & 15) as usize, z))
+            .filter(|block_state| !is_air(block_state))
+    };
+
+    let max_y = sections
+        .iter()
+        .filter(|section| section.block_states.is_some())
+        .map(|section| section.y as i32 * 16 + 16)
+        .max();
+
+    for z in 0..16 {
+        for x in 0..16 {
+            let top_block = match (&heights, max_y) {
+                (Some(heights), _) => {
+                    let y = heights[z * 16 + x] - 1;
+                    block_state(x, y, z).map(|block_state| TopBlock {
+                        y,
+                        block_state: block_state.clone(),
+                    })
+                }
+                (None, Some(max_y)) => {
+                    let min_y = chunk.height_range().min_y;
+
+                    (min_y..max_y).rev().find_map(|y| {
+                        block_state(x, y, z).map(|block_state| TopBlock {
+                            y,
+                            block_state: block_state.clone(),
+                        })
+                    })
+                }
+                (None, None) => None,
+            };
+
+            top_blocks.push(top_block);
+        }
+    }
+
+    Ok(top_blocks)
+}
+
+/// Renders region at the specified coordinates, missing chunks are transparent.
+pub fn render_region(
+    chunk_provider: &AnvilChunkProvider,
+    region_x: i32,
+    region_z: i32,
+    options: &RenderOptions,
+) -> Result<Tile, RenderError> {
+    let chunk_positions: Vec<_> = chunk_provider
+        .chunk_positions()?
+        .into_iter()
+        .filter(|(chunk_x, chunk_z)| chunk_x >> 5 == region_x && chunk_z >> 5 == region_z)
+        .collect();
+
+    Ok(render_chunks(
+        chunk_provider,
+        region_x,
+        region_z,
+        &chunk_positions,
+        options,
+    )?
+    .0)
+}
+
+/// Renders every region of provider into `r.<x>.<z>.png` tiles of output folder.
+pub fn render_provider(
+    chunk_provider: &AnvilChunkProvider,
+    output_folder_path: &Path,
+    options: &RenderOptions,
+) -> Result<RenderReport, RenderError> {
+    let mut regions: BTreeMap<(i32, i32), Vec<(i32, i32)>> = BTreeMap::new();
+    let mut report = RenderReport::default();
+
+    for (chunk_x, chunk_z) in chunk_provider.chunk_positions()? {
+        regions
+            .entry((chunk_x >> 5, chunk_z >> 5))
+            .or_default()
+            .push((chunk_x, chunk_z));
+    }
+
+    for ((region_x, region_z), chunk_positions) in regions {
+        let (tile, rendered_chunks) = render_chunks(
+            chunk_provider,
+            region_x,
+            region_z,
+            &chunk_positions,
+            options,
+        )?;
+
+        let file_name = format!("r.{}.{}.png", region_x, region_z);
+        tile.save_png(&output_folder_path.join(file_name))?;
+
+        report.tiles.push((region_x, region_z));
+        report.rendered_chunks += rendered_chunks;
+    }
+
+    Ok(report)
+}
+
+fn render_chunks(
+    chunk_provider: &AnvilChunkProvider,
+    region_x: i32,
+    region_z: i32,
+    chunk_positions: &[(i32, i32)],
+    options: &RenderOptions,
+) -> Result<(Tile, usize), RenderError> {
+    let mut tile = Tile::new(region_x, region_z);
+    let mut heights = vec![None; TILE_SIZE * TILE_SIZE];
+    let mut rendered_chunks = 0;
+
+    for &(chunk_x, chunk_z) in chunk_positions {
+        let mut chunk_compound_tag = match chunk_provider.load_chunk(chunk_x, chunk_z) {
+            Ok(chunk_compound_tag) => chunk_compound_tag,
+            Err(ChunkLoadError::ChunkNotFound { .. }) => continue,
+            Err(chunk_load_error) => return Err(chunk_load_error.into()),
+        };
+
+        if chunk_data_version(&chunk_compound_tag).unwrap_or(0) < FLATTENING_DATA_VERSION {
+            upgrade_chunk(&mut chunk_compound_tag, FLATTENING_DATA_VERSION)?;
+        }
+
+        let chunk = Chunk::new(chunk_compound_tag);
+        let top_blocks = top_blocks(&chunk, &options.heightmap)?;
+
+        let tile_x = (chunk_x & 31) as usize * 16;
+        let tile_z = (chunk_z & 31) as usize * 16;
+
+        for (index, top_block) in top_blocks.iter().enumerate() {
+            let top_block = match top_block {
+                Some(top_block) => top_block,
+                None => continue,
+            };
+
+            let color = match block_color(&top_block.block_state) {
+                Some(color) => color,
+                None => continue,
+            };
+
+            let x = tile_x + index % 16;
+            let z = tile_z + index / 16;
+
+            tile.set_pixel(x, z, [color[0], color[1], color[2], 255]);
+            heights[z * TILE_SIZE + x] = Some(top_block.y);
+        }
+
+        rendered_chunks += 1;
+    }
+
+    if options.height_shading {
+        shade(&mut tile, &heights);
+    }
+
+    Ok((tile, rendered_chunks))
+}
+
+/// Lightens pixels which are higher than northern neighbour and darkens lower ones.
+fn shade(tile: &mut Tile, heights: &[Option<i32>]) {
+    for z in 1..TILE_SIZE {
+        for x in 0..TILE_SIZE {
+            let (height, north_height) =
+                match (heights[z * TILE_SIZE + x], heights[(z - 1) * TILE_SIZE + x]) {
+                    (Some(height), Some(north_height)) => (height, north_height),
+                    _ => continue,
+                };
+
+            let factor = match height.cmp(&north_height) {
+                std::cmp::Ordering::Greater => 1.15,
+                std::cmp::Ordering::Less => 0.8,
+                std::cmp::Ordering::Equal => continue,
+            };
+
+            let mut pixel = tile.pixel(x, z);
+
+            for channel in &mut pixel[..3] {
+                *channel = (*channel as f32 * factor).min(255.0) as u8;
+            }
+
+            tile.set_pixel(x, z, pixel);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chunk::Chunk;
+    use crate::render::{
+        block_color, render_region, top_blocks, RenderOptions, BLOCK_COLORS, DEFAULT_COLOR,
+        TILE_SIZE,
+    };
+    use crate::section::BlockState;
+    use crate::AnvilChunkProvider;
+
+    #[test]
+    fn test_block_colors_sorted() {
+        for pair in BLOCK_COLORS.windows(2) {
+            assert!(pair[0].0 < pair[1].0, "{} is not sorted", pair[1].0);
+        }
+    }
+
+    #[test]
+    fn test_block_color() {
+        assert_eq!(block_color(&BlockState::new("minecraft:air")), None);
+        assert_eq!(
+            block_color(&BlockState::new("minecraft:water")),
+            Some([63, 118, 228])
+        );
+        assert_eq!(
+            block_color(&BlockState::new("minecraft:dark_oak_leaves")),
+            Some([60, 120, 40])
+        );
+        assert_eq!(
+            block_color(&BlockState::new("mod:machine")),
+            Some(DEFAULT_COLOR)
+        );
+    }
+
+    #[test]
+    fn test_top_blocks() {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+        let mut chunk = Chunk::new(chunk_provider.load_chunk(4, 2).unwrap());
+
+        // Chunk has no such heightmap, so sections are scanned from the top.
+        let scanned = top_blocks(&chunk, "SCANNED").unwrap();
+        assert_eq!(scanned.len(), 256);
+
+        let top_block = scanned[0].clone().unwrap();
+        assert!(!top_block.block_state.name.ends_with("air"));
+
+        let mut heights = [0; 256];
+        heights[0] = top_block.y + 1;
+        chunk.set_heightmap("SCANNED", &heights).unwrap();
+
+        let from_heightmap = top_blocks(&chunk, "SCANNED").unwrap();
+        assert_eq!(from_heightmap[0], Some(top_block));
+        assert_eq!(from_heightmap[1], None);
+    }
+
+    #[test]
+    fn test_render_region() {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+        let options = RenderOptions::default().without_height_shading();
+
+        let tile = render_region(&chunk_provider, 0, 0, &options).unwrap();
+        assert_eq!(tile.pixels.len(), TILE_SIZE * TILE_SIZE * 4);
+        assert_eq!(tile.pixel(4 * 16, 2 * 16)[3], 255);
+
+        let mut png = Vec::new();
+        tile.write_png(&mut png).unwrap();
+
+        let decoder = png::Decoder::new(png.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut pixels).unwrap();
+
+        assert_eq!(pixels, tile.pixels);
+
+        let tile = render_region(&chunk_provider, 5, 5, &options).unwrap();
+        assert!(tile.pixels.iter().all(|channel| *channel == 0));
+    }
+}
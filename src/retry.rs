@@ -0,0 +1,417 @@
+//! Chunk provider decorator which retries operations failed with transient errors.
+//!
+//! Region folders on network file systems and remote providers fail now and then with
+//! errors which go away on their own, like interrupted calls, timeouts and reset
+//! connections. [`RetryChunkProvider`] wraps any [`ChunkProvider`] and repeats such
+//! operations up to [`max_retries`] times, waiting longer before every attempt. Which
+//! errors are transient is decided by predicates, by default I/O errors for which
+//! [`is_transient`] returns true. Other errors, like missing or corrupted chunks, are
+//! returned right away. Error of the last attempt is returned with errors of previous
+//! attempts.
+//!
+//! [`max_retries`]: RetryOptions::max_retries
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::provider::ChunkProvider;
+//! use anvil_region::retry::{RetryChunkProvider, RetryOptions};
+//! use anvil_region::AnvilChunkProvider;
+//! use std::time::Duration;
+//!
+//! let retry_options = RetryOptions::new(5, Duration::from_millis(100));
+//! let chunk_provider =
+//!     RetryChunkProvider::new(AnvilChunkProvider::new("test/region"), retry_options);
+//!
+//! assert!(chunk_provider.load_chunk(4, 2).is_ok());
+//!
+//! let retry_error = chunk_provider.load_chunk(100, 100).unwrap_err();
+//! assert!(retry_error.previous_errors.is_empty());
+//! ```
+use crate::provider::ChunkProvider;
+use crate::{ChunkLoadError, ChunkSaveError};
+use nbt::CompoundTag;
+use std::error::Error;
+use std::time::Duration;
+use std::{fmt, io, thread};
+
+/// Default amount of retries after the first attempt.
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+/// Default delay before the first retry.
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(50);
+/// Default maximum delay between attempts.
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// How failed operations are retried.
+#[derive(Debug, Clone)]
+pub struct RetryOptions {
+    /// Amount of retries after the first attempt.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled before every next one.
+    pub initial_backoff: Duration,
+    /// Delay between attempts doesn't grow past this.
+    pub max_backoff: Duration,
+}
+
+impl RetryOptions {
+    pub fn new(max_retries: u32, initial_backoff: Duration) -> Self {
+        RetryOptions {
+            max_retries,
+            initial_backoff,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+        }
+    }
+
+    /// Limits delay between attempts.
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Returns delay before retry with specified number, starting from zero.
+    fn backoff(&self, retry: u32) -> Duration {
+        let multiplier = 1u32.checked_shl(retry).unwrap_or(u32::MAX);
+
+        self.initial_backoff
+            .checked_mul(multiplier)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
+}
+
+impl Default for RetryOptions {
+    fn default() -> Self {
+        RetryOptions::new(DEFAULT_MAX_RETRIES, DEFAULT_INITIAL_BACKOFF)
+    }
+}
+
+/// Error of the last attempt of operation with errors of attempts before it.
+#[derive(Debug)]
+pub struct RetryError<E> {
+    pub error: E,
+    /// Errors of failed attempts before the last one, the oldest first.
+    pub previous_errors: Vec<E>,
+}
+
+impl<E: fmt::Display> fmt::Display for RetryError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.previous_errors.len() {
+            0 => write!(f, "{}", self.error),
+            retries => write!(f, "{} after {} retries", self.error, retries),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for RetryError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Chunk provider which retries operations of wrapped provider failed with transient
+/// errors.
+pub struct RetryChunkProvider<P: ChunkProvider> {
+    chunk_provider: P,
+    options: RetryOptions,
+    is_transient_load_error: fn(&P::LoadError) -> bool,
+    is_transient_save_error: fn(&P::SaveError) -> bool,
+}
+
+impl<P> RetryChunkProvider<P>
+where
+    P: ChunkProvider<LoadError = ChunkLoadError, SaveError = ChunkSaveError>,
+{
+    /// Wraps provider, retrying errors for which [`is_transient_load_error`] and
+    /// [`is_transient_save_error`] return true.
+    pub fn new(chunk_provider: P, options: RetryOptions) -> Self {
+        RetryChunkProvider::with_predicates(
+            chunk_provider,
+            options,
+            is_transient_load_error,
+            is_transient_save_error,
+        )
+    }
+}
+
+impl<P: ChunkProvider> RetryChunkProvider<P> {
+    /// Wraps provider, retrying errors for which predicates return true.
+    pub fn with_predicates(
+        chunk_provider: P,
+        options: RetryOptions,
+        is_transient_load_error: fn(&P::LoadError) -> bool,
+        is_transient_save_error: fn(&P::SaveError) -> bool,
+    ) -> Self {
+        RetryChunkProvider {
+            chunk_provider,
+            options,
+            is_transient_load_error,
+            is_transient_save_error,
+        }
+    }
+
+    /// Returns wrapped provider.
+    pub fn get_ref(&self) -> &P {
+        &self.chunk_provider
+    }
+
+    /// Returns wrapped provider.
+    pub fn into_inner(self) -> P {
+        self.chunk_provider
+    }
+}
+
+impl<P: ChunkProvider> ChunkProvider for RetryChunkProvider<P> {
+    type LoadError = RetryError<P::LoadError>;
+    type SaveError = RetryError<P::SaveError>;
+
+    fn load_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<CompoundTag, Self::LoadError> {
+        retry(&self.options, self.is_transient_load_error, || {
+            self.chunk_provider.load_chunk(chunk_x, chunk_z)
+        })
+    }
+
+    /// Saves chunk at the specified coordinates, data is cloned for every attempt.
+    fn save_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        chunk_compound_tag: CompoundTag,
+    ) -> Result<(), Self::SaveError> {
+        retry(&self.options, self.is_transient_save_error, || {
+            self.chunk_provider
+                .save_chunk(chunk_x, chunk_z, chunk_compound_tag.clone())
+        })
+    }
+
+    fn delete_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<bool, Self::SaveError> {
+        retry(&self.options, self.is_transient_save_error, || {
+            self.chunk_provider.delete_chunk(chunk_x, chunk_z)
+        })
+    }
+
+    /// Returns coordinates of chunks, only error of the last attempt is returned.
+    fn chunk_positions(&self) -> Result<Vec<(i32, i32)>, io::Error> {
+        retry(&self.options, is_transient, || {
+            self.chunk_provider.chunk_positions()
+        })
+        .map_err(|retry_error| retry_error.error)
+    }
+}
+
+/// Returns true if I/O error may go away when operation is repeated.
+pub fn is_transient(io_error: &io::Error) -> bool {
+    matches!(
+        io_error.kind(),
+        io::ErrorKind::Interrupted
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::BrokenPipe
+    )
+}
+
+/// Returns true if chunk failed to load with transient I/O error.
+pub fn is_transient_load_error(chunk_load_error: &ChunkLoadError) -> bool {
+    match chunk_load_error.without_context() {
+        ChunkLoadError::ReadError { io_error } => is_transient(io_error),
+        _ => false,
+    }
+}
+
+/// Returns true if chunk failed to save with transient I/O error.
+pub fn is_transient_save_error(chunk_save_error: &ChunkSaveError) -> bool {
+    match chunk_save_error.without_context() {
+        ChunkSaveError::WriteError { io_error } => is_transient(io_error),
+        _ => false,
+    }
+}
+
+/// Repeats operation while it fails with transient error and retries are left.
+fn retry<T, E>(
+    options: &RetryOptions,
+    is_transient: fn(&E) -> bool,
+    mut operation: impl FnMut() -> Result<T, E>,
+) -> Result<T, RetryError<E>> {
+    let mut previous_errors = Vec::new();
+
+    loop {
+        let error = match operation() {
+            Ok(output) => return Ok(output),
+            Err(error) => error,
+        };
+
+        let retry = previous_errors.len() as u32;
+
+        if retry >= options.max_retries || !is_transient(&error) {
+            return Err(RetryError {
+                error,
+                previous_errors,
+            });
+        }
+
+        previous_errors.push(error);
+        thread::sleep(options.backoff(retry));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::provider::ChunkProvider;
+    use crate::retry::{
+        is_transient, is_transient_load_error, retry, RetryChunkProvider, RetryOptions,
+    };
+    use crate::{AnvilChunkProvider, ChunkLoadError};
+    use nbt::CompoundTag;
+    use std::cell::Cell;
+    use std::io;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    /// Provider which fails loads with error of kind until failures run out.
+    struct FlakyChunkProvider {
+        failures: Cell<usize>,
+        kind: io::ErrorKind,
+    }
+
+    impl ChunkProvider for FlakyChunkProvider {
+        type LoadError = io::Error;
+        type SaveError = io::Error;
+
+        fn load_chunk(&self, _chunk_x: i32, _chunk_z: i32) -> Result<CompoundTag, io::Error> {
+            match self.failures.get() {
+                0 => Ok(CompoundTag::new()),
+                failures => {
+                    self.failures.set(failures - 1);
+                    Err(self.kind.into())
+                }
+            }
+        }
+
+        fn save_chunk(&self, _: i32, _: i32, _: CompoundTag) -> Result<(), io::Error> {
+            Ok(())
+        }
+
+        fn delete_chunk(&self, _chunk_x: i32, _chunk_z: i32) -> Result<bool, io::Error> {
+            Ok(false)
+        }
+
+        fn chunk_positions(&self) -> Result<Vec<(i32, i32)>, io::Error> {
+            Ok(Vec::new())
+        }
+    }
+
+    fn interrupted() -> ChunkLoadError {
+        io::Error::from(io::ErrorKind::Interrupted).into()
+    }
+
+    #[test]
+    fn test_backoff() {
+        let options =
+            RetryOptions::new(10, Duration::from_millis(10)).max_backoff(Duration::from_millis(50));
+
+        assert_eq!(options.backoff(0), Duration::from_millis(10));
+        assert_eq!(options.backoff(2), Duration::from_millis(40));
+        assert_eq!(options.backoff(3), Duration::from_millis(50));
+        assert_eq!(options.backoff(100), Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_retry_transient() {
+        let options = RetryOptions::new(3, Duration::from_millis(1));
+        let mut attempts = 0;
+
+        let result = retry(&options, is_transient_load_error, || {
+            attempts += 1;
+
+            match attempts {
+                1 | 2 => Err(interrupted()),
+                _ => Ok(attempts),
+            }
+        });
+
+        assert_eq!(result.unwrap(), 3);
+
+        let retry_error = retry(&options, is_transient_load_error, || {
+            Err::<(), _>(interrupted())
+        })
+        .unwrap_err();
+
+        assert_eq!(retry_error.previous_errors.len(), 3);
+        assert!(retry_error.to_string().ends_with("after 3 retries"));
+    }
+
+    #[test]
+    fn test_retry_permanent() {
+        let options = RetryOptions::new(3, Duration::from_millis(1));
+        let mut attempts = 0;
+
+        let retry_error = retry(&options, is_transient_load_error, || {
+            attempts += 1;
+
+            Err::<(), _>(ChunkLoadError::ChunkNotFound {
+                chunk_x: 4,
+                chunk_z: 2,
+            })
+        })
+        .unwrap_err();
+
+        assert_eq!(attempts, 1);
+        assert!(retry_error.previous_errors.is_empty());
+    }
+
+    #[test]
+    fn test_save_load() {
+        let temp_dir = TempDir::new().unwrap();
+        let chunk_provider = RetryChunkProvider::new(
+            AnvilChunkProvider::new(temp_dir.path().to_str().unwrap()),
+            RetryOptions::default(),
+        );
+
+        let mut chunk_compound_tag = CompoundTag::new();
+        chunk_compound_tag.insert_i32("xPos", 4);
+        chunk_provider.save_chunk(4, 2, chunk_compound_tag).unwrap();
+
+        let chunk_compound_tag = chunk_provider.load_chunk(4, 2).unwrap();
+        assert_eq!(chunk_compound_tag.get_i32("xPos").unwrap(), 4);
+        assert_eq!(chunk_provider.chunk_positions().unwrap(), vec![(4, 2)]);
+
+        assert!(chunk_provider.delete_chunk(4, 2).unwrap());
+        assert!(!chunk_provider.delete_chunk(4, 2).unwrap());
+    }
+
+    #[test]
+    fn test_wrapped_provider() {
+        let options = RetryOptions::new(3, Duration::from_millis(1));
+        let flaky_chunk_provider = FlakyChunkProvider {
+            failures: Cell::new(2),
+            kind: io::ErrorKind::TimedOut,
+        };
+        let chunk_provider = RetryChunkProvider::with_predicates(
+            flaky_chunk_provider,
+            options.clone(),
+            is_transient,
+            is_transient,
+        );
+
+        assert!(chunk_provider.load_chunk(4, 2).is_ok());
+        assert_eq!(chunk_provider.get_ref().failures.get(), 0);
+
+        let flaky_chunk_provider = FlakyChunkProvider {
+            failures: Cell::new(2),
+            kind: io::ErrorKind::PermissionDenied,
+        };
+        let chunk_provider = RetryChunkProvider::with_predicates(
+            flaky_chunk_provider,
+            options,
+            is_transient,
+            is_transient,
+        );
+
+        let retry_error = chunk_provider.load_chunk(4, 2).unwrap_err();
+        assert_eq!(retry_error.error.kind(), io::ErrorKind::PermissionDenied);
+        assert!(retry_error.previous_errors.is_empty());
+        assert_eq!(chunk_provider.into_inner().failures.get(), 1);
+    }
+}
@@ -0,0 +1,257 @@
+//! Checking that chunks survive loading and saving unchanged.
+//!
+//! Chunks are stored as read, including tags added by mods or future game versions
+//! which the library doesn't know about. Tag order is kept as well, so saved chunks are
+//! byte identical before compression. [`verify_roundtrip`] lets users check this on
+//! their own worlds before running edits on them.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::roundtrip::verify_roundtrip;
+//! use anvil_region::AnvilChunkProvider;
+//!
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//! let roundtrip_report = verify_roundtrip(&chunk_provider, 4, 2).unwrap();
+//!
+//! assert!(roundtrip_report.is_faithful());
+//! ```
+use crate::diff::{diff, Difference};
+use crate::path::{join_index, join_name};
+use crate::tag::get_tag;
+use crate::{AnvilChunkProvider, ChunkLoadError};
+use nbt::decode::{read_zlib_compound_tag, TagDecodeError};
+use nbt::encode::{write_compound_tag, write_zlib_compound_tag};
+use nbt::{CompoundTag, Tag};
+use std::io;
+use std::io::Cursor;
+
+/// Possible errors while verifying round trip.
+#[derive(Debug)]
+pub enum RoundtripError {
+    /// Chunk can't be loaded.
+    ChunkLoadError { chunk_load_error: ChunkLoadError },
+    /// Chunk can't be encoded.
+    EncodeError { io_error: io::Error },
+    /// Encoded chunk can't be decoded back.
+    DecodeError { tag_decode_error: TagDecodeError },
+}
+
+impl From<ChunkLoadError> for RoundtripError {
+    fn from(chunk_load_error: ChunkLoadError) -> Self {
+        RoundtripError::ChunkLoadError { chunk_load_error }
+    }
+}
+
+impl From<io::Error> for RoundtripError {
+    fn from(io_error: io::Error) -> Self {
+        RoundtripError::EncodeError { io_error }
+    }
+}
+
+impl From<TagDecodeError> for RoundtripError {
+    fn from(tag_decode_error: TagDecodeError) -> Self {
+        RoundtripError::DecodeError { tag_decode_error }
+    }
+}
+
+/// Result of round trip verification.
+#[derive(Debug, Clone)]
+pub struct RoundtripReport {
+    /// Tags which were lost, added or changed.
+    pub differences: Vec<Difference>,
+    /// Paths of compounds which tags order changed.
+    pub reordered_compounds: Vec<String>,
+    /// Encoded chunk is identical before compression.
+    pub byte_identical: bool,
+}
+
+impl RoundtripReport {
+    /// Returns true if chunk is stored without any change.
+    pub fn is_faithful(&self) -> bool {
+        self.differences.is_empty() && self.reordered_compounds.is_empty() && self.byte_identical
+    }
+}
+
+/// Loads chunk and checks that saving it back doesn't change data.
+pub fn verify_roundtrip(
+    chunk_provider: &AnvilChunkProvider,
+    chunk_x: i32,
+    chunk_z: i32,
+) -> Result<RoundtripReport, RoundtripError> {
+    let chunk_compound_tag = chunk_provider.load_chunk(chunk_x, chunk_z)?;
+
+    verify_compound_tag_roundtrip(&chunk_compound_tag)
+}
+
+/// Encodes chunk like it is saved to region file, decodes it back and compares.
+pub fn verify_compound_tag_roundtrip(
+    chunk_compound_tag: &CompoundTag,
+) -> Result<RoundtripReport, RoundtripError> {
+    let mut compressed = Vec::new();
+    write_zlib_compound_tag(&mut compressed, chunk_compound_tag)?;
+
+    let decoded_compound_tag = read_zlib_compound_tag(&mut Cursor::new(&compressed))?;
+
+    let mut reordered_compounds = Vec::new();
+    find_reordered_compounds(
+        "",
+        chunk_compound_tag,
+        &decoded_compound_tag,
+        &mut reordered_compounds,
+    );
+
+    let byte_identical = encode(chunk_compound_tag)? == encode(&decoded_compound_tag)?;
+
+    Ok(RoundtripReport {
+        differences: diff(chunk_compound_tag, &decoded_compound_tag),
+        reordered_compounds,
+        byte_identical,
+    })
+}
+
+fn encode(compound_tag: &CompoundTag) -> Result<Vec<u8>, io::Error> {
+    let mut buffer = Vec::new();
+    write_compound_tag(&mut buffer, compound_tag)?;
+
+    Ok(buffer)
+}
+
+/// Collects paths of compounds present in both tags which have different tags order.
+fn find_reordered_compounds(
+    path: &str,
+    old: &CompoundTag,
+    new: &CompoundTag,
+    reordered_compounds: &mut Vec<String>,
+) {
+    let old_names = old
+        .iter()
+        .map(|(name, _)| name)
+        .filter(|name| new.contains_key(name));
+    let new_names = new
+        .iter()
+        .map(|(name, _)| name)
+        .filter(|name| old.contains_key(name));
+
+    if !old_names.eq(new_names) {
+        reordered_compounds.push(path.to_owned());
+    }
+
+    for (name, old_tag) in old.iter() {
+        if let Some(new_tag) = get_tag(new, name) {
+            find_reordered_tags(
+                &join_name(path, name),
+                old_tag,
+                new_tag,
+                reordered_compounds,
+            );
+        }
+    }
+}
+
+fn find_reordered_tags(path: &str, old: &Tag, new: &Tag, reordered_compounds: &mut Vec<String>) {
+    match (old, new) {
+        (Tag::Compound(old), Tag::Compound(new)) => {
+            find_reordered_compounds(path, old, new, reordered_compounds)
+        }
+        (Tag::List(old), Tag::List(new)) => {
+            for (index, (old, new)) in old.iter().zip(new).enumerate() {
+                find_reordered_tags(&join_index(path, index), old, new, reordered_compounds);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chunk::Chunk;
+    use crate::diff::diff;
+    use crate::roundtrip::{find_reordered_compounds, verify_compound_tag_roundtrip};
+    use crate::section::BlockState;
+    use crate::snbt;
+    use crate::upgrade::upgrade_chunk;
+    use crate::AnvilChunkProvider;
+    use nbt::CompoundTag;
+    use tempfile::TempDir;
+
+    /// Fixture chunk with tags added by mods at every level.
+    fn modded_chunk() -> CompoundTag {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+        let mut chunk_compound_tag = chunk_provider.load_chunk(4, 2).unwrap();
+
+        let mut chunk = Chunk::new(chunk_compound_tag.clone());
+        chunk.data_mut().insert_compound_tag(
+            "ForgeCaps",
+            snbt::from_str(r#"{"mod:energy": {Stored: 10L, Sides: [B; 1b, 0b]}}"#).unwrap(),
+        );
+        chunk
+            .sections_mut()
+            .next()
+            .unwrap()
+            .insert_str("ModBiome", "mod:dark");
+        chunk_compound_tag = chunk.into_compound_tag();
+        chunk_compound_tag.insert_i32("ModVersion", 7);
+
+        chunk_compound_tag
+    }
+
+    #[test]
+    fn test_verify_compound_tag_roundtrip() {
+        let roundtrip_report = verify_compound_tag_roundtrip(&modded_chunk()).unwrap();
+
+        assert!(roundtrip_report.is_faithful());
+    }
+
+    #[test]
+    fn test_save_load_preserves_unknown_tags() {
+        let temp_dir = TempDir::new().unwrap();
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+        let chunk_compound_tag = modded_chunk();
+
+        chunk_provider
+            .save_chunk(4, 2, chunk_compound_tag.clone())
+            .unwrap();
+
+        let loaded_compound_tag = chunk_provider.load_chunk(4, 2).unwrap();
+
+        assert!(diff(&chunk_compound_tag, &loaded_compound_tag).is_empty());
+        assert!(verify_compound_tag_roundtrip(&loaded_compound_tag)
+            .unwrap()
+            .is_faithful());
+    }
+
+    #[test]
+    fn test_edits_preserve_unknown_tags() {
+        let mut chunk = Chunk::new(modded_chunk());
+        let mut section = chunk.read_sections().unwrap().remove(0);
+        section.set_block_state(1, 1, 1, BlockState::new("minecraft:gold_block"));
+        chunk.write_section(&section).unwrap();
+
+        let chunk_compound_tag = chunk.into_compound_tag();
+        let mut upgraded_compound_tag = chunk_compound_tag.clone();
+        upgrade_chunk(&mut upgraded_compound_tag, 2860).unwrap();
+
+        for compound_tag in [chunk_compound_tag, upgraded_compound_tag] {
+            let chunk = Chunk::new(compound_tag);
+
+            assert!(chunk.data().contains_key("ForgeCaps"));
+            assert!(chunk.compound_tag().contains_key("ModVersion"));
+            assert!(chunk
+                .sections()
+                .iter()
+                .any(|section| section.contains_key("ModBiome")));
+        }
+    }
+
+    #[test]
+    fn test_find_reordered_compounds() {
+        let old = snbt::from_str("{a: {b: 1, c: 2}, d: [{e: 1, f: 1}]}").unwrap();
+        let new = snbt::from_str("{a: {c: 2, b: 1}, d: [{e: 1, f: 1}]}").unwrap();
+        let mut reordered_compounds = Vec::new();
+
+        find_reordered_compounds("", &old, &new, &mut reordered_compounds);
+
+        assert_eq!(reordered_compounds, vec!["a"]);
+    }
+}
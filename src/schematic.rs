@@ -0,0 +1,1401 @@
+//! Export of block selections into Sponge schematics and paste of schematics into world.
+//!
+//! Sponge schematic is gzip compressed NBT which WorldEdit, FAWE and most building tools
+//! load with `//schem load`. Blocks are stored as palette of block state strings like
+//! `minecraft:oak_stairs[facing=east,half=bottom]` and varint encoded palette index
+//! for every block, block entities keep their data with position relative to
+//! selection. Version 3 is written by WorldEdit 7.3 and later, version 2 is read by
+//! older tools. More information https://github.com/SpongePowered/Schematic-Specification.
+//!
+//! [`Schematic`] reads Sponge schematics of every version and MCEdit `.schematic` files
+//! with numeric block ids, which [`paste_schematic`] writes into chunks of provider,
+//! creating missing ones.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::schematic::{
+//!     export_schematic, paste_schematic, BlockSelection, PasteOptions, Schematic,
+//!     SchematicVersion,
+//! };
+//! use anvil_region::AnvilChunkProvider;
+//!
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//! let selection = BlockSelection::new((64, 0, 32), (79, 15, 47));
+//!
+//! let schematic = export_schematic(&chunk_provider, selection, SchematicVersion::V3).unwrap();
+//! let schematic_compound_tag = schematic.get_compound_tag("Schematic").unwrap();
+//!
+//! assert_eq!(schematic_compound_tag.get_i16("Width").unwrap(), 16);
+//!
+//! // Paste copy of chunk next to it, keeping blocks where copy has air.
+//! let schematic = Schematic::from_compound_tag(&schematic).unwrap();
+//! let paste_options = PasteOptions::default().skip_air();
+//!
+//! # let region_dir = tempfile::TempDir::new().unwrap();
+//! # let chunk_provider = AnvilChunkProvider::new(region_dir.path().to_str().unwrap());
+//! let report = paste_schematic(&chunk_provider, &schematic, (80, 0, 32), &paste_options).unwrap();
+//! assert_eq!(report.created_chunks, vec![(5, 2)]);
+//! ```
+use crate::chunk::{Chunk, ChunkStatus, ChunkTag};
+use crate::data::{read_data_file, write_data_file, DataFileError};
+use crate::height::HeightRange;
+use crate::light::invalidate_light;
+use crate::section::{block_index, BlockState, PalettedContainer, Section, SectionError};
+use crate::upgrade::legacy;
+use crate::version::{LEVEL_WRAPPER_REMOVAL_DATA_VERSION, RELEASES};
+use crate::{AnvilChunkProvider, ChunkLoadError, ChunkSaveError};
+use nbt::{CompoundTag, CompoundTagError, Tag};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::convert::TryFrom;
+use std::path::Path;
+
+/// Maximum amount of blocks along every axis of schematic, stored as unsigned short.
+pub const MAXIMUM_SCHEMATIC_SIZE: u32 = u16::MAX as u32;
+
+/// Block entity tags which are stored as schematic fields.
+const BLOCK_ENTITY_POSITION_TAGS: [&str; 5] = ["x", "y", "z", "id", "keepPacked"];
+
+/// Possible errors while exporting, reading or pasting schematic.
+#[derive(Debug)]
+pub enum SchematicError {
+    /// Chunk can't be loaded.
+    ChunkLoadError { chunk_load_error: ChunkLoadError },
+    /// Chunk sections can't be read.
+    SectionError { section_error: SectionError },
+    /// Selection is larger than schematic can store along some axis.
+    SelectionTooLarge { size: (u32, u32, u32) },
+    /// Chunk can't be saved.
+    ChunkSaveError { chunk_save_error: ChunkSaveError },
+    /// Schematic file can't be read or written.
+    DataFileError { data_file_error: DataFileError },
+    /// Required schematic tag is missing or has wrong type.
+    InvalidTag { name: String },
+    /// Sponge schematic version isn't supported.
+    UnsupportedVersion { version: i32 },
+}
+
+impl From<ChunkLoadError> for SchematicError {
+    fn from(chunk_load_error: ChunkLoadError) -> Self {
+        SchematicError::ChunkLoadError { chunk_load_error }
+    }
+}
+
+impl From<SectionError> for SchematicError {
+    fn from(section_error: SectionError) -> Self {
+        SchematicError::SectionError { section_error }
+    }
+}
+
+impl From<ChunkSaveError> for SchematicError {
+    fn from(chunk_save_error: ChunkSaveError) -> Self {
+        SchematicError::ChunkSaveError { chunk_save_error }
+    }
+}
+
+impl From<CompoundTagError<'_>> for SchematicError {
+    fn from(compound_tag_error: CompoundTagError) -> Self {
+        let name = match compound_tag_error {
+            CompoundTagError::TagNotFound { name } => name,
+            CompoundTagError::TagWrongType { name, .. } => name,
+        };
+
+        SchematicError::InvalidTag {
+            name: name.to_owned(),
+        }
+    }
+}
+
+impl From<DataFileError> for SchematicError {
+    fn from(data_file_error: DataFileError) -> Self {
+        SchematicError::DataFileError { data_file_error }
+    }
+}
+
+/// Version of Sponge schematic format.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum SchematicVersion {
+    /// Blocks, palette and block entities are fields of root `Schematic` compound.
+    V2,
+    /// Blocks, palette and block entities are grouped into `Blocks` compound.
+    #[default]
+    V3,
+}
+
+/// Box of blocks, both corners inclusive.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BlockSelection {
+    pub min: (i32, i32, i32),
+    pub max: (i32, i32, i32),
+}
+
+impl BlockSelection {
+    /// Creates selection between two opposite corners in any order.
+    pub fn new(corner: (i32, i32, i32), opposite_corner: (i32, i32, i32)) -> Self {
+        BlockSelection {
+            min: (
+                corner.0.min(opposite_corner.0),
+                corner.1.min(opposite_corner.1),
+                corner.2.min(opposite_corner.2),
+            ),
+            max: (
+                corner.0.max(opposite_corner.0),
+                corner.1.max(opposite_corner.1),
+                corner.2.max(opposite_corner.2),
+            ),
+        }
+    }
+
+    /// Returns amount of blocks along X, Y and Z.
+    pub fn size(&self) -> (u32, u32, u32) {
+        (
+            (self.max.0 as i64 - self.min.0 as i64 + 1) as u32,
+            (self.max.1 as i64 - self.min.1 as i64 + 1) as u32,
+            (self.max.2 as i64 - self.min.2 as i64 + 1) as u32,
+        )
+    }
+
+    pub fn contains(&self, (x, y, z): (i32, i32, i32)) -> bool {
+        (self.min.0..=self.max.0).contains(&x)
+            && (self.min.1..=self.max.1).contains(&y)
+            && (self.min.2..=self.max.2).contains(&z)
+    }
+}
+
+/// Blocks of selection indexed by `y * width * length + z * width + x`.
+struct SchematicBlocks {
+    selection: BlockSelection,
+    size: (usize, usize, usize),
+    /// Block state strings in order of palette indices, air first.
+    palette: Vec<String>,
+    palette_indices: HashMap<String, usize>,
+    indices: Vec<usize>,
+    block_entities: Vec<CompoundTag>,
+}
+
+impl SchematicBlocks {
+    fn new(selection: BlockSelection) -> Self {
+        let (width, height, length) = selection.size();
+        let size = (width as usize, height as usize, length as usize);
+
+        let mut schematic_blocks = SchematicBlocks {
+            selection,
+            size,
+            palette: Vec::new(),
+            palette_indices: HashMap::new(),
+            indices: vec![0; size.0 * size.1 * size.2],
+            block_entities: Vec::new(),
+        };
+        schematic_blocks.palette_index(&BlockState::new("minecraft:air"));
+
+        schematic_blocks
+    }
+
+    /// Returns palette index of block state, adding it to palette if needed.
+    fn palette_index(&mut self, block_state: &BlockState) -> usize {
+        let block_state_string = block_state_string(block_state);
+
+        if let Some(palette_index) = self.palette_indices.get(&block_state_string) {
+            return *palette_index;
+        }
+
+        let palette_index = self.palette.len();
+        self.palette.push(block_state_string.clone());
+        self.palette_indices
+            .insert(block_state_string, palette_index);
+
+        palette_index
+    }
+
+    /// Copies blocks and block entities of chunk which are inside of selection.
+    fn add_chunk(
+        &mut self,
+        chunk: &Chunk,
+        (chunk_x, chunk_z): (i32, i32),
+    ) -> Result<(), SchematicError> {
+        let (min_x, min_y, min_z) = self.selection.min;
+        let (max_x, max_y, max_z) = self.selection.max;
+        let (width, _, length) = self.size;
+
+        for section in chunk.read_sections()? {
+            let block_states = match &section.block_states {
+                Some(block_states) => block_states,
+                None => continue,
+            };
+
+            let section_min_y = section.y as i32 * 16;
+
+            if section_min_y > max_y || section_min_y + 15 < min_y {
+                continue;
+            }
+
+            let palette_indices: Vec<_> = block_states
+                .palette()
+                .iter()
+                .map(|block_state| self.palette_index(block_state))
+                .collect();
+
+            for y in section_min_y.max(min_y)..=(section_min_y + 15).min(max_y) {
+                for z in (chunk_z * 16).max(min_z)..=(chunk_z * 16 + 15).min(max_z) {
+                    for x in (chunk_x * 16).max(min_x)..=(chunk_x * 16 + 15).min(max_x) {
+                        let section_index = block_index(x as usize, y as usize, z as usize);
+                        let palette_index = block_states.indices()[section_index] as usize;
+
+                        let index = (y - min_y) as usize * width * length
+                            + (z - min_z) as usize * width
+                            + (x - min_x) as usize;
+                        self.indices[index] = palette_indices[palette_index];
+                    }
+                }
+            }
+        }
+
+        for block_entity in chunk.block_entities() {
+            let position = match (
+                block_entity.get_i32("x"),
+                block_entity.get_i32("y"),
+                block_entity.get_i32("z"),
+            ) {
+                (Ok(x), Ok(y), Ok(z)) => (x, y, z),
+                _ => continue,
+            };
+
+            if self.selection.contains(position) {
+                self.block_entities.push(block_entity.clone());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns palette compound with index of every block state string.
+    fn palette_compound_tag(&self) -> CompoundTag {
+        let mut palette_compound_tag = CompoundTag::new();
+
+        for (palette_index, block_state_string) in self.palette.iter().enumerate() {
+            palette_compound_tag.insert_i32(block_state_string, palette_index as i32);
+        }
+
+        palette_compound_tag
+    }
+
+    /// Returns palette indices encoded as varints.
+    fn block_data(&self) -> Vec<i8> {
+        let mut block_data = Vec::with_capacity(self.indices.len());
+
+        for &palette_index in &self.indices {
+            let mut value = palette_index;
+
+            while value >= 0x80 {
+                block_data.push((value as u8 & 0x7F | 0x80) as i8);
+                value >>= 7;
+            }
+
+            block_data.push(value as i8);
+        }
+
+        block_data
+    }
+
+    /// Returns block entities with position relative to selection.
+    ///
+    /// Version 3 nests block entity tags into `Data`, version 2 keeps them alongside.
+    fn block_entity_tags(&self, version: SchematicVersion) -> Vec<CompoundTag> {
+        let (min_x, min_y, min_z) = self.selection.min;
+
+        self.block_entities
+            .iter()
+            .map(|block_entity| {
+                let mut data_compound_tag = CompoundTag::new();
+
+                for (name, tag) in block_entity.iter() {
+                    if !BLOCK_ENTITY_POSITION_TAGS.contains(&name.as_str()) {
+                        data_compound_tag.insert(name, tag.clone());
+                    }
+                }
+
+                let mut schematic_block_entity = match version {
+                    SchematicVersion::V2 => data_compound_tag,
+                    SchematicVersion::V3 => {
+                        let mut schematic_block_entity = CompoundTag::new();
+                        schematic_block_entity.insert_compound_tag("Data", data_compound_tag);
+
+                        schematic_block_entity
+                    }
+                };
+
+                schematic_block_entity.insert_i32_vec(
+                    "Pos",
+                    vec![
+                        block_entity.get_i32("x").unwrap_or_default() - min_x,
+                        block_entity.get_i32("y").unwrap_or_default() - min_y,
+                        block_entity.get_i32("z").unwrap_or_default() - min_z,
+                    ],
+                );
+
+                if let Ok(id) = block_entity.get_str("id") {
+                    schematic_block_entity.insert_str("Id", id);
+                }
+
+                schematic_block_entity
+            })
+            .collect()
+    }
+}
+
+/// Returns block state in form used by commands, properties are sorted by name.
+fn block_state_string(block_state: &BlockState) -> String {
+    if block_state.properties.is_empty() {
+        return block_state.name.clone();
+    }
+
+    let mut properties: Vec<_> = block_state
+        .properties
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect();
+    properties.sort();
+
+    format!("{}[{}]", block_state.name, properties.join(","))
+}
+
+/// Exports blocks and block entities of selection into schematic compound tag.
+///
+/// Missing chunks and sections are exported as air. Schematic gets data version of
+/// the first exported chunk and offset of the minimum selection corner.
+pub fn export_schematic(
+    chunk_provider: &AnvilChunkProvider,
+    selection: BlockSelection,
+    version: SchematicVersion,
+) -> Result<CompoundTag, SchematicError> {
+    let size = selection.size();
+    let (width, height, length) = size;
+
+    if width > MAXIMUM_SCHEMATIC_SIZE
+        || height > MAXIMUM_SCHEMATIC_SIZE
+        || length > MAXIMUM_SCHEMATIC_SIZE
+    {
+        return Err(SchematicError::SelectionTooLarge { size });
+    }
+
+    let mut schematic_blocks = SchematicBlocks::new(selection);
+    let mut data_version = None;
+
+    for chunk_x in selection.min.0 >> 4..=selection.max.0 >> 4 {
+        for chunk_z in selection.min.2 >> 4..=selection.max.2 >> 4 {
+            let chunk = match chunk_provider.load_chunk(chunk_x, chunk_z) {
+                Ok(chunk_compound_tag) => Chunk::new(chunk_compound_tag),
+                Err(ChunkLoadError::RegionNotFound { .. })
+                | Err(ChunkLoadError::ChunkNotFound { .. }) => continue,
+                Err(chunk_load_error) => return Err(chunk_load_error.into()),
+            };
+
+            data_version = data_version.or_else(|| chunk.data_version());
+            schematic_blocks.add_chunk(&chunk, (chunk_x, chunk_z))?;
+        }
+    }
+
+    let data_version = data_version.unwrap_or(RELEASES[RELEASES.len() - 1].data_version);
+    let block_entities = schematic_blocks.block_entity_tags(version);
+
+    let mut schematic_compound_tag = CompoundTag::named("Schematic");
+    schematic_compound_tag.insert_i32("DataVersion", data_version);
+    schematic_compound_tag.insert_i16("Width", width as u16 as i16);
+    schematic_compound_tag.insert_i16("Height", height as u16 as i16);
+    schematic_compound_tag.insert_i16("Length", length as u16 as i16);
+    schematic_compound_tag.insert_i32_vec(
+        "Offset",
+        vec![selection.min.0, selection.min.1, selection.min.2],
+    );
+
+    match version {
+        SchematicVersion::V2 => {
+            schematic_compound_tag.insert_i32("Version", 2);
+            schematic_compound_tag.insert_i32("PaletteMax", schematic_blocks.palette.len() as i32);
+            schematic_compound_tag
+                .insert_compound_tag("Palette", schematic_blocks.palette_compound_tag());
+            schematic_compound_tag.insert_i8_vec("BlockData", schematic_blocks.block_data());
+            schematic_compound_tag.insert_compound_tag_vec("BlockEntities", block_entities);
+
+            Ok(schematic_compound_tag)
+        }
+        SchematicVersion::V3 => {
+            let mut blocks_compound_tag = CompoundTag::new();
+            blocks_compound_tag
+                .insert_compound_tag("Palette", schematic_blocks.palette_compound_tag());
+            blocks_compound_tag.insert_i8_vec("Data", schematic_blocks.block_data());
+            blocks_compound_tag.insert_compound_tag_vec("BlockEntities", block_entities);
+
+            schematic_compound_tag.insert_i32("Version", 3);
+            schematic_compound_tag.insert_compound_tag("Blocks", blocks_compound_tag);
+
+            // Version 3 root is unnamed and contains single schematic compound.
+            let mut root_compound_tag = CompoundTag::new();
+            root_compound_tag.insert("Schematic", Tag::Compound(schematic_compound_tag));
+
+            Ok(root_compound_tag)
+        }
+    }
+}
+
+/// Exports selection and writes it as schematic file, usually with `.schem` extension.
+pub fn save_schematic(
+    chunk_provider: &AnvilChunkProvider,
+    selection: BlockSelection,
+    version: SchematicVersion,
+    path: &Path,
+) -> Result<(), SchematicError> {
+    let schematic = export_schematic(chunk_provider, selection, version)?;
+    write_data_file(path, &schematic)?;
+
+    Ok(())
+}
+
+/// Block entity of schematic.
+#[derive(Debug, Clone)]
+pub struct SchematicBlockEntity {
+    /// Position relative to the minimum corner of schematic.
+    pub position: (i32, i32, i32),
+    /// Block entity tags with `id`, without position.
+    pub compound_tag: CompoundTag,
+}
+
+/// Blocks and block entities read from schematic file.
+#[derive(Debug, Clone)]
+pub struct Schematic {
+    /// Amount of blocks along X, Y and Z.
+    pub size: (u32, u32, u32),
+    /// Position of schematic relative to origin it was copied from.
+    pub offset: (i32, i32, i32),
+    /// Data version of blocks, absent in schematics made before data versions.
+    pub data_version: Option<i32>,
+    /// Block states indexed by `y * width * length + z * width + x`.
+    pub blocks: PalettedContainer<BlockState>,
+    pub block_entities: Vec<SchematicBlockEntity>,
+}
+
+impl Schematic {
+    /// Reads Sponge schematic of version 1 to 3 or MCEdit schematic from file.
+    pub fn load(path: &Path) -> Result<Self, SchematicError> {
+        let compound_tag = read_data_file(path)?;
+
+        Schematic::from_compound_tag(&compound_tag)
+    }
+
+    /// Decodes Sponge schematic of version 1 to 3 or MCEdit schematic.
+    ///
+    /// Numeric block ids of MCEdit schematics are flattened, ids of their block
+    /// entities are kept as they are stored.
+    pub fn from_compound_tag(compound_tag: &CompoundTag) -> Result<Self, SchematicError> {
+        // Version 3 nests schematic inside of unnamed root.
+        if let Ok(schematic_compound_tag) = compound_tag.get_compound_tag("Schematic") {
+            return Schematic::from_compound_tag(schematic_compound_tag);
+        }
+
+        if compound_tag.contains_key("Materials") {
+            return Schematic::from_mcedit_compound_tag(compound_tag);
+        }
+
+        let version = compound_tag.get_i32("Version")?;
+        let size = schematic_size(compound_tag)?;
+        let offset = match compound_tag.get_i32_vec("Offset").map(Vec::as_slice) {
+            Ok([x, y, z]) => (*x, *y, *z),
+            _ => (0, 0, 0),
+        };
+
+        let (palette_compound_tag, block_data, block_entities) = match version {
+            1 | 2 => (
+                compound_tag.get_compound_tag("Palette")?,
+                compound_tag.get_i8_vec("BlockData")?,
+                compound_tag
+                    .get_compound_tag_vec("BlockEntities")
+                    .or_else(|_| compound_tag.get_compound_tag_vec("TileEntities"))
+                    .unwrap_or_default(),
+            ),
+            3 => {
+                let blocks_compound_tag = compound_tag.get_compound_tag("Blocks")?;
+
+                (
+                    blocks_compound_tag.get_compound_tag("Palette")?,
+                    blocks_compound_tag.get_i8_vec("Data")?,
+                    blocks_compound_tag
+                        .get_compound_tag_vec("BlockEntities")
+                        .unwrap_or_default(),
+                )
+            }
+            _ => return Err(SchematicError::UnsupportedVersion { version }),
+        };
+
+        let palette = read_palette(palette_compound_tag)?;
+        let indices = read_varints(block_data, schematic_volume(size)).ok_or_else(|| {
+            SchematicError::InvalidTag {
+                name: "BlockData".to_owned(),
+            }
+        })?;
+
+        let block_entities = block_entities
+            .into_iter()
+            .filter_map(|block_entity| read_sponge_block_entity(block_entity, version))
+            .collect();
+
+        Ok(Schematic {
+            size,
+            offset,
+            data_version: compound_tag.get_i32("DataVersion").ok(),
+            blocks: PalettedContainer::from_parts(palette, indices)?,
+            block_entities,
+        })
+    }
+
+    fn from_mcedit_compound_tag(compound_tag: &CompoundTag) -> Result<Self, SchematicError> {
+        let size = schematic_size(compound_tag)?;
+        let volume = schematic_volume(size);
+        let blocks = compound_tag.get_i8_vec("Blocks")?;
+        let data = compound_tag.get_i8_vec("Data")?;
+        let add_blocks = compound_tag
+            .get_i8_vec("AddBlocks")
+            .map_or(&[][..], Vec::as_slice);
+
+        if blocks.len() < volume || data.len() < volume {
+            return Err(SchematicError::InvalidTag {
+                name: "Blocks".to_owned(),
+            });
+        }
+
+        let offset = (
+            compound_tag.get_i32("WEOffsetX").unwrap_or_default(),
+            compound_tag.get_i32("WEOffsetY").unwrap_or_default(),
+            compound_tag.get_i32("WEOffsetZ").unwrap_or_default(),
+        );
+
+        let mut palette = Vec::new();
+        let mut palette_indices = HashMap::new();
+        let mut indices = Vec::with_capacity(volume);
+
+        for index in 0..volume {
+            // Block ids above 255 keep upper bits in nibbles of `AddBlocks`.
+            let add = add_blocks
+                .get(index / 2)
+                .map_or(0, |add| (*add as u8 >> ((1 - index % 2) * 4)) & 0xF);
+            let id = blocks[index] as u8 as u16 | (add as u16) << 8;
+            let legacy_block = (id, data[index] as u8 & 0xF);
+
+            let palette_index = *palette_indices.entry(legacy_block).or_insert_with(|| {
+                let block_state_compound_tag = legacy::block_state(legacy_block.0, legacy_block.1);
+                palette.push(BlockState::from_compound_tag(&block_state_compound_tag));
+
+                palette.len() as u16 - 1
+            });
+
+            indices.push(palette_index);
+        }
+
+        let palette = palette.into_iter().collect::<Result<_, _>>()?;
+
+        let block_entities = compound_tag
+            .get_compound_tag_vec("TileEntities")
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|block_entity| {
+                let position = (
+                    block_entity.get_i32("x").ok()?,
+                    block_entity.get_i32("y").ok()?,
+                    block_entity.get_i32("z").ok()?,
+                );
+
+                Some(SchematicBlockEntity {
+                    position,
+                    compound_tag: copy_without_tags(block_entity, &["x", "y", "z"]),
+                })
+            })
+            .collect();
+
+        Ok(Schematic {
+            size,
+            offset,
+            data_version: None,
+            blocks: PalettedContainer::from_parts(palette, indices)?,
+            block_entities,
+        })
+    }
+
+    /// Returns block state at schematic relative coordinates.
+    pub fn block_state(&self, x: u32, y: u32, z: u32) -> Option<&BlockState> {
+        let (width, height, length) = self.size;
+
+        if x >= width || y >= height || z >= length {
+            return None;
+        }
+
+        let index = (y as usize * length as usize + z as usize) * width as usize + x as usize;
+
+        self.blocks.get(index)
+    }
+}
+
+/// How schematic is pasted into world.
+#[derive(Debug, Clone, Default)]
+pub struct PasteOptions {
+    /// Air blocks of schematic leave world blocks unchanged.
+    pub skip_air: bool,
+    /// Block entities of schematic aren't placed.
+    pub skip_block_entities: bool,
+    /// Data version of created chunks, schematic data version or the latest known
+    /// release by default.
+    pub data_version: Option<i32>,
+}
+
+impl PasteOptions {
+    pub fn skip_air(mut self) -> Self {
+        self.skip_air = true;
+        self
+    }
+
+    pub fn skip_block_entities(mut self) -> Self {
+        self.skip_block_entities = true;
+        self
+    }
+
+    pub fn data_version(mut self, data_version: i32) -> Self {
+        self.data_version = Some(data_version);
+        self
+    }
+}
+
+/// Result of pasting schematic.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PasteReport {
+    /// Amount of blocks which were set.
+    pub pasted_blocks: usize,
+    /// Amount of blocks which were outside of chunk height range.
+    pub clipped_blocks: usize,
+    /// Positions of existing chunks which were changed.
+    pub modified_chunks: Vec<(i32, i32)>,
+    /// Positions of chunks which were created.
+    pub created_chunks: Vec<(i32, i32)>,
+}
+
+/// Pastes schematic with its minimum corner at world position.
+///
+/// Position where schematic was copied from is usually origin plus schematic offset.
+/// Block entities of replaced blocks are removed. Light of changed chunks is
+/// invalidated for the game to recompute, heightmaps are left as they are.
+pub fn paste_schematic(
+    chunk_provider: &AnvilChunkProvider,
+    schematic: &Schematic,
+    (position_x, position_y, position_z): (i32, i32, i32),
+    options: &PasteOptions,
+) -> Result<PasteReport, SchematicError> {
+    let (width, height, length) = schematic.size;
+    let mut report = PasteReport::default();
+
+    if width == 0 || height == 0 || length == 0 {
+        return Ok(report);
+    }
+
+    let max_x = position_x + width as i32 - 1;
+    let max_z = position_z + length as i32 - 1;
+
+    for chunk_x in position_x >> 4..=max_x >> 4 {
+        for chunk_z in position_z >> 4..=max_z >> 4 {
+            let data_version = options
+                .data_version
+                .or(schematic.data_version)
+                .unwrap_or(RELEASES[RELEASES.len() - 1].data_version);
+            let (mut chunk, created) =
+                load_or_create_chunk(chunk_provider, chunk_x, chunk_z, data_version)?;
+
+            let chunk_paste = ChunkPaste {
+                schematic,
+                position: (position_x, position_y, position_z),
+                chunk_position: (chunk_x, chunk_z),
+                options,
+            };
+
+            let mut block_writer = ChunkBlockWriter::new(&chunk)?;
+            chunk_paste.set_blocks(&mut block_writer);
+            let block_entities = chunk_paste.block_entities(&block_writer.height_range);
+
+            report.pasted_blocks += block_writer.set_positions.len();
+            report.clipped_blocks += block_writer.clipped_blocks;
+
+            if !block_writer.write(&mut chunk, block_entities)? {
+                continue;
+            }
+
+            chunk_provider.save_chunk(chunk_x, chunk_z, chunk.into_compound_tag())?;
+
+            if created {
+                report.created_chunks.push((chunk_x, chunk_z));
+            } else {
+                report.modified_chunks.push((chunk_x, chunk_z));
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Part of schematic which falls into chunk.
+struct ChunkPaste<'a> {
+    schematic: &'a Schematic,
+    position: (i32, i32, i32),
+    chunk_position: (i32, i32),
+    options: &'a PasteOptions,
+}
+
+impl ChunkPaste<'_> {
+    fn set_blocks(&self, block_writer: &mut ChunkBlockWriter) {
+        let (position_x, position_y, position_z) = self.position;
+        let (chunk_x, chunk_z) = self.chunk_position;
+        let (width, height, length) = self.schematic.size;
+
+        let min_x = (chunk_x * 16).max(position_x);
+        let max_x = (chunk_x * 16 + 15).min(position_x + width as i32 - 1);
+        let min_z = (chunk_z * 16).max(position_z);
+        let max_z = (chunk_z * 16 + 15).min(position_z + length as i32 - 1);
+
+        for y in position_y..position_y + height as i32 {
+            for z in min_z..=max_z {
+                for x in min_x..=max_x {
+                    let block_state = self
+                        .schematic
+                        .block_state(
+                            (x - position_x) as u32,
+                            (y - position_y) as u32,
+                            (z - position_z) as u32,
+                        )
+                        .expect("Position is inside of schematic");
+
+                    if self.options.skip_air && is_air(block_state) {
+                        continue;
+                    }
+
+                    block_writer.set_block_state((x, y, z), block_state);
+                }
+            }
+        }
+    }
+
+    /// Returns block entities of schematic inside of chunk with world positions.
+    fn block_entities(&self, height_range: &HeightRange) -> Vec<CompoundTag> {
+        if self.options.skip_block_entities {
+            return Vec::new();
+        }
+
+        let (position_x, position_y, position_z) = self.position;
+
+        self.schematic
+            .block_entities
+            .iter()
+            .filter_map(|schematic_block_entity| {
+                let (x, y, z) = schematic_block_entity.position;
+                let (x, y, z) = (x + position_x, y + position_y, z + position_z);
+
+                if (x >> 4, z >> 4) != self.chunk_position || !height_range.contains(y) {
+                    return None;
+                }
+
+                let mut block_entity = schematic_block_entity.compound_tag.clone();
+                block_entity.insert_i32("x", x);
+                block_entity.insert_i32("y", y);
+                block_entity.insert_i32("z", z);
+
+                Some(block_entity)
+            })
+            .collect()
+    }
+}
+
+/// Sections of chunk which blocks are set in before they are written back.
+pub(crate) struct ChunkBlockWriter {
+    pub(crate) height_range: HeightRange,
+    sections: BTreeMap<i8, Section>,
+    changed_section_ys: BTreeSet<i8>,
+    /// World positions of set blocks.
+    pub(crate) set_positions: HashSet<(i32, i32, i32)>,
+    /// Amount of blocks outside of chunk height range.
+    pub(crate) clipped_blocks: usize,
+}
+
+impl ChunkBlockWriter {
+    pub(crate) fn new(chunk: &Chunk) -> Result<Self, SectionError> {
+        let sections = chunk
+            .read_sections()?
+            .into_iter()
+            .map(|section| (section.y, section))
+            .collect();
+
+        Ok(ChunkBlockWriter {
+            height_range: chunk.height_range(),
+            sections,
+            changed_section_ys: BTreeSet::new(),
+            set_positions: HashSet::new(),
+            clipped_blocks: 0,
+        })
+    }
+
+    /// Sets block at world position which is inside of chunk columns.
+    pub(crate) fn set_block_state(&mut self, (x, y, z): (i32, i32, i32), block_state: &BlockState) {
+        if !self.height_range.contains(y) {
+            self.clipped_blocks += 1;
+            return;
+        }
+
+        let section_y = (y >> 4) as i8;
+        self.sections
+            .entry(section_y)
+            .or_insert_with(|| Section::new(section_y))
+            .set_block_state(
+                (x & 15) as usize,
+                (y & 15) as usize,
+                (z & 15) as usize,
+                block_state.clone(),
+            );
+
+        self.changed_section_ys.insert(section_y);
+        self.set_positions.insert((x, y, z));
+    }
+
+    /// Writes changed sections and block entities into chunk, returns false if there
+    /// is nothing to write.
+    ///
+    /// Block entities of set blocks are replaced with the specified ones. Light is
+    /// invalidated for the game to recompute, heightmaps are left as they are.
+    pub(crate) fn write(
+        self,
+        chunk: &mut Chunk,
+        block_entities: Vec<CompoundTag>,
+    ) -> Result<bool, SectionError> {
+        if self.set_positions.is_empty() && block_entities.is_empty() {
+            return Ok(false);
+        }
+
+        for section_y in &self.changed_section_ys {
+            chunk.write_section(&self.sections[section_y])?;
+        }
+
+        let set_positions = &self.set_positions;
+        let block_entity_tags = block_entities.into_iter().map(Tag::Compound);
+
+        match chunk.get_mut(ChunkTag::BlockEntities) {
+            Some(Tag::List(tags)) => {
+                tags.retain(|tag| match tag {
+                    Tag::Compound(block_entity) => {
+                        !set_positions.contains(&block_entity_position(block_entity))
+                    }
+                    _ => true,
+                });
+                tags.extend(block_entity_tags);
+            }
+            _ => chunk.insert(
+                ChunkTag::BlockEntities,
+                Tag::List(block_entity_tags.collect()),
+            ),
+        }
+
+        invalidate_light(chunk);
+
+        Ok(true)
+    }
+}
+
+/// Loads chunk or creates it in format of data version if it's missing.
+///
+/// Returns true along with chunk if it was created.
+pub(crate) fn load_or_create_chunk(
+    chunk_provider: &AnvilChunkProvider,
+    chunk_x: i32,
+    chunk_z: i32,
+    data_version: i32,
+) -> Result<(Chunk, bool), ChunkLoadError> {
+    match chunk_provider.load_chunk(chunk_x, chunk_z) {
+        Ok(chunk_compound_tag) => Ok((Chunk::new(chunk_compound_tag), false)),
+        Err(ChunkLoadError::RegionNotFound { .. }) | Err(ChunkLoadError::ChunkNotFound { .. }) => {
+            Ok((new_chunk(chunk_x, chunk_z, data_version), true))
+        }
+        Err(chunk_load_error) => Err(chunk_load_error),
+    }
+}
+
+/// Creates chunk without sections in format of data version.
+fn new_chunk(chunk_x: i32, chunk_z: i32, data_version: i32) -> Chunk {
+    let mut data = CompoundTag::new();
+    data.insert_i32("xPos", chunk_x);
+    data.insert_i32("zPos", chunk_z);
+
+    let mut chunk_compound_tag = if data_version >= LEVEL_WRAPPER_REMOVAL_DATA_VERSION {
+        data.insert_i32("yPos", HeightRange::OVERWORLD.min_section_y());
+        data
+    } else {
+        let mut chunk_compound_tag = CompoundTag::new();
+        chunk_compound_tag.insert_compound_tag("Level", data);
+        chunk_compound_tag
+    };
+
+    chunk_compound_tag.insert_i32("DataVersion", data_version);
+
+    let mut chunk = Chunk::new(chunk_compound_tag);
+    chunk.set_status(ChunkStatus::Full);
+
+    chunk
+}
+
+pub(crate) fn is_air(block_state: &BlockState) -> bool {
+    matches!(
+        block_state.name.as_str(),
+        "minecraft:air" | "minecraft:cave_air" | "minecraft:void_air"
+    )
+}
+
+pub(crate) fn block_entity_position(block_entity: &CompoundTag) -> (i32, i32, i32) {
+    (
+        block_entity.get_i32("x").unwrap_or_default(),
+        block_entity.get_i32("y").unwrap_or_default(),
+        block_entity.get_i32("z").unwrap_or_default(),
+    )
+}
+
+/// Returns copy of compound tag without tags with specified names.
+pub(crate) fn copy_without_tags(compound_tag: &CompoundTag, names: &[&str]) -> CompoundTag {
+    let mut copy = CompoundTag::new();
+
+    for (name, tag) in compound_tag.iter() {
+        if !names.contains(&name.as_str()) {
+            copy.insert(name, tag.clone());
+        }
+    }
+
+    copy
+}
+
+fn read_sponge_block_entity(
+    block_entity: &CompoundTag,
+    version: i32,
+) -> Option<SchematicBlockEntity> {
+    let position = match block_entity.get_i32_vec("Pos").ok()?.as_slice() {
+        [x, y, z] => (*x, *y, *z),
+        _ => return None,
+    };
+
+    // Version 3 nests block entity tags into `Data`, earlier versions keep them alongside.
+    let mut compound_tag = match version {
+        3 => block_entity.get_compound_tag("Data").map_or_else(
+            |_| CompoundTag::new(),
+            |data| copy_without_tags(data, &BLOCK_ENTITY_POSITION_TAGS),
+        ),
+        _ => copy_without_tags(block_entity, &["Pos", "Id"]),
+    };
+
+    compound_tag.insert_str("id", block_entity.get_str("Id").ok()?);
+
+    Some(SchematicBlockEntity {
+        position,
+        compound_tag,
+    })
+}
+
+/// Returns width, height and length of schematic.
+fn schematic_size(compound_tag: &CompoundTag) -> Result<(u32, u32, u32), SchematicError> {
+    Ok((
+        compound_tag.get_i16("Width")? as u16 as u32,
+        compound_tag.get_i16("Height")? as u16 as u32,
+        compound_tag.get_i16("Length")? as u16 as u32,
+    ))
+}
+
+fn schematic_volume((width, height, length): (u32, u32, u32)) -> usize {
+    width as usize * height as usize * length as usize
+}
+
+/// Returns block states in order of palette indices.
+fn read_palette(palette_compound_tag: &CompoundTag) -> Result<Vec<BlockState>, SchematicError> {
+    let mut palette = Vec::new();
+
+    for (block_state_string, tag) in palette_compound_tag.iter() {
+        let palette_index = match tag {
+            Tag::Int(palette_index) if *palette_index >= 0 => *palette_index as usize,
+            _ => {
+                return Err(SchematicError::InvalidTag {
+                    name: block_state_string.clone(),
+                })
+            }
+        };
+
+        if palette.len() <= palette_index {
+            palette.resize(palette_index + 1, None);
+        }
+
+        palette[palette_index] = Some(parse_block_state_string(block_state_string));
+    }
+
+    // Gaps in palette indices are filled with air.
+    Ok(palette
+        .into_iter()
+        .map(|block_state| block_state.unwrap_or_else(|| BlockState::new("minecraft:air")))
+        .collect())
+}
+
+/// Parses block state in form used by commands like `minecraft:chest[facing=north]`.
+fn parse_block_state_string(block_state_string: &str) -> BlockState {
+    let (name, properties) = match block_state_string.find('[') {
+        Some(index) => (
+            &block_state_string[..index],
+            block_state_string[index + 1..].trim_end_matches(']'),
+        ),
+        None => (block_state_string, ""),
+    };
+
+    properties
+        .split(',')
+        .filter_map(|property| property.split_once('='))
+        .fold(BlockState::new(name), |block_state, (name, value)| {
+            block_state.with_property(name, value)
+        })
+}
+
+/// Decodes exactly specified amount of varints, returns none if data doesn't match.
+fn read_varints(data: &[i8], amount: usize) -> Option<Vec<u16>> {
+    let mut values = Vec::with_capacity(amount);
+    let mut value = 0u32;
+    let mut shift = 0;
+
+    for &byte in data {
+        value |= ((byte as u8 & 0x7F) as u32) << shift;
+
+        if byte as u8 & 0x80 == 0 {
+            values.push(u16::try_from(value).ok()?);
+            value = 0;
+            shift = 0;
+        } else {
+            shift += 7;
+
+            if shift > 21 {
+                return None;
+            }
+        }
+    }
+
+    if values.len() != amount || shift != 0 {
+        return None;
+    }
+
+    Some(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chunk::{Chunk, ChunkTag};
+    use crate::data::read_data_file;
+    use crate::relocate::copy_chunk;
+    use crate::schematic::{
+        export_schematic, paste_schematic, save_schematic, BlockSelection, PasteOptions, Schematic,
+        SchematicVersion,
+    };
+    use crate::section::BlockState;
+    use crate::version::RELEASES;
+    use crate::AnvilChunkProvider;
+    use nbt::{CompoundTag, Tag};
+    use tempfile::TempDir;
+
+    /// Returns provider with chunk 4 2 of fixture which has stairs and chest placed.
+    fn chunk_provider(temp_dir: &TempDir) -> AnvilChunkProvider<'_> {
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+        copy_chunk(&fixture_chunk_provider, (4, 2), &chunk_provider, (4, 2)).unwrap();
+
+        let mut chunk = Chunk::new(chunk_provider.load_chunk(4, 2).unwrap());
+        let mut section = chunk.read_sections().unwrap().remove(0);
+        let stairs = BlockState::new("minecraft:oak_stairs")
+            .with_property("half", "bottom")
+            .with_property("facing", "east");
+        section.set_block_state(1, 0, 1, stairs);
+        section.set_block_state(2, 0, 1, BlockState::new("minecraft:chest"));
+        chunk.write_section(&section).unwrap();
+
+        let section_y = section.y as i32 * 16;
+        let mut chest = CompoundTag::new();
+        chest.insert_str("id", "minecraft:chest");
+        chest.insert_i32("x", 66);
+        chest.insert_i32("y", section_y);
+        chest.insert_i32("z", 33);
+        chest.insert_compound_tag_vec("Items", Vec::new());
+        chunk.insert(
+            ChunkTag::BlockEntities,
+            Tag::List(vec![Tag::Compound(chest)]),
+        );
+
+        chunk_provider
+            .save_chunk(4, 2, chunk.into_compound_tag())
+            .unwrap();
+
+        chunk_provider
+    }
+
+    #[test]
+    fn test_export_v3() {
+        let temp_dir = TempDir::new().unwrap();
+        let chunk_provider = chunk_provider(&temp_dir);
+        let section_y = Chunk::new(chunk_provider.load_chunk(4, 2).unwrap())
+            .read_sections()
+            .unwrap()[0]
+            .y as i32
+            * 16;
+
+        let selection = BlockSelection::new((67, section_y + 1, 34), (65, section_y, 32));
+        let schematic_path = temp_dir.path().join("build.schem");
+        save_schematic(
+            &chunk_provider,
+            selection,
+            SchematicVersion::V3,
+            &schematic_path,
+        )
+        .unwrap();
+
+        let root_compound_tag = read_data_file(&schematic_path).unwrap();
+        let schematic = root_compound_tag.get_compound_tag("Schematic").unwrap();
+
+        assert_eq!(schematic.get_i32("Version").unwrap(), 3);
+        assert_eq!(schematic.get_i16("Width").unwrap(), 3);
+        assert_eq!(schematic.get_i16("Height").unwrap(), 2);
+        assert_eq!(schematic.get_i16("Length").unwrap(), 3);
+        assert_eq!(
+            schematic.get_i32_vec("Offset").unwrap(),
+            &vec![65, section_y, 32]
+        );
+
+        let blocks = schematic.get_compound_tag("Blocks").unwrap();
+        let palette = blocks.get_compound_tag("Palette").unwrap();
+        let stairs_index = palette
+            .get_i32("minecraft:oak_stairs[facing=east,half=bottom]")
+            .unwrap();
+        let chest_index = palette.get_i32("minecraft:chest").unwrap();
+
+        let block_data = blocks.get_i8_vec("Data").unwrap();
+        assert_eq!(block_data.len(), 18);
+        // Palette is short, so every varint is single byte. Stairs are at 0 0 1 and
+        // chest at 1 0 1 relative to selection.
+        assert_eq!(block_data[3] as i32, stairs_index);
+        assert_eq!(block_data[4] as i32, chest_index);
+
+        let block_entities = blocks.get_compound_tag_vec("BlockEntities").unwrap();
+        assert_eq!(block_entities.len(), 1);
+        assert_eq!(block_entities[0].get_str("Id").unwrap(), "minecraft:chest");
+        assert_eq!(
+            block_entities[0].get_i32_vec("Pos").unwrap(),
+            &vec![1, 0, 1]
+        );
+
+        let data = block_entities[0].get_compound_tag("Data").unwrap();
+        assert!(data.contains_key("Items"));
+        assert!(!data.contains_key("x"));
+    }
+
+    #[test]
+    fn test_export_v2() {
+        let temp_dir = TempDir::new().unwrap();
+        let chunk_provider = chunk_provider(&temp_dir);
+
+        // Selection reaches into missing chunk.
+        let selection = BlockSelection::new((60, -64, 30), (70, 319, 40));
+        let schematic_path = temp_dir.path().join("build.schem");
+        save_schematic(
+            &chunk_provider,
+            selection,
+            SchematicVersion::V2,
+            &schematic_path,
+        )
+        .unwrap();
+
+        let schematic = read_data_file(&schematic_path).unwrap();
+
+        assert_eq!(schematic.name.as_deref(), Some("Schematic"));
+        assert_eq!(schematic.get_i32("Version").unwrap(), 2);
+        assert_eq!(
+            schematic.get_i32("PaletteMax").unwrap() as usize,
+            schematic
+                .get_compound_tag("Palette")
+                .unwrap()
+                .iter()
+                .count()
+        );
+        assert_eq!(
+            schematic
+                .get_compound_tag("Palette")
+                .unwrap()
+                .get_i32("minecraft:air")
+                .unwrap(),
+            0
+        );
+
+        let block_entities = schematic.get_compound_tag_vec("BlockEntities").unwrap();
+        assert_eq!(block_entities.len(), 1);
+        assert!(block_entities[0].contains_key("Items"));
+        assert_eq!(block_entities[0].get_i32_vec("Pos").unwrap()[0], 6);
+    }
+
+    #[test]
+    fn test_load_and_paste() {
+        let temp_dir = TempDir::new().unwrap();
+        let chunk_provider = chunk_provider(&temp_dir);
+        let section_y = Chunk::new(chunk_provider.load_chunk(4, 2).unwrap())
+            .read_sections()
+            .unwrap()[0]
+            .y as i32
+            * 16;
+
+        for version in [SchematicVersion::V2, SchematicVersion::V3] {
+            let selection = BlockSelection::new((65, section_y, 33), (66, section_y, 33));
+            let schematic_path = temp_dir.path().join("build.schem");
+            save_schematic(&chunk_provider, selection, version, &schematic_path).unwrap();
+
+            let schematic = Schematic::load(&schematic_path).unwrap();
+            assert_eq!(schematic.size, (2, 1, 1));
+            assert_eq!(schematic.offset, (65, section_y, 33));
+            assert_eq!(
+                schematic.block_state(0, 0, 0).unwrap(),
+                &BlockState::new("minecraft:oak_stairs")
+                    .with_property("facing", "east")
+                    .with_property("half", "bottom")
+            );
+            assert_eq!(schematic.block_entities.len(), 1);
+            assert_eq!(schematic.block_entities[0].position, (1, 0, 0));
+            assert_eq!(
+                schematic.block_entities[0]
+                    .compound_tag
+                    .get_str("id")
+                    .unwrap(),
+                "minecraft:chest"
+            );
+
+            // Chunk spans two chunks, one of them is missing.
+            let paste_dir = TempDir::new().unwrap();
+            let paste_chunk_provider = AnvilChunkProvider::new(paste_dir.path().to_str().unwrap());
+            copy_chunk(&chunk_provider, (4, 2), &paste_chunk_provider, (4, 2)).unwrap();
+
+            let report = paste_schematic(
+                &paste_chunk_provider,
+                &schematic,
+                (79, 100, 40),
+                &PasteOptions::default(),
+            )
+            .unwrap();
+
+            assert_eq!(report.pasted_blocks, 2);
+            assert_eq!(report.clipped_blocks, 0);
+            assert_eq!(report.modified_chunks, vec![(4, 2)]);
+            assert_eq!(report.created_chunks, vec![(5, 2)]);
+
+            let chunk = Chunk::new(paste_chunk_provider.load_chunk(4, 2).unwrap());
+            assert_eq!(
+                chunk.block_state(15, 100, 8).unwrap().unwrap().name,
+                "minecraft:oak_stairs"
+            );
+
+            let created_chunk = Chunk::new(paste_chunk_provider.load_chunk(5, 2).unwrap());
+            assert_eq!(
+                created_chunk.block_state(0, 100, 8).unwrap().unwrap().name,
+                "minecraft:chest"
+            );
+
+            let block_entities = created_chunk.block_entities();
+            assert_eq!(block_entities.len(), 1);
+            assert_eq!(block_entities[0].get_i32("x").unwrap(), 80);
+            assert_eq!(block_entities[0].get_i32("y").unwrap(), 100);
+            assert!(block_entities[0].contains_key("Items"));
+        }
+    }
+
+    #[test]
+    fn test_paste_skip_air() {
+        let temp_dir = TempDir::new().unwrap();
+        let chunk_provider = chunk_provider(&temp_dir);
+        let section_y = Chunk::new(chunk_provider.load_chunk(4, 2).unwrap())
+            .read_sections()
+            .unwrap()[0]
+            .y as i32
+            * 16;
+
+        // Stairs, chest and air from the row above them.
+        let selection = BlockSelection::new((65, section_y, 33), (66, section_y + 1, 33));
+        let schematic = export_schematic(&chunk_provider, selection, SchematicVersion::V3).unwrap();
+        let mut schematic = Schematic::from_compound_tag(&schematic).unwrap();
+        schematic.blocks.set(2, BlockState::new("minecraft:air"));
+        schematic.blocks.set(3, BlockState::new("minecraft:air"));
+
+        let paste_dir = TempDir::new().unwrap();
+        let paste_chunk_provider = AnvilChunkProvider::new(paste_dir.path().to_str().unwrap());
+        let paste_options = PasteOptions::default()
+            .skip_air()
+            .skip_block_entities()
+            .data_version(RELEASES[RELEASES.len() - 1].data_version);
+
+        // Top row is above the world.
+        let report = paste_schematic(
+            &paste_chunk_provider,
+            &schematic,
+            (0, 319, 0),
+            &paste_options,
+        )
+        .unwrap();
+
+        assert_eq!(report.pasted_blocks, 2);
+        assert_eq!(report.clipped_blocks, 0);
+
+        let report = paste_schematic(
+            &paste_chunk_provider,
+            &schematic,
+            (0, 320, 0),
+            &paste_options,
+        )
+        .unwrap();
+
+        assert_eq!(report.pasted_blocks, 0);
+        assert_eq!(report.clipped_blocks, 2);
+        assert!(report.modified_chunks.is_empty());
+
+        let chunk = Chunk::new(paste_chunk_provider.load_chunk(0, 0).unwrap());
+        assert!(chunk.block_entities().is_empty());
+        assert_eq!(
+            chunk.block_state(1, 319, 0).unwrap().unwrap().name,
+            "minecraft:chest"
+        );
+    }
+
+    #[test]
+    fn test_mcedit_schematic() {
+        let mut schematic_compound_tag = CompoundTag::named("Schematic");
+        schematic_compound_tag.insert_i16("Width", 2);
+        schematic_compound_tag.insert_i16("Height", 1);
+        schematic_compound_tag.insert_i16("Length", 1);
+        schematic_compound_tag.insert_str("Materials", "Alpha");
+        // Stone and chest facing south.
+        schematic_compound_tag.insert_i8_vec("Blocks", vec![1, 54]);
+        schematic_compound_tag.insert_i8_vec("Data", vec![0, 3]);
+        schematic_compound_tag.insert_i32("WEOffsetX", -1);
+
+        let mut chest = CompoundTag::new();
+        chest.insert_str("id", "Chest");
+        chest.insert_i32("x", 1);
+        chest.insert_i32("y", 0);
+        chest.insert_i32("z", 0);
+        schematic_compound_tag.insert_compound_tag_vec("TileEntities", vec![chest]);
+
+        let schematic = Schematic::from_compound_tag(&schematic_compound_tag).unwrap();
+
+        assert_eq!(schematic.offset, (-1, 0, 0));
+        assert_eq!(schematic.data_version, None);
+        assert_eq!(
+            schematic.block_state(0, 0, 0).unwrap().name,
+            "minecraft:stone"
+        );
+        assert_eq!(
+            schematic.block_state(1, 0, 0).unwrap().name,
+            "minecraft:chest"
+        );
+        assert_eq!(schematic.block_entities[0].position, (1, 0, 0));
+        assert!(!schematic.block_entities[0].compound_tag.contains_key("x"));
+    }
+}
@@ -0,0 +1,312 @@
+//! Searching worlds for blocks and block entities.
+//!
+//! Sections are skipped without unpacking block data when their palette doesn't
+//! contain a matching block, which makes scans for rare blocks fast.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::search::find_block;
+//! use anvil_region::AnvilChunkProvider;
+//!
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//!
+//! if let Ok(positions) = find_block(&chunk_provider, "minecraft:diamond_ore") {
+//!     for (x, y, z) in positions {
+//!         println!("Diamond ore at {} {} {}", x, y, z);
+//!     }
+//! }
+//! ```
+use crate::chunk::Chunk;
+use crate::packed::unpacked;
+use crate::section::{block_state_bits, BlockState, SectionError, SectionFormat, SECTION_BLOCKS};
+use crate::{AnvilChunkProvider, ChunkLoadError};
+use nbt::CompoundTag;
+use std::io;
+
+/// Possible errors while searching world.
+#[derive(Debug)]
+pub enum SearchError {
+    /// Chunk can't be loaded.
+    ChunkLoadError { chunk_load_error: ChunkLoadError },
+    /// Chunk sections can't be read.
+    SectionError { section_error: SectionError },
+    /// Region folder can't be read.
+    ReadError { io_error: io::Error },
+}
+
+impl From<ChunkLoadError> for SearchError {
+    fn from(chunk_load_error: ChunkLoadError) -> Self {
+        SearchError::ChunkLoadError { chunk_load_error }
+    }
+}
+
+impl From<SectionError> for SearchError {
+    fn from(section_error: SectionError) -> Self {
+        SearchError::SectionError { section_error }
+    }
+}
+
+impl From<io::Error> for SearchError {
+    fn from(io_error: io::Error) -> Self {
+        SearchError::ReadError { io_error }
+    }
+}
+
+/// Block entity found in world.
+#[derive(Debug, Clone)]
+pub struct FoundBlockEntity {
+    /// World coordinates of block entity.
+    pub position: (i32, i32, i32),
+    /// Block entity tags like inventory `Items`.
+    pub compound_tag: CompoundTag,
+}
+
+impl Chunk {
+    /// Returns world coordinates of blocks matching predicate.
+    ///
+    /// Predicate is called once per palette entry, not per block.
+    pub fn find_blocks(
+        &self,
+        predicate: impl Fn(&BlockState) -> bool,
+    ) -> Result<Vec<(i32, i32, i32)>, SectionError> {
+        let section_format = self.section_format()?;
+        let (chunk_x, chunk_z) = self.position()?;
+        let mut positions = Vec::new();
+
+        for section in self.sections() {
+            let (block_states, palette_name, data_name) = match section_format {
+                SectionFormat::Modern => match section.get_compound_tag("block_states") {
+                    Ok(block_states) => (block_states, "palette", "data"),
+                    Err(_) => continue,
+                },
+                _ => (section, "Palette", "BlockStates"),
+            };
+
+            let palette = match block_states.get_compound_tag_vec(palette_name) {
+                Ok(palette) if !palette.is_empty() => palette,
+                _ => continue,
+            };
+
+            let matches = palette
+                .iter()
+                .map(|block_state| Ok(predicate(&BlockState::from_compound_tag(block_state)?)))
+                .collect::<Result<Vec<_>, SectionError>>()?;
+
+            // Palette shortcut.
+            if !matches.contains(&true) {
+                continue;
+            }
+
+            let section_y = section.get_i8("Y")? as i32;
+            let bits = block_state_bits(palette.len(), section_format);
+            let data = block_states
+                .get_i64_vec(data_name)
+                .map_or(&[][..], |data| data);
+
+            let indices = unpacked(data, bits, SECTION_BLOCKS, section_format.spanning());
+
+            for (block_index, index) in indices.enumerate() {
+                let is_match =
+                    matches
+                        .get(index as usize)
+                        .ok_or(SectionError::PaletteIndexOutOfBounds {
+                            index,
+                            palette_length: palette.len(),
+                        })?;
+
+                if *is_match {
+                    positions.push((
+                        chunk_x * 16 + (block_index & 15) as i32,
+                        section_y * 16 + (block_index >> 8) as i32,
+                        chunk_z * 16 + (block_index >> 4 & 15) as i32,
+                    ));
+                }
+            }
+        }
+
+        Ok(positions)
+    }
+}
+
+/// Returns world coordinates of blocks matching predicate in every chunk of provider.
+///
+/// Chunks saved before the flattening are skipped since they use numeric block ids.
+pub fn find_blocks(
+    chunk_provider: &AnvilChunkProvider,
+    predicate: impl Fn(&BlockState) -> bool,
+) -> Result<Vec<(i32, i32, i32)>, SearchError> {
+    let mut positions = Vec::new();
+
+    for (chunk_x, chunk_z) in chunk_provider.chunk_positions()? {
+        let chunk = Chunk::new(chunk_provider.load_chunk(chunk_x, chunk_z)?);
+
+        match chunk.find_blocks(&predicate) {
+            Ok(chunk_positions) => positions.extend(chunk_positions),
+            Err(SectionError::UnsupportedDataVersion { .. }) => continue,
+            Err(section_error) => return Err(section_error.into()),
+        }
+    }
+
+    Ok(positions)
+}
+
+/// Returns world coordinates of blocks with name in every chunk of provider.
+pub fn find_block(
+    chunk_provider: &AnvilChunkProvider,
+    name: &str,
+) -> Result<Vec<(i32, i32, i32)>, SearchError> {
+    find_blocks(chunk_provider, |block_state| block_state.name == name)
+}
+
+/// Returns block entities matching predicate in every chunk of provider.
+///
+/// Block entities without coordinates are skipped.
+pub fn find_block_entities(
+    chunk_provider: &AnvilChunkProvider,
+    predicate: impl Fn(&CompoundTag) -> bool,
+) -> Result<Vec<FoundBlockEntity>, SearchError> {
+    let mut found_block_entities = Vec::new();
+
+    for (chunk_x, chunk_z) in chunk_provider.chunk_positions()? {
+        let chunk = Chunk::new(chunk_provider.load_chunk(chunk_x, chunk_z)?);
+
+        for block_entity in chunk.block_entities() {
+            if !predicate(block_entity) {
+                continue;
+            }
+
+            let position = match (
+                block_entity.get_i32("x"),
+                block_entity.get_i32("y"),
+                block_entity.get_i32("z"),
+            ) {
+                (Ok(x), Ok(y), Ok(z)) => (x, y, z),
+                _ => continue,
+            };
+
+            found_block_entities.push(FoundBlockEntity {
+                position,
+                compound_tag: block_entity.clone(),
+            });
+        }
+    }
+
+    Ok(found_block_entities)
+}
+
+/// Returns block entities with id like `minecraft:chest` in every chunk of provider.
+///
+/// # Example
+///
+/// ```
+/// use anvil_region::search::find_block_entities_by_id;
+/// use anvil_region::AnvilChunkProvider;
+///
+/// let chunk_provider = AnvilChunkProvider::new("test/region");
+///
+/// if let Ok(chests) = find_block_entities_by_id(&chunk_provider, "minecraft:chest") {
+///     for chest in chests {
+///         let items = chest.compound_tag.get_compound_tag_vec("Items").unwrap_or_default();
+///         println!("Chest at {:?} has {} item stacks", chest.position, items.len());
+///     }
+/// }
+/// ```
+pub fn find_block_entities_by_id(
+    chunk_provider: &AnvilChunkProvider,
+    id: &str,
+) -> Result<Vec<FoundBlockEntity>, SearchError> {
+    find_block_entities(chunk_provider, |block_entity| {
+        block_entity.get_str("id").ok() == Some(id)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chunk::Chunk;
+    use crate::relocate::copy_chunk;
+    use crate::search::{find_block, find_block_entities_by_id};
+    use crate::section::BlockState;
+    use crate::AnvilChunkProvider;
+    use nbt::{CompoundTag, Tag};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_chunk_find_blocks() {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk = Chunk::new(chunk_provider.load_chunk(4, 2).unwrap());
+
+        let positions = chunk
+            .find_blocks(|block_state| block_state.name == "minecraft:bedrock")
+            .unwrap();
+
+        assert!(positions.contains(&(64, 0, 32)));
+        assert!(positions.iter().all(|(x, y, z)| {
+            (64..80).contains(x) && (0..5).contains(y) && (32..48).contains(z)
+        }));
+        assert!(chunk
+            .find_blocks(|block_state| block_state.name == "minecraft:nonexistent")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_find_block() {
+        let temp_dir = TempDir::new().unwrap();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+
+        copy_chunk(&fixture_chunk_provider, (4, 2), &chunk_provider, (4, 2)).unwrap();
+
+        let mut chunk = Chunk::new(chunk_provider.load_chunk(4, 2).unwrap());
+        let mut section = chunk.read_sections().unwrap().remove(0);
+        section.set_block_state(3, 7, 5, BlockState::new("minecraft:command_block"));
+        chunk.write_section(&section).unwrap();
+        chunk_provider
+            .save_chunk(4, 2, chunk.into_compound_tag())
+            .unwrap();
+
+        let positions = find_block(&chunk_provider, "minecraft:command_block").unwrap();
+        let y = section.y as i32 * 16 + 7;
+
+        assert_eq!(positions, vec![(67, y, 37)]);
+    }
+
+    fn block_entity(id: &str, x: i32, y: i32, z: i32) -> Tag {
+        let mut block_entity = CompoundTag::new();
+        block_entity.insert_str("id", id);
+        block_entity.insert_i32("x", x);
+        block_entity.insert_i32("y", y);
+        block_entity.insert_i32("z", z);
+        block_entity.insert_compound_tag_vec("Items", Vec::new());
+
+        Tag::Compound(block_entity)
+    }
+
+    #[test]
+    fn test_find_block_entities_by_id() {
+        let temp_dir = TempDir::new().unwrap();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+
+        let mut chunk_compound_tag = fixture_chunk_provider.load_chunk(4, 2).unwrap();
+        let level_compound_tag = chunk_compound_tag
+            .get_mut::<&mut CompoundTag>("Level")
+            .unwrap();
+        level_compound_tag.insert(
+            "TileEntities",
+            Tag::List(vec![
+                block_entity("minecraft:chest", 65, 40, 33),
+                block_entity("minecraft:furnace", 66, 40, 33),
+                block_entity("minecraft:chest", 67, 41, 34),
+            ]),
+        );
+        chunk_provider.save_chunk(4, 2, chunk_compound_tag).unwrap();
+
+        let chests = find_block_entities_by_id(&chunk_provider, "minecraft:chest").unwrap();
+        let positions: Vec<_> = chests.iter().map(|chest| chest.position).collect();
+
+        assert_eq!(positions, vec![(65, 40, 33), (67, 41, 34)]);
+        assert!(chests[0].compound_tag.contains_key("Items"));
+    }
+}
@@ -0,0 +1,989 @@
+//! Typed chunk sections with decoded block state and biome palettes.
+//!
+//! Section stores palette indices unpacked, so blocks and biomes can be changed
+//! without caring about bits per entry. Indices are packed again on write using
+//! as few bits as the palette allows.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::chunk::Chunk;
+//! use anvil_region::section::BlockState;
+//! use anvil_region::AnvilChunkProvider;
+//!
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//! let mut chunk = Chunk::new(chunk_provider.load_chunk(4, 2).unwrap());
+//! let mut sections = chunk.read_sections().unwrap();
+//!
+//! let section = &mut sections[0];
+//! section.set_block_state(0, 0, 0, BlockState::new("minecraft:diamond_block"));
+//!
+//! chunk.write_section(section).unwrap();
+//! ```
+use crate::chunk::{Chunk, ChunkTag};
+use crate::height::HeightRange;
+use crate::light::NibbleArray;
+use crate::packed::{pack, palette_bits, unpack, unpacked};
+use crate::tag::{compound_tags_mut, get_tag, remove_tag};
+use crate::version::{
+    FLATTENING_DATA_VERSION, LEVEL_WRAPPER_REMOVAL_DATA_VERSION, PADDED_PACKED_ARRAYS_DATA_VERSION,
+};
+use nbt::{CompoundTag, CompoundTagError, Tag};
+use std::collections::HashMap;
+
+/// Amount of blocks in section.
+pub const SECTION_BLOCKS: usize = 4096;
+/// Amount of biome cells in section.
+pub const SECTION_BIOMES: usize = 64;
+/// Minimum bits per block state index.
+const MINIMUM_BLOCK_STATE_BITS: u32 = 4;
+
+/// Possible errors while reading sections.
+#[derive(Debug)]
+pub enum SectionError {
+    /// Sections before the flattening use numeric block ids and are not supported.
+    UnsupportedDataVersion {
+        /// Chunk data version.
+        data_version: i32,
+    },
+    /// Section tag is missing or has unexpected type.
+    InvalidTag {
+        /// Tag name.
+        name: String,
+    },
+    /// Packed data refers to palette entry which doesn't exist.
+    PaletteIndexOutOfBounds {
+        /// Palette index.
+        index: u16,
+        /// Palette length.
+        palette_length: usize,
+    },
+    /// Block Y is outside of chunk height range.
+    OutOfHeightRange {
+        /// Block Y.
+        y: i32,
+        /// Chunk height range.
+        height_range: HeightRange,
+    },
+}
+
+impl From<CompoundTagError<'_>> for SectionError {
+    fn from(compound_tag_error: CompoundTagError) -> Self {
+        let name = match compound_tag_error {
+            CompoundTagError::TagNotFound { name } => name,
+            CompoundTagError::TagWrongType { name, .. } => name,
+        };
+
+        SectionError::InvalidTag {
+            name: name.to_owned(),
+        }
+    }
+}
+
+/// Layout of section tags.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SectionFormat {
+    /// `Palette` and `BlockStates` with values spanning across longs.
+    Spanning,
+    /// `Palette` and `BlockStates` with values padded to whole longs.
+    Padded,
+    /// `block_states` and `biomes` compound tags.
+    Modern,
+}
+
+impl SectionFormat {
+    /// Returns section format used by data version.
+    pub fn from_data_version(data_version: i32) -> Result<Self, SectionError> {
+        if data_version < FLATTENING_DATA_VERSION {
+            return Err(SectionError::UnsupportedDataVersion { data_version });
+        }
+
+        let section_format = if data_version < PADDED_PACKED_ARRAYS_DATA_VERSION {
+            SectionFormat::Spanning
+        } else if data_version < LEVEL_WRAPPER_REMOVAL_DATA_VERSION {
+            SectionFormat::Padded
+        } else {
+            SectionFormat::Modern
+        };
+
+        Ok(section_format)
+    }
+
+    pub(crate) fn spanning(self) -> bool {
+        self == SectionFormat::Spanning
+    }
+}
+
+/// Block state palette entry.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct BlockState {
+    /// Namespaced block name, for example `minecraft:stone`.
+    pub name: String,
+    /// Block state properties in stored order.
+    pub properties: Vec<(String, String)>,
+}
+
+impl BlockState {
+    pub fn new(name: &str) -> Self {
+        BlockState {
+            name: name.to_owned(),
+            properties: Vec::new(),
+        }
+    }
+
+    /// Adds property.
+    pub fn with_property(mut self, name: &str, value: &str) -> Self {
+        self.properties.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// Returns property value.
+    pub fn property(&self, name: &str) -> Option<&str> {
+        self.properties
+            .iter()
+            .find(|(property_name, _)| property_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn from_compound_tag(compound_tag: &CompoundTag) -> Result<Self, SectionError> {
+        let name = compound_tag.get_str("Name")?.to_owned();
+        let mut properties = Vec::new();
+
+        if let Ok(properties_compound_tag) = compound_tag.get_compound_tag("Properties") {
+            for (property_name, value) in properties_compound_tag.iter() {
+                if let Tag::String(value) = value {
+                    properties.push((property_name.clone(), value.clone()));
+                }
+            }
+        }
+
+        Ok(BlockState { name, properties })
+    }
+
+    pub fn to_compound_tag(&self) -> CompoundTag {
+        let mut compound_tag = CompoundTag::new();
+        compound_tag.insert_str("Name", &self.name);
+
+        if !self.properties.is_empty() {
+            let mut properties_compound_tag = CompoundTag::new();
+
+            for (name, value) in &self.properties {
+                properties_compound_tag.insert_str(name, value);
+            }
+
+            compound_tag.insert_compound_tag("Properties", properties_compound_tag);
+        }
+
+        compound_tag
+    }
+}
+
+/// Values stored as palette and palette index for every entry.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PalettedContainer<T> {
+    palette: Vec<T>,
+    indices: Vec<u16>,
+}
+
+impl<T: Clone + PartialEq> PalettedContainer<T> {
+    /// Creates container filled with value.
+    pub fn new(value: T, length: usize) -> Self {
+        PalettedContainer {
+            palette: vec![value],
+            indices: vec![0; length],
+        }
+    }
+
+    /// Creates container from palette and indices.
+    pub fn from_parts(palette: Vec<T>, indices: Vec<u16>) -> Result<Self, SectionError> {
+        if let Some(index) = indices
+            .iter()
+            .find(|index| **index as usize >= palette.len())
+        {
+            return Err(SectionError::PaletteIndexOutOfBounds {
+                index: *index,
+                palette_length: palette.len(),
+            });
+        }
+
+        Ok(PalettedContainer { palette, indices })
+    }
+
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    pub fn palette(&self) -> &[T] {
+        &self.palette
+    }
+
+    pub fn indices(&self) -> &[u16] {
+        &self.indices
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.indices
+            .get(index)
+            .map(|palette_index| &self.palette[*palette_index as usize])
+    }
+
+    /// Sets value adding it to the palette if needed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if index is out of bounds.
+    pub fn set(&mut self, index: usize, value: T) {
+        self.indices[index] = self.palette_index(value);
+    }
+
+    /// Sets value at every index looking it up in the palette once, returns amount of
+    /// entries which changed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if some index is out of bounds.
+    pub fn set_many(&mut self, indices: impl IntoIterator<Item = usize>, value: T) -> usize {
+        let palette_index = self.palette_index(value);
+
+        self.set_palette_index(indices, |_| true, palette_index)
+    }
+
+    /// Replaces value at every index where it equals to `from`, returns amount of
+    /// entries which changed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if some index is out of bounds.
+    pub fn replace_many(
+        &mut self,
+        indices: impl IntoIterator<Item = usize>,
+        from: &T,
+        to: T,
+    ) -> usize {
+        let from_palette_index = match self.palette.iter().position(|entry| entry == from) {
+            Some(from_palette_index) if *from != to => from_palette_index as u16,
+            _ => return 0,
+        };
+        let palette_index = self.palette_index(to);
+
+        self.set_palette_index(
+            indices,
+            |current_palette_index| current_palette_index == from_palette_index,
+            palette_index,
+        )
+    }
+
+    /// Returns values in order.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.indices
+            .iter()
+            .map(move |palette_index| &self.palette[*palette_index as usize])
+    }
+
+    /// Returns amount of entries using every palette value.
+    ///
+    /// Values which are not used are skipped.
+    pub fn counts(&self) -> Vec<(&T, usize)> {
+        let mut counts = vec![0; self.palette.len()];
+
+        for palette_index in &self.indices {
+            counts[*palette_index as usize] += 1;
+        }
+
+        self.palette
+            .iter()
+            .zip(counts)
+            .filter(|(_, count)| *count > 0)
+            .collect()
+    }
+
+    /// Returns palette index of value adding it to the palette if needed.
+    fn palette_index(&mut self, value: T) -> u16 {
+        match self.palette.iter().position(|entry| *entry == value) {
+            Some(palette_index) => palette_index as u16,
+            None => {
+                self.palette.push(value);
+                (self.palette.len() - 1) as u16
+            }
+        }
+    }
+
+    fn set_palette_index(
+        &mut self,
+        indices: impl IntoIterator<Item = usize>,
+        predicate: impl Fn(u16) -> bool,
+        palette_index: u16,
+    ) -> usize {
+        let mut changed = 0;
+
+        for index in indices {
+            let current_palette_index = self.indices[index];
+
+            if current_palette_index != palette_index && predicate(current_palette_index) {
+                self.indices[index] = palette_index;
+                changed += 1;
+            }
+        }
+
+        changed
+    }
+
+    /// Removes palette entries which are not used anymore.
+    pub fn compact(&mut self) {
+        let mut used = vec![false; self.palette.len()];
+
+        for palette_index in &self.indices {
+            used[*palette_index as usize] = true;
+        }
+
+        if used.iter().all(|used| *used) {
+            return;
+        }
+
+        let mut remap = vec![0; self.palette.len()];
+        let mut palette = Vec::with_capacity(self.palette.len());
+
+        for (palette_index, value) in self.palette.drain(..).enumerate() {
+            if used[palette_index] {
+                remap[palette_index] = palette.len() as u16;
+                palette.push(value);
+            }
+        }
+
+        for palette_index in self.indices.iter_mut() {
+            *palette_index = remap[*palette_index as usize];
+        }
+
+        self.palette = palette;
+    }
+}
+
+/// Chunk section of 16x16x16 blocks.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Section {
+    /// Section Y, which is block Y divided by 16 and can be negative since 1.18.
+    pub y: i8,
+    /// Block states indexed by `y * 256 + z * 16 + x`, absent in sections with only light.
+    pub block_states: Option<PalettedContainer<BlockState>>,
+    /// Biomes indexed by `(y / 4) * 16 + (z / 4) * 4 + x / 4`.
+    ///
+    /// Only present since 1.18, before that biomes are stored per chunk.
+    pub biomes: Option<PalettedContainer<String>>,
+    /// Light emitted by blocks.
+    pub block_light: Option<NibbleArray>,
+    /// Light coming from the sky.
+    pub sky_light: Option<NibbleArray>,
+}
+
+impl Section {
+    /// Creates section filled with air.
+    pub fn new(y: i8) -> Self {
+        Section {
+            y,
+            block_states: Some(PalettedContainer::new(
+                BlockState::new("minecraft:air"),
+                SECTION_BLOCKS,
+            )),
+            biomes: None,
+            block_light: None,
+            sky_light: None,
+        }
+    }
+
+    /// Decodes section compound tag.
+    pub fn from_compound_tag(
+        compound_tag: &CompoundTag,
+        section_format: SectionFormat,
+    ) -> Result<Self, SectionError> {
+        let y = compound_tag.get_i8("Y")?;
+
+        let (block_states, biomes) = match section_format {
+            SectionFormat::Spanning | SectionFormat::Padded => {
+                let block_states = match get_tag(compound_tag, "Palette") {
+                    Some(Tag::List(_)) => Some(read_block_states(
+                        compound_tag,
+                        "Palette",
+                        "BlockStates",
+                        section_format,
+                    )?),
+                    _ => None,
+                };
+
+                (block_states, None)
+            }
+            SectionFormat::Modern => {
+                let block_states = match compound_tag.get_compound_tag("block_states") {
+                    Ok(block_states) => Some(read_block_states(
+                        block_states,
+                        "palette",
+                        "data",
+                        section_format,
+                    )?),
+                    Err(_) => None,
+                };
+
+                let biomes = match compound_tag.get_compound_tag("biomes") {
+                    Ok(biomes) => Some(read_biomes(biomes)?),
+                    Err(_) => None,
+                };
+
+                (block_states, biomes)
+            }
+        };
+
+        let block_light = compound_tag
+            .get_i8_vec("BlockLight")
+            .ok()
+            .and_then(|bytes| NibbleArray::from_bytes(bytes));
+        let sky_light = compound_tag
+            .get_i8_vec("SkyLight")
+            .ok()
+            .and_then(|bytes| NibbleArray::from_bytes(bytes));
+
+        Ok(Section {
+            y,
+            block_states,
+            biomes,
+            block_light,
+            sky_light,
+        })
+    }
+
+    /// Writes blocks and biomes into section compound tag keeping other tags like light.
+    pub fn write_to_compound_tag(
+        &self,
+        compound_tag: &mut CompoundTag,
+        section_format: SectionFormat,
+    ) {
+        compound_tag.insert_i8("Y", self.y);
+
+        for (name, light) in &[
+            ("BlockLight", &self.block_light),
+            ("SkyLight", &self.sky_light),
+        ] {
+            match light {
+                Some(light) => compound_tag.insert_i8_vec(name, light.to_bytes()),
+                None => {
+                    remove_tag(compound_tag, name);
+                }
+            }
+        }
+
+        match section_format {
+            SectionFormat::Spanning | SectionFormat::Padded => {
+                remove_tag(compound_tag, "Palette");
+                remove_tag(compound_tag, "BlockStates");
+
+                if let Some(block_states) = &self.block_states {
+                    let (palette, indices) = compacted(block_states);
+                    let bits = palette_bits(palette.len(), MINIMUM_BLOCK_STATE_BITS);
+
+                    compound_tag.insert_compound_tag_vec(
+                        "Palette",
+                        palette
+                            .iter()
+                            .map(|block_state| block_state.to_compound_tag()),
+                    );
+                    compound_tag.insert_i64_vec(
+                        "BlockStates",
+                        pack(&indices, bits, section_format.spanning()),
+                    );
+                }
+            }
+            SectionFormat::Modern => {
+                remove_tag(compound_tag, "block_states");
+                remove_tag(compound_tag, "biomes");
+
+                if let Some(block_states) = &self.block_states {
+                    let (palette, indices) = compacted(block_states);
+                    let mut block_states_compound_tag = CompoundTag::new();
+
+                    block_states_compound_tag.insert_compound_tag_vec(
+                        "palette",
+                        palette
+                            .iter()
+                            .map(|block_state| block_state.to_compound_tag()),
+                    );
+
+                    if palette.len() > 1 {
+                        let bits = palette_bits(palette.len(), MINIMUM_BLOCK_STATE_BITS);
+                        block_states_compound_tag
+                            .insert_i64_vec("data", pack(&indices, bits, false));
+                    }
+
+                    compound_tag.insert_compound_tag("block_states", block_states_compound_tag);
+                }
+
+                if let Some(biomes) = &self.biomes {
+                    let (palette, indices) = compacted(biomes);
+                    let mut biomes_compound_tag = CompoundTag::new();
+
+                    if palette.len() > 1 {
+                        let bits = palette_bits(palette.len(), 0);
+                        biomes_compound_tag.insert_i64_vec("data", pack(&indices, bits, false));
+                    }
+
+                    biomes_compound_tag.insert_str_vec("palette", palette);
+
+                    compound_tag.insert_compound_tag("biomes", biomes_compound_tag);
+                }
+            }
+        }
+    }
+
+    /// Returns block state at section relative coordinates.
+    pub fn block_state(&self, x: usize, y: usize, z: usize) -> Option<&BlockState> {
+        self.block_states.as_ref()?.get(block_index(x, y, z))
+    }
+
+    /// Sets block state at section relative coordinates.
+    ///
+    /// Section with only light gets filled with air first.
+    pub fn set_block_state(&mut self, x: usize, y: usize, z: usize, block_state: BlockState) {
+        self.block_states
+            .get_or_insert_with(|| {
+                PalettedContainer::new(BlockState::new("minecraft:air"), SECTION_BLOCKS)
+            })
+            .set(block_index(x, y, z), block_state);
+    }
+
+    /// Returns section relative coordinates and block state of every block.
+    ///
+    /// Section with only light has no blocks.
+    pub fn blocks(&self) -> impl Iterator<Item = ((usize, usize, usize), &BlockState)> {
+        self.block_states
+            .iter()
+            .flat_map(|block_states| block_states.iter())
+            .enumerate()
+            .map(|(index, block_state)| ((index & 15, index >> 8, (index >> 4) & 15), block_state))
+    }
+
+    /// Returns amount of blocks of every block state.
+    pub fn block_state_counts(&self) -> Vec<(&BlockState, usize)> {
+        self.block_states
+            .as_ref()
+            .map_or_else(Vec::new, |block_states| block_states.counts())
+    }
+
+    /// Returns biome at section relative block coordinates.
+    pub fn biome(&self, x: usize, y: usize, z: usize) -> Option<&str> {
+        self.biomes
+            .as_ref()?
+            .get(biome_index(x, y, z))
+            .map(|biome| biome.as_str())
+    }
+
+    /// Sets biome of 4x4x4 cell containing section relative block coordinates.
+    ///
+    /// Section without biomes gets filled with plains first.
+    pub fn set_biome(&mut self, x: usize, y: usize, z: usize, biome: &str) {
+        self.biomes
+            .get_or_insert_with(|| {
+                PalettedContainer::new("minecraft:plains".to_owned(), SECTION_BIOMES)
+            })
+            .set(biome_index(x, y, z), biome.to_owned());
+    }
+}
+
+/// Returns index of block in section.
+pub fn block_index(x: usize, y: usize, z: usize) -> usize {
+    (y & 15) << 8 | (z & 15) << 4 | (x & 15)
+}
+
+/// Returns index of biome cell which contains block in section.
+pub fn biome_index(x: usize, y: usize, z: usize) -> usize {
+    ((y & 15) >> 2) << 4 | ((z & 15) >> 2) << 2 | ((x & 15) >> 2)
+}
+
+fn read_block_states(
+    compound_tag: &CompoundTag,
+    palette_name: &str,
+    data_name: &str,
+    section_format: SectionFormat,
+) -> Result<PalettedContainer<BlockState>, SectionError> {
+    let palette = compound_tag
+        .get_compound_tag_vec(palette_name)?
+        .into_iter()
+        .map(BlockState::from_compound_tag)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if palette.is_empty() {
+        return Err(SectionError::InvalidTag {
+            name: palette_name.to_owned(),
+        });
+    }
+
+    let bits = block_state_bits(palette.len(), section_format);
+    let data = compound_tag
+        .get_i64_vec(data_name)
+        .map_or(&[][..], |data| data);
+    let indices = unpack(data, bits, SECTION_BLOCKS, section_format.spanning());
+
+    PalettedContainer::from_parts(palette, indices)
+}
+
+/// Returns bits per block state index.
+pub(crate) fn block_state_bits(palette_length: usize, section_format: SectionFormat) -> u32 {
+    // Before 1.18 even single entry palette uses 4 bits.
+    if palette_length == 1 && section_format == SectionFormat::Modern {
+        0
+    } else {
+        palette_bits(palette_length, MINIMUM_BLOCK_STATE_BITS)
+    }
+}
+
+fn read_biomes(compound_tag: &CompoundTag) -> Result<PalettedContainer<String>, SectionError> {
+    let palette: Vec<String> = compound_tag
+        .get_str_vec("palette")?
+        .into_iter()
+        .map(|biome| biome.to_owned())
+        .collect();
+
+    if palette.is_empty() {
+        return Err(SectionError::InvalidTag {
+            name: "palette".to_owned(),
+        });
+    }
+
+    let bits = palette_bits(palette.len(), 0);
+    let data = compound_tag
+        .get_i64_vec("data")
+        .map_or(&[][..], |data| data);
+    let indices = unpack(data, bits, SECTION_BIOMES, false);
+
+    PalettedContainer::from_parts(palette, indices)
+}
+
+/// Returns palette without unused entries and remapped indices.
+fn compacted<T: Clone + PartialEq>(container: &PalettedContainer<T>) -> (Vec<T>, Vec<u16>) {
+    let mut container = container.clone();
+    container.compact();
+
+    (container.palette, container.indices)
+}
+
+impl Chunk {
+    /// Returns section format of chunk.
+    pub fn section_format(&self) -> Result<SectionFormat, SectionError> {
+        let data_version = self.data_version().unwrap_or(0);
+
+        SectionFormat::from_data_version(data_version)
+    }
+
+    /// Decodes chunk sections.
+    pub fn read_sections(&self) -> Result<Vec<Section>, SectionError> {
+        let section_format = self.section_format()?;
+
+        self.sections()
+            .into_iter()
+            .map(|section| Section::from_compound_tag(section, section_format))
+            .collect()
+    }
+
+    /// Counts blocks by name walking packed data of every section once.
+    ///
+    /// Unlike [`Chunk::read_sections`] palette entries are not decoded, which makes
+    /// it suitable for analytics like ore counting over entire worlds.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use anvil_region::chunk::Chunk;
+    /// use anvil_region::AnvilChunkProvider;
+    ///
+    /// let chunk_provider = AnvilChunkProvider::new("test/region");
+    /// let mut diamond_ore = 0;
+    ///
+    /// for (chunk_x, chunk_z) in chunk_provider.chunk_positions().unwrap() {
+    ///     let chunk = Chunk::new(chunk_provider.load_chunk(chunk_x, chunk_z).unwrap());
+    ///
+    ///     if let Ok(block_counts) = chunk.block_counts() {
+    ///         diamond_ore += block_counts.get("minecraft:diamond_ore").unwrap_or(&0);
+    ///     }
+    /// }
+    ///
+    /// assert!(diamond_ore > 0);
+    /// ```
+    pub fn block_counts(&self) -> Result<HashMap<String, usize>, SectionError> {
+        let section_format = self.section_format()?;
+        let mut block_counts: HashMap<String, usize> = HashMap::new();
+
+        for section in self.sections() {
+            let (block_states, palette_name, data_name) = match section_format {
+                SectionFormat::Modern => match section.get_compound_tag("block_states") {
+                    Ok(block_states) => (block_states, "palette", "data"),
+                    Err(_) => continue,
+                },
+                _ => (section, "Palette", "BlockStates"),
+            };
+
+            let palette = match block_states.get_compound_tag_vec(palette_name) {
+                Ok(palette) if !palette.is_empty() => palette,
+                _ => continue,
+            };
+
+            let bits = block_state_bits(palette.len(), section_format);
+            let data = block_states
+                .get_i64_vec(data_name)
+                .map_or(&[][..], |data| data);
+            let mut palette_counts = vec![0; palette.len()];
+
+            for index in unpacked(data, bits, SECTION_BLOCKS, section_format.spanning()) {
+                match palette_counts.get_mut(index as usize) {
+                    Some(count) => *count += 1,
+                    None => {
+                        return Err(SectionError::PaletteIndexOutOfBounds {
+                            index,
+                            palette_length: palette.len(),
+                        })
+                    }
+                }
+            }
+
+            for (block_state, count) in palette.iter().zip(palette_counts) {
+                if count == 0 {
+                    continue;
+                }
+
+                let name = block_state.get_str("Name")?;
+
+                match block_counts.get_mut(name) {
+                    Some(block_count) => *block_count += count,
+                    None => {
+                        block_counts.insert(name.to_owned(), count);
+                    }
+                }
+            }
+        }
+
+        Ok(block_counts)
+    }
+
+    /// Writes section replacing section with the same Y, or appending it.
+    ///
+    /// Returns error if chunk section format is unsupported, chunk isn't changed then.
+    pub fn write_section(&mut self, section: &Section) -> Result<(), SectionError> {
+        let section_format = self.section_format()?;
+
+        let sections_name = self.tag_name(ChunkTag::Sections);
+        let data = self.data_mut();
+
+        if !data.contains_key(sections_name) {
+            data.insert_compound_tag_vec(sections_name, Vec::new());
+        }
+
+        if let Some(section_compound_tag) = compound_tags_mut(data, sections_name)
+            .find(|section_compound_tag| section_compound_tag.get_i8("Y").ok() == Some(section.y))
+        {
+            section.write_to_compound_tag(section_compound_tag, section_format);
+            return Ok(());
+        }
+
+        let mut section_compound_tag = CompoundTag::new();
+        section.write_to_compound_tag(&mut section_compound_tag, section_format);
+
+        if let Ok(sections) = data.get_mut::<&mut Vec<Tag>>(sections_name) {
+            sections.push(Tag::Compound(section_compound_tag));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chunk::Chunk;
+    use crate::section::{BlockState, PalettedContainer, Section, SectionError, SectionFormat};
+    use crate::upgrade::upgrade_chunk;
+    use crate::AnvilChunkProvider;
+    use std::collections::HashMap;
+
+    fn fixture_chunk() -> Chunk {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+
+        Chunk::new(chunk_provider.load_chunk(4, 2).unwrap())
+    }
+
+    #[test]
+    fn test_read_sections() {
+        let chunk = fixture_chunk();
+        let sections = chunk.read_sections().unwrap();
+        let section = &sections[0];
+
+        assert_eq!(chunk.section_format().unwrap(), SectionFormat::Spanning);
+        assert_eq!(section.y, 0);
+        assert_eq!(
+            section.block_state(0, 0, 0).unwrap().name,
+            "minecraft:bedrock"
+        );
+        assert!(section.biome(0, 0, 0).is_none());
+    }
+
+    #[test]
+    fn test_palette_growth_across_bits_threshold() {
+        let mut chunk = fixture_chunk();
+        let mut section = chunk.read_sections().unwrap().remove(0);
+
+        for x in 0..16 {
+            let block_state = BlockState::new("minecraft:wool").with_property("x", &x.to_string());
+            section.set_block_state(x, 15, 15, block_state);
+        }
+
+        chunk.write_section(&section).unwrap();
+
+        let data = chunk.data().get_compound_tag_vec("Sections").unwrap()[0];
+        let palette_length = data.get_compound_tag_vec("Palette").unwrap().len();
+        let block_states_length = data.get_i64_vec("BlockStates").unwrap().len();
+
+        assert!(palette_length > 16);
+        assert_eq!(block_states_length, 320);
+
+        let written_block_states = chunk.read_sections().unwrap().remove(0).block_states;
+        let block_states = section.block_states.unwrap();
+
+        assert!(written_block_states.unwrap().iter().eq(block_states.iter()));
+    }
+
+    #[test]
+    fn test_palette_shrink_across_bits_threshold() {
+        let mut chunk = fixture_chunk();
+        let mut section = Section::new(0);
+
+        for x in 0..16 {
+            section.set_block_state(x, 0, 0, BlockState::new(&format!("minecraft:b{}", x)));
+        }
+
+        section.set_block_state(0, 1, 0, BlockState::new("minecraft:stone"));
+
+        for x in 0..16 {
+            section.set_block_state(x, 0, 0, BlockState::new("minecraft:stone"));
+        }
+
+        chunk.write_section(&section).unwrap();
+
+        let data = chunk.data().get_compound_tag_vec("Sections").unwrap()[0];
+
+        assert_eq!(data.get_compound_tag_vec("Palette").unwrap().len(), 2);
+        assert_eq!(data.get_i64_vec("BlockStates").unwrap().len(), 256);
+    }
+
+    #[test]
+    fn test_modern_section_roundtrip() {
+        let mut chunk_compound_tag = fixture_chunk().into_compound_tag();
+        upgrade_chunk(&mut chunk_compound_tag, 2860).unwrap();
+
+        let mut chunk = Chunk::new(chunk_compound_tag);
+        let mut section = chunk.read_sections().unwrap().remove(0);
+
+        assert_eq!(chunk.section_format().unwrap(), SectionFormat::Modern);
+        assert!(section.biome(0, 0, 0).is_some());
+
+        section.set_biome(15, 15, 15, "minecraft:desert");
+        chunk.write_section(&section).unwrap();
+
+        let section = &chunk.read_sections().unwrap()[0];
+
+        assert_eq!(section.biome(15, 15, 15), Some("minecraft:desert"));
+        assert_eq!(
+            section.block_state(0, 0, 0).unwrap().name,
+            "minecraft:bedrock"
+        );
+    }
+
+    #[test]
+    fn test_write_section_unsupported_format() {
+        let mut chunk_compound_tag = fixture_chunk().into_compound_tag();
+        chunk_compound_tag.insert_i32("DataVersion", 1343);
+
+        let mut chunk = Chunk::new(chunk_compound_tag);
+
+        match chunk.write_section(&Section::new(0)) {
+            Err(SectionError::UnsupportedDataVersion { data_version }) => {
+                assert_eq!(data_version, 1343)
+            }
+            result => panic!("Expected `UnsupportedDataVersion` but got `{:?}`", result),
+        }
+    }
+
+    #[test]
+    fn test_blocks() {
+        let mut section = Section::new(0);
+        section.set_block_state(1, 2, 3, BlockState::new("minecraft:stone"));
+
+        let stone: Vec<_> = section
+            .blocks()
+            .filter(|(_, block_state)| block_state.name == "minecraft:stone")
+            .map(|(position, _)| position)
+            .collect();
+
+        assert_eq!(stone, vec![(1, 2, 3)]);
+        assert_eq!(section.block_state_counts().len(), 2);
+        assert_eq!(section.block_state_counts()[0].1, 4095);
+    }
+
+    #[test]
+    fn test_block_counts() {
+        let chunk = fixture_chunk();
+        let block_counts = chunk.block_counts().unwrap();
+
+        let mut expected_block_counts = HashMap::new();
+
+        for section in chunk.read_sections().unwrap() {
+            for (block_state, count) in section.block_state_counts() {
+                *expected_block_counts
+                    .entry(block_state.name.clone())
+                    .or_insert(0) += count;
+            }
+        }
+
+        assert_eq!(block_counts, expected_block_counts);
+        assert_eq!(block_counts.values().sum::<usize>() % 4096, 0);
+    }
+
+    #[test]
+    fn test_paletted_container_index_out_of_bounds() {
+        match PalettedContainer::from_parts(vec![1], vec![0, 1]) {
+            Err(SectionError::PaletteIndexOutOfBounds {
+                index,
+                palette_length,
+            }) => {
+                assert_eq!(index, 1);
+                assert_eq!(palette_length, 1);
+            }
+            result => panic!("Expected `PaletteIndexOutOfBounds` but got `{:?}`", result),
+        }
+    }
+
+    #[test]
+    fn test_paletted_container_compact() {
+        let mut container = PalettedContainer::new("a", 4);
+        container.set(0, "b");
+        container.set(1, "c");
+        container.set(0, "a");
+        container.compact();
+
+        assert_eq!(container.palette(), &["a", "c"]);
+        assert_eq!(
+            container.iter().copied().collect::<Vec<_>>(),
+            vec!["a", "c", "a", "a"]
+        );
+    }
+
+    #[test]
+    fn test_paletted_container_set_and_replace_many() {
+        let mut container = PalettedContainer::new("a", 4);
+
+        assert_eq!(container.set_many(1..3, "b"), 2);
+        assert_eq!(container.set_many(0..3, "b"), 1);
+        assert_eq!(container.replace_many(2..4, &"b", "c"), 1);
+        assert_eq!(container.replace_many(0..4, &"d", "a"), 0);
+        assert_eq!(
+            container.iter().copied().collect::<Vec<_>>(),
+            vec!["b", "b", "c", "a"]
+        );
+    }
+}
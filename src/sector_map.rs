@@ -0,0 +1,202 @@
+//! Allocation table of region file sectors.
+//!
+//! [`SectorMap`] tells for every 4 KB sector of region file whether it holds header,
+//! belongs to chunk or is unused. Unused sectors which still contain data of deleted or
+//! moved chunks are told apart from zeroed ones, so defragmentation tools can draw file
+//! layout and report exactly how much space is wasted.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::sector_map::{SectorMap, SectorUsage};
+//!
+//! let sector_map = SectorMap::open("test/region/r.0.0.mca").unwrap();
+//!
+//! assert_eq!(sector_map.sectors()[0], SectorUsage::Header);
+//! println!("{} bytes wasted", sector_map.wasted_bytes());
+//! ```
+use crate::headers::RegionHeaders;
+use crate::{REGION_CHUNKS, REGION_HEADER_BYTES_LENGTH, REGION_SECTOR_BYTES_LENGTH};
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+
+/// What sector of region file is used for.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SectorUsage {
+    /// Sector of chunk offsets and timestamps.
+    Header,
+    /// Sector of chunk at region coordinates.
+    Chunk { chunk_x: u8, chunk_z: u8 },
+    /// Sector claimed by header and chunk or by several chunks, which corrupts them.
+    Shared,
+    /// Unused sector filled with zeros.
+    Free,
+    /// Unused sector which still contains data, usually of deleted or moved chunk.
+    Orphaned,
+}
+
+/// Usage of every sector of region file.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SectorMap {
+    /// Sectors in file order, incomplete last sector included.
+    sectors: Vec<SectorUsage>,
+}
+
+impl SectorMap {
+    /// Reads region file contents from reader to the end.
+    ///
+    /// Sectors which header assigns to chunks past the end of contents are not included.
+    pub fn read(reader: &mut impl Read) -> Result<Self, io::Error> {
+        let sector_length = REGION_SECTOR_BYTES_LENGTH as u64;
+
+        let mut header = Vec::with_capacity(REGION_HEADER_BYTES_LENGTH as usize);
+        reader
+            .take(REGION_HEADER_BYTES_LENGTH)
+            .read_to_end(&mut header)?;
+
+        let region_headers = RegionHeaders::read(&mut header.as_slice())?;
+        let header_sectors = (header.len() as u64).div_ceil(sector_length) as usize;
+        let mut sectors = vec![SectorUsage::Header; header_sectors];
+        let mut buffer = Vec::with_capacity(sector_length as usize);
+
+        loop {
+            buffer.clear();
+            reader.take(sector_length).read_to_end(&mut buffer)?;
+
+            if buffer.is_empty() {
+                break;
+            }
+
+            if buffer.iter().all(|&byte| byte == 0) {
+                sectors.push(SectorUsage::Free);
+            } else {
+                sectors.push(SectorUsage::Orphaned);
+            }
+        }
+
+        for index in 0..REGION_CHUNKS {
+            let chunk_x = (index % 32) as u8;
+            let chunk_z = (index / 32) as u8;
+            let metadata = region_headers.metadata(chunk_x, chunk_z);
+
+            let start_index = (metadata.sector_index as usize).min(sectors.len());
+            let end_index = (start_index + metadata.sectors as usize).min(sectors.len());
+
+            for sector in &mut sectors[start_index..end_index] {
+                *sector = match sector {
+                    SectorUsage::Free | SectorUsage::Orphaned => {
+                        SectorUsage::Chunk { chunk_x, chunk_z }
+                    }
+                    _ => SectorUsage::Shared,
+                };
+            }
+        }
+
+        Ok(SectorMap { sectors })
+    }
+
+    /// Reads region file at the specified path.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, io::Error> {
+        SectorMap::read(&mut File::open(path)?)
+    }
+
+    /// Returns usage of sectors in file order.
+    pub fn sectors(&self) -> &[SectorUsage] {
+        &self.sectors
+    }
+
+    /// Returns amount of sectors with usage.
+    pub fn count(&self, usage: SectorUsage) -> usize {
+        self.sectors
+            .iter()
+            .filter(|&&sector| sector == usage)
+            .count()
+    }
+
+    /// Returns length of free and orphaned sectors in bytes.
+    pub fn wasted_bytes(&self) -> u64 {
+        let unused_sectors = self.count(SectorUsage::Free) + self.count(SectorUsage::Orphaned);
+
+        unused_sectors as u64 * REGION_SECTOR_BYTES_LENGTH as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sector_map::{SectorMap, SectorUsage};
+    use crate::AnvilChunkProvider;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_sector_map() {
+        let sector_map = SectorMap::open("test/region/r.0.0.mca").unwrap();
+        let sectors = sector_map.sectors();
+
+        assert_eq!(&sectors[..2], &[SectorUsage::Header, SectorUsage::Header]);
+        assert_eq!(sector_map.count(SectorUsage::Shared), 0);
+        assert!(sectors.contains(&SectorUsage::Chunk {
+            chunk_x: 4,
+            chunk_z: 2,
+        }));
+        assert!(!sectors.contains(&SectorUsage::Chunk {
+            chunk_x: 15,
+            chunk_z: 14,
+        }));
+    }
+
+    #[test]
+    fn test_free_and_orphaned_sectors() {
+        let region_dir = TempDir::new().unwrap();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk_provider = AnvilChunkProvider::new(region_dir.path().to_str().unwrap());
+        let chunk_compound_tag = fixture_chunk_provider.load_chunk(4, 2).unwrap();
+
+        chunk_provider.save_chunk(0, 0, chunk_compound_tag).unwrap();
+
+        let region_path = region_dir.path().join("r.0.0.mca");
+        let chunk_sectors = SectorMap::open(&region_path)
+            .unwrap()
+            .count(SectorUsage::Chunk {
+                chunk_x: 0,
+                chunk_z: 0,
+            });
+
+        assert!(chunk_sectors > 0);
+
+        chunk_provider.delete_chunk(0, 0).unwrap();
+        chunk_provider
+            .preallocate_region(0, 0, 4096 * (chunk_sectors as u64 + 4))
+            .unwrap();
+
+        let sector_map = SectorMap::open(&region_path).unwrap();
+
+        assert_eq!(sector_map.count(SectorUsage::Orphaned), chunk_sectors);
+        assert_eq!(sector_map.count(SectorUsage::Free), 2);
+        assert_eq!(sector_map.wasted_bytes(), 4096 * (chunk_sectors as u64 + 2));
+    }
+
+    #[test]
+    fn test_shared_sectors() {
+        let mut data = vec![0u8; 4096 * 3];
+        // Two chunks claiming the same sector after header.
+        data[..4].copy_from_slice(&[0, 0, 2, 1]);
+        data[4..8].copy_from_slice(&[0, 0, 2, 1]);
+        // Chunk overlapping header.
+        data[8..12].copy_from_slice(&[0, 0, 1, 1]);
+
+        let sector_map = SectorMap::read(&mut data.as_slice()).unwrap();
+
+        assert_eq!(
+            sector_map.sectors(),
+            &[
+                SectorUsage::Header,
+                SectorUsage::Shared,
+                SectorUsage::Shared
+            ]
+        );
+    }
+}
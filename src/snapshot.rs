@@ -0,0 +1,226 @@
+//! Cheap point-in-time copies of world folders.
+//!
+//! Snapshot hard links every file of world into target folder, so it takes almost no
+//! space until files change. Writes made through this crate replace linked files with
+//! private copies first, so they never reach snapshot. Files changed in place by
+//! other programs, for example by running game, change in snapshot too, so world
+//! should be closed while snapshot is in use.
+//!
+//! On platforms other than Unix files are always copied.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::relocate::copy_chunk;
+//! use anvil_region::snapshot::snapshot;
+//! use anvil_region::AnvilChunkProvider;
+//! use tempfile::TempDir;
+//!
+//! let world_dir = TempDir::new().unwrap();
+//! let snapshot_dir = TempDir::new().unwrap();
+//! let region_folder = world_dir.path().join("region");
+//!
+//! let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+//! let chunk_provider = AnvilChunkProvider::new(region_folder.to_str().unwrap());
+//! copy_chunk(&fixture_chunk_provider, (4, 2), &chunk_provider, (4, 2)).unwrap();
+//!
+//! snapshot(world_dir.path(), snapshot_dir.path()).unwrap();
+//! chunk_provider.delete_chunk(4, 2).unwrap();
+//!
+//! let snapshot_region_folder = snapshot_dir.path().join("region");
+//! let snapshot_chunk_provider = AnvilChunkProvider::new(snapshot_region_folder.to_str().unwrap());
+//!
+//! assert!(snapshot_chunk_provider.load_chunk(4, 2).is_ok());
+//! ```
+use crate::open_locked;
+use std::path::Path;
+use std::{fs, io};
+
+/// Lock file of running game which must not be copied.
+const SESSION_LOCK_FILE: &str = "session.lock";
+
+/// Possible errors while creating snapshot.
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// Target folder is source folder or is inside of it.
+    TargetIsSource,
+    /// File can't be read, linked or copied.
+    IoError { io_error: io::Error },
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(io_error: io::Error) -> Self {
+        SnapshotError::IoError { io_error }
+    }
+}
+
+/// Result of snapshot.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SnapshotReport {
+    /// Files which were hard linked.
+    pub linked_files: usize,
+    /// Files which were copied because they couldn't be linked.
+    pub copied_files: usize,
+}
+
+/// Creates snapshot of world folder in target folder.
+///
+/// Every file except `session.lock` is hard linked, falling back to copy when target
+/// is on another file system or links are not supported.
+pub fn snapshot(
+    world_folder_path: &Path,
+    target_folder_path: &Path,
+) -> Result<SnapshotReport, SnapshotError> {
+    if target_folder_path.starts_with(world_folder_path) {
+        return Err(SnapshotError::TargetIsSource);
+    }
+
+    let mut snapshot_report = SnapshotReport::default();
+    snapshot_folder(world_folder_path, target_folder_path, &mut snapshot_report)?;
+
+    Ok(snapshot_report)
+}
+
+fn snapshot_folder(
+    folder_path: &Path,
+    target_folder_path: &Path,
+    snapshot_report: &mut SnapshotReport,
+) -> Result<(), io::Error> {
+    fs::create_dir_all(target_folder_path)?;
+
+    for entry in fs::read_dir(folder_path)? {
+        let path = entry?.path();
+
+        let file_name = match path.file_name() {
+            Some(file_name) => file_name,
+            None => continue,
+        };
+
+        let target_path = target_folder_path.join(file_name);
+
+        if path.is_dir() {
+            snapshot_folder(&path, &target_path, snapshot_report)?;
+        } else if file_name != SESSION_LOCK_FILE {
+            if cfg!(unix) && fs::hard_link(&path, &target_path).is_ok() {
+                snapshot_report.linked_files += 1;
+            } else {
+                fs::copy(&path, &target_path)?;
+                snapshot_report.copied_files += 1;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Replaces file shared by hard links with private copy, so writing it in place
+/// doesn't change other links. Returns true if file was replaced.
+pub(crate) fn break_hard_link(path: &Path) -> Result<bool, io::Error> {
+    if !is_hard_linked(path)? {
+        return Ok(false);
+    }
+
+    // Writes of other processes must not be lost between copy and rename.
+    let _file = open_locked(path)?;
+
+    // Other process could replace file while waiting for lock.
+    if !is_hard_linked(path)? {
+        return Ok(false);
+    }
+
+    let mut temporary_file_name = path.file_name().unwrap_or_default().to_owned();
+    temporary_file_name.push(".unlink");
+
+    let temporary_path = path.with_file_name(temporary_file_name);
+
+    fs::copy(path, &temporary_path)?;
+    fs::rename(&temporary_path, path)?;
+
+    Ok(true)
+}
+
+#[cfg(unix)]
+fn is_hard_linked(path: &Path) -> Result<bool, io::Error> {
+    use std::os::unix::fs::MetadataExt;
+
+    match fs::metadata(path) {
+        Ok(metadata) => Ok(metadata.nlink() > 1),
+        Err(io_error) if io_error.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(io_error) => Err(io_error),
+    }
+}
+
+/// Snapshot never links files on other platforms.
+#[cfg(not(unix))]
+fn is_hard_linked(_path: &Path) -> Result<bool, io::Error> {
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{read_data_file, write_data_file};
+    use crate::relocate::copy_chunk;
+    use crate::snapshot::{snapshot, SnapshotError};
+    use crate::AnvilChunkProvider;
+    use nbt::CompoundTag;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_snapshot_is_not_changed_by_writes() {
+        let world_dir = TempDir::new().unwrap();
+        let snapshot_dir = TempDir::new().unwrap();
+        let region_folder = world_dir.path().join("region");
+        let chunk_provider = AnvilChunkProvider::new(region_folder.to_str().unwrap());
+
+        copy_chunk(
+            &AnvilChunkProvider::new("test/region"),
+            (4, 2),
+            &chunk_provider,
+            (4, 2),
+        )
+        .unwrap();
+        write_data_file(&world_dir.path().join("level.dat"), &CompoundTag::new()).unwrap();
+        fs::write(world_dir.path().join("session.lock"), b"lock").unwrap();
+
+        let snapshot_report = snapshot(world_dir.path(), snapshot_dir.path()).unwrap();
+
+        assert_eq!(
+            snapshot_report.linked_files + snapshot_report.copied_files,
+            2
+        );
+        assert!(!snapshot_dir.path().join("session.lock").exists());
+
+        chunk_provider.save_chunk(4, 2, CompoundTag::new()).unwrap();
+        chunk_provider.save_chunk(5, 2, CompoundTag::new()).unwrap();
+        let mut level_dat = CompoundTag::new();
+        level_dat.insert_str("Changed", "true");
+        write_data_file(&world_dir.path().join("level.dat"), &level_dat).unwrap();
+
+        let snapshot_region_folder = snapshot_dir.path().join("region");
+        let snapshot_chunk_provider =
+            AnvilChunkProvider::new(snapshot_region_folder.to_str().unwrap());
+        let snapshot_level_dat = read_data_file(&snapshot_dir.path().join("level.dat")).unwrap();
+
+        assert_eq!(
+            snapshot_chunk_provider.chunk_positions().unwrap(),
+            vec![(4, 2)]
+        );
+        assert!(snapshot_chunk_provider
+            .load_chunk(4, 2)
+            .unwrap()
+            .contains_key("Level"));
+        assert!(!snapshot_level_dat.contains_key("Changed"));
+    }
+
+    #[test]
+    fn test_snapshot_into_itself() {
+        let world_dir = TempDir::new().unwrap();
+
+        match snapshot(world_dir.path(), &world_dir.path().join("snapshot")) {
+            Err(SnapshotError::TargetIsSource) => {}
+            result => panic!("Expected `TargetIsSource` but got `{:?}`", result),
+        }
+    }
+}
@@ -0,0 +1,766 @@
+//! Stringified NBT as used by game commands.
+//!
+//! Text form makes chunks comparable with text diff tools, and fragments can be
+//! pasted into `/data` commands.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::snbt;
+//! use nbt::CompoundTag;
+//!
+//! let mut compound_tag = CompoundTag::new();
+//! compound_tag.insert_i32("xPos", 4);
+//! compound_tag.insert_str("Status", "full");
+//!
+//! assert_eq!(snbt::to_string(&compound_tag), r#"{xPos:4,Status:"full"}"#);
+//! assert_eq!(snbt::from_str(r#"{xPos: 4, Status: "full"}"#).unwrap().get_i32("xPos").unwrap(), 4);
+//! ```
+use crate::tag::{get_tag_mut, set_tag};
+use crate::{AnvilChunkProvider, ChunkLoadError, ChunkSaveError};
+use nbt::{CompoundTag, Tag};
+
+/// Possible errors while parsing or importing SNBT.
+#[derive(Debug)]
+pub enum SnbtError {
+    /// Text ended before tag was complete.
+    UnexpectedEnd,
+    /// Character at byte position can't appear there.
+    UnexpectedCharacter { position: usize, character: char },
+    /// Array or list element at byte position has different type than other elements.
+    MixedTypes { position: usize },
+    /// Text contains tag which is not a compound.
+    NotCompound,
+    /// Merge path goes through tag which is not a compound.
+    InvalidPath { path: String },
+    /// Chunk can't be loaded.
+    ChunkLoadError { chunk_load_error: ChunkLoadError },
+    /// Chunk can't be saved.
+    ChunkSaveError { chunk_save_error: ChunkSaveError },
+}
+
+impl From<ChunkLoadError> for SnbtError {
+    fn from(chunk_load_error: ChunkLoadError) -> Self {
+        SnbtError::ChunkLoadError { chunk_load_error }
+    }
+}
+
+impl From<ChunkSaveError> for SnbtError {
+    fn from(chunk_save_error: ChunkSaveError) -> Self {
+        SnbtError::ChunkSaveError { chunk_save_error }
+    }
+}
+
+/// Indentation used for each nesting level by pretty printing.
+const INDENT: &str = "    ";
+
+/// Renders compound tag as single line SNBT.
+pub fn to_string(compound_tag: &CompoundTag) -> String {
+    let mut snbt = String::new();
+    write_compound_tag(&mut snbt, compound_tag, None);
+
+    snbt
+}
+
+/// Renders compound tag as SNBT with every compound and list entry on own line.
+///
+/// Arrays are kept on single line because they are usually packed data.
+pub fn to_string_pretty(compound_tag: &CompoundTag) -> String {
+    let mut snbt = String::new();
+    write_compound_tag(&mut snbt, compound_tag, Some(0));
+
+    snbt
+}
+
+/// Renders single tag as SNBT.
+pub fn tag_to_string(tag: &Tag) -> String {
+    let mut snbt = String::new();
+    write_tag(&mut snbt, tag, None);
+
+    snbt
+}
+
+/// Writes tag, `depth` is a nesting level when pretty printing.
+fn write_tag(snbt: &mut String, tag: &Tag, depth: Option<usize>) {
+    match tag {
+        Tag::Byte(value) => snbt.push_str(&format!("{}b", value)),
+        Tag::Short(value) => snbt.push_str(&format!("{}s", value)),
+        Tag::Int(value) => snbt.push_str(&value.to_string()),
+        Tag::Long(value) => snbt.push_str(&format!("{}L", value)),
+        Tag::Float(value) => snbt.push_str(&format!("{:?}f", value)),
+        Tag::Double(value) => snbt.push_str(&format!("{:?}d", value)),
+        Tag::ByteArray(values) => write_array(snbt, "B", values, "b", depth),
+        Tag::String(value) => write_quoted(snbt, value),
+        Tag::List(tags) => write_list(snbt, tags, depth),
+        Tag::Compound(compound_tag) => write_compound_tag(snbt, compound_tag, depth),
+        Tag::IntArray(values) => write_array(snbt, "I", values, "", depth),
+        Tag::LongArray(values) => write_array(snbt, "L", values, "L", depth),
+    }
+}
+
+fn write_compound_tag(snbt: &mut String, compound_tag: &CompoundTag, depth: Option<usize>) {
+    let entries = compound_tag.iter().map(|(name, tag)| (Some(name), tag));
+    write_entries(snbt, ('{', '}'), entries, depth);
+}
+
+fn write_list(snbt: &mut String, tags: &[Tag], depth: Option<usize>) {
+    let entries = tags.iter().map(|tag| (None, tag));
+    write_entries(snbt, ('[', ']'), entries, depth);
+}
+
+/// Writes compound or list entries, names are present only for compound.
+fn write_entries<'a>(
+    snbt: &mut String,
+    (open, close): (char, char),
+    entries: impl Iterator<Item = (Option<&'a String>, &'a Tag)>,
+    depth: Option<usize>,
+) {
+    let mut entries = entries.peekable();
+    snbt.push(open);
+
+    if entries.peek().is_none() {
+        snbt.push(close);
+        return;
+    }
+
+    let nested_depth = depth.map(|depth| depth + 1);
+
+    for (index, (name, tag)) in entries.enumerate() {
+        if index > 0 {
+            snbt.push(',');
+        }
+
+        if let Some(nested_depth) = nested_depth {
+            write_line_break(snbt, nested_depth);
+        }
+
+        if let Some(name) = name {
+            write_name(snbt, name);
+            snbt.push(':');
+
+            if depth.is_some() {
+                snbt.push(' ');
+            }
+        }
+
+        write_tag(snbt, tag, nested_depth);
+    }
+
+    if let Some(depth) = depth {
+        write_line_break(snbt, depth);
+    }
+
+    snbt.push(close);
+}
+
+fn write_array<T: ToString>(
+    snbt: &mut String,
+    prefix: &str,
+    values: &[T],
+    suffix: &str,
+    depth: Option<usize>,
+) {
+    let separator = if depth.is_some() { ", " } else { "," };
+
+    snbt.push('[');
+    snbt.push_str(prefix);
+    snbt.push(';');
+
+    for (index, value) in values.iter().enumerate() {
+        if index > 0 {
+            snbt.push_str(separator);
+        } else if depth.is_some() {
+            snbt.push(' ');
+        }
+
+        snbt.push_str(&value.to_string());
+        snbt.push_str(suffix);
+    }
+
+    snbt.push(']');
+}
+
+fn write_line_break(snbt: &mut String, depth: usize) {
+    snbt.push('\n');
+
+    for _ in 0..depth {
+        snbt.push_str(INDENT);
+    }
+}
+
+/// Writes compound entry name, quoted only when it contains characters not allowed bare.
+fn write_name(snbt: &mut String, name: &str) {
+    let is_bare = !name.is_empty() && name.chars().all(is_unquoted_character);
+
+    if is_bare {
+        snbt.push_str(name);
+    } else {
+        write_quoted(snbt, name);
+    }
+}
+
+fn write_quoted(snbt: &mut String, value: &str) {
+    snbt.push('"');
+
+    for c in value.chars() {
+        if c == '"' || c == '\\' {
+            snbt.push('\\');
+        }
+
+        snbt.push(c);
+    }
+
+    snbt.push('"');
+}
+
+/// Parses compound tag from SNBT.
+pub fn from_str(snbt: &str) -> Result<CompoundTag, SnbtError> {
+    match tag_from_str(snbt)? {
+        Tag::Compound(compound_tag) => Ok(compound_tag),
+        _ => Err(SnbtError::NotCompound),
+    }
+}
+
+/// Parses single tag from SNBT.
+///
+/// Unquoted values which are not numbers or booleans are parsed as strings.
+pub fn tag_from_str(snbt: &str) -> Result<Tag, SnbtError> {
+    let mut parser = Parser { snbt, position: 0 };
+
+    let tag = parser.read_tag()?;
+    parser.skip_whitespace();
+
+    match parser.peek() {
+        Some(character) => Err(parser.unexpected_character(character)),
+        None => Ok(tag),
+    }
+}
+
+/// Merges tags of source into target.
+///
+/// Nested compounds are merged recursively, other tags of target are replaced.
+pub fn merge(target: &mut CompoundTag, source: CompoundTag) {
+    for (name, tag) in source {
+        match (get_tag_mut(target, &name), tag) {
+            (Some(Tag::Compound(target_compound_tag)), Tag::Compound(source_compound_tag)) => {
+                merge(target_compound_tag, source_compound_tag)
+            }
+            (_, tag) => set_tag(target, &name, tag),
+        }
+    }
+}
+
+/// Merges SNBT compound into compound at dot separated path, empty path means root.
+///
+/// Missing compounds on path are created.
+pub fn merge_at_path(target: &mut CompoundTag, path: &str, snbt: &str) -> Result<(), SnbtError> {
+    let source = from_str(snbt)?;
+    let mut compound_tag = target;
+
+    for name in path.split('.').filter(|name| !name.is_empty()) {
+        if !compound_tag.contains_key(name) {
+            compound_tag.insert_compound_tag(name, CompoundTag::new());
+        }
+
+        compound_tag = match compound_tag.get_mut::<&mut CompoundTag>(name) {
+            Ok(compound_tag) => compound_tag,
+            Err(_) => {
+                return Err(SnbtError::InvalidPath {
+                    path: path.to_owned(),
+                })
+            }
+        };
+    }
+
+    merge(compound_tag, source);
+
+    Ok(())
+}
+
+/// Parses SNBT compound and saves it as chunk at the specified coordinates.
+///
+/// # Example
+///
+/// ```
+/// use anvil_region::snbt::import_chunk;
+/// use anvil_region::AnvilChunkProvider;
+/// use tempfile::TempDir;
+///
+/// let temp_dir = TempDir::new().unwrap();
+/// let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+///
+/// import_chunk(&chunk_provider, 1, 2, "{Level: {xPos: 1, zPos: 2}}").unwrap();
+///
+/// assert!(chunk_provider.load_chunk(1, 2).is_ok());
+/// ```
+pub fn import_chunk(
+    chunk_provider: &AnvilChunkProvider,
+    chunk_x: i32,
+    chunk_z: i32,
+    snbt: &str,
+) -> Result<(), SnbtError> {
+    let chunk_compound_tag = from_str(snbt)?;
+    chunk_provider.save_chunk(chunk_x, chunk_z, chunk_compound_tag)?;
+
+    Ok(())
+}
+
+/// Merges SNBT compound into stored chunk at dot separated path and saves chunk.
+///
+/// # Example
+///
+/// ```
+/// use anvil_region::snbt::{import_chunk, merge_into_chunk};
+/// use anvil_region::AnvilChunkProvider;
+/// use tempfile::TempDir;
+///
+/// let temp_dir = TempDir::new().unwrap();
+/// let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+///
+/// import_chunk(&chunk_provider, 1, 2, "{Level: {xPos: 1, zPos: 2}}").unwrap();
+/// merge_into_chunk(&chunk_provider, 1, 2, "Level", r#"{Status: "full"}"#).unwrap();
+///
+/// let chunk_compound_tag = chunk_provider.load_chunk(1, 2).unwrap();
+/// let level_compound_tag = chunk_compound_tag.get_compound_tag("Level").unwrap();
+///
+/// assert_eq!(level_compound_tag.get_str("Status").unwrap(), "full");
+/// ```
+pub fn merge_into_chunk(
+    chunk_provider: &AnvilChunkProvider,
+    chunk_x: i32,
+    chunk_z: i32,
+    path: &str,
+    snbt: &str,
+) -> Result<(), SnbtError> {
+    let mut chunk_compound_tag = chunk_provider.load_chunk(chunk_x, chunk_z)?;
+    merge_at_path(&mut chunk_compound_tag, path, snbt)?;
+    chunk_provider.save_chunk(chunk_x, chunk_z, chunk_compound_tag)?;
+
+    Ok(())
+}
+
+/// Recursive descent parser over SNBT text.
+struct Parser<'a> {
+    snbt: &'a str,
+    /// Byte position of the next character.
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.snbt[self.position..].chars().next()
+    }
+
+    fn next(&mut self) -> Result<char, SnbtError> {
+        let character = self.peek().ok_or(SnbtError::UnexpectedEnd)?;
+        self.position += character.len_utf8();
+
+        Ok(character)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(character) = self.peek() {
+            if !character.is_whitespace() {
+                break;
+            }
+
+            self.position += character.len_utf8();
+        }
+    }
+
+    fn unexpected_character(&self, character: char) -> SnbtError {
+        SnbtError::UnexpectedCharacter {
+            position: self.position,
+            character,
+        }
+    }
+
+    /// Skips whitespace and consumes expected character.
+    fn expect(&mut self, expected: char) -> Result<(), SnbtError> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some(character) if character == expected => {
+                self.position += character.len_utf8();
+                Ok(())
+            }
+            Some(character) => Err(self.unexpected_character(character)),
+            None => Err(SnbtError::UnexpectedEnd),
+        }
+    }
+
+    /// Skips whitespace and consumes character if it matches.
+    fn consume(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+
+        if self.peek() == Some(expected) {
+            self.position += expected.len_utf8();
+            return true;
+        }
+
+        false
+    }
+
+    fn read_tag(&mut self) -> Result<Tag, SnbtError> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some('{') => Ok(Tag::Compound(self.read_compound_tag()?)),
+            Some('[') => self.read_list_or_array(),
+            Some('"') | Some('\'') => Ok(Tag::String(self.read_quoted()?)),
+            Some(_) => {
+                let value = self.read_unquoted()?;
+
+                Ok(parse_unquoted(value).unwrap_or_else(|| Tag::String(value.to_owned())))
+            }
+            None => Err(SnbtError::UnexpectedEnd),
+        }
+    }
+
+    fn read_compound_tag(&mut self) -> Result<CompoundTag, SnbtError> {
+        self.expect('{')?;
+        let mut compound_tag = CompoundTag::new();
+
+        if self.consume('}') {
+            return Ok(compound_tag);
+        }
+
+        loop {
+            self.skip_whitespace();
+
+            let name = match self.peek() {
+                Some('"') | Some('\'') => self.read_quoted()?,
+                _ => self.read_unquoted()?.to_owned(),
+            };
+
+            self.expect(':')?;
+            compound_tag.insert(name, self.read_tag()?);
+
+            if !self.consume(',') {
+                break;
+            }
+        }
+
+        self.expect('}')?;
+
+        Ok(compound_tag)
+    }
+
+    fn read_list_or_array(&mut self) -> Result<Tag, SnbtError> {
+        self.expect('[')?;
+        self.skip_whitespace();
+
+        let rest = &self.snbt[self.position..];
+        let array_type = match rest.as_bytes() {
+            [array_type @ b'B', b';', ..]
+            | [array_type @ b'I', b';', ..]
+            | [array_type @ b'L', b';', ..] => Some(*array_type),
+            _ => None,
+        };
+
+        if let Some(array_type) = array_type {
+            self.position += 2;
+            return self.read_array(array_type);
+        }
+
+        let mut tags = Vec::new();
+
+        if self.consume(']') {
+            return Ok(Tag::List(tags));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let position = self.position;
+            let tag = self.read_tag()?;
+
+            if let Some(first_tag) = tags.first() {
+                if std::mem::discriminant(first_tag) != std::mem::discriminant(&tag) {
+                    return Err(SnbtError::MixedTypes { position });
+                }
+            }
+
+            tags.push(tag);
+
+            if !self.consume(',') {
+                break;
+            }
+        }
+
+        self.expect(']')?;
+
+        Ok(Tag::List(tags))
+    }
+
+    fn read_array(&mut self, array_type: u8) -> Result<Tag, SnbtError> {
+        let mut bytes = Vec::new();
+        let mut ints = Vec::new();
+        let mut longs = Vec::new();
+
+        if !self.consume(']') {
+            loop {
+                self.skip_whitespace();
+                let position = self.position;
+
+                match (array_type, self.read_tag()?) {
+                    (b'B', Tag::Byte(value)) => bytes.push(value),
+                    (b'I', Tag::Int(value)) => ints.push(value),
+                    (b'L', Tag::Long(value)) => longs.push(value),
+                    _ => return Err(SnbtError::MixedTypes { position }),
+                }
+
+                if !self.consume(',') {
+                    break;
+                }
+            }
+
+            self.expect(']')?;
+        }
+
+        let tag = match array_type {
+            b'B' => Tag::ByteArray(bytes),
+            b'I' => Tag::IntArray(ints),
+            _ => Tag::LongArray(longs),
+        };
+
+        Ok(tag)
+    }
+
+    fn read_quoted(&mut self) -> Result<String, SnbtError> {
+        let quote = self.next()?;
+        let mut value = String::new();
+
+        loop {
+            match self.next()? {
+                '\\' => {
+                    let character = self.next()?;
+
+                    if character != quote && character != '\\' {
+                        self.position -= character.len_utf8();
+                        return Err(self.unexpected_character(character));
+                    }
+
+                    value.push(character);
+                }
+                character if character == quote => return Ok(value),
+                character => value.push(character),
+            }
+        }
+    }
+
+    fn read_unquoted(&mut self) -> Result<&'a str, SnbtError> {
+        let start = self.position;
+
+        while let Some(character) = self.peek() {
+            if !is_unquoted_character(character) {
+                break;
+            }
+
+            self.position += 1;
+        }
+
+        if start == self.position {
+            return match self.peek() {
+                Some(character) => Err(self.unexpected_character(character)),
+                None => Err(SnbtError::UnexpectedEnd),
+            };
+        }
+
+        Ok(&self.snbt[start..self.position])
+    }
+}
+
+/// Characters which can appear in names and values without quotes.
+fn is_unquoted_character(character: char) -> bool {
+    character.is_ascii_alphanumeric() || "_-.+".contains(character)
+}
+
+/// Parses number or boolean, returns none if value should be a string.
+fn parse_unquoted(value: &str) -> Option<Tag> {
+    match value {
+        "true" => return Some(Tag::Byte(1)),
+        "false" => return Some(Tag::Byte(0)),
+        _ => {}
+    }
+
+    if !value.starts_with(|c: char| c.is_ascii_digit() || "+-.".contains(c)) {
+        return None;
+    }
+
+    let (number, suffix) = value.split_at(value.len() - 1);
+
+    let tag = match suffix {
+        "b" | "B" => Tag::Byte(number.parse().ok()?),
+        "s" | "S" => Tag::Short(number.parse().ok()?),
+        "l" | "L" => Tag::Long(number.parse().ok()?),
+        "f" | "F" => Tag::Float(parse_decimal(number)? as f32),
+        "d" | "D" => Tag::Double(parse_decimal(number)?),
+        _ => match value.parse() {
+            Ok(value) => Tag::Int(value),
+            Err(_) => Tag::Double(parse_decimal(value)?),
+        },
+    };
+
+    Some(tag)
+}
+
+/// Parses decimal number, rejecting words like `inf` accepted by Rust.
+fn parse_decimal(value: &str) -> Option<f64> {
+    let is_decimal = value.chars().any(|c| c.is_ascii_digit())
+        && value
+            .chars()
+            .all(|c| c.is_ascii_digit() || "+-.eE".contains(c));
+
+    if !is_decimal {
+        return None;
+    }
+
+    value.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::snbt::{
+        from_str, merge_at_path, tag_from_str, tag_to_string, to_string, to_string_pretty,
+        SnbtError,
+    };
+    use crate::AnvilChunkProvider;
+    use nbt::{CompoundTag, Tag};
+
+    #[test]
+    fn test_tag_to_string() {
+        assert_eq!(tag_to_string(&Tag::Byte(1)), "1b");
+        assert_eq!(tag_to_string(&Tag::Short(-2)), "-2s");
+        assert_eq!(tag_to_string(&Tag::Long(3)), "3L");
+        assert_eq!(tag_to_string(&Tag::Float(1.0)), "1.0f");
+        assert_eq!(tag_to_string(&Tag::Double(0.5)), "0.5d");
+        assert_eq!(tag_to_string(&Tag::ByteArray(vec![1, -1])), "[B;1b,-1b]");
+        assert_eq!(tag_to_string(&Tag::IntArray(vec![])), "[I;]");
+        assert_eq!(tag_to_string(&Tag::LongArray(vec![7])), "[L;7L]");
+        assert_eq!(
+            tag_to_string(&Tag::String(r#"a"b\"#.to_owned())),
+            r#""a\"b\\""#
+        );
+        assert_eq!(tag_to_string(&Tag::List(vec![])), "[]");
+    }
+
+    #[test]
+    fn test_to_string() {
+        let mut nested_compound_tag = CompoundTag::new();
+        nested_compound_tag.insert_str("Name", "minecraft:stone");
+
+        let mut compound_tag = CompoundTag::new();
+        compound_tag.insert_i32("DataVersion", 2586);
+        compound_tag.insert_compound_tag_vec("block states", vec![nested_compound_tag]);
+        compound_tag.insert_compound_tag("Level", CompoundTag::new());
+
+        assert_eq!(
+            to_string(&compound_tag),
+            r#"{DataVersion:2586,"block states":[{Name:"minecraft:stone"}],Level:{}}"#
+        );
+    }
+
+    #[test]
+    fn test_to_string_pretty() {
+        let mut compound_tag = CompoundTag::new();
+        compound_tag.insert_i32("xPos", 4);
+        compound_tag.insert_i64_vec("Data", vec![1, 2]);
+        compound_tag.insert("Pos", Tag::List(vec![Tag::Double(1.0), Tag::Double(2.0)]));
+
+        let expected = "{\n    xPos: 4,\n    Data: [L; 1L, 2L],\n    Pos: [\n        1.0d,\n        2.0d\n    ]\n}";
+
+        assert_eq!(to_string_pretty(&compound_tag), expected);
+    }
+
+    #[test]
+    fn test_tag_from_str() {
+        let test_cases = vec![
+            "1b",
+            "-2s",
+            "3",
+            "3L",
+            "1.0f",
+            "0.5d",
+            "[B;1b,-1b]",
+            "[I;]",
+            "[L;7L]",
+            "[]",
+        ];
+
+        for snbt in test_cases {
+            assert_eq!(tag_to_string(&tag_from_str(snbt).unwrap()), snbt);
+        }
+
+        assert_eq!(tag_to_string(&tag_from_str("true").unwrap()), "1b");
+        assert_eq!(tag_to_string(&tag_from_str("1.5").unwrap()), "1.5d");
+        assert_eq!(tag_to_string(&tag_from_str("stone").unwrap()), r#""stone""#);
+        assert_eq!(tag_to_string(&tag_from_str("inf").unwrap()), r#""inf""#);
+        assert_eq!(
+            tag_to_string(&tag_from_str(r#"'a\'b"'"#).unwrap()),
+            r#""a'b\"""#
+        );
+    }
+
+    #[test]
+    fn test_tag_from_str_errors() {
+        match tag_from_str("[1, 2b]") {
+            Err(SnbtError::MixedTypes { position: 4 }) => {}
+            result => panic!("Expected `MixedTypes` but got `{:?}`", result),
+        }
+
+        match tag_from_str("{a: 1") {
+            Err(SnbtError::UnexpectedEnd) => {}
+            result => panic!("Expected `UnexpectedEnd` but got `{:?}`", result),
+        }
+
+        match tag_from_str("{a: 1} x") {
+            Err(SnbtError::UnexpectedCharacter {
+                position: 7,
+                character: 'x',
+            }) => {}
+            result => panic!("Expected `UnexpectedCharacter` but got `{:?}`", result),
+        }
+
+        match from_str("[1]") {
+            Err(SnbtError::NotCompound) => {}
+            result => panic!("Expected `NotCompound` but got `{:?}`", result),
+        }
+    }
+
+    #[test]
+    fn test_from_str_chunk_roundtrip() {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk_compound_tag = chunk_provider.load_chunk(4, 2).unwrap();
+
+        let snbt = to_string(&chunk_compound_tag);
+
+        assert_eq!(to_string(&from_str(&snbt).unwrap()), snbt);
+        assert_eq!(
+            to_string(&from_str(&to_string_pretty(&chunk_compound_tag)).unwrap()),
+            snbt
+        );
+    }
+
+    #[test]
+    fn test_merge_at_path() {
+        let mut compound_tag = from_str(r#"{Level: {xPos: 1, Heightmaps: {A: 1}}}"#).unwrap();
+
+        merge_at_path(
+            &mut compound_tag,
+            "Level",
+            r#"{xPos: 2, Heightmaps: {B: 2}}"#,
+        )
+        .unwrap();
+        merge_at_path(&mut compound_tag, "Level.Extra", r#"{C: 3}"#).unwrap();
+
+        assert_eq!(
+            to_string(&compound_tag),
+            r#"{Level:{xPos:2,Heightmaps:{A:1,B:2},Extra:{C:3}}}"#
+        );
+
+        match merge_at_path(&mut compound_tag, "Level.xPos", "{}") {
+            Err(SnbtError::InvalidPath { path }) => assert_eq!(path, "Level.xPos"),
+            result => panic!("Expected `InvalidPath` but got `{:?}`", result),
+        }
+    }
+}
@@ -0,0 +1,457 @@
+//! Spatial queries over positions of existing chunks.
+//!
+//! [`ChunkSpatialIndex`] is built from region headers into 2-d tree stored in one
+//! vector, which answers which chunks intersect area and which chunk is the nearest to
+//! point without scanning all chunks, for example for renderers which draw only visible
+//! part of world or tools which look for safe teleport destination.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::spatial::ChunkSpatialIndex;
+//! use anvil_region::AnvilChunkProvider;
+//!
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//! let spatial_index = ChunkSpatialIndex::build(&chunk_provider).unwrap();
+//!
+//! let chunk_positions = spatial_index.chunks_in_block_area((64, 32), (79, 47));
+//! assert_eq!(chunk_positions, vec![(4, 2)]);
+//!
+//! assert!(spatial_index.nearest_chunk(-1000, 0).is_some());
+//! ```
+//!
+//! Single lookup doesn't need index, [`find_nearest_chunk`] reads only headers of
+//! regions around point.
+use crate::hash::CoordinateHashSet;
+use crate::AnvilChunkProvider;
+use std::io;
+
+/// Positions of existing chunks arranged as 2-d tree.
+#[derive(Debug, Clone, Default)]
+pub struct ChunkSpatialIndex {
+    /// Median of every subslice along axis of its depth is in the middle, positions
+    /// before it aren't greater and positions after it aren't less along that axis.
+    chunk_positions: Vec<(i32, i32)>,
+}
+
+impl ChunkSpatialIndex {
+    /// Builds index of chunks listed in region headers of provider.
+    pub fn build(chunk_provider: &AnvilChunkProvider) -> Result<Self, io::Error> {
+        Ok(ChunkSpatialIndex::from_chunk_positions(
+            chunk_provider.chunk_positions()?,
+        ))
+    }
+
+    /// Builds index of specified chunk positions.
+    pub fn from_chunk_positions(mut chunk_positions: Vec<(i32, i32)>) -> Self {
+        chunk_positions.sort_unstable();
+        chunk_positions.dedup();
+
+        arrange(&mut chunk_positions, 0);
+
+        ChunkSpatialIndex { chunk_positions }
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunk_positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunk_positions.is_empty()
+    }
+
+    /// Returns sorted positions of chunks inside of area, both corners are inclusive.
+    pub fn chunks_in_area(&self, min_chunk: (i32, i32), max_chunk: (i32, i32)) -> Vec<(i32, i32)> {
+        let mut chunk_positions = Vec::new();
+
+        query_area(
+            &self.chunk_positions,
+            0,
+            min_chunk,
+            max_chunk,
+            &mut chunk_positions,
+        );
+
+        chunk_positions.sort_unstable();
+
+        chunk_positions
+    }
+
+    /// Returns sorted positions of chunks which intersect block area, both corners
+    /// are inclusive.
+    pub fn chunks_in_block_area(
+        &self,
+        min_block: (i32, i32),
+        max_block: (i32, i32),
+    ) -> Vec<(i32, i32)> {
+        self.chunks_in_area(
+            (min_block.0 >> 4, min_block.1 >> 4),
+            (max_block.0 >> 4, max_block.1 >> 4),
+        )
+    }
+
+    /// Returns position of chunk which is the nearest to specified chunk, itself if it
+    /// exists, none if index is empty.
+    pub fn nearest_chunk(&self, chunk_x: i32, chunk_z: i32) -> Option<(i32, i32)> {
+        let mut nearest = None;
+
+        query_nearest(&self.chunk_positions, 0, (chunk_x, chunk_z), &mut nearest);
+
+        nearest.map(|(chunk_position, _)| chunk_position)
+    }
+
+    /// Returns position of chunk which contains block or is the nearest to it.
+    pub fn nearest_chunk_to_block(&self, block_x: i32, block_z: i32) -> Option<(i32, i32)> {
+        self.nearest_chunk(block_x >> 4, block_z >> 4)
+    }
+}
+
+/// Returns position of chunk which is the nearest to specified chunk, itself if it
+/// exists, none if provider has no chunks.
+///
+/// Region headers are searched in rings of regions around chunk, ring after ring until
+/// no chunk of the next ring can be nearer than the nearest one found, so only a few
+/// headers are read near populated area. Ties are resolved by the lower X, then Z.
+pub fn find_nearest_chunk(
+    chunk_provider: &AnvilChunkProvider,
+    chunk_x: i32,
+    chunk_z: i32,
+) -> Result<Option<(i32, i32)>, io::Error> {
+    let region_positions: CoordinateHashSet<_> = chunk_provider
+        .region_files()?
+        .into_iter()
+        .map(|region_file| region_file.region_position)
+        .collect();
+
+    let (region_x, region_z) = (chunk_x >> 5, chunk_z >> 5);
+
+    // Rings past the farthest region file have no chunks.
+    let max_ring = region_positions
+        .iter()
+        .map(|&(other_region_x, other_region_z)| {
+            (other_region_x - region_x)
+                .abs()
+                .max((other_region_z - region_z).abs())
+        })
+        .max();
+
+    let max_ring = match max_ring {
+        Some(max_ring) => max_ring,
+        None => return Ok(None),
+    };
+
+    let mut nearest: Option<((i32, i32), i64)> = None;
+
+    for ring in 0..=max_ring {
+        // Chunks of ring are separated from chunk by at least ring - 1 whole regions.
+        let min_distance = if ring == 0 {
+            0
+        } else {
+            (ring as i64 - 1) * 32 + 1
+        };
+
+        if let Some((_, distance)) = nearest {
+            if distance <= min_distance * min_distance {
+                break;
+            }
+        }
+
+        for (ring_region_x, ring_region_z) in ring_positions(region_x, region_z, ring) {
+            if !region_positions.contains(&(ring_region_x, ring_region_z)) {
+                continue;
+            }
+
+            let region_name = format!("r.{}.{}.mca", ring_region_x, ring_region_z);
+            let region_path = chunk_provider.folder_path.join(region_name);
+
+            let region_headers = match chunk_provider.region_headers(&region_path)? {
+                Some(region_headers) => region_headers,
+                None => continue,
+            };
+
+            for (region_chunk_x, region_chunk_z) in region_headers.chunk_positions() {
+                let chunk_position = (
+                    (ring_region_x << 5) + region_chunk_x as i32,
+                    (ring_region_z << 5) + region_chunk_z as i32,
+                );
+
+                let distance_x = (chunk_position.0 - chunk_x) as i64;
+                let distance_z = (chunk_position.1 - chunk_z) as i64;
+                let distance = distance_x * distance_x + distance_z * distance_z;
+
+                let is_nearer = match nearest {
+                    Some((nearest_position, nearest_distance)) => {
+                        (distance, chunk_position) < (nearest_distance, nearest_position)
+                    }
+                    None => true,
+                };
+
+                if is_nearer {
+                    nearest = Some((chunk_position, distance));
+                }
+            }
+        }
+    }
+
+    Ok(nearest.map(|(chunk_position, _)| chunk_position))
+}
+
+/// Returns positions of square ring of regions at the specified distance from center.
+fn ring_positions(center_x: i32, center_z: i32, ring: i32) -> Vec<(i32, i32)> {
+    if ring == 0 {
+        return vec![(center_x, center_z)];
+    }
+
+    let mut positions = Vec::with_capacity(8 * ring as usize);
+
+    for offset in -ring..=ring {
+        positions.push((center_x + offset, center_z - ring));
+        positions.push((center_x + offset, center_z + ring));
+    }
+
+    for offset in -ring + 1..ring {
+        positions.push((center_x - ring, center_z + offset));
+        positions.push((center_x + ring, center_z + offset));
+    }
+
+    positions
+}
+
+/// Returns coordinate of position along axis of depth, X on even depths.
+fn axis_value(chunk_position: (i32, i32), depth: usize) -> i32 {
+    match depth % 2 {
+        0 => chunk_position.0,
+        _ => chunk_position.1,
+    }
+}
+
+fn arrange(chunk_positions: &mut [(i32, i32)], depth: usize) {
+    if chunk_positions.len() <= 1 {
+        return;
+    }
+
+    let middle = chunk_positions.len() / 2;
+    chunk_positions
+        .select_nth_unstable_by_key(middle, |chunk_position| axis_value(*chunk_position, depth));
+
+    let (before, after) = chunk_positions.split_at_mut(middle);
+    arrange(before, depth + 1);
+    arrange(&mut after[1..], depth + 1);
+}
+
+fn query_area(
+    chunk_positions: &[(i32, i32)],
+    depth: usize,
+    min_chunk: (i32, i32),
+    max_chunk: (i32, i32),
+    output: &mut Vec<(i32, i32)>,
+) {
+    if chunk_positions.is_empty() {
+        return;
+    }
+
+    let middle = chunk_positions.len() / 2;
+    let (chunk_x, chunk_z) = chunk_positions[middle];
+
+    if chunk_x >= min_chunk.0
+        && chunk_x <= max_chunk.0
+        && chunk_z >= min_chunk.1
+        && chunk_z <= max_chunk.1
+    {
+        output.push((chunk_x, chunk_z));
+    }
+
+    let value = axis_value((chunk_x, chunk_z), depth);
+
+    if axis_value(min_chunk, depth) <= value {
+        query_area(
+            &chunk_positions[..middle],
+            depth + 1,
+            min_chunk,
+            max_chunk,
+            output,
+        );
+    }
+
+    if axis_value(max_chunk, depth) >= value {
+        query_area(
+            &chunk_positions[middle + 1..],
+            depth + 1,
+            min_chunk,
+            max_chunk,
+            output,
+        );
+    }
+}
+
+/// Updates nearest position and its squared distance with positions of subtree.
+fn query_nearest(
+    chunk_positions: &[(i32, i32)],
+    depth: usize,
+    target: (i32, i32),
+    nearest: &mut Option<((i32, i32), i64)>,
+) {
+    if chunk_positions.is_empty() {
+        return;
+    }
+
+    let middle = chunk_positions.len() / 2;
+    let chunk_position = chunk_positions[middle];
+
+    let distance_x = chunk_position.0 as i64 - target.0 as i64;
+    let distance_z = chunk_position.1 as i64 - target.1 as i64;
+    let distance = distance_x * distance_x + distance_z * distance_z;
+
+    if nearest.is_none_or(|(_, nearest_distance)| distance < nearest_distance) {
+        *nearest = Some((chunk_position, distance));
+    }
+
+    let axis_distance = axis_value(target, depth) as i64 - axis_value(chunk_position, depth) as i64;
+
+    let (near, far) = if axis_distance < 0 {
+        (&chunk_positions[..middle], &chunk_positions[middle + 1..])
+    } else {
+        (&chunk_positions[middle + 1..], &chunk_positions[..middle])
+    };
+
+    query_nearest(near, depth + 1, target, nearest);
+
+    // Other side may only be nearer if splitting line is nearer than found position.
+    if nearest.is_none_or(|(_, nearest_distance)| axis_distance * axis_distance < nearest_distance)
+    {
+        query_nearest(far, depth + 1, target, nearest);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::spatial::{find_nearest_chunk, ChunkSpatialIndex};
+    use crate::AnvilChunkProvider;
+    use nbt::CompoundTag;
+    use tempfile::TempDir;
+
+    /// Returns pseudo random chunk positions.
+    fn chunk_positions(count: usize) -> Vec<(i32, i32)> {
+        let mut state: u64 = 42;
+        let mut next = move || {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+
+            ((state >> 33) % 400) as i32 - 200
+        };
+
+        (0..count).map(|_| (next(), next())).collect()
+    }
+
+    fn distance(first: (i32, i32), second: (i32, i32)) -> i64 {
+        let distance_x = (first.0 - second.0) as i64;
+        let distance_z = (first.1 - second.1) as i64;
+
+        distance_x * distance_x + distance_z * distance_z
+    }
+
+    #[test]
+    fn test_build() {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+        let spatial_index = ChunkSpatialIndex::build(&chunk_provider).unwrap();
+
+        assert_eq!(
+            spatial_index.len(),
+            chunk_provider.chunk_positions().unwrap().len()
+        );
+        assert_eq!(spatial_index.nearest_chunk(15, 3), Some((15, 3)));
+        assert_eq!(spatial_index.nearest_chunk_to_block(70, 40), Some((4, 2)));
+    }
+
+    #[test]
+    fn test_queries_match_scan() {
+        let chunk_positions = chunk_positions(2000);
+        let spatial_index = ChunkSpatialIndex::from_chunk_positions(chunk_positions.clone());
+
+        for (min_chunk, max_chunk) in [((-50, -20), (30, 10)), ((0, 0), (0, 0)), ((5, 5), (-5, -5))]
+        {
+            let mut expected: Vec<_> = chunk_positions
+                .iter()
+                .copied()
+                .filter(|(chunk_x, chunk_z)| {
+                    *chunk_x >= min_chunk.0
+                        && *chunk_x <= max_chunk.0
+                        && *chunk_z >= min_chunk.1
+                        && *chunk_z <= max_chunk.1
+                })
+                .collect();
+            expected.sort_unstable();
+            expected.dedup();
+
+            assert_eq!(spatial_index.chunks_in_area(min_chunk, max_chunk), expected);
+        }
+
+        for target in [(0, 0), (-300, 250), (199, -199), (1000, 1000)] {
+            let nearest = spatial_index.nearest_chunk(target.0, target.1).unwrap();
+            let expected_distance = chunk_positions
+                .iter()
+                .map(|chunk_position| distance(*chunk_position, target))
+                .min()
+                .unwrap();
+
+            assert_eq!(distance(nearest, target), expected_distance);
+        }
+    }
+
+    #[test]
+    fn test_empty() {
+        let spatial_index = ChunkSpatialIndex::from_chunk_positions(Vec::new());
+
+        assert!(spatial_index.is_empty());
+        assert!(spatial_index.nearest_chunk(0, 0).is_none());
+        assert!(spatial_index
+            .chunks_in_area((-10, -10), (10, 10))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_find_nearest_chunk() {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+        let spatial_index = ChunkSpatialIndex::build(&chunk_provider).unwrap();
+
+        for &(chunk_x, chunk_z) in &[(15, 3), (-1000, 0), (40, 40), (100, -7)] {
+            let nearest_chunk = find_nearest_chunk(&chunk_provider, chunk_x, chunk_z)
+                .unwrap()
+                .unwrap();
+            let expected_chunk = spatial_index.nearest_chunk(chunk_x, chunk_z).unwrap();
+
+            assert_eq!(
+                distance(nearest_chunk, (chunk_x, chunk_z)),
+                distance(expected_chunk, (chunk_x, chunk_z))
+            );
+        }
+    }
+
+    #[test]
+    fn test_find_nearest_chunk_across_regions() {
+        let region_dir = TempDir::new().unwrap();
+        let chunk_provider = AnvilChunkProvider::new(region_dir.path().to_str().unwrap());
+        assert_eq!(find_nearest_chunk(&chunk_provider, 0, 0).unwrap(), None);
+
+        // Chunks are found in diagonal, neighbouring and distant regions.
+        for &(chunk_x, chunk_z) in &[(63, 0), (-1, -1), (-200, 500)] {
+            chunk_provider
+                .save_chunk(chunk_x, chunk_z, CompoundTag::new())
+                .unwrap();
+        }
+
+        assert_eq!(
+            find_nearest_chunk(&chunk_provider, 1, 1).unwrap(),
+            Some((-1, -1))
+        );
+        assert_eq!(
+            find_nearest_chunk(&chunk_provider, 50, 0).unwrap(),
+            Some((63, 0))
+        );
+        assert_eq!(
+            find_nearest_chunk(&chunk_provider, -150, 450).unwrap(),
+            Some((-200, 500))
+        );
+    }
+}
@@ -0,0 +1,374 @@
+//! Statistics of region files for tooling and dashboards.
+//!
+//! Every chunk is decompressed once to measure its size and read `DataVersion`,
+//! while fragmentation is computed from region headers. Chunks which can't be read
+//! are counted instead of failing the whole report.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::stats::provider_stats;
+//! use anvil_region::AnvilChunkProvider;
+//!
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//!
+//! if let Ok(provider_stats) = provider_stats(&chunk_provider) {
+//!     println!(
+//!         "{} chunks, {} bytes compressed, {} bytes uncompressed",
+//!         provider_stats.chunk_count,
+//!         provider_stats.compressed_bytes,
+//!         provider_stats.uncompressed_bytes
+//!     );
+//! }
+//! ```
+use crate::version::chunk_data_version;
+use crate::world::{AnvilWorld, Dimension};
+use crate::{
+    region_position, AnvilChunkProvider, AnvilRegion, GZIP_COMPRESSION_TYPE,
+    UNCOMPRESSED_COMPRESSION_TYPE, ZLIB_COMPRESSION_TYPE,
+};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use nbt::decode::read_compound_tag;
+use std::collections::BTreeMap;
+use std::io::{Cursor, Read};
+use std::{fs, io};
+
+/// Amount of sectors occupied by region header.
+const HEADER_SECTORS: usize = 2;
+
+/// Sector usage of single region file.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RegionStats {
+    pub region_x: i32,
+    pub region_z: i32,
+    /// Amount of chunks stored in region.
+    pub chunk_count: usize,
+    /// Amount of sectors in file including header.
+    pub total_sectors: usize,
+    /// Amount of sectors not used by header or any chunk.
+    pub free_sectors: usize,
+}
+
+impl RegionStats {
+    /// Returns share of free sectors among sectors after header, from 0 to 1.
+    pub fn fragmentation(&self) -> f64 {
+        let data_sectors = self.total_sectors.saturating_sub(HEADER_SECTORS);
+
+        if data_sectors == 0 {
+            return 0.0;
+        }
+
+        self.free_sectors as f64 / data_sectors as f64
+    }
+}
+
+/// Statistics of chunks stored in provider folder.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProviderStats {
+    /// Amount of chunks present in region headers.
+    pub chunk_count: usize,
+    /// Sum of compressed chunk lengths.
+    pub compressed_bytes: u64,
+    /// Sum of decompressed chunk lengths.
+    pub uncompressed_bytes: u64,
+    /// Amount of chunks by compression type id.
+    pub compression_types: BTreeMap<u8, usize>,
+    /// Amount of chunks by `DataVersion`.
+    pub data_versions: BTreeMap<i32, usize>,
+    /// Chunks which were decoded but don't have `DataVersion`.
+    pub unversioned_chunks: usize,
+    /// Chunks which can't be read, decompressed or decoded.
+    pub unreadable_chunks: usize,
+    /// Region files in ascending order of position.
+    pub regions: Vec<RegionStats>,
+}
+
+impl ProviderStats {
+    /// Returns uncompressed to compressed size ratio, none if provider has no chunks.
+    pub fn compression_ratio(&self) -> Option<f64> {
+        if self.compressed_bytes == 0 {
+            return None;
+        }
+
+        Some(self.uncompressed_bytes as f64 / self.compressed_bytes as f64)
+    }
+
+    /// Returns share of free sectors among sectors after headers of all regions.
+    pub fn fragmentation(&self) -> f64 {
+        let (data_sectors, free_sectors) =
+            self.regions
+                .iter()
+                .fold((0, 0), |(data_sectors, free_sectors), region_stats| {
+                    (
+                        data_sectors + region_stats.total_sectors.saturating_sub(HEADER_SECTORS),
+                        free_sectors + region_stats.free_sectors,
+                    )
+                });
+
+        if data_sectors == 0 {
+            return 0.0;
+        }
+
+        free_sectors as f64 / data_sectors as f64
+    }
+}
+
+/// Statistics of terrain, entity and point of interest chunks of dimension.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DimensionStats {
+    pub dimension: Dimension,
+    pub terrain: ProviderStats,
+    pub entities: ProviderStats,
+    pub poi: ProviderStats,
+}
+
+/// Statistics of every dimension of world.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WorldStats {
+    /// Dimensions in the same order as returned by [`AnvilWorld::dimensions`].
+    pub dimensions: Vec<DimensionStats>,
+}
+
+impl WorldStats {
+    /// Returns amount of terrain chunks in all dimensions.
+    pub fn chunk_count(&self) -> usize {
+        self.dimensions
+            .iter()
+            .map(|dimension_stats| dimension_stats.terrain.chunk_count)
+            .sum()
+    }
+
+    /// Returns compressed size of terrain, entity and point of interest chunks.
+    pub fn compressed_bytes(&self) -> u64 {
+        self.dimensions
+            .iter()
+            .map(|dimension_stats| {
+                dimension_stats.terrain.compressed_bytes
+                    + dimension_stats.entities.compressed_bytes
+                    + dimension_stats.poi.compressed_bytes
+            })
+            .sum()
+    }
+}
+
+/// Collects statistics of every region file in provider folder.
+pub fn provider_stats(chunk_provider: &AnvilChunkProvider) -> Result<ProviderStats, io::Error> {
+    let mut provider_stats = ProviderStats::default();
+
+    if !chunk_provider.folder_path.exists() {
+        return Ok(provider_stats);
+    }
+
+    for entry in fs::read_dir(chunk_provider.folder_path)? {
+        let path = entry?.path();
+
+        let (region_x, region_z) = match path
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .and_then(region_position)
+        {
+            Some(region_position) => region_position,
+            None => continue,
+        };
+
+        let mut region = AnvilRegion::new(&path)?;
+        let chunk_positions = region.chunk_positions();
+
+        for (chunk_x, chunk_z) in &chunk_positions {
+            provider_stats.chunk_count += 1;
+
+            let (compression_scheme, compressed_buffer) =
+                match region.read_chunk_data(*chunk_x, *chunk_z) {
+                    Ok(chunk_data) => chunk_data,
+                    Err(_) => {
+                        provider_stats.unreadable_chunks += 1;
+                        continue;
+                    }
+                };
+
+            *provider_stats
+                .compression_types
+                .entry(compression_scheme)
+                .or_insert(0) += 1;
+            provider_stats.compressed_bytes += compressed_buffer.len() as u64;
+
+            let buffer = match decompress(compression_scheme, compressed_buffer) {
+                Ok(buffer) => buffer,
+                Err(_) => {
+                    provider_stats.unreadable_chunks += 1;
+                    continue;
+                }
+            };
+
+            provider_stats.uncompressed_bytes += buffer.len() as u64;
+
+            let chunk_compound_tag = match read_compound_tag(&mut Cursor::new(&buffer)) {
+                Ok(chunk_compound_tag) => chunk_compound_tag,
+                Err(_) => {
+                    provider_stats.unreadable_chunks += 1;
+                    continue;
+                }
+            };
+
+            match chunk_data_version(&chunk_compound_tag) {
+                Some(data_version) => {
+                    *provider_stats
+                        .data_versions
+                        .entry(data_version)
+                        .or_insert(0) += 1
+                }
+                None => provider_stats.unversioned_chunks += 1,
+            }
+        }
+
+        provider_stats.regions.push(RegionStats {
+            region_x,
+            region_z,
+            chunk_count: chunk_positions.len(),
+            total_sectors: region.used_sectors.len(),
+            free_sectors: region.used_sectors.count_zeros(),
+        });
+    }
+
+    provider_stats
+        .regions
+        .sort_unstable_by_key(|region_stats| (region_stats.region_x, region_stats.region_z));
+
+    Ok(provider_stats)
+}
+
+/// Collects statistics of every dimension of world.
+pub fn world_stats(world: &AnvilWorld) -> Result<WorldStats, io::Error> {
+    let mut dimensions = Vec::new();
+
+    for dimension in world.dimensions()? {
+        let world_dimension = world.dimension(&dimension);
+
+        dimensions.push(DimensionStats {
+            terrain: provider_stats(&world_dimension.chunk_provider())?,
+            entities: provider_stats(&world_dimension.entity_chunk_provider())?,
+            poi: provider_stats(&world_dimension.poi_chunk_provider())?,
+            dimension,
+        });
+    }
+
+    Ok(WorldStats { dimensions })
+}
+
+fn decompress(compression_scheme: u8, compressed_buffer: Vec<u8>) -> Result<Vec<u8>, io::Error> {
+    let mut buffer = Vec::new();
+
+    match compression_scheme {
+        GZIP_COMPRESSION_TYPE => {
+            GzDecoder::new(compressed_buffer.as_slice()).read_to_end(&mut buffer)?
+        }
+        ZLIB_COMPRESSION_TYPE => {
+            ZlibDecoder::new(compressed_buffer.as_slice()).read_to_end(&mut buffer)?
+        }
+        UNCOMPRESSED_COMPRESSION_TYPE => return Ok(compressed_buffer),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Unsupported compression scheme",
+            ))
+        }
+    };
+
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::level::LevelData;
+    use crate::relocate::copy_chunk;
+    use crate::stats::{provider_stats, world_stats};
+    use crate::world::{AnvilWorld, Dimension};
+    use crate::AnvilChunkProvider;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_provider_stats() {
+        let temp_dir = TempDir::new().unwrap();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+
+        for (chunk_x, chunk_z) in &[(4, 2), (15, 3), (40, 0)] {
+            copy_chunk(
+                &fixture_chunk_provider,
+                (4, 2),
+                &chunk_provider,
+                (*chunk_x, *chunk_z),
+            )
+            .unwrap();
+        }
+
+        chunk_provider.delete_chunk(4, 2).unwrap();
+
+        let provider_stats = provider_stats(&chunk_provider).unwrap();
+
+        assert_eq!(provider_stats.chunk_count, 2);
+        assert_eq!(provider_stats.unreadable_chunks, 0);
+        assert_eq!(provider_stats.compression_types.get(&2), Some(&2));
+        assert_eq!(provider_stats.data_versions.get(&1631), Some(&2));
+        assert!(provider_stats.compression_ratio().unwrap() > 1.0);
+
+        let region_stats = &provider_stats.regions[0];
+        assert_eq!((region_stats.region_x, region_stats.region_z), (0, 0));
+        assert_eq!(region_stats.chunk_count, 1);
+        assert!(region_stats.free_sectors > 0);
+        assert!(region_stats.fragmentation() > 0.0);
+
+        let region_stats = &provider_stats.regions[1];
+        assert_eq!((region_stats.region_x, region_stats.region_z), (1, 0));
+        assert_eq!(region_stats.fragmentation(), 0.0);
+    }
+
+    #[test]
+    fn test_world_stats() {
+        let world_dir = TempDir::new().unwrap();
+        LevelData::new().save(world_dir.path()).unwrap();
+
+        let world = AnvilWorld::open(world_dir.path()).unwrap();
+        let nether = world.nether();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        copy_chunk(
+            &fixture_chunk_provider,
+            (4, 2),
+            &nether.chunk_provider(),
+            (4, 2),
+        )
+        .unwrap();
+
+        let world_stats = world_stats(&world).unwrap();
+        let nether_stats = world_stats
+            .dimensions
+            .iter()
+            .find(|dimension_stats| dimension_stats.dimension == Dimension::Nether)
+            .unwrap();
+
+        assert_eq!(world_stats.chunk_count(), 1);
+        assert_eq!(nether_stats.terrain.chunk_count, 1);
+        assert_eq!(nether_stats.entities.chunk_count, 0);
+        assert_eq!(
+            world_stats.compressed_bytes(),
+            nether_stats.terrain.compressed_bytes
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+        let provider_stats = provider_stats(&chunk_provider).unwrap();
+
+        let json = serde_json::to_string(&provider_stats).unwrap();
+        let deserialized_provider_stats: crate::stats::ProviderStats =
+            serde_json::from_str(&json).unwrap();
+
+        assert_eq!(deserialized_provider_stats, provider_stats);
+    }
+}
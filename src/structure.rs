@@ -0,0 +1,549 @@
+//! Structure starts and references of terrain chunks.
+//!
+//! Chunk where structure begins stores its start with bounding box and pieces. Every
+//! chunk structure intersects stores reference to the chunk with start, so references
+//! point to chunks which may be moved or deleted separately from referencing chunk.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::chunk::Chunk;
+//! use anvil_region::AnvilChunkProvider;
+//!
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//! let mut chunk = Chunk::new(chunk_provider.load_chunk(4, 2).unwrap());
+//!
+//! for structure_reference in chunk.structure_references() {
+//!     println!("{} {:?}", structure_reference.name, structure_reference.chunk_positions);
+//! }
+//!
+//! // Forget about structures which start outside of this chunk.
+//! chunk.retain_structure_references(|_, chunk_position| chunk_position == (4, 2));
+//! ```
+use crate::chunk::{Chunk, ChunkTag};
+use crate::packed::unpack_chunk_position;
+use crate::relocate::{offset_bounding_box, offset_int, offset_packed_chunk_position};
+use crate::tag::{compound_tags, compound_tags_mut, remove_tag};
+use crate::{AnvilChunkProvider, ChunkLoadError, ChunkSaveError};
+use nbt::{CompoundTag, Tag};
+use std::collections::{HashMap, HashSet};
+use std::io;
+
+/// Id of structure start which marks that structure is absent before 1.18.
+const INVALID_STRUCTURE_ID: &str = "INVALID";
+
+/// Possible errors while updating structure references of provider.
+#[derive(Debug)]
+pub enum StructureError {
+    /// Chunk can't be loaded.
+    ChunkLoadError { chunk_load_error: ChunkLoadError },
+    /// Chunk can't be saved.
+    ChunkSaveError { chunk_save_error: ChunkSaveError },
+    /// Region folder can't be read.
+    ReadError { io_error: io::Error },
+}
+
+impl From<ChunkLoadError> for StructureError {
+    fn from(chunk_load_error: ChunkLoadError) -> Self {
+        StructureError::ChunkLoadError { chunk_load_error }
+    }
+}
+
+impl From<ChunkSaveError> for StructureError {
+    fn from(chunk_save_error: ChunkSaveError) -> Self {
+        StructureError::ChunkSaveError { chunk_save_error }
+    }
+}
+
+impl From<io::Error> for StructureError {
+    fn from(io_error: io::Error) -> Self {
+        StructureError::ReadError { io_error }
+    }
+}
+
+/// Block bounds of structure or its piece, both inclusive.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StructureBoundingBox {
+    pub min: (i32, i32, i32),
+    pub max: (i32, i32, i32),
+}
+
+impl StructureBoundingBox {
+    fn from_compound_tag(compound_tag: &CompoundTag) -> Option<Self> {
+        match compound_tag.get_i32_vec("BB").ok()?.as_slice() {
+            [min_x, min_y, min_z, max_x, max_y, max_z] => Some(StructureBoundingBox {
+                min: (*min_x, *min_y, *min_z),
+                max: (*max_x, *max_y, *max_z),
+            }),
+            _ => None,
+        }
+    }
+
+    fn union(self, other: StructureBoundingBox) -> Self {
+        StructureBoundingBox {
+            min: (
+                self.min.0.min(other.min.0),
+                self.min.1.min(other.min.1),
+                self.min.2.min(other.min.2),
+            ),
+            max: (
+                self.max.0.max(other.max.0),
+                self.max.1.max(other.max.1),
+                self.max.2.max(other.max.2),
+            ),
+        }
+    }
+
+    /// Returns true if bounding box has blocks in chunk column.
+    pub fn intersects_chunk(&self, chunk_x: i32, chunk_z: i32) -> bool {
+        let (min_x, min_z) = (chunk_x * 16, chunk_z * 16);
+        let (max_x, max_z) = (min_x + 15, min_z + 15);
+
+        self.min.0 <= max_x && self.max.0 >= min_x && self.min.2 <= max_z && self.max.2 >= min_z
+    }
+}
+
+/// Structure which starts in chunk.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct StructureStart {
+    /// Structure name like `minecraft:village` or `Village` before 1.18.
+    pub name: String,
+    /// Chunk where structure starts.
+    pub chunk_position: (i32, i32),
+    /// Bounds of structure, taken from start or merged from its pieces.
+    pub bounding_box: Option<StructureBoundingBox>,
+}
+
+/// Structure which intersects chunk with positions of chunks containing its start.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct StructureReference {
+    pub name: String,
+    pub chunk_positions: Vec<(i32, i32)>,
+}
+
+impl Chunk {
+    /// Returns structures which start in chunk.
+    pub fn structure_starts(&self) -> Vec<StructureStart> {
+        let starts = match self.get(ChunkTag::StructureStarts) {
+            Some(Tag::Compound(starts)) => starts,
+            _ => return Vec::new(),
+        };
+
+        let chunk_position = self.position().unwrap_or((0, 0));
+        let mut structure_starts = Vec::new();
+
+        for (name, start) in starts.iter() {
+            let start = match start {
+                Tag::Compound(start) => start,
+                _ => continue,
+            };
+
+            if start.get_str("id").ok() == Some(INVALID_STRUCTURE_ID) {
+                continue;
+            }
+
+            let bounding_box = StructureBoundingBox::from_compound_tag(start).or_else(|| {
+                compound_tags(start, "Children")
+                    .filter_map(StructureBoundingBox::from_compound_tag)
+                    .reduce(StructureBoundingBox::union)
+            });
+
+            structure_starts.push(StructureStart {
+                name: name.clone(),
+                chunk_position: (
+                    start.get_i32("ChunkX").unwrap_or(chunk_position.0),
+                    start.get_i32("ChunkZ").unwrap_or(chunk_position.1),
+                ),
+                bounding_box,
+            });
+        }
+
+        structure_starts
+    }
+
+    /// Returns structures which intersect chunk and chunks where they start.
+    pub fn structure_references(&self) -> Vec<StructureReference> {
+        let references = match self.get(ChunkTag::StructureReferences) {
+            Some(Tag::Compound(references)) => references,
+            _ => return Vec::new(),
+        };
+
+        references
+            .iter()
+            .filter_map(|(name, reference)| match reference {
+                Tag::LongArray(chunk_positions) if !chunk_positions.is_empty() => {
+                    Some(StructureReference {
+                        name: name.clone(),
+                        chunk_positions: chunk_positions
+                            .iter()
+                            .map(|chunk_position| unpack_chunk_position(*chunk_position))
+                            .collect(),
+                    })
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns sorted names of structures which start in or reference chunk.
+    pub fn intersecting_structures(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .structure_starts()
+            .into_iter()
+            .map(|structure_start| structure_start.name)
+            .chain(
+                self.structure_references()
+                    .into_iter()
+                    .map(|structure_reference| structure_reference.name),
+            )
+            .collect();
+
+        names.sort();
+        names.dedup();
+
+        names
+    }
+
+    /// Removes start and references of structure, returns false if chunk has neither.
+    pub fn remove_structure(&mut self, name: &str) -> bool {
+        let mut removed = false;
+
+        for chunk_tag in &[ChunkTag::StructureStarts, ChunkTag::StructureReferences] {
+            if let Some(Tag::Compound(compound_tag)) = self.get_mut(*chunk_tag) {
+                removed |= remove_tag(compound_tag, name).is_some();
+            }
+        }
+
+        removed
+    }
+
+    /// Keeps only references for which predicate returns true, returns amount of removed.
+    ///
+    /// Structures left without references are removed from references tag.
+    pub fn retain_structure_references(
+        &mut self,
+        mut predicate: impl FnMut(&str, (i32, i32)) -> bool,
+    ) -> usize {
+        let references = match self.get_mut(ChunkTag::StructureReferences) {
+            Some(Tag::Compound(references)) => references,
+            _ => return 0,
+        };
+
+        let mut removed_references = 0;
+        let mut emptied_names = Vec::new();
+
+        for (name, reference) in references.iter_mut() {
+            if let Tag::LongArray(chunk_positions) = reference {
+                let length = chunk_positions.len();
+
+                chunk_positions.retain(|chunk_position| {
+                    predicate(name, unpack_chunk_position(*chunk_position))
+                });
+
+                removed_references += length - chunk_positions.len();
+
+                if chunk_positions.is_empty() && length > 0 {
+                    emptied_names.push(name.clone());
+                }
+            }
+        }
+
+        for name in emptied_names {
+            remove_tag(references, &name);
+        }
+
+        removed_references
+    }
+
+    /// Shifts structure starts and references by offset in chunks.
+    pub fn offset_structures(&mut self, offset_x: i32, offset_z: i32) {
+        if let Some(Tag::Compound(references)) = self.get_mut(ChunkTag::StructureReferences) {
+            for (_, reference) in references.iter_mut() {
+                if let Tag::LongArray(chunk_positions) = reference {
+                    for chunk_position in chunk_positions.iter_mut() {
+                        *chunk_position =
+                            offset_packed_chunk_position(*chunk_position, offset_x, offset_z);
+                    }
+                }
+            }
+        }
+
+        if let Some(Tag::Compound(starts)) = self.get_mut(ChunkTag::StructureStarts) {
+            for (_, start) in starts.iter_mut() {
+                if let Tag::Compound(start) = start {
+                    offset_structure_start(start, offset_x, offset_z);
+                }
+            }
+        }
+    }
+}
+
+fn offset_structure_start(start: &mut CompoundTag, offset_x: i32, offset_z: i32) {
+    let block_offset_x = offset_x * 16;
+    let block_offset_z = offset_z * 16;
+
+    offset_int(start, "ChunkX", offset_x);
+    offset_int(start, "ChunkZ", offset_z);
+    offset_bounding_box(start, block_offset_x, block_offset_z);
+
+    for child in compound_tags_mut(start, "Children") {
+        offset_bounding_box(child, block_offset_x, block_offset_z);
+        offset_int(child, "PosX", block_offset_x);
+        offset_int(child, "PosZ", block_offset_z);
+    }
+}
+
+/// Returns structures which bounding box intersects chunk, resolving its references.
+///
+/// Referenced chunks which are missing or don't contain start are skipped.
+pub fn structures_intersecting_chunk(
+    chunk_provider: &AnvilChunkProvider,
+    chunk_x: i32,
+    chunk_z: i32,
+) -> Result<Vec<StructureStart>, ChunkLoadError> {
+    let chunk = Chunk::new(chunk_provider.load_chunk(chunk_x, chunk_z)?);
+    let mut structure_starts = Vec::new();
+
+    let mut add = |structure_start: StructureStart| {
+        let intersects = structure_start
+            .bounding_box
+            .is_none_or(|bounding_box| bounding_box.intersects_chunk(chunk_x, chunk_z));
+
+        if intersects && !structure_starts.contains(&structure_start) {
+            structure_starts.push(structure_start);
+        }
+    };
+
+    for structure_start in chunk.structure_starts() {
+        add(structure_start);
+    }
+
+    for structure_reference in chunk.structure_references() {
+        for (start_chunk_x, start_chunk_z) in &structure_reference.chunk_positions {
+            let start_chunk = match chunk_provider.load_chunk(*start_chunk_x, *start_chunk_z) {
+                Ok(start_chunk_compound_tag) => Chunk::new(start_chunk_compound_tag),
+                Err(ChunkLoadError::RegionNotFound { .. })
+                | Err(ChunkLoadError::ChunkNotFound { .. }) => continue,
+                Err(chunk_load_error) => return Err(chunk_load_error),
+            };
+
+            start_chunk
+                .structure_starts()
+                .into_iter()
+                .filter(|structure_start| structure_start.name == structure_reference.name)
+                .for_each(&mut add);
+        }
+    }
+
+    Ok(structure_starts)
+}
+
+/// Removes references to chunks which are missing or no longer contain structure start.
+///
+/// Should be run after chunks were deleted or moved separately from their neighbours.
+/// Returns amount of removed references.
+pub fn strip_dangling_structure_references(
+    chunk_provider: &AnvilChunkProvider,
+) -> Result<usize, StructureError> {
+    let chunk_positions = chunk_provider.chunk_positions()?;
+    let mut start_names: HashMap<(i32, i32), HashSet<String>> = HashMap::new();
+
+    for (chunk_x, chunk_z) in &chunk_positions {
+        let chunk = Chunk::new(chunk_provider.load_chunk(*chunk_x, *chunk_z)?);
+        let names = chunk
+            .structure_starts()
+            .into_iter()
+            .map(|structure_start| structure_start.name)
+            .collect();
+
+        start_names.insert((*chunk_x, *chunk_z), names);
+    }
+
+    let mut removed_references = 0;
+
+    for (chunk_x, chunk_z) in chunk_positions {
+        let mut chunk = Chunk::new(chunk_provider.load_chunk(chunk_x, chunk_z)?);
+
+        let removed = chunk.retain_structure_references(|name, chunk_position| {
+            start_names
+                .get(&chunk_position)
+                .is_some_and(|names| names.contains(name))
+        });
+
+        if removed > 0 {
+            chunk_provider.save_chunk(chunk_x, chunk_z, chunk.into_compound_tag())?;
+            removed_references += removed;
+        }
+    }
+
+    Ok(removed_references)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chunk::Chunk;
+    use crate::snbt;
+    use crate::structure::{
+        strip_dangling_structure_references, structures_intersecting_chunk, StructureBoundingBox,
+        StructureReference,
+    };
+    use crate::AnvilChunkProvider;
+    use tempfile::TempDir;
+
+    fn chunk(chunk_x: i32, chunk_z: i32, structures: &str) -> Chunk {
+        let snbt = format!(
+            "{{DataVersion: 2860, xPos: {}, zPos: {}, structures: {}}}",
+            chunk_x, chunk_z, structures
+        );
+
+        Chunk::new(snbt::from_str(&snbt).unwrap())
+    }
+
+    fn village_chunk() -> Chunk {
+        chunk(
+            4,
+            2,
+            r#"{
+                starts: {
+                    "minecraft:village": {
+                        id: "minecraft:village",
+                        ChunkX: 4,
+                        ChunkZ: 2,
+                        Children: [{BB: [I; 64, 60, 32, 70, 70, 40]}, {BB: [I; 60, 62, 36, 90, 66, 44]}]
+                    }
+                },
+                References: {"minecraft:village": [L; 8589934596L], "minecraft:mineshaft": [L; 3L]}
+            }"#,
+        )
+    }
+
+    #[test]
+    fn test_structure_starts_and_references() {
+        let chunk = village_chunk();
+        let structure_starts = chunk.structure_starts();
+
+        assert_eq!(structure_starts.len(), 1);
+        assert_eq!(structure_starts[0].name, "minecraft:village");
+        assert_eq!(structure_starts[0].chunk_position, (4, 2));
+        assert_eq!(
+            structure_starts[0].bounding_box,
+            Some(StructureBoundingBox {
+                min: (60, 60, 32),
+                max: (90, 70, 44),
+            })
+        );
+        assert_eq!(
+            chunk.structure_references(),
+            vec![
+                StructureReference {
+                    name: "minecraft:village".to_owned(),
+                    chunk_positions: vec![(4, 2)],
+                },
+                StructureReference {
+                    name: "minecraft:mineshaft".to_owned(),
+                    chunk_positions: vec![(3, 0)],
+                },
+            ]
+        );
+        assert_eq!(
+            chunk.intersecting_structures(),
+            vec!["minecraft:mineshaft", "minecraft:village"]
+        );
+    }
+
+    #[test]
+    fn test_legacy_invalid_start_is_skipped() {
+        let chunk_compound_tag = snbt::from_str(
+            r#"{
+                DataVersion: 1631,
+                Level: {
+                    xPos: 0,
+                    zPos: 0,
+                    Structures: {Starts: {Village: {id: "INVALID"}, Mineshaft: {id: "Mineshaft", ChunkX: 0, ChunkZ: 0, BB: [I; 0, 10, 0, 20, 30, 20]}}}
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let structure_starts = Chunk::new(chunk_compound_tag).structure_starts();
+
+        assert_eq!(structure_starts.len(), 1);
+        assert_eq!(structure_starts[0].name, "Mineshaft");
+        assert!(structure_starts[0]
+            .bounding_box
+            .unwrap()
+            .intersects_chunk(1, 1));
+        assert!(!structure_starts[0]
+            .bounding_box
+            .unwrap()
+            .intersects_chunk(2, 0));
+    }
+
+    #[test]
+    fn test_remove_and_retain_structures() {
+        let mut chunk = village_chunk();
+
+        assert_eq!(
+            chunk.retain_structure_references(|name, _| name == "minecraft:village"),
+            1
+        );
+        assert_eq!(chunk.intersecting_structures(), vec!["minecraft:village"]);
+
+        assert!(chunk.remove_structure("minecraft:village"));
+        assert!(!chunk.remove_structure("minecraft:village"));
+        assert!(chunk.intersecting_structures().is_empty());
+    }
+
+    #[test]
+    fn test_offset_structures() {
+        let mut chunk = village_chunk();
+        chunk.offset_structures(-4, 1);
+
+        let structure_start = &chunk.structure_starts()[0];
+
+        assert_eq!(structure_start.chunk_position, (0, 3));
+        assert_eq!(
+            structure_start.bounding_box.unwrap().min,
+            (60 - 64, 60, 32 + 16)
+        );
+        assert_eq!(
+            chunk.structure_references()[0].chunk_positions,
+            vec![(0, 3)]
+        );
+    }
+
+    #[test]
+    fn test_provider_structure_references() {
+        let temp_dir = TempDir::new().unwrap();
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+
+        let neighbour = chunk(
+            5,
+            2,
+            r#"{starts: {}, References: {"minecraft:village": [L; 8589934596L], "minecraft:mineshaft": [L; 3L]}}"#,
+        );
+        chunk_provider
+            .save_chunk(4, 2, village_chunk().into_compound_tag())
+            .unwrap();
+        chunk_provider
+            .save_chunk(5, 2, neighbour.into_compound_tag())
+            .unwrap();
+
+        let structure_starts = structures_intersecting_chunk(&chunk_provider, 5, 2).unwrap();
+        assert_eq!(structure_starts.len(), 1);
+        assert_eq!(structure_starts[0].chunk_position, (4, 2));
+
+        // Mineshaft start chunk 3, 0 doesn't exist.
+        assert_eq!(
+            strip_dangling_structure_references(&chunk_provider).unwrap(),
+            2
+        );
+
+        let neighbour = Chunk::new(chunk_provider.load_chunk(5, 2).unwrap());
+        assert_eq!(
+            neighbour.intersecting_structures(),
+            vec!["minecraft:village"]
+        );
+    }
+}
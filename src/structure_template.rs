@@ -0,0 +1,874 @@
+//! Structure files saved by structure blocks.
+//!
+//! Structure file is gzip compressed NBT with size, palette of block states, list of
+//! blocks with palette index and block entity data, and list of entities, all with
+//! positions relative to the minimum corner. Structure blocks save them into
+//! `generated/<namespace>/structures` folder of world, datapacks ship them in
+//! `data/<namespace>/structure`. Unlike schematics structure files keep entities and
+//! leave out positions of structure void, which keep world blocks when placed.
+//!
+//! Since 1.17 entities are stored in separate entity chunks, [`capture_structure`] and
+//! [`place_structure`] only handle entities of terrain chunks before 1.17. Entities of
+//! newer worlds are handled with [`capture_entities`] and [`place_entities`] on provider
+//! of `entities` folder.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::schematic::BlockSelection;
+//! use anvil_region::structure_template::{capture_structure, place_structure};
+//! use anvil_region::AnvilChunkProvider;
+//!
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//! let selection = BlockSelection::new((64, 0, 32), (79, 15, 47));
+//!
+//! let structure_template = capture_structure(&chunk_provider, selection).unwrap();
+//! assert_eq!(structure_template.size, (16, 16, 16));
+//!
+//! # let region_dir = tempfile::TempDir::new().unwrap();
+//! # let chunk_provider = AnvilChunkProvider::new(region_dir.path().to_str().unwrap());
+//! let report = place_structure(&chunk_provider, &structure_template, (0, 0, 0)).unwrap();
+//! assert_eq!(report.created_chunks, vec![(0, 0)]);
+//! ```
+use crate::chunk::Chunk;
+use crate::data::{read_data_file, write_data_file, DataFileError};
+use crate::schematic::{
+    block_entity_position, copy_without_tags, load_or_create_chunk, BlockSelection,
+    ChunkBlockWriter, PasteReport,
+};
+use crate::section::{block_index, BlockState, SectionError};
+use crate::version::RELEASES;
+use crate::{AnvilChunkProvider, ChunkLoadError, ChunkSaveError};
+use nbt::{CompoundTag, CompoundTagError, Tag};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+
+/// Block which marks positions left out of structure.
+const STRUCTURE_VOID: &str = "minecraft:structure_void";
+
+/// Possible errors while capturing, reading or placing structure.
+#[derive(Debug)]
+pub enum StructureTemplateError {
+    /// Chunk can't be loaded.
+    ChunkLoadError { chunk_load_error: ChunkLoadError },
+    /// Chunk can't be saved.
+    ChunkSaveError { chunk_save_error: ChunkSaveError },
+    /// Chunk sections can't be read.
+    SectionError { section_error: SectionError },
+    /// Structure file can't be read or written.
+    DataFileError { data_file_error: DataFileError },
+    /// Required structure tag is missing or has wrong type.
+    InvalidTag { name: String },
+}
+
+impl From<ChunkLoadError> for StructureTemplateError {
+    fn from(chunk_load_error: ChunkLoadError) -> Self {
+        StructureTemplateError::ChunkLoadError { chunk_load_error }
+    }
+}
+
+impl From<ChunkSaveError> for StructureTemplateError {
+    fn from(chunk_save_error: ChunkSaveError) -> Self {
+        StructureTemplateError::ChunkSaveError { chunk_save_error }
+    }
+}
+
+impl From<SectionError> for StructureTemplateError {
+    fn from(section_error: SectionError) -> Self {
+        StructureTemplateError::SectionError { section_error }
+    }
+}
+
+impl From<DataFileError> for StructureTemplateError {
+    fn from(data_file_error: DataFileError) -> Self {
+        StructureTemplateError::DataFileError { data_file_error }
+    }
+}
+
+impl From<CompoundTagError<'_>> for StructureTemplateError {
+    fn from(compound_tag_error: CompoundTagError) -> Self {
+        let name = match compound_tag_error {
+            CompoundTagError::TagNotFound { name } => name,
+            CompoundTagError::TagWrongType { name, .. } => name,
+        };
+
+        StructureTemplateError::InvalidTag {
+            name: name.to_owned(),
+        }
+    }
+}
+
+/// Block of structure.
+#[derive(Debug, Clone)]
+pub struct StructureBlock {
+    /// Position relative to the minimum corner of structure.
+    pub position: (i32, i32, i32),
+    /// Index of block state in palette.
+    pub state: usize,
+    /// Block entity tags with `id`, without position.
+    pub block_entity: Option<CompoundTag>,
+}
+
+/// Entity of structure.
+#[derive(Debug, Clone)]
+pub struct StructureEntity {
+    /// Exact position relative to the minimum corner of structure.
+    pub position: (f64, f64, f64),
+    /// Position of block entity is inside of relative to the minimum corner.
+    pub block_position: (i32, i32, i32),
+    /// Entity tags without `UUID`, so placed copies get new ones.
+    pub entity: CompoundTag,
+}
+
+/// Blocks and entities of structure file.
+#[derive(Debug, Clone)]
+pub struct StructureTemplate {
+    pub data_version: i32,
+    /// Amount of blocks along X, Y and Z.
+    pub size: (u32, u32, u32),
+    pub palette: Vec<BlockState>,
+    /// Blocks in placement order, structure void positions are absent.
+    pub blocks: Vec<StructureBlock>,
+    pub entities: Vec<StructureEntity>,
+}
+
+impl StructureTemplate {
+    /// Reads structure from file, usually with `.nbt` extension.
+    pub fn load(path: &Path) -> Result<Self, StructureTemplateError> {
+        let compound_tag = read_data_file(path)?;
+
+        StructureTemplate::from_compound_tag(&compound_tag)
+    }
+
+    /// Writes structure as file.
+    pub fn save(&self, path: &Path) -> Result<(), StructureTemplateError> {
+        write_data_file(path, &self.to_compound_tag())?;
+
+        Ok(())
+    }
+
+    /// Decodes structure compound tag.
+    ///
+    /// Structures with several palettes, like shipwrecks, are read with the first one.
+    pub fn from_compound_tag(compound_tag: &CompoundTag) -> Result<Self, StructureTemplateError> {
+        let size = match int_list(compound_tag, "size")?.as_slice() {
+            [width, height, length] => (*width as u32, *height as u32, *length as u32),
+            _ => return Err(invalid_tag("size")),
+        };
+
+        let palette_tags = match compound_tag.get::<&Vec<Tag>>("palette") {
+            Ok(palette_tags) => palette_tags,
+            Err(_) => match compound_tag.get::<&Vec<Tag>>("palettes")?.first() {
+                Some(Tag::List(palette_tags)) => palette_tags,
+                _ => return Err(invalid_tag("palettes")),
+            },
+        };
+
+        let mut palette = Vec::with_capacity(palette_tags.len());
+
+        for tag in palette_tags {
+            match tag {
+                Tag::Compound(block_state) => {
+                    palette.push(BlockState::from_compound_tag(block_state)?)
+                }
+                _ => return Err(invalid_tag("palette")),
+            }
+        }
+
+        let mut blocks = Vec::new();
+
+        for block in compound_tag.get_compound_tag_vec("blocks")? {
+            let state = block.get_i32("state")?;
+
+            if state < 0 || state as usize >= palette.len() {
+                return Err(invalid_tag("state"));
+            }
+
+            blocks.push(StructureBlock {
+                position: int_position(block, "pos")?,
+                state: state as usize,
+                block_entity: block.get_compound_tag("nbt").ok().cloned(),
+            });
+        }
+
+        let mut entities = Vec::new();
+
+        for entity in compound_tag
+            .get_compound_tag_vec("entities")
+            .unwrap_or_default()
+        {
+            let position = match entity.get_f64_vec("pos")?.as_slice() {
+                [x, y, z] => (*x, *y, *z),
+                _ => return Err(invalid_tag("pos")),
+            };
+
+            entities.push(StructureEntity {
+                position,
+                block_position: int_position(entity, "blockPos")?,
+                entity: entity.get_compound_tag("nbt")?.clone(),
+            });
+        }
+
+        Ok(StructureTemplate {
+            data_version: compound_tag.get_i32("DataVersion")?,
+            size,
+            palette,
+            blocks,
+            entities,
+        })
+    }
+
+    /// Encodes structure into compound tag with single palette.
+    pub fn to_compound_tag(&self) -> CompoundTag {
+        let (width, height, length) = self.size;
+
+        let blocks: Vec<_> = self
+            .blocks
+            .iter()
+            .map(|block| {
+                let mut block_compound_tag = CompoundTag::new();
+                block_compound_tag.insert_i32("state", block.state as i32);
+                block_compound_tag.insert("pos", int_list_tag(block.position));
+
+                if let Some(block_entity) = &block.block_entity {
+                    block_compound_tag.insert_compound_tag("nbt", block_entity.clone());
+                }
+
+                block_compound_tag
+            })
+            .collect();
+
+        let entities: Vec<_> = self
+            .entities
+            .iter()
+            .map(|entity| {
+                let (x, y, z) = entity.position;
+
+                let mut entity_compound_tag = CompoundTag::new();
+                entity_compound_tag.insert_f64_vec("pos", vec![x, y, z]);
+                entity_compound_tag.insert("blockPos", int_list_tag(entity.block_position));
+                entity_compound_tag.insert_compound_tag("nbt", entity.entity.clone());
+
+                entity_compound_tag
+            })
+            .collect();
+
+        let mut compound_tag = CompoundTag::new();
+        compound_tag.insert_i32("DataVersion", self.data_version);
+        compound_tag.insert(
+            "size",
+            int_list_tag((width as i32, height as i32, length as i32)),
+        );
+        compound_tag.insert_compound_tag_vec(
+            "palette",
+            self.palette.iter().map(BlockState::to_compound_tag),
+        );
+        compound_tag.insert_compound_tag_vec("blocks", blocks);
+        compound_tag.insert_compound_tag_vec("entities", entities);
+
+        compound_tag
+    }
+}
+
+/// Captures blocks, block entities and entities of selection into structure.
+///
+/// Missing chunks and sections are captured as air, structure void is left out.
+/// Entities are captured from terrain chunks which store them, that is before 1.17.
+/// Structure gets data version of the first captured chunk.
+pub fn capture_structure(
+    chunk_provider: &AnvilChunkProvider,
+    selection: BlockSelection,
+) -> Result<StructureTemplate, StructureTemplateError> {
+    let (min_x, min_y, min_z) = selection.min;
+    let (max_x, max_y, max_z) = selection.max;
+
+    let mut palette = Vec::new();
+    let mut palette_indices = HashMap::new();
+    let mut palette_index = |block_state: &BlockState| {
+        *palette_indices
+            .entry(block_state.clone())
+            .or_insert_with(|| {
+                palette.push(block_state.clone());
+                palette.len() - 1
+            })
+    };
+
+    let air_index = palette_index(&BlockState::new("minecraft:air"));
+
+    // Blocks keyed by Y, Z and X, so they are placed bottom up.
+    let mut blocks = BTreeMap::new();
+    let mut block_entities = HashMap::new();
+    let mut entities = Vec::new();
+    let mut data_version = None;
+
+    for chunk_x in min_x >> 4..=max_x >> 4 {
+        for chunk_z in min_z >> 4..=max_z >> 4 {
+            let chunk = match chunk_provider.load_chunk(chunk_x, chunk_z) {
+                Ok(chunk_compound_tag) => Chunk::new(chunk_compound_tag),
+                Err(ChunkLoadError::RegionNotFound { .. })
+                | Err(ChunkLoadError::ChunkNotFound { .. }) => continue,
+                Err(chunk_load_error) => return Err(chunk_load_error.into()),
+            };
+
+            data_version = data_version.or_else(|| chunk.data_version());
+
+            for section in chunk.read_sections()? {
+                let block_states = match &section.block_states {
+                    Some(block_states) => block_states,
+                    None => continue,
+                };
+
+                let section_min_y = section.y as i32 * 16;
+
+                if section_min_y > max_y || section_min_y + 15 < min_y {
+                    continue;
+                }
+
+                for y in section_min_y.max(min_y)..=(section_min_y + 15).min(max_y) {
+                    for z in (chunk_z * 16).max(min_z)..=(chunk_z * 16 + 15).min(max_z) {
+                        for x in (chunk_x * 16).max(min_x)..=(chunk_x * 16 + 15).min(max_x) {
+                            let block_state = block_states
+                                .get(block_index(x as usize, y as usize, z as usize))
+                                .expect("Index is inside of section");
+
+                            blocks.insert((y, z, x), palette_index(block_state));
+                        }
+                    }
+                }
+            }
+
+            for block_entity in chunk.block_entities() {
+                let position = block_entity_position(block_entity);
+
+                if selection.contains(position) {
+                    block_entities.insert(
+                        position,
+                        copy_without_tags(block_entity, &["x", "y", "z", "keepPacked"]),
+                    );
+                }
+            }
+
+            entities.extend(chunk_entities(&chunk, selection));
+        }
+    }
+
+    let structure_void_index = palette_indices
+        .get(&BlockState::new(STRUCTURE_VOID))
+        .copied();
+
+    let mut structure_blocks = Vec::new();
+
+    for y in min_y..=max_y {
+        for z in min_z..=max_z {
+            for x in min_x..=max_x {
+                let state = blocks.get(&(y, z, x)).copied().unwrap_or(air_index);
+
+                if Some(state) == structure_void_index {
+                    continue;
+                }
+
+                structure_blocks.push(StructureBlock {
+                    position: (x - min_x, y - min_y, z - min_z),
+                    state,
+                    block_entity: block_entities.remove(&(x, y, z)),
+                });
+            }
+        }
+    }
+
+    Ok(StructureTemplate {
+        data_version: data_version.unwrap_or(RELEASES[RELEASES.len() - 1].data_version),
+        size: selection.size(),
+        palette,
+        blocks: structure_blocks,
+        entities,
+    })
+}
+
+/// Captures entities of selection from provider of entity chunks.
+pub fn capture_entities(
+    entity_chunk_provider: &AnvilChunkProvider,
+    selection: BlockSelection,
+) -> Result<Vec<StructureEntity>, StructureTemplateError> {
+    let mut entities = Vec::new();
+
+    for chunk_x in selection.min.0 >> 4..=selection.max.0 >> 4 {
+        for chunk_z in selection.min.2 >> 4..=selection.max.2 >> 4 {
+            match entity_chunk_provider.load_chunk(chunk_x, chunk_z) {
+                Ok(entity_chunk) => {
+                    entities.extend(chunk_entities(&Chunk::new(entity_chunk), selection))
+                }
+                Err(ChunkLoadError::RegionNotFound { .. })
+                | Err(ChunkLoadError::ChunkNotFound { .. }) => continue,
+                Err(chunk_load_error) => return Err(chunk_load_error.into()),
+            }
+        }
+    }
+
+    Ok(entities)
+}
+
+/// Places structure with its minimum corner at world position.
+///
+/// Missing chunks are created in format of structure data version. Block entities of
+/// replaced blocks are removed. Entities are placed into terrain chunks which store
+/// them, that is before 1.17.
+pub fn place_structure(
+    chunk_provider: &AnvilChunkProvider,
+    structure_template: &StructureTemplate,
+    (position_x, position_y, position_z): (i32, i32, i32),
+) -> Result<PasteReport, StructureTemplateError> {
+    let mut chunk_blocks: BTreeMap<(i32, i32), Vec<&StructureBlock>> = BTreeMap::new();
+
+    for block in &structure_template.blocks {
+        let (x, _, z) = block.position;
+
+        chunk_blocks
+            .entry(((x + position_x) >> 4, (z + position_z) >> 4))
+            .or_default()
+            .push(block);
+    }
+
+    let mut chunk_entities: BTreeMap<(i32, i32), Vec<CompoundTag>> = BTreeMap::new();
+
+    for entity in &structure_template.entities {
+        let entity = world_entity(entity, (position_x, position_y, position_z));
+        let (x, _, z) = entity_position(&entity).expect("Position is set");
+
+        chunk_entities
+            .entry(((x.floor() as i32) >> 4, (z.floor() as i32) >> 4))
+            .or_default()
+            .push(entity);
+    }
+
+    let mut report = PasteReport::default();
+
+    for (chunk_x, chunk_z) in chunk_blocks
+        .keys()
+        .chain(chunk_entities.keys())
+        .copied()
+        .collect::<BTreeSet<_>>()
+    {
+        let (mut chunk, created) = load_or_create_chunk(
+            chunk_provider,
+            chunk_x,
+            chunk_z,
+            structure_template.data_version,
+        )?;
+
+        let mut block_writer = ChunkBlockWriter::new(&chunk)?;
+        let mut block_entities = Vec::new();
+
+        for block in chunk_blocks.remove(&(chunk_x, chunk_z)).unwrap_or_default() {
+            let (x, y, z) = block.position;
+            let position = (x + position_x, y + position_y, z + position_z);
+
+            block_writer.set_block_state(position, &structure_template.palette[block.state]);
+
+            if let Some(block_entity) = &block.block_entity {
+                if block_writer.height_range.contains(position.1) {
+                    let mut block_entity = block_entity.clone();
+                    block_entity.insert_i32("x", position.0);
+                    block_entity.insert_i32("y", position.1);
+                    block_entity.insert_i32("z", position.2);
+
+                    block_entities.push(block_entity);
+                }
+            }
+        }
+
+        report.pasted_blocks += block_writer.set_positions.len();
+        report.clipped_blocks += block_writer.clipped_blocks;
+
+        let mut changed = block_writer.write(&mut chunk, block_entities)?;
+
+        if chunk.has_level_wrapper() {
+            let entities = chunk_entities
+                .remove(&(chunk_x, chunk_z))
+                .unwrap_or_default();
+
+            if !entities.is_empty() {
+                append_entities(chunk.data_mut(), entities);
+                changed = true;
+            }
+        }
+
+        if !changed {
+            continue;
+        }
+
+        chunk_provider.save_chunk(chunk_x, chunk_z, chunk.into_compound_tag())?;
+
+        if created {
+            report.created_chunks.push((chunk_x, chunk_z));
+        } else {
+            report.modified_chunks.push((chunk_x, chunk_z));
+        }
+    }
+
+    Ok(report)
+}
+
+/// Places entities of structure into entity chunks, creating missing ones.
+///
+/// Returns amount of placed entities.
+pub fn place_entities(
+    entity_chunk_provider: &AnvilChunkProvider,
+    structure_template: &StructureTemplate,
+    position: (i32, i32, i32),
+) -> Result<usize, StructureTemplateError> {
+    let mut chunk_entities: BTreeMap<(i32, i32), Vec<CompoundTag>> = BTreeMap::new();
+
+    for entity in &structure_template.entities {
+        let entity = world_entity(entity, position);
+        let (x, _, z) = entity_position(&entity).expect("Position is set");
+
+        chunk_entities
+            .entry(((x.floor() as i32) >> 4, (z.floor() as i32) >> 4))
+            .or_default()
+            .push(entity);
+    }
+
+    for ((chunk_x, chunk_z), entities) in chunk_entities {
+        let mut entity_chunk = match entity_chunk_provider.load_chunk(chunk_x, chunk_z) {
+            Ok(entity_chunk) => entity_chunk,
+            Err(ChunkLoadError::RegionNotFound { .. })
+            | Err(ChunkLoadError::ChunkNotFound { .. }) => {
+                let mut entity_chunk = CompoundTag::new();
+                entity_chunk.insert_i32("DataVersion", structure_template.data_version);
+                entity_chunk.insert_i32_vec("Position", vec![chunk_x, chunk_z]);
+
+                entity_chunk
+            }
+            Err(chunk_load_error) => return Err(chunk_load_error.into()),
+        };
+
+        append_entities(&mut entity_chunk, entities);
+        entity_chunk_provider.save_chunk(chunk_x, chunk_z, entity_chunk)?;
+    }
+
+    Ok(structure_template.entities.len())
+}
+
+/// Returns entities of chunk data which are inside of selection.
+fn chunk_entities(chunk: &Chunk, selection: BlockSelection) -> Vec<StructureEntity> {
+    let (min_x, min_y, min_z) = selection.min;
+    let entities = chunk
+        .data()
+        .get_compound_tag_vec("Entities")
+        .unwrap_or_default();
+
+    entities
+        .into_iter()
+        .filter_map(|entity| {
+            let (x, y, z) = entity_position(entity)?;
+            let block_position = (x.floor() as i32, y.floor() as i32, z.floor() as i32);
+
+            if !selection.contains(block_position) {
+                return None;
+            }
+
+            Some(StructureEntity {
+                position: (x - min_x as f64, y - min_y as f64, z - min_z as f64),
+                block_position: (
+                    block_position.0 - min_x,
+                    block_position.1 - min_y,
+                    block_position.2 - min_z,
+                ),
+                entity: copy_without_tags(entity, &["UUID", "UUIDMost", "UUIDLeast"]),
+            })
+        })
+        .collect()
+}
+
+/// Returns entity tags with position moved to world.
+fn world_entity(entity: &StructureEntity, (x, y, z): (i32, i32, i32)) -> CompoundTag {
+    let (entity_x, entity_y, entity_z) = entity.position;
+
+    let mut entity = entity.entity.clone();
+    entity.insert_f64_vec(
+        "Pos",
+        vec![
+            entity_x + x as f64,
+            entity_y + y as f64,
+            entity_z + z as f64,
+        ],
+    );
+
+    entity
+}
+
+fn entity_position(entity: &CompoundTag) -> Option<(f64, f64, f64)> {
+    match entity.get_f64_vec("Pos").ok()?.as_slice() {
+        [x, y, z] => Some((*x, *y, *z)),
+        _ => None,
+    }
+}
+
+/// Appends entities to `Entities` list of compound tag, creating it if needed.
+fn append_entities(compound_tag: &mut CompoundTag, entities: Vec<CompoundTag>) {
+    let entity_tags = entities.into_iter().map(Tag::Compound);
+
+    match compound_tag.get_mut::<&mut Vec<Tag>>("Entities") {
+        Ok(tags) => tags.extend(entity_tags),
+        Err(_) => compound_tag.insert("Entities", Tag::List(entity_tags.collect())),
+    }
+}
+
+fn int_list(compound_tag: &CompoundTag, name: &str) -> Result<Vec<i32>, StructureTemplateError> {
+    compound_tag
+        .get::<&Vec<Tag>>(name)?
+        .iter()
+        .map(|tag| match tag {
+            Tag::Int(value) => Ok(*value),
+            _ => Err(invalid_tag(name)),
+        })
+        .collect()
+}
+
+fn int_position(
+    compound_tag: &CompoundTag,
+    name: &str,
+) -> Result<(i32, i32, i32), StructureTemplateError> {
+    match int_list(compound_tag, name)?.as_slice() {
+        [x, y, z] => Ok((*x, *y, *z)),
+        _ => Err(invalid_tag(name)),
+    }
+}
+
+/// Returns list of ints, structure positions are stored as lists instead of arrays.
+fn int_list_tag((x, y, z): (i32, i32, i32)) -> Tag {
+    Tag::List(vec![Tag::Int(x), Tag::Int(y), Tag::Int(z)])
+}
+
+fn invalid_tag(name: &str) -> StructureTemplateError {
+    StructureTemplateError::InvalidTag {
+        name: name.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chunk::{Chunk, ChunkTag};
+    use crate::data::read_data_file;
+    use crate::relocate::copy_chunk;
+    use crate::schematic::BlockSelection;
+    use crate::section::BlockState;
+    use crate::structure_template::{
+        capture_entities, capture_structure, place_entities, place_structure, StructureBlock,
+        StructureEntity, StructureTemplate,
+    };
+    use crate::AnvilChunkProvider;
+    use nbt::{CompoundTag, Tag};
+    use tempfile::TempDir;
+
+    fn entity(id: &str, (x, y, z): (f64, f64, f64)) -> CompoundTag {
+        let mut entity = CompoundTag::new();
+        entity.insert_str("id", id);
+        entity.insert_f64_vec("Pos", vec![x, y, z]);
+        entity.insert_i32_vec("UUID", vec![1, 2, 3, 4]);
+
+        entity
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut chest = CompoundTag::new();
+        chest.insert_str("id", "minecraft:chest");
+
+        let mut entity = CompoundTag::new();
+        entity.insert_str("id", "minecraft:armor_stand");
+
+        let structure_template = StructureTemplate {
+            data_version: 3465,
+            size: (2, 1, 3),
+            palette: vec![
+                BlockState::new("minecraft:stone"),
+                BlockState::new("minecraft:chest").with_property("facing", "west"),
+            ],
+            blocks: vec![
+                StructureBlock {
+                    position: (0, 0, 0),
+                    state: 0,
+                    block_entity: None,
+                },
+                StructureBlock {
+                    position: (1, 0, 2),
+                    state: 1,
+                    block_entity: Some(chest),
+                },
+            ],
+            entities: vec![StructureEntity {
+                position: (0.5, 0.0, 1.5),
+                block_position: (0, 0, 1),
+                entity,
+            }],
+        };
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("house.nbt");
+        structure_template.save(&path).unwrap();
+
+        let compound_tag = read_data_file(&path).unwrap();
+        assert!(matches!(compound_tag.get::<&Vec<Tag>>("size"), Ok(size) if size.len() == 3));
+
+        let loaded = StructureTemplate::load(&path).unwrap();
+        assert_eq!(loaded.data_version, 3465);
+        assert_eq!(loaded.size, (2, 1, 3));
+        assert_eq!(loaded.palette, structure_template.palette);
+        assert_eq!(loaded.blocks.len(), 2);
+        assert_eq!(loaded.blocks[1].position, (1, 0, 2));
+        assert_eq!(loaded.blocks[1].state, 1);
+        assert_eq!(
+            loaded.blocks[1]
+                .block_entity
+                .as_ref()
+                .unwrap()
+                .get_str("id")
+                .unwrap(),
+            "minecraft:chest"
+        );
+        assert!(loaded.blocks[0].block_entity.is_none());
+        assert_eq!(loaded.entities[0].position, (0.5, 0.0, 1.5));
+        assert_eq!(loaded.entities[0].block_position, (0, 0, 1));
+    }
+
+    #[test]
+    fn test_load_palettes() {
+        let mut compound_tag = CompoundTag::new();
+        compound_tag.insert_i32("DataVersion", 3465);
+        compound_tag.insert("size", Tag::List(vec![Tag::Int(1); 3]));
+
+        let palette = vec![Tag::Compound(
+            BlockState::new("minecraft:oak_planks").to_compound_tag(),
+        )];
+        compound_tag.insert("palettes", Tag::List(vec![Tag::List(palette)]));
+
+        let mut block = CompoundTag::new();
+        block.insert_i32("state", 0);
+        block.insert("pos", Tag::List(vec![Tag::Int(0); 3]));
+        compound_tag.insert_compound_tag_vec("blocks", vec![block]);
+
+        let structure_template = StructureTemplate::from_compound_tag(&compound_tag).unwrap();
+        assert_eq!(structure_template.palette[0].name, "minecraft:oak_planks");
+        assert!(structure_template.entities.is_empty());
+    }
+
+    #[test]
+    fn test_capture_and_place() {
+        let temp_dir = TempDir::new().unwrap();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+        copy_chunk(&fixture_chunk_provider, (4, 2), &chunk_provider, (4, 2)).unwrap();
+
+        let mut chunk = Chunk::new(chunk_provider.load_chunk(4, 2).unwrap());
+        let mut section = chunk.read_sections().unwrap().remove(0);
+        let section_y = section.y as i32 * 16;
+        section.set_block_state(0, 0, 0, BlockState::new("minecraft:structure_void"));
+        section.set_block_state(1, 0, 0, BlockState::new("minecraft:chest"));
+        chunk.write_section(&section).unwrap();
+
+        let mut chest = CompoundTag::new();
+        chest.insert_str("id", "minecraft:chest");
+        chest.insert_i32("x", 65);
+        chest.insert_i32("y", section_y);
+        chest.insert_i32("z", 32);
+        chunk.insert(
+            ChunkTag::BlockEntities,
+            Tag::List(vec![Tag::Compound(chest)]),
+        );
+        chunk_provider
+            .save_chunk(4, 2, chunk.into_compound_tag())
+            .unwrap();
+
+        let selection = BlockSelection::new((64, section_y, 32), (65, section_y + 1, 32));
+        let structure_template = capture_structure(&chunk_provider, selection).unwrap();
+
+        assert_eq!(structure_template.size, (2, 2, 1));
+        // Structure void is left out.
+        assert_eq!(structure_template.blocks.len(), 3);
+        assert_eq!(structure_template.blocks[0].position, (1, 0, 0));
+        assert_eq!(
+            structure_template.palette[structure_template.blocks[0].state].name,
+            "minecraft:chest"
+        );
+
+        let block_entity = structure_template.blocks[0].block_entity.as_ref().unwrap();
+        assert_eq!(block_entity.get_str("id").unwrap(), "minecraft:chest");
+        assert!(!block_entity.contains_key("x"));
+
+        let place_dir = TempDir::new().unwrap();
+        let place_chunk_provider = AnvilChunkProvider::new(place_dir.path().to_str().unwrap());
+
+        let report =
+            place_structure(&place_chunk_provider, &structure_template, (14, 10, 0)).unwrap();
+        assert_eq!(report.pasted_blocks, 3);
+        assert_eq!(report.created_chunks, vec![(0, 0)]);
+
+        let chunk = Chunk::new(place_chunk_provider.load_chunk(0, 0).unwrap());
+        assert_eq!(
+            chunk.block_state(15, 10, 0).unwrap().unwrap().name,
+            "minecraft:chest"
+        );
+        // Position of structure void keeps air of created chunk.
+        assert_eq!(
+            chunk.block_state(14, 10, 0).unwrap().unwrap().name,
+            "minecraft:air"
+        );
+
+        let block_entities = chunk.block_entities();
+        assert_eq!(block_entities.len(), 1);
+        assert_eq!(block_entities[0].get_i32("x").unwrap(), 15);
+        assert_eq!(block_entities[0].get_i32("y").unwrap(), 10);
+    }
+
+    #[test]
+    fn test_capture_and_place_entities() {
+        let temp_dir = TempDir::new().unwrap();
+        let entity_chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+
+        let mut entity_chunk = CompoundTag::new();
+        entity_chunk.insert_i32("DataVersion", 3465);
+        entity_chunk.insert_i32_vec("Position", vec![0, 0]);
+        entity_chunk.insert_compound_tag_vec(
+            "Entities",
+            vec![
+                entity("minecraft:cow", (3.5, 64.0, 4.5)),
+                entity("minecraft:pig", (30.5, 64.0, 4.5)),
+            ],
+        );
+        entity_chunk_provider
+            .save_chunk(0, 0, entity_chunk)
+            .unwrap();
+
+        let selection = BlockSelection::new((0, 60, 0), (7, 70, 7));
+        let entities = capture_entities(&entity_chunk_provider, selection).unwrap();
+
+        assert_eq!(entities.len(), 1);
+        assert_eq!(entities[0].position, (3.5, 4.0, 4.5));
+        assert_eq!(entities[0].block_position, (3, 4, 4));
+        assert!(!entities[0].entity.contains_key("UUID"));
+
+        let structure_template = StructureTemplate {
+            data_version: 3465,
+            size: (8, 11, 8),
+            palette: Vec::new(),
+            blocks: Vec::new(),
+            entities,
+        };
+
+        let placed =
+            place_entities(&entity_chunk_provider, &structure_template, (-8, 0, 0)).unwrap();
+        assert_eq!(placed, 1);
+
+        let entity_chunk = entity_chunk_provider.load_chunk(-1, 0).unwrap();
+        assert_eq!(entity_chunk.get_i32_vec("Position").unwrap(), &vec![-1, 0]);
+
+        let entities = entity_chunk.get_compound_tag_vec("Entities").unwrap();
+        assert_eq!(entities[0].get_str("id").unwrap(), "minecraft:cow");
+        assert_eq!(
+            entities[0].get_f64_vec("Pos").unwrap(),
+            vec![-4.5, 4.0, 4.5]
+        );
+    }
+}
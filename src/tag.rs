@@ -0,0 +1,154 @@
+//! Helpers for compound tag manipulations which are not covered by typed getters and setters.
+use nbt::{CompoundTag, Tag};
+
+/// Returns tag with specified name.
+pub(crate) fn get_tag<'a>(compound_tag: &'a CompoundTag, name: &str) -> Option<&'a Tag> {
+    compound_tag
+        .iter()
+        .find(|(tag_name, _)| tag_name.as_str() == name)
+        .map(|(_, tag)| tag)
+}
+
+/// Returns mutable tag with specified name.
+pub(crate) fn get_tag_mut<'a>(
+    compound_tag: &'a mut CompoundTag,
+    name: &str,
+) -> Option<&'a mut Tag> {
+    compound_tag
+        .iter_mut()
+        .find(|(tag_name, _)| tag_name.as_str() == name)
+        .map(|(_, tag)| tag)
+}
+
+/// Replaces tag with specified name in place or inserts it at the end.
+pub(crate) fn set_tag(compound_tag: &mut CompoundTag, name: &str, tag: Tag) {
+    match get_tag_mut(compound_tag, name) {
+        Some(old_tag) => *old_tag = tag,
+        None => compound_tag.insert(name, tag),
+    }
+}
+
+/// Returns compound tags which are stored inside list with specified name.
+pub(crate) fn compound_tags<'a>(
+    compound_tag: &'a CompoundTag,
+    name: &str,
+) -> impl Iterator<Item = &'a CompoundTag> {
+    let tags = match get_tag(compound_tag, name) {
+        Some(Tag::List(tags)) => Some(tags),
+        _ => None,
+    };
+
+    tags.into_iter()
+        .flat_map(|tags| tags.iter())
+        .filter_map(|tag| match tag {
+            Tag::Compound(compound_tag) => Some(compound_tag),
+            _ => None,
+        })
+}
+
+/// Returns mutable compound tags which are stored inside list with specified name.
+pub(crate) fn compound_tags_mut<'a>(
+    compound_tag: &'a mut CompoundTag,
+    name: &str,
+) -> impl Iterator<Item = &'a mut CompoundTag> {
+    let tags = match get_tag_mut(compound_tag, name) {
+        Some(Tag::List(tags)) => Some(tags),
+        _ => None,
+    };
+
+    tags.into_iter()
+        .flat_map(|tags| tags.iter_mut())
+        .filter_map(|tag| match tag {
+            Tag::Compound(compound_tag) => Some(compound_tag),
+            _ => None,
+        })
+}
+
+/// Removes tag with specified name preserving order of the remaining tags.
+pub(crate) fn remove_tag(compound_tag: &mut CompoundTag, name: &str) -> Option<Tag> {
+    if !compound_tag.contains_key(name) {
+        return None;
+    }
+
+    let compound_name = compound_tag.name.take();
+    let tags = std::mem::replace(compound_tag, CompoundTag::new());
+    let mut removed_tag = None;
+
+    *compound_tag = tags
+        .into_iter()
+        .filter_map(|(tag_name, tag)| {
+            if tag_name == name {
+                removed_tag = Some(tag);
+                None
+            } else {
+                Some((tag_name, tag))
+            }
+        })
+        .collect();
+
+    compound_tag.name = compound_name;
+
+    removed_tag
+}
+
+/// Renames tag preserving its position.
+pub(crate) fn rename_tag(compound_tag: &mut CompoundTag, from: &str, to: &str) {
+    if !compound_tag.contains_key(from) {
+        return;
+    }
+
+    let compound_name = compound_tag.name.take();
+    let tags = std::mem::replace(compound_tag, CompoundTag::new());
+
+    *compound_tag = tags
+        .into_iter()
+        .map(|(tag_name, tag)| {
+            if tag_name == from {
+                (to.to_owned(), tag)
+            } else {
+                (tag_name, tag)
+            }
+        })
+        .collect();
+
+    compound_tag.name = compound_name;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tag::{remove_tag, rename_tag};
+    use nbt::{CompoundTag, Tag};
+
+    #[test]
+    fn test_remove_tag() {
+        let mut compound_tag = CompoundTag::named("root");
+        compound_tag.insert_i32("a", 1);
+        compound_tag.insert_i32("b", 2);
+        compound_tag.insert_i32("c", 3);
+
+        match remove_tag(&mut compound_tag, "b") {
+            Some(Tag::Int(2)) => {}
+            tag => panic!("Expected `Int(2)` but got `{:?}`", tag),
+        }
+
+        let names: Vec<&String> = compound_tag.iter().map(|(name, _)| name).collect();
+
+        assert_eq!(names, vec!["a", "c"]);
+        assert_eq!(compound_tag.name.as_deref(), Some("root"));
+        assert!(remove_tag(&mut compound_tag, "b").is_none());
+    }
+
+    #[test]
+    fn test_rename_tag() {
+        let mut compound_tag = CompoundTag::new();
+        compound_tag.insert_i32("a", 1);
+        compound_tag.insert_i32("b", 2);
+
+        rename_tag(&mut compound_tag, "a", "d");
+
+        let names: Vec<&String> = compound_tag.iter().map(|(name, _)| name).collect();
+
+        assert_eq!(names, vec!["d", "b"]);
+        assert_eq!(compound_tag.get_i32("d").unwrap(), 1);
+    }
+}
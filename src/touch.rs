@@ -0,0 +1,309 @@
+//! Rewriting save times of chunks.
+//!
+//! Region header stores time of the last save of every chunk, which backup tools use
+//! to find changed chunks and [`crate::prune`] uses to keep recently saved ones.
+//! Timestamps are rewritten in place of header, chunk data is not read or written.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::touch::{set_timestamps, TimestampPolicy};
+//! use anvil_region::AnvilChunkProvider;
+//! use std::time::{Duration, UNIX_EPOCH};
+//!
+//! # let region_dir = tempfile::TempDir::new().unwrap();
+//! # let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+//! # let chunk_provider = AnvilChunkProvider::new(region_dir.path().to_str().unwrap());
+//! # anvil_region::relocate::copy_chunk(&fixture_chunk_provider, (4, 2), &chunk_provider, (4, 2)).unwrap();
+//! let saved_at = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+//! let touch_report = set_timestamps(&chunk_provider, &TimestampPolicy::Fixed(saved_at)).unwrap();
+//!
+//! assert_eq!(touch_report.updated_chunks, 1);
+//! assert_eq!(chunk_provider.chunk_last_modified(4, 2).unwrap(), Some(1_600_000_000));
+//! ```
+use crate::headers::RegionHeaders;
+use crate::relocate::WORLD_REGION_FOLDERS;
+use crate::snapshot::break_hard_link;
+use crate::world::AnvilWorld;
+use crate::{current_timestamp, AnvilChunkMetadata, AnvilChunkProvider, AnvilRegion};
+use std::io;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Which timestamps chunks get.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum TimestampPolicy {
+    /// Sets every timestamp to current time.
+    Now,
+    /// Sets every timestamp to time, times before Unix epoch become zero.
+    Fixed(SystemTime),
+    /// Copies timestamps of chunks at the same position from region folder, chunks
+    /// missing there keep their timestamps.
+    CopyFrom(PathBuf),
+}
+
+/// Result of rewriting timestamps.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TouchReport {
+    /// Chunks which timestamp was changed.
+    pub updated_chunks: usize,
+    /// Chunks which already had the timestamp or are missing in copied folder.
+    pub unchanged_chunks: usize,
+}
+
+impl TouchReport {
+    fn add(&mut self, touch_report: TouchReport) {
+        self.updated_chunks += touch_report.updated_chunks;
+        self.unchanged_chunks += touch_report.unchanged_chunks;
+    }
+}
+
+/// Rewrites timestamps of every chunk of provider according to policy.
+pub fn set_timestamps(
+    chunk_provider: &AnvilChunkProvider,
+    policy: &TimestampPolicy,
+) -> Result<TouchReport, io::Error> {
+    let fixed_timestamp = match policy {
+        TimestampPolicy::Now => Some(current_timestamp()),
+        TimestampPolicy::Fixed(time) => Some(
+            time.duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_secs().min(u32::MAX as u64) as u32),
+        ),
+        TimestampPolicy::CopyFrom(_) => None,
+    };
+
+    let mut touch_report = TouchReport::default();
+
+    for region_file in chunk_provider.region_files()? {
+        let region_headers = RegionHeaders::open(&region_file.path)?;
+
+        let source_region_headers = match policy {
+            TimestampPolicy::CopyFrom(folder_path) => {
+                let source_region_path = folder_path.join(region_file.path.file_name().unwrap());
+
+                if source_region_path.is_file() {
+                    Some(RegionHeaders::open(&source_region_path)?)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+
+        let mut updated_metadata = Vec::new();
+
+        for (region_chunk_x, region_chunk_z) in region_headers.chunk_positions() {
+            let metadata = region_headers.metadata(region_chunk_x, region_chunk_z);
+
+            let timestamp = fixed_timestamp.or_else(|| {
+                source_region_headers
+                    .as_ref()
+                    .and_then(|source_region_headers| {
+                        source_region_headers.chunk_last_modified(region_chunk_x, region_chunk_z)
+                    })
+            });
+
+            match timestamp {
+                Some(timestamp) if timestamp != metadata.last_modified_timestamp => {
+                    updated_metadata.push((
+                        region_chunk_x,
+                        region_chunk_z,
+                        AnvilChunkMetadata {
+                            last_modified_timestamp: timestamp,
+                            ..metadata
+                        },
+                    ));
+                }
+                _ => touch_report.unchanged_chunks += 1,
+            }
+        }
+
+        if updated_metadata.is_empty() {
+            continue;
+        }
+
+        break_hard_link(&region_file.path)?;
+
+        let mut region = AnvilRegion::new(&region_file.path)?;
+
+        for (region_chunk_x, region_chunk_z, metadata) in updated_metadata {
+            region.update_metadata(region_chunk_x, region_chunk_z, metadata)?;
+            touch_report.updated_chunks += 1;
+        }
+
+        chunk_provider.invalidate_region_headers(&region_file.path);
+    }
+
+    Ok(touch_report)
+}
+
+/// Rewrites timestamps of terrain, entity and point of interest chunks of every
+/// dimension of world.
+///
+/// Timestamps are copied from the same folders of world at [`TimestampPolicy::CopyFrom`].
+pub fn set_world_timestamps(
+    world: &AnvilWorld,
+    policy: &TimestampPolicy,
+) -> Result<TouchReport, io::Error> {
+    let mut touch_report = TouchReport::default();
+
+    for dimension in world.dimensions()? {
+        let dimension_folder_path = dimension.folder_path(world.folder_path());
+
+        for folder in WORLD_REGION_FOLDERS {
+            let folder_path = dimension_folder_path.join(folder);
+            let chunk_provider = AnvilChunkProvider::from_path(&folder_path);
+
+            let folder_policy = match policy {
+                TimestampPolicy::CopyFrom(world_folder_path) => {
+                    TimestampPolicy::CopyFrom(dimension.folder_path(world_folder_path).join(folder))
+                }
+                policy => policy.clone(),
+            };
+
+            touch_report.add(set_timestamps(&chunk_provider, &folder_policy)?);
+        }
+    }
+
+    Ok(touch_report)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::level::LevelData;
+    use crate::relocate::copy_chunk;
+    use crate::touch::{set_timestamps, set_world_timestamps, TimestampPolicy, TouchReport};
+    use crate::world::AnvilWorld;
+    use crate::AnvilChunkProvider;
+    use std::fs;
+    use std::time::{Duration, UNIX_EPOCH};
+    use tempfile::TempDir;
+
+    fn fixed(timestamp: u64) -> TimestampPolicy {
+        TimestampPolicy::Fixed(UNIX_EPOCH + Duration::from_secs(timestamp))
+    }
+
+    #[test]
+    fn test_set_timestamps() {
+        let region_dir = TempDir::new().unwrap();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk_provider = AnvilChunkProvider::new(region_dir.path().to_str().unwrap());
+
+        for &chunk_x in &[4, 40] {
+            copy_chunk(
+                &fixture_chunk_provider,
+                (4, 2),
+                &chunk_provider,
+                (chunk_x, 2),
+            )
+            .unwrap();
+        }
+
+        let region_path = region_dir.path().join("r.0.0.mca");
+        let region_buffer = fs::read(&region_path).unwrap();
+        let touch_report = set_timestamps(&chunk_provider, &fixed(1000)).unwrap();
+
+        assert_eq!(
+            touch_report,
+            TouchReport {
+                updated_chunks: 2,
+                unchanged_chunks: 0,
+            }
+        );
+        assert_eq!(
+            chunk_provider.chunk_last_modified(4, 2).unwrap(),
+            Some(1000)
+        );
+        assert_eq!(
+            chunk_provider.chunk_last_modified(40, 2).unwrap(),
+            Some(1000)
+        );
+        // Only the timestamp table after the first header sector is rewritten.
+        assert_eq!(
+            fs::read(&region_path).unwrap()[..4096],
+            region_buffer[..4096]
+        );
+        assert_eq!(
+            fs::read(&region_path).unwrap()[8192..],
+            region_buffer[8192..]
+        );
+
+        let touch_report = set_timestamps(&chunk_provider, &fixed(1000)).unwrap();
+        assert_eq!(touch_report.updated_chunks, 0);
+        assert_eq!(touch_report.unchanged_chunks, 2);
+    }
+
+    #[test]
+    fn test_copy_timestamps() {
+        let region_dir = TempDir::new().unwrap();
+        let source_region_dir = TempDir::new().unwrap();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk_provider = AnvilChunkProvider::new(region_dir.path().to_str().unwrap());
+        let source_chunk_provider =
+            AnvilChunkProvider::new(source_region_dir.path().to_str().unwrap());
+
+        for &chunk_x in &[4, 5] {
+            copy_chunk(
+                &fixture_chunk_provider,
+                (4, 2),
+                &chunk_provider,
+                (chunk_x, 2),
+            )
+            .unwrap();
+        }
+
+        copy_chunk(
+            &fixture_chunk_provider,
+            (4, 2),
+            &source_chunk_provider,
+            (4, 2),
+        )
+        .unwrap();
+        set_timestamps(&chunk_provider, &fixed(1000)).unwrap();
+        set_timestamps(&source_chunk_provider, &fixed(2000)).unwrap();
+
+        let policy = TimestampPolicy::CopyFrom(source_region_dir.path().to_path_buf());
+        let touch_report = set_timestamps(&chunk_provider, &policy).unwrap();
+
+        assert_eq!(touch_report.updated_chunks, 1);
+        assert_eq!(touch_report.unchanged_chunks, 1);
+        assert_eq!(
+            chunk_provider.chunk_last_modified(4, 2).unwrap(),
+            Some(2000)
+        );
+        // Chunk missing in source keeps its timestamp.
+        assert_eq!(
+            chunk_provider.chunk_last_modified(5, 2).unwrap(),
+            Some(1000)
+        );
+    }
+
+    #[test]
+    fn test_set_world_timestamps() {
+        let world_dir = TempDir::new().unwrap();
+        LevelData::new().save(world_dir.path()).unwrap();
+
+        let world = AnvilWorld::open(world_dir.path()).unwrap();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        copy_chunk(
+            &fixture_chunk_provider,
+            (4, 2),
+            &world.nether().entity_chunk_provider(),
+            (4, 2),
+        )
+        .unwrap();
+
+        let touch_report = set_world_timestamps(&world, &fixed(1000)).unwrap();
+
+        assert_eq!(touch_report.updated_chunks, 1);
+        assert_eq!(
+            world
+                .nether()
+                .entity_chunk_provider()
+                .chunk_last_modified(4, 2)
+                .unwrap(),
+            Some(1000)
+        );
+    }
+}
@@ -0,0 +1,97 @@
+//! Instrumentation with `tracing` crate enabled by `tracing` feature.
+//!
+//! Macros expand to nothing without the feature, so instrumented code doesn't need
+//! `cfg` attributes of its own.
+
+/// Enters debug span which lasts until the end of the enclosing block.
+macro_rules! enter_span {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!($($arg)*).entered();
+    };
+}
+
+/// Records trace event in the current span.
+macro_rules! trace_event {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::trace!($($arg)*);
+    };
+}
+
+pub(crate) use enter_span;
+pub(crate) use trace_event;
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use crate::AnvilChunkProvider;
+    use std::sync::{Arc, Mutex};
+    use tempfile::TempDir;
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    /// Subscriber which records names of spans and messages of events.
+    #[derive(Default)]
+    struct RecordingSubscriber {
+        records: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Subscriber for RecordingSubscriber {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut records = self.records.lock().unwrap();
+            records.push(span.metadata().name().to_owned());
+
+            Id::from_u64(records.len() as u64)
+        }
+
+        fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut message = String::new();
+            event.record(
+                &mut |field: &tracing::field::Field, value: &dyn std::fmt::Debug| {
+                    if field.name() == "message" {
+                        message = format!("{:?}", value);
+                    }
+                },
+            );
+
+            self.records.lock().unwrap().push(message);
+        }
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    #[test]
+    fn test_spans_and_events() {
+        let temp_dir = TempDir::new().unwrap();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+        let chunk_compound_tag = fixture_chunk_provider.load_chunk(4, 2).unwrap();
+
+        let subscriber = RecordingSubscriber::default();
+        let records = subscriber.records.clone();
+
+        tracing::subscriber::with_default(subscriber, || {
+            chunk_provider.save_chunk(4, 2, chunk_compound_tag).unwrap();
+            chunk_provider.load_chunk(4, 2).unwrap();
+            chunk_provider.load_chunk(4, 2).unwrap();
+        });
+
+        let records = records.lock().unwrap();
+        assert_eq!(records[0], "write_chunk");
+        assert!(records.contains(&"region opened".to_owned()));
+        assert!(records.contains(&"read_chunk".to_owned()));
+        assert!(records.contains(&"region header cache miss".to_owned()));
+        assert!(records.contains(&"region header cache hit".to_owned()));
+        assert!(records.contains(&"chunk read".to_owned()));
+    }
+}
@@ -0,0 +1,314 @@
+//! Batches of chunk changes which are written all at once or not at all.
+//!
+//! [`ChunkTransaction`] stages saves and deletes in memory. On [`commit`] every
+//! affected region file is copied to temporary file, changes are written to the copy,
+//! which is synced and renamed over the region file. Crash in the middle of commit
+//! leaves every region file either unchanged or with all its changes, never with only
+//! some of them. Regions are committed one after another, so crash may leave some
+//! regions of transaction committed and others not. [`rollback`] or dropping
+//! transaction discards staged changes.
+//!
+//! [`commit`]: ChunkTransaction::commit
+//! [`rollback`]: ChunkTransaction::rollback
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::AnvilChunkProvider;
+//! use nbt::CompoundTag;
+//! use tempfile::TempDir;
+//!
+//! let region_dir = TempDir::new().unwrap();
+//! let chunk_provider = AnvilChunkProvider::new(region_dir.path().to_str().unwrap());
+//!
+//! let mut transaction = chunk_provider.begin();
+//! transaction.save_chunk(4, 2, CompoundTag::new());
+//! transaction.save_chunk(100, -7, CompoundTag::new());
+//! transaction.delete_chunk(15, 3);
+//!
+//! // Nothing is written until commit.
+//! assert!(chunk_provider.load_chunk(4, 2).is_err());
+//!
+//! transaction.commit().unwrap();
+//! assert!(chunk_provider.load_chunk(100, -7).is_ok());
+//! ```
+use crate::{open_locked, AnvilChunkProvider, AnvilRegion, ChunkLoadError, ChunkSaveError};
+use nbt::CompoundTag;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::path::Path;
+
+/// Suffix of temporary copy of region file which is being committed.
+const TRANSACTION_FILE_SUFFIX: &str = ".transaction";
+
+/// Staged chunk changes of region keyed by chunk position inside of region.
+type RegionChanges = BTreeMap<(u8, u8), Option<CompoundTag>>;
+
+/// Chunk saves and deletes of provider which aren't written yet.
+pub struct ChunkTransaction<'p, 'a> {
+    chunk_provider: &'p AnvilChunkProvider<'a>,
+    /// Changes keyed by region position, data is none for deleted chunks.
+    region_changes: BTreeMap<(i32, i32), RegionChanges>,
+}
+
+impl<'a> AnvilChunkProvider<'a> {
+    /// Starts transaction which writes staged changes on commit, see
+    /// [`ChunkTransaction`].
+    pub fn begin(&self) -> ChunkTransaction<'_, 'a> {
+        ChunkTransaction {
+            chunk_provider: self,
+            region_changes: BTreeMap::new(),
+        }
+    }
+}
+
+impl<'p, 'a> ChunkTransaction<'p, 'a> {
+    /// Stages save of chunk at the specified coordinates, replacing staged change of it.
+    pub fn save_chunk(&mut self, chunk_x: i32, chunk_z: i32, chunk_compound_tag: CompoundTag) {
+        self.stage(chunk_x, chunk_z, Some(chunk_compound_tag));
+    }
+
+    /// Stages delete of chunk at the specified coordinates, replacing staged change of it.
+    pub fn delete_chunk(&mut self, chunk_x: i32, chunk_z: i32) {
+        self.stage(chunk_x, chunk_z, None);
+    }
+
+    /// Loads chunk as it will be after commit, from staged changes or provider.
+    pub fn load_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<CompoundTag, ChunkLoadError> {
+        let staged_chunk = self
+            .region_changes
+            .get(&(chunk_x >> 5, chunk_z >> 5))
+            .and_then(|region_changes| {
+                region_changes.get(&((chunk_x & 31) as u8, (chunk_z & 31) as u8))
+            });
+
+        match staged_chunk {
+            Some(Some(chunk_compound_tag)) => Ok(chunk_compound_tag.clone()),
+            Some(None) => Err(ChunkLoadError::ChunkNotFound { chunk_x, chunk_z }),
+            None => self.chunk_provider.load_chunk(chunk_x, chunk_z),
+        }
+    }
+
+    /// Returns amount of staged changes.
+    pub fn len(&self) -> usize {
+        self.region_changes.values().map(BTreeMap::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.region_changes.is_empty()
+    }
+
+    /// Writes staged changes region by region.
+    ///
+    /// If writing region fails, region file and regions which follow it are left
+    /// unchanged, while regions before it stay committed.
+    pub fn commit(self) -> Result<(), ChunkSaveError> {
+        let folder_path = self.chunk_provider.folder_path;
+        self.chunk_provider.create_folder()?;
+
+        for ((region_x, region_z), region_changes) in self.region_changes {
+            let region_name = format!("r.{}.{}.mca", region_x, region_z);
+            let region_path = folder_path.join(region_name);
+
+            let result = commit_region(&region_path, region_changes);
+            self.chunk_provider.invalidate_region_headers(&region_path);
+
+            result?;
+        }
+
+        Ok(())
+    }
+
+    /// Discards staged changes, same as dropping transaction.
+    pub fn rollback(self) {}
+
+    fn stage(&mut self, chunk_x: i32, chunk_z: i32, chunk_compound_tag: Option<CompoundTag>) {
+        self.region_changes
+            .entry((chunk_x >> 5, chunk_z >> 5))
+            .or_default()
+            .insert(
+                ((chunk_x & 31) as u8, (chunk_z & 31) as u8),
+                chunk_compound_tag,
+            );
+    }
+}
+
+/// Writes changes to copy of region file and replaces region file with it.
+fn commit_region(region_path: &Path, region_changes: RegionChanges) -> Result<(), ChunkSaveError> {
+    let region_exists = region_path.exists();
+
+    // Deleting chunks of missing region changes nothing.
+    if !region_exists && region_changes.values().all(Option::is_none) {
+        return Ok(());
+    }
+
+    let mut transaction_file_name = region_path.file_name().unwrap_or_default().to_owned();
+    transaction_file_name.push(TRANSACTION_FILE_SUFFIX);
+
+    let transaction_path = region_path.with_file_name(transaction_file_name);
+
+    // Writes of other processes to region file must not be lost between copy and swap,
+    // lock is held until file is closed at the end of commit. Writers waiting for lock
+    // notice that file was replaced and write to the new one.
+    let _region_file: Option<File> = if region_exists {
+        Some(open_locked(region_path)?)
+    } else {
+        None
+    };
+
+    let result = write_region_copy(
+        region_path,
+        &transaction_path,
+        region_exists,
+        region_changes,
+    )
+    .and_then(|()| Ok(fs::rename(&transaction_path, region_path)?));
+
+    if result.is_err() {
+        let _ = fs::remove_file(&transaction_path);
+    }
+
+    result
+}
+
+fn write_region_copy(
+    region_path: &Path,
+    transaction_path: &Path,
+    region_exists: bool,
+    region_changes: RegionChanges,
+) -> Result<(), ChunkSaveError> {
+    if region_exists {
+        fs::copy(region_path, transaction_path)?;
+    } else {
+        // Leftover of interrupted commit.
+        let _ = fs::remove_file(transaction_path);
+    }
+
+    let mut region = AnvilRegion::new(transaction_path)?;
+
+    for ((region_chunk_x, region_chunk_z), chunk_compound_tag) in region_changes {
+        match chunk_compound_tag {
+            Some(chunk_compound_tag) => {
+                region.write_chunk(region_chunk_x, region_chunk_z, chunk_compound_tag)?
+            }
+            None => {
+                region.delete_chunk(region_chunk_x, region_chunk_z)?;
+            }
+        }
+    }
+
+    region.file.sync_all()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AnvilChunkProvider, ChunkLoadError};
+    use nbt::CompoundTag;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn chunk_compound_tag(value: i32) -> CompoundTag {
+        let mut chunk_compound_tag = CompoundTag::new();
+        chunk_compound_tag.insert_i32("Value", value);
+
+        chunk_compound_tag
+    }
+
+    #[test]
+    fn test_commit() {
+        let temp_dir = TempDir::new().unwrap();
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+
+        chunk_provider
+            .save_chunk(1, 1, chunk_compound_tag(1))
+            .unwrap();
+        chunk_provider
+            .save_chunk(2, 2, chunk_compound_tag(2))
+            .unwrap();
+
+        let mut transaction = chunk_provider.begin();
+        transaction.save_chunk(1, 1, chunk_compound_tag(10));
+        transaction.delete_chunk(2, 2);
+        transaction.save_chunk(-40, 70, chunk_compound_tag(3));
+        transaction.delete_chunk(1000, 1000);
+
+        assert_eq!(transaction.len(), 4);
+        assert_eq!(
+            transaction
+                .load_chunk(1, 1)
+                .unwrap()
+                .get_i32("Value")
+                .unwrap(),
+            10
+        );
+        assert!(transaction.load_chunk(2, 2).is_err());
+        assert_eq!(
+            chunk_provider
+                .load_chunk(1, 1)
+                .unwrap()
+                .get_i32("Value")
+                .unwrap(),
+            1
+        );
+
+        transaction.commit().unwrap();
+
+        assert_eq!(
+            chunk_provider
+                .load_chunk(1, 1)
+                .unwrap()
+                .get_i32("Value")
+                .unwrap(),
+            10
+        );
+        assert_eq!(
+            chunk_provider
+                .load_chunk(-40, 70)
+                .unwrap()
+                .get_i32("Value")
+                .unwrap(),
+            3
+        );
+
+        match chunk_provider.load_chunk(2, 2) {
+            Err(ChunkLoadError::ChunkNotFound { .. }) => {}
+            result => panic!("Expected `ChunkNotFound` but got `{:?}`", result),
+        }
+
+        let mut file_names: Vec<_> = fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        file_names.sort();
+
+        assert_eq!(file_names, vec!["r.-2.2.mca", "r.0.0.mca"]);
+    }
+
+    #[test]
+    fn test_rollback() {
+        let temp_dir = TempDir::new().unwrap();
+        let chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+
+        chunk_provider
+            .save_chunk(1, 1, chunk_compound_tag(1))
+            .unwrap();
+
+        let mut transaction = chunk_provider.begin();
+        transaction.save_chunk(1, 1, chunk_compound_tag(10));
+        transaction.save_chunk(40, 40, chunk_compound_tag(2));
+        transaction.rollback();
+
+        assert_eq!(
+            chunk_provider
+                .load_chunk(1, 1)
+                .unwrap()
+                .get_i32("Value")
+                .unwrap(),
+            1
+        );
+        assert!(chunk_provider.load_chunk(40, 40).is_err());
+        assert_eq!(chunk_provider.region_files().unwrap().len(), 1);
+    }
+}
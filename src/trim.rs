@@ -0,0 +1,465 @@
+//! Cutting worlds down to an area.
+//!
+//! Trimming deletes chunks on one side of area border, or copies chunks of the other
+//! side into new world. Terrain, entity and point of interest chunks are trimmed
+//! together and region files left without chunks are deleted.
+//!
+//! [`trim_world_border`] deletes chunks outside of world border of every dimension,
+//! which players can't reach anyway.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::trim::{copy_trimmed_provider, TrimArea, TrimSide};
+//! use anvil_region::AnvilChunkProvider;
+//! use tempfile::TempDir;
+//!
+//! let target_dir = TempDir::new().unwrap();
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//! let target_chunk_provider = AnvilChunkProvider::new(target_dir.path().to_str().unwrap());
+//!
+//! // Spawn area of 128 blocks around 0, 0.
+//! let area = TrimArea::BlockRadius { center: (0, 0), radius: 128 };
+//! copy_trimmed_provider(&chunk_provider, &target_chunk_provider, &area, TrimSide::Inside).unwrap();
+//!
+//! assert!(target_chunk_provider.load_chunk(4, 2).is_ok());
+//! assert!(target_chunk_provider.load_chunk(15, 3).is_err());
+//! ```
+use crate::level::WorldBorder;
+use crate::relocate::{copy_chunk, RelocateError, WORLD_REGION_FOLDERS};
+use crate::world::{AnvilWorld, Dimension, WorldError};
+use crate::{AnvilChunkProvider, ChunkSaveError};
+use std::io;
+use std::path::Path;
+
+/// Possible errors while trimming chunks.
+#[derive(Debug)]
+pub enum TrimError {
+    /// Chunk can't be deleted.
+    ChunkSaveError { chunk_save_error: ChunkSaveError },
+    /// Chunk can't be copied.
+    RelocateError { relocate_error: RelocateError },
+    /// Region folder can't be read.
+    ReadError { io_error: io::Error },
+    /// World can't be opened.
+    WorldError { world_error: WorldError },
+}
+
+impl From<ChunkSaveError> for TrimError {
+    fn from(chunk_save_error: ChunkSaveError) -> Self {
+        TrimError::ChunkSaveError { chunk_save_error }
+    }
+}
+
+impl From<RelocateError> for TrimError {
+    fn from(relocate_error: RelocateError) -> Self {
+        TrimError::RelocateError { relocate_error }
+    }
+}
+
+impl From<io::Error> for TrimError {
+    fn from(io_error: io::Error) -> Self {
+        TrimError::ReadError { io_error }
+    }
+}
+
+impl From<WorldError> for TrimError {
+    fn from(world_error: WorldError) -> Self {
+        TrimError::WorldError { world_error }
+    }
+}
+
+/// Area of the world, bounds are inclusive.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TrimArea {
+    /// Chunks between lowest and highest chunk X and Z.
+    ChunkRectangle { min: (i32, i32), max: (i32, i32) },
+    /// Chunks which contain at least one block between lowest and highest block X and Z.
+    BlockRectangle { min: (i32, i32), max: (i32, i32) },
+    /// Chunks within distance in chunks from center chunk.
+    ChunkRadius { center: (i32, i32), radius: i32 },
+    /// Chunks which center is within distance in blocks from center block.
+    BlockRadius { center: (i32, i32), radius: i32 },
+}
+
+impl TrimArea {
+    /// Returns true if chunk belongs to area.
+    pub fn contains_chunk(&self, chunk_x: i32, chunk_z: i32) -> bool {
+        match *self {
+            TrimArea::ChunkRectangle { min, max } => {
+                chunk_x >= min.0 && chunk_x <= max.0 && chunk_z >= min.1 && chunk_z <= max.1
+            }
+            TrimArea::BlockRectangle { min, max } => TrimArea::ChunkRectangle {
+                min: (min.0 >> 4, min.1 >> 4),
+                max: (max.0 >> 4, max.1 >> 4),
+            }
+            .contains_chunk(chunk_x, chunk_z),
+            TrimArea::ChunkRadius { center, radius } => {
+                within_radius(chunk_x - center.0, chunk_z - center.1, radius)
+            }
+            TrimArea::BlockRadius { center, radius } => within_radius(
+                chunk_x * 16 + 8 - center.0,
+                chunk_z * 16 + 8 - center.1,
+                radius,
+            ),
+        }
+    }
+}
+
+impl TrimArea {
+    /// Returns area of chunks which have at least one block inside of world border
+    /// extended by margin in blocks.
+    pub fn world_border(world_border: &WorldBorder, margin: i32) -> Self {
+        let half_size = world_border.size / 2.0 + margin as f64;
+        let block = |value: f64| value.clamp(i32::MIN as f64, i32::MAX as f64) as i32;
+
+        // Border includes its minimum edge and excludes its maximum edge.
+        TrimArea::BlockRectangle {
+            min: (
+                block((world_border.center_x - half_size).floor()),
+                block((world_border.center_z - half_size).floor()),
+            ),
+            max: (
+                block((world_border.center_x + half_size).ceil() - 1.0),
+                block((world_border.center_z + half_size).ceil() - 1.0),
+            ),
+        }
+    }
+}
+
+fn within_radius(distance_x: i32, distance_z: i32, radius: i32) -> bool {
+    let distance_x = distance_x as i64;
+    let distance_z = distance_z as i64;
+    let radius = radius as i64;
+
+    distance_x * distance_x + distance_z * distance_z <= radius * radius
+}
+
+/// Side of area border which chunks are kept.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TrimSide {
+    Inside,
+    Outside,
+}
+
+impl TrimSide {
+    fn keeps(self, area: &TrimArea, chunk_x: i32, chunk_z: i32) -> bool {
+        area.contains_chunk(chunk_x, chunk_z) == (self == TrimSide::Inside)
+    }
+}
+
+/// Result of trimming.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TrimReport {
+    /// Chunks which were kept or copied.
+    pub kept_chunks: usize,
+    /// Chunks which were deleted or not copied.
+    pub trimmed_chunks: usize,
+    /// Region files which were deleted because all their chunks were trimmed.
+    pub deleted_regions: usize,
+}
+
+impl TrimReport {
+    fn add(&mut self, trim_report: TrimReport) {
+        self.kept_chunks += trim_report.kept_chunks;
+        self.trimmed_chunks += trim_report.trimmed_chunks;
+        self.deleted_regions += trim_report.deleted_regions;
+    }
+}
+
+/// Deletes chunks of provider on the other side of area border than kept one.
+pub fn trim_provider(
+    chunk_provider: &AnvilChunkProvider,
+    area: &TrimArea,
+    side: TrimSide,
+) -> Result<TrimReport, TrimError> {
+    let mut trim_report = TrimReport::default();
+
+    for (chunk_x, chunk_z) in chunk_provider.chunk_positions()? {
+        if side.keeps(area, chunk_x, chunk_z) {
+            trim_report.kept_chunks += 1;
+            continue;
+        }
+
+        chunk_provider.delete_chunk(chunk_x, chunk_z)?;
+        trim_report.trimmed_chunks += 1;
+    }
+
+    trim_report.deleted_regions = chunk_provider.delete_empty_regions()?;
+
+    Ok(trim_report)
+}
+
+/// Copies chunks of provider on kept side of area border into target provider.
+pub fn copy_trimmed_provider(
+    chunk_provider: &AnvilChunkProvider,
+    target_chunk_provider: &AnvilChunkProvider,
+    area: &TrimArea,
+    side: TrimSide,
+) -> Result<TrimReport, TrimError> {
+    if chunk_provider.folder_path == target_chunk_provider.folder_path {
+        return Err(RelocateError::TargetIsSource.into());
+    }
+
+    let mut trim_report = TrimReport::default();
+
+    for (chunk_x, chunk_z) in chunk_provider.chunk_positions()? {
+        if !side.keeps(area, chunk_x, chunk_z) {
+            trim_report.trimmed_chunks += 1;
+            continue;
+        }
+
+        copy_chunk(
+            chunk_provider,
+            (chunk_x, chunk_z),
+            target_chunk_provider,
+            (chunk_x, chunk_z),
+        )?;
+        trim_report.kept_chunks += 1;
+    }
+
+    Ok(trim_report)
+}
+
+/// Trims terrain, entity and point of interest chunks of world dimension folder.
+pub fn trim_world(
+    world_folder_path: &Path,
+    area: &TrimArea,
+    side: TrimSide,
+) -> Result<TrimReport, TrimError> {
+    let mut trim_report = TrimReport::default();
+
+    for folder in WORLD_REGION_FOLDERS {
+        let region_folder_path = world_folder_path.join(folder);
+
+        let chunk_provider = AnvilChunkProvider::from_path(&region_folder_path);
+
+        trim_report.add(trim_provider(&chunk_provider, area, side)?);
+    }
+
+    Ok(trim_report)
+}
+
+/// Deletes chunks of every dimension of world which are fully outside of world border
+/// from `level.dat` extended by margin in blocks.
+///
+/// Border of the nether is scaled down 8 times like the game does.
+pub fn trim_world_border(world_folder_path: &Path, margin: i32) -> Result<TrimReport, TrimError> {
+    let world = AnvilWorld::open(world_folder_path)?;
+    let world_border = world.level_data().world_border();
+    let mut trim_report = TrimReport::default();
+
+    for dimension in world.dimensions()? {
+        let scale = if dimension == Dimension::Nether {
+            8.0
+        } else {
+            1.0
+        };
+
+        let dimension_world_border = WorldBorder {
+            center_x: world_border.center_x / scale,
+            center_z: world_border.center_z / scale,
+            size: world_border.size / scale,
+            ..world_border.clone()
+        };
+
+        let area = TrimArea::world_border(&dimension_world_border, margin);
+        let dimension_folder_path = world.dimension(&dimension).folder_path().to_path_buf();
+
+        trim_report.add(trim_world(&dimension_folder_path, &area, TrimSide::Inside)?);
+    }
+
+    Ok(trim_report)
+}
+
+/// Copies kept terrain, entity and point of interest chunks of world dimension folder
+/// into target world.
+pub fn copy_trimmed_world(
+    world_folder_path: &Path,
+    target_world_folder_path: &Path,
+    area: &TrimArea,
+    side: TrimSide,
+) -> Result<TrimReport, TrimError> {
+    let mut trim_report = TrimReport::default();
+
+    for folder in WORLD_REGION_FOLDERS {
+        let region_folder_path = world_folder_path.join(folder);
+        let target_region_folder_path = target_world_folder_path.join(folder);
+
+        if !region_folder_path.exists() {
+            continue;
+        }
+
+        let chunk_provider = AnvilChunkProvider::from_path(&region_folder_path);
+
+        let target_chunk_provider = AnvilChunkProvider::from_path(&target_region_folder_path);
+
+        trim_report.add(copy_trimmed_provider(
+            &chunk_provider,
+            &target_chunk_provider,
+            area,
+            side,
+        )?);
+    }
+
+    Ok(trim_report)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::level::{LevelData, WorldBorder};
+    use crate::relocate::copy_chunk;
+    use crate::trim::{trim_world, trim_world_border, TrimArea, TrimReport, TrimSide};
+    use crate::world::AnvilWorld;
+    use crate::AnvilChunkProvider;
+    use nbt::CompoundTag;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_contains_chunk() {
+        let chunk_rectangle = TrimArea::ChunkRectangle {
+            min: (-2, -2),
+            max: (2, 3),
+        };
+        assert!(chunk_rectangle.contains_chunk(2, 3));
+        assert!(!chunk_rectangle.contains_chunk(3, 0));
+
+        let block_rectangle = TrimArea::BlockRectangle {
+            min: (-1, 0),
+            max: (16, 15),
+        };
+        assert!(block_rectangle.contains_chunk(-1, 0));
+        assert!(block_rectangle.contains_chunk(1, 0));
+        assert!(!block_rectangle.contains_chunk(2, 0));
+        assert!(!block_rectangle.contains_chunk(0, -1));
+
+        let chunk_radius = TrimArea::ChunkRadius {
+            center: (10, 10),
+            radius: 5,
+        };
+        assert!(chunk_radius.contains_chunk(13, 14));
+        assert!(!chunk_radius.contains_chunk(14, 14));
+
+        let block_radius = TrimArea::BlockRadius {
+            center: (0, 0),
+            radius: 100,
+        };
+        assert!(block_radius.contains_chunk(5, 0));
+        assert!(!block_radius.contains_chunk(6, 0));
+    }
+
+    #[test]
+    fn test_trim_world() {
+        let world_dir = TempDir::new().unwrap();
+        let region_folder = world_dir.path().join("region");
+        let entities_folder = world_dir.path().join("entities");
+        let chunk_provider = AnvilChunkProvider::new(region_folder.to_str().unwrap());
+        let entity_chunk_provider = AnvilChunkProvider::new(entities_folder.to_str().unwrap());
+
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        copy_chunk(&fixture_chunk_provider, (4, 2), &chunk_provider, (4, 2)).unwrap();
+        copy_chunk(&fixture_chunk_provider, (4, 2), &chunk_provider, (40, 2)).unwrap();
+
+        for (chunk_x, chunk_z) in &[(4, 2), (40, 2)] {
+            let mut entity_chunk = CompoundTag::new();
+            entity_chunk.insert_i32_vec("Position", vec![*chunk_x, *chunk_z]);
+            entity_chunk_provider
+                .save_chunk(*chunk_x, *chunk_z, entity_chunk)
+                .unwrap();
+        }
+
+        let area = TrimArea::ChunkRectangle {
+            min: (0, 0),
+            max: (31, 31),
+        };
+        let trim_report = trim_world(world_dir.path(), &area, TrimSide::Inside).unwrap();
+
+        assert_eq!(
+            trim_report,
+            TrimReport {
+                kept_chunks: 2,
+                trimmed_chunks: 2,
+                deleted_regions: 2,
+            }
+        );
+        assert_eq!(chunk_provider.chunk_positions().unwrap(), vec![(4, 2)]);
+        assert_eq!(
+            entity_chunk_provider.chunk_positions().unwrap(),
+            vec![(4, 2)]
+        );
+        assert!(!region_folder.join("r.1.0.mca").exists());
+        assert!(!entities_folder.join("r.1.0.mca").exists());
+    }
+
+    #[test]
+    fn test_world_border_area() {
+        let world_border = WorldBorder {
+            center_x: 8.0,
+            center_z: -0.5,
+            size: 32.0,
+            ..WorldBorder::default()
+        };
+
+        assert_eq!(
+            TrimArea::world_border(&world_border, 0),
+            TrimArea::BlockRectangle {
+                min: (-8, -17),
+                max: (23, 15),
+            }
+        );
+
+        let area = TrimArea::world_border(&world_border, 16);
+        assert!(area.contains_chunk(2, 1));
+        assert!(!area.contains_chunk(3, 0));
+        assert!(area.contains_chunk(-2, -3));
+        assert!(!area.contains_chunk(-3, 0));
+    }
+
+    #[test]
+    fn test_trim_world_border() {
+        let world_dir = TempDir::new().unwrap();
+        let mut level_data = LevelData::new();
+        level_data.set_world_border(&WorldBorder {
+            size: 1024.0,
+            ..WorldBorder::default()
+        });
+        level_data.save(world_dir.path()).unwrap();
+
+        let world = AnvilWorld::open(world_dir.path()).unwrap();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+
+        for &chunk_x in &[4, 40] {
+            copy_chunk(
+                &fixture_chunk_provider,
+                (4, 2),
+                &world.overworld().chunk_provider(),
+                (chunk_x, 2),
+            )
+            .unwrap();
+            copy_chunk(
+                &fixture_chunk_provider,
+                (4, 2),
+                &world.nether().chunk_provider(),
+                (chunk_x, 2),
+            )
+            .unwrap();
+        }
+
+        // Border of the nether is 128 blocks wide, both nether chunks are outside of it.
+        let trim_report = trim_world_border(world_dir.path(), 0).unwrap();
+
+        assert_eq!(trim_report.kept_chunks, 1);
+        assert_eq!(trim_report.trimmed_chunks, 3);
+        assert_eq!(
+            world
+                .overworld()
+                .chunk_provider()
+                .chunk_positions()
+                .unwrap(),
+            vec![(4, 2)]
+        );
+    }
+}
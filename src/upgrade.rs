@@ -0,0 +1,661 @@
+//! Chunk format upgrades between major versions.
+//!
+//! Upgrade consists of steps, each step converts chunk to the format introduced
+//! by specific data version. Only steps between chunk data version and target data
+//! version are applied, so partially upgraded chunks are handled naturally.
+//!
+//! Ids renamed between versions are renamed with [`crate::remap`] tables.
+//!
+//! Upgrades are best effort: they restructure data so that game is able to load it,
+//! but data which game recomputes by itself (heightmaps, lighting of the new sections)
+//! can be dropped.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::upgrade::upgrade_chunk;
+//! use anvil_region::version::LEVEL_WRAPPER_REMOVAL_DATA_VERSION;
+//! use anvil_region::AnvilChunkProvider;
+//!
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//! let mut chunk_compound_tag = chunk_provider.load_chunk(4, 2).unwrap();
+//!
+//! upgrade_chunk(&mut chunk_compound_tag, LEVEL_WRAPPER_REMOVAL_DATA_VERSION).unwrap();
+//!
+//! assert!(!chunk_compound_tag.contains_key("Level"));
+//! assert_eq!(chunk_compound_tag.get_i32("xPos").unwrap(), 4);
+//! assert_eq!(chunk_compound_tag.get_str("Status").unwrap(), "minecraft:full");
+//! ```
+use crate::chunk::ChunkStatus;
+use crate::packed::{pack, palette_bits, unpack};
+use crate::remap::{apply_remap, IdRemap};
+use crate::section::{SECTION_BIOMES, SECTION_BLOCKS};
+use crate::tag::{compound_tags_mut, get_tag_mut, remove_tag, rename_tag};
+use crate::version::{
+    chunk_data_version, BIOMES_3D_DATA_VERSION, CHUNK_STATUS_RENAME_DATA_VERSION,
+    FLATTENING_DATA_VERSION, LEVEL_WRAPPER_REMOVAL_DATA_VERSION, PADDED_PACKED_ARRAYS_DATA_VERSION,
+};
+use crate::{AnvilChunkProvider, ChunkLoadError, ChunkSaveError};
+use nbt::{CompoundTag, CompoundTagError, Tag};
+use std::collections::HashMap;
+use std::io;
+
+pub(crate) mod legacy;
+
+/// Amount of columns in chunk heightmap.
+pub(crate) const HEIGHTMAP_COLUMNS: usize = 256;
+/// Bits per column in heightmap of 256 blocks high world.
+pub(crate) const HEIGHTMAP_BITS: u32 = 9;
+
+/// Possible errors while upgrading the chunk.
+#[derive(Debug)]
+pub enum ChunkUpgradeError {
+    /// Chunk was written by newer version than upgrade target.
+    NewerDataVersion {
+        /// Chunk data version.
+        data_version: i32,
+        /// Upgrade target data version.
+        target_data_version: i32,
+    },
+    /// Tag required by upgrade step is missing or has unexpected type.
+    InvalidTag {
+        /// Tag name.
+        name: String,
+    },
+    /// Error while loading chunk which should be upgraded.
+    ChunkLoadError { chunk_load_error: ChunkLoadError },
+    /// Error while saving upgraded chunk.
+    ChunkSaveError { chunk_save_error: ChunkSaveError },
+    /// I/O Error which happened while were listing chunks.
+    ReadError { io_error: io::Error },
+}
+
+impl From<CompoundTagError<'_>> for ChunkUpgradeError {
+    fn from(compound_tag_error: CompoundTagError) -> Self {
+        let name = match compound_tag_error {
+            CompoundTagError::TagNotFound { name } => name,
+            CompoundTagError::TagWrongType { name, .. } => name,
+        };
+
+        ChunkUpgradeError::InvalidTag {
+            name: name.to_owned(),
+        }
+    }
+}
+
+impl From<ChunkLoadError> for ChunkUpgradeError {
+    fn from(chunk_load_error: ChunkLoadError) -> Self {
+        ChunkUpgradeError::ChunkLoadError { chunk_load_error }
+    }
+}
+
+impl From<ChunkSaveError> for ChunkUpgradeError {
+    fn from(chunk_save_error: ChunkSaveError) -> Self {
+        ChunkUpgradeError::ChunkSaveError { chunk_save_error }
+    }
+}
+
+impl From<io::Error> for ChunkUpgradeError {
+    fn from(io_error: io::Error) -> Self {
+        ChunkUpgradeError::ReadError { io_error }
+    }
+}
+
+/// Step which converts chunk to the format of specified data version.
+struct UpgradeStep {
+    /// Data version which introduced format.
+    data_version: i32,
+    /// Converts chunk of the previous format.
+    upgrade: fn(&mut CompoundTag) -> Result<(), ChunkUpgradeError>,
+}
+
+/// Upgrade steps in ascending order of data version.
+const UPGRADE_STEPS: &[UpgradeStep] = &[
+    UpgradeStep {
+        data_version: FLATTENING_DATA_VERSION,
+        upgrade: flatten,
+    },
+    UpgradeStep {
+        data_version: CHUNK_STATUS_RENAME_DATA_VERSION,
+        upgrade: rename_statuses,
+    },
+    UpgradeStep {
+        data_version: BIOMES_3D_DATA_VERSION,
+        upgrade: extend_biomes_3d,
+    },
+    UpgradeStep {
+        data_version: PADDED_PACKED_ARRAYS_DATA_VERSION,
+        upgrade: pad_packed_arrays,
+    },
+    UpgradeStep {
+        data_version: LEVEL_WRAPPER_REMOVAL_DATA_VERSION,
+        upgrade: remove_level_wrapper,
+    },
+];
+
+/// Upgrades chunk to the format of target data version.
+///
+/// Chunks without data version are treated as written before 1.9.
+pub fn upgrade_chunk(
+    chunk_compound_tag: &mut CompoundTag,
+    target_data_version: i32,
+) -> Result<(), ChunkUpgradeError> {
+    let data_version = chunk_data_version(chunk_compound_tag).unwrap_or(0);
+
+    if data_version > target_data_version {
+        return Err(ChunkUpgradeError::NewerDataVersion {
+            data_version,
+            target_data_version,
+        });
+    }
+
+    if data_version == target_data_version {
+        return Ok(());
+    }
+
+    for step in UPGRADE_STEPS {
+        if step.data_version > data_version && step.data_version <= target_data_version {
+            (step.upgrade)(chunk_compound_tag)?;
+        }
+    }
+
+    apply_remap(
+        chunk_compound_tag,
+        &IdRemap::between(data_version, target_data_version),
+    );
+    chunk_compound_tag.insert_i32("DataVersion", target_data_version);
+
+    Ok(())
+}
+
+/// Upgrades all chunks of provider to the format of target data version.
+///
+/// Returns amount of upgraded chunks.
+pub fn upgrade_provider(
+    chunk_provider: &AnvilChunkProvider,
+    target_data_version: i32,
+) -> Result<usize, ChunkUpgradeError> {
+    let mut upgraded_chunks = 0;
+
+    for (chunk_x, chunk_z) in chunk_provider.chunk_positions()? {
+        let mut chunk_compound_tag = chunk_provider.load_chunk(chunk_x, chunk_z)?;
+        let data_version = chunk_data_version(&chunk_compound_tag).unwrap_or(0);
+
+        if data_version >= target_data_version {
+            continue;
+        }
+
+        upgrade_chunk(&mut chunk_compound_tag, target_data_version)?;
+        chunk_provider.save_chunk(chunk_x, chunk_z, chunk_compound_tag)?;
+
+        upgraded_chunks += 1;
+    }
+
+    Ok(upgraded_chunks)
+}
+
+fn level_mut(chunk_compound_tag: &mut CompoundTag) -> Result<&mut CompoundTag, ChunkUpgradeError> {
+    Ok(chunk_compound_tag.get_mut::<&mut CompoundTag>("Level")?)
+}
+
+/// Replaces numeric block ids with block state palettes.
+fn flatten(chunk_compound_tag: &mut CompoundTag) -> Result<(), ChunkUpgradeError> {
+    let level = level_mut(chunk_compound_tag)?;
+
+    for section in compound_tags_mut(level, "Sections") {
+        flatten_section(section);
+    }
+
+    // Biomes stored as unsigned bytes before flattening.
+    let biomes: Option<Vec<i32>> = level
+        .get_i8_vec("Biomes")
+        .ok()
+        .map(|biomes| biomes.iter().map(|biome| *biome as u8 as i32).collect());
+
+    if let Some(biomes) = biomes {
+        level.insert_i32_vec("Biomes", biomes);
+    }
+
+    if !level.contains_key("Status") {
+        let status = match level.get_bool("TerrainPopulated") {
+            Ok(true) => "postprocessed",
+            _ => "base",
+        };
+
+        level.insert_str("Status", status);
+    }
+
+    // Game recomputes heightmaps of the new format.
+    remove_tag(level, "HeightMap");
+
+    Ok(())
+}
+
+fn flatten_section(section: &mut CompoundTag) {
+    let blocks = match remove_tag(section, "Blocks") {
+        Some(Tag::ByteArray(blocks)) => blocks,
+        _ => return,
+    };
+
+    let data = match remove_tag(section, "Data") {
+        Some(Tag::ByteArray(data)) => data,
+        _ => Vec::new(),
+    };
+
+    let add = match remove_tag(section, "Add") {
+        Some(Tag::ByteArray(add)) => add,
+        _ => Vec::new(),
+    };
+
+    let nibble = |array: &[i8], index: usize| match array.get(index / 2) {
+        Some(value) => (*value as u8 >> ((index % 2) * 4)) & 0xF,
+        None => 0,
+    };
+
+    let mut palette = vec![legacy::block_state(0, 0)];
+    let mut palette_indices = HashMap::new();
+    let mut indices = Vec::with_capacity(SECTION_BLOCKS);
+
+    palette_indices.insert(palette[0].to_string(), 0);
+
+    for index in 0..SECTION_BLOCKS {
+        let block = blocks.get(index).copied().unwrap_or(0) as u8;
+        let id = block as u16 | (nibble(&add, index) as u16) << 8;
+        let block_state = legacy::block_state(id, nibble(&data, index));
+        let key = block_state.to_string();
+
+        let palette_index = match palette_indices.get(&key) {
+            Some(palette_index) => *palette_index,
+            None => {
+                let palette_index = palette.len() as u16;
+                palette_indices.insert(key, palette_index);
+                palette.push(block_state);
+
+                palette_index
+            }
+        };
+
+        indices.push(palette_index);
+    }
+
+    let bits = palette_bits(palette.len(), 4);
+
+    section.insert_compound_tag_vec("Palette", palette);
+    section.insert_i64_vec("BlockStates", pack(&indices, bits, true));
+}
+
+/// Renames statuses of the proto chunks to the ones introduced in 1.14.
+fn rename_statuses(chunk_compound_tag: &mut CompoundTag) -> Result<(), ChunkUpgradeError> {
+    let level = level_mut(chunk_compound_tag)?;
+
+    let status = level
+        .get_str("Status")
+        .ok()
+        .and_then(ChunkStatus::from_legacy_name);
+
+    if let Some(status) = status {
+        level.insert_str("Status", status.name());
+    }
+
+    Ok(())
+}
+
+/// Converts biome per column to biome per 4x4x4 cell.
+fn extend_biomes_3d(chunk_compound_tag: &mut CompoundTag) -> Result<(), ChunkUpgradeError> {
+    let level = level_mut(chunk_compound_tag)?;
+
+    let biomes = match level.get_i32_vec("Biomes") {
+        Ok(biomes) if biomes.len() == 256 => biomes.clone(),
+        _ => return Ok(()),
+    };
+
+    let mut biomes_3d = Vec::with_capacity(1024);
+
+    for _ in 0..64 {
+        for cell_z in 0..4 {
+            for cell_x in 0..4 {
+                biomes_3d.push(biomes[cell_z * 4 * 16 + cell_x * 4]);
+            }
+        }
+    }
+
+    level.insert_i32_vec("Biomes", biomes_3d);
+
+    Ok(())
+}
+
+/// Repacks block states and heightmaps so values don't span across longs.
+fn pad_packed_arrays(chunk_compound_tag: &mut CompoundTag) -> Result<(), ChunkUpgradeError> {
+    let level = level_mut(chunk_compound_tag)?;
+
+    for section in compound_tags_mut(level, "Sections") {
+        let palette_length = match section.get_compound_tag_vec("Palette") {
+            Ok(palette) => palette.len(),
+            Err(_) => continue,
+        };
+
+        let bits = palette_bits(palette_length, 4);
+
+        if let Some(Tag::LongArray(block_states)) = get_tag_mut(section, "BlockStates") {
+            let indices = unpack(block_states, bits, SECTION_BLOCKS, true);
+            *block_states = pack(&indices, bits, false);
+        }
+    }
+
+    if let Ok(heightmaps) = level.get_mut::<&mut CompoundTag>("Heightmaps") {
+        for (_, heightmap) in heightmaps.iter_mut() {
+            if let Tag::LongArray(heightmap) = heightmap {
+                let heights = unpack(heightmap, HEIGHTMAP_BITS, HEIGHTMAP_COLUMNS, true);
+                *heightmap = pack(&heights, HEIGHTMAP_BITS, false);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Moves level tags to the root and biomes into sections as introduced in 1.18.
+fn remove_level_wrapper(chunk_compound_tag: &mut CompoundTag) -> Result<(), ChunkUpgradeError> {
+    let mut level = match remove_tag(chunk_compound_tag, "Level") {
+        Some(Tag::Compound(level)) => level,
+        _ => {
+            return Err(ChunkUpgradeError::InvalidTag {
+                name: "Level".to_owned(),
+            })
+        }
+    };
+
+    let biomes = match remove_tag(&mut level, "Biomes") {
+        Some(Tag::IntArray(biomes)) => biomes,
+        _ => Vec::new(),
+    };
+
+    // Worlds with experimental extended height had 1536 biomes starting from -64.
+    let biomes_min_section_y = if biomes.len() > 1024 { -4 } else { 0 };
+    let mut min_section_y = None;
+
+    for section in compound_tags_mut(&mut level, "Sections") {
+        let section_y = section.get_i8("Y").unwrap_or(0) as i32;
+        let biomes_offset = (section_y - biomes_min_section_y) * SECTION_BIOMES as i32;
+        let section_biomes = if biomes_offset >= 0 {
+            biomes
+                .get(biomes_offset as usize..biomes_offset as usize + SECTION_BIOMES)
+                .unwrap_or(&[])
+        } else {
+            &[]
+        };
+
+        move_block_states(section);
+        move_biomes(section, section_biomes);
+
+        min_section_y = Some(min_section_y.map_or(section_y, |y: i32| y.min(section_y)));
+    }
+
+    if let Ok(structures) = level.get_mut::<&mut CompoundTag>("Structures") {
+        rename_tag(structures, "Starts", "starts");
+    }
+
+    let status = level.get_str("Status").ok().map(|status| {
+        if status.contains(':') {
+            status.to_owned()
+        } else {
+            format!("minecraft:{}", status)
+        }
+    });
+
+    if let Some(status) = status {
+        level.insert_str("Status", status);
+    }
+
+    // Heightmaps are relative to the world bottom which changed, game recomputes them.
+    remove_tag(&mut level, "Heightmaps");
+
+    for (from, to) in &[
+        ("Sections", "sections"),
+        ("TileEntities", "block_entities"),
+        ("TileTicks", "block_ticks"),
+        ("LiquidTicks", "fluid_ticks"),
+        ("Structures", "structures"),
+    ] {
+        rename_tag(&mut level, from, to);
+    }
+
+    level.insert_i32("yPos", min_section_y.unwrap_or(0));
+
+    for (name, tag) in level {
+        chunk_compound_tag.insert(name, tag);
+    }
+
+    Ok(())
+}
+
+fn move_block_states(section: &mut CompoundTag) {
+    let mut palette = match remove_tag(section, "Palette") {
+        Some(Tag::List(palette)) => palette,
+        _ => Vec::new(),
+    };
+
+    let data = match remove_tag(section, "BlockStates") {
+        Some(Tag::LongArray(data)) => data,
+        _ => Vec::new(),
+    };
+
+    if palette.is_empty() {
+        let mut air = CompoundTag::new();
+        air.insert_str("Name", "minecraft:air");
+
+        palette.push(Tag::Compound(air));
+    }
+
+    let mut block_states = CompoundTag::new();
+
+    if palette.len() > 1 {
+        block_states.insert_i64_vec("data", data);
+    }
+
+    block_states.insert("palette", palette);
+    section.insert_compound_tag("block_states", block_states);
+}
+
+fn move_biomes(section: &mut CompoundTag, section_biomes: &[i32]) {
+    let mut palette: Vec<&str> = Vec::new();
+    let mut indices = Vec::with_capacity(SECTION_BIOMES);
+
+    for biome in section_biomes {
+        let name = legacy::biome_name(*biome);
+
+        let palette_index = match palette.iter().position(|entry| *entry == name) {
+            Some(palette_index) => palette_index,
+            None => {
+                palette.push(name);
+                palette.len() - 1
+            }
+        };
+
+        indices.push(palette_index as u16);
+    }
+
+    if palette.is_empty() {
+        palette.push(legacy::biome_name(1));
+    }
+
+    let mut biomes = CompoundTag::new();
+    let bits = palette_bits(palette.len(), 0);
+
+    if bits > 0 {
+        biomes.insert_i64_vec("data", pack(&indices, bits, false));
+    }
+
+    biomes.insert_str_vec("palette", palette);
+    section.insert_compound_tag("biomes", biomes);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::packed::unpack;
+    use crate::upgrade::{upgrade_chunk, upgrade_provider, ChunkUpgradeError};
+    use crate::version::{
+        BIOMES_3D_DATA_VERSION, FLATTENING_DATA_VERSION, LEVEL_WRAPPER_REMOVAL_DATA_VERSION,
+        PADDED_PACKED_ARRAYS_DATA_VERSION,
+    };
+    use crate::AnvilChunkProvider;
+    use nbt::CompoundTag;
+    use tempfile::TempDir;
+
+    fn legacy_chunk() -> CompoundTag {
+        let mut blocks = vec![0i8; 4096];
+        let mut data = vec![0i8; 2048];
+
+        // Bedrock layer.
+        for block in blocks.iter_mut().take(256) {
+            *block = 7;
+        }
+
+        // Red wool at x = 1, y = 1, z = 0.
+        blocks[257] = 35;
+        data[128] = (14 << 4) as i8;
+
+        let mut section = CompoundTag::new();
+        section.insert_i8("Y", 0);
+        section.insert_i8_vec("Blocks", blocks);
+        section.insert_i8_vec("Data", data);
+
+        let mut level = CompoundTag::new();
+        level.insert_i32("xPos", 1);
+        level.insert_i32("zPos", 2);
+        level.insert_bool("TerrainPopulated", true);
+        level.insert_i8_vec("Biomes", vec![-127i8; 256]);
+        level.insert_compound_tag_vec("Sections", vec![section]);
+
+        let mut chunk_compound_tag = CompoundTag::new();
+        chunk_compound_tag.insert_compound_tag("Level", level);
+
+        chunk_compound_tag
+    }
+
+    #[test]
+    fn test_flatten() {
+        let mut chunk_compound_tag = legacy_chunk();
+        upgrade_chunk(&mut chunk_compound_tag, FLATTENING_DATA_VERSION).unwrap();
+
+        let level = chunk_compound_tag.get_compound_tag("Level").unwrap();
+        let section = level.get_compound_tag_vec("Sections").unwrap()[0];
+        let palette = section.get_compound_tag_vec("Palette").unwrap();
+        let names: Vec<&str> = palette
+            .iter()
+            .map(|state| state.get_str("Name").unwrap())
+            .collect();
+
+        assert_eq!(
+            names,
+            vec!["minecraft:air", "minecraft:bedrock", "minecraft:red_wool"]
+        );
+
+        let block_states = section.get_i64_vec("BlockStates").unwrap();
+        let indices = unpack(block_states, 4, 4096, true);
+
+        assert_eq!(indices[0], 1);
+        assert_eq!(indices[257], 2);
+        assert_eq!(indices[258], 0);
+        assert!(!section.contains_key("Blocks"));
+        assert_eq!(level.get_i32_vec("Biomes").unwrap()[0], 129);
+        assert_eq!(level.get_str("Status").unwrap(), "postprocessed");
+        assert_eq!(
+            chunk_compound_tag.get_i32("DataVersion").unwrap(),
+            FLATTENING_DATA_VERSION
+        );
+    }
+
+    #[test]
+    fn test_extend_biomes_3d() {
+        let mut chunk_compound_tag = legacy_chunk();
+        upgrade_chunk(&mut chunk_compound_tag, BIOMES_3D_DATA_VERSION).unwrap();
+
+        let level = chunk_compound_tag.get_compound_tag("Level").unwrap();
+
+        assert_eq!(level.get_i32_vec("Biomes").unwrap().len(), 1024);
+        assert_eq!(level.get_str("Status").unwrap(), "full");
+    }
+
+    #[test]
+    fn test_pad_packed_arrays() {
+        let mut chunk_compound_tag = legacy_chunk();
+        upgrade_chunk(&mut chunk_compound_tag, PADDED_PACKED_ARRAYS_DATA_VERSION).unwrap();
+
+        let level = chunk_compound_tag.get_compound_tag("Level").unwrap();
+        let section = level.get_compound_tag_vec("Sections").unwrap()[0];
+        let block_states = section.get_i64_vec("BlockStates").unwrap();
+
+        // 4 bits values fit longs exactly, so length doesn't change.
+        assert_eq!(block_states.len(), 256);
+        assert_eq!(unpack(block_states, 4, 4096, false)[257], 2);
+    }
+
+    #[test]
+    fn test_remove_level_wrapper() {
+        let mut chunk_compound_tag = legacy_chunk();
+        upgrade_chunk(&mut chunk_compound_tag, LEVEL_WRAPPER_REMOVAL_DATA_VERSION).unwrap();
+
+        assert!(!chunk_compound_tag.contains_key("Level"));
+        assert_eq!(chunk_compound_tag.get_i32("xPos").unwrap(), 1);
+        assert_eq!(chunk_compound_tag.get_i32("yPos").unwrap(), 0);
+        assert_eq!(
+            chunk_compound_tag.get_str("Status").unwrap(),
+            "minecraft:full"
+        );
+
+        let section = chunk_compound_tag.get_compound_tag_vec("sections").unwrap()[0];
+        let block_states = section.get_compound_tag("block_states").unwrap();
+        let biomes = section.get_compound_tag("biomes").unwrap();
+
+        assert_eq!(
+            block_states.get_compound_tag_vec("palette").unwrap().len(),
+            3
+        );
+        assert_eq!(block_states.get_i64_vec("data").unwrap().len(), 256);
+        assert_eq!(
+            biomes.get_str_vec("palette").unwrap(),
+            vec!["minecraft:sunflower_plains"]
+        );
+        assert!(!biomes.contains_key("data"));
+    }
+
+    #[test]
+    fn test_upgrade_newer_data_version() {
+        let mut chunk_compound_tag = CompoundTag::new();
+        chunk_compound_tag.insert_i32("DataVersion", 2586);
+
+        match upgrade_chunk(&mut chunk_compound_tag, 1631) {
+            Err(ChunkUpgradeError::NewerDataVersion {
+                data_version,
+                target_data_version,
+            }) => {
+                assert_eq!(data_version, 2586);
+                assert_eq!(target_data_version, 1631);
+            }
+            result => panic!("Expected `NewerDataVersion` but got `{:?}`", result),
+        }
+    }
+
+    #[test]
+    fn test_upgrade_provider() {
+        let folder = TempDir::new().unwrap();
+        let chunk_provider = AnvilChunkProvider::new(folder.path().to_str().unwrap());
+
+        chunk_provider.save_chunk(1, 2, legacy_chunk()).unwrap();
+        chunk_provider.save_chunk(-40, 70, legacy_chunk()).unwrap();
+
+        let upgraded_chunks =
+            upgrade_provider(&chunk_provider, LEVEL_WRAPPER_REMOVAL_DATA_VERSION).unwrap();
+
+        assert_eq!(upgraded_chunks, 2);
+
+        let chunk_compound_tag = chunk_provider.load_chunk(-40, 70).unwrap();
+
+        assert!(chunk_compound_tag.contains_key("sections"));
+        assert_eq!(
+            upgrade_provider(&chunk_provider, LEVEL_WRAPPER_REMOVAL_DATA_VERSION).unwrap(),
+            0
+        );
+    }
+}
@@ -0,0 +1,646 @@
+//! Tables which map pre-flattening numeric identifiers to namespaced names.
+use nbt::CompoundTag;
+
+/// Dye colors in order of legacy data values.
+const COLORS: [&str; 16] = [
+    "white",
+    "orange",
+    "magenta",
+    "light_blue",
+    "yellow",
+    "lime",
+    "pink",
+    "gray",
+    "light_gray",
+    "cyan",
+    "purple",
+    "blue",
+    "brown",
+    "green",
+    "red",
+    "black",
+];
+
+/// Wood types in order of legacy data values.
+const WOODS: [&str; 6] = ["oak", "spruce", "birch", "jungle", "acacia", "dark_oak"];
+
+/// Block names of legacy ids with data value 0.
+///
+/// Unused ids are mapped to air.
+const BLOCKS: [&str; 256] = [
+    "air",
+    "stone",
+    "grass_block",
+    "dirt",
+    "cobblestone",
+    "oak_planks",
+    "oak_sapling",
+    "bedrock",
+    "water",
+    "water",
+    "lava",
+    "lava",
+    "sand",
+    "gravel",
+    "gold_ore",
+    "iron_ore",
+    "coal_ore",
+    "oak_log",
+    "oak_leaves",
+    "sponge",
+    "glass",
+    "lapis_ore",
+    "lapis_block",
+    "dispenser",
+    "sandstone",
+    "note_block",
+    "red_bed",
+    "powered_rail",
+    "detector_rail",
+    "sticky_piston",
+    "cobweb",
+    "grass",
+    "dead_bush",
+    "piston",
+    "piston_head",
+    "white_wool",
+    "moving_piston",
+    "dandelion",
+    "poppy",
+    "brown_mushroom",
+    "red_mushroom",
+    "gold_block",
+    "iron_block",
+    "stone_slab",
+    "stone_slab",
+    "bricks",
+    "tnt",
+    "bookshelf",
+    "mossy_cobblestone",
+    "obsidian",
+    "torch",
+    "fire",
+    "spawner",
+    "oak_stairs",
+    "chest",
+    "redstone_wire",
+    "diamond_ore",
+    "diamond_block",
+    "crafting_table",
+    "wheat",
+    "farmland",
+    "furnace",
+    "furnace",
+    "sign",
+    "oak_door",
+    "ladder",
+    "rail",
+    "cobblestone_stairs",
+    "wall_sign",
+    "lever",
+    "stone_pressure_plate",
+    "iron_door",
+    "oak_pressure_plate",
+    "redstone_ore",
+    "redstone_ore",
+    "redstone_torch",
+    "redstone_torch",
+    "stone_button",
+    "snow",
+    "ice",
+    "snow_block",
+    "cactus",
+    "clay",
+    "sugar_cane",
+    "jukebox",
+    "oak_fence",
+    "carved_pumpkin",
+    "netherrack",
+    "soul_sand",
+    "glowstone",
+    "nether_portal",
+    "jack_o_lantern",
+    "cake",
+    "repeater",
+    "repeater",
+    "white_stained_glass",
+    "oak_trapdoor",
+    "infested_stone",
+    "stone_bricks",
+    "brown_mushroom_block",
+    "red_mushroom_block",
+    "iron_bars",
+    "glass_pane",
+    "melon",
+    "pumpkin_stem",
+    "melon_stem",
+    "vine",
+    "oak_fence_gate",
+    "brick_stairs",
+    "stone_brick_stairs",
+    "mycelium",
+    "lily_pad",
+    "nether_bricks",
+    "nether_brick_fence",
+    "nether_brick_stairs",
+    "nether_wart",
+    "enchanting_table",
+    "brewing_stand",
+    "cauldron",
+    "end_portal",
+    "end_portal_frame",
+    "end_stone",
+    "dragon_egg",
+    "redstone_lamp",
+    "redstone_lamp",
+    "oak_slab",
+    "oak_slab",
+    "cocoa",
+    "sandstone_stairs",
+    "emerald_ore",
+    "ender_chest",
+    "tripwire_hook",
+    "tripwire",
+    "emerald_block",
+    "spruce_stairs",
+    "birch_stairs",
+    "jungle_stairs",
+    "command_block",
+    "beacon",
+    "cobblestone_wall",
+    "flower_pot",
+    "carrots",
+    "potatoes",
+    "oak_button",
+    "skeleton_skull",
+    "anvil",
+    "trapped_chest",
+    "light_weighted_pressure_plate",
+    "heavy_weighted_pressure_plate",
+    "comparator",
+    "comparator",
+    "daylight_detector",
+    "redstone_block",
+    "nether_quartz_ore",
+    "hopper",
+    "quartz_block",
+    "quartz_stairs",
+    "activator_rail",
+    "dropper",
+    "white_terracotta",
+    "white_stained_glass_pane",
+    "acacia_leaves",
+    "acacia_log",
+    "acacia_stairs",
+    "dark_oak_stairs",
+    "slime_block",
+    "barrier",
+    "iron_trapdoor",
+    "prismarine",
+    "sea_lantern",
+    "hay_block",
+    "white_carpet",
+    "terracotta",
+    "coal_block",
+    "packed_ice",
+    "sunflower",
+    "white_banner",
+    "white_wall_banner",
+    "daylight_detector",
+    "red_sandstone",
+    "red_sandstone_stairs",
+    "red_sandstone_slab",
+    "red_sandstone_slab",
+    "spruce_fence_gate",
+    "birch_fence_gate",
+    "jungle_fence_gate",
+    "dark_oak_fence_gate",
+    "acacia_fence_gate",
+    "spruce_fence",
+    "birch_fence",
+    "jungle_fence",
+    "dark_oak_fence",
+    "acacia_fence",
+    "spruce_door",
+    "birch_door",
+    "jungle_door",
+    "acacia_door",
+    "dark_oak_door",
+    "end_rod",
+    "chorus_plant",
+    "chorus_flower",
+    "purpur_block",
+    "purpur_pillar",
+    "purpur_stairs",
+    "purpur_slab",
+    "purpur_slab",
+    "end_stone_bricks",
+    "beetroots",
+    "grass_path",
+    "end_gateway",
+    "repeating_command_block",
+    "chain_command_block",
+    "frosted_ice",
+    "magma_block",
+    "nether_wart_block",
+    "red_nether_bricks",
+    "bone_block",
+    "structure_void",
+    "observer",
+    "white_shulker_box",
+    "orange_shulker_box",
+    "magenta_shulker_box",
+    "light_blue_shulker_box",
+    "yellow_shulker_box",
+    "lime_shulker_box",
+    "pink_shulker_box",
+    "gray_shulker_box",
+    "light_gray_shulker_box",
+    "cyan_shulker_box",
+    "purple_shulker_box",
+    "blue_shulker_box",
+    "brown_shulker_box",
+    "green_shulker_box",
+    "red_shulker_box",
+    "black_shulker_box",
+    "white_glazed_terracotta",
+    "orange_glazed_terracotta",
+    "magenta_glazed_terracotta",
+    "light_blue_glazed_terracotta",
+    "yellow_glazed_terracotta",
+    "lime_glazed_terracotta",
+    "pink_glazed_terracotta",
+    "gray_glazed_terracotta",
+    "light_gray_glazed_terracotta",
+    "cyan_glazed_terracotta",
+    "purple_glazed_terracotta",
+    "blue_glazed_terracotta",
+    "brown_glazed_terracotta",
+    "green_glazed_terracotta",
+    "red_glazed_terracotta",
+    "black_glazed_terracotta",
+    "white_concrete",
+    "white_concrete_powder",
+    "air",
+    "air",
+    "structure_block",
+];
+
+/// Returns flattened block state of legacy block id and data value.
+///
+/// Variants encoded in data value are resolved (stone types, wood types, colors,
+/// slab halves, log axes and similar), while orientation of directional blocks is
+/// left to the game defaults.
+pub(crate) fn block_state(id: u16, data: u8) -> CompoundTag {
+    let data = data & 0xF;
+    let mut properties: Vec<(&str, String)> = Vec::new();
+
+    let name = match id {
+        1 => match data {
+            1 => "granite",
+            2 => "polished_granite",
+            3 => "diorite",
+            4 => "polished_diorite",
+            5 => "andesite",
+            6 => "polished_andesite",
+            _ => "stone",
+        }
+        .to_owned(),
+        3 => match data {
+            1 => "coarse_dirt",
+            2 => "podzol",
+            _ => "dirt",
+        }
+        .to_owned(),
+        5 | 6 => format!(
+            "{}_{}",
+            WOODS[(data & 0x7).min(5) as usize],
+            if id == 5 { "planks" } else { "sapling" }
+        ),
+        8..=11 => {
+            properties.push(("level", data.to_string()));
+            BLOCKS[id as usize].to_owned()
+        }
+        12 if data == 1 => "red_sand".to_owned(),
+        17 | 162 => {
+            let wood_index = (data & 0x3) as usize + if id == 162 { 4 } else { 0 };
+            let axis = match data >> 2 {
+                1 => "x",
+                2 => "z",
+                _ => "y",
+            };
+
+            properties.push(("axis", axis.to_owned()));
+            format!("{}_log", WOODS[wood_index.min(5)])
+        }
+        18 | 161 => {
+            let wood_index = (data & 0x3) as usize + if id == 161 { 4 } else { 0 };
+
+            properties.push(("persistent", ((data & 0x4) != 0).to_string()));
+            format!("{}_leaves", WOODS[wood_index.min(5)])
+        }
+        19 if data == 1 => "wet_sponge".to_owned(),
+        24 | 179 => {
+            let prefix = if id == 179 { "red_" } else { "" };
+
+            match data {
+                1 => format!("chiseled_{}sandstone", prefix),
+                2 => format!("cut_{}sandstone", prefix),
+                _ => format!("{}sandstone", prefix),
+            }
+        }
+        31 => match data {
+            0 => "dead_bush",
+            2 => "fern",
+            _ => "grass",
+        }
+        .to_owned(),
+        35 | 95 | 159 | 160 | 171 | 251 | 252 => {
+            let suffix = match id {
+                35 => "wool",
+                95 => "stained_glass",
+                159 => "terracotta",
+                160 => "stained_glass_pane",
+                171 => "carpet",
+                251 => "concrete",
+                _ => "concrete_powder",
+            };
+
+            format!("{}_{}", COLORS[data as usize], suffix)
+        }
+        38 => match data {
+            1 => "blue_orchid",
+            2 => "allium",
+            3 => "azure_bluet",
+            4 => "red_tulip",
+            5 => "orange_tulip",
+            6 => "white_tulip",
+            7 => "pink_tulip",
+            8 => "oxeye_daisy",
+            _ => "poppy",
+        }
+        .to_owned(),
+        43 | 44 | 125 | 126 | 181 | 182 | 204 | 205 => {
+            let slab_type = match (id, data & 0x8) {
+                (43, _) | (125, _) | (181, _) | (204, _) => "double",
+                (_, 0) => "bottom",
+                _ => "top",
+            };
+            let name = match id {
+                43 | 44 => match data & 0x7 {
+                    1 => "sandstone_slab",
+                    2 => "petrified_oak_slab",
+                    3 => "cobblestone_slab",
+                    4 => "brick_slab",
+                    5 => "stone_brick_slab",
+                    6 => "nether_brick_slab",
+                    7 => "quartz_slab",
+                    _ => "stone_slab",
+                }
+                .to_owned(),
+                125 | 126 => format!("{}_slab", WOODS[(data & 0x7).min(5) as usize]),
+                181 | 182 => "red_sandstone_slab".to_owned(),
+                _ => "purpur_slab".to_owned(),
+            };
+
+            properties.push(("type", slab_type.to_owned()));
+            name
+        }
+        62 | 74 | 124 => {
+            properties.push(("lit", "true".to_owned()));
+            BLOCKS[id as usize].to_owned()
+        }
+        73 | 75 => {
+            properties.push(("lit", "false".to_owned()));
+            BLOCKS[id as usize].to_owned()
+        }
+        97 => match data {
+            1 => "infested_cobblestone",
+            2 => "infested_stone_bricks",
+            3 => "infested_mossy_stone_bricks",
+            4 => "infested_cracked_stone_bricks",
+            5 => "infested_chiseled_stone_bricks",
+            _ => "infested_stone",
+        }
+        .to_owned(),
+        98 => match data {
+            1 => "mossy_stone_bricks",
+            2 => "cracked_stone_bricks",
+            3 => "chiseled_stone_bricks",
+            _ => "stone_bricks",
+        }
+        .to_owned(),
+        139 if data == 1 => "mossy_cobblestone_wall".to_owned(),
+        145 => match data >> 2 {
+            1 => "chipped_anvil",
+            2 => "damaged_anvil",
+            _ => "anvil",
+        }
+        .to_owned(),
+        155 => match data {
+            1 => "chiseled_quartz_block".to_owned(),
+            2..=4 => {
+                let axis = match data {
+                    3 => "x",
+                    4 => "z",
+                    _ => "y",
+                };
+
+                properties.push(("axis", axis.to_owned()));
+                "quartz_pillar".to_owned()
+            }
+            _ => "quartz_block".to_owned(),
+        },
+        168 => match data {
+            1 => "prismarine_bricks",
+            2 => "dark_prismarine",
+            _ => "prismarine",
+        }
+        .to_owned(),
+        175 => {
+            let half = if data & 0x8 != 0 { "upper" } else { "lower" };
+            let name = match data & 0x7 {
+                1 => "lilac",
+                2 => "tall_grass",
+                3 => "large_fern",
+                4 => "rose_bush",
+                5 => "peony",
+                _ => "sunflower",
+            };
+
+            properties.push(("half", half.to_owned()));
+            name.to_owned()
+        }
+        178 => {
+            properties.push(("inverted", "true".to_owned()));
+            BLOCKS[id as usize].to_owned()
+        }
+        _ => BLOCKS.get(id as usize).copied().unwrap_or("air").to_owned(),
+    };
+
+    let mut block_state = CompoundTag::new();
+    block_state.insert_str("Name", format!("minecraft:{}", name));
+
+    if !properties.is_empty() {
+        let mut properties_compound_tag = CompoundTag::new();
+
+        for (property_name, value) in properties {
+            properties_compound_tag.insert_str(property_name, value);
+        }
+
+        block_state.insert_compound_tag("Properties", properties_compound_tag);
+    }
+
+    block_state
+}
+
+/// Returns 1.18 biome name of legacy numeric biome id.
+///
+/// Biomes removed in 1.18 are mapped to the biome which replaced them,
+/// unknown ids are mapped to plains.
+pub(crate) fn biome_name(id: i32) -> &'static str {
+    match id {
+        0 => "minecraft:ocean",
+        1 => "minecraft:plains",
+        2 | 17 | 130 => "minecraft:desert",
+        3 | 20 => "minecraft:windswept_hills",
+        4 | 18 => "minecraft:forest",
+        5 | 19 | 133 => "minecraft:taiga",
+        6 | 134 => "minecraft:swamp",
+        7 => "minecraft:river",
+        8 => "minecraft:nether_wastes",
+        9 => "minecraft:the_end",
+        10 => "minecraft:frozen_ocean",
+        11 => "minecraft:frozen_river",
+        12 | 13 => "minecraft:snowy_plains",
+        14 | 15 => "minecraft:mushroom_fields",
+        16 => "minecraft:beach",
+        21 | 22 | 149 => "minecraft:jungle",
+        23 | 151 => "minecraft:sparse_jungle",
+        24 => "minecraft:deep_ocean",
+        25 => "minecraft:stony_shore",
+        26 => "minecraft:snowy_beach",
+        27 | 28 => "minecraft:birch_forest",
+        29 | 157 => "minecraft:dark_forest",
+        30 | 31 | 158 => "minecraft:snowy_taiga",
+        32 | 33 => "minecraft:old_growth_pine_taiga",
+        34 => "minecraft:windswept_forest",
+        35 => "minecraft:savanna",
+        36 => "minecraft:savanna_plateau",
+        37 | 39 | 167 => "minecraft:badlands",
+        38 | 166 => "minecraft:wooded_badlands",
+        40 => "minecraft:small_end_islands",
+        41 => "minecraft:end_midlands",
+        42 => "minecraft:end_highlands",
+        43 => "minecraft:end_barrens",
+        44 | 47 => "minecraft:warm_ocean",
+        45 => "minecraft:lukewarm_ocean",
+        46 => "minecraft:cold_ocean",
+        48 => "minecraft:deep_lukewarm_ocean",
+        49 => "minecraft:deep_cold_ocean",
+        50 => "minecraft:deep_frozen_ocean",
+        127 => "minecraft:the_void",
+        129 => "minecraft:sunflower_plains",
+        131 | 162 => "minecraft:windswept_gravelly_hills",
+        132 => "minecraft:flower_forest",
+        140 => "minecraft:ice_spikes",
+        155 | 156 => "minecraft:old_growth_birch_forest",
+        160 | 161 => "minecraft:old_growth_spruce_taiga",
+        163 | 164 => "minecraft:windswept_savanna",
+        165 => "minecraft:eroded_badlands",
+        168 | 169 => "minecraft:bamboo_jungle",
+        170 => "minecraft:soul_sand_valley",
+        171 => "minecraft:crimson_forest",
+        172 => "minecraft:warped_forest",
+        173 => "minecraft:basalt_deltas",
+        174 => "minecraft:dripstone_caves",
+        175 => "minecraft:lush_caves",
+        _ => "minecraft:plains",
+    }
+}
+
+/// Returns legacy numeric id of 1.18 biome name.
+///
+/// Biomes which were merged in 1.18 are mapped to the lowest of their ids.
+pub(crate) fn biome_id(name: &str) -> Option<i32> {
+    (0..=255).find(|id| biome_name(*id) == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::upgrade::legacy::{biome_id, biome_name, block_state};
+
+    #[test]
+    fn test_block_state_simple() {
+        let block_state = block_state(1, 0);
+
+        assert_eq!(block_state.get_str("Name").unwrap(), "minecraft:stone");
+        assert!(!block_state.contains_key("Properties"));
+    }
+
+    #[test]
+    fn test_block_state_table_alignment() {
+        assert_eq!(
+            block_state(54, 0).get_str("Name").unwrap(),
+            "minecraft:chest"
+        );
+        assert_eq!(
+            block_state(137, 0).get_str("Name").unwrap(),
+            "minecraft:command_block"
+        );
+        assert_eq!(
+            block_state(219, 0).get_str("Name").unwrap(),
+            "minecraft:white_shulker_box"
+        );
+        assert_eq!(
+            block_state(255, 0).get_str("Name").unwrap(),
+            "minecraft:structure_block"
+        );
+    }
+
+    #[test]
+    fn test_block_state_variants() {
+        assert_eq!(
+            block_state(35, 14).get_str("Name").unwrap(),
+            "minecraft:red_wool"
+        );
+        assert_eq!(
+            block_state(5, 5).get_str("Name").unwrap(),
+            "minecraft:dark_oak_planks"
+        );
+        assert_eq!(
+            block_state(1, 3).get_str("Name").unwrap(),
+            "minecraft:diorite"
+        );
+        assert_eq!(
+            block_state(254, 0).get_str("Name").unwrap(),
+            "minecraft:air"
+        );
+    }
+
+    #[test]
+    fn test_block_state_properties() {
+        let block_state = block_state(162, 0b0101);
+        let properties = block_state.get_compound_tag("Properties").unwrap();
+
+        assert_eq!(
+            block_state.get_str("Name").unwrap(),
+            "minecraft:dark_oak_log"
+        );
+        assert_eq!(properties.get_str("axis").unwrap(), "x");
+    }
+
+    #[test]
+    fn test_biome_name() {
+        assert_eq!(biome_name(4), "minecraft:forest");
+        assert_eq!(biome_name(3), "minecraft:windswept_hills");
+        assert_eq!(biome_name(1000), "minecraft:plains");
+    }
+
+    #[test]
+    fn test_biome_id() {
+        assert_eq!(biome_id("minecraft:plains"), Some(1));
+        assert_eq!(biome_id("minecraft:windswept_hills"), Some(3));
+        assert_eq!(biome_id("minecraft:meadow"), None);
+    }
+}
@@ -0,0 +1,476 @@
+//! Checking chunks against the format expected by the game.
+//!
+//! The game silently regenerates or drops chunks it can't read, so edited worlds
+//! should be validated before they are handed back to it.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::validate::validate_chunk;
+//! use anvil_region::AnvilChunkProvider;
+//!
+//! let chunk_provider = AnvilChunkProvider::new("test/region");
+//! let chunk_compound_tag = chunk_provider.load_chunk(4, 2).unwrap();
+//!
+//! assert!(validate_chunk(&chunk_compound_tag).is_empty());
+//! ```
+use crate::chunk::{Chunk, ChunkTag};
+use crate::packed::{packed_length, palette_bits, unpacked};
+use crate::path::{join_index, join_name};
+use crate::section::{block_state_bits, SectionFormat, SECTION_BIOMES, SECTION_BLOCKS};
+use crate::tag::get_tag;
+use crate::upgrade::HEIGHTMAP_COLUMNS;
+use crate::version::{
+    BIOMES_3D_DATA_VERSION, FLATTENING_DATA_VERSION, LEVEL_WRAPPER_REMOVAL_DATA_VERSION,
+};
+use nbt::{CompoundTag, Tag};
+
+/// Length of legacy 2D biome array.
+const BIOMES_2D_LENGTH: usize = 256;
+/// Length of 3D biome array used before biomes moved into sections.
+const BIOMES_3D_LENGTH: usize = 1024;
+/// Length of light and legacy block data arrays.
+const NIBBLE_ARRAY_LENGTH: usize = SECTION_BLOCKS / 2;
+
+/// Problem found in chunk.
+#[derive(Debug, Clone, Eq, PartialEq)]
+// Expected tag type names are static, so violations are only serialized.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Violation {
+    /// Path to tag in syntax accepted by [`crate::path`].
+    pub path: String,
+    /// What is wrong with tag.
+    pub kind: ViolationKind,
+}
+
+/// Possible problems of chunk tags.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ViolationKind {
+    /// Required tag is missing.
+    MissingTag,
+    /// Tag has unexpected type.
+    WrongType {
+        /// Expected tag type name.
+        expected: &'static str,
+    },
+    /// Array or list has unexpected length.
+    WrongLength {
+        length: usize,
+        expected_length: usize,
+    },
+    /// Palette doesn't contain any entry.
+    EmptyPalette,
+    /// Packed data refers to palette entry which doesn't exist.
+    PaletteIndexOutOfBounds { index: u16, palette_length: usize },
+}
+
+/// Checks required tags, array lengths and palette indices for chunk data version.
+///
+/// Returns empty list if no problems found. Chunks without data version can't be
+/// checked further than reporting missing data version.
+pub fn validate_chunk(chunk_compound_tag: &CompoundTag) -> Vec<Violation> {
+    let mut validator = Validator::default();
+
+    let data_version = match validator.int(chunk_compound_tag, "", "DataVersion") {
+        Some(data_version) => data_version,
+        None => return validator.violations,
+    };
+
+    let chunk = Chunk::new(chunk_compound_tag.clone());
+    let data = chunk.data();
+    let section_format = SectionFormat::from_data_version(data_version).ok();
+
+    // Data tags are nested in level compound for older versions.
+    let path = if chunk.has_level_wrapper() {
+        "Level"
+    } else if data_version < LEVEL_WRAPPER_REMOVAL_DATA_VERSION {
+        validator.typed(chunk_compound_tag, "", "Level", "Compound");
+        return validator.violations;
+    } else {
+        ""
+    };
+
+    validator.int(data, path, "xPos");
+    validator.int(data, path, "zPos");
+
+    if data_version >= FLATTENING_DATA_VERSION {
+        validator.typed(data, path, "Status", "String");
+    }
+
+    validator.validate_heightmaps(
+        data,
+        path,
+        section_format,
+        chunk.height_range().heightmap_bits(),
+    );
+
+    if data_version < LEVEL_WRAPPER_REMOVAL_DATA_VERSION {
+        let expected_length = if data_version < BIOMES_3D_DATA_VERSION {
+            BIOMES_2D_LENGTH
+        } else {
+            BIOMES_3D_LENGTH
+        };
+
+        if let Some(tag) = get_tag(data, "Biomes") {
+            let path = join_name(path, "Biomes");
+
+            match tag {
+                Tag::IntArray(biomes) => validator.length(&path, biomes.len(), expected_length),
+                // Byte biomes are used before the flattening.
+                Tag::ByteArray(biomes) => validator.length(&path, biomes.len(), BIOMES_2D_LENGTH),
+                _ => validator.push(
+                    &path,
+                    ViolationKind::WrongType {
+                        expected: "IntArray",
+                    },
+                ),
+            }
+        }
+    }
+
+    let sections_name = chunk.tag_name(ChunkTag::Sections);
+    let sections_path = join_name(path, sections_name);
+
+    let sections = match validator.typed(data, path, sections_name, "List") {
+        Some(Tag::List(sections)) => sections,
+        _ => return validator.violations,
+    };
+
+    for (index, section) in sections.iter().enumerate() {
+        let path = join_index(&sections_path, index);
+
+        match section {
+            Tag::Compound(section) => validator.validate_section(section, &path, section_format),
+            _ => validator.push(
+                &path,
+                ViolationKind::WrongType {
+                    expected: "Compound",
+                },
+            ),
+        }
+    }
+
+    validator.violations
+}
+
+/// Collects violations while walking chunk tags.
+#[derive(Default)]
+struct Validator {
+    violations: Vec<Violation>,
+}
+
+impl Validator {
+    fn push(&mut self, path: &str, kind: ViolationKind) {
+        self.violations.push(Violation {
+            path: path.to_owned(),
+            kind,
+        });
+    }
+
+    fn length(&mut self, path: &str, length: usize, expected_length: usize) {
+        if length != expected_length {
+            self.push(
+                path,
+                ViolationKind::WrongLength {
+                    length,
+                    expected_length,
+                },
+            );
+        }
+    }
+
+    /// Returns required tag if it has expected type.
+    fn typed<'a>(
+        &mut self,
+        compound_tag: &'a CompoundTag,
+        path: &str,
+        name: &str,
+        expected: &'static str,
+    ) -> Option<&'a Tag> {
+        let path = join_name(path, name);
+
+        let tag = match get_tag(compound_tag, name) {
+            Some(tag) => tag,
+            None => {
+                self.push(&path, ViolationKind::MissingTag);
+                return None;
+            }
+        };
+
+        if tag_type_name(tag) != expected {
+            self.push(&path, ViolationKind::WrongType { expected });
+            return None;
+        }
+
+        Some(tag)
+    }
+
+    fn int(&mut self, compound_tag: &CompoundTag, path: &str, name: &str) -> Option<i32> {
+        match self.typed(compound_tag, path, name, "Int") {
+            Some(Tag::Int(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn validate_heightmaps(
+        &mut self,
+        data: &CompoundTag,
+        path: &str,
+        section_format: Option<SectionFormat>,
+        heightmap_bits: u32,
+    ) {
+        let heightmaps = match get_tag(data, "Heightmaps") {
+            Some(Tag::Compound(heightmaps)) => heightmaps,
+            Some(_) => {
+                let path = join_name(path, "Heightmaps");
+                self.push(
+                    &path,
+                    ViolationKind::WrongType {
+                        expected: "Compound",
+                    },
+                );
+                return;
+            }
+            None => return,
+        };
+
+        let path = join_name(path, "Heightmaps");
+        let spanning = section_format != Some(SectionFormat::Padded)
+            && section_format != Some(SectionFormat::Modern);
+        let expected_length = packed_length(heightmap_bits, HEIGHTMAP_COLUMNS, spanning);
+
+        for (name, heightmap) in heightmaps.iter() {
+            let path = join_name(&path, name);
+
+            match heightmap {
+                Tag::LongArray(heightmap) => self.length(&path, heightmap.len(), expected_length),
+                _ => self.push(
+                    &path,
+                    ViolationKind::WrongType {
+                        expected: "LongArray",
+                    },
+                ),
+            }
+        }
+    }
+
+    fn validate_section(
+        &mut self,
+        section: &CompoundTag,
+        path: &str,
+        section_format: Option<SectionFormat>,
+    ) {
+        self.typed(section, path, "Y", "Byte");
+
+        for name in &["BlockLight", "SkyLight"] {
+            match get_tag(section, name) {
+                Some(Tag::ByteArray(light)) => {
+                    self.length(&join_name(path, name), light.len(), NIBBLE_ARRAY_LENGTH)
+                }
+                Some(_) => self.push(
+                    &join_name(path, name),
+                    ViolationKind::WrongType {
+                        expected: "ByteArray",
+                    },
+                ),
+                None => {}
+            }
+        }
+
+        match section_format {
+            None => {
+                // Numeric block ids before the flattening.
+                if let Some(Tag::ByteArray(blocks)) =
+                    self.typed(section, path, "Blocks", "ByteArray")
+                {
+                    self.length(&join_name(path, "Blocks"), blocks.len(), SECTION_BLOCKS);
+                }
+
+                if let Some(Tag::ByteArray(data)) = self.typed(section, path, "Data", "ByteArray") {
+                    self.length(&join_name(path, "Data"), data.len(), NIBBLE_ARRAY_LENGTH);
+                }
+            }
+            Some(SectionFormat::Modern) => {
+                if let Some(Tag::Compound(block_states)) = get_tag(section, "block_states") {
+                    self.validate_palette(
+                        block_states,
+                        &join_name(path, "block_states"),
+                        ("palette", "data"),
+                        SECTION_BLOCKS,
+                        SectionFormat::Modern,
+                        |palette_length| block_state_bits(palette_length, SectionFormat::Modern),
+                    );
+                }
+
+                if let Some(Tag::Compound(biomes)) = get_tag(section, "biomes") {
+                    self.validate_palette(
+                        biomes,
+                        &join_name(path, "biomes"),
+                        ("palette", "data"),
+                        SECTION_BIOMES,
+                        SectionFormat::Modern,
+                        |palette_length| palette_bits(palette_length, 0),
+                    );
+                }
+            }
+            Some(section_format) => {
+                // Sections without blocks don't have palette before 1.18.
+                if get_tag(section, "Palette").is_some() {
+                    self.validate_palette(
+                        section,
+                        path,
+                        ("Palette", "BlockStates"),
+                        SECTION_BLOCKS,
+                        section_format,
+                        |palette_length| block_state_bits(palette_length, section_format),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Validates palette and packed indices into it.
+    fn validate_palette(
+        &mut self,
+        compound_tag: &CompoundTag,
+        path: &str,
+        (palette_name, data_name): (&str, &str),
+        length: usize,
+        section_format: SectionFormat,
+        bits: impl Fn(usize) -> u32,
+    ) {
+        let palette_length = match self.typed(compound_tag, path, palette_name, "List") {
+            Some(Tag::List(palette)) => palette.len(),
+            _ => return,
+        };
+
+        if palette_length == 0 {
+            self.push(&join_name(path, palette_name), ViolationKind::EmptyPalette);
+            return;
+        }
+
+        let bits = bits(palette_length);
+
+        // Single entry palette doesn't need data since 1.18.
+        if bits == 0 {
+            return;
+        }
+
+        let data = match self.typed(compound_tag, path, data_name, "LongArray") {
+            Some(Tag::LongArray(data)) => data,
+            _ => return,
+        };
+
+        let data_path = join_name(path, data_name);
+        let spanning = section_format == SectionFormat::Spanning;
+        let expected_length = packed_length(bits, length, spanning);
+
+        if data.len() != expected_length {
+            self.length(&data_path, data.len(), expected_length);
+            return;
+        }
+
+        let out_of_bounds =
+            unpacked(data, bits, length, spanning).find(|index| *index as usize >= palette_length);
+
+        if let Some(index) = out_of_bounds {
+            self.push(
+                &data_path,
+                ViolationKind::PaletteIndexOutOfBounds {
+                    index,
+                    palette_length,
+                },
+            );
+        }
+    }
+}
+
+/// Returns type name of tag as used in violation messages.
+fn tag_type_name(tag: &Tag) -> &'static str {
+    match tag {
+        Tag::Byte(_) => "Byte",
+        Tag::Short(_) => "Short",
+        Tag::Int(_) => "Int",
+        Tag::Long(_) => "Long",
+        Tag::Float(_) => "Float",
+        Tag::Double(_) => "Double",
+        Tag::ByteArray(_) => "ByteArray",
+        Tag::String(_) => "String",
+        Tag::List(_) => "List",
+        Tag::Compound(_) => "Compound",
+        Tag::IntArray(_) => "IntArray",
+        Tag::LongArray(_) => "LongArray",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::path::{get_path_mut, set_path};
+    use crate::validate::{validate_chunk, Violation, ViolationKind};
+    use crate::AnvilChunkProvider;
+    use nbt::{CompoundTag, Tag};
+
+    fn fixture_chunk() -> CompoundTag {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+
+        chunk_provider.load_chunk(4, 2).unwrap()
+    }
+
+    #[test]
+    fn test_validate_chunk_missing_data_version() {
+        assert_eq!(
+            validate_chunk(&CompoundTag::new()),
+            vec![Violation {
+                path: "DataVersion".to_owned(),
+                kind: ViolationKind::MissingTag,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_validate_chunk_wrong_tags() {
+        let mut chunk_compound_tag = fixture_chunk();
+        set_path(&mut chunk_compound_tag, "Level.xPos", 1i64).unwrap();
+        set_path(&mut chunk_compound_tag, "Level.Biomes", vec![1; 16]).unwrap();
+
+        let violations = validate_chunk(&chunk_compound_tag);
+
+        assert_eq!(
+            violations,
+            vec![
+                Violation {
+                    path: "Level.xPos".to_owned(),
+                    kind: ViolationKind::WrongType { expected: "Int" },
+                },
+                Violation {
+                    path: "Level.Biomes".to_owned(),
+                    kind: ViolationKind::WrongLength {
+                        length: 16,
+                        expected_length: 256,
+                    },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_chunk_palette_index_out_of_bounds() {
+        let mut chunk_compound_tag = fixture_chunk();
+
+        match get_path_mut(&mut chunk_compound_tag, "Level.Sections[1].BlockStates") {
+            Ok(Tag::LongArray(block_states)) => block_states[0] = -1,
+            result => panic!("Expected `LongArray` but got `{:?}`", result),
+        }
+
+        let violations = validate_chunk(&chunk_compound_tag);
+
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].path, "Level.Sections[1].BlockStates");
+
+        match violations[0].kind {
+            ViolationKind::PaletteIndexOutOfBounds { .. } => {}
+            ref kind => panic!("Expected `PaletteIndexOutOfBounds` but got `{:?}`", kind),
+        }
+    }
+}
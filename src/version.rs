@@ -0,0 +1,175 @@
+//! Chunk data version detection.
+//!
+//! Since 1.9 every chunk stores `DataVersion` tag with number of the game version
+//! which written it. More information https://minecraft.gamepedia.com/Data_version.
+use nbt::CompoundTag;
+
+/// Data version of 17w47a which replaced numeric block ids with block state palettes.
+pub const FLATTENING_DATA_VERSION: i32 = 1451;
+/// Data version of 1.14 which renamed chunk statuses.
+pub const CHUNK_STATUS_RENAME_DATA_VERSION: i32 = 1952;
+/// Data version of 19w36a which made biomes three-dimensional.
+pub const BIOMES_3D_DATA_VERSION: i32 = 2203;
+/// Data version of 20w17a since which packed values don't span across longs.
+pub const PADDED_PACKED_ARRAYS_DATA_VERSION: i32 = 2527;
+/// Data version of 1.17 which added first part of caves and cliffs blocks.
+pub const CAVES_AND_CLIFFS_DATA_VERSION: i32 = 2724;
+/// Data version of 21w43a which removed `Level` wrapper and moved biomes into sections.
+pub const LEVEL_WRAPPER_REMOVAL_DATA_VERSION: i32 = 2844;
+
+/// Minecraft release with data version which it writes.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Release {
+    /// Data version written by release.
+    pub data_version: i32,
+    /// Release name, for example `1.16.5`.
+    pub name: &'static str,
+}
+
+impl Release {
+    const fn new(data_version: i32, name: &'static str) -> Self {
+        Release { data_version, name }
+    }
+}
+
+/// Known releases in ascending order of data version.
+pub const RELEASES: &[Release] = &[
+    Release::new(169, "1.9"),
+    Release::new(175, "1.9.1"),
+    Release::new(176, "1.9.2"),
+    Release::new(183, "1.9.3"),
+    Release::new(184, "1.9.4"),
+    Release::new(510, "1.10"),
+    Release::new(511, "1.10.1"),
+    Release::new(512, "1.10.2"),
+    Release::new(819, "1.11"),
+    Release::new(921, "1.11.1"),
+    Release::new(922, "1.11.2"),
+    Release::new(1139, "1.12"),
+    Release::new(1241, "1.12.1"),
+    Release::new(1343, "1.12.2"),
+    Release::new(1519, "1.13"),
+    Release::new(1628, "1.13.1"),
+    Release::new(1631, "1.13.2"),
+    Release::new(1952, "1.14"),
+    Release::new(1957, "1.14.1"),
+    Release::new(1963, "1.14.2"),
+    Release::new(1968, "1.14.3"),
+    Release::new(1976, "1.14.4"),
+    Release::new(2225, "1.15"),
+    Release::new(2227, "1.15.1"),
+    Release::new(2230, "1.15.2"),
+    Release::new(2566, "1.16"),
+    Release::new(2567, "1.16.1"),
+    Release::new(2578, "1.16.2"),
+    Release::new(2580, "1.16.3"),
+    Release::new(2584, "1.16.4"),
+    Release::new(2586, "1.16.5"),
+    Release::new(2724, "1.17"),
+    Release::new(2730, "1.17.1"),
+    Release::new(2860, "1.18"),
+    Release::new(2865, "1.18.1"),
+    Release::new(2975, "1.18.2"),
+    Release::new(3105, "1.19"),
+    Release::new(3117, "1.19.1"),
+    Release::new(3120, "1.19.2"),
+    Release::new(3218, "1.19.3"),
+    Release::new(3337, "1.19.4"),
+    Release::new(3463, "1.20"),
+    Release::new(3465, "1.20.1"),
+    Release::new(3578, "1.20.2"),
+    Release::new(3698, "1.20.3"),
+    Release::new(3700, "1.20.4"),
+    Release::new(3837, "1.20.5"),
+    Release::new(3839, "1.20.6"),
+    Release::new(3953, "1.21"),
+    Release::new(3955, "1.21.1"),
+    Release::new(4080, "1.21.2"),
+    Release::new(4082, "1.21.3"),
+    Release::new(4189, "1.21.4"),
+    Release::new(4325, "1.21.5"),
+    Release::new(4435, "1.21.6"),
+    Release::new(4438, "1.21.7"),
+    Release::new(4440, "1.21.8"),
+];
+
+/// Returns data version of the chunk.
+///
+/// Chunks written before 1.9 don't have data version.
+///
+/// # Example
+///
+/// ```
+/// use anvil_region::AnvilChunkProvider;
+/// use anvil_region::version::{chunk_data_version, closest_release};
+///
+/// let chunk_provider = AnvilChunkProvider::new("test/region");
+/// let chunk_compound_tag = chunk_provider.load_chunk(4, 2).unwrap();
+///
+/// let data_version = chunk_data_version(&chunk_compound_tag).unwrap();
+/// let release = closest_release(data_version).unwrap();
+///
+/// assert_eq!(format!("chunk written by {}", release.name), "chunk written by 1.13.2");
+/// ```
+pub fn chunk_data_version(chunk_compound_tag: &CompoundTag) -> Option<i32> {
+    chunk_compound_tag.get_i32("DataVersion").ok()
+}
+
+/// Returns release which writes exactly specified data version.
+pub fn release(data_version: i32) -> Option<Release> {
+    RELEASES
+        .binary_search_by_key(&data_version, |release| release.data_version)
+        .ok()
+        .map(|index| RELEASES[index])
+}
+
+/// Returns the latest release which data version is not newer than specified.
+///
+/// Snapshots and pre-releases data versions don't match any release
+/// so they are attributed to the previous release.
+pub fn closest_release(data_version: i32) -> Option<Release> {
+    let index = RELEASES.partition_point(|release| release.data_version <= data_version);
+
+    index.checked_sub(1).map(|index| RELEASES[index])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::version::{chunk_data_version, closest_release, release, RELEASES};
+    use nbt::CompoundTag;
+
+    #[test]
+    fn test_releases_sorted() {
+        for window in RELEASES.windows(2) {
+            assert!(window[0].data_version < window[1].data_version);
+        }
+    }
+
+    #[test]
+    fn test_chunk_data_version() {
+        let mut chunk_compound_tag = CompoundTag::new();
+        assert_eq!(chunk_data_version(&chunk_compound_tag), None);
+
+        chunk_compound_tag.insert_i32("DataVersion", 2586);
+        assert_eq!(chunk_data_version(&chunk_compound_tag), Some(2586));
+    }
+
+    #[test]
+    fn test_release() {
+        assert_eq!(release(2586).unwrap().name, "1.16.5");
+        assert_eq!(release(1631).unwrap().name, "1.13.2");
+        assert!(release(2585).is_none());
+    }
+
+    #[test]
+    fn test_closest_release() {
+        // 20w45a snapshot.
+        assert_eq!(closest_release(2681).unwrap().name, "1.16.5");
+        assert_eq!(closest_release(169).unwrap().name, "1.9");
+        assert_eq!(
+            closest_release(i32::MAX).unwrap(),
+            *RELEASES.last().unwrap()
+        );
+        assert!(closest_release(100).is_none());
+    }
+}
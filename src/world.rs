@@ -0,0 +1,1317 @@
+//! Whole world folder with its dimensions and player data.
+//!
+//! World folder contains `level.dat`, player data and a folder per dimension
+//! with `region`, `entities` and `poi` region folders. Overworld is stored in world
+//! folder itself, nether in `DIM-1`, end in `DIM1` and datapack dimensions in
+//! `dimensions/<namespace>/<name>`.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::level::LevelData;
+//! use anvil_region::world::AnvilWorld;
+//! use tempfile::TempDir;
+//!
+//! let world_dir = TempDir::new().unwrap();
+//! LevelData::new().save(world_dir.path()).unwrap();
+//!
+//! let world = AnvilWorld::open(world_dir.path()).unwrap();
+//! let overworld = world.overworld();
+//! let chunk_provider = overworld.chunk_provider();
+//!
+//! assert!(chunk_provider.chunk_positions().unwrap().is_empty());
+//! ```
+use crate::cancel::CancellationToken;
+use crate::data::DataFileError;
+use crate::level::{LevelData, LEVEL_DAT_FILE, LEVEL_DAT_OLD_FILE};
+use crate::limits::ParseLimits;
+use crate::player::advancements::AdvancementsProvider;
+use crate::player::stats::StatsProvider;
+use crate::player::PlayerDataProvider;
+use crate::progress::{ProgressReporter, ProgressTracker};
+use crate::{zip, AnvilChunkProvider, AnvilRegion, ChunkLoadError, RegionFile};
+use nbt::CompoundTag;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::thread::{self, JoinHandle};
+use std::{fmt, fs, io, panic, vec};
+
+/// Folder of dimension which contains terrain region files.
+pub(crate) const REGION_FOLDER: &str = "region";
+/// Folder of dimension which contains entity region files.
+pub(crate) const ENTITIES_FOLDER: &str = "entities";
+/// Folder of dimension which contains point of interest region files.
+pub(crate) const POI_FOLDER: &str = "poi";
+/// Folder of world which contains player files.
+pub(crate) const PLAYER_DATA_FOLDER: &str = "playerdata";
+/// Folder of world which contains player advancements files.
+const ADVANCEMENTS_FOLDER: &str = "advancements";
+/// Folder of world which contains player statistics files.
+const STATS_FOLDER: &str = "stats";
+/// Folder of nether dimension inside of world folder.
+const NETHER_FOLDER: &str = "DIM-1";
+/// Folder of end dimension inside of world folder.
+const END_FOLDER: &str = "DIM1";
+/// Folder of world which contains datapack dimensions by namespace.
+pub(crate) const DIMENSIONS_FOLDER: &str = "dimensions";
+
+/// Dimension of world.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Dimension {
+    Overworld,
+    Nether,
+    End,
+    /// Dimension added by datapack or mod with namespace and name.
+    Custom(String, String),
+}
+
+impl Dimension {
+    /// Returns dimension by its identifier like `minecraft:the_nether`.
+    ///
+    /// Identifier without namespace belongs to `minecraft` namespace.
+    pub fn from_name(name: &str) -> Self {
+        let (namespace, path) = match name.find(':') {
+            Some(index) => (&name[..index], &name[index + 1..]),
+            None => ("minecraft", name),
+        };
+
+        match (namespace, path) {
+            ("minecraft", "overworld") => Dimension::Overworld,
+            ("minecraft", "the_nether") => Dimension::Nether,
+            ("minecraft", "the_end") => Dimension::End,
+            _ => Dimension::Custom(namespace.to_owned(), path.to_owned()),
+        }
+    }
+
+    /// Returns dimension identifier.
+    pub fn name(&self) -> String {
+        match self {
+            Dimension::Overworld => "minecraft:overworld".to_owned(),
+            Dimension::Nether => "minecraft:the_nether".to_owned(),
+            Dimension::End => "minecraft:the_end".to_owned(),
+            Dimension::Custom(namespace, name) => format!("{}:{}", namespace, name),
+        }
+    }
+
+    /// Returns dimension folder inside of world folder.
+    pub fn folder_path(&self, world_folder_path: &Path) -> PathBuf {
+        match self {
+            Dimension::Overworld => world_folder_path.to_path_buf(),
+            Dimension::Nether => world_folder_path.join(NETHER_FOLDER),
+            Dimension::End => world_folder_path.join(END_FOLDER),
+            Dimension::Custom(namespace, name) => world_folder_path
+                .join(DIMENSIONS_FOLDER)
+                .join(namespace)
+                .join(name),
+        }
+    }
+}
+
+/// Possible errors while opening world.
+#[derive(Debug)]
+pub enum WorldError {
+    /// Folder contains neither `level.dat` nor `level.dat_old`.
+    LevelDatNotFound {
+        /// Path to world folder.
+        folder_path: PathBuf,
+    },
+    /// `level.dat` can't be read or written.
+    DataFileError { data_file_error: DataFileError },
+    /// World archive can't be extracted.
+    ZipError { io_error: io::Error },
+}
+
+impl From<DataFileError> for WorldError {
+    fn from(data_file_error: DataFileError) -> Self {
+        WorldError::DataFileError { data_file_error }
+    }
+}
+
+/// Region folders of single dimension.
+#[derive(Debug, Clone)]
+pub struct WorldDimension {
+    folder_path: PathBuf,
+    region_folder_path: PathBuf,
+    entities_folder_path: PathBuf,
+    poi_folder_path: PathBuf,
+}
+
+impl WorldDimension {
+    /// Creates dimension stored in folder.
+    pub fn new(folder_path: &Path) -> Self {
+        WorldDimension {
+            folder_path: folder_path.to_path_buf(),
+            region_folder_path: folder_path.join(REGION_FOLDER),
+            entities_folder_path: folder_path.join(ENTITIES_FOLDER),
+            poi_folder_path: folder_path.join(POI_FOLDER),
+        }
+    }
+
+    pub fn folder_path(&self) -> &Path {
+        &self.folder_path
+    }
+
+    /// Returns provider of terrain chunks.
+    pub fn chunk_provider(&self) -> AnvilChunkProvider<'_> {
+        AnvilChunkProvider::from_path(&self.region_folder_path)
+    }
+
+    /// Returns provider of entity chunks stored separately since 1.17.
+    pub fn entity_chunk_provider(&self) -> AnvilChunkProvider<'_> {
+        AnvilChunkProvider::from_path(&self.entities_folder_path)
+    }
+
+    /// Returns provider of point of interest chunks like villager workstations.
+    pub fn poi_chunk_provider(&self) -> AnvilChunkProvider<'_> {
+        AnvilChunkProvider::from_path(&self.poi_folder_path)
+    }
+}
+
+/// World folder with loaded `level.dat`.
+#[derive(Debug, Clone)]
+pub struct AnvilWorld {
+    folder_path: PathBuf,
+    level_data: LevelData,
+    player_data_folder_path: PathBuf,
+    advancements_folder_path: PathBuf,
+    stats_folder_path: PathBuf,
+}
+
+impl AnvilWorld {
+    /// Opens world folder reading its `level.dat`.
+    pub fn open(folder_path: impl AsRef<Path>) -> Result<Self, WorldError> {
+        let folder_path = folder_path.as_ref();
+
+        if !folder_path.join(LEVEL_DAT_FILE).is_file()
+            && !folder_path.join(LEVEL_DAT_OLD_FILE).is_file()
+        {
+            return Err(WorldError::LevelDatNotFound {
+                folder_path: folder_path.to_path_buf(),
+            });
+        }
+
+        let level_data = LevelData::load(folder_path)?;
+
+        Ok(AnvilWorld {
+            folder_path: folder_path.to_path_buf(),
+            level_data,
+            player_data_folder_path: folder_path.join(PLAYER_DATA_FOLDER),
+            advancements_folder_path: folder_path.join(ADVANCEMENTS_FOLDER),
+            stats_folder_path: folder_path.join(STATS_FOLDER),
+        })
+    }
+
+    /// Extracts world archive into folder and opens it.
+    ///
+    /// World may be stored at archive root or in its single top level folder.
+    pub fn open_zip(
+        zip_path: impl AsRef<Path>,
+        extract_folder_path: impl AsRef<Path>,
+    ) -> Result<Self, WorldError> {
+        AnvilWorld::open_zip_with_limits(zip_path, extract_folder_path, &ParseLimits::default())
+    }
+
+    /// Extracts world archive from untrusted source into folder and opens it, see
+    /// [`open_zip`].
+    ///
+    /// Extraction fails once extracted data exceeds maximum archive length of limits,
+    /// with [`LimitExceeded`] inside of zip I/O error. Other limits apply to providers
+    /// with limits set.
+    ///
+    /// [`open_zip`]: AnvilWorld::open_zip
+    /// [`LimitExceeded`]: crate::limits::LimitExceeded
+    pub fn open_zip_with_limits(
+        zip_path: impl AsRef<Path>,
+        extract_folder_path: impl AsRef<Path>,
+        parse_limits: &ParseLimits,
+    ) -> Result<Self, WorldError> {
+        let extract_folder_path = extract_folder_path.as_ref();
+
+        zip::extract(
+            zip_path.as_ref(),
+            extract_folder_path,
+            parse_limits.max_archive_length,
+        )
+        .map_err(|io_error| WorldError::ZipError { io_error })?;
+
+        if extract_folder_path.join(LEVEL_DAT_FILE).is_file() {
+            return AnvilWorld::open(extract_folder_path);
+        }
+
+        let folder_paths: Vec<PathBuf> = fs::read_dir(extract_folder_path)
+            .map_err(|io_error| WorldError::ZipError { io_error })?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.join(LEVEL_DAT_FILE).is_file())
+            .collect();
+
+        match folder_paths.as_slice() {
+            [folder_path] => AnvilWorld::open(folder_path),
+            _ => Err(WorldError::LevelDatNotFound {
+                folder_path: extract_folder_path.to_path_buf(),
+            }),
+        }
+    }
+
+    pub fn folder_path(&self) -> &Path {
+        &self.folder_path
+    }
+
+    pub fn level_data(&self) -> &LevelData {
+        &self.level_data
+    }
+
+    pub fn level_data_mut(&mut self) -> &mut LevelData {
+        &mut self.level_data
+    }
+
+    /// Writes `level.dat` back to world folder.
+    pub fn save_level_data(&self) -> Result<(), DataFileError> {
+        self.level_data.save(&self.folder_path)
+    }
+
+    /// Returns region folders of dimension.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use anvil_region::level::LevelData;
+    /// use anvil_region::world::{AnvilWorld, Dimension};
+    /// use tempfile::TempDir;
+    ///
+    /// let world_dir = TempDir::new().unwrap();
+    /// LevelData::new().save(world_dir.path()).unwrap();
+    ///
+    /// let world = AnvilWorld::open(world_dir.path()).unwrap();
+    /// let dimension = world.dimension(&Dimension::from_name("mymod:mining"));
+    ///
+    /// assert!(dimension.folder_path().ends_with("dimensions/mymod/mining"));
+    /// ```
+    pub fn dimension(&self, dimension: &Dimension) -> WorldDimension {
+        WorldDimension::new(&dimension.folder_path(&self.folder_path))
+    }
+
+    /// Returns vanilla dimensions followed by custom dimensions in name order.
+    ///
+    /// Custom dimensions are taken from `level.dat` registry and from folders with
+    /// region files inside of `dimensions` folder, which also finds dimensions of
+    /// removed datapacks.
+    pub fn dimensions(&self) -> Result<Vec<Dimension>, io::Error> {
+        let mut custom_dimensions: Vec<Dimension> = self
+            .level_data
+            .dimension_names()
+            .into_iter()
+            .map(Dimension::from_name)
+            .filter(|dimension| matches!(dimension, Dimension::Custom(..)))
+            .collect();
+
+        let dimensions_folder_path = self.folder_path.join(DIMENSIONS_FOLDER);
+
+        if dimensions_folder_path.is_dir() {
+            for entry in fs::read_dir(&dimensions_folder_path)? {
+                let namespace_folder_path = entry?.path();
+
+                if let Some(namespace) = namespace_folder_path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                {
+                    find_custom_dimensions(
+                        namespace,
+                        &namespace_folder_path,
+                        "",
+                        &mut custom_dimensions,
+                    )?;
+                }
+            }
+        }
+
+        custom_dimensions.sort_by_key(|dimension| dimension.name());
+        custom_dimensions.dedup();
+
+        let mut dimensions = vec![Dimension::Overworld, Dimension::Nether, Dimension::End];
+        dimensions.extend(custom_dimensions);
+
+        Ok(dimensions)
+    }
+
+    pub fn overworld(&self) -> WorldDimension {
+        self.dimension(&Dimension::Overworld)
+    }
+
+    pub fn nether(&self) -> WorldDimension {
+        self.dimension(&Dimension::Nether)
+    }
+
+    pub fn end(&self) -> WorldDimension {
+        self.dimension(&Dimension::End)
+    }
+
+    /// Returns iterator over terrain chunks of dimension.
+    ///
+    /// Region files are listed upfront and opened one at a time while iterating.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use anvil_region::level::LevelData;
+    /// use anvil_region::world::{AnvilWorld, Dimension};
+    /// use tempfile::TempDir;
+    ///
+    /// let world_dir = TempDir::new().unwrap();
+    /// LevelData::new().save(world_dir.path()).unwrap();
+    ///
+    /// let world = AnvilWorld::open(world_dir.path()).unwrap();
+    ///
+    /// for world_chunk in world.iter_chunks(&Dimension::Nether).unwrap() {
+    ///     let world_chunk = world_chunk.unwrap();
+    ///     println!("{} {}", world_chunk.chunk_x, world_chunk.chunk_z);
+    /// }
+    /// ```
+    pub fn iter_chunks(&self, dimension: &Dimension) -> Result<WorldChunks, io::Error> {
+        WorldChunks::new(self, vec![dimension.clone()])
+    }
+
+    /// Returns iterator over terrain chunks of every dimension in order of [`dimensions`].
+    ///
+    /// [`dimensions`]: AnvilWorld::dimensions
+    pub fn iter_all_chunks(&self) -> Result<WorldChunks, io::Error> {
+        WorldChunks::new(self, self.dimensions()?)
+    }
+
+    /// Passes terrain chunks of dimension to closure, processing region files in parallel.
+    ///
+    /// Every region file is opened and its chunks are decoded on a worker thread of
+    /// rayon global pool, so closure is called from many threads in no particular order.
+    /// Errors are passed to closure the same way as [`iter_chunks`] yields them.
+    ///
+    /// [`iter_chunks`]: AnvilWorld::iter_chunks
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use anvil_region::level::LevelData;
+    /// use anvil_region::world::{AnvilWorld, Dimension};
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use tempfile::TempDir;
+    ///
+    /// let world_dir = TempDir::new().unwrap();
+    /// LevelData::new().save(world_dir.path()).unwrap();
+    ///
+    /// let world = AnvilWorld::open(world_dir.path()).unwrap();
+    /// let chunk_count = AtomicUsize::new(0);
+    ///
+    /// world
+    ///     .par_iter_chunks(&Dimension::Overworld, |world_chunk| {
+    ///         if world_chunk.is_ok() {
+    ///             chunk_count.fetch_add(1, Ordering::Relaxed);
+    ///         }
+    ///     })
+    ///     .unwrap();
+    ///
+    /// assert_eq!(chunk_count.into_inner(), 0);
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn par_iter_chunks<F>(&self, dimension: &Dimension, f: F) -> Result<(), io::Error>
+    where
+        F: Fn(Result<WorldChunk, WorldChunkError>) + Sync,
+    {
+        par_iter_region_files(world_region_files(self, vec![dimension.clone()])?, f);
+
+        Ok(())
+    }
+
+    /// Passes terrain chunks of every dimension to closure, processing region files
+    /// in parallel like [`par_iter_chunks`].
+    ///
+    /// [`par_iter_chunks`]: AnvilWorld::par_iter_chunks
+    #[cfg(feature = "rayon")]
+    pub fn par_iter_all_chunks<F>(&self, f: F) -> Result<(), io::Error>
+    where
+        F: Fn(Result<WorldChunk, WorldChunkError>) + Sync,
+    {
+        par_iter_region_files(world_region_files(self, self.dimensions()?)?, f);
+
+        Ok(())
+    }
+
+    /// Returns provider of player data files.
+    pub fn player_data_provider(&self) -> PlayerDataProvider<'_> {
+        PlayerDataProvider {
+            folder_path: &self.player_data_folder_path,
+        }
+    }
+
+    /// Returns provider of player advancements files.
+    pub fn advancements_provider(&self) -> AdvancementsProvider<'_> {
+        AdvancementsProvider {
+            folder_path: &self.advancements_folder_path,
+        }
+    }
+
+    /// Returns provider of player statistics files.
+    pub fn stats_provider(&self) -> StatsProvider<'_> {
+        StatsProvider {
+            folder_path: &self.stats_folder_path,
+        }
+    }
+}
+
+/// Terrain chunk yielded by world iterators.
+#[derive(Debug, Clone)]
+pub struct WorldChunk {
+    pub dimension: Dimension,
+    pub chunk_x: i32,
+    pub chunk_z: i32,
+    pub compound_tag: CompoundTag,
+}
+
+/// Chunk which can't be read by world iterators with where it's stored.
+#[derive(Debug)]
+pub struct WorldChunkError {
+    pub dimension: Dimension,
+    /// Path of region file.
+    pub region_path: PathBuf,
+    /// Chunk coordinates, none if region file can't be opened.
+    pub chunk_position: Option<(i32, i32)>,
+    pub chunk_load_error: ChunkLoadError,
+}
+
+impl fmt::Display for WorldChunkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((chunk_x, chunk_z)) = self.chunk_position {
+            write!(f, "chunk {} {} of ", chunk_x, chunk_z)?;
+        }
+
+        write!(
+            f,
+            "{} in {}: {}",
+            self.dimension.name(),
+            self.region_path.display(),
+            self.chunk_load_error
+        )
+    }
+}
+
+impl Error for WorldChunkError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.chunk_load_error)
+    }
+}
+
+/// Iterator over terrain chunks of world.
+///
+/// While chunks of one region are consumed, the next region file is read and decoded
+/// on background thread, so at most two decoded regions are kept in memory. Chunks
+/// which can't be read are yielded as errors and iteration continues with the next
+/// chunk, region which can't be opened is skipped after its error. Use
+/// [`skip_errors`] to collect errors into report instead.
+///
+/// [`skip_errors`]: WorldChunks::skip_errors
+pub struct WorldChunks {
+    region_files: vec::IntoIter<(Dimension, RegionFile)>,
+    /// Chunks of region which is being consumed.
+    region_chunks: vec::IntoIter<Result<WorldChunk, WorldChunkError>>,
+    /// Next region which is read and decoded on background thread meanwhile.
+    next_region: Option<JoinHandle<RegionChunks>>,
+    cancellation_token: Option<CancellationToken>,
+    progress_tracker: ProgressTracker,
+}
+
+/// Path of region file and its decoded chunks.
+type RegionChunks = (PathBuf, Vec<Result<WorldChunk, WorldChunkError>>);
+
+impl WorldChunks {
+    fn new(world: &AnvilWorld, dimensions: Vec<Dimension>) -> Result<Self, io::Error> {
+        let region_files = world_region_files(world, dimensions)?;
+
+        Ok(WorldChunks {
+            region_files: region_files.into_iter(),
+            region_chunks: Vec::new().into_iter(),
+            next_region: None,
+            cancellation_token: None,
+            progress_tracker: ProgressTracker::new(None),
+        })
+    }
+
+    /// Ends iteration once token is cancelled.
+    ///
+    /// Check token after iteration to tell apart cancelled and finished iteration.
+    pub fn cancellable(mut self, cancellation_token: CancellationToken) -> Self {
+        self.cancellation_token = Some(cancellation_token);
+        self
+    }
+
+    /// Reports progress of iteration to reporter after every chunk.
+    ///
+    /// Must be called before iteration starts, so totals cover all region files.
+    pub fn with_progress(mut self, progress_reporter: ProgressReporter) -> Self {
+        self.progress_tracker = ProgressTracker::new(Some(progress_reporter));
+
+        for (_, region_file) in self.region_files.as_slice() {
+            self.progress_tracker.add_region(&region_file.path);
+        }
+
+        self
+    }
+
+    /// Returns iterator which skips chunks and regions which can't be read instead of
+    /// yielding errors, see [`SalvageChunks`].
+    pub fn skip_errors(self) -> SalvageChunks {
+        SalvageChunks {
+            world_chunks: self,
+            salvage_report: SalvageReport::default(),
+        }
+    }
+
+    /// Starts reading region file which follows on background thread.
+    fn read_ahead(&mut self) {
+        self.next_region = self.region_files.next().map(|(dimension, region_file)| {
+            thread::spawn(move || {
+                let region_path = region_file.path.clone();
+                let mut region_chunks = Vec::new();
+                read_region_file(dimension, region_file, |world_chunk| {
+                    region_chunks.push(world_chunk)
+                });
+
+                (region_path, region_chunks)
+            })
+        });
+    }
+}
+
+impl Iterator for WorldChunks {
+    type Item = Result<WorldChunk, WorldChunkError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(cancellation_token) = &self.cancellation_token {
+            if cancellation_token.is_cancelled() {
+                // Region which is being read ahead is dropped once its thread finishes.
+                self.region_chunks = Vec::new().into_iter();
+                self.next_region = None;
+                return None;
+            }
+        }
+
+        loop {
+            if let Some(world_chunk) = self.region_chunks.next() {
+                let chunk_position = match &world_chunk {
+                    Ok(world_chunk) => Some((world_chunk.chunk_x, world_chunk.chunk_z)),
+                    Err(world_chunk_error) => world_chunk_error.chunk_position,
+                };
+
+                if let Some((chunk_x, chunk_z)) = chunk_position {
+                    self.progress_tracker.chunk(chunk_x, chunk_z);
+                }
+
+                return Some(world_chunk);
+            }
+
+            let (region_path, region_chunks) = match self.next_region.take() {
+                Some(next_region) => {
+                    let region_chunks = next_region
+                        .join()
+                        .unwrap_or_else(|panic| panic::resume_unwind(panic));
+                    self.read_ahead();
+
+                    region_chunks
+                }
+                None => {
+                    let (dimension, region_file) = match self.region_files.next() {
+                        Some(region_file) => region_file,
+                        None => {
+                            self.progress_tracker.finish();
+                            return None;
+                        }
+                    };
+                    self.read_ahead();
+
+                    let region_path = region_file.path.clone();
+                    let mut region_chunks = Vec::new();
+                    read_region_file(dimension, region_file, |world_chunk| {
+                        region_chunks.push(world_chunk)
+                    });
+
+                    (region_path, region_chunks)
+                }
+            };
+
+            self.progress_tracker.start_region(&region_path);
+            self.region_chunks = region_chunks.into_iter();
+        }
+    }
+}
+
+/// Iterator over terrain chunks of world which skips chunks and regions which can't
+/// be read, recording them in report.
+///
+/// Meant for salvage tools run against damaged worlds, which should recover as much
+/// as possible instead of stopping at the first error.
+///
+/// # Example
+///
+/// ```
+/// use anvil_region::level::LevelData;
+/// use anvil_region::world::{AnvilWorld, Dimension};
+/// use tempfile::TempDir;
+///
+/// let world_dir = TempDir::new().unwrap();
+/// LevelData::new().save(world_dir.path()).unwrap();
+///
+/// let world = AnvilWorld::open(world_dir.path()).unwrap();
+/// let mut world_chunks = world.iter_chunks(&Dimension::Overworld).unwrap().skip_errors();
+///
+/// for world_chunk in &mut world_chunks {
+///     println!("{} {}", world_chunk.chunk_x, world_chunk.chunk_z);
+/// }
+///
+/// let salvage_report = world_chunks.into_report();
+/// assert!(salvage_report.is_empty());
+/// ```
+pub struct SalvageChunks {
+    world_chunks: WorldChunks,
+    salvage_report: SalvageReport,
+}
+
+/// Chunks and regions which were skipped by [`SalvageChunks`].
+#[derive(Debug, Default)]
+pub struct SalvageReport {
+    /// Errors in order of iteration.
+    pub errors: Vec<WorldChunkError>,
+}
+
+impl SalvageChunks {
+    /// Returns report of what was skipped so far.
+    pub fn report(&self) -> &SalvageReport {
+        &self.salvage_report
+    }
+
+    pub fn into_report(self) -> SalvageReport {
+        self.salvage_report
+    }
+}
+
+impl Iterator for SalvageChunks {
+    type Item = WorldChunk;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.world_chunks.next()? {
+                Ok(world_chunk) => return Some(world_chunk),
+                Err(world_chunk_error) => self.salvage_report.errors.push(world_chunk_error),
+            }
+        }
+    }
+}
+
+impl SalvageReport {
+    pub fn len(&self) -> usize {
+        self.errors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Returns dimensions and coordinates of skipped chunks.
+    pub fn chunk_positions(&self) -> Vec<(Dimension, i32, i32)> {
+        self.errors
+            .iter()
+            .filter_map(|world_chunk_error| {
+                let (chunk_x, chunk_z) = world_chunk_error.chunk_position?;
+                Some((world_chunk_error.dimension.clone(), chunk_x, chunk_z))
+            })
+            .collect()
+    }
+
+    /// Returns paths of region files which were skipped because they can't be opened.
+    pub fn region_paths(&self) -> Vec<&Path> {
+        self.errors
+            .iter()
+            .filter(|world_chunk_error| world_chunk_error.chunk_position.is_none())
+            .map(|world_chunk_error| world_chunk_error.region_path.as_path())
+            .collect()
+    }
+}
+
+/// Returns terrain region files of dimensions in order of dimensions.
+fn world_region_files(
+    world: &AnvilWorld,
+    dimensions: Vec<Dimension>,
+) -> Result<Vec<(Dimension, RegionFile)>, io::Error> {
+    let mut region_files = Vec::new();
+
+    for dimension in dimensions {
+        let world_dimension = world.dimension(&dimension);
+
+        for region_file in world_dimension.chunk_provider().region_files()? {
+            region_files.push((dimension.clone(), region_file));
+        }
+    }
+
+    Ok(region_files)
+}
+
+/// Reads and decodes chunks of region file passing them to closure in order of header.
+fn read_region_file<F>(dimension: Dimension, region_file: RegionFile, mut f: F)
+where
+    F: FnMut(Result<WorldChunk, WorldChunkError>),
+{
+    let mut region = match AnvilRegion::new(&region_file.path) {
+        Ok(region) => region,
+        Err(io_error) => {
+            return f(Err(WorldChunkError {
+                dimension,
+                region_path: region_file.path,
+                chunk_position: None,
+                chunk_load_error: io_error.into(),
+            }))
+        }
+    };
+
+    let (region_x, region_z) = region_file.region_position;
+
+    for (region_chunk_x, region_chunk_z) in region.chunk_positions() {
+        let chunk_x = (region_x << 5) + region_chunk_x as i32;
+        let chunk_z = (region_z << 5) + region_chunk_z as i32;
+
+        let world_chunk = region
+            .read_chunk(region_chunk_x, region_chunk_z)
+            .map(|compound_tag| WorldChunk {
+                dimension: dimension.clone(),
+                chunk_x,
+                chunk_z,
+                compound_tag,
+            })
+            .map_err(|chunk_load_error| WorldChunkError {
+                dimension: dimension.clone(),
+                region_path: region_file.path.clone(),
+                chunk_position: Some((chunk_x, chunk_z)),
+                chunk_load_error,
+            });
+
+        f(world_chunk);
+    }
+}
+
+/// Reads and decodes chunks of every region file on rayon worker threads.
+#[cfg(feature = "rayon")]
+fn par_iter_region_files<F>(region_files: Vec<(Dimension, RegionFile)>, f: F)
+where
+    F: Fn(Result<WorldChunk, WorldChunkError>) + Sync,
+{
+    region_files
+        .into_par_iter()
+        .for_each(|(dimension, region_file)| read_region_file(dimension, region_file, &f));
+}
+
+/// Collects dimensions with region files in folder of namespace, names may contain slashes.
+fn find_custom_dimensions(
+    namespace: &str,
+    folder_path: &Path,
+    name: &str,
+    dimensions: &mut Vec<Dimension>,
+) -> Result<(), io::Error> {
+    if !folder_path.is_dir() {
+        return Ok(());
+    }
+
+    if !name.is_empty() && folder_path.join(REGION_FOLDER).is_dir() {
+        dimensions.push(Dimension::Custom(namespace.to_owned(), name.to_owned()));
+    }
+
+    for entry in fs::read_dir(folder_path)? {
+        let path = entry?.path();
+
+        let folder_name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(folder_name) => folder_name,
+            None => continue,
+        };
+
+        // Region folders of dimension are not nested dimensions.
+        if !name.is_empty()
+            && [REGION_FOLDER, ENTITIES_FOLDER, POI_FOLDER, "data"].contains(&folder_name)
+        {
+            continue;
+        }
+
+        let name = if name.is_empty() {
+            folder_name.to_owned()
+        } else {
+            format!("{}/{}", name, folder_name)
+        };
+
+        find_custom_dimensions(namespace, &path, &name, dimensions)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cancel::CancellationToken;
+    use crate::data::write_data_file;
+    use crate::level::LevelData;
+    use crate::limits::{LimitExceeded, ParseLimits};
+    use crate::player::advancements::Advancements;
+    use crate::player::stats::Statistics;
+    use crate::player::PlayerData;
+    use crate::progress::ProgressReporter;
+    use crate::relocate::copy_chunk;
+    use crate::snbt;
+    use crate::world::{AnvilWorld, Dimension, WorldError};
+    use crate::zip::write_zip;
+    use crate::AnvilChunkProvider;
+    use nbt::CompoundTag;
+    use std::fs;
+    use std::sync::{Arc, Mutex};
+    use tempfile::TempDir;
+
+    fn level() -> CompoundTag {
+        let mut data = CompoundTag::new();
+        data.insert_str("LevelName", "Test");
+
+        let mut level = CompoundTag::new();
+        level.insert_compound_tag("Data", data);
+
+        level
+    }
+
+    #[test]
+    fn test_open() {
+        let world_dir = TempDir::new().unwrap();
+        write_data_file(&world_dir.path().join("level.dat"), &level()).unwrap();
+
+        let nether_folder = world_dir.path().join("DIM-1/region");
+        copy_chunk(
+            &AnvilChunkProvider::new("test/region"),
+            (4, 2),
+            &AnvilChunkProvider::new(nether_folder.to_str().unwrap()),
+            (4, 2),
+        )
+        .unwrap();
+
+        let mut world = AnvilWorld::open(world_dir.path()).unwrap();
+
+        assert!(world.overworld().chunk_provider().load_chunk(4, 2).is_err());
+        assert!(world.nether().chunk_provider().load_chunk(4, 2).is_ok());
+        assert!(world
+            .end()
+            .entity_chunk_provider()
+            .chunk_positions()
+            .unwrap()
+            .is_empty());
+
+        world.level_data_mut().set_seed(7);
+        world.save_level_data().unwrap();
+
+        let world = AnvilWorld::open(world_dir.path()).unwrap();
+        assert_eq!(world.level_data().seed(), Some(7));
+        assert_eq!(world.level_data().name(), Some("Test"));
+    }
+
+    #[test]
+    fn test_iter_chunks() {
+        let world_dir = TempDir::new().unwrap();
+        LevelData::new().save(world_dir.path()).unwrap();
+
+        let world = AnvilWorld::open(world_dir.path()).unwrap();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+
+        for (world_dimension, chunk_position) in &[
+            (world.overworld(), (40, -3)),
+            (world.overworld(), (4, 2)),
+            (world.end(), (0, 0)),
+        ] {
+            copy_chunk(
+                &fixture_chunk_provider,
+                (4, 2),
+                &world_dimension.chunk_provider(),
+                *chunk_position,
+            )
+            .unwrap();
+        }
+
+        let chunks: Vec<_> = world
+            .iter_all_chunks()
+            .unwrap()
+            .map(|world_chunk| {
+                let world_chunk = world_chunk.unwrap();
+                (
+                    world_chunk.dimension,
+                    world_chunk.chunk_x,
+                    world_chunk.chunk_z,
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            chunks,
+            vec![
+                (Dimension::Overworld, 4, 2),
+                (Dimension::Overworld, 40, -3),
+                (Dimension::End, 0, 0),
+            ]
+        );
+        assert_eq!(world.iter_chunks(&Dimension::Nether).unwrap().count(), 0);
+
+        let cancellation_token = CancellationToken::new();
+        let mut world_chunks = world
+            .iter_all_chunks()
+            .unwrap()
+            .cancellable(cancellation_token.clone());
+
+        assert!(world_chunks.next().is_some());
+        cancellation_token.cancel();
+        assert!(world_chunks.next().is_none());
+    }
+
+    #[test]
+    fn test_iter_chunks_read_ahead() {
+        let world_dir = TempDir::new().unwrap();
+        LevelData::new().save(world_dir.path()).unwrap();
+
+        let world = AnvilWorld::open(world_dir.path()).unwrap();
+        let overworld = world.overworld();
+        let chunk_provider = overworld.chunk_provider();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+
+        for region_x in 0..4 {
+            copy_chunk(
+                &fixture_chunk_provider,
+                (4, 2),
+                &chunk_provider,
+                (region_x << 5, 0),
+            )
+            .unwrap();
+        }
+
+        // Chunk which can't be decoded is yielded as error between other regions.
+        let mut region_data = vec![0; 3 * 4096];
+        region_data[..4].copy_from_slice(&[0, 0, 2, 1]);
+        region_data[8192..8197].copy_from_slice(&[0, 0, 0, 100, 2]);
+        fs::write(world_dir.path().join("region/r.1.0.mca"), region_data).unwrap();
+
+        let results: Vec<_> = world.iter_chunks(&Dimension::Overworld).unwrap().collect();
+
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].as_ref().unwrap().chunk_x, 0);
+
+        let world_chunk_error = results[1].as_ref().unwrap_err();
+        assert_eq!(world_chunk_error.chunk_position, Some((32, 0)));
+        assert!(world_chunk_error.region_path.ends_with("region/r.1.0.mca"));
+        assert!(world_chunk_error
+            .to_string()
+            .starts_with("chunk 32 0 of minecraft:overworld in "));
+
+        assert_eq!(results[2].as_ref().unwrap().chunk_x, 64);
+        assert_eq!(results[3].as_ref().unwrap().chunk_x, 96);
+    }
+
+    #[test]
+    fn test_iter_chunks_with_progress() {
+        let world_dir = TempDir::new().unwrap();
+        LevelData::new().save(world_dir.path()).unwrap();
+
+        let world = AnvilWorld::open(world_dir.path()).unwrap();
+        let overworld = world.overworld();
+        let chunk_provider = overworld.chunk_provider();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+
+        for region_x in 0..3 {
+            copy_chunk(
+                &fixture_chunk_provider,
+                (4, 2),
+                &chunk_provider,
+                (region_x << 5, 0),
+            )
+            .unwrap();
+        }
+
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let reported = reports.clone();
+        let progress_reporter =
+            ProgressReporter::new(move |progress| reported.lock().unwrap().push(progress.clone()));
+
+        let world_chunks = world
+            .iter_chunks(&Dimension::Overworld)
+            .unwrap()
+            .with_progress(progress_reporter);
+
+        assert_eq!(world_chunks.count(), 3);
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports[0].chunk_position, Some((0, 0)));
+        assert_eq!(reports[0].processed_regions, 0);
+
+        let last_progress = reports.last().unwrap();
+        assert_eq!(last_progress.processed_regions, 3);
+        assert_eq!(last_progress.total_regions, 3);
+        assert_eq!(last_progress.processed_chunks, 3);
+        assert_eq!(last_progress.fraction(), Some(1.0));
+    }
+
+    #[test]
+    fn test_iter_chunks_skip_errors() {
+        let world_dir = TempDir::new().unwrap();
+        LevelData::new().save(world_dir.path()).unwrap();
+
+        let world = AnvilWorld::open(world_dir.path()).unwrap();
+        let overworld = world.overworld();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+
+        for chunk_position in &[(0, 0), (96, 0)] {
+            copy_chunk(
+                &fixture_chunk_provider,
+                (4, 2),
+                &overworld.chunk_provider(),
+                *chunk_position,
+            )
+            .unwrap();
+        }
+
+        let mut region_data = vec![0; 3 * 4096];
+        region_data[..4].copy_from_slice(&[0, 0, 2, 1]);
+        region_data[8192..8197].copy_from_slice(&[0, 0, 0, 100, 2]);
+        fs::write(world_dir.path().join("region/r.1.0.mca"), region_data).unwrap();
+        fs::create_dir(world_dir.path().join("region/r.2.0.mca")).unwrap();
+
+        let mut world_chunks = world
+            .iter_chunks(&Dimension::Overworld)
+            .unwrap()
+            .skip_errors();
+
+        let chunk_positions: Vec<_> = (&mut world_chunks)
+            .map(|world_chunk| world_chunk.chunk_x)
+            .collect();
+
+        assert_eq!(chunk_positions, vec![0, 96]);
+
+        let salvage_report = world_chunks.into_report();
+        assert_eq!(salvage_report.len(), 2);
+        assert_eq!(
+            salvage_report.chunk_positions(),
+            vec![(Dimension::Overworld, 32, 0)]
+        );
+
+        let region_paths = salvage_report.region_paths();
+        assert_eq!(region_paths.len(), 1);
+        assert!(region_paths[0].ends_with("region/r.2.0.mca"));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_par_iter_chunks() {
+        let world_dir = TempDir::new().unwrap();
+        LevelData::new().save(world_dir.path()).unwrap();
+
+        let world = AnvilWorld::open(world_dir.path()).unwrap();
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+
+        for (world_dimension, chunk_position) in &[
+            (world.overworld(), (40, -3)),
+            (world.overworld(), (4, 2)),
+            (world.end(), (0, 0)),
+        ] {
+            copy_chunk(
+                &fixture_chunk_provider,
+                (4, 2),
+                &world_dimension.chunk_provider(),
+                *chunk_position,
+            )
+            .unwrap();
+        }
+
+        let chunks = std::sync::Mutex::new(Vec::new());
+
+        world
+            .par_iter_all_chunks(|world_chunk| {
+                let world_chunk = world_chunk.unwrap();
+                chunks.lock().unwrap().push((
+                    world_chunk.dimension,
+                    world_chunk.chunk_x,
+                    world_chunk.chunk_z,
+                ));
+            })
+            .unwrap();
+
+        let mut chunks = chunks.into_inner().unwrap();
+        chunks.sort_by_key(|(dimension, chunk_x, chunk_z)| (dimension.name(), *chunk_x, *chunk_z));
+
+        assert_eq!(
+            chunks,
+            vec![
+                (Dimension::Overworld, 4, 2),
+                (Dimension::Overworld, 40, -3),
+                (Dimension::End, 0, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dimension() {
+        let world_folder = std::path::Path::new("world");
+
+        for (name, folder) in &[
+            ("minecraft:overworld", "world"),
+            ("the_nether", "world/DIM-1"),
+            ("minecraft:the_end", "world/DIM1"),
+            ("mymod:mining", "world/dimensions/mymod/mining"),
+        ] {
+            let dimension = Dimension::from_name(name);
+
+            assert_eq!(dimension.folder_path(world_folder).to_str(), Some(*folder));
+            assert_eq!(Dimension::from_name(&dimension.name()), dimension);
+        }
+
+        assert_eq!(
+            Dimension::from_name("mymod:mining"),
+            Dimension::Custom("mymod".to_owned(), "mining".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_dimensions() {
+        let world_dir = TempDir::new().unwrap();
+        let mut level_data = LevelData::new();
+        level_data.data_mut().insert_compound_tag(
+            "WorldGenSettings",
+            snbt::from_str(r#"{dimensions: {"minecraft:overworld": {}, "mymod:sky": {}}}"#)
+                .unwrap(),
+        );
+        level_data.save(world_dir.path()).unwrap();
+
+        for folder in &[
+            "dimensions/mymod/mining/region",
+            "dimensions/mymod/caves/deep/region",
+            "dimensions/other/empty",
+        ] {
+            fs::create_dir_all(world_dir.path().join(folder)).unwrap();
+        }
+
+        let world = AnvilWorld::open(world_dir.path()).unwrap();
+        let names: Vec<_> = world
+            .dimensions()
+            .unwrap()
+            .iter()
+            .map(|dimension| dimension.name())
+            .collect();
+
+        assert_eq!(
+            names,
+            vec![
+                "minecraft:overworld",
+                "minecraft:the_nether",
+                "minecraft:the_end",
+                "mymod:caves/deep",
+                "mymod:mining",
+                "mymod:sky",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_open_without_level_dat() {
+        let world_dir = TempDir::new().unwrap();
+
+        match AnvilWorld::open(world_dir.path()) {
+            Err(WorldError::LevelDatNotFound { folder_path }) => {
+                assert_eq!(folder_path, world_dir.path())
+            }
+            result => panic!("Expected `LevelDatNotFound` but got `{:?}`", result),
+        }
+    }
+
+    #[test]
+    fn test_open_zip() {
+        let temp_dir = TempDir::new().unwrap();
+        let level_dat_path = temp_dir.path().join("level.dat");
+        write_data_file(&level_dat_path, &level()).unwrap();
+
+        let zip_path = temp_dir.path().join("world.zip");
+        write_zip(
+            &zip_path,
+            &[
+                ("Test/level.dat", &fs::read(level_dat_path).unwrap()),
+                (
+                    "Test/region/r.0.0.mca",
+                    &fs::read("test/region/r.0.0.mca").unwrap(),
+                ),
+            ],
+            8,
+        );
+
+        let world = AnvilWorld::open_zip(&zip_path, temp_dir.path().join("extracted")).unwrap();
+
+        assert!(world.folder_path().ends_with("extracted/Test"));
+        assert!(world.overworld().chunk_provider().load_chunk(4, 2).is_ok());
+    }
+
+    #[test]
+    fn test_open_zip_with_limits() {
+        let temp_dir = TempDir::new().unwrap();
+        let zip_path = temp_dir.path().join("world.zip");
+        write_zip(&zip_path, &[("level.dat", &[0; 10_000])], 8);
+
+        let mut parse_limits = ParseLimits::untrusted();
+        parse_limits.max_archive_length = Some(1000);
+
+        let extract_folder_path = temp_dir.path().join("extracted");
+
+        match AnvilWorld::open_zip_with_limits(&zip_path, &extract_folder_path, &parse_limits) {
+            Err(WorldError::ZipError { io_error }) => {
+                let limit_exceeded = io_error.get_ref().unwrap().downcast_ref();
+
+                assert_eq!(
+                    limit_exceeded,
+                    Some(&LimitExceeded::ArchiveLength {
+                        max_archive_length: 1000
+                    })
+                );
+            }
+            result => panic!("Expected `ZipError` but got `{:?}`", result),
+        }
+
+        assert!(!extract_folder_path.join("level.dat").exists());
+    }
+
+    #[test]
+    fn test_player_data_provider() {
+        let world_dir = TempDir::new().unwrap();
+        write_data_file(&world_dir.path().join("level.dat"), &level()).unwrap();
+
+        let world = AnvilWorld::open(world_dir.path()).unwrap();
+        let uuid = "069a79f4-44e9-4726-a5be-fca90e38aaf5";
+        world
+            .player_data_provider()
+            .save_player(uuid, &PlayerData::new())
+            .unwrap();
+
+        assert!(world_dir
+            .path()
+            .join("playerdata")
+            .join(format!("{}.dat", uuid))
+            .is_file());
+
+        world
+            .stats_provider()
+            .save_player(uuid, &Statistics::new())
+            .unwrap();
+        world
+            .advancements_provider()
+            .save_player(uuid, &Advancements::new())
+            .unwrap();
+
+        assert!(world_dir
+            .path()
+            .join("stats")
+            .join(format!("{}.json", uuid))
+            .is_file());
+        assert_eq!(
+            world.advancements_provider().player_uuids().unwrap(),
+            vec![uuid]
+        );
+    }
+}
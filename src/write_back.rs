@@ -0,0 +1,667 @@
+//! Chunk provider which keeps saved chunks in memory and writes them later.
+//!
+//! Saves and deletes only mark chunks as dirty, so they are cheap and never wait for
+//! disk. Dirty chunks are flushed on background thread every [`flush_interval`], when
+//! there are more than [`max_dirty_chunks`] of them, on [`flush`] and when provider is
+//! dropped. Flush opens every region file once and writes all its dirty chunks,
+//! instead of opening region file for every chunk.
+//!
+//! Chunks are written only when flushed, so data of chunks saved after the last flush
+//! is lost if process is killed.
+//!
+//! [`flush_interval`]: WriteBackOptions::flush_interval
+//! [`max_dirty_chunks`]: WriteBackOptions::max_dirty_chunks
+//! [`flush`]: WriteBackChunkProvider::flush
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::write_back::{WriteBackChunkProvider, WriteBackOptions};
+//! use anvil_region::AnvilChunkProvider;
+//! use tempfile::TempDir;
+//!
+//! let chunk_compound_tag = AnvilChunkProvider::new("test/region")
+//!     .load_chunk(4, 2)
+//!     .unwrap();
+//!
+//! let region_dir = TempDir::new().unwrap();
+//! let chunk_provider = WriteBackChunkProvider::new(region_dir.path(), WriteBackOptions::default());
+//!
+//! chunk_provider.save_chunk(4, 2, chunk_compound_tag).unwrap();
+//! assert!(chunk_provider.load_chunk(4, 2).is_ok());
+//!
+//! chunk_provider.flush().unwrap();
+//! ```
+use crate::concurrent::ConcurrentAnvilChunkProvider;
+use crate::hash::CoordinateHashMap;
+use crate::memory::{compound_tag_size, MemoryBudget, MemoryConsumer, MemoryRegistration};
+use crate::{ChunkLoadError, ChunkSaveError};
+use nbt::CompoundTag;
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Default interval between background flushes.
+pub const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+/// Default amount of dirty chunks which triggers flush.
+pub const DEFAULT_MAX_DIRTY_CHUNKS: usize = 4096;
+
+/// When dirty chunks are flushed.
+#[derive(Debug, Clone)]
+pub struct WriteBackOptions {
+    /// Interval between background flushes.
+    pub flush_interval: Duration,
+    /// Saving more dirty chunks than this flushes them on saving thread.
+    pub max_dirty_chunks: usize,
+    /// Budget which accounts dirty chunks together with caches of other providers.
+    pub memory_budget: Option<MemoryBudget>,
+}
+
+impl WriteBackOptions {
+    pub fn new(flush_interval: Duration, max_dirty_chunks: usize) -> Self {
+        WriteBackOptions {
+            flush_interval,
+            max_dirty_chunks,
+            memory_budget: None,
+        }
+    }
+
+    /// Accounts dirty chunks in memory budget, they are flushed when it's exceeded.
+    pub fn memory_budget(mut self, memory_budget: MemoryBudget) -> Self {
+        self.memory_budget = Some(memory_budget);
+        self
+    }
+}
+
+impl Default for WriteBackOptions {
+    fn default() -> Self {
+        WriteBackOptions::new(DEFAULT_FLUSH_INTERVAL, DEFAULT_MAX_DIRTY_CHUNKS)
+    }
+}
+
+/// Provider of chunks of region folder which flushes saved chunks in batches.
+pub struct WriteBackChunkProvider {
+    shared: Arc<Shared>,
+    /// Thread which flushes chunks every flush interval.
+    flush_thread: Option<JoinHandle<()>>,
+}
+
+/// State shared with flush thread.
+struct Shared {
+    chunk_provider: ConcurrentAnvilChunkProvider,
+    options: WriteBackOptions,
+    state: Mutex<WriteBackState>,
+    /// Notified when provider is dropped.
+    closed_condvar: Condvar,
+    /// Held while dirty chunks are written, so flushes don't overtake each other.
+    flush_lock: Mutex<()>,
+}
+
+struct WriteBackState {
+    /// Changed chunks which are not written yet, keyed by chunk position.
+    dirty_chunks: CoordinateHashMap<(i32, i32), DirtyChunk>,
+    /// Incremented on every change to tell apart changes made while flushing.
+    generation: u64,
+    /// Error of the last background flush which wasn't returned yet.
+    flush_error: Option<ChunkSaveError>,
+    closed: bool,
+    /// Estimated amount of bytes held by dirty chunks.
+    memory_usage: usize,
+    memory_registration: Option<MemoryRegistration>,
+}
+
+struct DirtyChunk {
+    /// Saved chunk data, none if chunk was deleted.
+    chunk_compound_tag: Option<CompoundTag>,
+    generation: u64,
+    /// Estimated amount of bytes held by chunk data.
+    memory_usage: usize,
+}
+
+impl WriteBackChunkProvider {
+    pub fn new(folder_path: impl AsRef<Path>, options: WriteBackOptions) -> Self {
+        let shared = Arc::new_cyclic(|weak_shared: &Weak<Shared>| {
+            let memory_registration = options
+                .memory_budget
+                .as_ref()
+                .map(|memory_budget| memory_budget.register(weak_shared.clone()));
+
+            let state = WriteBackState {
+                dirty_chunks: CoordinateHashMap::default(),
+                generation: 0,
+                flush_error: None,
+                closed: false,
+                memory_usage: 0,
+                memory_registration,
+            };
+
+            Shared {
+                chunk_provider: ConcurrentAnvilChunkProvider::new(folder_path),
+                options,
+                state: Mutex::new(state),
+                closed_condvar: Condvar::new(),
+                flush_lock: Mutex::new(()),
+            }
+        });
+
+        let flush_thread = {
+            let shared = shared.clone();
+            thread::spawn(move || shared.run_flush_thread())
+        };
+
+        WriteBackChunkProvider {
+            shared,
+            flush_thread: Some(flush_thread),
+        }
+    }
+
+    pub fn folder_path(&self) -> &Path {
+        self.shared.chunk_provider.folder_path()
+    }
+
+    /// Loads chunk from the specified coordinates, including chunks which are not
+    /// flushed yet.
+    pub fn load_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<CompoundTag, ChunkLoadError> {
+        if let Some(result) = self.shared.load_dirty_chunk(chunk_x, chunk_z) {
+            return result;
+        }
+
+        // Flush removes chunk from dirty chunks only after writing it, so holding flush
+        // lock guarantees that chunk missing from dirty chunks is already on disk.
+        let _flush_guard = self.shared.flush_lock();
+
+        if let Some(result) = self.shared.load_dirty_chunk(chunk_x, chunk_z) {
+            return result;
+        }
+
+        self.shared.chunk_provider.load_chunk(chunk_x, chunk_z)
+    }
+
+    /// Marks chunk at the specified coordinates as saved with specified data.
+    ///
+    /// Returns error of failed background flush, chunk is saved anyway.
+    pub fn save_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        chunk_compound_tag: CompoundTag,
+    ) -> Result<(), ChunkSaveError> {
+        self.change_chunk(chunk_x, chunk_z, Some(chunk_compound_tag))
+    }
+
+    /// Marks chunk at the specified coordinates as deleted, returns false if chunk is
+    /// not present.
+    pub fn delete_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<bool, ChunkSaveError> {
+        let is_present = self
+            .shared
+            .state()
+            .dirty_chunks
+            .get(&(chunk_x, chunk_z))
+            .map(|dirty_chunk| dirty_chunk.chunk_compound_tag.is_some());
+
+        let is_present = match is_present {
+            Some(is_present) => is_present,
+            None => self.shared.chunk_provider.has_chunk(chunk_x, chunk_z)?,
+        };
+
+        if !is_present {
+            return Ok(false);
+        }
+
+        self.change_chunk(chunk_x, chunk_z, None)?;
+
+        Ok(true)
+    }
+
+    /// Returns coordinates of all chunks, including chunks which are not flushed yet.
+    pub fn chunk_positions(&self) -> Result<Vec<(i32, i32)>, io::Error> {
+        let _flush_guard = self.shared.flush_lock();
+        let mut chunk_positions = self.shared.chunk_provider.chunk_positions()?;
+        let state = self.shared.state();
+
+        chunk_positions.retain(|chunk_position| !state.dirty_chunks.contains_key(chunk_position));
+
+        for (chunk_position, dirty_chunk) in &state.dirty_chunks {
+            if dirty_chunk.chunk_compound_tag.is_some() {
+                chunk_positions.push(*chunk_position);
+            }
+        }
+
+        chunk_positions.sort_unstable();
+
+        Ok(chunk_positions)
+    }
+
+    /// Returns amount of chunks which are not flushed yet.
+    pub fn dirty_chunk_count(&self) -> usize {
+        self.shared.state().dirty_chunks.len()
+    }
+
+    /// Writes all dirty chunks to region files.
+    ///
+    /// Returns error of failed background flush if it wasn't returned yet.
+    pub fn flush(&self) -> Result<(), ChunkSaveError> {
+        self.shared.flush()?;
+
+        match self.shared.state().flush_error.take() {
+            Some(chunk_save_error) => Err(chunk_save_error),
+            None => Ok(()),
+        }
+    }
+
+    /// Stops background flushes and writes remaining dirty chunks.
+    ///
+    /// Unlike dropping provider, returns error of the final flush or of failed
+    /// background flush which wasn't returned yet. Chunks which can't be written are
+    /// discarded.
+    pub fn close(mut self) -> Result<(), ChunkSaveError> {
+        self.stop_flush_thread();
+        self.flush()?;
+
+        Ok(())
+    }
+
+    fn stop_flush_thread(&mut self) {
+        self.shared.state().closed = true;
+        self.shared.closed_condvar.notify_all();
+
+        if let Some(flush_thread) = self.flush_thread.take() {
+            let _ = flush_thread.join();
+        }
+    }
+
+    fn change_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        chunk_compound_tag: Option<CompoundTag>,
+    ) -> Result<(), ChunkSaveError> {
+        let dirty_chunk_count = {
+            let mut state = self.shared.state();
+            state.generation += 1;
+
+            let dirty_chunk = DirtyChunk {
+                memory_usage: chunk_compound_tag.as_ref().map_or(0, compound_tag_size),
+                chunk_compound_tag,
+                generation: state.generation,
+            };
+
+            state.insert((chunk_x, chunk_z), dirty_chunk);
+
+            if let Some(chunk_save_error) = state.flush_error.take() {
+                return Err(chunk_save_error);
+            }
+
+            state.dirty_chunks.len()
+        };
+
+        if dirty_chunk_count > self.shared.options.max_dirty_chunks {
+            self.shared.flush()?;
+        }
+
+        if let Some(memory_budget) = &self.shared.options.memory_budget {
+            memory_budget.enforce();
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for WriteBackChunkProvider {
+    /// Stops flush thread and flushes remaining dirty chunks.
+    ///
+    /// Errors can't be returned from drop, so chunks which can't be written and error
+    /// of background flush which wasn't returned are reported to standard error, and
+    /// debug builds panic. Use [`close`] to handle them.
+    ///
+    /// [`close`]: WriteBackChunkProvider::close
+    fn drop(&mut self) {
+        if self.flush_thread.is_none() {
+            // Closed explicitly, which returned errors and discarded chunks.
+            return;
+        }
+
+        self.stop_flush_thread();
+
+        let flush_result = self.shared.flush();
+        let mut state = self.shared.state();
+        let discarded_chunk_count = state.discard();
+        let flush_error = flush_result.err().or_else(|| state.flush_error.take());
+
+        if let Some(chunk_save_error) = flush_error {
+            eprintln!(
+                "Write-back provider of {} dropped with {} chunks which can't be written: {}",
+                self.shared.chunk_provider.folder_path().display(),
+                discarded_chunk_count,
+                chunk_save_error
+            );
+
+            debug_assert!(
+                thread::panicking(),
+                "Write-back provider dropped with error, use `close` to handle it"
+            );
+        }
+    }
+}
+
+impl Shared {
+    fn state(&self) -> MutexGuard<'_, WriteBackState> {
+        self.state.lock().unwrap_or_else(|error| error.into_inner())
+    }
+
+    fn flush_lock(&self) -> MutexGuard<'_, ()> {
+        self.flush_lock
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+
+    /// Returns data of dirty chunk, none if chunk is not dirty.
+    fn load_dirty_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> Option<Result<CompoundTag, ChunkLoadError>> {
+        let state = self.state();
+        let dirty_chunk = state.dirty_chunks.get(&(chunk_x, chunk_z))?;
+
+        let result = match &dirty_chunk.chunk_compound_tag {
+            Some(chunk_compound_tag) => Ok(chunk_compound_tag.clone()),
+            None => Err(ChunkLoadError::ChunkNotFound { chunk_x, chunk_z }),
+        };
+
+        Some(result)
+    }
+
+    fn run_flush_thread(&self) {
+        let mut state = self.state();
+
+        // Closing before thread starts waiting must not be missed.
+        while !state.closed {
+            state = self
+                .closed_condvar
+                .wait_timeout(state, self.options.flush_interval)
+                .unwrap_or_else(|error| error.into_inner())
+                .0;
+
+            if state.closed {
+                return;
+            }
+
+            drop(state);
+
+            if let Err(chunk_save_error) = self.flush() {
+                self.state().flush_error = Some(chunk_save_error);
+            }
+
+            state = self.state();
+        }
+    }
+
+    /// Writes dirty chunks grouped by region, chunks changed while being written stay
+    /// dirty.
+    fn flush(&self) -> Result<(), ChunkSaveError> {
+        let _flush_guard = self.flush_lock();
+        let mut region_chunks = BTreeMap::new();
+
+        for (&(chunk_x, chunk_z), dirty_chunk) in &self.state().dirty_chunks {
+            region_chunks
+                .entry((chunk_x >> 5, chunk_z >> 5))
+                .or_insert_with(Vec::new)
+                .push((
+                    chunk_x,
+                    chunk_z,
+                    dirty_chunk.chunk_compound_tag.clone(),
+                    dirty_chunk.generation,
+                ));
+        }
+
+        for (region_position, chunks) in region_chunks {
+            let written_chunks: Vec<_> = chunks
+                .iter()
+                .map(|(chunk_x, chunk_z, _, generation)| ((*chunk_x, *chunk_z), *generation))
+                .collect();
+
+            let chunks = chunks
+                .into_iter()
+                .map(|(chunk_x, chunk_z, chunk_compound_tag, _)| {
+                    (chunk_x, chunk_z, chunk_compound_tag)
+                })
+                .collect();
+
+            self.chunk_provider
+                .write_region_chunks(region_position, chunks)?;
+
+            let mut state = self.state();
+
+            for (chunk_position, generation) in written_chunks {
+                let is_unchanged = state
+                    .dirty_chunks
+                    .get(&chunk_position)
+                    .is_some_and(|dirty_chunk| dirty_chunk.generation == generation);
+
+                if is_unchanged {
+                    state.remove(chunk_position);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl MemoryConsumer for Shared {
+    /// Flushes dirty chunks, error is returned by the next save or flush.
+    fn release_memory(&self, _bytes: usize) {
+        if let Err(chunk_save_error) = self.flush() {
+            self.state().flush_error = Some(chunk_save_error);
+        }
+    }
+}
+
+impl WriteBackState {
+    fn insert(&mut self, chunk_position: (i32, i32), dirty_chunk: DirtyChunk) {
+        self.memory_usage += dirty_chunk.memory_usage;
+
+        if let Some(previous_dirty_chunk) = self.dirty_chunks.insert(chunk_position, dirty_chunk) {
+            self.memory_usage -= previous_dirty_chunk.memory_usage;
+        }
+
+        self.update_memory_usage();
+    }
+
+    /// Removes all dirty chunks, returns amount of them.
+    fn discard(&mut self) -> usize {
+        let dirty_chunk_count = self.dirty_chunks.len();
+        self.dirty_chunks.clear();
+        self.memory_usage = 0;
+        self.update_memory_usage();
+
+        dirty_chunk_count
+    }
+
+    fn remove(&mut self, chunk_position: (i32, i32)) {
+        if let Some(dirty_chunk) = self.dirty_chunks.remove(&chunk_position) {
+            self.memory_usage -= dirty_chunk.memory_usage;
+            self.update_memory_usage();
+        }
+    }
+
+    fn update_memory_usage(&self) {
+        if let Some(memory_registration) = &self.memory_registration {
+            memory_registration.set_usage(self.memory_usage);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::memory::{compound_tag_size, MemoryBudget};
+    use crate::write_back::{WriteBackChunkProvider, WriteBackOptions};
+    use crate::{AnvilChunkProvider, ChunkLoadError};
+    use nbt::CompoundTag;
+    use std::fs;
+    use std::thread;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_flush() {
+        let temp_dir = TempDir::new().unwrap();
+        let chunk_compound_tag = AnvilChunkProvider::new("test/region")
+            .load_chunk(4, 2)
+            .unwrap();
+
+        let options = WriteBackOptions::new(Duration::from_secs(3600), 1024);
+        let chunk_provider = WriteBackChunkProvider::new(temp_dir.path(), options);
+        let folder_chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+
+        chunk_provider
+            .save_chunk(4, 2, chunk_compound_tag.clone())
+            .unwrap();
+        chunk_provider
+            .save_chunk(40, 2, chunk_compound_tag)
+            .unwrap();
+
+        assert_eq!(chunk_provider.dirty_chunk_count(), 2);
+        assert_eq!(
+            chunk_provider.chunk_positions().unwrap(),
+            vec![(4, 2), (40, 2)]
+        );
+        assert!(folder_chunk_provider.chunk_positions().unwrap().is_empty());
+
+        chunk_provider.flush().unwrap();
+
+        assert_eq!(chunk_provider.dirty_chunk_count(), 0);
+        assert_eq!(
+            folder_chunk_provider.chunk_positions().unwrap(),
+            vec![(4, 2), (40, 2)]
+        );
+
+        assert!(chunk_provider.delete_chunk(4, 2).unwrap());
+        assert!(!chunk_provider.delete_chunk(4, 2).unwrap());
+        assert!(!chunk_provider.delete_chunk(5, 2).unwrap());
+
+        match chunk_provider.load_chunk(4, 2) {
+            Err(ChunkLoadError::ChunkNotFound { chunk_x, chunk_z }) => {
+                assert_eq!(chunk_x, 4);
+                assert_eq!(chunk_z, 2);
+            }
+            result => panic!("Expected `ChunkNotFound` but got `{:?}`", result),
+        }
+
+        drop(chunk_provider);
+
+        assert_eq!(
+            folder_chunk_provider.chunk_positions().unwrap(),
+            vec![(40, 2)]
+        );
+    }
+
+    #[test]
+    fn test_close() {
+        let temp_dir = TempDir::new().unwrap();
+        let options = WriteBackOptions::new(Duration::from_secs(3600), 1024);
+        let chunk_provider = WriteBackChunkProvider::new(temp_dir.path(), options.clone());
+
+        chunk_provider.save_chunk(4, 2, CompoundTag::new()).unwrap();
+        chunk_provider.close().unwrap();
+
+        let folder_chunk_provider = AnvilChunkProvider::new(temp_dir.path().to_str().unwrap());
+        assert!(folder_chunk_provider.load_chunk(4, 2).is_ok());
+
+        // Region folder can't be created inside of file.
+        let folder_path = temp_dir.path().join("r.0.0.mca").join("region");
+        let chunk_provider = WriteBackChunkProvider::new(folder_path, options);
+
+        chunk_provider.save_chunk(4, 2, CompoundTag::new()).unwrap();
+        assert!(chunk_provider.close().is_err());
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "use `close` to handle it")]
+    fn test_drop_with_error() {
+        let temp_dir = TempDir::new().unwrap();
+        let folder_path = temp_dir.path().join("file");
+        fs::write(&folder_path, b"").unwrap();
+
+        let options = WriteBackOptions::new(Duration::from_secs(3600), 1024);
+        let chunk_provider = WriteBackChunkProvider::new(folder_path, options);
+
+        chunk_provider.save_chunk(4, 2, CompoundTag::new()).unwrap();
+    }
+
+    #[test]
+    fn test_flush_on_max_dirty_chunks() {
+        let temp_dir = TempDir::new().unwrap();
+        let chunk_compound_tag = AnvilChunkProvider::new("test/region")
+            .load_chunk(4, 2)
+            .unwrap();
+
+        let options = WriteBackOptions::new(Duration::from_secs(3600), 2);
+        let chunk_provider = WriteBackChunkProvider::new(temp_dir.path(), options);
+
+        for chunk_x in 0..3 {
+            chunk_provider
+                .save_chunk(chunk_x, 0, chunk_compound_tag.clone())
+                .unwrap();
+        }
+
+        assert_eq!(chunk_provider.dirty_chunk_count(), 0);
+    }
+
+    #[test]
+    fn test_background_flush() {
+        let temp_dir = TempDir::new().unwrap();
+        let chunk_compound_tag = AnvilChunkProvider::new("test/region")
+            .load_chunk(4, 2)
+            .unwrap();
+
+        let options = WriteBackOptions::new(Duration::from_millis(10), 1024);
+        let chunk_provider = WriteBackChunkProvider::new(temp_dir.path(), options);
+
+        chunk_provider.save_chunk(4, 2, chunk_compound_tag).unwrap();
+
+        for _ in 0..500 {
+            if chunk_provider.dirty_chunk_count() == 0 {
+                break;
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(chunk_provider.dirty_chunk_count(), 0);
+        assert!(chunk_provider.flush().is_ok());
+    }
+    #[test]
+    fn test_flush_on_memory_budget() {
+        let temp_dir = TempDir::new().unwrap();
+        let chunk_compound_tag = AnvilChunkProvider::new("test/region")
+            .load_chunk(4, 2)
+            .unwrap();
+
+        // Budget fits two chunks, saving the third one flushes them.
+        let memory_budget = MemoryBudget::new(compound_tag_size(&chunk_compound_tag) * 5 / 2);
+        let options = WriteBackOptions::new(Duration::from_secs(3600), 1024)
+            .memory_budget(memory_budget.clone());
+        let chunk_provider = WriteBackChunkProvider::new(temp_dir.path(), options);
+
+        for chunk_x in 0..2 {
+            chunk_provider
+                .save_chunk(chunk_x, 0, chunk_compound_tag.clone())
+                .unwrap();
+        }
+
+        assert_eq!(chunk_provider.dirty_chunk_count(), 2);
+        assert!(memory_budget.used() > 0);
+
+        chunk_provider.save_chunk(2, 0, chunk_compound_tag).unwrap();
+
+        assert_eq!(chunk_provider.dirty_chunk_count(), 0);
+        assert_eq!(memory_budget.used(), 0);
+        assert_eq!(chunk_provider.chunk_positions().unwrap().len(), 3);
+    }
+}
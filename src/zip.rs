@@ -0,0 +1,453 @@
+//! Extraction of zip archives which worlds are commonly shared in.
+//!
+//! Only stored and deflated entries without zip64 extensions are supported, which
+//! covers archives made by the game, realms backups and common archivers.
+use crate::limits::LimitExceeded;
+use byteorder::{LittleEndian, ReadBytesExt};
+use flate2::read::DeflateDecoder;
+use std::fs::File;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::{fs, io};
+
+/// Signature of end of central directory record.
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0605_4b50;
+/// Signature of central directory file header.
+const CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0201_4b50;
+/// Signature of local file header.
+const LOCAL_FILE_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+/// Length of end of central directory record without comment.
+const END_OF_CENTRAL_DIRECTORY_LENGTH: u64 = 22;
+/// Maximum length of archive comment.
+const MAXIMUM_COMMENT_LENGTH: u64 = u16::MAX as u64;
+
+/// Entry is stored uncompressed.
+const STORED_METHOD: u16 = 0;
+/// Entry is compressed with deflate.
+const DEFLATED_METHOD: u16 = 8;
+
+/// Entry of central directory.
+#[derive(Debug, Clone)]
+pub(crate) struct ZipEntry {
+    /// Entry path with forward slashes, folders end with slash.
+    pub(crate) name: String,
+    method: u16,
+    compressed_size: u32,
+    uncompressed_size: u32,
+    local_header_offset: u32,
+}
+
+impl ZipEntry {
+    pub(crate) fn is_folder(&self) -> bool {
+        self.name.ends_with('/')
+    }
+}
+
+/// Extracts every entry of archive into folder.
+///
+/// Fails with [`LimitExceeded`] error before total length of extracted data exceeds
+/// maximum, whatever sizes entries declare.
+pub(crate) fn extract(
+    zip_path: &Path,
+    folder_path: &Path,
+    max_archive_length: Option<u64>,
+) -> Result<(), io::Error> {
+    let mut file = File::open(zip_path)?;
+    let mut remaining_length = max_archive_length.unwrap_or(u64::MAX);
+
+    for entry in entries(&mut file)? {
+        let path = entry_path(folder_path, &entry.name)?;
+
+        if entry.is_folder() {
+            fs::create_dir_all(&path)?;
+            continue;
+        }
+
+        let data = read_entry(&mut file, &entry, remaining_length)?;
+
+        if data.len() as u64 > remaining_length {
+            let limit_exceeded = LimitExceeded::ArchiveLength {
+                max_archive_length: max_archive_length.unwrap_or(u64::MAX),
+            };
+
+            return Err(io::Error::new(io::ErrorKind::InvalidData, limit_exceeded));
+        }
+
+        remaining_length -= data.len() as u64;
+
+        if let Some(parent_path) = path.parent() {
+            fs::create_dir_all(parent_path)?;
+        }
+
+        fs::write(path, data)?;
+    }
+
+    Ok(())
+}
+
+/// Reads central directory of archive.
+pub(crate) fn entries(reader: &mut (impl Read + Seek)) -> Result<Vec<ZipEntry>, io::Error> {
+    let (entry_count, central_directory_offset) = find_central_directory(reader)?;
+    reader.seek(SeekFrom::Start(central_directory_offset))?;
+
+    let mut central_directory = Vec::new();
+    reader.read_to_end(&mut central_directory)?;
+    let mut cursor = Cursor::new(central_directory);
+    let mut entries = Vec::with_capacity(entry_count as usize);
+
+    for _ in 0..entry_count {
+        if cursor.read_u32::<LittleEndian>()? != CENTRAL_DIRECTORY_SIGNATURE {
+            return Err(invalid_data("Invalid central directory file header"));
+        }
+
+        cursor.seek(SeekFrom::Current(6))?;
+        let method = cursor.read_u16::<LittleEndian>()?;
+        cursor.seek(SeekFrom::Current(8))?;
+        let compressed_size = cursor.read_u32::<LittleEndian>()?;
+        let uncompressed_size = cursor.read_u32::<LittleEndian>()?;
+        let name_length = cursor.read_u16::<LittleEndian>()?;
+        let extra_length = cursor.read_u16::<LittleEndian>()?;
+        let comment_length = cursor.read_u16::<LittleEndian>()?;
+        cursor.seek(SeekFrom::Current(8))?;
+        let local_header_offset = cursor.read_u32::<LittleEndian>()?;
+
+        let mut name = vec![0; name_length as usize];
+        cursor.read_exact(&mut name)?;
+        cursor.seek(SeekFrom::Current(
+            extra_length as i64 + comment_length as i64,
+        ))?;
+
+        entries.push(ZipEntry {
+            name: String::from_utf8_lossy(&name).replace('\\', "/"),
+            method,
+            compressed_size,
+            uncompressed_size,
+            local_header_offset,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Reads and decompresses data of entry, stops after the first byte past maximum length.
+pub(crate) fn read_entry(
+    reader: &mut (impl Read + Seek),
+    entry: &ZipEntry,
+    max_length: u64,
+) -> Result<Vec<u8>, io::Error> {
+    let mut compressed = read_compressed_entry(reader, entry)?;
+    let limit = max_length.saturating_add(1);
+
+    match entry.method {
+        STORED_METHOD => {
+            compressed.truncate(limit.min(usize::MAX as u64) as usize);
+
+            Ok(compressed)
+        }
+        DEFLATED_METHOD => {
+            let mut data = Vec::with_capacity((entry.uncompressed_size as u64).min(limit) as usize);
+            DeflateDecoder::new(compressed.as_slice())
+                .take(limit)
+                .read_to_end(&mut data)?;
+
+            Ok(data)
+        }
+        _ => Err(unsupported_method()),
+    }
+}
+
+/// Decompresses data of entry as it is stored in archive.
+pub(crate) fn inflate_entry(entry: &ZipEntry, compressed: &[u8]) -> Result<Vec<u8>, io::Error> {
+    match entry.method {
+        STORED_METHOD => Ok(compressed.to_vec()),
+        DEFLATED_METHOD => {
+            let mut data = Vec::with_capacity(entry.uncompressed_size as usize);
+            DeflateDecoder::new(compressed).read_to_end(&mut data)?;
+
+            Ok(data)
+        }
+        _ => Err(unsupported_method()),
+    }
+}
+
+/// Reads data of entry as it is stored in archive.
+pub(crate) fn read_compressed_entry(
+    reader: &mut (impl Read + Seek),
+    entry: &ZipEntry,
+) -> Result<Vec<u8>, io::Error> {
+    reader.seek(SeekFrom::Start(entry.local_header_offset as u64))?;
+
+    if reader.read_u32::<LittleEndian>()? != LOCAL_FILE_HEADER_SIGNATURE {
+        return Err(invalid_data("Invalid local file header"));
+    }
+
+    reader.seek(SeekFrom::Current(22))?;
+    let local_name_length = reader.read_u16::<LittleEndian>()?;
+    let local_extra_length = reader.read_u16::<LittleEndian>()?;
+    reader.seek(SeekFrom::Current(
+        local_name_length as i64 + local_extra_length as i64,
+    ))?;
+
+    let mut compressed = vec![0; entry.compressed_size as usize];
+    reader.read_exact(&mut compressed)?;
+
+    Ok(compressed)
+}
+
+/// Decompresses range of entry data from data as it is stored in archive.
+///
+/// Deflate stream can't be entered in the middle, so data before range is decompressed
+/// and discarded, data after range isn't decompressed. Range is cut short if entry ends
+/// before its end.
+pub(crate) fn read_entry_range(
+    entry: &ZipEntry,
+    compressed: &[u8],
+    offset: usize,
+    length: usize,
+) -> Result<Vec<u8>, io::Error> {
+    match entry.method {
+        STORED_METHOD => {
+            let data = compressed.get(offset..).unwrap_or_default();
+
+            Ok(data[..length.min(data.len())].to_vec())
+        }
+        DEFLATED_METHOD => {
+            let mut decoder = DeflateDecoder::new(compressed);
+            io::copy(&mut (&mut decoder).take(offset as u64), &mut io::sink())?;
+
+            let mut data = Vec::with_capacity(length);
+            decoder.take(length as u64).read_to_end(&mut data)?;
+
+            Ok(data)
+        }
+        _ => Err(unsupported_method()),
+    }
+}
+
+/// Returns amount of entries and offset of central directory.
+fn find_central_directory(reader: &mut (impl Read + Seek)) -> Result<(u16, u64), io::Error> {
+    let file_length = reader.seek(SeekFrom::End(0))?;
+
+    if file_length < END_OF_CENTRAL_DIRECTORY_LENGTH {
+        return Err(invalid_data("File is too short for zip archive"));
+    }
+
+    let search_length = file_length.min(END_OF_CENTRAL_DIRECTORY_LENGTH + MAXIMUM_COMMENT_LENGTH);
+    reader.seek(SeekFrom::Start(file_length - search_length))?;
+
+    let mut tail = vec![0; search_length as usize];
+    reader.read_exact(&mut tail)?;
+
+    // Record is searched from the end since comment may contain signature bytes.
+    let record_position = (0..=tail.len() - END_OF_CENTRAL_DIRECTORY_LENGTH as usize)
+        .rev()
+        .find(|position| {
+            tail[*position..*position + 4] == END_OF_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes()
+        })
+        .ok_or_else(|| invalid_data("End of central directory not found"))?;
+
+    let mut cursor = Cursor::new(&tail[record_position + 10..]);
+    let entries = cursor.read_u16::<LittleEndian>()?;
+    cursor.seek(SeekFrom::Current(4))?;
+    let central_directory_offset = cursor.read_u32::<LittleEndian>()?;
+
+    Ok((entries, central_directory_offset as u64))
+}
+
+/// Returns path of entry inside of folder, rejecting names which escape it.
+fn entry_path(folder_path: &Path, name: &str) -> Result<PathBuf, io::Error> {
+    let mut path = folder_path.to_path_buf();
+
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(component) => path.push(component),
+            Component::CurDir => {}
+            _ => return Err(invalid_data("Entry name escapes extraction folder")),
+        }
+    }
+
+    Ok(path)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn unsupported_method() -> io::Error {
+    invalid_data("Unsupported compression method")
+}
+
+/// Writes archive with entries compressed by given method.
+#[cfg(test)]
+pub(crate) fn write_zip(zip_path: &Path, entries: &[(&str, &[u8])], method: u16) {
+    use byteorder::WriteBytesExt;
+    use flate2::write::DeflateEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    let mut archive = Vec::new();
+    let mut central_directory = Vec::new();
+
+    for (name, data) in entries {
+        let compressed = if method == DEFLATED_METHOD {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(data).unwrap();
+            encoder.finish().unwrap()
+        } else {
+            data.to_vec()
+        };
+
+        let local_header_offset = archive.len() as u32;
+
+        archive
+            .write_u32::<LittleEndian>(LOCAL_FILE_HEADER_SIGNATURE)
+            .unwrap();
+        archive.write_all(&[20, 0, 0, 0]).unwrap();
+        archive.write_u16::<LittleEndian>(method).unwrap();
+        archive.write_all(&[0; 8]).unwrap();
+        archive
+            .write_u32::<LittleEndian>(compressed.len() as u32)
+            .unwrap();
+        archive
+            .write_u32::<LittleEndian>(data.len() as u32)
+            .unwrap();
+        archive
+            .write_u16::<LittleEndian>(name.len() as u16)
+            .unwrap();
+        archive.write_u16::<LittleEndian>(0).unwrap();
+        archive.write_all(name.as_bytes()).unwrap();
+        archive.write_all(&compressed).unwrap();
+
+        central_directory
+            .write_u32::<LittleEndian>(CENTRAL_DIRECTORY_SIGNATURE)
+            .unwrap();
+        central_directory.write_all(&[20, 0, 20, 0, 0, 0]).unwrap();
+        central_directory.write_u16::<LittleEndian>(method).unwrap();
+        central_directory.write_all(&[0; 8]).unwrap();
+        central_directory
+            .write_u32::<LittleEndian>(compressed.len() as u32)
+            .unwrap();
+        central_directory
+            .write_u32::<LittleEndian>(data.len() as u32)
+            .unwrap();
+        central_directory
+            .write_u16::<LittleEndian>(name.len() as u16)
+            .unwrap();
+        central_directory.write_all(&[0; 12]).unwrap();
+        central_directory
+            .write_u32::<LittleEndian>(local_header_offset)
+            .unwrap();
+        central_directory.write_all(name.as_bytes()).unwrap();
+    }
+
+    let central_directory_offset = archive.len() as u32;
+    archive.write_all(&central_directory).unwrap();
+    archive
+        .write_u32::<LittleEndian>(END_OF_CENTRAL_DIRECTORY_SIGNATURE)
+        .unwrap();
+    archive.write_all(&[0; 4]).unwrap();
+    archive
+        .write_u16::<LittleEndian>(entries.len() as u16)
+        .unwrap();
+    archive
+        .write_u16::<LittleEndian>(entries.len() as u16)
+        .unwrap();
+    archive
+        .write_u32::<LittleEndian>(central_directory.len() as u32)
+        .unwrap();
+    archive
+        .write_u32::<LittleEndian>(central_directory_offset)
+        .unwrap();
+    archive.write_u16::<LittleEndian>(0).unwrap();
+
+    fs::write(zip_path, archive).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::zip::{
+        entries, extract, read_compressed_entry, read_entry_range, write_zip, DEFLATED_METHOD,
+        STORED_METHOD,
+    };
+    use std::fs;
+    use std::fs::File;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_extract() {
+        let temp_dir = TempDir::new().unwrap();
+        let region = fs::read("test/region/r.0.0.mca").unwrap();
+
+        for method in [STORED_METHOD, DEFLATED_METHOD] {
+            let zip_path = temp_dir.path().join(format!("world_{}.zip", method));
+            let folder_path = temp_dir.path().join(format!("world_{}", method));
+
+            write_zip(
+                &zip_path,
+                &[
+                    ("World/", b""),
+                    ("World/level.dat", b"level"),
+                    ("World/region/r.0.0.mca", &region),
+                ],
+                method,
+            );
+
+            extract(&zip_path, &folder_path, None).unwrap();
+
+            assert_eq!(
+                fs::read(folder_path.join("World/level.dat")).unwrap(),
+                b"level"
+            );
+            assert_eq!(
+                fs::read(folder_path.join("World/region/r.0.0.mca")).unwrap(),
+                region
+            );
+        }
+    }
+
+    #[test]
+    fn test_extract_rejects_escaping_names() {
+        let temp_dir = TempDir::new().unwrap();
+        let zip_path = temp_dir.path().join("world.zip");
+
+        write_zip(&zip_path, &[("../level.dat", b"level")], STORED_METHOD);
+
+        assert!(extract(&zip_path, &temp_dir.path().join("world"), None).is_err());
+        assert!(!temp_dir.path().join("level.dat").exists());
+    }
+
+    #[test]
+    fn test_extract_invalid_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let zip_path = temp_dir.path().join("world.zip");
+        fs::write(&zip_path, vec![0; 100]).unwrap();
+
+        assert!(extract(&zip_path, temp_dir.path(), None).is_err());
+    }
+
+    #[test]
+    fn test_read_entry_range() {
+        let temp_dir = TempDir::new().unwrap();
+        let region = fs::read("test/region/r.0.0.mca").unwrap();
+
+        for method in [STORED_METHOD, DEFLATED_METHOD] {
+            let zip_path = temp_dir.path().join(format!("world_{}.zip", method));
+            write_zip(&zip_path, &[("r.0.0.mca", &region)], method);
+
+            let mut file = File::open(&zip_path).unwrap();
+            let entry = entries(&mut file).unwrap().remove(0);
+            let compressed = read_compressed_entry(&mut file, &entry).unwrap();
+
+            let data = read_entry_range(&entry, &compressed, 8192, 4096).unwrap();
+            assert_eq!(data, &region[8192..8192 + 4096]);
+
+            // Range past the end of entry is cut short.
+            let offset = region.len() - 10;
+            let data = read_entry_range(&entry, &compressed, offset, 4096).unwrap();
+            assert_eq!(data, &region[offset..]);
+
+            let data = read_entry_range(&entry, &compressed, region.len() + 1, 10).unwrap();
+            assert!(data.is_empty());
+        }
+    }
+}
@@ -0,0 +1,464 @@
+//! Zstd compression of chunks with dictionary trained on chunks of world.
+//!
+//! Region format reserves compression type 127 for custom compression: the type is
+//! followed by namespaced id of algorithm as string of modified UTF-8 with 2 bytes of
+//! length, and then by compressed data. Chunks compressed here use id
+//! [`ZSTD_ALGORITHM`] and single zstd frame, which refers to dictionary by its id.
+//!
+//! Most chunks are a few kilobytes of the same tag names and palette entries, which
+//! zlib has to learn again for every chunk. [`ZstdDictionary::train`] builds dictionary
+//! from a sample of chunks of world, so small chunks compress much better. It's
+//! saved alongside region files with [`ZstdDictionary::save_alongside`] and chunks
+//! can't be read without it.
+//!
+//! Provider with [`AnvilChunkProvider::zstd_dictionary`] compresses every saved chunk
+//! with the dictionary. Reading only needs dictionary to be registered with
+//! [`register`], then every provider and reader of crate decodes chunks compressed
+//! with it. Game and other tools can't read such chunks.
+//!
+//! # Example
+//!
+//! ```
+//! use anvil_region::relocate::copy_chunk;
+//! use anvil_region::zstd_dictionary::{TrainOptions, ZstdDictionary};
+//! use anvil_region::AnvilChunkProvider;
+//! use std::sync::Arc;
+//! use tempfile::TempDir;
+//!
+//! let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+//! let options = TrainOptions::new().dictionary_length(4096);
+//! let dictionary = ZstdDictionary::train(&fixture_chunk_provider, &options).unwrap();
+//!
+//! let region_dir = TempDir::new().unwrap();
+//! let chunk_provider = AnvilChunkProvider::new(region_dir.path().to_str().unwrap())
+//!     .zstd_dictionary(Arc::new(dictionary));
+//!
+//! copy_chunk(&fixture_chunk_provider, (4, 2), &chunk_provider, (4, 2)).unwrap();
+//! assert!(chunk_provider.load_chunk(4, 2).is_ok());
+//! ```
+use crate::{AnvilChunkProvider, ChunkLoadError, CUSTOM_COMPRESSION_TYPE};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use nbt::decode::TagDecodeError;
+use nbt::encode::write_compound_tag;
+use nbt::CompoundTag;
+use std::fmt::{self, Debug, Formatter};
+use std::io::{Read, Write};
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::{fs, io};
+use zstd::dict::{DecoderDictionary, EncoderDictionary};
+use zstd::stream::read::Decoder;
+use zstd::stream::write::Encoder;
+use zstd::zstd_safe;
+
+/// Namespaced id of custom compression written before zstd frame.
+pub const ZSTD_ALGORITHM: &str = "anvil_region:zstd";
+/// Name of dictionary file saved alongside region files.
+pub const DICTIONARY_FILE_NAME: &str = "chunks.zstd_dict";
+/// Zstd compression level of saved chunks, default level of zstd.
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// Dictionaries which decode chunks, looked up by id stored in zstd frame.
+static DICTIONARIES: Mutex<Vec<Arc<ZstdDictionary>>> = Mutex::new(Vec::new());
+
+/// Possible errors while training dictionary.
+#[derive(Debug)]
+pub enum TrainError {
+    /// World has no chunks to train dictionary on.
+    NoChunks,
+    /// Sampled chunk can't be loaded.
+    LoadError {
+        chunk_x: i32,
+        chunk_z: i32,
+        chunk_load_error: ChunkLoadError,
+    },
+    /// Region folder can't be listed or zstd failed to train dictionary, like when
+    /// samples are too small for requested dictionary length.
+    IoError { io_error: io::Error },
+}
+
+impl From<io::Error> for TrainError {
+    fn from(io_error: io::Error) -> Self {
+        TrainError::IoError { io_error }
+    }
+}
+
+/// Options of dictionary training.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TrainOptions {
+    /// Maximum amount of chunks sampled evenly from all chunks of world.
+    pub max_samples: usize,
+    /// Maximum length of dictionary in bytes.
+    pub dictionary_length: usize,
+}
+
+impl TrainOptions {
+    pub fn new() -> Self {
+        TrainOptions::default()
+    }
+
+    /// Sets maximum amount of sampled chunks, 2048 by default.
+    pub fn max_samples(mut self, max_samples: usize) -> Self {
+        self.max_samples = max_samples;
+        self
+    }
+
+    /// Sets maximum length of dictionary, 110 KiB by default as in zstd.
+    pub fn dictionary_length(mut self, dictionary_length: usize) -> Self {
+        self.dictionary_length = dictionary_length;
+        self
+    }
+}
+
+impl Default for TrainOptions {
+    fn default() -> Self {
+        TrainOptions {
+            max_samples: 2048,
+            dictionary_length: 110 * 1024,
+        }
+    }
+}
+
+/// Zstd dictionary prepared for compression and decompression of chunks.
+pub struct ZstdDictionary {
+    /// Id of dictionary stored in zstd frames compressed with it.
+    id: u32,
+    data: Vec<u8>,
+    encoder_dictionary: EncoderDictionary<'static>,
+    decoder_dictionary: DecoderDictionary<'static>,
+}
+
+impl ZstdDictionary {
+    /// Creates dictionary from contents of dictionary file trained by zstd.
+    ///
+    /// Raw content without zstd dictionary header isn't accepted, chunks compressed
+    /// with it couldn't tell which dictionary to decode them with.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, io::Error> {
+        let id = zstd_safe::get_dict_id_from_dict(&data)
+            .map(NonZeroU32::get)
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "File is not zstd dictionary")
+            })?;
+
+        Ok(ZstdDictionary {
+            id,
+            encoder_dictionary: EncoderDictionary::try_copy(&data, COMPRESSION_LEVEL)?,
+            decoder_dictionary: DecoderDictionary::try_copy(&data)?,
+            data,
+        })
+    }
+
+    /// Trains dictionary on uncompressed NBT of chunks sampled evenly over all chunks
+    /// of provider.
+    pub fn train(
+        chunk_provider: &AnvilChunkProvider,
+        options: &TrainOptions,
+    ) -> Result<Self, TrainError> {
+        let chunk_positions = chunk_provider.chunk_positions()?;
+
+        if chunk_positions.is_empty() || options.max_samples == 0 {
+            return Err(TrainError::NoChunks);
+        }
+
+        let step = chunk_positions.len().div_ceil(options.max_samples);
+        let mut samples = Vec::new();
+
+        for (chunk_x, chunk_z) in chunk_positions.into_iter().step_by(step) {
+            let chunk_compound_tag =
+                chunk_provider
+                    .load_chunk(chunk_x, chunk_z)
+                    .map_err(|chunk_load_error| TrainError::LoadError {
+                        chunk_x,
+                        chunk_z,
+                        chunk_load_error,
+                    })?;
+
+            let mut sample = Vec::new();
+            write_compound_tag(&mut sample, &chunk_compound_tag)?;
+            samples.push(sample);
+        }
+
+        let data = zstd::dict::from_samples(&samples, options.dictionary_length)?;
+
+        Ok(ZstdDictionary::from_bytes(data)?)
+    }
+
+    /// Returns id which zstd frames compressed with dictionary refer to.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Returns contents of dictionary file.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Saves dictionary to file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), io::Error> {
+        fs::write(path, &self.data)
+    }
+
+    /// Loads dictionary which was saved with [`save`].
+    ///
+    /// [`save`]: ZstdDictionary::save
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, io::Error> {
+        ZstdDictionary::from_bytes(fs::read(path)?)
+    }
+
+    /// Saves dictionary into [`DICTIONARY_FILE_NAME`] file of region folder of provider.
+    pub fn save_alongside(&self, chunk_provider: &AnvilChunkProvider) -> Result<(), io::Error> {
+        chunk_provider.create_folder()?;
+
+        self.save(dictionary_path(chunk_provider))
+    }
+
+    /// Loads dictionary saved alongside region files of provider.
+    pub fn load_alongside(chunk_provider: &AnvilChunkProvider) -> Result<Self, io::Error> {
+        ZstdDictionary::load(dictionary_path(chunk_provider))
+    }
+}
+
+impl Debug for ZstdDictionary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ZstdDictionary")
+            .field("id", &self.id)
+            .field("length", &self.data.len())
+            .finish()
+    }
+}
+
+/// Makes chunks compressed with dictionary readable by every provider and reader of
+/// process. Dictionary with the same id registered before is replaced.
+pub fn register(dictionary: Arc<ZstdDictionary>) {
+    let mut dictionaries = DICTIONARIES
+        .lock()
+        .unwrap_or_else(|error| error.into_inner());
+
+    dictionaries.retain(|registered| registered.id != dictionary.id);
+    dictionaries.push(dictionary);
+}
+
+/// Returns registered dictionary with the specified id.
+fn registered(id: u32) -> Option<Arc<ZstdDictionary>> {
+    DICTIONARIES
+        .lock()
+        .unwrap_or_else(|error| error.into_inner())
+        .iter()
+        .find(|dictionary| dictionary.id == id)
+        .cloned()
+}
+
+fn dictionary_path(chunk_provider: &AnvilChunkProvider) -> PathBuf {
+    chunk_provider.folder_path.join(DICTIONARY_FILE_NAME)
+}
+
+/// Compresses chunk with dictionary into buffer which starts with compression type.
+pub(crate) fn encode_chunk(
+    dictionary: &ZstdDictionary,
+    chunk_compound_tag: &CompoundTag,
+) -> Result<Vec<u8>, io::Error> {
+    let mut buffer = Vec::new();
+
+    buffer.write_u8(CUSTOM_COMPRESSION_TYPE)?;
+    buffer.write_u16::<BigEndian>(ZSTD_ALGORITHM.len() as u16)?;
+    buffer.write_all(ZSTD_ALGORITHM.as_bytes())?;
+
+    let mut encoder = Encoder::with_prepared_dictionary(buffer, &dictionary.encoder_dictionary)?;
+    write_compound_tag(&mut encoder, chunk_compound_tag)?;
+
+    encoder.finish()
+}
+
+/// Decompresses chunk data of custom compression type replacing buffer contents.
+///
+/// Returns amount of bytes which follow the end of zstd frame.
+pub(crate) fn decompress(
+    compressed_buffer: &[u8],
+    decompressed: &mut Vec<u8>,
+    max_length: usize,
+) -> Result<usize, ChunkLoadError> {
+    let mut input = compressed_buffer;
+    let algorithm_length = input
+        .read_u16::<BigEndian>()
+        .map_err(TagDecodeError::from)? as usize;
+
+    if input.get(..algorithm_length) != Some(ZSTD_ALGORITHM.as_bytes()) {
+        return Err(ChunkLoadError::UnsupportedCompressionScheme {
+            compression_scheme: CUSTOM_COMPRESSION_TYPE,
+        });
+    }
+
+    let frame = &input[algorithm_length..];
+    let frame_length = zstd_safe::find_frame_compressed_size(frame).map_err(|_| {
+        TagDecodeError::from(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Corrupted zstd frame",
+        ))
+    })?;
+
+    let decoder = match zstd_safe::get_dict_id_from_frame(frame) {
+        Some(id) => {
+            let dictionary = registered(id.get()).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Zstd dictionary {} isn't registered", id),
+                )
+            })?;
+
+            Decoder::with_prepared_dictionary(
+                &frame[..frame_length],
+                &dictionary.decoder_dictionary,
+            )
+            .map(|decoder| read_frame(decoder, decompressed, max_length))
+        }
+        None => Decoder::with_buffer(&frame[..frame_length])
+            .map(|decoder| read_frame(decoder, decompressed, max_length)),
+    };
+
+    decoder
+        .and_then(|result| result)
+        .map_err(TagDecodeError::from)?;
+
+    Ok(frame.len() - frame_length)
+}
+
+/// Reads frame until its end or until buffer is longer than maximum length.
+fn read_frame(
+    decoder: Decoder<'_, &[u8]>,
+    decompressed: &mut Vec<u8>,
+    max_length: usize,
+) -> Result<(), io::Error> {
+    // Byte over maximum tells that data is longer than maximum.
+    decoder
+        .single_frame()
+        .take(max_length.saturating_add(1) as u64)
+        .read_to_end(decompressed)
+        .map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::buffer::decode_chunk;
+    use crate::diff::compound_tags_equal;
+    use crate::relocate::copy_chunk;
+    use crate::zstd_dictionary::{
+        encode_chunk, register, TrainError, TrainOptions, ZstdDictionary, DICTIONARY_FILE_NAME,
+    };
+    use crate::{AnvilChunkProvider, ChunkLoadError, CUSTOM_COMPRESSION_TYPE};
+    use nbt::CompoundTag;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    fn train_fixture() -> ZstdDictionary {
+        let chunk_provider = AnvilChunkProvider::new("test/region");
+        let options = TrainOptions::new().dictionary_length(4096);
+
+        ZstdDictionary::train(&chunk_provider, &options).unwrap()
+    }
+
+    #[test]
+    fn test_encode_decode_chunk() {
+        let dictionary = Arc::new(train_fixture());
+        register(dictionary.clone());
+
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk_compound_tag = fixture_chunk_provider.load_chunk(4, 2).unwrap();
+
+        let buffer = encode_chunk(&dictionary, &chunk_compound_tag).unwrap();
+        assert_eq!(buffer[0], CUSTOM_COMPRESSION_TYPE);
+
+        let decoded_compound_tag = decode_chunk(buffer[0], &buffer[1..]).unwrap();
+        assert!(compound_tags_equal(
+            &decoded_compound_tag,
+            &chunk_compound_tag
+        ));
+    }
+
+    #[test]
+    fn test_unknown_algorithm() {
+        let mut buffer = vec![0, 9];
+        buffer.extend_from_slice(b"other:lz4");
+        buffer.extend_from_slice(&[1, 2, 3]);
+
+        match decode_chunk(CUSTOM_COMPRESSION_TYPE, &buffer) {
+            Err(ChunkLoadError::UnsupportedCompressionScheme { compression_scheme }) => {
+                assert_eq!(compression_scheme, CUSTOM_COMPRESSION_TYPE)
+            }
+            result => panic!(
+                "Expected `UnsupportedCompressionScheme` but got `{:?}`",
+                result
+            ),
+        }
+    }
+
+    #[test]
+    fn test_unregistered_dictionary() {
+        // Dictionary trained on other data gets other id and isn't registered.
+        let samples: Vec<_> = (0..64u8)
+            .map(|index| vec![index; 64 + index as usize])
+            .collect();
+        let data = zstd::dict::from_samples(&samples, 1024).unwrap();
+        let dictionary = ZstdDictionary::from_bytes(data).unwrap();
+
+        let mut chunk_compound_tag = CompoundTag::new();
+        chunk_compound_tag.insert_i32("xPos", 4);
+        let buffer = encode_chunk(&dictionary, &chunk_compound_tag).unwrap();
+
+        match decode_chunk(buffer[0], &buffer[1..]) {
+            Err(ChunkLoadError::ReadError { io_error }) => {
+                assert_eq!(io_error.kind(), std::io::ErrorKind::NotFound)
+            }
+            result => panic!("Expected `ReadError` but got `{:?}`", result),
+        }
+    }
+
+    #[test]
+    fn test_provider_saves_with_dictionary() {
+        let region_dir = TempDir::new().unwrap();
+        let folder = region_dir.path().to_str().unwrap();
+
+        let dictionary = Arc::new(train_fixture());
+        let fixture_chunk_provider = AnvilChunkProvider::new("test/region");
+        let chunk_provider = AnvilChunkProvider::new(folder).zstd_dictionary(dictionary.clone());
+
+        dictionary.save_alongside(&chunk_provider).unwrap();
+        assert!(region_dir.path().join(DICTIONARY_FILE_NAME).exists());
+
+        for (chunk_x, chunk_z) in fixture_chunk_provider.chunk_positions().unwrap() {
+            copy_chunk(
+                &fixture_chunk_provider,
+                (chunk_x, chunk_z),
+                &chunk_provider,
+                (chunk_x, chunk_z),
+            )
+            .unwrap();
+        }
+
+        let loaded_dictionary = ZstdDictionary::load_alongside(&chunk_provider).unwrap();
+        assert_eq!(loaded_dictionary.id(), dictionary.id());
+        assert_eq!(loaded_dictionary.as_bytes(), dictionary.as_bytes());
+
+        let reopened_chunk_provider = AnvilChunkProvider::new(folder);
+
+        for (chunk_x, chunk_z) in fixture_chunk_provider.chunk_positions().unwrap() {
+            assert!(compound_tags_equal(
+                &reopened_chunk_provider
+                    .load_chunk(chunk_x, chunk_z)
+                    .unwrap(),
+                &fixture_chunk_provider.load_chunk(chunk_x, chunk_z).unwrap()
+            ));
+        }
+    }
+
+    #[test]
+    fn test_train_empty_world() {
+        let region_dir = TempDir::new().unwrap();
+        let chunk_provider = AnvilChunkProvider::new(region_dir.path().to_str().unwrap());
+
+        match ZstdDictionary::train(&chunk_provider, &TrainOptions::new()) {
+            Err(TrainError::NoChunks) => {}
+            result => panic!("Expected `NoChunks` but got `{:?}`", result),
+        }
+    }
+}